@@ -3,6 +3,6 @@ fn main() {
     tonic_build::configure()
         .out_dir("src/protobuf")
         .format(true)
-        .compile(&["proto/codec.proto"], &["proto"])
+        .compile(&["proto/codec.proto", "proto/transforms.proto"], &["proto"])
         .expect("Failed to compile StreamingFast Ethereum proto(s)");
 }
@@ -1,13 +1,16 @@
 use anyhow::Error;
 use graph::{
     log::logger,
-    prelude::{prost, tokio, tonic},
-    {firehose, firehose::FirehoseEndpoint, firehose::ForkStep},
+    prelude::{futures03::StreamExt, prost, tokio},
+    {
+        firehose,
+        firehose::ForkStep,
+        firehose::{FirehoseConnectionSettings, FirehoseEndpoint, FirehoseStream},
+    },
 };
 use graph_chain_ethereum::codec;
 use prost::Message;
 use std::sync::Arc;
-use tonic::Streaming;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
@@ -15,12 +18,19 @@ async fn main() -> Result<(), Error> {
 
     let logger = logger(true);
     let firehose = Arc::new(
-        FirehoseEndpoint::new(logger, "firehose", "https://bsc.streamingfast.io:443", None).await?,
+        FirehoseEndpoint::new(
+            logger,
+            "firehose",
+            "https://bsc.streamingfast.io:443",
+            None,
+            FirehoseConnectionSettings::default(),
+        )
+        .await?,
     );
 
     loop {
         println!("connecting to the stream!");
-        let mut stream: Streaming<firehose::Response> = match firehose
+        let mut stream: FirehoseStream = match firehose
             .clone()
             .stream_blocks(firehose::Request {
                 start_block_num: 7000000,
@@ -41,13 +51,13 @@ async fn main() -> Result<(), Error> {
         };
 
         loop {
-            let resp = match stream.message().await {
-                Ok(Some(t)) => t,
-                Ok(None) => {
+            let resp = match stream.next().await {
+                Some(Ok(t)) => t,
+                None => {
                     println!("stream completed");
                     break;
                 }
-                Err(e) => {
+                Some(Err(e)) => {
                     println!("error getting message {}", e);
                     break;
                 }
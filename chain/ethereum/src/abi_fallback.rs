@@ -0,0 +1,158 @@
+//! Fetch the ABIs of verified contracts from Sourcify for dynamic data
+//! sources whose template does not bundle the ABI it refers to.
+//!
+//! This is only enabled when `GRAPH_ETHEREUM_ABI_FALLBACK_URL` is set.
+//! Since the ABI comes from an external service, indexing a subgraph that
+//! relies on it is only as deterministic as the ABIs that service returns.
+//! To limit that, fetched ABIs are cached in the database by contract
+//! address and the hash of the contract's code, and the cache is always
+//! consulted first.
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use graph::prelude::{
+    anyhow::{anyhow, Context, Error},
+    ethabi::{Address, Contract},
+    info, lazy_static, reqwest, serde_json, tokio, ChainStore, Logger,
+};
+
+use crate::{EthereumAdapter, MappingABI};
+
+lazy_static! {
+    /// The base URL of the Sourcify server API, for example
+    /// `https://sourcify.dev/server`. Fetching ABIs is disabled if this is
+    /// not set.
+    static ref ABI_FALLBACK_URL: Option<String> = std::env::var("GRAPH_ETHEREUM_ABI_FALLBACK_URL")
+        .ok()
+        .map(|url| url.trim_end_matches('/').to_string());
+
+    /// The fallbacks for each network that has one, keyed by network name
+    static ref FALLBACKS: RwLock<HashMap<String, Arc<AbiFallback>>> = RwLock::new(HashMap::new());
+}
+
+/// How long to wait for a response from Sourcify
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct AbiFallback {
+    logger: Logger,
+    url: String,
+    adapter: Arc<EthereumAdapter>,
+    chain_store: Arc<dyn ChainStore>,
+    client: reqwest::Client,
+}
+
+impl AbiFallback {
+    /// Make ABIs for the network `network` available through `for_network`
+    /// if fetching ABIs is enabled. The `adapter` is used to look up the
+    /// chain id and contract code
+    pub(crate) fn register(
+        logger: &Logger,
+        network: &str,
+        adapter: Arc<EthereumAdapter>,
+        chain_store: Arc<dyn ChainStore>,
+    ) {
+        let url = match ABI_FALLBACK_URL.as_ref() {
+            Some(url) => url.clone(),
+            None => return,
+        };
+        info!(logger, "Fetching missing ABIs for dynamic data sources is enabled";
+              "network" => network, "url" => &url);
+
+        let fallback = AbiFallback {
+            logger: logger.clone(),
+            url,
+            adapter,
+            chain_store,
+            client: reqwest::Client::new(),
+        };
+        FALLBACKS
+            .write()
+            .unwrap()
+            .insert(network.to_string(), Arc::new(fallback));
+    }
+
+    /// Return the fallback for `network` if fetching ABIs is enabled for it
+    pub(crate) fn for_network(network: &str) -> Option<Arc<AbiFallback>> {
+        FALLBACKS.read().unwrap().get(network).cloned()
+    }
+
+    /// Resolve the ABI for the contract at `address` and name it `name`.
+    /// This blocks the current thread until the ABI has been fetched, and
+    /// must be called from a thread that runs inside the Tokio runtime.
+    pub(crate) fn resolve_blocking(
+        self: Arc<Self>,
+        name: &str,
+        address: Address,
+    ) -> Result<Arc<MappingABI>, Error> {
+        // We might be on a thread that is driving an async task, which
+        // makes it impossible to block on another future there
+        let handle = tokio::runtime::Handle::current();
+        let contract = std::thread::spawn(move || handle.block_on(self.resolve(address)))
+            .join()
+            .map_err(|_| anyhow!("fetching the ABI for contract {:?} panicked", address))??;
+        Ok(Arc::new(MappingABI {
+            name: name.to_string(),
+            contract,
+        }))
+    }
+
+    async fn resolve(&self, address: Address) -> Result<Contract, Error> {
+        let code_hash = self.adapter.code_hash(address).await?;
+
+        if let Some(abi) = self
+            .chain_store
+            .cached_abi(address.as_bytes(), code_hash.as_bytes())?
+        {
+            return Contract::load(abi.as_bytes())
+                .with_context(|| format!("cached ABI for contract {:?} is invalid", address));
+        }
+
+        let abi = self.fetch(address).await?;
+        let contract = Contract::load(abi.as_bytes())
+            .with_context(|| format!("Sourcify returned an invalid ABI for {:?}", address))?;
+        self.chain_store
+            .set_cached_abi(address.as_bytes(), code_hash.as_bytes(), &abi)?;
+        info!(self.logger, "Fetched ABI from Sourcify"; "address" => format!("{:?}", address));
+        Ok(contract)
+    }
+
+    /// Fetch the metadata for `address` from Sourcify and return the ABI
+    /// from it as a JSON string. Both full and partial matches are accepted
+    async fn fetch(&self, address: Address) -> Result<String, Error> {
+        let chain_id = self.adapter.chain_id().await?;
+        let url = format!("{}/files/any/{}/{:?}", self.url, chain_id, address);
+
+        let response = self
+            .client
+            .get(&url)
+            .timeout(FETCH_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("no verified contract {:?} on Sourcify", address))?;
+        let files: SourcifyFiles = response.json().await?;
+
+        let metadata = files
+            .files
+            .into_iter()
+            .find(|file| file.name == "metadata.json")
+            .ok_or_else(|| anyhow!("Sourcify has no metadata for contract {:?}", address))?;
+        let metadata: serde_json::Value = serde_json::from_str(&metadata.content)?;
+        let abi = metadata
+            .pointer("/output/abi")
+            .ok_or_else(|| anyhow!("the Sourcify metadata for {:?} has no ABI", address))?;
+        Ok(abi.to_string())
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct SourcifyFiles {
+    files: Vec<SourcifyFile>,
+}
+
+#[derive(serde::Deserialize)]
+struct SourcifyFile {
+    name: String,
+    content: String,
+}
@@ -5,8 +5,9 @@ use graph::blockchain::ChainIdentifier;
 use graph::env::env_var;
 use mockall::automock;
 use mockall::predicate::*;
+use prost::Message;
 use std::cmp;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt;
 use std::marker::Unpin;
 use thiserror::Error;
@@ -22,6 +23,7 @@ use graph::{
 
 use crate::capabilities::NodeCapabilities;
 use crate::data_source::BlockHandlerFilter;
+use crate::transform::{CallToFilter, CombinedFilter, LogFilter};
 use crate::{data_source::DataSource, Chain};
 
 pub type EventSignature = H256;
@@ -119,6 +121,10 @@ pub struct TriggerFilter {
     pub(crate) log: EthereumLogFilter,
     pub(crate) call: EthereumCallFilter,
     pub(crate) block: EthereumBlockFilter,
+    pub(crate) creation: EthereumCreationFilter,
+    /// Blocks for which mappings scheduled handlers with
+    /// `scheduler.atBlock`; scanning block ranges must include them
+    pub(crate) scheduled_blocks: BTreeSet<BlockNumber>,
 }
 
 impl TriggerFilter {
@@ -134,7 +140,13 @@ impl bc::TriggerFilter<Chain> for TriggerFilter {
         self.call
             .extend(EthereumCallFilter::from_data_sources(data_sources.clone()));
         self.block
-            .extend(EthereumBlockFilter::from_data_sources(data_sources));
+            .extend(EthereumBlockFilter::from_data_sources(data_sources.clone()));
+        self.creation
+            .extend(EthereumCreationFilter::from_data_sources(data_sources));
+    }
+
+    fn extend_with_scheduled_blocks(&mut self, blocks: impl Iterator<Item = BlockNumber>) {
+        self.scheduled_blocks.extend(blocks);
     }
 
     fn node_capabilities(&self) -> NodeCapabilities {
@@ -143,6 +155,68 @@ impl bc::TriggerFilter<Chain> for TriggerFilter {
             traces: self.requires_traces(),
         }
     }
+
+    fn to_firehose_filter(&self) -> Vec<prost_types::Any> {
+        // Contract creations can only be found by looking at all the
+        // transactions in a block, which needs unfiltered blocks
+        if !self.creation.is_empty() {
+            return vec![];
+        }
+
+        let log_filters: Vec<LogFilter> = self
+            .log
+            .clone()
+            .eth_get_logs_filters()
+            .map(|filter| LogFilter {
+                addresses: filter
+                    .contracts
+                    .iter()
+                    .map(|address| address.as_bytes().to_vec())
+                    .collect(),
+                event_signatures: filter
+                    .event_signatures
+                    .iter()
+                    .map(|sig| sig.as_bytes().to_vec())
+                    .collect(),
+            })
+            .collect();
+
+        // Block handlers with a call filter need all calls to the contract
+        let call_filters: Vec<CallToFilter> = self
+            .call
+            .contract_addresses_function_signatures
+            .iter()
+            .map(|(address, (_, sigs))| CallToFilter {
+                addresses: vec![address.as_bytes().to_vec()],
+                signatures: sigs.iter().map(|sig| sig.to_vec()).collect(),
+            })
+            .chain(
+                self.block
+                    .contract_addresses
+                    .iter()
+                    .map(|(_, address)| CallToFilter {
+                        addresses: vec![address.as_bytes().to_vec()],
+                        signatures: vec![],
+                    }),
+            )
+            .collect();
+
+        if log_filters.is_empty() && call_filters.is_empty() {
+            return vec![];
+        }
+
+        // We need to see every block to advance the subgraph pointer and to
+        // run block handlers, even if nothing in the block matches
+        let filter = CombinedFilter {
+            log_filters,
+            call_filters,
+            send_all_block_headers: true,
+        };
+        vec![prost_types::Any {
+            type_url: "type.googleapis.com/sf.ethereum.transform.v1.CombinedFilter".to_string(),
+            value: filter.encode_to_vec(),
+        }]
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -154,6 +228,10 @@ pub(crate) struct EthereumLogFilter {
 
     // Event sigs with no associated address, matching on all addresses.
     wildcard_events: HashSet<EventSignature>,
+
+    /// Whether some event handler asked for the receipt of the transaction
+    /// that emitted the event.
+    requires_transaction_receipt: bool,
 }
 
 impl EthereumLogFilter {
@@ -182,6 +260,8 @@ impl EthereumLogFilter {
     pub fn from_data_sources<'a>(iter: impl IntoIterator<Item = &'a DataSource>) -> Self {
         let mut this = EthereumLogFilter::default();
         for ds in iter {
+            this.requires_transaction_receipt |=
+                ds.mapping.event_handlers.iter().any(|e| e.receipt);
             for event_sig in ds.mapping.event_handlers.iter().map(|e| e.topic0()) {
                 match ds.source.address {
                     Some(contract) => {
@@ -206,11 +286,13 @@ impl EthereumLogFilter {
         let EthereumLogFilter {
             contracts_and_events_graph,
             wildcard_events,
+            requires_transaction_receipt,
         } = other;
         for (s, t, ()) in contracts_and_events_graph.all_edges() {
             self.contracts_and_events_graph.add_edge(s, t, ());
         }
         self.wildcard_events.extend(wildcard_events);
+        self.requires_transaction_receipt |= requires_transaction_receipt;
     }
 
     /// An empty filter is one that never matches.
@@ -219,10 +301,16 @@ impl EthereumLogFilter {
         let EthereumLogFilter {
             contracts_and_events_graph,
             wildcard_events,
+            requires_transaction_receipt: _,
         } = self;
         contracts_and_events_graph.edge_count() == 0 && wildcard_events.is_empty()
     }
 
+    /// Whether log triggers need to carry the receipt of their transaction.
+    pub fn requires_transaction_receipt(&self) -> bool {
+        self.requires_transaction_receipt
+    }
+
     /// Filters for `eth_getLogs` calls. The filters will not return false positives. This attempts
     /// to balance between having granular filters but too many calls and having few calls but too
     /// broad filters causing the Ethereum endpoint to timeout.
@@ -509,6 +597,58 @@ impl EthereumBlockFilter {
     }
 }
 
+/// Matches contract creation transactions by the account that sent them
+/// and the hash of their init code
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EthereumCreationFilter {
+    /// The start block, deployer and init code hash of each data source
+    /// with creation handlers. A `None` deployer or code hash matches any
+    /// deployer or code
+    pub creators: HashSet<(BlockNumber, Option<Address>, Option<H256>)>,
+}
+
+impl EthereumCreationFilter {
+    /// Whether a contract creation transaction in block `block_number`
+    /// sent by `from` that ran `init_code` matches the filter
+    pub fn matches(&self, block_number: BlockNumber, from: Address, init_code: &[u8]) -> bool {
+        let code_hash = H256::from(keccak256(init_code));
+        self.creators.iter().any(|(start_block, creator, hash)| {
+            *start_block <= block_number
+                && creator.map_or(true, |creator| creator == from)
+                && hash.map_or(true, |hash| hash == code_hash)
+        })
+    }
+
+    pub fn from_data_sources<'a>(iter: impl IntoIterator<Item = &'a DataSource>) -> Self {
+        let creators = iter
+            .into_iter()
+            .flat_map(|data_source| {
+                data_source
+                    .mapping
+                    .creation_handlers
+                    .iter()
+                    .map(move |handler| {
+                        (
+                            data_source.source.start_block,
+                            data_source.source.address,
+                            handler.code_hash,
+                        )
+                    })
+            })
+            .collect();
+        Self { creators }
+    }
+
+    pub fn extend(&mut self, other: EthereumCreationFilter) {
+        self.creators.extend(other.creators);
+    }
+
+    /// An empty filter is one that never matches.
+    pub fn is_empty(&self) -> bool {
+        self.creators.is_empty()
+    }
+}
+
 #[derive(Clone)]
 pub struct ProviderEthRpcMetrics {
     request_duration: Box<HistogramVec>,
@@ -686,14 +826,20 @@ pub trait EthereumAdapter: Send + Sync + 'static {
 
 #[cfg(test)]
 mod tests {
-    use super::EthereumCallFilter;
+    use super::{EthereumCallFilter, EthereumCreationFilter, TriggerFilter};
+    use crate::transform::CombinedFilter;
+
+    use graph::blockchain::TriggerFilter as _;
+    use prost::Message;
 
     use graph::prelude::web3::types::Address;
     use graph::prelude::web3::types::Bytes;
+    use graph::prelude::web3::types::H256;
     use graph::prelude::EthereumCall;
 
     use std::collections::{HashMap, HashSet};
     use std::iter::FromIterator;
+    use tiny_keccak::keccak256;
 
     #[test]
     fn matching_ethereum_call_filter() {
@@ -819,6 +965,7 @@ fn complete_log_filter() {
             let logs_filters: Vec<_> = EthereumLogFilter {
                 contracts_and_events_graph,
                 wildcard_events: HashSet::new(),
+                requires_transaction_receipt: false,
             }
             .eth_get_logs_filters()
             .collect();
@@ -849,4 +996,64 @@ fn complete_log_filter() {
             }
         }
     }
+
+    #[test]
+    fn matching_ethereum_creation_filter() {
+        let deployer = Address::from_low_u64_be(1);
+        let other = Address::from_low_u64_be(2);
+        let init_code = vec![0x60, 0x80, 0x60, 0x40];
+        let code_hash = H256::from(keccak256(&init_code));
+
+        let filter = EthereumCreationFilter {
+            creators: HashSet::from_iter(vec![
+                (10, Some(deployer), None),
+                (0, None, Some(code_hash)),
+            ]),
+        };
+
+        // Any code deployed by `deployer` after its start block
+        assert!(filter.matches(10, deployer, &[1, 2, 3]));
+        assert!(!filter.matches(9, deployer, &[1, 2, 3]));
+        // The code hash matches regardless of the deployer
+        assert!(filter.matches(0, other, &init_code));
+        assert!(!filter.matches(0, other, &[1, 2, 3]));
+
+        // Contract creations need unfiltered Firehose blocks, even if
+        // there are other filters
+        let filter = TriggerFilter {
+            call: EthereumCallFilter::from_iter(vec![(0, deployer, [1, 2, 3, 4])]),
+            creation: filter,
+            ..Default::default()
+        };
+        assert!(filter.to_firehose_filter().is_empty());
+    }
+
+    #[test]
+    fn firehose_filter_from_trigger_filter() {
+        // Without any log or call filters, blocks are not filtered
+        assert!(TriggerFilter::default().to_firehose_filter().is_empty());
+
+        let address = Address::from_low_u64_be(1);
+        let filter = TriggerFilter {
+            call: EthereumCallFilter::from_iter(vec![(0, address, [1, 2, 3, 4])]),
+            ..Default::default()
+        };
+
+        let transforms = filter.to_firehose_filter();
+        assert_eq!(1, transforms.len());
+        assert_eq!(
+            "type.googleapis.com/sf.ethereum.transform.v1.CombinedFilter",
+            transforms[0].type_url
+        );
+
+        let combined = CombinedFilter::decode(transforms[0].value.as_slice()).unwrap();
+        assert!(combined.send_all_block_headers);
+        assert!(combined.log_filters.is_empty());
+        assert_eq!(1, combined.call_filters.len());
+        assert_eq!(
+            vec![address.as_bytes().to_vec()],
+            combined.call_filters[0].addresses
+        );
+        assert_eq!(vec![vec![1, 2, 3, 4]], combined.call_filters[0].signatures);
+    }
 }
@@ -1,9 +1,9 @@
 use anyhow::{Context, Error};
 use graph::blockchain::BlockchainKind;
-use graph::components::store::WritableStore;
+use graph::components::store::{ChainBlockTimestamps, WritableStore};
 use graph::data::subgraph::UnifiedMappingApiVersion;
 use graph::env::env_var;
-use graph::firehose::{FirehoseEndpoints, ForkStep};
+use graph::firehose::FirehoseEndpoints;
 use graph::prelude::{
     EthereumBlock, EthereumCallCache, LightEthereumBlock, LightEthereumBlockExt, StopwatchMetrics,
 };
@@ -11,16 +11,19 @@ use graph::slog::debug;
 use graph::{
     blockchain::{
         block_stream::{
-            BlockStreamEvent, BlockStreamMetrics, BlockWithTriggers, FirehoseError,
-            FirehoseMapper as FirehoseMapperTrait, TriggersAdapter as TriggersAdapterTrait,
+            BlockStreamMetrics, BlockWithTriggers, FirehoseError,
+            TriggersAdapter as TriggersAdapterTrait,
         },
         firehose_block_stream::FirehoseBlockStream,
+        firehose_mapper::{
+            FirehoseBlockDecoder as FirehoseBlockDecoderTrait, GenericFirehoseMapper,
+        },
+        firehose_multiplexer::FirehoseMultiplexer,
         polling_block_stream::PollingBlockStream,
         Block, BlockPtr, Blockchain, ChainHeadUpdateListener, IngestorError, TriggerFilter as _,
     },
     cheap_clone::CheapClone,
     components::store::DeploymentLocator,
-    firehose,
     prelude::{
         async_trait, lazy_static, o, serde_json as json, BlockNumber, ChainStore,
         EthereumBlockWithCalls, Future01CompatExt, Logger, LoggerFactory, MetricsRegistry, NodeId,
@@ -31,6 +34,7 @@ use std::collections::HashSet;
 use std::iter::FromIterator;
 use std::sync::Arc;
 
+use crate::abi_fallback::AbiFallback;
 use crate::data_source::DataSourceTemplate;
 use crate::data_source::UnresolvedDataSourceTemplate;
 use crate::RuntimeAdapter;
@@ -40,12 +44,12 @@ use crate::{
     data_source::{DataSource, UnresolvedDataSource},
     ethereum_adapter::{
         blocks_with_triggers, get_calls, parse_block_triggers, parse_call_triggers,
-        parse_log_triggers,
+        parse_creation_triggers, parse_log_triggers,
     },
     SubgraphEthRpcMetrics, TriggerFilter,
 };
 use crate::{network::EthereumNetworkAdapters, EthereumAdapter};
-use graph::blockchain::block_stream::{BlockStream, FirehoseCursor};
+use graph::blockchain::block_stream::BlockStream;
 
 lazy_static! {
     /// Maximum number of blocks to request in each chunk.
@@ -54,6 +58,9 @@ lazy_static! {
     /// Ideal number of triggers in a range. The range size will adapt to try to meet this.
     static ref TARGET_TRIGGERS_PER_BLOCK_RANGE: u64 = env_var("GRAPH_ETHEREUM_TARGET_TRIGGERS_PER_BLOCK_RANGE", 100);
 
+    /// How many block ranges to scan for triggers concurrently.
+    static ref BLOCK_RANGE_SCAN_PARALLELISM: usize = env_var("GRAPH_ETHEREUM_BLOCK_RANGE_SCAN_PARALLELISM", 1);
+
     /// Controls if firehose should be preferred over RPC if Firehose endpoints are present, if not set, the default behavior is
     /// is kept which is to automatically favor Firehose.
     static ref IS_FIREHOSE_PREFERRED: bool = env_var("GRAPH_ETHEREUM_IS_FIREHOSE_PREFERRED", true);
@@ -68,6 +75,7 @@ pub struct Chain {
     node_id: NodeId,
     registry: Arc<dyn MetricsRegistry>,
     firehose_endpoints: Arc<FirehoseEndpoints>,
+    firehose_multiplexer: FirehoseMultiplexer<Chain>,
     eth_adapters: Arc<EthereumNetworkAdapters>,
     chain_store: Arc<dyn ChainStore>,
     call_cache: Arc<dyn EthereumCallCache>,
@@ -96,12 +104,21 @@ impl Chain {
         reorg_threshold: BlockNumber,
         is_ingestible: bool,
     ) -> Self {
+        if let Some(adapter) = eth_adapters.cheapest() {
+            let logger = logger_factory.component_logger("AbiFallback", None);
+            AbiFallback::register(&logger, &name, adapter, chain_store.clone());
+        }
+
+        let firehose_multiplexer =
+            FirehoseMultiplexer::new(logger_factory.component_logger("FirehoseMultiplexer", None));
+
         Chain {
             logger_factory,
             name,
             node_id,
             registry,
             firehose_endpoints: Arc::new(firehose_endpoints),
+            firehose_multiplexer,
             eth_adapters: Arc::new(eth_adapters),
             chain_store,
             call_cache,
@@ -116,6 +133,40 @@ impl Chain {
     pub fn cheapest_adapter(&self) -> Arc<EthereumAdapter> {
         self.eth_adapters.cheapest().unwrap().clone()
     }
+
+    /// Fetch the timestamps of final blocks of this chain from its
+    /// providers
+    pub fn block_timestamps(&self) -> Arc<dyn ChainBlockTimestamps> {
+        Arc::new(EthereumBlockTimestamps {
+            logger: self
+                .logger_factory
+                .component_logger("EthereumBlockTimestamps", None),
+            chain_store: self.chain_store.cheap_clone(),
+            eth_adapters: self.eth_adapters.cheap_clone(),
+        })
+    }
+}
+
+struct EthereumBlockTimestamps {
+    logger: Logger,
+    chain_store: Arc<dyn ChainStore>,
+    eth_adapters: Arc<EthereumNetworkAdapters>,
+}
+
+impl ChainBlockTimestamps for EthereumBlockTimestamps {
+    fn block_timestamp(&self, number: BlockNumber) -> Result<Option<u64>, Error> {
+        // Only final blocks have a timestamp that can not change anymore
+        match self.chain_store.final_block_number()? {
+            Some(final_number) if number >= 0 && number <= final_number => {}
+            _ => return Ok(None),
+        }
+        let eth_adapter = self
+            .eth_adapters
+            .cheapest()
+            .ok_or_else(|| anyhow::anyhow!("no adapter to look up block {}", number))?;
+        let block = graph::block_on(eth_adapter.block_by_number(&self.logger, number).compat())?;
+        Ok(block.map(|block| block.timestamp.low_u64()))
+    }
 }
 
 #[async_trait]
@@ -191,6 +242,7 @@ impl Blockchain for Chain {
         filter: Arc<Self::TriggerFilter>,
         metrics: Arc<BlockStreamMetrics>,
         unified_api_version: UnifiedMappingApiVersion,
+        firehose_provider: Option<String>,
     ) -> Result<Box<dyn BlockStream<Self>>, Error> {
         let requirements = filter.node_capabilities();
         let adapter = self
@@ -205,27 +257,34 @@ impl Blockchain for Chain {
                 self.name, requirements
             ));
 
-        let firehose_endpoint = match self.firehose_endpoints.random() {
-            Some(e) => e.clone(),
-            None => return Err(anyhow::format_err!("no firehose endpoint available",)),
-        };
+        let firehose_endpoint = self
+            .firehose_endpoints
+            .select(firehose_provider.as_deref())?;
 
         let logger = self
             .logger_factory
             .subgraph_logger(&deployment)
             .new(o!("component" => "FirehoseBlockStream"));
 
-        let firehose_mapper = Arc::new(FirehoseMapper {});
+        let firehose_mapper = Arc::new(GenericFirehoseMapper::new(FirehoseBlockDecoder {}));
         let firehose_cursor = writable.block_cursor();
+        let upstream = self.firehose_multiplexer.subscribe(
+            &firehose_endpoint,
+            filter.to_firehose_filter(),
+            firehose_mapper.clone(),
+        );
 
         Ok(Box::new(FirehoseBlockStream::new(
             firehose_endpoint,
+            upstream,
             firehose_cursor,
+            writable.block_ptr(),
             firehose_mapper,
             adapter,
             filter,
             start_blocks,
             logger,
+            metrics,
         )))
     }
 
@@ -237,6 +296,7 @@ impl Blockchain for Chain {
         filter: Arc<Self::TriggerFilter>,
         metrics: Arc<BlockStreamMetrics>,
         unified_api_version: UnifiedMappingApiVersion,
+        checkpoint_interval: Option<BlockNumber>,
     ) -> Result<Box<dyn BlockStream<Self>>, Error> {
         let requirements = filter.node_capabilities();
         let adapter = self
@@ -283,8 +343,10 @@ impl Blockchain for Chain {
             metrics,
             *MAX_BLOCK_RANGE_SIZE,
             *TARGET_TRIGGERS_PER_BLOCK_RANGE,
+            *BLOCK_RANGE_SCAN_PARALLELISM,
             unified_api_version,
             subgraph_start_block,
+            checkpoint_interval,
         )))
     }
 
@@ -312,6 +374,7 @@ impl Blockchain for Chain {
         Arc::new(RuntimeAdapter {
             eth_adapters: self.eth_adapters.cheap_clone(),
             call_cache: self.call_cache.cheap_clone(),
+            chain_store: self.chain_store.cheap_clone(),
         })
     }
 
@@ -365,6 +428,15 @@ impl Block for BlockFinality {
         }
     }
 
+    fn timestamp(&self) -> Option<u64> {
+        let timestamp = self.light_block().timestamp;
+        if timestamp > u64::MAX.into() {
+            None
+        } else {
+            Some(timestamp.as_u64())
+        }
+    }
+
     fn data(&self) -> Result<json::Value, json::Error> {
         // The serialization here very delicately depends on how the
         // `ChainStore`'s `blocks` and `ancestor_block` return the data we
@@ -472,6 +544,10 @@ impl TriggersAdapterTrait<Chain> for TriggersAdapter {
                 ));
                 triggers.append(&mut parse_call_triggers(&filter.call, &full_block)?);
                 triggers.append(&mut parse_block_triggers(&filter.block, &full_block));
+                triggers.append(&mut parse_creation_triggers(
+                    &filter.creation,
+                    &full_block.ethereum_block,
+                ));
                 Ok(BlockWithTriggers::new(block, triggers))
             }
         }
@@ -521,70 +597,14 @@ impl TriggersAdapterTrait<Chain> for TriggersAdapter {
     }
 }
 
-pub struct FirehoseMapper {}
-
-#[async_trait]
-impl FirehoseMapperTrait<Chain> for FirehoseMapper {
-    async fn to_block_stream_event(
-        &self,
-        logger: &Logger,
-        response: &firehose::Response,
-        adapter: &TriggersAdapter,
-        filter: &TriggerFilter,
-    ) -> Result<BlockStreamEvent<Chain>, FirehoseError> {
-        let step = ForkStep::from_i32(response.step).unwrap_or_else(|| {
-            panic!(
-                "unknown step i32 value {}, maybe you forgot update & re-regenerate the protobuf definitions?",
-                response.step
-            )
-        });
-        let any_block = response
-            .block
-            .as_ref()
-            .expect("block payload information should always be present");
-
-        // Right now, this is done in all cases but in reality, with how the BlockStreamEvent::Revert
-        // is defined right now, only block hash and block number is necessary. However, this information
-        // is not part of the actual firehose::Response payload. As such, we need to decode the full
-        // block which is useless.
-        //
-        // Check about adding basic information about the block in the firehose::Response or maybe
-        // define a slimmed down stuct that would decode only a few fields and ignore all the rest.
-        let block = codec::Block::decode(any_block.value.as_ref())?;
-
-        use firehose::ForkStep::*;
-        match step {
-            StepNew => {
-                let ethereum_block: EthereumBlockWithCalls = (&block).into();
-                let block_with_triggers = adapter
-                    .triggers_in_block(logger, BlockFinality::NonFinal(ethereum_block), filter)
-                    .await?;
-
-                Ok(BlockStreamEvent::ProcessBlock(
-                    block_with_triggers,
-                    FirehoseCursor::Some(response.cursor.clone()),
-                ))
-            }
-
-            StepUndo => {
-                let parent_ptr = block
-                    .parent_ptr()
-                    .expect("Genesis block should never be reverted");
+pub struct FirehoseBlockDecoder {}
 
-                Ok(BlockStreamEvent::Revert(
-                    block.ptr(),
-                    parent_ptr,
-                    FirehoseCursor::Some(response.cursor.clone()),
-                ))
-            }
-
-            StepIrreversible => {
-                unreachable!("irreversible step is not handled and should not be requested in the Firehose request")
-            }
+impl FirehoseBlockDecoderTrait<Chain> for FirehoseBlockDecoder {
+    type HeaderOnlyBlock = codec::HeaderOnlyBlock;
 
-            StepUnknown => {
-                unreachable!("unknown step should not happen in the Firehose response")
-            }
-        }
+    fn decode_block(&self, payload: &[u8]) -> Result<BlockFinality, FirehoseError> {
+        let block = codec::Block::decode(payload)?;
+        let ethereum_block: EthereumBlockWithCalls = (&block).into();
+        Ok(BlockFinality::NonFinal(ethereum_block))
     }
 }
@@ -335,3 +335,58 @@ impl BlockchainBlock for Block {
         }
     }
 }
+
+impl<'a> From<&'a HeaderOnlyBlock> for BlockPtr {
+    fn from(b: &'a HeaderOnlyBlock) -> BlockPtr {
+        BlockPtr::from((H256::from_slice(b.hash.as_ref()), b.number))
+    }
+}
+
+impl BlockchainBlock for HeaderOnlyBlock {
+    fn number(&self) -> i32 {
+        BlockNumber::try_from(self.number).unwrap()
+    }
+
+    fn ptr(&self) -> BlockPtr {
+        self.into()
+    }
+
+    fn parent_ptr(&self) -> Option<BlockPtr> {
+        let parent_hash = &self.header.as_ref().unwrap().parent_hash;
+
+        match parent_hash.len() {
+            0 => None,
+            _ => Some(BlockPtr::from((
+                H256::from_slice(parent_hash.as_ref()),
+                self.number - 1,
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graph::blockchain::Block as BlockchainBlock;
+    use prost::Message;
+
+    use super::{Block, BlockHeader, HeaderOnlyBlock, TransactionTrace};
+
+    #[test]
+    fn header_only_block_has_same_pointers() {
+        let block = Block {
+            hash: vec![1; 32],
+            number: 10,
+            header: Some(BlockHeader {
+                parent_hash: vec![2; 32],
+                number: 10,
+                ..Default::default()
+            }),
+            transaction_traces: vec![TransactionTrace::default(); 3],
+            ..Default::default()
+        };
+
+        let header = HeaderOnlyBlock::decode(block.encode_to_vec().as_ref()).unwrap();
+        assert_eq!(block.ptr(), header.ptr());
+        assert_eq!(block.parent_ptr(), header.parent_ptr());
+    }
+}
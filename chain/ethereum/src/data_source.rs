@@ -10,7 +10,7 @@ use graph::prelude::{Entity, Link, SubgraphManifestValidationError};
 use graph::slog::{o, trace};
 use std::collections::BTreeMap;
 use std::str::FromStr;
-use std::{convert::TryFrom, sync::Arc};
+use std::{convert::TryFrom, sync::Arc, time::Duration};
 use tiny_keccak::{keccak256, Keccak};
 
 use graph::{
@@ -25,10 +25,13 @@ use graph::{
     },
 };
 
-use graph::data::subgraph::{calls_host_fn, DataSourceContext, Source};
+use graph::data::subgraph::{calls_host_fn, DataSourceContext, Source, API_VERSION_0_0_12};
 
+use crate::abi_fallback::AbiFallback;
 use crate::chain::Chain;
-use crate::trigger::{EthereumBlockTriggerType, EthereumTrigger, MappingTrigger};
+use crate::trigger::{
+    EthereumBlockTriggerType, EthereumContractCreation, EthereumTrigger, MappingTrigger,
+};
 
 // The recommended kind is `ethereum`, `ethereum/contract` is accepted for backwards compatibility.
 const ETHEREUM_KINDS: &[&str] = &["ethereum/contract", "ethereum"];
@@ -67,6 +70,19 @@ impl blockchain::DataSource<Chain> for DataSource {
         self.match_and_decode(trigger, block, logger)
     }
 
+    fn scheduled_handler_trigger(
+        &self,
+        handler: &str,
+        block: Arc<<Chain as Blockchain>::Block>,
+    ) -> Result<TriggerWithHandler<Chain>, Error> {
+        Ok(TriggerWithHandler::new(
+            MappingTrigger::Block {
+                block: block.light_block(),
+            },
+            handler.to_owned(),
+        ))
+    }
+
     fn name(&self) -> &str {
         &self.name
     }
@@ -113,6 +129,7 @@ impl blockchain::DataSource<Chain> for DataSource {
             && mapping.event_handlers == other.mapping.event_handlers
             && mapping.call_handlers == other.mapping.call_handlers
             && mapping.block_handlers == other.mapping.block_handlers
+            && mapping.creation_handlers == other.mapping.creation_handlers
             && context == &other.context
     }
 
@@ -146,7 +163,10 @@ impl blockchain::DataSource<Chain> for DataSource {
             .map(|ctx| serde_json::from_str::<Entity>(&ctx))
             .transpose()?;
 
-        let contract_abi = template.mapping.find_abi(&template.source.abi)?;
+        let contract_abi = match source.address {
+            Some(address) => template.abi_for(address)?,
+            None => template.mapping.find_abi(&template.source.abi)?,
+        };
 
         Ok(DataSource {
             kind: template.kind.to_string(),
@@ -198,6 +218,39 @@ impl blockchain::DataSource<Chain> for DataSource {
             errors.push(anyhow!("data source has duplicated block handlers"));
         }
 
+        // Creation handlers need to be restricted by deployer or code,
+        // otherwise they would trigger for every contract on the chain
+        if self
+            .mapping
+            .creation_handlers
+            .iter()
+            .any(|handler| no_source_address && handler.code_hash.is_none())
+        {
+            errors.push(anyhow!(
+                "data source has a creation handler without a source address or `codeHash`"
+            ));
+        }
+        if self.mapping.creation_handlers.len() > 1 {
+            errors.push(anyhow!("data source has more than one creation handler"));
+        }
+        if !self.mapping.creation_handlers.is_empty()
+            && self.mapping.api_version < semver::Version::new(0, 0, 6)
+        {
+            errors.push(anyhow!(
+                "creation handlers require `apiVersion` 0.0.6 or higher"
+            ));
+        }
+
+        // Receipts are only part of the event passed to mappings
+        // starting with api version 0.0.12
+        if self.mapping.event_handlers.iter().any(|h| h.receipt)
+            && self.mapping.api_version < API_VERSION_0_0_12
+        {
+            errors.push(anyhow!(
+                "event handlers with `receipt: true` require `apiVersion` 0.0.12 or higher"
+            ));
+        }
+
         errors
     }
 
@@ -208,6 +261,10 @@ impl blockchain::DataSource<Chain> for DataSource {
     fn runtime(&self) -> &[u8] {
         self.mapping.runtime.as_ref()
     }
+
+    fn handler_timeout(&self) -> Option<Duration> {
+        self.mapping.handler_timeout
+    }
 }
 
 impl DataSource {
@@ -294,6 +351,21 @@ impl DataSource {
         }
     }
 
+    fn handler_for_creation(
+        &self,
+        creation: &EthereumContractCreation,
+    ) -> Option<MappingCreationHandler> {
+        self.mapping
+            .creation_handlers
+            .iter()
+            .find(|handler| {
+                handler
+                    .code_hash
+                    .map_or(true, |hash| hash == creation.code_hash())
+            })
+            .cloned()
+    }
+
     /// Returns the contract event with the given signature, if it exists. A an event from the ABI
     /// will be matched if:
     /// 1. An event signature is equal to `signature`.
@@ -433,7 +505,9 @@ impl DataSource {
         let trigger_address = match trigger {
             EthereumTrigger::Block(_, EthereumBlockTriggerType::WithCallTo(address)) => address,
             EthereumTrigger::Call(call) => &call.to,
-            EthereumTrigger::Log(log) => &log.address,
+            EthereumTrigger::Log(log, _) => &log.address,
+            // For creations, the data source address is the deployer
+            EthereumTrigger::Creation(creation) => &creation.from,
 
             // Unfiltered block triggers match any data source address.
             EthereumTrigger::Block(_, EthereumBlockTriggerType::Every) => return true,
@@ -469,7 +543,7 @@ impl DataSource {
                     handler.handler,
                 )))
             }
-            EthereumTrigger::Log(log) => {
+            EthereumTrigger::Log(log, receipt) => {
                 let potential_handlers = self.handlers_for_log(log)?;
 
                 // Map event handlers to (event handler, event ABI) pairs; fail if there are
@@ -569,6 +643,7 @@ impl DataSource {
                         transaction: Arc::new(transaction),
                         log: log.cheap_clone(),
                         params,
+                        receipt: receipt.as_ref().filter(|_| event_handler.receipt).cloned(),
                     },
                     event_handler.handler,
                     logging_extras,
@@ -675,6 +750,30 @@ impl DataSource {
                     logging_extras,
                 )))
             }
+            EthereumTrigger::Creation(creation) => {
+                let handler = match self.handler_for_creation(creation) {
+                    Some(handler) => handler,
+                    None => return Ok(None),
+                };
+                let transaction = block
+                    .transactions
+                    .iter()
+                    .find(|tx| tx.hash == creation.transaction_hash)
+                    .cloned()
+                    .context("Found no transaction for contract creation")?;
+                let logging_extras = Arc::new(o! {
+                    "address" => format!("{}", &creation.address),
+                });
+                Ok(Some(TriggerWithHandler::new_with_logging_extras(
+                    MappingTrigger::Creation {
+                        block,
+                        transaction: Arc::new(transaction),
+                        creation: creation.cheap_clone(),
+                    },
+                    handler.handler,
+                    logging_extras,
+                )))
+            }
         }
     }
 }
@@ -743,8 +842,7 @@ impl TryFrom<DataSourceTemplateInfo<Chain>> for DataSource {
         })?;
 
         let contract_abi = template
-            .mapping
-            .find_abi(&template.source.abi)
+            .abi_for(address)
             .with_context(|| format!("template `{}`", template.name))?;
 
         Ok(DataSource {
@@ -776,6 +874,24 @@ pub struct BaseDataSourceTemplate<M> {
 pub type UnresolvedDataSourceTemplate = BaseDataSourceTemplate<UnresolvedMapping>;
 pub type DataSourceTemplate = BaseDataSourceTemplate<Mapping>;
 
+impl DataSourceTemplate {
+    /// Find the ABI the template refers to. If the template's mapping does
+    /// not contain that ABI and fetching ABIs is enabled for the template's
+    /// network, fetch the ABI of the verified contract at `address`
+    fn abi_for(&self, address: Address) -> Result<Arc<MappingABI>, Error> {
+        let err = match self.mapping.find_abi(&self.source.abi) {
+            Ok(abi) => return Ok(abi),
+            Err(e) => e,
+        };
+        match self.network.as_deref().and_then(AbiFallback::for_network) {
+            Some(fallback) => fallback
+                .resolve_blocking(&self.source.abi, address)
+                .with_context(|| format!("failed to fetch ABI `{}`", self.source.abi)),
+            None => Err(err),
+        }
+    }
+}
+
 #[async_trait]
 impl blockchain::UnresolvedDataSourceTemplate<Chain> for UnresolvedDataSourceTemplate {
     async fn resolve(
@@ -831,6 +947,12 @@ pub struct UnresolvedMapping {
     pub call_handlers: Vec<MappingCallHandler>,
     #[serde(default)]
     pub event_handlers: Vec<MappingEventHandler>,
+    #[serde(default)]
+    pub creation_handlers: Vec<MappingCreationHandler>,
+    /// How many seconds a handler of this mapping may run, overriding
+    /// `GRAPH_MAPPING_HANDLER_TIMEOUT`
+    #[serde(default)]
+    pub handler_timeout: Option<u64>,
     pub file: Link,
 }
 
@@ -844,6 +966,8 @@ pub struct Mapping {
     pub block_handlers: Vec<MappingBlockHandler>,
     pub call_handlers: Vec<MappingCallHandler>,
     pub event_handlers: Vec<MappingEventHandler>,
+    pub creation_handlers: Vec<MappingCreationHandler>,
+    pub handler_timeout: Option<Duration>,
     pub runtime: Arc<Vec<u8>>,
     pub link: Link,
 }
@@ -888,6 +1012,8 @@ impl UnresolvedMapping {
             block_handlers,
             call_handlers,
             event_handlers,
+            creation_handlers,
+            handler_timeout,
             file: link,
         } = self;
 
@@ -921,6 +1047,8 @@ impl UnresolvedMapping {
             block_handlers: block_handlers.clone(),
             call_handlers: call_handlers.clone(),
             event_handlers: event_handlers.clone(),
+            creation_handlers,
+            handler_timeout: handler_timeout.map(Duration::from_secs),
             runtime,
             link,
         })
@@ -986,6 +1114,18 @@ pub struct MappingEventHandler {
     pub event: String,
     pub topic0: Option<H256>,
     pub handler: String,
+    /// Pass the receipt of the transaction that emitted the event to the
+    /// handler
+    #[serde(default)]
+    pub receipt: bool,
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MappingCreationHandler {
+    pub handler: String,
+    /// Only trigger for contracts whose init code has this keccak256 hash
+    pub code_hash: Option<H256>,
 }
 
 impl MappingEventHandler {
@@ -45,11 +45,11 @@ use crate::chain::BlockFinality;
 use crate::{
     adapter::{
         EthGetLogsFilter, EthereumAdapter as EthereumAdapterTrait, EthereumBlockFilter,
-        EthereumCallFilter, EthereumContractCall, EthereumContractCallError, EthereumLogFilter,
-        ProviderEthRpcMetrics, SubgraphEthRpcMetrics,
+        EthereumCallFilter, EthereumContractCall, EthereumContractCallError,
+        EthereumCreationFilter, EthereumLogFilter, ProviderEthRpcMetrics, SubgraphEthRpcMetrics,
     },
     transport::Transport,
-    trigger::{EthereumBlockTriggerType, EthereumTrigger},
+    trigger::{EthereumBlockTriggerType, EthereumContractCreation, EthereumTrigger},
     TriggerFilter,
 };
 
@@ -841,6 +841,85 @@ impl EthereumAdapter {
         )
     }
 
+    /// Find the contract creations matching `filter` in the blocks `from`
+    /// to `to`. Contract creations are only visible in the transactions of
+    /// a block, and this therefore loads every block in the range. The
+    /// receipts of matching transactions are fetched to check that the
+    /// creation succeeded.
+    ///
+    /// Reorg safety: `to` must be a final block.
+    pub(crate) async fn creations_in_block_range(
+        &self,
+        logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
+        from: BlockNumber,
+        to: BlockNumber,
+        filter: &EthereumCreationFilter,
+    ) -> Result<Vec<EthereumTrigger>, Error> {
+        let ptrs = self
+            .block_range_to_ptrs(logger.clone(), from, to)
+            .compat()
+            .await?;
+        let block_hashes = ptrs.iter().map(|ptr| ptr.hash_as_h256()).collect();
+        let blocks: Vec<Arc<LightEthereumBlock>> = self
+            .load_blocks(logger.clone(), chain_store, block_hashes)
+            .collect()
+            .compat()
+            .await?;
+
+        let mut triggers = Vec::new();
+        for block in blocks {
+            let number = block.number();
+            for tx in block.transactions.iter().filter(|tx| {
+                tx.to.is_none()
+                    && tx
+                        .from
+                        .map_or(false, |from| filter.matches(number, from, &tx.input.0))
+            }) {
+                let receipt = fetch_receipt_from_ethereum_client(self, &tx.hash).await?;
+                if let Some(creation) = EthereumContractCreation::from_transaction(tx, &receipt) {
+                    triggers.push(EthereumTrigger::Creation(Arc::new(creation)));
+                }
+            }
+        }
+        Ok(triggers)
+    }
+
+    /// Return the keccak256 hash of the runtime code of the contract at
+    /// `address` at the latest block
+    pub async fn code_hash(&self, address: H160) -> Result<H256, Error> {
+        let logger = self.logger.clone();
+        let web3 = self.web3.clone();
+        let code = retry("eth_getCode RPC call", &logger)
+            .limit(*REQUEST_RETRIES)
+            .timeout_secs(*JSON_RPC_TIMEOUT)
+            .run(move || {
+                let web3 = web3.cheap_clone();
+                async move { web3.eth().code(address, None).await }
+            })
+            .await?;
+        Ok(H256::from(tiny_keccak::keccak256(&code.0)))
+    }
+
+    /// Return the receipt of the transaction with hash `hash`, or `None` if
+    /// the node does not know the transaction
+    pub async fn transaction_receipt(
+        &self,
+        hash: H256,
+    ) -> Result<Option<TransactionReceipt>, Error> {
+        let logger = self.logger.clone();
+        let web3 = self.web3.clone();
+        retry("eth_getTransactionReceipt RPC call", &logger)
+            .limit(*REQUEST_RETRIES)
+            .timeout_secs(*JSON_RPC_TIMEOUT)
+            .run(move || {
+                let web3 = web3.cheap_clone();
+                async move { web3.eth().transaction_receipt(hash).await }
+            })
+            .await
+            .map_err(|e| anyhow!("Failed to fetch transaction receipt: {}", e))
+    }
+
     pub async fn chain_id(&self) -> Result<u64, Error> {
         let logger = self.logger.clone();
         let web3 = self.web3.clone();
@@ -1179,24 +1258,9 @@ impl EthereumAdapterTrait for EthereumAdapter {
         call: EthereumContractCall,
         cache: Arc<dyn EthereumCallCache>,
     ) -> Box<dyn Future<Item = Vec<Token>, Error = EthereumContractCallError> + Send> {
-        // Emit custom error for type mismatches.
-        for (token, kind) in call
-            .args
-            .iter()
-            .zip(call.function.inputs.iter().map(|p| &p.kind))
-        {
-            if !token.type_check(kind) {
-                return Box::new(future::err(EthereumContractCallError::TypeError(
-                    token.clone(),
-                    kind.clone(),
-                )));
-            }
-        }
-
-        // Encode the call parameters according to the ABI
-        let call_data = match call.function.encode_input(&call.args) {
+        let call_data = match encode_contract_call(&call) {
             Ok(data) => data,
-            Err(e) => return Box::new(future::err(EthereumContractCallError::EncodingError(e))),
+            Err(e) => return Box::new(future::err(e)),
         };
 
         trace!(logger, "eth_call";
@@ -1243,20 +1307,7 @@ impl EthereumAdapterTrait for EthereumAdapter {
                 }
             }
             // Decode the return values according to the ABI
-            .and_then(move |output| {
-                if output.is_empty() {
-                    // We got a `0x` response. For old Geth, this can mean a revert. It can also be
-                    // that the contract actually returned an empty response. A view call is meant
-                    // to return something, so we treat empty responses the same as reverts.
-                    Err(EthereumContractCallError::Revert("empty response".into()))
-                } else {
-                    // Decode failures are reverts. The reasoning is that if Solidity fails to
-                    // decode an argument, that's a revert, so the same goes for the output.
-                    call.function.decode_output(&output).map_err(|e| {
-                        EthereumContractCallError::Revert(format!("failed to decode output: {}", e))
-                    })
-                }
-            }),
+            .and_then(move |output| decode_contract_call_output(&call, &output)),
         )
     }
 
@@ -1310,6 +1361,50 @@ impl EthereumAdapterTrait for EthereumAdapter {
     }
 }
 
+/// Check the arguments of `call` against the function's ABI and encode
+/// them as call data
+pub(crate) fn encode_contract_call(
+    call: &EthereumContractCall,
+) -> Result<Vec<u8>, EthereumContractCallError> {
+    // Emit custom error for type mismatches.
+    for (token, kind) in call
+        .args
+        .iter()
+        .zip(call.function.inputs.iter().map(|p| &p.kind))
+    {
+        if !token.type_check(kind) {
+            return Err(EthereumContractCallError::TypeError(
+                token.clone(),
+                kind.clone(),
+            ));
+        }
+    }
+
+    // Encode the call parameters according to the ABI
+    call.function
+        .encode_input(&call.args)
+        .map_err(EthereumContractCallError::EncodingError)
+}
+
+/// Decode the return values of `call` according to the function's ABI
+pub(crate) fn decode_contract_call_output(
+    call: &EthereumContractCall,
+    output: &[u8],
+) -> Result<Vec<Token>, EthereumContractCallError> {
+    if output.is_empty() {
+        // We got a `0x` response. For old Geth, this can mean a revert. It can also be
+        // that the contract actually returned an empty response. A view call is meant
+        // to return something, so we treat empty responses the same as reverts.
+        Err(EthereumContractCallError::Revert("empty response".into()))
+    } else {
+        // Decode failures are reverts. The reasoning is that if Solidity fails to
+        // decode an argument, that's a revert, so the same goes for the output.
+        call.function.decode_output(output).map_err(|e| {
+            EthereumContractCallError::Revert(format!("failed to decode output: {}", e))
+        })
+    }
+}
+
 /// Returns blocks with triggers, corresponding to the specified range and filters.
 /// If a block contains no triggers, there may be no corresponding item in the stream.
 /// However the `to` block will always be present, even if triggers are empty.
@@ -1357,7 +1452,7 @@ pub(crate) async fn blocks_with_triggers(
             .map_ok(|logs: Vec<Log>| {
                 logs.into_iter()
                     .map(Arc::new)
-                    .map(EthereumTrigger::Log)
+                    .map(|log| EthereumTrigger::Log(log, None))
                     .collect()
             })
             .compat(),
@@ -1399,6 +1494,21 @@ pub(crate) async fn blocks_with_triggers(
         ));
     }
 
+    if !filter.creation.is_empty() {
+        let eth = eth.cheap_clone();
+        let logger = logger.cheap_clone();
+        let chain_store = chain_store.cheap_clone();
+        let creation_filter = filter.creation.clone();
+        trigger_futs.push(Box::new(
+            async move {
+                eth.creations_in_block_range(&logger, chain_store, from, to, &creation_filter)
+                    .await
+            }
+            .boxed()
+            .compat(),
+        ))
+    }
+
     let logger1 = logger.cheap_clone();
     let logger2 = logger.cheap_clone();
     let eth_clone = eth.cheap_clone();
@@ -1422,6 +1532,14 @@ pub(crate) async fn blocks_with_triggers(
         .compat()
         .await?;
 
+    // `eth_getLogs` does not return receipts, they need to be fetched
+    // separately for the handlers that want them
+    let triggers = if filter.log.requires_transaction_receipt() {
+        attach_receipts_to_log_triggers(&eth, &logger, triggers).await?
+    } else {
+        triggers
+    };
+
     let mut block_hashes: HashSet<H256> =
         triggers.iter().map(EthereumTrigger::block_hash).collect();
     let mut triggers_by_block: HashMap<BlockNumber, Vec<EthereumTrigger>> =
@@ -1430,6 +1548,22 @@ pub(crate) async fn blocks_with_triggers(
             map
         });
 
+    // Blocks for which mappings scheduled handlers need to be passed to the
+    // subgraph even if they contain no triggers
+    let scheduled_blocks: Vec<BlockNumber> =
+        filter.scheduled_blocks.range(from..=to).cloned().collect();
+    if !scheduled_blocks.is_empty() {
+        let ptrs: Vec<BlockPtr> = eth
+            .load_block_ptrs_rpc(logger.clone(), scheduled_blocks)
+            .collect()
+            .compat()
+            .await?;
+        for ptr in ptrs {
+            block_hashes.insert(ptr.hash_as_h256());
+            triggers_by_block.entry(ptr.number).or_default();
+        }
+    }
+
     debug!(logger, "Found {} relevant block(s)", block_hashes.len());
 
     // Make sure `to` is included, even if empty.
@@ -1548,11 +1682,20 @@ pub(crate) fn parse_log_triggers(
         .transaction_receipts
         .iter()
         .flat_map(move |receipt| {
+            // Only hand the receipt to the triggers if some handler asked
+            // for it, to avoid copying it needlessly
+            let shared_receipt = if log_filter.requires_transaction_receipt() {
+                Some(Arc::new(receipt.clone()))
+            } else {
+                None
+            };
             receipt
                 .logs
                 .iter()
                 .filter(move |log| log_filter.matches(log))
-                .map(move |log| EthereumTrigger::Log(Arc::new(log.clone())))
+                .map(move |log| {
+                    EthereumTrigger::Log(Arc::new(log.clone()), shared_receipt.cheap_clone())
+                })
         })
         .collect()
 }
@@ -1616,6 +1759,36 @@ pub(crate) fn parse_block_triggers(
     triggers
 }
 
+pub(crate) fn parse_creation_triggers(
+    creation_filter: &EthereumCreationFilter,
+    block: &EthereumBlock,
+) -> Vec<EthereumTrigger> {
+    if creation_filter.is_empty() {
+        return vec![];
+    }
+
+    let number = block.block.number();
+    block
+        .block
+        .transactions
+        .iter()
+        .filter(|tx| {
+            tx.to.is_none()
+                && tx.from.map_or(false, |from| {
+                    creation_filter.matches(number, from, &tx.input.0)
+                })
+        })
+        .filter_map(|tx| {
+            block
+                .transaction_receipts
+                .iter()
+                .find(|receipt| receipt.transaction_hash == tx.hash)
+                .and_then(|receipt| EthereumContractCreation::from_transaction(tx, receipt))
+        })
+        .map(|creation| EthereumTrigger::Creation(Arc::new(creation)))
+        .collect()
+}
+
 async fn fetch_receipt_from_ethereum_client(
     eth: &EthereumAdapter,
     transaction_hash: &H256,
@@ -1627,6 +1800,103 @@ async fn fetch_receipt_from_ethereum_client(
     }
 }
 
+/// The transactions of the log triggers that do not have a receipt yet,
+/// grouped by the hash of the block that contains them. Every transaction
+/// is listed once, no matter how many logs it emitted
+pub(crate) fn log_receipts_to_fetch(
+    triggers: &[EthereumTrigger],
+) -> BTreeMap<H256, BTreeSet<H256>> {
+    let mut by_block: BTreeMap<H256, BTreeSet<H256>> = BTreeMap::new();
+    for trigger in triggers {
+        if let EthereumTrigger::Log(log, None) = trigger {
+            if let (Some(block_hash), Some(transaction_hash)) =
+                (log.block_hash, log.transaction_hash)
+            {
+                by_block
+                    .entry(block_hash)
+                    .or_default()
+                    .insert(transaction_hash);
+            }
+        }
+    }
+    by_block
+}
+
+/// Attach the receipts in `receipts`, keyed by transaction hash, to the
+/// log triggers that do not have a receipt yet
+pub(crate) fn attach_receipts(
+    triggers: Vec<EthereumTrigger>,
+    receipts: &HashMap<H256, Arc<TransactionReceipt>>,
+) -> Vec<EthereumTrigger> {
+    triggers
+        .into_iter()
+        .map(|trigger| match trigger {
+            EthereumTrigger::Log(log, None) => {
+                let receipt = log
+                    .transaction_hash
+                    .and_then(|hash| receipts.get(&hash))
+                    .cloned();
+                EthereumTrigger::Log(log, receipt)
+            }
+            trigger => trigger,
+        })
+        .collect()
+}
+
+/// Attach the receipt of their transaction to all log triggers. Each
+/// receipt is fetched only once, and receipts are fetched the same way as
+/// for full blocks: concurrently, or in one batch per block if
+/// `GRAPH_ETHEREUM_FETCH_TXN_RECEIPTS_IN_BATCHES` is set. Receipts that
+/// belong to a different block than the log fail the request, since the
+/// block is no longer on the main chain
+async fn attach_receipts_to_log_triggers(
+    eth: &EthereumAdapter,
+    logger: &Logger,
+    triggers: Vec<EthereumTrigger>,
+) -> anyhow::Result<Vec<EthereumTrigger>> {
+    let to_fetch = log_receipts_to_fetch(&triggers);
+    if to_fetch.is_empty() {
+        return Ok(triggers);
+    }
+
+    let web3 = eth.web3.cheap_clone();
+    let logger = logger.cheap_clone();
+    let fetched: Vec<TransactionReceipt> = if *FETCH_RECEIPTS_IN_BATCHES {
+        futures03::stream::iter(to_fetch.into_iter().map(|(block_hash, hashes)| {
+            fetch_transaction_receipts_in_batch_with_retry(
+                web3.cheap_clone(),
+                hashes.into_iter().collect(),
+                block_hash,
+                logger.cheap_clone(),
+            )
+        }))
+        .buffered(*MAX_CONCURRENT_JSON_RPC_CALLS)
+        .try_concat()
+        .await?
+    } else {
+        let requests = to_fetch.into_iter().flat_map(|(block_hash, hashes)| {
+            hashes.into_iter().map(move |hash| (block_hash, hash))
+        });
+        futures03::stream::iter(requests.map(|(block_hash, hash)| {
+            fetch_transaction_receipt_with_retry(
+                web3.cheap_clone(),
+                hash,
+                block_hash,
+                logger.cheap_clone(),
+            )
+        }))
+        .buffered(*MAX_CONCURRENT_JSON_RPC_CALLS)
+        .try_collect()
+        .await?
+    };
+
+    let receipts: HashMap<H256, Arc<TransactionReceipt>> = fetched
+        .into_iter()
+        .map(|receipt| (receipt.transaction_hash, Arc::new(receipt)))
+        .collect();
+    Ok(attach_receipts(triggers, &receipts))
+}
+
 async fn filter_call_triggers_from_unsuccessful_transactions(
     mut block: BlockWithTriggers<crate::Chain>,
     eth: &EthereumAdapter,
@@ -1,10 +1,11 @@
 use crate::{chain::BlockFinality, EthereumAdapter, EthereumAdapterTrait};
 use graph::{
-    blockchain::{BlockHash, BlockPtr, IngestorError},
+    blockchain::{catch_up::CatchUp, Block, BlockHash, BlockPtr, IngestorError},
     cheap_clone::CheapClone,
     prelude::{
-        error, ethabi::ethereum_types::H256, info, lazy_static, tokio, trace, warn, ChainStore,
-        Error, EthereumBlockWithCalls, Future01CompatExt, LogCode, Logger,
+        anyhow::anyhow, error, ethabi::ethereum_types::H256, futures03, info, lazy_static, tokio,
+        trace, warn, BlockNumber, ChainStore, Error, EthereumBlockWithCalls, Future01CompatExt,
+        LightEthereumBlock, LogCode, Logger,
     },
 };
 use std::{sync::Arc, time::Duration};
@@ -24,6 +25,7 @@ pub struct BlockIngestor {
     eth_adapter: Arc<EthereumAdapter>,
     chain_store: Arc<dyn ChainStore>,
     polling_interval: Duration,
+    catch_up: CatchUp,
 }
 
 impl BlockIngestor {
@@ -34,12 +36,14 @@ impl BlockIngestor {
         chain_store: Arc<dyn ChainStore>,
         polling_interval: Duration,
     ) -> Result<BlockIngestor, Error> {
+        let catch_up = CatchUp::new(logger.clone());
         Ok(BlockIngestor {
             logger,
             ancestor_count,
             eth_adapter,
             chain_store,
             polling_interval,
+            catch_up,
         })
     }
 
@@ -114,7 +118,7 @@ impl BlockIngestor {
         }
 
         // Compare latest block with head ptr, alert user if far behind
-        match head_block_ptr_opt {
+        match &head_block_ptr_opt {
             None => {
                 info!(
                     self.logger,
@@ -147,6 +151,12 @@ impl BlockIngestor {
             }
         }
 
+        // After downtime, fetch the blocks between our head and the latest
+        // block by number, without overwhelming the provider
+        if let Some(head_block_ptr) = &head_block_ptr_opt {
+            self.catch_up(head_block_ptr, &latest_block).await?;
+        }
+
         // Store latest block in block store.
         // Might be a no-op if latest block is one that we have seen.
         // ingest_blocks will return a (potentially incomplete) list of blocks that are
@@ -177,20 +187,68 @@ impl BlockIngestor {
         Ok(())
     }
 
-    async fn ingest_block(
-        &self,
-        block_hash: &BlockHash,
-    ) -> Result<Option<BlockHash>, IngestorError> {
-        // TODO: H256::from_slice can panic
-        let block_hash = H256::from_slice(block_hash.as_slice());
+    /// Fetch the blocks that are missing between `head` and `latest`, at
+    /// most `ancestor_count` of them, with the concurrency and rate that
+    /// the catch-up limits allow, and store them in the chain store.
+    /// Blocks that an earlier, interrupted catch-up already stored are not
+    /// fetched again. Walking back from `latest` afterwards fills in any
+    /// blocks that changed because of reorgs in the meantime
+    async fn catch_up(&self, head: &BlockPtr, latest: &BlockPtr) -> Result<(), IngestorError> {
+        use futures03::stream::{self, StreamExt, TryStreamExt};
 
-        // Get the fully populated block
+        let stop = latest.number - 1;
+        let mut start = (head.number + 1).max(latest.number - self.ancestor_count);
+        while start <= stop
+            && !self
+                .chain_store
+                .block_hashes_by_block_number(start)?
+                .is_empty()
+        {
+            start += 1;
+        }
+        if start > stop {
+            return Ok(());
+        }
+
+        info!(
+            self.logger,
+            "Catching up with the chain";
+            "from" => start,
+            "to" => stop,
+            "concurrency" => self.catch_up.concurrency(),
+        );
+
+        let catch_up = &self.catch_up;
+        let mut blocks = stream::iter(start..=stop)
+            .then(|number| async move {
+                catch_up.acquire().await;
+                number
+            })
+            .map(|number| self.full_block_by_number(number))
+            .buffered(catch_up.concurrency());
+        while let Some(block) = blocks.try_next().await? {
+            let number = block.number();
+            self.chain_store.upsert_block(Arc::new(block)).await?;
+            catch_up.record(number, Some(stop));
+        }
+        Ok(())
+    }
+
+    async fn full_block_by_number(
+        &self,
+        number: BlockNumber,
+    ) -> Result<BlockFinality, IngestorError> {
         let block = self
             .eth_adapter
-            .block_by_hash(&self.logger, block_hash)
+            .block_by_number(&self.logger, number)
             .compat()
             .await?
-            .ok_or_else(|| IngestorError::BlockUnavailable(block_hash))?;
+            .ok_or_else(|| anyhow!("block {} is not available", number))?;
+        self.full_block(block).await
+    }
+
+    /// Load the receipts for `block`
+    async fn full_block(&self, block: LightEthereumBlock) -> Result<BlockFinality, IngestorError> {
         let ethereum_block = self
             .eth_adapter
             .load_full_block(&self.logger, block)
@@ -198,10 +256,27 @@ impl BlockIngestor {
 
         // We need something that implements `Block` to store the block; the
         // store does not care whether the block is final or not
-        let ethereum_block = BlockFinality::NonFinal(EthereumBlockWithCalls {
+        Ok(BlockFinality::NonFinal(EthereumBlockWithCalls {
             ethereum_block,
             calls: None,
-        });
+        }))
+    }
+
+    async fn ingest_block(
+        &self,
+        block_hash: &BlockHash,
+    ) -> Result<Option<BlockHash>, IngestorError> {
+        // TODO: H256::from_slice can panic
+        let block_hash = H256::from_slice(block_hash.as_slice());
+
+        // Get the fully populated block
+        let block = self
+            .eth_adapter
+            .block_by_hash(&self.logger, block_hash)
+            .compat()
+            .await?
+            .ok_or_else(|| IngestorError::BlockUnavailable(block_hash))?;
+        let ethereum_block = self.full_block(block).await?;
 
         // Store it in the database and try to advance the chain head pointer
         self.chain_store
@@ -1,3 +1,4 @@
+mod abi_fallback;
 mod adapter;
 mod capabilities;
 pub mod codec;
@@ -21,6 +22,9 @@ pub mod chain;
 mod network;
 mod trigger;
 
+#[path = "protobuf/sf.ethereum.transform.v1.rs"]
+mod transform;
+
 pub use crate::adapter::{
     EthereumAdapter as EthereumAdapterTrait, EthereumContractCall, EthereumContractCallError,
     MockEthereumAdapter, ProviderEthRpcMetrics, SubgraphEthRpcMetrics, TriggerFilter,
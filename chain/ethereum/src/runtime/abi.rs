@@ -1,3 +1,5 @@
+use graph::prelude::transaction_receipt::LightTransactionReceipt;
+use graph::prelude::web3::types::{Log, TransactionReceipt};
 use graph::prelude::{ethabi, BigInt};
 use graph::runtime::{asc_get, asc_new, AscPtr, DeterministicHostError, FromAscObj, ToAscObj};
 use graph::runtime::{AscHeap, AscIndexId, AscType, IndexForAscTypeId};
@@ -8,7 +10,8 @@ use graph_runtime_wasm::asc_abi::class::{
 use semver::Version;
 
 use crate::trigger::{
-    EthereumBlockData, EthereumCallData, EthereumEventData, EthereumTransactionData,
+    EthereumBlockData, EthereumCallData, EthereumContractCreationData, EthereumEventData,
+    EthereumTransactionData,
 };
 
 use super::runtime_adapter::UnresolvedContractCall;
@@ -44,6 +47,35 @@ impl AscIndexId for AscLogParamArray {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayEventParam;
 }
 
+pub struct AscEthereumLogArray(Array<AscPtr<AscEthereumLog>>);
+
+impl AscType for AscEthereumLogArray {
+    fn to_asc_bytes(&self) -> Result<Vec<u8>, DeterministicHostError> {
+        self.0.to_asc_bytes()
+    }
+    fn from_asc_bytes(
+        asc_obj: &[u8],
+        api_version: &Version,
+    ) -> Result<Self, DeterministicHostError> {
+        Ok(Self(Array::from_asc_bytes(asc_obj, api_version)?))
+    }
+}
+
+impl ToAscObj<AscEthereumLogArray> for Vec<Log> {
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscEthereumLogArray, DeterministicHostError> {
+        let content: Result<Vec<_>, _> = self.iter().map(|x| asc_new(heap, x)).collect();
+        let content = content?;
+        Ok(AscEthereumLogArray(Array::new(&*content, heap)?))
+    }
+}
+
+impl AscIndexId for AscEthereumLogArray {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayEthereumLog;
+}
+
 #[repr(C)]
 #[derive(AscType)]
 pub struct AscUnresolvedContractCall_0_0_4 {
@@ -137,7 +169,7 @@ pub(crate) struct AscEthereumBlock_0_0_6 {
     pub difficulty: AscPtr<AscBigInt>,
     pub total_difficulty: AscPtr<AscBigInt>,
     pub size: AscPtr<AscBigInt>,
-    pub base_fee_per_block: AscPtr<AscBigInt>,
+    pub base_fee_per_gas: AscPtr<AscBigInt>,
 }
 
 impl AscIndexId for AscEthereumBlock_0_0_6 {
@@ -177,6 +209,11 @@ impl AscIndexId for AscEthereumTransaction_0_0_2 {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumTransaction;
 }
 
+/// The transaction passed to mappings with api version 0.0.6 and later.
+/// The London fields of type 2 transactions, `maxFeePerGas` and
+/// `maxPriorityFeePerGas`, are not part of it since the `Transaction` of
+/// the version of rust-web3 we use does not deserialize them; the block's
+/// `baseFeePerGas` is available though
 #[repr(C)]
 #[derive(AscType)]
 pub(crate) struct AscEthereumTransaction_0_0_6 {
@@ -223,6 +260,83 @@ impl AscIndexId for AscEthereumEvent<AscEthereumTransaction_0_0_6, AscEthereumBl
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumEvent;
 }
 
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumEvent_0_0_12<T, B>
+where
+    T: AscType,
+    B: AscType,
+{
+    pub address: AscPtr<AscAddress>,
+    pub log_index: AscPtr<AscBigInt>,
+    pub transaction_log_index: AscPtr<AscBigInt>,
+    pub log_type: AscPtr<AscString>,
+    pub block: AscPtr<B>,
+    pub transaction: AscPtr<T>,
+    pub params: AscPtr<AscLogParamArray>,
+    pub receipt: AscPtr<AscEthereumTransactionReceipt>,
+}
+
+impl AscIndexId for AscEthereumEvent_0_0_12<AscEthereumTransaction_0_0_6, AscEthereumBlock_0_0_6> {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumEvent;
+}
+
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumLog {
+    pub address: AscPtr<AscAddress>,
+    pub topics: AscPtr<Array<AscPtr<AscH256>>>,
+    pub data: AscPtr<Uint8Array>,
+    pub block_hash: AscPtr<AscH256>,
+    pub block_number: AscPtr<AscBigInt>,
+    pub transaction_hash: AscPtr<AscH256>,
+    pub transaction_index: AscPtr<AscBigInt>,
+    pub log_index: AscPtr<AscBigInt>,
+    pub transaction_log_index: AscPtr<AscBigInt>,
+    pub log_type: AscPtr<AscString>,
+}
+
+impl AscIndexId for AscEthereumLog {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumLog;
+}
+
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumTransactionReceipt {
+    pub transaction_hash: AscPtr<AscH256>,
+    pub transaction_index: AscPtr<AscBigInt>,
+    pub block_hash: AscPtr<AscH256>,
+    pub block_number: AscPtr<AscBigInt>,
+    pub cumulative_gas_used: AscPtr<AscBigInt>,
+    pub gas_used: AscPtr<AscBigInt>,
+    pub contract_address: AscPtr<AscAddress>,
+    pub logs: AscPtr<AscEthereumLogArray>,
+    pub status: AscPtr<AscBigInt>,
+    pub root: AscPtr<AscH256>,
+    pub logs_bloom: AscPtr<Uint8Array>,
+}
+
+impl AscIndexId for AscEthereumTransactionReceipt {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumTransactionReceipt;
+}
+
+/// The receipt returned by `ethereum.getTransactionReceipt`, which only has
+/// the fields that the chain store keeps for each receipt
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumLightTransactionReceipt {
+    pub transaction_hash: AscPtr<AscH256>,
+    pub transaction_index: AscPtr<AscBigInt>,
+    pub block_hash: AscPtr<AscH256>,
+    pub block_number: AscPtr<AscBigInt>,
+    pub gas_used: AscPtr<AscBigInt>,
+    pub status: AscPtr<AscBigInt>,
+}
+
+impl AscIndexId for AscEthereumLightTransactionReceipt {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumLightTransactionReceipt;
+}
+
 #[repr(C)]
 #[derive(AscType)]
 pub(crate) struct AscLogParam {
@@ -271,6 +385,20 @@ where
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumCall;
 }
 
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumContractCreation {
+    pub address: AscPtr<AscAddress>,
+    pub from: AscPtr<AscAddress>,
+    pub init_code: AscPtr<Uint8Array>,
+    pub block: AscPtr<AscEthereumBlock_0_0_6>,
+    pub transaction: AscPtr<AscEthereumTransaction_0_0_6>,
+}
+
+impl AscIndexId for AscEthereumContractCreation {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumContractCreation;
+}
+
 impl ToAscObj<AscEthereumBlock> for EthereumBlockData {
     fn to_asc_obj<H: AscHeap + ?Sized>(
         &self,
@@ -321,7 +449,7 @@ impl ToAscObj<AscEthereumBlock_0_0_6> for EthereumBlockData {
                 .size
                 .map(|size| asc_new(heap, &BigInt::from_unsigned_u256(&size)))
                 .unwrap_or(Ok(AscPtr::null()))?,
-            base_fee_per_block: self
+            base_fee_per_gas: self
                 .base_fee_per_gas
                 .map(|base_fee| asc_new(heap, &BigInt::from_unsigned_u256(&base_fee)))
                 .unwrap_or(Ok(AscPtr::null()))?,
@@ -422,6 +550,155 @@ where
     }
 }
 
+impl<T, B> ToAscObj<AscEthereumEvent_0_0_12<T, B>> for EthereumEventData
+where
+    T: AscType + AscIndexId,
+    B: AscType + AscIndexId,
+    EthereumTransactionData: ToAscObj<T>,
+    EthereumBlockData: ToAscObj<B>,
+{
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscEthereumEvent_0_0_12<T, B>, DeterministicHostError> {
+        let AscEthereumEvent {
+            address,
+            log_index,
+            transaction_log_index,
+            log_type,
+            block,
+            transaction,
+            params,
+        } = ToAscObj::<AscEthereumEvent<T, B>>::to_asc_obj(self, heap)?;
+        Ok(AscEthereumEvent_0_0_12 {
+            address,
+            log_index,
+            transaction_log_index,
+            log_type,
+            block,
+            transaction,
+            params,
+            receipt: self
+                .receipt
+                .as_ref()
+                .map(|receipt| asc_new(heap, receipt.as_ref()))
+                .unwrap_or(Ok(AscPtr::null()))?,
+        })
+    }
+}
+
+impl ToAscObj<AscEthereumLog> for Log {
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscEthereumLog, DeterministicHostError> {
+        Ok(AscEthereumLog {
+            address: asc_new(heap, &self.address)?,
+            topics: asc_new(heap, &*self.topics)?,
+            data: asc_new(heap, &*self.data.0)?,
+            block_hash: self
+                .block_hash
+                .map(|block_hash| asc_new(heap, &block_hash))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            block_number: self
+                .block_number
+                .map(|block_number| asc_new(heap, &BigInt::from(block_number)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            transaction_hash: self
+                .transaction_hash
+                .map(|transaction_hash| asc_new(heap, &transaction_hash))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            transaction_index: self
+                .transaction_index
+                .map(|transaction_index| asc_new(heap, &BigInt::from(transaction_index)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            log_index: self
+                .log_index
+                .map(|log_index| asc_new(heap, &BigInt::from_unsigned_u256(&log_index)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            transaction_log_index: self
+                .transaction_log_index
+                .map(|index| asc_new(heap, &BigInt::from_unsigned_u256(&index)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            log_type: self
+                .log_type
+                .as_ref()
+                .map(|log_type| asc_new(heap, log_type.as_str()))
+                .unwrap_or(Ok(AscPtr::null()))?,
+        })
+    }
+}
+
+impl ToAscObj<AscEthereumTransactionReceipt> for TransactionReceipt {
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscEthereumTransactionReceipt, DeterministicHostError> {
+        Ok(AscEthereumTransactionReceipt {
+            transaction_hash: asc_new(heap, &self.transaction_hash)?,
+            transaction_index: asc_new(heap, &BigInt::from(self.transaction_index))?,
+            block_hash: self
+                .block_hash
+                .map(|block_hash| asc_new(heap, &block_hash))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            block_number: self
+                .block_number
+                .map(|block_number| asc_new(heap, &BigInt::from(block_number)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            cumulative_gas_used: asc_new(
+                heap,
+                &BigInt::from_unsigned_u256(&self.cumulative_gas_used),
+            )?,
+            gas_used: self
+                .gas_used
+                .map(|gas_used| asc_new(heap, &BigInt::from_unsigned_u256(&gas_used)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            contract_address: self
+                .contract_address
+                .map(|contract_address| asc_new(heap, &contract_address))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            logs: asc_new(heap, &self.logs)?,
+            status: self
+                .status
+                .map(|status| asc_new(heap, &BigInt::from(status)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            root: self
+                .root
+                .map(|root| asc_new(heap, &root))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            logs_bloom: asc_new(heap, &self.logs_bloom.0[..])?,
+        })
+    }
+}
+
+impl ToAscObj<AscEthereumLightTransactionReceipt> for LightTransactionReceipt {
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscEthereumLightTransactionReceipt, DeterministicHostError> {
+        Ok(AscEthereumLightTransactionReceipt {
+            transaction_hash: asc_new(heap, &self.transaction_hash)?,
+            transaction_index: asc_new(heap, &BigInt::from(self.transaction_index))?,
+            block_hash: self
+                .block_hash
+                .map(|block_hash| asc_new(heap, &block_hash))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            block_number: self
+                .block_number
+                .map(|block_number| asc_new(heap, &BigInt::from(block_number)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            gas_used: self
+                .gas_used
+                .map(|gas_used| asc_new(heap, &BigInt::from_unsigned_u256(&gas_used)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            status: self
+                .status
+                .map(|status| asc_new(heap, &BigInt::from(status)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+        })
+    }
+}
+
 impl ToAscObj<AscEthereumCall> for EthereumCallData {
     fn to_asc_obj<H: AscHeap + ?Sized>(
         &self,
@@ -479,6 +756,21 @@ impl ToAscObj<AscEthereumCall_0_0_3<AscEthereumTransaction_0_0_6, AscEthereumBlo
     }
 }
 
+impl ToAscObj<AscEthereumContractCreation> for EthereumContractCreationData {
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscEthereumContractCreation, DeterministicHostError> {
+        Ok(AscEthereumContractCreation {
+            address: asc_new(heap, &self.address)?,
+            from: asc_new(heap, &self.from)?,
+            init_code: asc_new(heap, &*self.init_code)?,
+            block: asc_new(heap, &self.block)?,
+            transaction: asc_new(heap, &self.transaction)?,
+        })
+    }
+}
+
 impl ToAscObj<AscLogParam> for ethabi::LogParam {
     fn to_asc_obj<H: AscHeap + ?Sized>(
         &self,
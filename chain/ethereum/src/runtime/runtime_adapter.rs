@@ -1,6 +1,7 @@
 use std::{sync::Arc, time::Instant};
 
 use crate::data_source::MappingABI;
+use crate::ethereum_adapter::{decode_contract_call_output, encode_contract_call};
 use crate::{
     capabilities::NodeCapabilities, network::EthereumNetworkAdapters, Chain, DataSource,
     EthereumAdapter, EthereumAdapterTrait, EthereumContractCall, EthereumContractCallError,
@@ -14,15 +15,20 @@ use graph::{
     cheap_clone::CheapClone,
     prelude::{
         ethabi::{self, Address, Token},
-        EthereumCallCache, Future01CompatExt,
+        transaction_receipt::LightTransactionReceipt,
+        web3::types::H256,
+        ChainStore, EthereumCallCache, Future01CompatExt,
     },
     runtime::{asc_get, asc_new, AscPtr, HostExportError},
     semver::Version,
     slog::{info, trace, Logger},
 };
-use graph_runtime_wasm::asc_abi::class::{AscEnumArray, EthereumValueKind};
+use graph_runtime_wasm::asc_abi::class::{AscEnumArray, AscString, EthereumValueKind, Uint8Array};
+use lazy_static::lazy_static;
 
-use super::abi::{AscUnresolvedContractCall, AscUnresolvedContractCall_0_0_4};
+use super::abi::{
+    AscEthereumLightTransactionReceipt, AscUnresolvedContractCall, AscUnresolvedContractCall_0_0_4,
+};
 
 // Allow up to 1,000 ethereum calls. The justification is that we don't know how much Ethereum gas a
 // call takes, but we limit the maximum to 25 million. One unit of Ethereum gas is at least 100ns
@@ -33,15 +39,64 @@ use super::abi::{AscUnresolvedContractCall, AscUnresolvedContractCall_0_0_4};
 // [1] - https://www.sciencedirect.com/science/article/abs/pii/S0166531620300900
 pub const ETHEREUM_CALL: Gas = Gas::new(25_000_000_000);
 
+// Looking up a receipt is usually answered from the chain store, and only
+// needs the Ethereum node when the receipts of the block are not cached.
+pub const ETHEREUM_GET_TRANSACTION_RECEIPT: Gas = Gas::new(5_000_000_000);
+
+// A reverse lookup makes two Ethereum calls, one to the ENS registry and one to the resolver.
+pub const ENS_REVERSE_LOOKUP: Gas = Gas::new(50_000_000_000);
+
+lazy_static! {
+    /// The ENS registry, which has the same address on mainnet and the public testnets
+    static ref ENS_REGISTRY: Address = "00000000000C2E074eC69A0dFb2997BA6C7d2e1e".parse().unwrap();
+
+    /// The functions of the ENS registry and resolvers that a reverse lookup calls
+    static ref ENS_ABIS: Vec<Arc<MappingABI>> = vec![
+        ens_abi(
+            "ENSRegistry",
+            r#"[{"name":"resolver","type":"function","stateMutability":"view",
+                 "inputs":[{"name":"node","type":"bytes32"}],
+                 "outputs":[{"name":"","type":"address"}]}]"#,
+        ),
+        ens_abi(
+            "ENSResolver",
+            r#"[{"name":"name","type":"function","stateMutability":"view",
+                 "inputs":[{"name":"node","type":"bytes32"}],
+                 "outputs":[{"name":"","type":"string"}]}]"#,
+        ),
+    ];
+}
+
+fn ens_abi(name: &str, json: &str) -> Arc<MappingABI> {
+    Arc::new(MappingABI {
+        name: name.to_owned(),
+        contract: ethabi::Contract::load(json.as_bytes()).expect("the ENS ABIs are valid"),
+    })
+}
+
 pub struct RuntimeAdapter {
     pub(crate) eth_adapters: Arc<EthereumNetworkAdapters>,
     pub(crate) call_cache: Arc<dyn EthereumCallCache>,
+    pub(crate) chain_store: Arc<dyn ChainStore>,
 }
 
 impl blockchain::RuntimeAdapter<Chain> for RuntimeAdapter {
     fn host_fns(&self, ds: &DataSource) -> Result<Vec<HostFn>, Error> {
+        self.ethereum_host_fns(ds, false)
+    }
+
+    /// Replays answer `ethereum.call` only from the call cache so that they
+    /// never depend on what the chain's nodes return
+    fn replay_host_fns(&self, ds: &DataSource) -> Result<Vec<HostFn>, Error> {
+        self.ethereum_host_fns(ds, true)
+    }
+}
+
+impl RuntimeAdapter {
+    fn ethereum_host_fns(&self, ds: &DataSource, cache_only: bool) -> Result<Vec<HostFn>, Error> {
         let abis = ds.mapping.abis.clone();
         let call_cache = self.call_cache.cheap_clone();
+        let chain_store = self.chain_store.cheap_clone();
         let eth_adapter = self
             .eth_adapters
             .cheapest_with(&NodeCapabilities {
@@ -49,16 +104,58 @@ impl blockchain::RuntimeAdapter<Chain> for RuntimeAdapter {
                 traces: false,
             })?
             .cheap_clone();
+        let receipt_eth_adapter = eth_adapter.cheap_clone();
+        let ens_eth_adapter = eth_adapter.cheap_clone();
+        let ens_call_cache = call_cache.cheap_clone();
 
         let ethereum_call = HostFn {
             name: "ethereum.call",
             func: Arc::new(move |ctx, wasm_ptr| {
-                ethereum_call(&eth_adapter, call_cache.cheap_clone(), ctx, wasm_ptr, &abis)
-                    .map(|ptr| ptr.wasm_ptr())
+                ethereum_call(
+                    &eth_adapter,
+                    call_cache.cheap_clone(),
+                    cache_only,
+                    ctx,
+                    wasm_ptr,
+                    &abis,
+                )
+                .map(|ptr| ptr.wasm_ptr())
             }),
         };
 
-        Ok(vec![ethereum_call])
+        let ethereum_get_transaction_receipt = HostFn {
+            name: "ethereum.getTransactionReceipt",
+            func: Arc::new(move |ctx, wasm_ptr| {
+                ethereum_get_transaction_receipt(
+                    &receipt_eth_adapter,
+                    chain_store.as_ref(),
+                    cache_only,
+                    ctx,
+                    wasm_ptr,
+                )
+                .map(|ptr| ptr.wasm_ptr())
+            }),
+        };
+
+        let ens_reverse_lookup = HostFn {
+            name: "ens.reverseLookup",
+            func: Arc::new(move |ctx, wasm_ptr| {
+                ens_reverse_lookup(
+                    &ens_eth_adapter,
+                    ens_call_cache.cheap_clone(),
+                    cache_only,
+                    ctx,
+                    wasm_ptr,
+                )
+                .map(|ptr| ptr.wasm_ptr())
+            }),
+        };
+
+        Ok(vec![
+            ethereum_call,
+            ethereum_get_transaction_receipt,
+            ens_reverse_lookup,
+        ])
     }
 }
 
@@ -66,6 +163,7 @@ impl blockchain::RuntimeAdapter<Chain> for RuntimeAdapter {
 fn ethereum_call(
     eth_adapter: &EthereumAdapter,
     call_cache: Arc<dyn EthereumCallCache>,
+    cache_only: bool,
     ctx: HostFnCtx<'_>,
     wasm_ptr: u32,
     abis: &[Arc<MappingABI>],
@@ -84,6 +182,7 @@ fn ethereum_call(
     let result = eth_call(
         eth_adapter,
         call_cache,
+        cache_only,
         &ctx.logger,
         &ctx.block_ptr,
         call,
@@ -95,10 +194,119 @@ fn ethereum_call(
     }
 }
 
-/// Returns `Ok(None)` if the call was reverted.
+/// function ethereum.getTransactionReceipt(hash: Bytes): TransactionReceipt | null
+///
+/// Only transactions in the block that is being processed can be looked
+/// up; for any other hash, the result is `null`. The receipts are taken
+/// from the chain store, and only fetched from the Ethereum node if the
+/// block was cached without them.
+fn ethereum_get_transaction_receipt(
+    eth_adapter: &EthereumAdapter,
+    chain_store: &dyn ChainStore,
+    cache_only: bool,
+    ctx: HostFnCtx<'_>,
+    wasm_ptr: u32,
+) -> Result<AscPtr<AscEthereumLightTransactionReceipt>, HostExportError> {
+    ctx.gas.consume_host_fn(ETHEREUM_GET_TRANSACTION_RECEIPT)?;
+
+    let hash: H256 = asc_get::<_, Uint8Array, _>(ctx.heap, wasm_ptr.into())?;
+    let block_hash = ctx.block_ptr.hash_as_h256();
+
+    let receipts = graph::block_on(chain_store.transaction_receipts_in_block(&block_hash))
+        .map_err(|e| HostExportError::Unknown(e.into()))?;
+    let receipt = if !receipts.is_empty() {
+        receipts
+            .into_iter()
+            .find(|receipt| receipt.transaction_hash == hash)
+    } else if cache_only {
+        return Err(HostExportError::Deterministic(anyhow::anyhow!(
+            "Failed to replay ethereum.getTransactionReceipt: the receipts of block {} \
+             are not in the chain store",
+            ctx.block_ptr
+        )));
+    } else {
+        graph::block_on(eth_adapter.transaction_receipt(hash))
+            .map_err(HostExportError::PossibleReorg)?
+            .map(LightTransactionReceipt::from)
+            .filter(|receipt| receipt.block_hash == Some(block_hash))
+    };
+
+    match receipt {
+        Some(receipt) => Ok(asc_new(ctx.heap, &receipt)?),
+        None => Ok(AscPtr::null()),
+    }
+}
+
+/// function ens.reverseLookup(address: Address): string | null
+///
+/// The primary ENS name of `address`, i.e., the name that its reverse
+/// record in `addr.reverse` points to. The name is looked up with calls to
+/// the ENS registry and resolver at the block that is being processed, so
+/// that the result is the same on every node, and is `null` if there is no
+/// reverse record or the network has no ENS registry. Note that ENS does
+/// not check that the name resolves back to `address`.
+fn ens_reverse_lookup(
+    eth_adapter: &EthereumAdapter,
+    call_cache: Arc<dyn EthereumCallCache>,
+    cache_only: bool,
+    ctx: HostFnCtx<'_>,
+    wasm_ptr: u32,
+) -> Result<AscPtr<AscString>, HostExportError> {
+    ctx.gas.consume_host_fn(ENS_REVERSE_LOOKUP)?;
+
+    let address: Address = asc_get::<_, Uint8Array, _>(ctx.heap, wasm_ptr.into())?;
+    let node = Token::FixedBytes(namehash(&format!("{:x}.addr.reverse", address)).to_vec());
+
+    let logger = &ctx.logger;
+    let block_ptr = &ctx.block_ptr;
+    let ens_call = |contract_name: &str, function_name: &str, contract_address: Address| {
+        let call = UnresolvedContractCall {
+            contract_name: contract_name.to_owned(),
+            contract_address,
+            function_name: function_name.to_owned(),
+            function_signature: None,
+            function_args: vec![node.clone()],
+        };
+        eth_call(
+            eth_adapter,
+            call_cache.cheap_clone(),
+            cache_only,
+            logger,
+            block_ptr,
+            call,
+            &ENS_ABIS,
+        )
+        .map(|tokens| tokens.and_then(|mut tokens| tokens.pop()))
+    };
+
+    let resolver = match ens_call("ENSRegistry", "resolver", *ENS_REGISTRY)? {
+        Some(Token::Address(resolver)) if !resolver.is_zero() => resolver,
+        _ => return Ok(AscPtr::null()),
+    };
+    match ens_call("ENSResolver", "name", resolver)? {
+        Some(Token::String(name)) if !name.is_empty() => Ok(asc_new(ctx.heap, name.as_str())?),
+        _ => Ok(AscPtr::null()),
+    }
+}
+
+/// The ENS namehash of `name` as defined in EIP-137
+fn namehash(name: &str) -> [u8; 32] {
+    let mut node = [0u8; 32];
+    for label in name.rsplit('.') {
+        let mut data = node.to_vec();
+        data.extend_from_slice(&tiny_keccak::keccak256(label.as_bytes()));
+        node = tiny_keccak::keccak256(&data);
+    }
+    node
+}
+
+/// Returns `Ok(None)` if the call was reverted. With `cache_only`, the
+/// result must already be in `call_cache`; the call is never sent to an
+/// Ethereum node.
 fn eth_call(
     eth_adapter: &EthereumAdapter,
     call_cache: Arc<dyn EthereumCallCache>,
+    cache_only: bool,
     logger: &Logger,
     block_ptr: &BlockPtr,
     unresolved_call: UnresolvedContractCall,
@@ -164,6 +372,10 @@ fn eth_call(
         args: unresolved_call.function_args.clone(),
     };
 
+    if cache_only {
+        return cached_eth_call(call_cache.as_ref(), &call, &unresolved_call);
+    }
+
     // Run Ethereum call in tokio runtime
     let logger1 = logger.clone();
     let call_cache = call_cache.clone();
@@ -211,6 +423,38 @@ fn eth_call(
     result
 }
 
+/// Answer `call` from the call cache. Returns `Ok(None)` if the cached
+/// call reverted. Calls that are not cached fail deterministically, since
+/// whether a call is cached does not change when a replay is repeated
+fn cached_eth_call(
+    call_cache: &dyn EthereumCallCache,
+    call: &EthereumContractCall,
+    unresolved_call: &UnresolvedContractCall,
+) -> Result<Option<Vec<Token>>, HostExportError> {
+    let failed = |e: &dyn std::fmt::Display| {
+        anyhow::anyhow!(
+            "Failed to replay call of function \"{}\" of contract \"{}\": {}",
+            unresolved_call.function_name,
+            unresolved_call.contract_name,
+            e
+        )
+    };
+
+    let call_data =
+        encode_contract_call(call).map_err(|e| HostExportError::Deterministic(failed(&e)))?;
+    let output = call_cache
+        .get_call(call.address, &call_data, call.block_ptr.cheap_clone())
+        .map_err(|e| HostExportError::Unknown(failed(&e)))?
+        .ok_or_else(|| {
+            HostExportError::Deterministic(failed(&"the call is not in the call cache"))
+        })?;
+    match decode_contract_call_output(call, &output) {
+        Ok(tokens) => Ok(Some(tokens)),
+        Err(EthereumContractCallError::Revert(_)) => Ok(None),
+        Err(e) => Err(HostExportError::Deterministic(failed(&e))),
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct UnresolvedContractCall {
     pub contract_name: String,
@@ -223,3 +467,15 @@ pub struct UnresolvedContractCall {
 impl AscIndexId for AscUnresolvedContractCall {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::SmartContractCall;
 }
+
+#[test]
+fn namehash_matches_eip_137() {
+    assert_eq!(
+        hex::encode(namehash("eth")),
+        "93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae"
+    );
+    assert_eq!(
+        hex::encode(namehash("foo.eth")),
+        "de9b09fd7c5f901e23a3f19fecc54828e9c848539801e86591bd9801b019f84f"
+    );
+}
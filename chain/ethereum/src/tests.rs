@@ -1,15 +1,17 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use graph::{
     blockchain::{block_stream::BlockWithTriggers, BlockPtr},
     prelude::{
-        web3::types::{Address, Bytes, Log, H160, H256, U64},
+        web3::types::{Address, Bytes, Log, TransactionReceipt, H160, H2048, H256, U64},
         EthereumCall,
     },
 };
 
 use crate::{
     chain::BlockFinality,
+    ethereum_adapter::{attach_receipts, log_receipts_to_fetch},
     trigger::{EthereumBlockTriggerType, EthereumTrigger},
 };
 
@@ -60,15 +62,15 @@ fn test_trigger_ordering() {
 
     // Event with transaction_index 1 and log_index 0;
     // should be the first element after sorting
-    let log1 = EthereumTrigger::Log(create_log(1, 0));
+    let log1 = EthereumTrigger::Log(create_log(1, 0), None);
 
     // Event with transaction_index 1 and log_index 1;
     // should be the second element after sorting
-    let log2 = EthereumTrigger::Log(create_log(1, 1));
+    let log2 = EthereumTrigger::Log(create_log(1, 1), None);
 
     // Event with transaction_index 2 and log_index 5;
     // should come after call1 and before call2 after sorting
-    let log3 = EthereumTrigger::Log(create_log(2, 5));
+    let log3 = EthereumTrigger::Log(create_log(2, 5), None);
 
     let triggers = vec![
         // Call triggers; these should be in the order 1, 2, 4, 3 after sorting
@@ -95,3 +97,95 @@ fn test_trigger_ordering() {
         vec![log1, log2, call1, log3, call2, call4, call3, block2, block1]
     );
 }
+
+#[test]
+fn log_receipts_are_fetched_once_per_transaction() {
+    fn log(block: u64, tx: u64) -> EthereumTrigger {
+        EthereumTrigger::Log(
+            Arc::new(Log {
+                address: H160::default(),
+                topics: vec![],
+                data: Bytes::default(),
+                block_hash: Some(H256::from_low_u64_be(block)),
+                block_number: Some(U64::from(block)),
+                transaction_hash: Some(H256::from_low_u64_be(tx)),
+                transaction_index: Some(U64::zero()),
+                log_index: Some(0.into()),
+                transaction_log_index: Some(0.into()),
+                log_type: None,
+                removed: Some(false),
+            }),
+            None,
+        )
+    }
+
+    let receipt = |tx: u64| {
+        Arc::new(TransactionReceipt {
+            transaction_hash: H256::from_low_u64_be(tx),
+            transaction_index: U64::zero(),
+            block_hash: None,
+            block_number: None,
+            cumulative_gas_used: 0.into(),
+            gas_used: None,
+            contract_address: None,
+            logs: vec![],
+            status: None,
+            root: None,
+            logs_bloom: H2048::default(),
+        })
+    };
+
+    let mut with_receipt = log(1, 12);
+    if let EthereumTrigger::Log(_, r) = &mut with_receipt {
+        *r = Some(receipt(12));
+    }
+    let triggers = vec![
+        log(1, 10),
+        log(1, 10),
+        log(1, 11),
+        log(2, 20),
+        with_receipt,
+        EthereumTrigger::Block(
+            BlockPtr::from((H256::from_low_u64_be(1), 1u64)),
+            EthereumBlockTriggerType::Every,
+        ),
+    ];
+
+    // Logs of the same transaction need only one receipt, and logs that
+    // already have their receipt need none
+    let to_fetch = log_receipts_to_fetch(&triggers);
+    let expected = vec![
+        (H256::from_low_u64_be(1), vec![10, 11]),
+        (H256::from_low_u64_be(2), vec![20]),
+    ];
+    let actual: Vec<_> = to_fetch
+        .into_iter()
+        .map(|(block, txs)| {
+            (
+                block,
+                txs.into_iter()
+                    .map(|tx| tx.to_low_u64_be())
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect();
+    assert_eq!(expected, actual);
+
+    let receipts: HashMap<H256, Arc<TransactionReceipt>> = vec![10, 11, 20]
+        .into_iter()
+        .map(|tx| (H256::from_low_u64_be(tx), receipt(tx)))
+        .collect();
+    let attached: Vec<Option<u64>> = attach_receipts(triggers, &receipts)
+        .into_iter()
+        .map(|trigger| match trigger {
+            EthereumTrigger::Log(_, receipt) => {
+                Some(receipt.unwrap().transaction_hash.to_low_u64_be())
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        vec![Some(10), Some(10), Some(11), Some(20), Some(12), None],
+        attached
+    );
+}
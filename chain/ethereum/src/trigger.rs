@@ -1,5 +1,7 @@
 use graph::blockchain;
 use graph::blockchain::TriggerData;
+use graph::components::ethereum::evaluate_transaction_status;
+use graph::data::subgraph::API_VERSION_0_0_12;
 use graph::prelude::ethabi::ethereum_types::H160;
 use graph::prelude::ethabi::ethereum_types::H256;
 use graph::prelude::ethabi::ethereum_types::U128;
@@ -8,9 +10,11 @@ use graph::prelude::ethabi::ethereum_types::U64;
 use graph::prelude::ethabi::Address;
 use graph::prelude::ethabi::Bytes;
 use graph::prelude::ethabi::LogParam;
+use graph::prelude::tiny_keccak;
 use graph::prelude::web3::types::Block;
 use graph::prelude::web3::types::Log;
 use graph::prelude::web3::types::Transaction;
+use graph::prelude::web3::types::TransactionReceipt;
 use graph::prelude::BlockNumber;
 use graph::prelude::BlockPtr;
 use graph::prelude::{CheapClone, EthereumCall};
@@ -27,7 +31,9 @@ use crate::runtime::abi::AscEthereumBlock;
 use crate::runtime::abi::AscEthereumBlock_0_0_6;
 use crate::runtime::abi::AscEthereumCall;
 use crate::runtime::abi::AscEthereumCall_0_0_3;
+use crate::runtime::abi::AscEthereumContractCreation;
 use crate::runtime::abi::AscEthereumEvent;
+use crate::runtime::abi::AscEthereumEvent_0_0_12;
 use crate::runtime::abi::AscEthereumTransaction_0_0_1;
 use crate::runtime::abi::AscEthereumTransaction_0_0_2;
 use crate::runtime::abi::AscEthereumTransaction_0_0_6;
@@ -41,6 +47,7 @@ pub enum MappingTrigger {
         transaction: Arc<Transaction>,
         log: Arc<Log>,
         params: Vec<LogParam>,
+        receipt: Option<Arc<TransactionReceipt>>,
     },
     Call {
         block: Arc<LightEthereumBlock>,
@@ -52,6 +59,11 @@ pub enum MappingTrigger {
     Block {
         block: Arc<LightEthereumBlock>,
     },
+    Creation {
+        block: Arc<LightEthereumBlock>,
+        transaction: Arc<Transaction>,
+        creation: Arc<EthereumContractCreation>,
+    },
 }
 
 // Logging the block is too verbose, so this strips the block from the trigger for Debug.
@@ -63,6 +75,7 @@ impl std::fmt::Debug for MappingTrigger {
                 _transaction: Arc<Transaction>,
                 _log: Arc<Log>,
                 _params: Vec<LogParam>,
+                _receipt: Option<Arc<TransactionReceipt>>,
             },
             Call {
                 _transaction: Arc<Transaction>,
@@ -71,6 +84,10 @@ impl std::fmt::Debug for MappingTrigger {
                 _outputs: Vec<LogParam>,
             },
             Block,
+            Creation {
+                _transaction: Arc<Transaction>,
+                _creation: Arc<EthereumContractCreation>,
+            },
         }
 
         let trigger_without_block = match self {
@@ -79,10 +96,12 @@ impl std::fmt::Debug for MappingTrigger {
                 transaction,
                 log,
                 params,
+                receipt,
             } => MappingTriggerWithoutBlock::Log {
                 _transaction: transaction.cheap_clone(),
                 _log: log.cheap_clone(),
                 _params: params.clone(),
+                _receipt: receipt.cheap_clone(),
             },
             MappingTrigger::Call {
                 block: _,
@@ -97,6 +116,14 @@ impl std::fmt::Debug for MappingTrigger {
                 _outputs: outputs.clone(),
             },
             MappingTrigger::Block { block: _ } => MappingTriggerWithoutBlock::Block,
+            MappingTrigger::Creation {
+                block: _,
+                transaction,
+                creation,
+            } => MappingTriggerWithoutBlock::Creation {
+                _transaction: transaction.cheap_clone(),
+                _creation: creation.cheap_clone(),
+            },
         };
 
         write!(f, "{:?}", trigger_without_block)
@@ -111,6 +138,7 @@ impl blockchain::MappingTrigger for MappingTrigger {
                 transaction,
                 log,
                 params,
+                receipt,
             } => {
                 let ethereum_event_data = EthereumEventData {
                     block: EthereumBlockData::from(block.as_ref()),
@@ -120,9 +148,20 @@ impl blockchain::MappingTrigger for MappingTrigger {
                     transaction_log_index: log.log_index.unwrap_or(U256::zero()),
                     log_type: log.log_type.clone(),
                     params,
+                    receipt,
                 };
                 let api_version = heap.api_version();
-                if api_version >= Version::new(0, 0, 6) {
+                if api_version >= API_VERSION_0_0_12 {
+                    asc_new::<
+                        AscEthereumEvent_0_0_12<
+                            AscEthereumTransaction_0_0_6,
+                            AscEthereumBlock_0_0_6,
+                        >,
+                        _,
+                        _,
+                    >(heap, &ethereum_event_data)?
+                    .erase()
+                } else if api_version >= Version::new(0, 0, 6) {
                     asc_new::<
                         AscEthereumEvent<AscEthereumTransaction_0_0_6, AscEthereumBlock_0_0_6>,
                         _,
@@ -184,6 +223,20 @@ impl blockchain::MappingTrigger for MappingTrigger {
                     asc_new::<AscEthereumBlock, _, _>(heap, &block)?.erase()
                 }
             }
+            MappingTrigger::Creation {
+                block,
+                transaction,
+                creation,
+            } => {
+                let creation = EthereumContractCreationData {
+                    address: creation.address,
+                    from: creation.from,
+                    init_code: creation.init_code.clone(),
+                    block: EthereumBlockData::from(block.as_ref()),
+                    transaction: EthereumTransactionData::from(transaction.deref()),
+                };
+                asc_new::<AscEthereumContractCreation, _, _>(heap, &creation)?.erase()
+            }
         })
     }
 }
@@ -192,7 +245,10 @@ impl blockchain::MappingTrigger for MappingTrigger {
 pub enum EthereumTrigger {
     Block(BlockPtr, EthereumBlockTriggerType),
     Call(Arc<EthereumCall>),
-    Log(Arc<Log>),
+    /// An event, together with the receipt of its transaction if a data
+    /// source asked for it
+    Log(Arc<Log>, Option<Arc<TransactionReceipt>>),
+    Creation(Arc<EthereumContractCreation>),
 }
 
 impl PartialEq for EthereumTrigger {
@@ -204,10 +260,12 @@ impl PartialEq for EthereumTrigger {
 
             (Self::Call(a), Self::Call(b)) => a == b,
 
-            (Self::Log(a), Self::Log(b)) => {
+            (Self::Log(a, _), Self::Log(b, _)) => {
                 a.transaction_hash == b.transaction_hash && a.log_index == b.log_index
             }
 
+            (Self::Creation(a), Self::Creation(b)) => a == b,
+
             _ => false,
         }
     }
@@ -226,7 +284,10 @@ impl EthereumTrigger {
         match self {
             EthereumTrigger::Block(block_ptr, _) => block_ptr.number,
             EthereumTrigger::Call(call) => call.block_number,
-            EthereumTrigger::Log(log) => i32::try_from(log.block_number.unwrap().as_u64()).unwrap(),
+            EthereumTrigger::Log(log, _) => {
+                i32::try_from(log.block_number.unwrap().as_u64()).unwrap()
+            }
+            EthereumTrigger::Creation(creation) => creation.block_number,
         }
     }
 
@@ -234,7 +295,19 @@ impl EthereumTrigger {
         match self {
             EthereumTrigger::Block(block_ptr, _) => block_ptr.hash_as_h256(),
             EthereumTrigger::Call(call) => call.block_hash,
-            EthereumTrigger::Log(log) => log.block_hash.unwrap(),
+            EthereumTrigger::Log(log, _) => log.block_hash.unwrap(),
+            EthereumTrigger::Creation(creation) => creation.block_hash,
+        }
+    }
+
+    /// The index of the transaction that caused this trigger, or `None`
+    /// for block triggers
+    fn transaction_index(&self) -> Option<u64> {
+        match self {
+            EthereumTrigger::Block(..) => None,
+            EthereumTrigger::Call(call) => Some(call.transaction_index),
+            EthereumTrigger::Log(log, _) => Some(log.transaction_index.unwrap().as_u64()),
+            EthereumTrigger::Creation(creation) => Some(creation.transaction_index),
         }
     }
 }
@@ -253,24 +326,40 @@ impl Ord for EthereumTrigger {
             (Self::Call(a), Self::Call(b)) => a.transaction_index.cmp(&b.transaction_index),
 
             // Events are ordered by their log index
-            (Self::Log(a), Self::Log(b)) => a.log_index.cmp(&b.log_index),
+            (Self::Log(a, _), Self::Log(b, _)) => a.log_index.cmp(&b.log_index),
+
+            // A contract creation comes before anything else that happens
+            // in the same transaction
+            (Self::Creation(a), other)
+                if Some(a.transaction_index) == other.transaction_index() =>
+            {
+                Ordering::Less
+            }
+            (other, Self::Creation(b))
+                if other.transaction_index() == Some(b.transaction_index) =>
+            {
+                Ordering::Greater
+            }
+            (Self::Creation(_), _) | (_, Self::Creation(_)) => {
+                self.transaction_index().cmp(&other.transaction_index())
+            }
 
             // Calls vs. events are logged by their tx index;
             // if they are from the same transaction, events come first
-            (Self::Call(a), Self::Log(b))
+            (Self::Call(a), Self::Log(b, _))
                 if a.transaction_index == b.transaction_index.unwrap().as_u64() =>
             {
                 Ordering::Greater
             }
-            (Self::Log(a), Self::Call(b))
+            (Self::Log(a, _), Self::Call(b))
                 if a.transaction_index.unwrap().as_u64() == b.transaction_index =>
             {
                 Ordering::Less
             }
-            (Self::Call(a), Self::Log(b)) => a
+            (Self::Call(a), Self::Log(b, _)) => a
                 .transaction_index
                 .cmp(&b.transaction_index.unwrap().as_u64()),
-            (Self::Log(a), Self::Call(b)) => a
+            (Self::Log(a, _), Self::Call(b)) => a
                 .transaction_index
                 .unwrap()
                 .as_u64()
@@ -288,8 +377,9 @@ impl PartialOrd for EthereumTrigger {
 impl TriggerData for EthereumTrigger {
     fn error_context(&self) -> std::string::String {
         let transaction_id = match self {
-            EthereumTrigger::Log(log) => log.transaction_hash,
+            EthereumTrigger::Log(log, _) => log.transaction_hash,
             EthereumTrigger::Call(call) => call.transaction_hash,
+            EthereumTrigger::Creation(creation) => Some(creation.transaction_hash),
             EthereumTrigger::Block(..) => None,
         };
 
@@ -390,6 +480,7 @@ pub struct EthereumEventData {
     pub block: EthereumBlockData,
     pub transaction: EthereumTransactionData,
     pub params: Vec<LogParam>,
+    pub receipt: Option<Arc<TransactionReceipt>>,
 }
 
 impl Clone for EthereumEventData {
@@ -409,6 +500,7 @@ impl Clone for EthereumEventData {
                     value: log_param.value.clone(),
                 })
                 .collect(),
+            receipt: self.receipt.cheap_clone(),
         }
     }
 }
@@ -450,3 +542,58 @@ impl Clone for EthereumCallData {
         }
     }
 }
+
+/// A contract that was deployed by a contract creation transaction, i.e.,
+/// a transaction without a `to` address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EthereumContractCreation {
+    pub block_number: BlockNumber,
+    pub block_hash: H256,
+    pub transaction_hash: H256,
+    pub transaction_index: u64,
+    /// The account that deployed the contract
+    pub from: Address,
+    /// The address of the new contract
+    pub address: Address,
+    /// The init code that the creation transaction ran
+    pub init_code: Bytes,
+}
+
+impl EthereumContractCreation {
+    /// The contract created by `transaction` if it is a contract creation
+    /// transaction; `receipt` must be the receipt for `transaction`.
+    /// Returns `None` if the transaction is not a contract creation or if
+    /// it failed
+    pub fn from_transaction(
+        transaction: &Transaction,
+        receipt: &TransactionReceipt,
+    ) -> Option<Self> {
+        if transaction.to.is_some() || !evaluate_transaction_status(receipt.status) {
+            return None;
+        }
+        Some(EthereumContractCreation {
+            block_number: BlockNumber::try_from(transaction.block_number?.as_u64()).ok()?,
+            block_hash: transaction.block_hash?,
+            transaction_hash: transaction.hash,
+            transaction_index: transaction.transaction_index?.as_u64(),
+            from: transaction.from?,
+            address: receipt.contract_address?,
+            init_code: transaction.input.0.clone(),
+        })
+    }
+
+    /// The keccak256 hash of the init code
+    pub fn code_hash(&self) -> H256 {
+        H256::from(tiny_keccak::keccak256(&self.init_code))
+    }
+}
+
+/// A contract creation as it is passed to mappings
+#[derive(Debug)]
+pub struct EthereumContractCreationData {
+    pub address: Address,
+    pub from: Address,
+    pub init_code: Bytes,
+    pub block: EthereumBlockData,
+    pub transaction: EthereumTransactionData,
+}
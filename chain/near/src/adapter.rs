@@ -1,22 +1,65 @@
+use std::collections::HashSet;
+
 use crate::capabilities::NodeCapabilities;
+use crate::transform::BasicReceiptFilter;
 use crate::{data_source::DataSource, Chain};
 use graph::blockchain as bc;
 use graph::prelude::*;
+use prost::Message;
 
 #[derive(Clone, Debug, Default)]
 pub struct TriggerFilter {
     pub(crate) block: NearBlockFilter,
+    pub(crate) receipt: NearReceiptFilter,
 }
 
 impl bc::TriggerFilter<Chain> for TriggerFilter {
     fn extend<'a>(&mut self, data_sources: impl Iterator<Item = &'a DataSource> + Clone) {
         self.block
-            .extend(NearBlockFilter::from_data_sources(data_sources));
+            .extend(NearBlockFilter::from_data_sources(data_sources.clone()));
+        self.receipt
+            .extend(NearReceiptFilter::from_data_sources(data_sources));
     }
 
     fn node_capabilities(&self) -> NodeCapabilities {
         NodeCapabilities {}
     }
+
+    fn to_firehose_filter(&self) -> Vec<prost_types::Any> {
+        // Block handlers need to see all receipts
+        if self.block.trigger_every_block || self.receipt.accounts.is_empty() {
+            return vec![];
+        }
+
+        let mut accounts: Vec<_> = self.receipt.accounts.iter().cloned().collect();
+        accounts.sort();
+        let filter = BasicReceiptFilter { accounts };
+        vec![prost_types::Any {
+            type_url: "type.googleapis.com/sf.near.transform.v1.BasicReceiptFilter".to_string(),
+            value: filter.encode_to_vec(),
+        }]
+    }
+}
+
+/// The accounts whose receipts data sources have receipt handlers for
+#[derive(Clone, Debug, Default)]
+pub(crate) struct NearReceiptFilter {
+    pub accounts: HashSet<String>,
+}
+
+impl NearReceiptFilter {
+    pub fn from_data_sources<'a>(iter: impl IntoIterator<Item = &'a DataSource>) -> Self {
+        let accounts = iter
+            .into_iter()
+            .filter(|data_source| !data_source.mapping.receipt_handlers.is_empty())
+            .filter_map(|data_source| data_source.source.account.clone())
+            .collect();
+        NearReceiptFilter { accounts }
+    }
+
+    pub fn extend(&mut self, other: NearReceiptFilter) {
+        self.accounts.extend(other.accounts);
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -27,7 +70,7 @@ pub(crate) struct NearBlockFilter {
 impl NearBlockFilter {
     pub fn from_data_sources<'a>(iter: impl IntoIterator<Item = &'a DataSource>) -> Self {
         iter.into_iter()
-            .filter(|data_source| data_source.source.account.is_some())
+            .filter(|data_source| !data_source.mapping.block_handlers.is_empty())
             .fold(Self::default(), |mut filter_opt, _data_source| {
                 filter_opt.extend(Self {
                     trigger_every_block: true,
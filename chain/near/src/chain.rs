@@ -8,14 +8,17 @@ use graph::{
     anyhow,
     blockchain::{
         block_stream::{
-            BlockStreamEvent, BlockStreamMetrics, BlockWithTriggers, FirehoseError,
-            FirehoseMapper as FirehoseMapperTrait, TriggersAdapter as TriggersAdapterTrait,
+            BlockStreamMetrics, BlockWithTriggers, FirehoseError,
+            TriggersAdapter as TriggersAdapterTrait,
         },
         firehose_block_stream::FirehoseBlockStream,
-        BlockHash, BlockPtr, Blockchain, IngestorError,
+        firehose_mapper::{
+            FirehoseBlockDecoder as FirehoseBlockDecoderTrait, GenericFirehoseMapper,
+        },
+        firehose_multiplexer::FirehoseMultiplexer,
+        BlockHash, BlockPtr, Blockchain, IngestorError, TriggerFilter as _,
     },
     components::store::DeploymentLocator,
-    firehose::{self as firehose, ForkStep},
     prelude::{async_trait, o, BlockNumber, ChainStore, Error, Logger, LoggerFactory},
 };
 use prost::Message;
@@ -36,6 +39,7 @@ pub struct Chain {
     logger_factory: LoggerFactory,
     name: String,
     firehose_endpoints: Arc<FirehoseEndpoints>,
+    firehose_multiplexer: FirehoseMultiplexer<Chain>,
     chain_store: Arc<dyn ChainStore>,
 }
 
@@ -52,10 +56,14 @@ impl Chain {
         chain_store: Arc<dyn ChainStore>,
         firehose_endpoints: FirehoseEndpoints,
     ) -> Self {
+        let firehose_multiplexer =
+            FirehoseMultiplexer::new(logger_factory.component_logger("FirehoseMultiplexer", None));
+
         Chain {
             logger_factory,
             name,
             firehose_endpoints: Arc::new(firehose_endpoints),
+            firehose_multiplexer,
             chain_store,
         }
     }
@@ -106,6 +114,7 @@ impl Blockchain for Chain {
         filter: Arc<Self::TriggerFilter>,
         metrics: Arc<BlockStreamMetrics>,
         unified_api_version: UnifiedMappingApiVersion,
+        firehose_provider: Option<String>,
     ) -> Result<Box<dyn BlockStream<Self>>, Error> {
         let adapter = self
             .triggers_adapter(
@@ -116,27 +125,34 @@ impl Blockchain for Chain {
             )
             .expect(&format!("no adapter for network {}", self.name,));
 
-        let firehose_endpoint = match self.firehose_endpoints.random() {
-            Some(e) => e.clone(),
-            None => return Err(anyhow::format_err!("no firehose endpoint available",)),
-        };
+        let firehose_endpoint = self
+            .firehose_endpoints
+            .select(firehose_provider.as_deref())?;
 
         let logger = self
             .logger_factory
             .subgraph_logger(&deployment)
             .new(o!("component" => "FirehoseBlockStream"));
 
-        let firehose_mapper = Arc::new(FirehoseMapper {});
+        let firehose_mapper = Arc::new(GenericFirehoseMapper::new(FirehoseBlockDecoder {}));
         let firehose_cursor = store.block_cursor();
+        let upstream = self.firehose_multiplexer.subscribe(
+            &firehose_endpoint,
+            filter.to_firehose_filter(),
+            firehose_mapper.clone(),
+        );
 
         Ok(Box::new(FirehoseBlockStream::new(
             firehose_endpoint,
+            upstream,
             firehose_cursor,
+            store.block_ptr(),
             firehose_mapper,
             adapter,
             filter,
             start_blocks,
             logger,
+            metrics,
         )))
     }
 
@@ -148,6 +164,7 @@ impl Blockchain for Chain {
         _filter: Arc<Self::TriggerFilter>,
         _metrics: Arc<BlockStreamMetrics>,
         _unified_api_version: UnifiedMappingApiVersion,
+        _checkpoint_interval: Option<BlockNumber>,
     ) -> Result<Box<dyn BlockStream<Self>>, Error> {
         panic!("NEAR does not support polling block stream")
     }
@@ -263,65 +280,12 @@ impl TriggersAdapterTrait<Chain> for TriggersAdapter {
     }
 }
 
-pub struct FirehoseMapper {}
+pub struct FirehoseBlockDecoder {}
 
-#[async_trait]
-impl FirehoseMapperTrait<Chain> for FirehoseMapper {
-    async fn to_block_stream_event(
-        &self,
-        logger: &Logger,
-        response: &firehose::Response,
-        adapter: &TriggersAdapter,
-        filter: &TriggerFilter,
-    ) -> Result<BlockStreamEvent<Chain>, FirehoseError> {
-        let step = ForkStep::from_i32(response.step).unwrap_or_else(|| {
-            panic!(
-                "unknown step i32 value {}, maybe you forgot update & re-regenerate the protobuf definitions?",
-                response.step
-            )
-        });
+impl FirehoseBlockDecoderTrait<Chain> for FirehoseBlockDecoder {
+    type HeaderOnlyBlock = codec::HeaderOnlyBlock;
 
-        let any_block = response
-            .block
-            .as_ref()
-            .expect("block payload information should always be present");
-
-        // Right now, this is done in all cases but in reality, with how the BlockStreamEvent::Revert
-        // is defined right now, only block hash and block number is necessary. However, this information
-        // is not part of the actual bstream::BlockResponseV2 payload. As such, we need to decode the full
-        // block which is useless.
-        //
-        // Check about adding basic information about the block in the bstream::BlockResponseV2 or maybe
-        // define a slimmed down stuct that would decode only a few fields and ignore all the rest.
-        let block = codec::Block::decode(any_block.value.as_ref())?;
-
-        use ForkStep::*;
-        match step {
-            StepNew => Ok(BlockStreamEvent::ProcessBlock(
-                adapter.triggers_in_block(logger, block, filter).await?,
-                Some(response.cursor.clone()),
-            )),
-
-            StepUndo => {
-                let parent_ptr = block
-                    .header()
-                    .parent_ptr()
-                    .expect("Genesis block should never be reverted");
-
-                Ok(BlockStreamEvent::Revert(
-                    block.ptr(),
-                    parent_ptr,
-                    Some(response.cursor.clone()),
-                ))
-            }
-
-            StepIrreversible => {
-                panic!("irreversible step is not handled and should not be requested in the Firehose request")
-            }
-
-            StepUnknown => {
-                panic!("unknown step should not happen in the Firehose response")
-            }
-        }
+    fn decode_block(&self, payload: &[u8]) -> Result<codec::Block, FirehoseError> {
+        Ok(codec::Block::decode(payload)?)
     }
 }
@@ -6,6 +6,7 @@ use graph::{
     blockchain::BlockPtr,
     prelude::{hex, web3::types::H256, BlockNumber},
 };
+use prost::Message;
 use std::convert::TryFrom;
 use std::fmt::LowerHex;
 
@@ -70,6 +71,14 @@ impl BlockchainBlock for Block {
     fn parent_ptr(&self) -> Option<BlockPtr> {
         self.parent_ptr()
     }
+
+    fn timestamp(&self) -> Option<u64> {
+        Some(self.header().timestamp_nanosec / 1_000_000_000)
+    }
+
+    fn data_bytes(&self) -> Option<Vec<u8>> {
+        Some(self.encode_to_vec())
+    }
 }
 
 impl HeaderOnlyBlock {
@@ -96,6 +105,10 @@ impl BlockchainBlock for HeaderOnlyBlock {
     fn parent_ptr(&self) -> Option<BlockPtr> {
         self.header().parent_ptr()
     }
+
+    fn data_bytes(&self) -> Option<Vec<u8>> {
+        Some(self.encode_to_vec())
+    }
 }
 
 impl execution_outcome::Status {
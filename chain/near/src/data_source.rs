@@ -77,6 +77,17 @@ impl blockchain::DataSource<Chain> for DataSource {
         )))
     }
 
+    fn scheduled_handler_trigger(
+        &self,
+        handler: &str,
+        block: Arc<<Chain as Blockchain>::Block>,
+    ) -> Result<TriggerWithHandler<Chain>, Error> {
+        Ok(TriggerWithHandler::new(
+            NearTrigger::Block(block),
+            handler.to_owned(),
+        ))
+    }
+
     fn name(&self) -> &str {
         &self.name
     }
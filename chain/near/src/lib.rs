@@ -6,6 +6,9 @@ mod data_source;
 mod runtime;
 mod trigger;
 
+#[path = "protobuf/sf.near.transform.v1.rs"]
+mod transform;
+
 pub use crate::chain::Chain;
 pub use codec::Block;
 pub use codec::HeaderOnlyBlock;
@@ -0,0 +1,7 @@
+/// BasicReceiptFilter strips all receipts from the block that were not
+/// executed by one of `accounts`
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BasicReceiptFilter {
+    #[prost(string, repeated, tag = "1")]
+    pub accounts: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
@@ -436,6 +436,20 @@ mod tests {
             Ok((self.memory.len() - bytes.len()) as u32)
         }
 
+        fn raw_write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), DeterministicHostError> {
+            let start = offset as usize;
+            match self.memory.get_mut(start..start + bytes.len()) {
+                Some(memory) => {
+                    memory.copy_from_slice(bytes);
+                    Ok(())
+                }
+                None => Err(DeterministicHostError::from(anyhow!(
+                    "Write at offset {} is outside of allocated memory",
+                    offset
+                ))),
+            }
+        }
+
         fn get(&self, offset: u32, size: u32) -> Result<Vec<u8>, DeterministicHostError> {
             let memory_byte_count = self.memory.len();
             if memory_byte_count == 0 {
@@ -219,39 +219,95 @@ impl LinkResolverTrait for LinkResolver {
         let max_file_size: Option<u64> = read_u64_from_env(MAX_IPFS_FILE_SIZE_VAR);
         restrict_file_size(&path, &stat, &max_file_size)?;
 
-        let path = path.clone();
-        let this = self.clone();
+        // The stat is only an estimate of the file size; read the file in
+        // chunks so that we never hold more than one chunk beyond the limit
+        // in memory
+        let limit = max_file_size.unwrap_or(u64::MAX);
         let timeout = self.timeout;
-        let logger = logger.clone();
-        let data = retry_policy(self.retry, "ipfs.cat", &logger)
-            .run(move || {
-                let path = path.clone();
-                let client = client.clone();
-                let this = this.clone();
-                let logger = logger.clone();
-                async move {
-                    let data = client.cat_all(path.clone(), timeout).await?.to_vec();
-
-                    // Only cache files if they are not too large
-                    if data.len() <= *MAX_IPFS_CACHE_FILE_SIZE as usize {
-                        let mut cache = this.cache.lock().unwrap();
-                        if !cache.contains_key(&path) {
-                            cache.insert(path.to_owned(), data.clone());
+        let data = {
+            let path = path.clone();
+            retry_policy(self.retry, "ipfs.cat", logger)
+                .run(move || {
+                    let path = path.clone();
+                    let client = client.clone();
+                    async move {
+                        let mut stream = client.cat(path, Some(timeout)).await?.boxed();
+                        let mut data = Vec::new();
+                        while let Some(chunk) = stream.next().await {
+                            data.extend_from_slice(&chunk?);
+                            if data.len() as u64 > limit {
+                                break;
+                            }
                         }
-                    } else {
-                        debug!(logger, "File too large for cache";
-                                    "path" => path,
-                                    "size" => data.len()
-                        );
+                        Result::<Vec<u8>, reqwest::Error>::Ok(data)
                     }
-                    Result::<Vec<u8>, reqwest::Error>::Ok(data)
-                }
-            })
-            .await?;
+                })
+                .await?
+        };
+        if data.len() as u64 > limit {
+            return Err(anyhow!(
+                "IPFS file {} is too large. It can be at most {} bytes",
+                path,
+                limit
+            ));
+        }
+
+        // Only cache files if they are not too large
+        if data.len() <= *MAX_IPFS_CACHE_FILE_SIZE as usize {
+            let mut cache = self.cache.lock().unwrap();
+            if !cache.contains_key(&path) {
+                cache.insert(path.to_owned(), data.clone());
+            }
+        } else {
+            debug!(logger, "File too large for cache";
+                        "path" => path,
+                        "size" => data.len()
+            );
+        }
 
         Ok(data)
     }
 
+    /// Supports links of the form `/ipfs/ipfs_hash` or just `ipfs_hash`.
+    async fn get_block(&self, logger: &Logger, link: &Link) -> Result<Vec<u8>, Error> {
+        // Discard the `/ipfs/` prefix (if present) to get the hash.
+        let path = link.link.trim_start_matches("/ipfs/").to_owned();
+        trace!(logger, "IPFS block get"; "hash" => &path);
+
+        // `object.stat` does not work for all kinds of blocks, ask all
+        // clients for the block and use whichever answers first. Blocks
+        // are small enough that this does not waste much bandwidth
+        let timeout = self.timeout;
+        let mut blocks: FuturesUnordered<_> = self
+            .clients
+            .iter()
+            .map(|client| {
+                let client = client.cheap_clone();
+                let path = path.clone();
+                retry_policy(self.retry, "ipfs.getBlock", logger).run(move || {
+                    let client = client.cheap_clone();
+                    let path = path.clone();
+                    async move { client.get_block(path, timeout).await }
+                })
+            })
+            .collect();
+
+        let mut err: Option<Error> = None;
+        while let Some(result) = blocks.next().await {
+            match result {
+                Ok(block) => return Ok(block.to_vec()),
+                Err(e) => err = Some(e.into()),
+            }
+        }
+
+        Err(err.unwrap_or_else(|| {
+            anyhow!(
+                "No IPFS clients were supplied to handle the call to block.get. Block: {}",
+                path
+            )
+        }))
+    }
+
     async fn json_stream(&self, logger: &Logger, link: &Link) -> Result<JsonValueStream, Error> {
         // Discard the `/ipfs/` prefix (if present) to get the hash.
         let path = link.link.trim_start_matches("/ipfs/");
@@ -269,7 +325,12 @@ impl LinkResolverTrait for LinkResolver {
             read_u64_from_env(MAX_IPFS_MAP_FILE_SIZE_VAR).or(Some(DEFAULT_MAX_IPFS_MAP_FILE_SIZE));
         restrict_file_size(path, &stat, &max_file_size)?;
 
-        let mut stream = client.cat(path.to_string()).await?.fuse().boxed().compat();
+        let mut stream = client
+            .cat(path.to_string(), None)
+            .await?
+            .fuse()
+            .boxed()
+            .compat();
 
         let mut buf = BytesMut::with_capacity(1024);
 
@@ -8,6 +8,7 @@ use std::str::FromStr;
 use graph::{blockchain::DataSource, prelude::*};
 use graph::{
     blockchain::{Block, Blockchain},
+    components::store::{DataSourceContextUpdate, ScheduledBlockHandler},
     components::subgraph::{MappingError, SharedProofOfIndexing},
 };
 
@@ -87,6 +88,7 @@ where
             } else {
                 let sender = T::spawn_mapping(
                     module_bytes.to_owned(),
+                    data_source.api_version(),
                     logger,
                     self.subgraph_id.clone(),
                     host_metrics.clone(),
@@ -126,6 +128,25 @@ where
         .await
     }
 
+    /// The index of the first data source whose mapping handles `trigger`,
+    /// or `None` if no data source handles it
+    pub(crate) fn first_matching_host(
+        &self,
+        logger: &Logger,
+        block: &Arc<C::Block>,
+        trigger: &C::TriggerData,
+    ) -> Result<Option<usize>, MappingError> {
+        for (index, host) in self.hosts.iter().enumerate() {
+            if host
+                .match_and_decode(trigger, block.cheap_clone(), logger)?
+                .is_some()
+            {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
     pub(crate) async fn process_trigger_in_runtime_hosts(
         logger: &Logger,
         hosts: &[Arc<T::Host>],
@@ -179,6 +200,64 @@ where
         Ok(state)
     }
 
+    /// Run a block handler that a mapping scheduled for `block` with
+    /// `scheduler.atBlock`. The handler runs in the runtime host of the
+    /// data source that scheduled it
+    pub(crate) async fn process_scheduled_handler(
+        &self,
+        logger: &Logger,
+        block: &Arc<C::Block>,
+        handler: &ScheduledBlockHandler,
+        mut state: BlockState<C>,
+        proof_of_indexing: SharedProofOfIndexing,
+        causality_region: &str,
+    ) -> Result<BlockState<C>, MappingError> {
+        let mut found = None;
+        for host in &self.hosts {
+            if let Some(trigger) = host.match_scheduled_handler(handler, block.cheap_clone())? {
+                found = Some((host, trigger));
+                break;
+            }
+        }
+        let (host, trigger) = match found {
+            Some(found) => found,
+            None => {
+                warn!(logger, "No data source for scheduled block handler, skipping it";
+                    "data_source" => &handler.data_source,
+                    "handler" => &handler.handler);
+                return Ok(state);
+            }
+        };
+
+        let error_count = state.deterministic_errors.len();
+
+        if let Some(proof_of_indexing) = &proof_of_indexing {
+            proof_of_indexing
+                .borrow_mut()
+                .start_handler(causality_region);
+        }
+
+        state = host
+            .process_mapping_trigger(
+                logger,
+                block.ptr(),
+                trigger,
+                state,
+                proof_of_indexing.cheap_clone(),
+            )
+            .await?;
+
+        if let Some(proof_of_indexing) = &proof_of_indexing {
+            if state.deterministic_errors.len() != error_count {
+                proof_of_indexing
+                    .borrow_mut()
+                    .write_deterministic_error(&logger, causality_region);
+            }
+        }
+
+        Ok(state)
+    }
+
     pub(crate) fn add_dynamic_data_source(
         &mut self,
         logger: &Logger,
@@ -227,6 +306,24 @@ where
         }
     }
 
+    /// Make the hosts use the contexts that mappings stored with
+    /// `dataSource.setContext`. With `reset`, the hosts first go back to
+    /// the contexts their data sources were created with
+    pub(crate) fn update_data_source_contexts(
+        &self,
+        updates: &[DataSourceContextUpdate],
+        reset: bool,
+    ) {
+        for host in &self.hosts {
+            if reset {
+                host.reset_context();
+            }
+            for update in updates {
+                host.update_context(update);
+            }
+        }
+    }
+
     pub(crate) fn network(&self) -> &str {
         &self.network
     }
@@ -3,9 +3,12 @@ use super::SubgraphInstance;
 use atomic_refcell::AtomicRefCell;
 use fail::fail_point;
 use graph::blockchain::block_stream::{BlockStream, BufferedBlockStream};
+use graph::blockchain::polling_block_stream::CHECKPOINT_INTERVAL_LABEL;
+use graph::blockchain::replay_block_stream::{ReplayBlockStream, REPLAY_LABEL};
 use graph::blockchain::{BlockchainKind, DataSource};
 use graph::data::store::scalar::Bytes;
 use graph::data::subgraph::{UnifiedMappingApiVersion, MAX_SPEC_VERSION};
+use graph::firehose::FIREHOSE_PROVIDER_LABEL;
 use graph::prelude::{SubgraphInstanceManager as SubgraphInstanceManagerTrait, *};
 use graph::util::{backoff::ExponentialBackoff, lfu_cache::LfuCache};
 use graph::{blockchain::block_stream::BlockStreamMetrics, components::store::WritableStore};
@@ -21,10 +24,13 @@ use graph::{
 };
 use graph::{
     blockchain::{Block, BlockchainMap},
-    components::store::{DeploymentId, DeploymentLocator, ModificationsAndCache},
+    components::store::{
+        BlockTimestampLookup, DeploymentId, DeploymentLocator, MemoryUsage, ModificationsAndCache,
+        ScheduledBlockHandler,
+    },
 };
 use lazy_static::lazy_static;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 use tokio::task;
@@ -47,6 +53,11 @@ lazy_static! {
             .parse::<u64>()
             .map(Duration::from_secs)
             .expect("invalid GRAPH_SUBGRAPH_ERROR_RETRY_CEIL_SECS");
+
+    /// Process the triggers of different data sources in a block
+    /// concurrently, see `process_triggers_in_parallel`
+    static ref PARALLEL_TRIGGERS: bool =
+        std::env::var("GRAPH_EXPERIMENTAL_PARALLEL_TRIGGERS").is_ok();
 }
 
 type SharedInstanceKeepAliveMap = Arc<RwLock<HashMap<DeploymentId, CancelGuard>>>;
@@ -61,6 +72,14 @@ struct IndexingInputs<C: Blockchain> {
     chain: Arc<C>,
     templates: Arc<Vec<C::DataSourceTemplate>>,
     unified_api_version: UnifiedMappingApiVersion,
+    /// The Firehose provider selected through the deployment's labels
+    firehose_provider: Option<String>,
+    /// Whether to only replay blocks from the block cache, selected
+    /// through the deployment's labels
+    replay: bool,
+    /// How many blocks the subgraph pointer may at most fall behind while
+    /// no triggers are found, selected through the deployment's labels
+    checkpoint_interval: Option<BlockNumber>,
 }
 
 struct IndexingState<T: RuntimeHostBuilder<C>, C: Blockchain> {
@@ -69,6 +88,9 @@ struct IndexingState<T: RuntimeHostBuilder<C>, C: Blockchain> {
     instances: SharedInstanceKeepAliveMap,
     filter: C::TriggerFilter,
     entity_lfu_cache: LfuCache<EntityKey, Option<Entity>>,
+    /// The block handlers that mappings scheduled with
+    /// `scheduler.atBlock`, by the block at which they are due
+    scheduled_handlers: BTreeMap<BlockNumber, Vec<ScheduledBlockHandler>>,
 }
 
 struct IndexingContext<T: RuntimeHostBuilder<C>, C: Blockchain> {
@@ -87,6 +109,7 @@ struct IndexingContext<T: RuntimeHostBuilder<C>, C: Blockchain> {
 pub struct SubgraphInstanceManager<S, M, L> {
     logger_factory: LoggerFactory,
     subgraph_store: Arc<S>,
+    block_timestamps: Arc<dyn BlockTimestampLookup>,
     chains: Arc<BlockchainMap>,
     metrics_registry: Arc<M>,
     manager_metrics: SubgraphInstanceManagerMetrics,
@@ -117,6 +140,10 @@ struct SubgraphInstanceMetrics {
     pub block_ops_transaction_duration: Box<Histogram>,
 
     trigger_processing_duration: Box<Histogram>,
+
+    entity_cache_bytes: Box<Gauge>,
+    wasm_memory_bytes: Box<Gauge>,
+    buffered_triggers: Box<Gauge>,
 }
 
 impl SubgraphInstanceMetrics {
@@ -153,12 +180,36 @@ impl SubgraphInstanceMetrics {
                 vec![0.01, 0.05, 0.1, 0.3, 0.7, 2.0],
             )
             .expect("failed to create `deployment_transact_block_operations_duration_{}");
+        let entity_cache_bytes = registry
+            .new_deployment_gauge(
+                "deployment_entity_cache_bytes",
+                "Approximate size of the entity cache of a subgraph deployment",
+                subgraph_hash,
+            )
+            .expect("failed to create `deployment_entity_cache_bytes` gauge");
+        let wasm_memory_bytes = registry
+            .new_deployment_gauge(
+                "deployment_wasm_memory_bytes",
+                "Size of the Wasm memory of the mapping that handled the last trigger",
+                subgraph_hash,
+            )
+            .expect("failed to create `deployment_wasm_memory_bytes` gauge");
+        let buffered_triggers = registry
+            .new_deployment_gauge(
+                "deployment_buffered_triggers",
+                "Number of triggers in blocks buffered by the block stream of a subgraph deployment",
+                subgraph_hash,
+            )
+            .expect("failed to create `deployment_buffered_triggers` gauge");
 
         Self {
             block_trigger_count,
             block_processing_duration,
             trigger_processing_duration,
             block_ops_transaction_duration,
+            entity_cache_bytes,
+            wasm_memory_bytes,
+            buffered_triggers,
         }
     }
 
@@ -166,11 +217,21 @@ impl SubgraphInstanceMetrics {
         self.trigger_processing_duration.observe(duration);
     }
 
+    pub fn observe_memory_usage(&self, memory_usage: &MemoryUsage) {
+        let info = memory_usage.info();
+        self.entity_cache_bytes.set(info.entity_cache_bytes as f64);
+        self.wasm_memory_bytes.set(info.wasm_memory_bytes as f64);
+        self.buffered_triggers.set(info.buffered_triggers as f64);
+    }
+
     pub fn unregister<M: MetricsRegistry>(&self, registry: Arc<M>) {
         registry.unregister(self.block_processing_duration.clone());
         registry.unregister(self.block_trigger_count.clone());
         registry.unregister(self.trigger_processing_duration.clone());
         registry.unregister(self.block_ops_transaction_duration.clone());
+        registry.unregister(self.entity_cache_bytes.clone());
+        registry.unregister(self.wasm_memory_bytes.clone());
+        registry.unregister(self.buffered_triggers.clone());
     }
 }
 
@@ -228,13 +289,18 @@ where
         });
     }
 
-    fn stop_subgraph(&self, loc: DeploymentLocator) {
+    async fn stop_subgraph(&self, loc: DeploymentLocator) {
         let logger = self.logger_factory.subgraph_logger(&loc);
         info!(logger, "Stop subgraph");
 
         // Drop the cancel guard to shut down the subgraph now
-        let mut instances = self.instances.write().unwrap();
-        instances.remove(&loc.id);
+        self.instances.write().unwrap().remove(&loc.id);
+
+        // Write the blocks that are still batched in the store so that the
+        // subgraph resumes after them when it is started again
+        if let Err(e) = self.subgraph_store.stop_subgraph(&loc).await {
+            error!(logger, "Writing pending changes of stopped subgraph failed"; "error" => e.to_string());
+        }
 
         self.manager_metrics.subgraph_count.dec();
     }
@@ -249,6 +315,7 @@ where
     pub fn new(
         logger_factory: &LoggerFactory,
         subgraph_store: Arc<S>,
+        block_timestamps: Arc<dyn BlockTimestampLookup>,
         chains: Arc<BlockchainMap>,
         metrics_registry: Arc<M>,
         link_resolver: Arc<L>,
@@ -259,6 +326,7 @@ where
         SubgraphInstanceManager {
             logger_factory,
             subgraph_store,
+            block_timestamps,
             chains,
             manager_metrics: SubgraphInstanceManagerMetrics::new(metrics_registry.cheap_clone()),
             metrics_registry,
@@ -346,6 +414,24 @@ where
             .with_context(|| format!("no chain configured for network {}", network))?
             .clone();
 
+        // Refuse to index the deployment if the chain that is now
+        // configured under its network name is not the one it was created
+        // against
+        let genesis_block_hash = chain.chain_store().genesis_block_ptr()?.hash;
+        if let Some(bound) = subgraph_store.genesis_block_hash(&deployment)? {
+            if bound != genesis_block_hash {
+                return Err(anyhow!(
+                    "deployment {} was created on a chain with genesis block {}, but network {} \
+                     now has genesis block {}; if the deployment should be indexed on this \
+                     chain, bind it to it with `graphman deployment rebind`",
+                    deployment,
+                    bound,
+                    network,
+                    genesis_block_hash
+                ));
+            }
+        }
+
         // Obtain filters from the manifest
         let filter = C::TriggerFilter::from_data_sources(manifest.data_sources.iter());
         let start_blocks = manifest.start_blocks();
@@ -372,10 +458,14 @@ where
             deployment.hash.as_str(),
         ));
         let subgraph_metrics_unregister = subgraph_metrics.clone();
+        let memory_usage = store
+            .memory_usage()
+            .unwrap_or_else(|| Arc::new(MemoryUsage::default()));
         let host_metrics = Arc::new(HostMetrics::new(
             registry.cheap_clone(),
             deployment.hash.as_str(),
             stopwatch_metrics.clone(),
+            memory_usage,
         ));
         let block_stream_metrics = Arc::new(BlockStreamMetrics::new(
             registry.cheap_clone(),
@@ -390,14 +480,47 @@ where
         let deployment_head = store.block_ptr().map(|ptr| ptr.number).unwrap_or(0) as f64;
         block_stream_metrics.deployment_head.set(deployment_head);
 
+        let features = manifest.features.clone();
+        let unified_api_version = manifest.unified_mapping_api_version()?;
+        let mut labels = subgraph_store.deployment_labels(&deployment.hash)?;
+        let firehose_provider = labels.remove(FIREHOSE_PROVIDER_LABEL);
+        if let Some(provider) = &firehose_provider {
+            info!(logger, "Using firehose provider from deployment label"; "provider" => provider);
+        }
+        let replay = labels
+            .remove(REPLAY_LABEL)
+            .map_or(false, |value| value == "true");
+        if replay {
+            info!(
+                logger,
+                "Replaying blocks from the block cache because of deployment label"
+            );
+        }
+
         let host_builder = graph_runtime_wasm::RuntimeHostBuilder::new(
             chain.runtime_adapter(),
             self.link_resolver.cheap_clone(),
             subgraph_store.ens_lookup(),
+            self.block_timestamps.cheap_clone(),
+            manifest.big_decimal_precision,
+            replay,
         );
 
-        let features = manifest.features.clone();
-        let unified_api_version = manifest.unified_mapping_api_version()?;
+        let checkpoint_interval = match labels.remove(CHECKPOINT_INTERVAL_LABEL) {
+            Some(value) => match value.parse::<BlockNumber>() {
+                Ok(interval) if interval > 0 => {
+                    info!(logger, "Using checkpoint interval from deployment label";
+                        "interval" => interval);
+                    Some(interval)
+                }
+                _ => {
+                    warn!(logger, "Ignoring invalid checkpoint interval in deployment label";
+                        "value" => value);
+                    None
+                }
+            },
+            None => None,
+        };
         let instance =
             SubgraphInstance::from_manifest(&logger, manifest, host_builder, host_metrics.clone())?;
 
@@ -411,6 +534,9 @@ where
             chain,
             templates,
             unified_api_version,
+            firehose_provider,
+            replay,
+            checkpoint_interval,
         };
 
         // The subgraph state tracks the state of the subgraph instance over time
@@ -421,6 +547,7 @@ where
                 instances: self.instances.cheap_clone(),
                 filter,
                 entity_lfu_cache: LfuCache::new(),
+                scheduled_handlers: BTreeMap::new(),
             },
             subgraph_metrics,
             host_metrics,
@@ -459,8 +586,26 @@ async fn new_block_stream<C: Blockchain>(
     inputs: Arc<IndexingInputs<C>>,
     filter: C::TriggerFilter,
     block_stream_metrics: Arc<BlockStreamMetrics>,
+    logger: &Logger,
 ) -> Result<Box<dyn BlockStream<C>>, Error> {
     let chain = inputs.chain.cheap_clone();
+
+    if inputs.replay {
+        let block_stream = ReplayBlockStream::<C>::new(
+            chain.chain_store(),
+            inputs.triggers_adapter.cheap_clone(),
+            Arc::new(filter),
+            inputs.store.block_ptr(),
+            logger.cheap_clone(),
+        );
+        return Ok(BufferedBlockStream::spawn_from_stream(
+            Box::new(block_stream),
+            BUFFERED_BLOCK_STREAM_SIZE,
+            inputs.store.write_backlog(),
+            inputs.store.memory_usage(),
+        ));
+    }
+
     let is_firehose = chain.is_firehose_supported();
 
     let buffer_size = match is_firehose {
@@ -476,6 +621,7 @@ async fn new_block_stream<C: Blockchain>(
             Arc::new(filter.clone()),
             block_stream_metrics.clone(),
             inputs.unified_api_version.clone(),
+            inputs.firehose_provider.clone(),
         ),
         false => {
             let start_block = inputs.store.block_ptr();
@@ -487,6 +633,7 @@ async fn new_block_stream<C: Blockchain>(
                 Arc::new(filter.clone()),
                 block_stream_metrics.clone(),
                 inputs.unified_api_version.clone(),
+                inputs.checkpoint_interval,
             )
         }
     }
@@ -495,6 +642,8 @@ async fn new_block_stream<C: Blockchain>(
     Ok(BufferedBlockStream::spawn_from_stream(
         block_stream,
         buffer_size,
+        inputs.store.write_backlog(),
+        inputs.store.memory_usage(),
     ))
 }
 
@@ -523,16 +672,20 @@ where
     loop {
         debug!(logger, "Starting or restarting subgraph");
 
+        load_scheduled_handlers(&mut ctx, &inputs).await?;
+        load_data_source_contexts(&ctx, &inputs).await?;
+
         let block_stream_canceler = CancelGuard::new();
         let block_stream_cancel_handle = block_stream_canceler.handle();
 
         let metrics = ctx.block_stream_metrics.clone();
         let filter = ctx.state.filter.clone();
         let stream_inputs = inputs.clone();
-        let mut block_stream = new_block_stream(stream_inputs, filter, metrics.cheap_clone())
-            .await?
-            .map_err(CancelableError::Error)
-            .cancelable(&block_stream_canceler, || Err(CancelableError::Cancel));
+        let mut block_stream =
+            new_block_stream(stream_inputs, filter, metrics.cheap_clone(), &logger)
+                .await?
+                .map_err(CancelableError::Error)
+                .cancelable(&block_stream_canceler, || Err(CancelableError::Cancel));
         let chain = inputs.chain.clone();
         let chain_store = chain.chain_store();
 
@@ -594,6 +747,27 @@ where
                     // match any data sources.
                     ctx.state.instance.revert_data_sources(subgraph_ptr.number);
                     ctx.state.entity_lfu_cache = LfuCache::new();
+
+                    // Handlers that ran in the reverted block are due
+                    // again, and handlers that were scheduled in it are gone
+                    if let Err(e) = load_scheduled_handlers(&mut ctx, &inputs).await {
+                        error!(
+                            &logger,
+                            "Could not reload scheduled block handlers. Retrying";
+                            "error" => format!("{:#}", e),
+                        );
+                        break;
+                    }
+
+                    // Contexts stored in the reverted block are gone
+                    if let Err(e) = load_data_source_contexts(&ctx, &inputs).await {
+                        error!(
+                            &logger,
+                            "Could not reload data source contexts. Retrying";
+                            "error" => format!("{:#}", e),
+                        );
+                        break;
+                    }
                     continue;
                 }
 
@@ -612,6 +786,12 @@ where
                     );
                     continue;
                 }
+                // Only the replay stream ends, once it runs out of cached
+                // blocks
+                None if inputs.replay => {
+                    info!(&logger, "Replay finished");
+                    return Ok(());
+                }
                 None => unreachable!("The block stream stopped producing blocks"),
             };
 
@@ -1010,6 +1190,40 @@ async fn process_block<T: RuntimeHostBuilder<C>, C: Blockchain>(
         }
     }
 
+    // Run the block handlers that mappings scheduled for this block, in
+    // the order in which they were scheduled
+    if let Some(handlers) = ctx.state.scheduled_handlers.get(&block_ptr.number) {
+        for handler in handlers {
+            block_state = ctx
+                .state
+                .instance
+                .process_scheduled_handler(
+                    &logger,
+                    &block,
+                    handler,
+                    block_state,
+                    proof_of_indexing.cheap_clone(),
+                    &causality_region,
+                )
+                .await
+                .map_err(|e| match e {
+                    // Dynamic data sources created in this block have
+                    // already been added, so a possible reorg can not be
+                    // handled by simply restarting
+                    MappingError::PossibleReorg(e) | MappingError::Unknown(e) => {
+                        BlockProcessingError::Unknown(e.context(format!(
+                            "failed to process scheduled block handler `{}`",
+                            handler.handler
+                        )))
+                    }
+                })?;
+        }
+    }
+
+    // Handlers scheduled in this block only take effect once the block
+    // stream has been restarted with them
+    let needs_restart = needs_restart || !block_state.scheduled_handlers.is_empty();
+
     let has_errors = block_state.has_errors();
     let is_non_fatal_errors_active = inputs.features.contains(&SubgraphFeature::NonFatalErrors);
 
@@ -1046,6 +1260,10 @@ async fn process_block<T: RuntimeHostBuilder<C>, C: Blockchain>(
     assert!(ctx.state.entity_lfu_cache.is_empty());
     ctx.state.entity_lfu_cache = cache;
 
+    let memory_usage = &ctx.host_metrics.memory_usage;
+    memory_usage.set_entity_cache_bytes(ctx.state.entity_lfu_cache.weight());
+    metrics.observe_memory_usage(memory_usage);
+
     if !mods.is_empty() {
         info!(&logger, "Applying {} entity operation(s)", mods.len());
     }
@@ -1081,6 +1299,9 @@ async fn process_block<T: RuntimeHostBuilder<C>, C: Blockchain>(
 
     let BlockState {
         deterministic_errors,
+        warnings,
+        scheduled_handlers,
+        data_source_contexts,
         ..
     } = block_state;
 
@@ -1092,9 +1313,22 @@ async fn process_block<T: RuntimeHostBuilder<C>, C: Blockchain>(
         mods,
         stopwatch,
         data_sources,
+        scheduled_handlers,
+        data_source_contexts.clone(),
         deterministic_errors,
     ) {
         Ok(_) => {
+            // Later blocks see the contexts that were stored in this one
+            ctx.state
+                .instance
+                .update_data_source_contexts(&data_source_contexts, false);
+
+            // Warnings are purely informational, and failing to record
+            // them is no reason to stop processing blocks
+            if let Err(e) = store.add_warnings(warnings) {
+                error!(&logger, "Failed to record subgraph warnings"; "error" => e.to_string());
+            }
+
             // For subgraphs with `nonFatalErrors` feature disabled, we consider
             // any error as fatal.
             //
@@ -1129,6 +1363,38 @@ async fn process_block<T: RuntimeHostBuilder<C>, C: Blockchain>(
     }
 }
 
+/// Load the block handlers that are still due from the store and make sure
+/// the block stream includes their blocks
+async fn load_scheduled_handlers<T: RuntimeHostBuilder<C>, C: Blockchain>(
+    ctx: &mut IndexingContext<T, C>,
+    inputs: &IndexingInputs<C>,
+) -> Result<(), Error> {
+    let handlers = inputs.store.load_scheduled_block_handlers().await?;
+
+    let mut scheduled: BTreeMap<BlockNumber, Vec<ScheduledBlockHandler>> = BTreeMap::new();
+    for handler in handlers {
+        scheduled.entry(handler.block).or_default().push(handler);
+    }
+    ctx.state
+        .filter
+        .extend_with_scheduled_blocks(scheduled.keys().cloned());
+    ctx.state.scheduled_handlers = scheduled;
+    Ok(())
+}
+
+/// Load the latest contexts that mappings stored with `dataSource.setContext`
+/// and make the hosts use them
+async fn load_data_source_contexts<T: RuntimeHostBuilder<C>, C: Blockchain>(
+    ctx: &IndexingContext<T, C>,
+    inputs: &IndexingInputs<C>,
+) -> Result<(), Error> {
+    let contexts = inputs.store.load_data_source_contexts().await?;
+    ctx.state
+        .instance
+        .update_data_source_contexts(&contexts, true);
+    Ok(())
+}
+
 /// Transform the proof of indexing changes into entity updates that will be
 /// inserted when as_modifications is called.
 async fn update_proof_of_indexing(
@@ -1188,6 +1454,23 @@ async fn process_triggers<C: Blockchain>(
 ) -> Result<BlockState<C>, MappingError> {
     use graph::blockchain::TriggerData;
 
+    if *PARALLEL_TRIGGERS
+        && triggers.len() > 1
+        && process_triggers_in_parallel(
+            logger,
+            &mut block_state,
+            &proof_of_indexing,
+            &subgraph_metrics,
+            instance,
+            block,
+            &triggers,
+            causality_region,
+        )
+        .await?
+    {
+        return Ok(block_state);
+    }
+
     for trigger in triggers.into_iter() {
         let start = Instant::now();
         block_state = instance
@@ -1213,6 +1496,133 @@ async fn process_triggers<C: Blockchain>(
     Ok(block_state)
 }
 
+/// Process the triggers of a block concurrently, grouped by the first data
+/// source that handles them. The triggers of a group are processed in order
+/// with a block state of their own, and the states of all groups are merged
+/// into `block_state` if no group changed an entity that another group read
+/// or changed, and the handlers had no effects other than entity changes.
+/// Events for the proof of indexing are recorded for each group and then
+/// replayed in the order of the triggers. Together, this makes the outcome
+/// the same as if the triggers had been processed one after the other.
+///
+/// Returns `false` without changing `block_state` if the groups can not be
+/// merged, and the triggers need to be processed sequentially instead
+async fn process_triggers_in_parallel<C: Blockchain>(
+    logger: &Logger,
+    block_state: &mut BlockState<C>,
+    proof_of_indexing: &SharedProofOfIndexing,
+    subgraph_metrics: &SubgraphInstanceMetrics,
+    instance: &SubgraphInstance<C, impl RuntimeHostBuilder<C>>,
+    block: &Arc<C::Block>,
+    triggers: &[C::TriggerData],
+    causality_region: &str,
+) -> Result<bool, MappingError> {
+    use graph::blockchain::TriggerData;
+
+    let mut groups: Vec<Vec<&C::TriggerData>> = Vec::new();
+    let mut group_of_host: HashMap<usize, usize> = HashMap::new();
+    let mut group_of_trigger: Vec<Option<usize>> = Vec::with_capacity(triggers.len());
+    for trigger in triggers {
+        let group = match instance.first_matching_host(logger, block, trigger)? {
+            Some(host) => {
+                let group = *group_of_host.entry(host).or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+                groups[group].push(trigger);
+                Some(group)
+            }
+            None => None,
+        };
+        group_of_trigger.push(group);
+    }
+    if groups.len() < 2 {
+        return Ok(false);
+    }
+
+    let store = block_state.entity_cache.store.cheap_clone();
+    let results = futures03::future::try_join_all(groups.into_iter().map(|triggers| {
+        let mut state = BlockState::new(store.cheap_clone(), LfuCache::new());
+        let recording_poi: SharedProofOfIndexing = proof_of_indexing.as_ref().map(|_| {
+            Arc::new(AtomicRefCell::new(ProofOfIndexing::recording(
+                block.number(),
+            )))
+        });
+
+        async move {
+            for trigger in triggers {
+                let start = Instant::now();
+                state = instance
+                    .process_trigger(
+                        logger,
+                        block,
+                        trigger,
+                        state,
+                        recording_poi.cheap_clone(),
+                        causality_region,
+                    )
+                    .await
+                    .map_err(|mut e| {
+                        let error_context = trigger.error_context();
+                        if !error_context.is_empty() {
+                            e = e.context(error_context);
+                        }
+                        e.context("failed to process trigger".to_string())
+                    })?;
+                let elapsed = start.elapsed().as_secs_f64();
+                subgraph_metrics.observe_trigger_processing_duration(elapsed);
+            }
+            Ok::<_, MappingError>((state, recording_poi))
+        }
+    }))
+    .await?;
+
+    if !results
+        .iter()
+        .all(|(state, _)| state.has_only_entity_changes())
+    {
+        debug!(
+            logger,
+            "Processing triggers sequentially since handlers had effects besides entity changes"
+        );
+        return Ok(false);
+    }
+
+    let caches: Vec<_> = results
+        .iter()
+        .map(|(state, _)| &state.entity_cache)
+        .collect();
+    if let Some(key) = EntityCache::conflicting_key(&caches) {
+        debug!(
+            logger,
+            "Processing triggers sequentially since they conflict";
+            "entity_type" => key.entity_type.as_str(),
+            "entity_id" => &key.entity_id,
+        );
+        return Ok(false);
+    }
+
+    let mut recorded_handlers = Vec::with_capacity(results.len());
+    for (state, recording_poi) in results {
+        block_state.extend(state);
+        if let Some(recording_poi) = recording_poi {
+            let recording_poi = std::mem::take(&mut *recording_poi.borrow_mut());
+            recorded_handlers.push(recording_poi.take_recorded_handlers());
+        }
+    }
+
+    if let Some(proof_of_indexing) = proof_of_indexing {
+        proof_of_indexing.borrow_mut().replay_in_trigger_order(
+            logger,
+            causality_region,
+            &group_of_trigger,
+            recorded_handlers,
+        );
+    }
+
+    Ok(true)
+}
+
 fn create_dynamic_data_sources<T: RuntimeHostBuilder<C>, C: Blockchain>(
     logger: Logger,
     ctx: &mut IndexingContext<T, C>,
@@ -85,7 +85,7 @@ where
             .remove(&deployment.id)
         {
             // Shut down subgraph processing
-            self.instance_manager.stop_subgraph(deployment);
+            self.instance_manager.stop_subgraph(deployment).await;
             Ok(())
         } else {
             Err(SubgraphAssignmentProviderError::NotRunning(deployment))
@@ -510,7 +510,9 @@ async fn create_subgraph_version<C: Blockchain, S: SubgraphStore, L: LinkResolve
         .validate(store.cheap_clone(), true)
         .map_err(SubgraphRegistrarError::ManifestValidationError)?;
 
-    let network_name = manifest.network_name();
+    // Deployments record the chain's own name even if the manifest uses
+    // one of its aliases
+    let network_name = chains.canonical_name(&manifest.network_name());
 
     let chain = chains
         .get::<C>(network_name.clone())
@@ -2,11 +2,12 @@ use anyhow::Error;
 use async_stream::stream;
 use futures03::Stream;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 use tokio::sync::mpsc::{self, Receiver, Sender};
 
 use super::{Block, BlockPtr, Blockchain};
-use crate::components::store::BlockNumber;
+use crate::components::store::{BlockNumber, MemoryUsage, WriteBacklog};
 use crate::firehose;
 use crate::{prelude::*, prometheus::labels};
 
@@ -15,17 +16,34 @@ pub struct BufferedBlockStream<C: Blockchain> {
 }
 
 impl<C: Blockchain + 'static> BufferedBlockStream<C> {
+    /// Buffer up to `size_hint` events from `stream`. With a `backlog`, no
+    /// more events are pulled from `stream` while the store has too many
+    /// changes waiting to be written. With `memory_usage`, the blocks in
+    /// the buffer are counted there
     pub fn spawn_from_stream(
         stream: Box<dyn BlockStream<C>>,
         size_hint: usize,
+        backlog: Option<Arc<WriteBacklog>>,
+        memory_usage: Option<Arc<MemoryUsage>>,
     ) -> Box<dyn BlockStream<C>> {
         let (sender, receiver) = mpsc::channel::<Result<BlockStreamEvent<C>, Error>>(size_hint);
-        crate::spawn(async move { BufferedBlockStream::stream_blocks(stream, sender).await });
+        // Blocks that were still buffered when a previous stream for the
+        // same deployment was dropped are gone
+        if let Some(memory_usage) = &memory_usage {
+            memory_usage.clear_buffered_blocks();
+        }
+        let sender_memory_usage = memory_usage.clone();
+        crate::spawn(async move {
+            BufferedBlockStream::stream_blocks(stream, sender, backlog, sender_memory_usage).await
+        });
 
-        Box::new(BufferedBlockStream::new(receiver))
+        Box::new(BufferedBlockStream::new(receiver, memory_usage))
     }
 
-    pub fn new(mut receiver: Receiver<Result<BlockStreamEvent<C>, Error>>) -> Self {
+    pub fn new(
+        mut receiver: Receiver<Result<BlockStreamEvent<C>, Error>>,
+        memory_usage: Option<Arc<MemoryUsage>>,
+    ) -> Self {
         let inner = stream! {
             loop {
                 let event = match receiver.recv().await {
@@ -33,6 +51,12 @@ impl<C: Blockchain + 'static> BufferedBlockStream<C> {
                     None => return,
                 };
 
+                if let (Some(memory_usage), Ok(BlockStreamEvent::ProcessBlock(block, _))) =
+                    (&memory_usage, &event)
+                {
+                    memory_usage.remove_buffered_block(block.trigger_count());
+                }
+
                 yield event
             }
         };
@@ -45,8 +69,14 @@ impl<C: Blockchain + 'static> BufferedBlockStream<C> {
     pub async fn stream_blocks(
         mut stream: Box<dyn BlockStream<C>>,
         sender: Sender<Result<BlockStreamEvent<C>, Error>>,
+        backlog: Option<Arc<WriteBacklog>>,
+        memory_usage: Option<Arc<MemoryUsage>>,
     ) -> Result<(), Error> {
         loop {
+            if let Some(backlog) = &backlog {
+                backlog.wait_for_capacity().await;
+            }
+
             let event = match stream.next().await {
                 Some(evt) => evt,
                 None => {
@@ -54,6 +84,14 @@ impl<C: Blockchain + 'static> BufferedBlockStream<C> {
                 }
             };
 
+            let triggers = match &event {
+                Ok(BlockStreamEvent::ProcessBlock(block, _)) => Some(block.trigger_count()),
+                _ => None,
+            };
+            if let (Some(memory_usage), Some(triggers)) = (&memory_usage, triggers) {
+                memory_usage.add_buffered_block(triggers);
+            }
+
             match sender.send(event).await {
                 Ok(_) => continue,
                 Err(err) => {
@@ -89,6 +127,12 @@ pub trait BlockStream<C: Blockchain>:
 
 pub type FirehoseCursor = Option<String>;
 
+/// The cursor that block streams which do not use Firehose report for each
+/// block. When a Firehose block stream starts with an empty cursor, it
+/// continues right after the subgraph's block pointer, so that providers
+/// can be switched from RPC to Firehose without rewinding the deployment
+pub const BLOCK_PTR_CURSOR: FirehoseCursor = Some(String::new());
+
 pub struct BlockWithTriggers<C: Blockchain> {
     pub block: C::Block,
     pub trigger_data: Vec<C::TriggerData>,
@@ -160,6 +204,11 @@ pub trait FirehoseMapper<C: Blockchain>: Send + Sync {
         adapter: &C::TriggersAdapter,
         filter: &C::TriggerFilter,
     ) -> Result<BlockStreamEvent<C>, FirehoseError>;
+
+    /// Returns the pointer of the block in `response` by decoding only its
+    /// header. Used to position subscribers on a shared Firehose stream
+    /// without decoding the full block
+    fn block_ptr(&self, response: &firehose::Response) -> Result<BlockPtr, FirehoseError>;
 }
 
 #[derive(Error, Debug)]
@@ -187,6 +236,16 @@ pub struct BlockStreamMetrics {
     pub deployment_head: Box<Gauge>,
     pub deployment_failed: Box<Gauge>,
     pub reverted_blocks: Box<Gauge>,
+    /// How many blocks the deployment is behind the chain head
+    pub head_distance_blocks: Box<Gauge>,
+    /// How many seconds ago the last block the block stream produced was
+    /// mined
+    pub head_distance_seconds: Box<Gauge>,
+    /// When the block stream last produced an event, in seconds since the
+    /// epoch
+    pub cursor_timestamp: Box<Gauge>,
+    /// Total time the block stream spent in each of its states
+    pub state_duration: Box<CounterVec>,
     pub stopwatch: StopwatchMetrics,
 }
 
@@ -224,13 +283,76 @@ impl BlockStreamMetrics {
                 labels,
             )
             .expect("failed to create `deployment_failed` gauge");
+        let head_distance_blocks = registry
+            .new_deployment_gauge(
+                "deployment_head_distance_blocks",
+                "Number of blocks the deployment is behind the chain head",
+                deployment_id.as_str(),
+            )
+            .expect("failed to create `deployment_head_distance_blocks` gauge");
+        let head_distance_seconds = registry
+            .new_deployment_gauge(
+                "deployment_head_distance_seconds",
+                "Age in seconds of the last block the block stream produced",
+                deployment_id.as_str(),
+            )
+            .expect("failed to create `deployment_head_distance_seconds` gauge");
+        let cursor_timestamp = registry
+            .new_deployment_gauge(
+                "deployment_cursor_timestamp_seconds",
+                "Time at which the block stream last moved its cursor, in seconds since the epoch",
+                deployment_id.as_str(),
+            )
+            .expect("failed to create `deployment_cursor_timestamp_seconds` gauge");
+        let state_duration = registry
+            .new_deployment_counter_vec(
+                "deployment_block_stream_state_seconds",
+                "Total time in seconds the block stream spent in each state",
+                deployment_id.as_str(),
+                vec![String::from("state")],
+            )
+            .expect("failed to create `deployment_block_stream_state_seconds` counter");
         Self {
             deployment_head,
             deployment_failed,
             reverted_blocks,
+            head_distance_blocks,
+            head_distance_seconds,
+            cursor_timestamp,
+            state_duration,
             stopwatch,
         }
     }
+
+    /// Record that the block stream moved its cursor to `block`
+    pub fn block_produced<B: Block>(&self, block: &B) {
+        let now = unix_now();
+        self.cursor_timestamp.set(now as f64);
+        if let Some(timestamp) = block.timestamp() {
+            self.head_distance_seconds
+                .set(now.saturating_sub(timestamp) as f64);
+        }
+    }
+
+    /// Record that the block stream moved its cursor without producing a
+    /// block, for example because of a revert
+    pub fn cursor_moved(&self) {
+        self.cursor_timestamp.set(unix_now() as f64);
+    }
+
+    /// Record that the block stream spent `duration` in `state`
+    pub fn state_left(&self, state: &str, duration: Duration) {
+        self.state_duration
+            .with_label_values(&[state])
+            .inc_by(duration.as_secs_f64());
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
 /// Notifications about the chain head advancing. The block ingestor sends
@@ -246,13 +368,14 @@ pub trait ChainHeadUpdateListener: Send + Sync + 'static {
 
 #[cfg(test)]
 mod test {
-    use std::{collections::HashSet, task::Poll};
+    use std::{collections::HashSet, sync::Arc, task::Poll, time::Duration};
 
     use anyhow::Error;
     use futures03::{Stream, StreamExt, TryStreamExt};
 
     use crate::{
         blockchain::mock::{MockBlock, MockBlockchain},
+        components::store::WriteBacklog,
         ext::futures::{CancelableError, SharedCancelGuard, StreamExtension},
     };
 
@@ -295,7 +418,7 @@ mod test {
         });
         let guard = SharedCancelGuard::new();
 
-        let mut stream = BufferedBlockStream::spawn_from_stream(stream, buffer_size)
+        let mut stream = BufferedBlockStream::spawn_from_stream(stream, buffer_size, None, None)
             .map_err(CancelableError::Error)
             .cancelable(&guard, || Err(CancelableError::Cancel));
 
@@ -328,4 +451,23 @@ mod test {
         );
         assert_eq!(count, blocks.len(), "should not have duplicated blocks");
     }
+
+    #[tokio::test]
+    async fn pause_while_write_backlog_is_full() {
+        let backlog = Arc::new(WriteBacklog::new(10, usize::MAX));
+        backlog.add(11, 0);
+
+        let stream = Box::new(TestStream { number: 1 });
+        let mut stream =
+            BufferedBlockStream::spawn_from_stream(stream, 5, Some(backlog.clone()), None);
+
+        let next = tokio::time::timeout(Duration::from_millis(100), stream.next()).await;
+        assert!(next.is_err(), "no blocks while the backlog is full");
+
+        backlog.remove(11, 0);
+        match stream.next().await {
+            Some(Ok(BlockStreamEvent::ProcessBlock(block, _))) => assert_eq!(1, block.block.number),
+            _ => panic!("expected a block once the backlog drained"),
+        }
+    }
 }
@@ -0,0 +1,177 @@
+//! Limits for block ingestors that catch up with the chain.
+//!
+//! After a node was down for a while, its block ingestors have to fetch all
+//! the blocks that were produced in the meantime. Doing that as fast as
+//! possible can overwhelm providers that are shared with other nodes. A
+//! `CatchUp` bounds how many blocks an ingestor fetches concurrently and
+//! how many blocks per second it fetches, and periodically logs how far
+//! catching up has progressed.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{
+    components::store::BlockNumber,
+    env::env_var,
+    prelude::{info, lazy_static, Logger},
+};
+
+lazy_static! {
+    /// Maximum number of blocks an ingestor fetches concurrently while
+    /// catching up with the chain.
+    static ref CONCURRENCY: usize = env_var("GRAPH_INGESTOR_CATCH_UP_CONCURRENCY", 4);
+
+    /// Maximum number of blocks per second an ingestor fetches while
+    /// catching up with the chain. A value of `0` disables the limit.
+    static ref RATE: u32 = env_var("GRAPH_INGESTOR_CATCH_UP_RATE", 0);
+
+    /// Time in seconds between two log messages about catch-up progress.
+    static ref PROGRESS_INTERVAL: Duration =
+        Duration::from_secs(env_var("GRAPH_INGESTOR_CATCH_UP_PROGRESS_INTERVAL", 30));
+}
+
+struct State {
+    /// The earliest time at which the next block may be fetched
+    next_fetch: Instant,
+    /// Blocks ingested since the last progress report
+    ingested: u64,
+    last_report: Instant,
+}
+
+pub struct CatchUp {
+    logger: Logger,
+    concurrency: usize,
+    /// The minimum time between fetching two blocks, `None` if the rate is
+    /// not limited
+    min_interval: Option<Duration>,
+    progress_interval: Duration,
+    state: Mutex<State>,
+}
+
+impl CatchUp {
+    /// Create limits from the environment
+    pub fn new(logger: Logger) -> Self {
+        Self::with_limits(logger, *CONCURRENCY, *RATE, *PROGRESS_INTERVAL)
+    }
+
+    pub fn with_limits(
+        logger: Logger,
+        concurrency: usize,
+        rate: u32,
+        progress_interval: Duration,
+    ) -> Self {
+        let now = Instant::now();
+        CatchUp {
+            logger,
+            concurrency: concurrency.max(1),
+            min_interval: match rate {
+                0 => None,
+                rate => Some(Duration::from_secs(1) / rate),
+            },
+            progress_interval,
+            state: Mutex::new(State {
+                next_fetch: now,
+                ingested: 0,
+                last_report: now,
+            }),
+        }
+    }
+
+    /// How many blocks may be fetched at the same time
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Reserve the next slot for fetching a block at or after `now` and
+    /// return how long to wait for it
+    fn reserve(&self, now: Instant) -> Duration {
+        let min_interval = match self.min_interval {
+            Some(min_interval) => min_interval,
+            None => return Duration::from_secs(0),
+        };
+        let mut state = self.state.lock().unwrap();
+        let slot = state.next_fetch.max(now);
+        state.next_fetch = slot + min_interval;
+        slot - now
+    }
+
+    /// Wait until the rate limit allows fetching another block
+    pub async fn acquire(&self) {
+        let wait = self.reserve(Instant::now());
+        if wait > Duration::from_secs(0) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Record that block `number` was ingested while catching up with the
+    /// chain up to `target`, if that is known, and log progress if the
+    /// last report is long enough ago
+    pub fn record(&self, number: BlockNumber, target: Option<BlockNumber>) {
+        let mut state = self.state.lock().unwrap();
+        state.ingested += 1;
+
+        let elapsed = state.last_report.elapsed();
+        if elapsed < self.progress_interval {
+            return;
+        }
+        let blocks_per_sec = state.ingested as f64 / elapsed.as_secs_f64();
+        state.ingested = 0;
+        state.last_report = Instant::now();
+        drop(state);
+
+        match target {
+            Some(target) => info!(
+                self.logger,
+                "Catching up with the chain";
+                "block" => number,
+                "target" => target,
+                "blocks_behind" => target - number,
+                "blocks_per_sec" => format!("{:.1}", blocks_per_sec),
+            ),
+            None => info!(
+                self.logger,
+                "Catching up with the chain";
+                "block" => number,
+                "blocks_per_sec" => format!("{:.1}", blocks_per_sec),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::logger;
+
+    #[test]
+    fn reserve_spaces_out_fetches() {
+        let catch_up = CatchUp::with_limits(logger(false), 2, 10, Duration::from_secs(30));
+        let now = Instant::now();
+
+        let waits: Vec<_> = (0..3).map(|_| catch_up.reserve(now)).collect();
+        assert_eq!(
+            vec![
+                Duration::from_millis(0),
+                Duration::from_millis(100),
+                Duration::from_millis(200)
+            ],
+            waits
+        );
+
+        // Slots that have passed are not made up for
+        let later = now + Duration::from_secs(1);
+        assert_eq!(Duration::from_millis(0), catch_up.reserve(later));
+        assert_eq!(Duration::from_millis(100), catch_up.reserve(later));
+    }
+
+    #[test]
+    fn reserve_without_rate_limit() {
+        let catch_up = CatchUp::with_limits(logger(false), 0, 0, Duration::from_secs(30));
+        let now = Instant::now();
+
+        assert_eq!(1, catch_up.concurrency());
+        for _ in 0..10 {
+            assert_eq!(Duration::from_secs(0), catch_up.reserve(now));
+        }
+    }
+}
@@ -1,25 +1,303 @@
-use std::{marker::PhantomData, sync::Arc, time::Duration};
+use std::{
+    marker::PhantomData,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{
-    blockchain::Block as BlockchainBlock,
-    components::store::ChainStore,
-    firehose::{self, decode_firehose_block, FirehoseEndpoint},
-    prelude::{error, info, Logger},
+    blockchain::{catch_up::CatchUp, Block as BlockchainBlock, BlockPtr},
+    components::{
+        metrics::{Counter, MetricsRegistry},
+        store::{BackfillCursor, BlockCopier, BlockNumber, ChainStore},
+    },
+    env::env_var,
+    firehose::{
+        self, decode_firehose_block, FirehoseEndpoint, FirehoseEndpoints, FirehoseStream, ForkStep,
+    },
+    prelude::{error, info, lazy_static, warn, Logger},
     util::backoff::ExponentialBackoff,
 };
 use anyhow::{Context, Error};
-use futures03::StreamExt;
-use slog::trace;
-use tonic::Streaming;
+use futures03::{future::join_all, FutureExt, StreamExt};
+use rand::Rng;
+use slog::{debug, trace};
+
+/// Fraction of each backoff delay by which reconnects are randomized
+const BACKOFF_JITTER: f64 = 0.5;
+
+lazy_static! {
+    /// Block number up to which the ingestor backfills historical blocks
+    /// into the chain store. A value of `0` disables the backfill.
+    static ref BACKFILL_TARGET: BlockNumber = env_var("GRAPH_FIREHOSE_BACKFILL_TARGET", 0);
+
+    /// Number of disjoint sub-ranges, each consumed by its own stream, the
+    /// backfill range is split into.
+    static ref BACKFILL_CONCURRENCY: usize = env_var("GRAPH_FIREHOSE_BACKFILL_CONCURRENCY", 4);
+
+    /// Maximum number of blocks that are written to the chain store in one
+    /// batch while catching up.
+    static ref BATCH_SIZE: usize = env_var("GRAPH_FIREHOSE_INGESTOR_BATCH_SIZE", 100);
+
+    /// Maximum time in milliseconds that blocks are held back in a batch
+    /// before they are written to the chain store.
+    static ref BATCH_INTERVAL: Duration =
+        Duration::from_millis(env_var("GRAPH_FIREHOSE_INGESTOR_BATCH_INTERVAL", 1000));
+
+    /// Number of consecutive failed connection attempts after which the
+    /// circuit for an endpoint opens and the ingestor fails over to the
+    /// next endpoint of the pool.
+    static ref MAX_RETRIES: u32 = env_var("GRAPH_FIREHOSE_INGESTOR_MAX_RETRIES", 10);
+
+    /// Time in seconds for which an endpoint whose circuit opened is not
+    /// used.
+    static ref CIRCUIT_OPEN_DURATION: Duration =
+        Duration::from_secs(env_var("GRAPH_FIREHOSE_INGESTOR_CIRCUIT_OPEN_DURATION", 60));
+}
+
+/// The endpoints a stream of the ingestor can connect to, with a circuit
+/// breaker per endpoint. Once `max_retries` consecutive attempts on an
+/// endpoint failed, its circuit opens for `open_duration` and the stream
+/// fails over to the next endpoint whose circuit is closed.
+struct EndpointPool<T> {
+    endpoints: Vec<T>,
+    current: usize,
+    failures: Vec<u32>,
+    open_until: Vec<Option<Instant>>,
+    max_retries: u32,
+    open_duration: Duration,
+}
+
+impl<T: Clone> EndpointPool<T> {
+    /// Create a pool that starts out with the endpoint at index `start`.
+    /// Panics if `endpoints` is empty
+    fn new(endpoints: Vec<T>, start: usize, max_retries: u32, open_duration: Duration) -> Self {
+        assert!(!endpoints.is_empty(), "an endpoint pool can not be empty");
+        let len = endpoints.len();
+        EndpointPool {
+            endpoints,
+            current: start % len,
+            failures: vec![0; len],
+            open_until: vec![None; len],
+            max_retries: max_retries.max(1),
+            open_duration,
+        }
+    }
+
+    fn current(&self) -> T {
+        self.endpoints[self.current].clone()
+    }
+
+    /// Record that the current endpoint made progress, which closes its
+    /// circuit
+    fn record_success(&mut self) {
+        self.failures[self.current] = 0;
+        self.open_until[self.current] = None;
+    }
+
+    /// Record a failed attempt on the current endpoint. Returns `true` if
+    /// that opened the circuit of the endpoint, in which case the pool
+    /// switched to the next endpoint whose circuit is closed, or, if all
+    /// circuits are open, to the endpoint whose circuit closes first
+    fn record_failure(&mut self, now: Instant) -> bool {
+        let failed = self.current;
+        self.failures[failed] += 1;
+        if self.failures[failed] < self.max_retries {
+            return false;
+        }
+
+        self.failures[failed] = 0;
+        self.open_until[failed] = Some(now + self.open_duration);
+
+        let len = self.endpoints.len();
+        let open_until = &self.open_until;
+        self.current = (1..=len)
+            .map(|offset| (failed + offset) % len)
+            .find(|idx| open_until[*idx].map_or(true, |until| until <= now))
+            .unwrap_or_else(|| {
+                (0..len)
+                    .min_by_key(|idx| open_until[*idx])
+                    .unwrap_or(failed)
+            });
+        true
+    }
+
+    /// How long to wait before the circuit of the current endpoint closes
+    fn wait_time(&self, now: Instant) -> Duration {
+        self.open_until[self.current]
+            .map(|until| until.saturating_duration_since(now))
+            .unwrap_or_default()
+    }
+}
+
+/// Blocks that have been received but not yet written to the chain store,
+/// together with the cursor of the last of them.
+struct BlockBatch {
+    blocks: Vec<Arc<dyn BlockchainBlock>>,
+    cursor: String,
+    started: Option<Instant>,
+}
+
+impl BlockBatch {
+    fn new() -> Self {
+        BlockBatch {
+            blocks: Vec::new(),
+            cursor: String::new(),
+            started: None,
+        }
+    }
+
+    fn push(&mut self, block: Arc<dyn BlockchainBlock>, cursor: String) {
+        self.started.get_or_insert_with(Instant::now);
+        self.blocks.push(block);
+        self.cursor = cursor;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// A batch is full once it holds `size` blocks or its first block was
+    /// received more than `interval` ago.
+    fn is_full(&self, size: usize, interval: Duration) -> bool {
+        self.blocks.len() >= size
+            || self
+                .started
+                .map(|started| started.elapsed() >= interval)
+                .unwrap_or(false)
+    }
+
+    fn take(&mut self) -> (Vec<Arc<dyn BlockchainBlock>>, String) {
+        self.started = None;
+        (
+            std::mem::take(&mut self.blocks),
+            std::mem::take(&mut self.cursor),
+        )
+    }
+}
+
+/// An inclusive range of blocks `[start, stop]` consumed by one backfill stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BackfillRange {
+    pub start: BlockNumber,
+    pub stop: BlockNumber,
+}
+
+impl BackfillRange {
+    /// Splits `[start, stop]` into at most `parts` disjoint, contiguous
+    /// sub-ranges of roughly equal size.
+    pub fn split(start: BlockNumber, stop: BlockNumber, parts: usize) -> Vec<BackfillRange> {
+        if stop < start {
+            return vec![];
+        }
+
+        let total = (stop - start) as i64 + 1;
+        let parts = (parts.max(1) as i64).min(total);
+        let size = total / parts;
+        let remainder = total % parts;
+
+        let mut ranges = Vec::with_capacity(parts as usize);
+        let mut next = start as i64;
+        for i in 0..parts {
+            // Spread the remainder over the first ranges so that no range is
+            // more than one block larger than any other
+            let len = size + if i < remainder { 1 } else { 0 };
+            ranges.push(BackfillRange {
+                start: next as BlockNumber,
+                stop: (next + len - 1) as BlockNumber,
+            });
+            next += len;
+        }
+        ranges
+    }
+
+    /// Where a stream from `provider` resumes the backfill of this range
+    /// after `last_block` was ingested. Cursors are specific to the provider
+    /// that issued them; with `cursor` from any other provider, streaming
+    /// starts after `last_block` without a cursor. Returns the cursor and
+    /// the block number to start streaming from
+    fn resume_position(
+        &self,
+        cursor: Option<&BackfillCursor>,
+        provider: &str,
+        last_block: Option<BlockNumber>,
+    ) -> (String, BlockNumber) {
+        let start_block = last_block.map(|number| number + 1).unwrap_or(self.start);
+        match cursor {
+            Some(cursor) if cursor.provider == provider => (cursor.cursor.clone(), start_block),
+            Some(_) | None => (String::new(), start_block),
+        }
+    }
+}
+
+/// The progress made by the backfill of one sub-range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RangeProgress {
+    /// The number of the last block ingested, `None` if no block of the
+    /// range was ingested.
+    pub last_block: Option<BlockNumber>,
+    /// Whether the stream for the range ended cleanly after all its blocks
+    /// were ingested. Chains like NEAR skip block numbers, so `last_block`
+    /// can be below the end of a range that is complete.
+    pub complete: bool,
+}
+
+impl RangeProgress {
+    fn is_complete(&self, range: &BackfillRange) -> bool {
+        self.complete || matches!(self.last_block, Some(last) if last >= range.stop)
+    }
+}
+
+/// Outcome of a backfill, with the progress made by each sub-range.
+#[derive(Clone, Debug, Default)]
+pub struct BackfillStatus {
+    pub ranges: Vec<(BackfillRange, RangeProgress)>,
+}
+
+impl BackfillStatus {
+    pub fn is_complete(&self) -> bool {
+        self.ranges
+            .iter()
+            .all(|(range, progress)| progress.is_complete(range))
+    }
+
+    /// The highest block number such that all blocks from the start of the
+    /// backfill up to it have been ingested.
+    pub fn contiguous_progress(&self) -> Option<BlockNumber> {
+        let mut contiguous = None;
+        for (range, progress) in &self.ranges {
+            if progress.is_complete(range) {
+                contiguous = Some(range.stop);
+                continue;
+            }
+            return progress.last_block.or(contiguous);
+        }
+        contiguous
+    }
+}
 
 pub struct FirehoseBlockIngestor<M>
 where
     M: prost::Message + BlockchainBlock + Default + 'static,
 {
     chain_store: Arc<dyn ChainStore>,
-    endpoint: Arc<FirehoseEndpoint>,
+    endpoints: FirehoseEndpoints,
     logger: Logger,
 
+    /// The fork steps requested from the Firehose when following the
+    /// chain head
+    fork_steps: Vec<ForkStep>,
+
+    backfill_target: Option<BlockNumber>,
+    backfill_concurrency: usize,
+
+    /// Counts how often the stored head cursor had to be discarded because
+    /// it did not match the blocks in the chain store
+    cursor_repairs: Option<Box<Counter>>,
+
+    /// Limits how fast we consume blocks while catching up with the chain
+    /// head
+    catch_up: CatchUp,
+
     phantom: PhantomData<M>,
 }
 
@@ -27,40 +305,171 @@ impl<M> FirehoseBlockIngestor<M>
 where
     M: prost::Message + BlockchainBlock + Default + 'static,
 {
+    /// Create an ingestor that streams blocks from `endpoints`, failing
+    /// over between them when an endpoint keeps failing. Panics if
+    /// `endpoints` is empty
     pub fn new(
         chain_store: Arc<dyn ChainStore>,
-        endpoint: Arc<FirehoseEndpoint>,
+        endpoints: FirehoseEndpoints,
         logger: Logger,
     ) -> FirehoseBlockIngestor<M> {
+        assert!(
+            endpoints.len() > 0,
+            "One Firehose endpoint should exist at that execution point"
+        );
+        let catch_up = CatchUp::new(logger.clone());
         FirehoseBlockIngestor {
             chain_store,
-            endpoint,
+            endpoints,
             logger,
+            fork_steps: vec![ForkStep::StepNew, ForkStep::StepUndo],
+            backfill_target: match *BACKFILL_TARGET {
+                0 => None,
+                target => Some(target),
+            },
+            backfill_concurrency: *BACKFILL_CONCURRENCY,
+            cursor_repairs: None,
+            catch_up,
             phantom: PhantomData {},
         }
     }
 
+    /// Request `fork_steps` instead of `StepNew` and `StepUndo` when
+    /// following the chain head. Requesting only `StepIrreversible` makes
+    /// the ingestor advance the chain head with final blocks only, which
+    /// is useful for chains with instant finality.
+    pub fn with_fork_steps(mut self, fork_steps: Vec<ForkStep>) -> Self {
+        self.fork_steps = fork_steps;
+        self
+    }
+
+    /// Backfill historical blocks up to `target` (inclusive) concurrently
+    /// with ingesting the chain head. Passing `None` disables the backfill.
+    pub fn with_backfill(mut self, target: Option<BlockNumber>, concurrency: usize) -> Self {
+        self.backfill_target = target;
+        self.backfill_concurrency = concurrency;
+        self
+    }
+
+    /// Report metrics for the chain `network` to `registry`
+    pub fn with_metrics(mut self, registry: Arc<dyn MetricsRegistry>, network: &str) -> Self {
+        self.cursor_repairs = registry
+            .new_counter_with_labels(
+                "firehose_ingestor_cursor_repairs",
+                "Number of times the stored chain head cursor did not match the chain store and was discarded",
+                std::iter::once((String::from("network"), network.to_string())).collect(),
+            )
+            .map_err(|e| error!(self.logger, "Failed to register cursor repair counter: {}", e))
+            .ok();
+        self
+    }
+
     pub async fn run(self) {
-        use firehose::ForkStep::*;
+        // A target set through `graphman chain backfill set-target` takes
+        // precedence over the one the ingestor was configured with
+        let target = match self.chain_store.chain_backfill_target_block_num() {
+            Ok(Some(target)) => Some(target),
+            Ok(None) => self.backfill_target,
+            Err(e) => {
+                error!(self.logger, "Fetching backfill target failed: {:?}", e);
+                self.backfill_target
+            }
+        };
 
-        let mut latest_cursor = self.fetch_head_cursor().await;
-        let mut backoff =
-            ExponentialBackoff::new(Duration::from_millis(250), Duration::from_secs(30));
+        match target {
+            Some(target) => {
+                let backfill = async {
+                    let status = self.run_backfill(target).await;
+                    info!(
+                        self.logger,
+                        "Backfill finished";
+                        "target" => target,
+                        "complete" => status.is_complete(),
+                        "progress" => format_args!("{:?}", status.contiguous_progress()),
+                    );
+                };
+                futures03::join!(self.run_head(), backfill);
+            }
+            None => self.run_head().await,
+        }
+    }
+
+    /// A pool of all our endpoints, starting with a random one so that
+    /// the nodes of a fleet spread out over the endpoints
+    fn endpoint_pool(&self) -> EndpointPool<Arc<FirehoseEndpoint>> {
+        let endpoints: Vec<_> = self.endpoints.iter().cloned().collect();
+        let start = rand::thread_rng().gen_range(0, endpoints.len());
+        EndpointPool::new(endpoints, start, *MAX_RETRIES, *CIRCUIT_OPEN_DURATION)
+    }
+
+    fn backoff() -> ExponentialBackoff {
+        ExponentialBackoff::new(Duration::from_millis(250), Duration::from_secs(30))
+            .with_jitter(BACKOFF_JITTER)
+    }
+
+    /// Record the outcome of an attempt to stream from the current endpoint
+    /// of `endpoints` and wait before the next attempt
+    async fn after_attempt(
+        logger: &Logger,
+        endpoints: &mut EndpointPool<Arc<FirehoseEndpoint>>,
+        backoff: &mut ExponentialBackoff,
+        progress: bool,
+    ) {
+        if progress {
+            endpoints.record_success();
+            backoff.reset();
+        } else {
+            let failed = endpoints.current();
+            if endpoints.record_failure(Instant::now()) {
+                let wait = endpoints.wait_time(Instant::now());
+                warn!(
+                    logger,
+                    "Too many failed attempts, opening circuit for endpoint";
+                    "endpoint uri" => format_args!("{}", failed),
+                    "next endpoint uri" => format_args!("{}", endpoints.current()),
+                    "wait_secs" => wait.as_secs(),
+                );
+                backoff.reset();
+                if wait > Duration::from_secs(0) {
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+
+        backoff.sleep_async().await;
+    }
+
+    async fn run_head(&self) {
+        let mut endpoints = self.endpoint_pool();
+        let mut backoff = Self::backoff();
+        // The provider that issued `latest_cursor`
+        let mut provider: Option<String> = None;
+        let mut latest_cursor = String::new();
+        let mut start_block_num = -1;
 
         loop {
+            let endpoint = endpoints.current();
+            if provider.as_deref() != Some(endpoint.provider.as_str()) {
+                // Cursors are specific to the provider that issued them
+                let (cursor, start) = self.head_position(&endpoint.provider).await;
+                latest_cursor = cursor;
+                start_block_num = start;
+                provider = Some(endpoint.provider.clone());
+            }
+
             info!(
                 self.logger,
-                "Blockstream disconnected, connecting"; "endpoint uri" => format_args!("{}", self.endpoint), "cursor" => format_args!("{}", latest_cursor),
+                "Blockstream disconnected, connecting"; "endpoint uri" => format_args!("{}", endpoint), "cursor" => format_args!("{}", latest_cursor),
             );
 
-            let result = self
-                .endpoint
-                .clone()
+            let previous_cursor = latest_cursor.clone();
+            let result = endpoint
                 .stream_blocks(firehose::Request {
-                    // Starts at current HEAD block of the chain (viewed from Firehose side)
-                    start_block_num: -1,
+                    // Without a cursor, starts at the current HEAD block of the chain
+                    // (viewed from Firehose side) unless the cursor was repaired
+                    start_block_num,
                     start_cursor: latest_cursor.clone(),
-                    fork_steps: vec![StepNew as i32, StepUndo as i32],
+                    fork_steps: self.fork_steps.iter().map(|step| *step as i32).collect(),
                     ..Default::default()
                 })
                 .await;
@@ -70,7 +479,9 @@ where
                     info!(self.logger, "Blockstream connected, consuming blocks");
 
                     // Consume the stream of blocks until an error is hit
-                    latest_cursor = self.process_blocks(latest_cursor, stream).await
+                    latest_cursor = self
+                        .process_blocks(&endpoint.provider, latest_cursor, stream)
+                        .await
                 }
                 Err(e) => {
                     error!(self.logger, "Unable to connect to endpoint: {:?}", e);
@@ -78,16 +489,324 @@ where
             }
 
             // If we reach this point, we must wait a bit before retrying
-            backoff.sleep_async().await;
+            let progress = latest_cursor != previous_cursor;
+            Self::after_attempt(&self.logger, &mut endpoints, &mut backoff, progress).await;
         }
     }
 
-    async fn fetch_head_cursor(&self) -> String {
+    /// Ingests all blocks from the start of the chain up to `target` into
+    /// the chain store. The range is split into `backfill_concurrency`
+    /// disjoint sub-ranges, each consumed by its own stream with its own
+    /// cursor, and the progress of all of them is merged once they are done.
+    ///
+    /// The backfill resumes after the progress recorded in the chain store.
+    /// Only the progress of the first sub-range is recorded while the
+    /// backfill runs, since only that one extends the blocks that are
+    /// backfilled contiguously from the start of the chain.
+    pub async fn run_backfill(&self, target: BlockNumber) -> BackfillStatus {
+        let (progress, cursor) = match self.chain_store.chain_backfill_progress() {
+            Ok(progress) => progress,
+            Err(e) => {
+                error!(self.logger, "Fetching backfill progress failed: {:?}", e);
+                (None, None)
+            }
+        };
+        let start = progress.map(|number| number + 1).unwrap_or(0);
+        let ranges = BackfillRange::split(start, target, self.backfill_concurrency);
+
+        info!(
+            self.logger,
+            "Starting backfill";
+            "start" => start,
+            "target" => target,
+            "ranges" => ranges.len(),
+        );
+
+        let progress = join_all(ranges.iter().enumerate().map(|(i, range)| {
+            let cursor = if i == 0 { cursor.clone() } else { None };
+            self.backfill_range(*range, cursor, i == 0)
+        }))
+        .await;
+
+        let status = BackfillStatus {
+            ranges: ranges.into_iter().zip(progress).collect(),
+        };
+        if let Some(progress) = status.contiguous_progress() {
+            if let Err(e) = self
+                .chain_store
+                .set_chain_backfill_progress(Some(progress), None)
+            {
+                error!(self.logger, "Recording backfill progress failed: {:?}", e);
+            }
+        }
+        status
+    }
+
+    /// Streams the irreversible blocks of `range` into the chain store,
+    /// reconnecting from the last received cursor until the end of the range
+    /// is reached, either because the stream ended cleanly or because it
+    /// served the last block of the range. Blocks are written in batches; if
+    /// `record` is set, the progress is recorded in the chain store after
+    /// each batch.
+    async fn backfill_range(
+        &self,
+        range: BackfillRange,
+        mut cursor: Option<BackfillCursor>,
+        record: bool,
+    ) -> RangeProgress {
+        use firehose::ForkStep::*;
+
+        let logger = self.logger.new(slog::o!(
+            "backfill_start" => range.start,
+            "backfill_stop" => range.stop,
+        ));
+        let mut last_block: Option<BlockNumber> = None;
+        let mut endpoints = self.endpoint_pool();
+        let mut backoff = Self::backoff();
+        let mut copier = self.chain_store.block_copier();
+
+        loop {
+            let endpoint = endpoints.current();
+            let (start_cursor, start_block) =
+                range.resume_position(cursor.as_ref(), &endpoint.provider, last_block);
+            debug!(logger, "Connecting backfill stream"; "endpoint uri" => format_args!("{}", endpoint), "cursor" => &start_cursor, "start_block" => start_block);
+
+            let previous_block = last_block;
+            let mut complete = false;
+            let result = endpoint
+                .stream_blocks(firehose::Request {
+                    start_block_num: start_block as i64,
+                    stop_block_num: range.stop as u64,
+                    start_cursor,
+                    fork_steps: vec![StepIrreversible as i32],
+                    ..Default::default()
+                })
+                .await;
+
+            match result {
+                Ok(mut stream) => {
+                    let mut batch = BlockBatch::new();
+                    // Whether the server ended the stream, which it only
+                    // does once it has sent every block of the range
+                    let mut ended = true;
+
+                    while let Some(message) = stream.next().await {
+                        let response = match message {
+                            Ok(response) => response,
+                            Err(e) => {
+                                info!(logger, "An error occurred while backfilling blocks: {}", e);
+                                ended = false;
+                                break;
+                            }
+                        };
+
+                        let block = match decode_firehose_block::<M>(&response)
+                            .context("Mapping firehose block to blockchain::Block")
+                        {
+                            Ok(block) => block,
+                            Err(e) => {
+                                error!(logger, "Process backfill block failed: {:?}", e);
+                                ended = false;
+                                break;
+                            }
+                        };
+                        trace!(logger, "Received backfill block to ingest {}", block.ptr());
+                        batch.push(block, response.cursor);
+
+                        if batch.is_full(*BATCH_SIZE, *BATCH_INTERVAL) {
+                            if !self
+                                .flush_backfill_batch(
+                                    &logger,
+                                    copier.as_mut(),
+                                    &endpoint.provider,
+                                    &mut batch,
+                                    record,
+                                    &mut last_block,
+                                    &mut cursor,
+                                )
+                                .await
+                            {
+                                ended = false;
+                                break;
+                            }
+                        }
+                    }
+
+                    let flushed = self
+                        .flush_backfill_batch(
+                            &logger,
+                            copier.as_mut(),
+                            &endpoint.provider,
+                            &mut batch,
+                            record,
+                            &mut last_block,
+                            &mut cursor,
+                        )
+                        .await;
+                    complete = ended && flushed;
+                }
+                Err(e) => {
+                    error!(logger, "Unable to connect to endpoint: {:?}", e);
+                }
+            }
+
+            let status = RangeProgress {
+                last_block,
+                complete,
+            };
+            if status.is_complete(&range) {
+                info!(logger, "Backfill range complete"; "last_block" => format_args!("{:?}", last_block));
+                return status;
+            }
+
+            let progress = last_block != previous_block;
+            Self::after_attempt(&logger, &mut endpoints, &mut backoff, progress).await;
+        }
+    }
+
+    /// Writes the blocks in `batch` to the chain store and advances
+    /// `last_block` and `cursor` past them; the blocks were streamed from
+    /// `provider`. Returns `false` if writing the blocks failed, in which
+    /// case they will be streamed again.
+    async fn flush_backfill_batch(
+        &self,
+        logger: &Logger,
+        copier: &mut dyn BlockCopier,
+        provider: &str,
+        batch: &mut BlockBatch,
+        record: bool,
+        last_block: &mut Option<BlockNumber>,
+        cursor: &mut Option<BackfillCursor>,
+    ) -> bool {
+        if batch.is_empty() {
+            return true;
+        }
+
+        let (blocks, batch_cursor) = batch.take();
+        let number = blocks.iter().map(|block| block.number()).max();
+
+        if let Err(e) = copier
+            .copy_blocks(blocks)
+            .await
+            .context("Inserting backfill blocks")
+        {
+            error!(logger, "Process backfill block failed: {:?}", e);
+            return false;
+        }
+
+        *last_block = number;
+        *cursor = Some(BackfillCursor {
+            provider: provider.to_string(),
+            cursor: batch_cursor,
+        });
+
+        if record {
+            if let Err(e) = self
+                .chain_store
+                .set_chain_backfill_progress(number, cursor.clone())
+            {
+                error!(logger, "Recording backfill progress failed: {:?}", e);
+            }
+        }
+        true
+    }
+
+    /// Check that the stored head `cursor` can be trusted. The cursor is
+    /// always written together with the chain head pointer; if the block
+    /// that pointer refers to is not in the chain store, for example after
+    /// a partial restore of the database, the cursor does not match the
+    /// blocks we have. In that case, the cursor is discarded and streaming
+    /// resumes right after the newest block that is actually stored.
+    /// Returns the cursor and the block number to start streaming from
+    fn verify_head_cursor(&self, cursor: String) -> (String, i64) {
+        // Start at the current chain head unless we find out otherwise
+        const HEAD: i64 = -1;
+
+        if cursor.is_empty() {
+            return (cursor, HEAD);
+        }
+
+        let head = match self.chain_store.chain_head_ptr() {
+            Ok(head) => head,
+            Err(e) => {
+                error!(self.logger, "Fetching chain head failed: {:?}", e);
+                return (cursor, HEAD);
+            }
+        };
+        let stored = match &head {
+            Some(head) => self.chain_store.block_number(head.hash_as_h256()),
+            None => Ok(None),
+        };
+        match stored {
+            Ok(Some(_)) => return (cursor, HEAD),
+            Ok(None) => { /* the cursor does not match the chain store */ }
+            Err(e) => {
+                error!(self.logger, "Checking chain head cursor failed: {:?}", e);
+                return (cursor, HEAD);
+            }
+        }
+
+        let latest = self
+            .chain_store
+            .latest_stored_block_ptr()
+            .unwrap_or_else(|e| {
+                error!(self.logger, "Fetching newest stored block failed: {:?}", e);
+                None
+            });
+        warn!(
+            self.logger,
+            "Chain head cursor refers to a block that is not in the chain store, \
+             discarding it and resuming after the newest stored block";
+            "head" => format_args!("{:?}", head),
+            "newest_stored_block" => format_args!("{:?}", latest),
+        );
+        if let Some(counter) = &self.cursor_repairs {
+            counter.inc();
+        }
+        if let Err(e) = self.chain_store.clear_chain_head_cursor() {
+            error!(self.logger, "Clearing chain head cursor failed: {:?}", e);
+        }
+
+        let start_block_num = latest.map(|ptr| ptr.number as i64 + 1).unwrap_or(HEAD);
+        (String::new(), start_block_num)
+    }
+
+    /// Determine where streaming from `provider` starts: with the cursor
+    /// that `provider` gave us for the current chain head if there is one.
+    /// Otherwise, the chain head was advanced by another provider whose
+    /// cursors `provider` can not use, and streaming starts right after the
+    /// chain head by block number. Returns the cursor and the block number
+    /// to start streaming from
+    async fn head_position(&self, provider: &str) -> (String, i64) {
+        // Start at the current chain head unless we find out otherwise
+        const HEAD: i64 = -1;
+
+        let stored = self.fetch_head_cursor(provider).await;
+        let head = self.chain_store.chain_head_ptr().unwrap_or_else(|e| {
+            error!(self.logger, "Fetching chain head failed: {:?}", e);
+            None
+        });
+        match (stored, head) {
+            (Some((cursor, ptr)), Some(head)) if ptr == head => self.verify_head_cursor(cursor),
+            (_, Some(head)) => {
+                info!(
+                    self.logger,
+                    "No cursor from this provider for the chain head, starting after the chain head";
+                    "provider" => provider,
+                    "head" => format_args!("{}", head),
+                );
+                (String::new(), head.number as i64 + 1)
+            }
+            (_, None) => (String::new(), HEAD),
+        }
+    }
+
+    async fn fetch_head_cursor(&self, provider: &str) -> Option<(String, BlockPtr)> {
         let mut backoff =
             ExponentialBackoff::new(Duration::from_millis(250), Duration::from_secs(30));
         loop {
-            match self.chain_store.clone().chain_head_cursor() {
-                Ok(cursor) => return cursor.unwrap_or_else(|| "".to_string()),
+            match self.chain_store.provider_head_cursor(provider) {
+                Ok(cursor) => return cursor,
                 Err(e) => {
                     error!(self.logger, "Fetching chain head cursor failed: {:?}", e);
 
@@ -100,30 +819,84 @@ where
     /// Consumes the incoming stream of blocks infinitely until it hits an error. In which case
     /// the error is logged right away and the latest available cursor is returned
     /// upstream for future consumption.
+    ///
+    /// New blocks are written in batches while the stream has blocks ready,
+    /// i.e., while we are catching up; once we are at the chain head, every
+    /// block is written as soon as it arrives.
     async fn process_blocks(
         &self,
+        provider: &str,
         cursor: String,
-        mut stream: Streaming<firehose::Response>,
+        mut stream: FirehoseStream,
     ) -> String {
-        use firehose::ForkStep;
         use firehose::ForkStep::*;
 
         let mut latest_cursor = cursor;
+        let mut batch = BlockBatch::new();
+
+        loop {
+            let (message, catching_up) = match stream.next().now_or_never() {
+                Some(message) => (message, true),
+                None => {
+                    // No block is ready, we are caught up
+                    if let Err(e) = self
+                        .flush_batch(provider, &mut batch, &mut latest_cursor)
+                        .await
+                    {
+                        error!(self.logger, "Process block failed: {:?}", e);
+                        break;
+                    }
+                    (stream.next().await, false)
+                }
+            };
 
-        while let Some(message) = stream.next().await {
             match message {
-                Ok(v) => {
+                Some(Ok(v)) => {
                     let step = ForkStep::from_i32(v.step)
                         .expect("Fork step should always match to known value");
 
                     let result = match step {
-                        StepNew => self.process_new_block(&v).await,
-                        StepUndo => {
-                            trace!(self.logger, "Received undo block to ingest, skipping");
-                            Ok(())
+                        StepNew => {
+                            if catching_up {
+                                self.catch_up.acquire().await;
+                            }
+                            self.process_new_block(
+                                provider,
+                                &mut batch,
+                                &mut latest_cursor,
+                                v,
+                                catching_up,
+                            )
+                            .await
+                        }
+                        StepUndo => match self
+                            .flush_batch(provider, &mut batch, &mut latest_cursor)
+                            .await
+                        {
+                            Ok(()) => {
+                                trace!(self.logger, "Received undo block to ingest, skipping");
+                                latest_cursor = v.cursor;
+                                Ok(())
+                            }
+                            Err(e) => Err(e),
+                        },
+                        StepIrreversible => {
+                            match self
+                                .flush_batch(provider, &mut batch, &mut latest_cursor)
+                                .await
+                            {
+                                Ok(()) => {
+                                    self.process_irreversible_block(provider, &v)
+                                        .await
+                                        .map(|()| {
+                                            latest_cursor = v.cursor;
+                                        })
+                                }
+                                Err(e) => Err(e),
+                            }
                         }
-                        StepIrreversible | StepUnknown => panic!(
-                            "We explicitly requested StepNew|StepUndo but received something else"
+                        StepUnknown => panic!(
+                            "We explicitly requested known fork steps but received something else"
                         ),
                     };
 
@@ -131,19 +904,25 @@ where
                         error!(self.logger, "Process block failed: {:?}", e);
                         break;
                     }
-
-                    latest_cursor = v.cursor;
                 }
-                Err(e) => {
+                Some(Err(e)) => {
                     info!(
                         self.logger,
                         "An error occurred while streaming blocks: {}", e
                     );
                     break;
                 }
+                None => break,
             }
         }
 
+        if let Err(e) = self
+            .flush_batch(provider, &mut batch, &mut latest_cursor)
+            .await
+        {
+            error!(self.logger, "Process block failed: {:?}", e);
+        }
+
         error!(
             self.logger,
             "Stream blocks complete unexpectedly, expecting stream to always stream blocks"
@@ -151,18 +930,260 @@ where
         latest_cursor
     }
 
-    async fn process_new_block(&self, response: &firehose::Response) -> Result<(), Error> {
-        let block = decode_firehose_block::<M>(response)
+    /// Adds the new block to `batch`, writing the batch once it is full.
+    /// The cursor of the block only becomes the latest cursor once the
+    /// batch has been written.
+    async fn process_new_block(
+        &self,
+        provider: &str,
+        batch: &mut BlockBatch,
+        latest_cursor: &mut String,
+        response: firehose::Response,
+        catching_up: bool,
+    ) -> Result<(), Error> {
+        let block = decode_firehose_block::<M>(&response)
             .context("Mapping firehose block to blockchain::Block")?;
 
         trace!(self.logger, "Received new block to ingest {}", block.ptr());
+        if catching_up {
+            self.catch_up.record(block.number(), None);
+        }
+
+        batch.push(block, response.cursor);
+        if batch.is_full(*BATCH_SIZE, *BATCH_INTERVAL) {
+            self.flush_batch(provider, batch, latest_cursor).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes all blocks in `batch` and makes the last of them the chain
+    /// head with a single update, then advances `latest_cursor`.
+    async fn flush_batch(
+        &self,
+        provider: &str,
+        batch: &mut BlockBatch,
+        latest_cursor: &mut String,
+    ) -> Result<(), Error> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let (blocks, cursor) = batch.take();
+        debug!(self.logger, "Writing batch of new blocks"; "blocks" => blocks.len());
 
         self.chain_store
             .clone()
-            .set_chain_head(block, response.cursor.clone())
+            .set_chain_head_batch(blocks, cursor.clone(), provider)
             .await
             .context("Updating chain head")?;
 
+        *latest_cursor = cursor;
         Ok(())
     }
+
+    /// Records the block as the latest final block of the chain. When we
+    /// do not follow new blocks, final blocks also advance the chain head.
+    async fn process_irreversible_block(
+        &self,
+        provider: &str,
+        response: &firehose::Response,
+    ) -> Result<(), Error> {
+        let block = decode_firehose_block::<M>(response)
+            .context("Mapping firehose block to blockchain::Block")?;
+        let ptr = block.ptr();
+
+        trace!(self.logger, "Received irreversible block to ingest {}", ptr);
+
+        if !self.fork_steps.contains(&ForkStep::StepNew) {
+            self.chain_store
+                .clone()
+                .set_chain_head(block, response.cursor.clone(), provider)
+                .await
+                .context("Updating chain head")?;
+        }
+
+        self.chain_store
+            .set_chain_final_block(&ptr)
+            .context("Updating final block")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{
+        BackfillCursor, BackfillRange, BackfillStatus, BlockBatch, EndpointPool, RangeProgress,
+    };
+
+    fn range(start: i32, stop: i32) -> BackfillRange {
+        BackfillRange { start, stop }
+    }
+
+    fn last(last_block: Option<i32>) -> RangeProgress {
+        RangeProgress {
+            last_block,
+            complete: false,
+        }
+    }
+
+    fn ended(last_block: Option<i32>) -> RangeProgress {
+        RangeProgress {
+            last_block,
+            complete: true,
+        }
+    }
+
+    #[test]
+    fn split_backfill_range() {
+        assert_eq!(
+            BackfillRange::split(0, 9, 3),
+            vec![range(0, 3), range(4, 6), range(7, 9)]
+        );
+        assert_eq!(
+            BackfillRange::split(0, 1, 4),
+            vec![range(0, 0), range(1, 1)]
+        );
+        assert_eq!(BackfillRange::split(5, 5, 0), vec![range(5, 5)]);
+        assert_eq!(BackfillRange::split(5, 4, 2), vec![]);
+    }
+
+    #[test]
+    fn resume_backfill_range() {
+        let cursor = BackfillCursor {
+            provider: "p1".to_string(),
+            cursor: "c1".to_string(),
+        };
+        let backfill = range(10, 20);
+
+        assert_eq!(
+            backfill.resume_position(None, "p1", None),
+            (String::new(), 10)
+        );
+        assert_eq!(
+            backfill.resume_position(Some(&cursor), "p1", None),
+            ("c1".to_string(), 10)
+        );
+        assert_eq!(
+            backfill.resume_position(Some(&cursor), "p1", Some(14)),
+            ("c1".to_string(), 15)
+        );
+        // After failing over to another provider, the cursor is useless
+        assert_eq!(
+            backfill.resume_position(Some(&cursor), "p2", Some(14)),
+            (String::new(), 15)
+        );
+        assert_eq!(
+            backfill.resume_position(Some(&cursor), "p2", None),
+            (String::new(), 10)
+        );
+    }
+
+    #[test]
+    fn backfill_status_progress() {
+        let status = BackfillStatus {
+            ranges: vec![
+                (range(0, 3), last(Some(3))),
+                (range(4, 6), last(Some(5))),
+                (range(7, 9), last(Some(9))),
+            ],
+        };
+        assert!(!status.is_complete());
+        assert_eq!(status.contiguous_progress(), Some(5));
+
+        let status = BackfillStatus {
+            ranges: vec![(range(0, 3), last(Some(3))), (range(4, 6), last(None))],
+        };
+        assert_eq!(status.contiguous_progress(), Some(3));
+
+        let status = BackfillStatus {
+            ranges: vec![(range(0, 3), last(Some(3))), (range(4, 6), last(Some(6)))],
+        };
+        assert!(status.is_complete());
+        assert_eq!(status.contiguous_progress(), Some(6));
+    }
+
+    #[test]
+    fn backfill_status_with_skipped_blocks() {
+        // The streams ended cleanly although the chain skipped the last
+        // blocks of the first two ranges, and all blocks of the second one
+        let status = BackfillStatus {
+            ranges: vec![
+                (range(0, 3), ended(Some(2))),
+                (range(4, 6), ended(None)),
+                (range(7, 9), ended(Some(9))),
+            ],
+        };
+        assert!(status.is_complete());
+        assert_eq!(status.contiguous_progress(), Some(9));
+
+        // A stream that broke off is not complete, even if the blocks it
+        // is still missing were skipped by the chain
+        let status = BackfillStatus {
+            ranges: vec![(range(0, 3), ended(Some(2))), (range(4, 6), last(Some(5)))],
+        };
+        assert!(!status.is_complete());
+        assert_eq!(status.contiguous_progress(), Some(5));
+
+        let status = BackfillStatus {
+            ranges: vec![(range(0, 3), last(Some(2))), (range(4, 6), ended(Some(6)))],
+        };
+        assert!(!status.is_complete());
+        assert_eq!(status.contiguous_progress(), Some(2));
+    }
+
+    #[test]
+    fn block_batch_is_full() {
+        let mut batch = BlockBatch::new();
+        assert!(batch.is_empty());
+        assert!(!batch.is_full(1, Duration::from_secs(1)));
+        assert!(batch.is_full(0, Duration::from_secs(1)));
+
+        batch.started = Some(Instant::now() - Duration::from_secs(2));
+        assert!(batch.is_full(100, Duration::from_secs(1)));
+        assert!(!batch.is_full(100, Duration::from_secs(60)));
+
+        let (blocks, _) = batch.take();
+        assert!(blocks.is_empty());
+        assert!(!batch.is_full(100, Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn endpoint_pool_fails_over() {
+        let open = Duration::from_secs(60);
+        let mut pool = EndpointPool::new(vec!["a", "b", "c"], 1, 2, open);
+        let now = Instant::now();
+        assert_eq!("b", pool.current());
+
+        // Progress resets the failure count
+        assert!(!pool.record_failure(now));
+        pool.record_success();
+        assert!(!pool.record_failure(now));
+        assert_eq!("b", pool.current());
+
+        // The circuit for `b` opens and we move on to `c`
+        assert!(pool.record_failure(now));
+        assert_eq!("c", pool.current());
+        assert_eq!(Duration::from_secs(0), pool.wait_time(now));
+
+        assert!(!pool.record_failure(now));
+        assert!(pool.record_failure(now));
+        assert_eq!("a", pool.current());
+
+        // With all circuits open, we wait for the one that closes first
+        let later = now + Duration::from_secs(1);
+        assert!(!pool.record_failure(later));
+        assert!(pool.record_failure(later));
+        assert_eq!("b", pool.current());
+        assert_eq!(open - Duration::from_secs(1), pool.wait_time(later));
+
+        // Once its circuit closed, `b` is used again
+        let reopened = now + open;
+        assert_eq!(Duration::from_secs(0), pool.wait_time(reopened));
+        assert!(!pool.record_failure(reopened));
+        assert!(pool.record_failure(reopened));
+        assert_eq!("c", pool.current());
+    }
 }
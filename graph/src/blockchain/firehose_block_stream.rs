@@ -7,8 +7,9 @@ use std::time::Duration;
 use crate::prelude::*;
 use crate::util::backoff::ExponentialBackoff;
 
-use super::block_stream::{BlockStream, BlockStreamEvent, FirehoseMapper};
-use super::Blockchain;
+use super::block_stream::{BlockStream, BlockStreamEvent, BlockStreamMetrics, FirehoseMapper};
+use super::firehose_multiplexer::FirehoseUpstream;
+use super::{Block, BlockPtr, Blockchain, TriggerFilter};
 use crate::{firehose, firehose::FirehoseEndpoint};
 
 pub struct FirehoseBlockStream<C: Blockchain> {
@@ -19,14 +20,24 @@ impl<C> FirehoseBlockStream<C>
 where
     C: Blockchain,
 {
+    /// Stream blocks starting at `cursor`. Without a cursor, the stream
+    /// starts right after `subgraph_current_block`, which makes it possible
+    /// to switch a deployment that was indexed with the polling block
+    /// stream over to Firehose.
+    ///
+    /// With an `upstream`, the stream switches over to that shared stream
+    /// as soon as the block it reached is in the upstream's backlog
     pub fn new<F>(
         endpoint: Arc<FirehoseEndpoint>,
+        upstream: Option<Arc<FirehoseUpstream>>,
         cursor: Option<String>,
+        subgraph_current_block: Option<BlockPtr>,
         mapper: Arc<F>,
         adapter: Arc<C::TriggersAdapter>,
         filter: Arc<C::TriggerFilter>,
         start_blocks: Vec<BlockNumber>,
         logger: Logger,
+        metrics: Arc<BlockStreamMetrics>,
     ) -> Self
     where
         F: FirehoseMapper<C> + 'static,
@@ -38,15 +49,27 @@ where
             // start at Genesis block.
             .unwrap_or(0);
 
+        // The block pointer of the subgraph is the only checkpoint we have if
+        // the subgraph was not indexed from Firehose so far. We need to make
+        // sure that the first block we get from Firehose actually follows it
+        let cursor = cursor.filter(|cursor| !cursor.is_empty());
+        let (start_block_num, expected_parent) = match (&cursor, subgraph_current_block) {
+            (None, Some(ptr)) => (ptr.number + 1, Some(ptr)),
+            _ => (start_block_num, None),
+        };
+
         FirehoseBlockStream {
             stream: Box::pin(stream_blocks(
                 endpoint,
+                upstream,
                 cursor,
                 mapper,
                 adapter,
                 filter,
                 start_block_num,
+                expected_parent,
                 logger,
+                metrics,
             )),
         }
     }
@@ -54,20 +77,67 @@ where
 
 fn stream_blocks<C: Blockchain, F: FirehoseMapper<C>>(
     endpoint: Arc<FirehoseEndpoint>,
+    upstream: Option<Arc<FirehoseUpstream>>,
     cursor: Option<String>,
     mapper: Arc<F>,
     adapter: Arc<C::TriggersAdapter>,
     filter: Arc<C::TriggerFilter>,
     start_block_num: BlockNumber,
+    mut expected_parent: Option<BlockPtr>,
     logger: Logger,
+    metrics: Arc<BlockStreamMetrics>,
 ) -> impl Stream<Item = Result<BlockStreamEvent<C>, Error>> {
     use firehose::ForkStep::*;
 
+    let transforms = filter.to_firehose_filter();
+
     try_stream! {
         let mut latest_cursor = cursor.unwrap_or_else(|| "".to_string());
-        let mut backoff = ExponentialBackoff::new(Duration::from_millis(500), Duration::from_secs(45));
+        let mut backoff = ExponentialBackoff::new(Duration::from_millis(500), Duration::from_secs(45))
+            .with_jitter(0.5);
+        // A subgraph that is at a block the shared stream still has in its
+        // backlog can start off following the shared stream right away
+        let mut joined = match (&upstream, &expected_parent) {
+            (Some(upstream), Some(ptr)) => upstream.join(StepNew, ptr),
+            _ => None,
+        };
 
         loop {
+            if let Some((mut pending, mut receiver)) = joined.take() {
+                info!(&logger, "Following shared Firehose stream");
+                expected_parent = None;
+
+                loop {
+                    let shared = match pending.pop_front() {
+                        Some(shared) => shared,
+                        None => match receiver.recv().await {
+                            Ok(shared) => shared,
+                            Err(e) => {
+                                info!(&logger, "Leaving shared Firehose stream"; "reason" => e.to_string());
+                                break;
+                            }
+                        },
+                    };
+
+                    match mapper.to_block_stream_event(&logger, &shared.response, &adapter, &filter).await {
+                        Ok(event) => {
+                            match &event {
+                                BlockStreamEvent::ProcessBlock(block, _) => metrics.block_produced(&block.block),
+                                BlockStreamEvent::Revert(..) => metrics.cursor_moved(),
+                            }
+
+                            yield event;
+
+                            latest_cursor = shared.response.cursor.clone();
+                        }
+                        Err(e) => {
+                            error!(logger, "Mapping block to BlockStreamEvent failed: {:?}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+
             info!(
                 &logger,
                 "Blockstream disconnected, connecting";
@@ -82,6 +152,7 @@ fn stream_blocks<C: Blockchain, F: FirehoseMapper<C>>(
                 start_block_num: start_block_num as i64,
                 start_cursor: latest_cursor.clone(),
                 fork_steps: vec![StepNew as i32, StepUndo as i32],
+                transforms: transforms.clone(),
                 ..Default::default()
             }).await;
 
@@ -95,9 +166,38 @@ fn stream_blocks<C: Blockchain, F: FirehoseMapper<C>>(
                             Ok(v) => {
                                 match mapper.to_block_stream_event(&logger, &v, &adapter, &filter).await {
                                     Ok(event) => {
+                                        let (step, ptr) = match &event {
+                                            BlockStreamEvent::ProcessBlock(block, _) => {
+                                                if let Some(parent) = expected_parent.take() {
+                                                    if block.block.parent_ptr().as_ref() != Some(&parent) {
+                                                        Err(anyhow!(
+                                                            "block {} from Firehose does not follow block {} \
+                                                             that the subgraph is at, the subgraph's block is \
+                                                             not on the chain that Firehose follows",
+                                                            block.block.ptr(),
+                                                            parent
+                                                        ))?;
+                                                    }
+                                                }
+                                                metrics.block_produced(&block.block);
+                                                (StepNew, block.block.ptr())
+                                            }
+                                            BlockStreamEvent::Revert(from, _, _) => {
+                                                metrics.cursor_moved();
+                                                (StepUndo, from.clone())
+                                            }
+                                        };
+
                                         yield event;
 
                                         latest_cursor = v.cursor;
+
+                                        // Leave this private stream for the shared one
+                                        // once the shared one has caught up with us
+                                        joined = upstream.as_ref().and_then(|upstream| upstream.join(step, &ptr));
+                                        if joined.is_some() {
+                                            break;
+                                        }
                                     },
                                     Err(e) => {
                                         error!(
@@ -115,6 +215,10 @@ fn stream_blocks<C: Blockchain, F: FirehoseMapper<C>>(
                         }
                     }
 
+                    if joined.is_some() {
+                        continue;
+                    }
+
                     error!(logger, "Stream blocks complete unexpectedly, expecting stream to always stream blocks");
                 },
                 Err(e) => {
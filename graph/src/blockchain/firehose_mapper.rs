@@ -0,0 +1,93 @@
+use anyhow::anyhow;
+
+use super::block_stream::{BlockStreamEvent, FirehoseError, FirehoseMapper, TriggersAdapter};
+use super::{Block, BlockPtr, Blockchain};
+use crate::firehose::{self, decode_firehose_block_header, ForkStep};
+use crate::prelude::*;
+
+/// The chain specific part of turning Firehose responses into block stream
+/// events. Chains only need to say how their blocks are decoded, everything
+/// else is handled by `GenericFirehoseMapper`
+pub trait FirehoseBlockDecoder<C: Blockchain>: Send + Sync {
+    /// A message that declares only the header fields of the chain's full
+    /// block message, with the same tags. It is used when only the block
+    /// pointers are needed, e.g., for reverts
+    type HeaderOnlyBlock: prost::Message + Block + Default;
+
+    /// Decode the full block payload of a Firehose response
+    fn decode_block(&self, payload: &[u8]) -> Result<C::Block, FirehoseError>;
+}
+
+/// A `FirehoseMapper` that handles all fork steps and the cursor in the
+/// same way for every chain and delegates decoding blocks to `D`
+pub struct GenericFirehoseMapper<D> {
+    decoder: D,
+}
+
+impl<D> GenericFirehoseMapper<D> {
+    pub fn new(decoder: D) -> Self {
+        GenericFirehoseMapper { decoder }
+    }
+}
+
+#[async_trait]
+impl<C, D> FirehoseMapper<C> for GenericFirehoseMapper<D>
+where
+    C: Blockchain,
+    D: FirehoseBlockDecoder<C>,
+{
+    async fn to_block_stream_event(
+        &self,
+        logger: &Logger,
+        response: &firehose::Response,
+        adapter: &C::TriggersAdapter,
+        filter: &C::TriggerFilter,
+    ) -> Result<BlockStreamEvent<C>, FirehoseError> {
+        let step = ForkStep::from_i32(response.step).ok_or_else(|| {
+            anyhow!(
+                "unknown step i32 value {}, maybe you forgot update & re-regenerate the protobuf definitions?",
+                response.step
+            )
+        })?;
+        let cursor = Some(response.cursor.clone());
+
+        use ForkStep::*;
+        match step {
+            // When only irreversible steps are requested, every block is
+            // sent exactly once as irreversible, and is processed like a
+            // new block
+            StepNew | StepIrreversible => {
+                let any_block = response
+                    .block
+                    .as_ref()
+                    .expect("block payload information should always be present");
+                let block = self.decoder.decode_block(any_block.value.as_ref())?;
+
+                Ok(BlockStreamEvent::ProcessBlock(
+                    adapter.triggers_in_block(logger, block, filter).await?,
+                    cursor,
+                ))
+            }
+
+            StepUndo => {
+                // A revert only needs the block pointers, decoding the
+                // whole block would be wasted effort
+                let block = decode_firehose_block_header::<D::HeaderOnlyBlock>(response)?;
+                let parent_ptr = block
+                    .parent_ptr()
+                    .ok_or_else(|| anyhow!("genesis block {} can not be reverted", block.ptr()))?;
+
+                Ok(BlockStreamEvent::Revert(block.ptr(), parent_ptr, cursor))
+            }
+
+            StepUnknown => {
+                Err(anyhow!("unknown step should not happen in the Firehose response").into())
+            }
+        }
+    }
+
+    fn block_ptr(&self, response: &firehose::Response) -> Result<BlockPtr, FirehoseError> {
+        let block = decode_firehose_block_header::<D::HeaderOnlyBlock>(response)?;
+        Ok(block.ptr())
+    }
+}
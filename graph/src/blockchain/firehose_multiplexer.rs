@@ -0,0 +1,319 @@
+//! Sharing of upstream Firehose streams between deployments.
+//!
+//! Deployments on the same chain often send identical filters to Firehose
+//! and therefore receive identical streams. The multiplexer keeps one
+//! upstream stream per endpoint and filter and fans its responses out to
+//! every block stream that subscribed to it. Subscribers keep track of
+//! their own cursor; they only join the upstream stream once they reached
+//! a block that is still in its backlog, and fall back to a private stream
+//! from their own cursor whenever they fall too far behind.
+
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use futures03::StreamExt;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use super::block_stream::FirehoseMapper;
+use super::{BlockPtr, Blockchain};
+use crate::{
+    env::env_var,
+    firehose::{self, FirehoseEndpoint, ForkStep},
+    prelude::{debug, error, info, lazy_static, o, Logger},
+    util::backoff::ExponentialBackoff,
+};
+
+lazy_static! {
+    /// Whether block streams for deployments with identical filters share
+    /// one upstream Firehose stream.
+    pub static ref MULTIPLEXER_ENABLED: bool = env_var("GRAPH_FIREHOSE_MULTIPLEXER", false);
+
+    /// Number of responses each upstream stream keeps around for block
+    /// streams that want to join it. Also bounds how far a subscriber can
+    /// fall behind before it is moved back to a private stream.
+    static ref BACKLOG_SIZE: usize = env_var("GRAPH_FIREHOSE_MULTIPLEXER_BACKLOG", 250);
+}
+
+/// A response of an upstream stream, together with the position it moves
+/// subscribers to
+pub struct SharedResponse {
+    pub step: ForkStep,
+    pub ptr: BlockPtr,
+    pub response: firehose::Response,
+}
+
+struct State {
+    backlog: VecDeque<Arc<SharedResponse>>,
+    sender: broadcast::Sender<Arc<SharedResponse>>,
+}
+
+struct Shared {
+    backlog_size: usize,
+    // The sender lives behind the same lock as the backlog so that a
+    // subscriber that joins sees every response exactly once: either in
+    // the backlog or through its receiver
+    state: Mutex<State>,
+}
+
+impl Shared {
+    fn push(&self, response: SharedResponse) {
+        let response = Arc::new(response);
+        let mut state = self.state.lock().unwrap();
+        state.backlog.push_back(response.clone());
+        while state.backlog.len() > self.backlog_size {
+            state.backlog.pop_front();
+        }
+        // Not having any receivers is not an error, subscribers may still
+        // join from the backlog
+        let _ = state.sender.send(response);
+    }
+}
+
+/// One upstream Firehose stream. The stream is closed when the last block
+/// stream that holds on to it is dropped
+pub struct FirehoseUpstream {
+    shared: Arc<Shared>,
+    task: JoinHandle<()>,
+}
+
+impl FirehoseUpstream {
+    /// Join the upstream stream at the position a subscriber reached by
+    /// processing the response with `step` for the block `ptr`. Returns the
+    /// responses from the backlog that follow that position and a receiver
+    /// for all later responses, or `None` if the position is not in the
+    /// backlog
+    pub fn join(
+        &self,
+        step: ForkStep,
+        ptr: &BlockPtr,
+    ) -> Option<(
+        VecDeque<Arc<SharedResponse>>,
+        broadcast::Receiver<Arc<SharedResponse>>,
+    )> {
+        let state = self.shared.state.lock().unwrap();
+        let pos = state
+            .backlog
+            .iter()
+            .rposition(|response| response.step == step && &response.ptr == ptr)?;
+        let pending = state.backlog.iter().skip(pos + 1).cloned().collect();
+        Some((pending, state.sender.subscribe()))
+    }
+}
+
+impl Drop for FirehoseUpstream {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+type UpstreamKey = (String, Vec<(String, Vec<u8>)>);
+
+/// Keeps one upstream Firehose stream per endpoint and filter. Each chain
+/// owns its own multiplexer
+pub struct FirehoseMultiplexer<C: Blockchain> {
+    logger: Logger,
+    upstreams: Mutex<HashMap<UpstreamKey, Weak<FirehoseUpstream>>>,
+    phantom: PhantomData<C>,
+}
+
+impl<C: Blockchain> FirehoseMultiplexer<C> {
+    pub fn new(logger: Logger) -> Self {
+        FirehoseMultiplexer {
+            logger,
+            upstreams: Mutex::new(HashMap::new()),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Return the upstream stream for `endpoint` and `transforms`, starting
+    /// one if there is none yet. Returns `None` if the multiplexer is
+    /// disabled
+    pub fn subscribe<F>(
+        &self,
+        endpoint: &Arc<FirehoseEndpoint>,
+        transforms: Vec<prost_types::Any>,
+        mapper: Arc<F>,
+    ) -> Option<Arc<FirehoseUpstream>>
+    where
+        F: FirehoseMapper<C> + 'static,
+    {
+        if !*MULTIPLEXER_ENABLED {
+            return None;
+        }
+
+        let key = (
+            endpoint.provider.clone(),
+            transforms
+                .iter()
+                .map(|transform| (transform.type_url.clone(), transform.value.clone()))
+                .collect(),
+        );
+
+        let mut upstreams = self.upstreams.lock().unwrap();
+        upstreams.retain(|_, upstream| upstream.strong_count() > 0);
+        if let Some(upstream) = upstreams.get(&key).and_then(Weak::upgrade) {
+            return Some(upstream);
+        }
+
+        let backlog_size = *BACKLOG_SIZE;
+        let (sender, _) = broadcast::channel(backlog_size.max(1));
+        let shared = Arc::new(Shared {
+            backlog_size,
+            state: Mutex::new(State {
+                backlog: VecDeque::with_capacity(backlog_size),
+                sender,
+            }),
+        });
+        let logger = self.logger.new(o!(
+            "component" => "FirehoseMultiplexer",
+            "provider" => endpoint.provider.clone(),
+        ));
+        info!(logger, "Starting shared Firehose stream";
+            "upstreams" => upstreams.len() + 1);
+        let task = crate::task_spawn::spawn(run_upstream::<C, F>(
+            logger,
+            endpoint.clone(),
+            transforms,
+            mapper,
+            shared.clone(),
+        ));
+
+        let upstream = Arc::new(FirehoseUpstream { shared, task });
+        upstreams.insert(key, Arc::downgrade(&upstream));
+        Some(upstream)
+    }
+}
+
+async fn run_upstream<C, F>(
+    logger: Logger,
+    endpoint: Arc<FirehoseEndpoint>,
+    transforms: Vec<prost_types::Any>,
+    mapper: Arc<F>,
+    shared: Arc<Shared>,
+) where
+    C: Blockchain,
+    F: FirehoseMapper<C>,
+{
+    use firehose::ForkStep::*;
+
+    let mut latest_cursor = String::new();
+    let mut backoff = ExponentialBackoff::new(Duration::from_millis(500), Duration::from_secs(45))
+        .with_jitter(0.5);
+
+    loop {
+        // Start a little behind the chain head so that the backlog is
+        // filled right away and subscribers close to the head can join
+        let result = endpoint
+            .clone()
+            .stream_blocks(firehose::Request {
+                start_block_num: -(shared.backlog_size as i64),
+                start_cursor: latest_cursor.clone(),
+                fork_steps: vec![StepNew as i32, StepUndo as i32],
+                transforms: transforms.clone(),
+                ..Default::default()
+            })
+            .await;
+
+        match result {
+            Ok(mut stream) => {
+                debug!(logger, "Shared Firehose stream connected"; "cursor" => &latest_cursor);
+                backoff.reset();
+
+                while let Some(response) = stream.next().await {
+                    let response = match response {
+                        Ok(response) => response,
+                        Err(e) => {
+                            info!(logger, "An error occurred while streaming blocks: {:?}", e);
+                            break;
+                        }
+                    };
+                    let step = match ForkStep::from_i32(response.step) {
+                        Some(step @ StepNew) | Some(step @ StepUndo) => step,
+                        _ => {
+                            error!(
+                                logger,
+                                "Unexpected step {} in shared Firehose stream", response.step
+                            );
+                            break;
+                        }
+                    };
+                    let ptr = match mapper.block_ptr(&response) {
+                        Ok(ptr) => ptr,
+                        Err(e) => {
+                            error!(logger, "Decoding block header failed: {:?}", e);
+                            break;
+                        }
+                    };
+
+                    latest_cursor = response.cursor.clone();
+                    shared.push(SharedResponse {
+                        step,
+                        ptr,
+                        response,
+                    });
+                }
+            }
+            Err(e) => {
+                error!(logger, "Unable to connect to endpoint: {:?}", e);
+            }
+        }
+
+        backoff.sleep_async().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ptr(number: i32) -> BlockPtr {
+        BlockPtr::from((vec![number as u8], number))
+    }
+
+    fn upstream(backlog_size: usize) -> FirehoseUpstream {
+        let (sender, _) = broadcast::channel(backlog_size);
+        let shared = Arc::new(Shared {
+            backlog_size,
+            state: Mutex::new(State {
+                backlog: VecDeque::new(),
+                sender,
+            }),
+        });
+        let task = crate::task_spawn::spawn(async {});
+        FirehoseUpstream { shared, task }
+    }
+
+    fn push(upstream: &FirehoseUpstream, step: ForkStep, number: i32) {
+        upstream.shared.push(SharedResponse {
+            step,
+            ptr: ptr(number),
+            response: firehose::Response::default(),
+        });
+    }
+
+    #[tokio::test]
+    async fn join_returns_responses_after_position() {
+        use ForkStep::*;
+
+        let upstream = upstream(3);
+        for number in 1..=4 {
+            push(&upstream, StepNew, number);
+        }
+
+        // Block 1 fell out of the backlog
+        assert!(upstream.join(StepNew, &ptr(1)).is_none());
+        // Reverting block 2 is a different position than processing it
+        assert!(upstream.join(StepUndo, &ptr(2)).is_none());
+
+        let (pending, mut receiver) = upstream.join(StepNew, &ptr(2)).unwrap();
+        let pending: Vec<_> = pending.iter().map(|r| r.ptr.number).collect();
+        assert_eq!(vec![3, 4], pending);
+
+        push(&upstream, StepUndo, 4);
+        let next = receiver.recv().await.unwrap();
+        assert_eq!((StepUndo, 4), (next.step, next.ptr.number));
+    }
+}
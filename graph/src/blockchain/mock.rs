@@ -96,6 +96,14 @@ impl<C: Blockchain> DataSource<C> for MockDataSource {
         todo!()
     }
 
+    fn scheduled_handler_trigger(
+        &self,
+        _handler: &str,
+        _block: std::sync::Arc<C::Block>,
+    ) -> Result<TriggerWithHandler<C>, anyhow::Error> {
+        todo!()
+    }
+
     fn is_duplicate_of(&self, _other: &Self) -> bool {
         todo!()
     }
@@ -232,6 +240,10 @@ impl<C: Blockchain> TriggerFilter<C> for MockTriggerFilter {
     fn node_capabilities(&self) -> C::NodeCapabilities {
         todo!()
     }
+
+    fn to_firehose_filter(&self) -> Vec<prost_types::Any> {
+        todo!()
+    }
 }
 
 #[derive(Debug)]
@@ -301,6 +313,7 @@ impl Blockchain for MockBlockchain {
         _filter: std::sync::Arc<Self::TriggerFilter>,
         _metrics: std::sync::Arc<block_stream::BlockStreamMetrics>,
         _unified_api_version: crate::data::subgraph::UnifiedMappingApiVersion,
+        _firehose_provider: Option<String>,
     ) -> Result<Box<dyn block_stream::BlockStream<Self>>, anyhow::Error> {
         todo!()
     }
@@ -313,6 +326,7 @@ impl Blockchain for MockBlockchain {
         _filter: std::sync::Arc<Self::TriggerFilter>,
         _metrics: std::sync::Arc<block_stream::BlockStreamMetrics>,
         _unified_api_version: crate::data::subgraph::UnifiedMappingApiVersion,
+        _checkpoint_interval: Option<crate::components::store::BlockNumber>,
     ) -> Result<Box<dyn block_stream::BlockStream<Self>>, anyhow::Error> {
         todo!()
     }
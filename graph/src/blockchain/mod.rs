@@ -3,10 +3,15 @@
 //! trait which is the centerpiece of this module.
 
 pub mod block_stream;
+pub mod catch_up;
 pub mod firehose_block_ingestor;
 pub mod firehose_block_stream;
+pub mod firehose_mapper;
+pub mod firehose_multiplexer;
 pub mod mock;
+pub mod multi_chain;
 pub mod polling_block_stream;
+pub mod replay_block_stream;
 mod types;
 
 // Try to reexport most of the necessary types
@@ -48,6 +53,16 @@ pub use types::{BlockHash, BlockPtr, ChainIdentifier};
 
 use self::block_stream::{BlockStream, BlockStreamMetrics};
 
+lazy_static::lazy_static! {
+    /// Blocks that are this many blocks behind the chain head are
+    /// considered final on chains that do not record their final block
+    pub static ref REORG_THRESHOLD: BlockNumber = std::env::var("ETHEREUM_REORG_THRESHOLD")
+        .ok()
+        .map(|s| BlockNumber::from_str(&s)
+            .unwrap_or_else(|_| panic!("failed to parse env var ETHEREUM_REORG_THRESHOLD")))
+        .unwrap_or(50);
+}
+
 pub trait Block: Send + Sync {
     fn ptr(&self) -> BlockPtr;
     fn parent_ptr(&self) -> Option<BlockPtr>;
@@ -64,10 +79,24 @@ pub trait Block: Send + Sync {
         self.parent_ptr().map(|ptr| ptr.hash)
     }
 
+    /// The time at which this block was produced, in seconds since the
+    /// epoch, if the chain records that
+    fn timestamp(&self) -> Option<u64> {
+        None
+    }
+
     /// The data that should be stored for this block in the `ChainStore`
     fn data(&self) -> Result<serde_json::Value, serde_json::Error> {
         Ok(serde_json::Value::Null)
     }
+
+    /// The protobuf encoding of this block for chains whose blocks come
+    /// from Firehose. Chain stores with their own database schema store
+    /// these bytes, which are much smaller and faster to decode than JSON.
+    /// Chains that provide this should not also return data from `data`
+    fn data_bytes(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 #[async_trait]
@@ -108,6 +137,9 @@ pub trait Blockchain: Debug + Sized + Send + Sync + Unpin + 'static {
         stopwatch_metrics: StopwatchMetrics,
     ) -> Result<Arc<Self::TriggersAdapter>, Error>;
 
+    /// Create a block stream that is fed by a Firehose endpoint. If
+    /// `firehose_provider` is set, the endpoint of that provider is used
+    /// rather than a random endpoint for the chain
     async fn new_firehose_block_stream(
         &self,
         deployment: DeploymentLocator,
@@ -116,6 +148,7 @@ pub trait Blockchain: Debug + Sized + Send + Sync + Unpin + 'static {
         filter: Arc<Self::TriggerFilter>,
         metrics: Arc<BlockStreamMetrics>,
         unified_api_version: UnifiedMappingApiVersion,
+        firehose_provider: Option<String>,
     ) -> Result<Box<dyn BlockStream<Self>>, Error>;
 
     async fn new_polling_block_stream(
@@ -126,6 +159,7 @@ pub trait Blockchain: Debug + Sized + Send + Sync + Unpin + 'static {
         filter: Arc<Self::TriggerFilter>,
         metrics: Arc<BlockStreamMetrics>,
         unified_api_version: UnifiedMappingApiVersion,
+        checkpoint_interval: Option<BlockNumber>,
     ) -> Result<Box<dyn BlockStream<Self>>, Error>;
 
     fn chain_store(&self) -> Arc<dyn ChainStore>;
@@ -181,7 +215,19 @@ pub trait TriggerFilter<C: Blockchain>: Default + Clone + Send + Sync {
 
     fn extend<'a>(&mut self, data_sources: impl Iterator<Item = &'a C::DataSource> + Clone);
 
+    /// Make sure that the blocks with the given numbers are passed to the
+    /// subgraph even if nothing in them matches the filter, so that the
+    /// handlers that mappings scheduled for them can run. Chains whose
+    /// block streams pass every block to the subgraph do not need to do
+    /// anything
+    fn extend_with_scheduled_blocks(&mut self, _blocks: impl Iterator<Item = BlockNumber>) {}
+
     fn node_capabilities(&self) -> C::NodeCapabilities;
+
+    /// Firehose transforms that let the Firehose server strip everything
+    /// from blocks that this filter is not interested in. Returning no
+    /// transforms requests unfiltered blocks.
+    fn to_firehose_filter(&self) -> Vec<prost_types::Any>;
 }
 
 pub trait DataSource<C: Blockchain>:
@@ -197,6 +243,12 @@ pub trait DataSource<C: Blockchain>:
     fn api_version(&self) -> semver::Version;
     fn runtime(&self) -> &[u8];
 
+    /// How long a handler of this data source may run. `None` means that
+    /// the node's default, `GRAPH_MAPPING_HANDLER_TIMEOUT`, applies
+    fn handler_timeout(&self) -> Option<std::time::Duration> {
+        None
+    }
+
     /// Checks if `trigger` matches this data source, and if so decodes it into a `MappingTrigger`.
     /// A return of `Ok(None)` mean the trigger does not match.
     fn match_and_decode(
@@ -206,6 +258,14 @@ pub trait DataSource<C: Blockchain>:
         logger: &Logger,
     ) -> Result<Option<TriggerWithHandler<C>>, Error>;
 
+    /// The trigger for calling `handler`, a block handler that a mapping of
+    /// this data source scheduled for `block` with `scheduler.atBlock`
+    fn scheduled_handler_trigger(
+        &self,
+        handler: &str,
+        block: Arc<C::Block>,
+    ) -> Result<TriggerWithHandler<C>, Error>;
+
     fn is_duplicate_of(&self, other: &Self) -> bool;
 
     fn as_stored_dynamic_data_source(&self) -> StoredDynamicDataSource;
@@ -285,6 +345,13 @@ impl CheapClone for HostFn {
 
 pub trait RuntimeAdapter<C: Blockchain>: Send + Sync {
     fn host_fns(&self, ds: &C::DataSource) -> Result<Vec<HostFn>, Error>;
+
+    /// The host functions for a deployment that is replayed from the block
+    /// cache. They must not contact the chain's nodes, and should fail
+    /// deterministically when they need data that is not cached
+    fn replay_host_fns(&self, ds: &C::DataSource) -> Result<Vec<HostFn>, Error> {
+        self.host_fns(ds)
+    }
 }
 
 pub trait NodeCapabilities<C: Blockchain> {
@@ -345,8 +412,13 @@ impl BlockchainKind {
 }
 
 /// A collection of blockchains, keyed by `BlockchainKind` and network.
+/// Networks can also be known under aliases, which resolve to the name
+/// under which the chain was inserted
 #[derive(Default)]
-pub struct BlockchainMap(HashMap<(BlockchainKind, String), Arc<dyn Any + Send + Sync>>);
+pub struct BlockchainMap {
+    chains: HashMap<(BlockchainKind, String), Arc<dyn Any + Send + Sync>>,
+    aliases: HashMap<String, String>,
+}
 
 impl BlockchainMap {
     pub fn new() -> Self {
@@ -354,12 +426,27 @@ impl BlockchainMap {
     }
 
     pub fn insert<C: Blockchain>(&mut self, network: String, chain: Arc<C>) {
-        self.0.insert((C::KIND, network), chain);
+        self.chains.insert((C::KIND, network), chain);
+    }
+
+    /// Make the chain for `network` also available as `alias`
+    pub fn insert_alias(&mut self, alias: String, network: String) {
+        self.aliases.insert(alias, network);
+    }
+
+    /// The name under which the chain for `network` was inserted; that is
+    /// `network` itself unless it is an alias
+    pub fn canonical_name(&self, network: &str) -> String {
+        self.aliases
+            .get(network)
+            .cloned()
+            .unwrap_or_else(|| network.to_string())
     }
 
     pub fn get<C: Blockchain>(&self, network: String) -> Result<Arc<C>, Error> {
-        self.0
-            .get(&(C::KIND, network.clone()))
+        let canonical = self.canonical_name(&network);
+        self.chains
+            .get(&(C::KIND, canonical))
             .with_context(|| format!("no network {} found on chain {}", network, C::KIND))?
             .cheap_clone()
             .downcast()
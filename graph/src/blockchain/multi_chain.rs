@@ -0,0 +1,165 @@
+//! The order in which blocks from several chains are processed when they
+//! are combined into one stream.
+//!
+//! Blocks are ordered by their timestamp. Blocks from different chains
+//! often have the same timestamp, and to make sure that every node
+//! processes them in the same order, ties are broken first by the chain
+//! id, compared as a string, and then by the block hash. Blocks from the
+//! same chain never compare as equal unless they are the same block, and
+//! the order of the blocks of one chain is never changed, even if a chain
+//! reports timestamps that go backwards.
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+
+use super::BlockHash;
+
+/// The position of a block in the combined order of several chains
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiChainKey {
+    /// The block timestamp in seconds since the epoch
+    pub timestamp: u64,
+    /// The chain id of the chain the block belongs to
+    pub chain_id: String,
+    pub hash: BlockHash,
+}
+
+impl MultiChainKey {
+    pub fn new(timestamp: u64, chain_id: impl Into<String>, hash: BlockHash) -> Self {
+        Self {
+            timestamp,
+            chain_id: chain_id.into(),
+            hash,
+        }
+    }
+
+    /// The bytes that identify the position of this block in the combined
+    /// order, suitable for inclusion in a proof of indexing. The encoding
+    /// is unambiguous since the chain id is length-prefixed
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + 8 + self.chain_id.len() + self.hash.0.len());
+        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
+        bytes.extend_from_slice(&(self.chain_id.len() as u64).to_be_bytes());
+        bytes.extend_from_slice(self.chain_id.as_bytes());
+        bytes.extend_from_slice(self.hash.as_slice());
+        bytes
+    }
+}
+
+impl Ord for MultiChainKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.timestamp
+            .cmp(&other.timestamp)
+            .then_with(|| self.chain_id.cmp(&other.chain_id))
+            .then_with(|| self.hash.as_slice().cmp(other.hash.as_slice()))
+    }
+}
+
+impl PartialOrd for MultiChainKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Merge the blocks of several chains into one sequence. Each element of
+/// `chains` must contain the blocks of one chain in the order in which that
+/// chain produced them. The result does not depend on the order of
+/// `chains`.
+pub fn merge<T>(chains: Vec<Vec<(MultiChainKey, T)>>) -> Vec<(MultiChainKey, T)> {
+    let mut chains: Vec<VecDeque<_>> = chains.into_iter().map(VecDeque::from).collect();
+    let mut merged = Vec::with_capacity(chains.iter().map(|chain| chain.len()).sum());
+
+    loop {
+        // Only the first block of each chain is a candidate, which keeps
+        // the blocks of each chain in their original order
+        let next = chains
+            .iter()
+            .enumerate()
+            .filter_map(|(i, chain)| chain.front().map(|(key, _)| (i, key)))
+            .min_by(|(_, a), (_, b)| a.cmp(b))
+            .map(|(i, _)| i);
+        match next {
+            Some(i) => merged.push(chains[i].pop_front().unwrap()),
+            None => return merged,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(timestamp: u64, chain_id: &str, hash: u8) -> MultiChainKey {
+        MultiChainKey::new(
+            timestamp,
+            chain_id,
+            BlockHash(vec![hash].into_boxed_slice()),
+        )
+    }
+
+    fn chain(chain_id: &str, blocks: &[(u64, u8)]) -> Vec<(MultiChainKey, String)> {
+        blocks
+            .iter()
+            .map(|(timestamp, hash)| {
+                (
+                    key(*timestamp, chain_id, *hash),
+                    format!("{}-{}", chain_id, hash),
+                )
+            })
+            .collect()
+    }
+
+    fn names(merged: Vec<(MultiChainKey, String)>) -> Vec<String> {
+        merged.into_iter().map(|(_, name)| name).collect()
+    }
+
+    #[test]
+    fn ties_are_broken_by_chain_and_hash() {
+        assert!(key(1, "b", 0) > key(1, "a", 9));
+        assert!(key(1, "a", 2) > key(1, "a", 1));
+        assert!(key(2, "a", 0) > key(1, "b", 9));
+        assert_eq!(key(1, "a", 1), key(1, "a", 1));
+    }
+
+    #[test]
+    fn merge_is_independent_of_input_order() {
+        let a = chain("1", &[(10, 1), (12, 2), (12, 3), (15, 4)]);
+        let b = chain("137", &[(10, 1), (11, 2), (12, 3), (12, 4)]);
+        let c = chain("56", &[(9, 1), (12, 2), (15, 3)]);
+
+        let expected = names(merge(vec![a.clone(), b.clone(), c.clone()]));
+        // Every node has to arrive at the same order, no matter in which
+        // order it happens to receive the chains
+        for chains in vec![
+            vec![a.clone(), c.clone(), b.clone()],
+            vec![b.clone(), a.clone(), c.clone()],
+            vec![b.clone(), c.clone(), a.clone()],
+            vec![c.clone(), a.clone(), b.clone()],
+            vec![c.clone(), b.clone(), a.clone()],
+        ] {
+            assert_eq!(expected, names(merge(chains)));
+        }
+        assert_eq!(
+            vec![
+                "56-1", "1-1", "137-1", "137-2", "1-2", "1-3", "137-3", "137-4", "56-2", "1-4",
+                "56-3"
+            ],
+            expected
+        );
+    }
+
+    #[test]
+    fn merge_keeps_chain_order() {
+        // A chain whose timestamps go backwards still has its blocks
+        // processed in chain order
+        let a = chain("1", &[(10, 1), (8, 2)]);
+        let b = chain("2", &[(9, 1)]);
+        assert_eq!(vec!["2-1", "1-1", "1-2"], names(merge(vec![a, b])));
+    }
+
+    #[test]
+    fn encoding_is_unambiguous() {
+        let a = MultiChainKey::new(1, "ab", BlockHash(vec![1].into_boxed_slice()));
+        let b = MultiChainKey::new(1, "a", BlockHash(vec![b'b', 1].into_boxed_slice()));
+        assert_ne!(a.to_bytes(), b.to_bytes());
+    }
+}
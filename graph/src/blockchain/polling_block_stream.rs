@@ -5,11 +5,11 @@ use std::collections::VecDeque;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use super::block_stream::{
     BlockStream, BlockStreamEvent, BlockStreamMetrics, BlockWithTriggers, ChainHeadUpdateStream,
-    FirehoseCursor, TriggersAdapter,
+    TriggersAdapter, BLOCK_PTR_CURSOR,
 };
 use super::{Block, BlockPtr, Blockchain};
 
@@ -22,6 +22,11 @@ use fail::fail_point;
 // A high number here forces a slow start.
 const STARTING_PREVIOUS_TRIGGERS_PER_BLOCK: f64 = 1_000_000.0;
 
+/// The deployment label that sets the checkpoint interval. If the label is
+/// set to `N`, the subgraph pointer advances at least every `N` blocks,
+/// even when the deployment's filter does not match anything in them
+pub const CHECKPOINT_INTERVAL_LABEL: &str = "checkpoint_interval";
+
 enum BlockStreamState<C>
 where
     C: Blockchain,
@@ -55,6 +60,19 @@ where
     Idle,
 }
 
+impl<C: Blockchain> BlockStreamState<C> {
+    /// The name of the state for metrics
+    fn name(&self) -> &'static str {
+        match self {
+            BlockStreamState::BeginReconciliation => "begin_reconciliation",
+            BlockStreamState::Reconciliation(_) => "reconciliation",
+            BlockStreamState::YieldingBlocks(_) => "yielding_blocks",
+            BlockStreamState::RetryAfterDelay(_) => "retry_after_delay",
+            BlockStreamState::Idle => "idle",
+        }
+    }
+}
+
 /// A single next step to take in reconciling the state of the subgraph store with the state of the
 /// chain store.
 enum ReconciliationStep<C>
@@ -66,7 +84,8 @@ where
     /// current one. The second BlockPtr is the parent.
     Revert(BlockPtr, BlockPtr),
 
-    /// Move forwards, processing one or more blocks. Second element is the block range size.
+    /// Move forwards, processing one or more blocks. Second element is the number of blocks that
+    /// were scanned.
     ProcessDescendantBlocks(Vec<BlockWithTriggers<C>>, BlockNumber),
 
     /// This step is a no-op, but we need to check again for a next step.
@@ -98,8 +117,13 @@ where
     // Not a BlockNumber, but the difference between two block numbers
     max_block_range_size: BlockNumber,
     target_triggers_per_block_range: u64,
+    // How many sub-ranges of a block range to scan concurrently
+    scan_parallelism: usize,
     unified_api_version: UnifiedMappingApiVersion,
     current_block: Option<BlockPtr>,
+    // Never scan more than this many blocks in one step, so that the
+    // subgraph pointer is checkpointed at least this often
+    checkpoint_interval: Option<BlockNumber>,
 }
 
 impl<C: Blockchain> Clone for PollingBlockStreamContext<C> {
@@ -118,14 +142,18 @@ impl<C: Blockchain> Clone for PollingBlockStreamContext<C> {
             previous_block_range_size: self.previous_block_range_size,
             max_block_range_size: self.max_block_range_size,
             target_triggers_per_block_range: self.target_triggers_per_block_range,
+            scan_parallelism: self.scan_parallelism,
             unified_api_version: self.unified_api_version.clone(),
             current_block: self.current_block.clone(),
+            checkpoint_interval: self.checkpoint_interval,
         }
     }
 }
 
 pub struct PollingBlockStream<C: Blockchain> {
     state: BlockStreamState<C>,
+    // When the stream entered its current state
+    state_entered: Instant,
     consecutive_err_count: u32,
     chain_head_update_stream: ChainHeadUpdateStream,
     ctx: PollingBlockStreamContext<C>,
@@ -136,7 +164,7 @@ enum NextBlocks<C>
 where
     C: Blockchain,
 {
-    /// Blocks and range size
+    /// Blocks and the number of blocks that were scanned
     Blocks(VecDeque<BlockWithTriggers<C>>, BlockNumber),
 
     // The payload is the current subgraph head pointer, which should be reverted and its parent, such that the
@@ -162,11 +190,14 @@ where
         metrics: Arc<BlockStreamMetrics>,
         max_block_range_size: BlockNumber,
         target_triggers_per_block_range: u64,
+        scan_parallelism: usize,
         unified_api_version: UnifiedMappingApiVersion,
         start_block: Option<BlockPtr>,
+        checkpoint_interval: Option<BlockNumber>,
     ) -> Self {
         Self {
             state: BlockStreamState::BeginReconciliation,
+            state_entered: Instant::now(),
             consecutive_err_count: 0,
             chain_head_update_stream,
             ctx: PollingBlockStreamContext {
@@ -184,12 +215,76 @@ where
                 previous_block_range_size: 1,
                 max_block_range_size,
                 target_triggers_per_block_range,
+                scan_parallelism: scan_parallelism.max(1),
                 unified_api_version,
+                checkpoint_interval: checkpoint_interval.filter(|interval| *interval > 0),
             },
         }
     }
 }
 
+/// Calculate the size of the next block range according to the target
+/// number of triggers, respecting the global maximum and also not
+/// increasing too drastically from the previous block range size.
+///
+/// An example of the block range dynamics:
+/// - Start with a block range of 1, target of 1000.
+/// - Scan 1 block:
+///   0 triggers found, max_range_size = 10, range_size = 10
+/// - Scan 10 blocks:
+///   2 triggers found, 0.2 per block, range_size = 1000 / 0.2 = 5000,
+///   limited to 10 * 10 = 100
+/// - Scan 100 blocks:
+///   20 triggers found, 0.2 per block, range_size = 1000
+/// - Scan 1000 blocks:
+///   4000 triggers found, 4 per block, range_size = 1000 / 4 = 250
+fn next_range_size(
+    previous_range_size: BlockNumber,
+    triggers_per_block: f64,
+    target_triggers: u64,
+    max_range_size: BlockNumber,
+) -> BlockNumber {
+    let upper_limit = max_range_size.min(previous_range_size.saturating_mul(10));
+    if triggers_per_block == 0.0 {
+        upper_limit
+    } else {
+        (target_triggers as f64 / triggers_per_block)
+            .max(1.0)
+            .min(upper_limit as f64) as BlockNumber
+    }
+}
+
+/// Combine the trigger density we just `observed` with the `previous`
+/// estimate. Denser ranges are taken into account right away so that the
+/// range shrinks quickly and memory usage stays bounded. Sparser ranges
+/// only lower the estimate gradually so that a single quiet range does not
+/// cause a huge range to be scanned next.
+fn smoothed_triggers_per_block(previous: f64, observed: f64) -> f64 {
+    if previous == STARTING_PREVIOUS_TRIGGERS_PER_BLOCK || observed >= previous {
+        observed
+    } else {
+        (previous + observed) / 2.0
+    }
+}
+
+/// Split the block range `[from, to]` into consecutive sub-ranges of at
+/// most `size` blocks each
+fn split_range(
+    from: BlockNumber,
+    to: BlockNumber,
+    size: BlockNumber,
+) -> Vec<(BlockNumber, BlockNumber)> {
+    let size = size.max(1);
+    let mut ranges = Vec::new();
+    let mut start = from;
+    while start <= to {
+        let end = cmp::min(start.saturating_add(size - 1), to);
+        ranges.push((start, end));
+        start = end + 1;
+    }
+    ranges
+}
+
 impl<C> PollingBlockStreamContext<C>
 where
     C: Blockchain,
@@ -257,10 +352,14 @@ where
         // subgraph_ptr > head_ptr shouldn't happen, but if it does, it's safest to just stop.
         if let Some(ptr) = &subgraph_ptr {
             if ptr.number >= head_ptr.number {
+                self.metrics.head_distance_blocks.set(0.0);
                 return Ok(ReconciliationStep::Done);
             }
 
             self.metrics.deployment_head.set(ptr.number as f64);
+            self.metrics
+                .head_distance_blocks
+                .set((head_ptr.number - ptr.number) as f64);
         }
 
         // Subgraph ptr is behind head ptr.
@@ -357,30 +456,22 @@ where
             // reorg threshold due to race conditions.
             let to_limit = cmp::min(head_ptr.number - reorg_threshold, next_start_block - 1);
 
-            // Calculate the range size according to the target number of triggers,
-            // respecting the global maximum and also not increasing too
-            // drastically from the previous block range size.
-            //
-            // An example of the block range dynamics:
-            // - Start with a block range of 1, target of 1000.
-            // - Scan 1 block:
-            //   0 triggers found, max_range_size = 10, range_size = 10
-            // - Scan 10 blocks:
-            //   2 triggers found, 0.2 per block, range_size = 1000 / 0.2 = 5000
-            // - Scan 5000 blocks:
-            //   10000 triggers found, 2 per block, range_size = 1000 / 2 = 500
-            // - Scan 500 blocks:
-            //   1000 triggers found, 2 per block, range_size = 1000 / 2 = 500
-            let range_size_upper_limit =
-                max_block_range_size.min(ctx.previous_block_range_size * 10);
-            let range_size = if ctx.previous_triggers_per_block == 0.0 {
-                range_size_upper_limit
-            } else {
-                (self.target_triggers_per_block_range as f64 / ctx.previous_triggers_per_block)
-                    .max(1.0)
-                    .min(range_size_upper_limit as f64) as BlockNumber
-            };
-            let to = cmp::min(from + range_size - 1, to_limit);
+            let range_size = next_range_size(
+                ctx.previous_block_range_size,
+                ctx.previous_triggers_per_block,
+                self.target_triggers_per_block_range,
+                max_block_range_size,
+            );
+            // When sub-ranges are scanned concurrently, each of them is as
+            // big as a range would be without concurrency
+            let total_size = range_size.saturating_mul(self.scan_parallelism as BlockNumber);
+            let mut to = cmp::min(from.saturating_add(total_size - 1), to_limit);
+            // The last block of a range is always passed on, with or
+            // without triggers; processing it moves the subgraph pointer
+            // forward and serves as a checkpoint
+            if let Some(interval) = self.checkpoint_interval {
+                to = cmp::min(to, from.saturating_add(interval - 1));
+            }
 
             info!(
                 ctx.logger,
@@ -388,10 +479,13 @@ where
                 "range_size" => range_size
             );
 
-            let blocks = self.adapter.scan_triggers(from, to, &self.filter).await?;
+            let blocks = self.scan_triggers(from, to, range_size).await?;
 
+            // The range we actually scanned can be smaller than `range_size`
+            // if it was cut short by a start block or the reorg threshold
             Ok(ReconciliationStep::ProcessDescendantBlocks(
-                blocks, range_size,
+                blocks,
+                to - from + 1,
             ))
         } else {
             // The subgraph ptr is not too far behind the head ptr.
@@ -469,6 +563,43 @@ where
         }
     }
 
+    /// Scan `[from, to]` for triggers in sub-ranges of `range_size` blocks,
+    /// at most `scan_parallelism` of them at once. The blocks from all
+    /// sub-ranges are returned in order
+    async fn scan_triggers(
+        &self,
+        from: BlockNumber,
+        to: BlockNumber,
+        range_size: BlockNumber,
+    ) -> Result<Vec<BlockWithTriggers<C>>, Error> {
+        use futures03::stream::{self, StreamExt, TryStreamExt};
+
+        let ranges = split_range(from, to, range_size);
+        if ranges.len() == 1 {
+            return self.adapter.scan_triggers(from, to, &self.filter).await;
+        }
+
+        let scans: Vec<Vec<BlockWithTriggers<C>>> = stream::iter(ranges)
+            .map(|(from, to)| self.adapter.scan_triggers(from, to, &self.filter))
+            .buffered(self.scan_parallelism)
+            .try_collect()
+            .await?;
+
+        // Each scan returns at least one block, even if that block has no
+        // triggers. We only need that for the last sub-range so that the
+        // subgraph pointer advances to the end of the whole range
+        let last = scans.len() - 1;
+        Ok(scans
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, blocks)| {
+                blocks
+                    .into_iter()
+                    .filter(move |block| i == last || !block.trigger_data.is_empty())
+            })
+            .collect())
+    }
+
     async fn parent_ptr(&self, block_ptr: &BlockPtr) -> Result<BlockPtr, Error> {
         let ptr = self
             .adapter
@@ -480,6 +611,17 @@ where
     }
 }
 
+impl<C: Blockchain> PollingBlockStream<C> {
+    fn set_state(&mut self, state: BlockStreamState<C>) {
+        let now = Instant::now();
+        self.ctx
+            .metrics
+            .state_left(self.state.name(), now.duration_since(self.state_entered));
+        self.state = state;
+        self.state_entered = now;
+    }
+}
+
 impl<C: Blockchain> BlockStream<C> for PollingBlockStream<C> {}
 
 impl<C: Blockchain> Stream for PollingBlockStream<C> {
@@ -492,7 +634,7 @@ impl<C: Blockchain> Stream for PollingBlockStream<C> {
                     // Start the reconciliation process by asking for blocks
                     let ctx = self.ctx.clone();
                     let fut = async move { ctx.next_blocks().await };
-                    self.state = BlockStreamState::Reconciliation(fut.boxed());
+                    self.set_state(BlockStreamState::Reconciliation(fut.boxed()));
                 }
 
                 // Waiting for the reconciliation to complete or yield blocks
@@ -512,8 +654,10 @@ impl<C: Blockchain> Stream for PollingBlockStream<C> {
 
                                 let total_triggers =
                                     next_blocks.iter().map(|b| b.trigger_count()).sum::<usize>();
-                                self.ctx.previous_triggers_per_block =
-                                    total_triggers as f64 / block_range_size as f64;
+                                self.ctx.previous_triggers_per_block = smoothed_triggers_per_block(
+                                    self.ctx.previous_triggers_per_block,
+                                    total_triggers as f64 / block_range_size as f64,
+                                );
                                 self.ctx.previous_block_range_size = block_range_size;
                                 if total_triggers > 0 {
                                     debug!(
@@ -523,8 +667,9 @@ impl<C: Blockchain> Stream for PollingBlockStream<C> {
                                 }
 
                                 // Switch to yielding state until next_blocks is depleted
-                                self.state =
-                                    BlockStreamState::YieldingBlocks(Box::new(next_blocks));
+                                self.set_state(BlockStreamState::YieldingBlocks(Box::new(
+                                    next_blocks,
+                                )));
 
                                 // Yield the first block in next_blocks
                                 continue;
@@ -535,19 +680,20 @@ impl<C: Blockchain> Stream for PollingBlockStream<C> {
                                 self.consecutive_err_count = 0;
 
                                 // Switch to idle
-                                self.state = BlockStreamState::Idle;
+                                self.set_state(BlockStreamState::Idle);
 
                                 // Poll for chain head update
                                 continue;
                             }
                             NextBlocks::Revert(from, parent_ptr) => {
                                 self.ctx.current_block = Some(parent_ptr.clone());
+                                self.ctx.metrics.cursor_moved();
 
-                                self.state = BlockStreamState::BeginReconciliation;
+                                self.set_state(BlockStreamState::BeginReconciliation);
                                 break Poll::Ready(Some(Ok(BlockStreamEvent::Revert(
                                     from,
                                     parent_ptr,
-                                    FirehoseCursor::None,
+                                    BLOCK_PTR_CURSOR,
                                 ))));
                             }
                         },
@@ -562,9 +708,9 @@ impl<C: Blockchain> Stream for PollingBlockStream<C> {
                             // Pause before trying again
                             let secs = (5 * self.consecutive_err_count).max(120) as u64;
 
-                            self.state = BlockStreamState::RetryAfterDelay(Box::pin(
+                            self.set_state(BlockStreamState::RetryAfterDelay(Box::pin(
                                 tokio::time::sleep(Duration::from_secs(secs)).map(Ok),
-                            ));
+                            )));
 
                             break Poll::Ready(Some(Err(e)));
                         }
@@ -577,16 +723,17 @@ impl<C: Blockchain> Stream for PollingBlockStream<C> {
                         // Yield one block
                         Some(next_block) => {
                             self.ctx.current_block = Some(next_block.block.ptr());
+                            self.ctx.metrics.block_produced(&next_block.block);
 
                             break Poll::Ready(Some(Ok(BlockStreamEvent::ProcessBlock(
                                 next_block,
-                                FirehoseCursor::None,
+                                BLOCK_PTR_CURSOR,
                             ))));
                         }
 
                         // Done yielding blocks
                         None => {
-                            self.state = BlockStreamState::BeginReconciliation;
+                            self.set_state(BlockStreamState::BeginReconciliation);
                         }
                     }
                 }
@@ -594,7 +741,7 @@ impl<C: Blockchain> Stream for PollingBlockStream<C> {
                 // Pausing after an error, before looking for more blocks
                 BlockStreamState::RetryAfterDelay(ref mut delay) => match delay.as_mut().poll(cx) {
                     Poll::Ready(Ok(..)) | Poll::Ready(Err(_)) => {
-                        self.state = BlockStreamState::BeginReconciliation;
+                        self.set_state(BlockStreamState::BeginReconciliation);
                     }
 
                     Poll::Pending => {
@@ -607,7 +754,7 @@ impl<C: Blockchain> Stream for PollingBlockStream<C> {
                     match Pin::new(self.chain_head_update_stream.as_mut()).poll_next(cx) {
                         // Chain head was updated
                         Poll::Ready(Some(())) => {
-                            self.state = BlockStreamState::BeginReconciliation;
+                            self.set_state(BlockStreamState::BeginReconciliation);
                         }
 
                         // Chain head update stream ended
@@ -651,3 +798,47 @@ fn test_reorg(ptr: BlockPtr) -> bool {
 
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_size_grows_on_sparse_history() {
+        // No triggers: grow as fast as allowed
+        assert_eq!(10, next_range_size(1, 0.0, 1000, 2000));
+        assert_eq!(2000, next_range_size(1000, 0.0, 1000, 2000));
+        // Sparse triggers: grow towards the target, at most 10x per step
+        assert_eq!(100, next_range_size(10, 0.2, 1000, 2000));
+        assert_eq!(1000, next_range_size(100, 1.0, 1000, 2000));
+    }
+
+    #[test]
+    fn range_size_shrinks_on_dense_history() {
+        assert_eq!(250, next_range_size(1000, 4.0, 1000, 2000));
+        // Never go below one block
+        assert_eq!(1, next_range_size(1000, 5000.0, 1000, 2000));
+    }
+
+    #[test]
+    fn ranges_are_split_into_sub_ranges() {
+        assert_eq!(vec![(0, 9)], split_range(0, 9, 10));
+        assert_eq!(vec![(0, 3)], split_range(0, 3, 10));
+        assert_eq!(vec![(5, 14), (15, 24), (25, 27)], split_range(5, 27, 10));
+        assert_eq!(vec![(7, 7), (8, 8)], split_range(7, 8, 1));
+    }
+
+    #[test]
+    fn triggers_per_block_are_smoothed() {
+        // The first observation is taken as is
+        assert_eq!(
+            0.5,
+            smoothed_triggers_per_block(STARTING_PREVIOUS_TRIGGERS_PER_BLOCK, 0.5)
+        );
+        // Denser ranges are taken into account right away
+        assert_eq!(4.0, smoothed_triggers_per_block(1.0, 4.0));
+        // Sparser ranges only lower the estimate gradually
+        assert_eq!(2.0, smoothed_triggers_per_block(4.0, 0.0));
+        assert_eq!(1.0, smoothed_triggers_per_block(2.0, 0.0));
+    }
+}
@@ -0,0 +1,112 @@
+//! A block stream that replays blocks from the local block cache
+//!
+//! The stream starts after the subgraph's current block and follows the
+//! chain forward through the blocks that are already stored in the chain
+//! store, without contacting any network endpoints for blocks. Triggers
+//! are extracted from the cached blocks. This is useful for debugging
+//! handler determinism and for reindexing after changes that do not
+//! require new chain data.
+//!
+//! The stream ends as soon as the next block is not in the cache. Since
+//! it only uses the cache, it never reverts blocks. Chains that do not
+//! store block data in the chain store can not be replayed, and data
+//! sources that need call traces may still cause requests to the chain's
+//! nodes. Host functions that would contact the chain's nodes are replaced
+//! with ones that only use cached data, see
+//! `RuntimeAdapter::replay_host_fns`.
+use anyhow::Error;
+use async_stream::stream;
+use futures03::Stream;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use super::block_stream::{BlockStream, BlockStreamEvent, TriggersAdapter, BLOCK_PTR_CURSOR};
+use super::{Block, BlockPtr, Blockchain};
+use crate::components::store::ChainStore;
+use crate::prelude::*;
+
+/// The deployment label that turns on replaying from the block cache. The
+/// deployment is replayed if the label has the value `true`
+pub const REPLAY_LABEL: &str = "replay";
+
+pub struct ReplayBlockStream<C: Blockchain> {
+    inner: Pin<Box<dyn Stream<Item = Result<BlockStreamEvent<C>, Error>> + Send>>,
+}
+
+impl<C: Blockchain> ReplayBlockStream<C> {
+    /// Replay the blocks following `start_block`, or starting at the
+    /// genesis block if `start_block` is `None`
+    pub fn new(
+        chain_store: Arc<dyn ChainStore>,
+        adapter: Arc<C::TriggersAdapter>,
+        filter: Arc<C::TriggerFilter>,
+        start_block: Option<BlockPtr>,
+        logger: Logger,
+    ) -> Self {
+        let inner = stream! {
+            let mut current = start_block;
+            loop {
+                let block = match next_cached_block::<C>(
+                    chain_store.as_ref(),
+                    adapter.as_ref(),
+                    current.as_ref(),
+                ) {
+                    Ok(Some(block)) => block,
+                    Ok(None) => {
+                        info!(logger, "Replay reached the end of the block cache";
+                              "block" => current.as_ref().map(|ptr| ptr.to_string()));
+                        return;
+                    }
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                };
+                current = Some(block.ptr());
+
+                match adapter.triggers_in_block(&logger, block, filter.as_ref()).await {
+                    Ok(block) => yield Ok(BlockStreamEvent::ProcessBlock(block, BLOCK_PTR_CURSOR)),
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                }
+            }
+        };
+
+        Self {
+            inner: Box::pin(inner),
+        }
+    }
+}
+
+/// Find the child of `current` in the block cache. If there are several
+/// blocks with the next number, only the one whose parent is `current` is
+/// taken
+fn next_cached_block<C: Blockchain>(
+    chain_store: &dyn ChainStore,
+    adapter: &C::TriggersAdapter,
+    current: Option<&BlockPtr>,
+) -> Result<Option<C::Block>, Error> {
+    let number = current.map_or(0, |ptr| ptr.number + 1);
+    for hash in chain_store.block_hashes_by_block_number(number)? {
+        let ptr = BlockPtr::from((hash, number));
+        if let Some(block) = adapter.ancestor_block(ptr, 0)? {
+            if block.parent_ptr().as_ref() == current {
+                return Ok(Some(block));
+            }
+        }
+    }
+    Ok(None)
+}
+
+impl<C: Blockchain> BlockStream<C> for ReplayBlockStream<C> {}
+
+impl<C: Blockchain> Stream for ReplayBlockStream<C> {
+    type Item = Result<BlockStreamEvent<C>, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
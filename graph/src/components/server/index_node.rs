@@ -1,8 +1,10 @@
 use std::sync::Arc;
 
 use futures::prelude::*;
+use serde::Serialize;
+use web3::types::H256;
 
-use crate::prelude::{BlockNumber, Schema};
+use crate::prelude::{serde_json, BlockNumber, Schema};
 
 #[derive(Debug)]
 /// This is only needed to support the explorer API
@@ -19,6 +21,66 @@ pub struct VersionInfo {
     pub network: String,
 }
 
+/// How to find a block in the chain store for the block debug API
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlockLookup {
+    Number(BlockNumber),
+    Hash(H256),
+}
+
+/// A summary of a block as it is stored in the chain store. This is only
+/// meant to help operators check what data the node actually ingested.
+/// Chains that do not store block data in the chain store only have a hash
+/// and a number
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredBlock {
+    pub hash: String,
+    pub number: BlockNumber,
+    pub parent_hash: Option<String>,
+    /// The block timestamp in seconds since the epoch
+    pub timestamp: Option<u64>,
+    pub transaction_count: Option<usize>,
+    /// Whether the logs bloom filter of the block is empty. If it is, the
+    /// block can not contain any events
+    pub logs_bloom_empty: Option<bool>,
+}
+
+impl StoredBlock {
+    /// Summarize the block with `hash` and `number` whose stored JSON
+    /// representation is `data`
+    pub fn new(hash: H256, number: BlockNumber, data: &serde_json::Value) -> Self {
+        fn as_u64(value: &serde_json::Value) -> Option<u64> {
+            match value {
+                serde_json::Value::Number(n) => n.as_u64(),
+                serde_json::Value::String(s) => match s.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16).ok(),
+                    None => s.parse().ok(),
+                },
+                _ => None,
+            }
+        }
+
+        StoredBlock {
+            hash: format!("{:x}", hash),
+            number,
+            parent_hash: data
+                .get("parentHash")
+                .and_then(|hash| hash.as_str())
+                .map(|hash| hash.trim_start_matches("0x").to_string()),
+            timestamp: data.get("timestamp").and_then(as_u64),
+            transaction_count: data
+                .get("transactions")
+                .and_then(|txs| txs.as_array())
+                .map(|txs| txs.len()),
+            logs_bloom_empty: data
+                .get("logsBloom")
+                .and_then(|bloom| bloom.as_str())
+                .map(|bloom| bloom.trim_start_matches("0x").chars().all(|c| c == '0')),
+        }
+    }
+}
+
 /// Common trait for index node server implementations.
 pub trait IndexNodeServer {
     type ServeError;
@@ -29,3 +91,31 @@ pub trait IndexNodeServer {
         port: u16,
     ) -> Result<Box<dyn Future<Item = (), Error = ()> + Send>, Self::ServeError>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarize_stored_block() {
+        let data = serde_json::json!({
+            "parentHash": "0x0101010101010101010101010101010101010101010101010101010101010101",
+            "timestamp": "0x5f5e100",
+            "transactions": [{ "hash": "0x01" }, { "hash": "0x02" }],
+            "logsBloom": "0x0000"
+        });
+        let block = StoredBlock::new(H256::from_low_u64_be(2), 7, &data);
+        assert_eq!(7, block.number);
+        assert_eq!(Some("01".repeat(32)), block.parent_hash);
+        assert_eq!(Some(100_000_000), block.timestamp);
+        assert_eq!(Some(2), block.transaction_count);
+        assert_eq!(Some(true), block.logs_bloom_empty);
+
+        // Chains that do not store block data
+        let block = StoredBlock::new(H256::from_low_u64_be(2), 7, &serde_json::Value::Null);
+        assert_eq!(None, block.parent_hash);
+        assert_eq!(None, block.timestamp);
+        assert_eq!(None, block.transaction_count);
+        assert_eq!(None, block.logs_bloom_empty);
+    }
+}
@@ -11,13 +11,13 @@ use std::fmt;
 use std::fmt::Display;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
 use thiserror::Error;
 use web3::types::{Address, H256};
 
-use crate::blockchain::{Block, Blockchain};
-use crate::components::server::index_node::VersionInfo;
+use crate::blockchain::{Block, BlockHash, Blockchain};
+use crate::components::server::index_node::{BlockLookup, StoredBlock, VersionInfo};
 use crate::components::transaction_receipt;
 use crate::data::subgraph::status;
 use crate::data::{store::*, subgraph::Source};
@@ -25,7 +25,10 @@ use crate::prelude::*;
 use crate::util::lfu_cache::LfuCache;
 use crate::{
     blockchain::DataSource,
-    data::{query::QueryTarget, subgraph::schema::*},
+    data::{
+        query::{Deprecation, QueryTarget},
+        subgraph::schema::*,
+    },
 };
 
 lazy_static! {
@@ -173,6 +176,10 @@ pub enum EntityFilter {
     NotIn(Attribute, Vec<Value>),
     Contains(Attribute, Value),
     NotContains(Attribute, Value),
+    /// The list attribute shares at least one element with the given list
+    ContainsAny(Attribute, Value),
+    /// The list attribute contains all elements of the given list
+    ContainsAll(Attribute, Value),
     StartsWith(Attribute, Value),
     NotStartsWith(Attribute, Value),
     EndsWith(Attribute, Value),
@@ -228,6 +235,10 @@ pub enum EntityOrder {
     Ascending(String, ValueType),
     /// Order descending by the given attribute. Use `id` as a tie-breaker
     Descending(String, ValueType),
+    /// Order by several attributes, each in its own direction, with later
+    /// attributes breaking ties between earlier ones. Use `id` as the last
+    /// tie-breaker
+    Multiple(Vec<(String, ValueType, OrderDirection)>),
     /// Order by the `id` of the entities
     Default,
     /// Do not order at all. This speeds up queries where we know that
@@ -235,6 +246,13 @@ pub enum EntityOrder {
     Unordered,
 }
 
+/// The direction in which `EntityOrder::Multiple` orders by an attribute
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrderDirection {
+    Ascending,
+    Descending,
+}
+
 /// How many entities to return, how many to skip etc.
 #[derive(Clone, Debug, PartialEq)]
 pub struct EntityRange {
@@ -255,6 +273,25 @@ impl EntityRange {
     }
 }
 
+/// Return all versions of entities that were valid at some point between
+/// the blocks `from` and `to`, inclusive, rather than the version that is
+/// valid at one block. Each version carries the block range in which it is
+/// valid in the attributes `VALID_FROM_ATTRIBUTE` and `VALID_TO_ATTRIBUTE`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EntityHistory {
+    pub from: BlockNumber,
+    pub to: BlockNumber,
+}
+
+/// The attribute that holds the first block in which an entity version
+/// returned by a history query is valid
+pub const VALID_FROM_ATTRIBUTE: &str = "g$valid_from";
+
+/// The attribute that holds the first block in which an entity version
+/// returned by a history query is no longer valid, or null if the version
+/// is still current
+pub const VALID_TO_ATTRIBUTE: &str = "g$valid_to";
+
 /// The attribute we want to window by in an `EntityWindow`. We have to
 /// distinguish between scalar and list attributes since we need to use
 /// different queries for them, and the JSONB storage scheme can not
@@ -304,6 +341,22 @@ pub enum EntityLink {
     Direct(WindowAttribute, ChildMultiplicity),
     /// Join with the parents table to get at the parent id
     Parent(ParentLink),
+    /// Parent and child are connected through the join table of a
+    /// `@relation(through: ..)` field
+    Through(JoinLink, ChildMultiplicity),
+}
+
+/// Identifies the join table that connects parents and children through
+/// a `@relation(through: ..)` field
+#[derive(Clone, Debug, PartialEq)]
+pub struct JoinLink {
+    /// The entity type that declares the `@relation` field
+    pub owner: EntityType,
+    /// The name of the `@relation` field in `owner`
+    pub field: String,
+    /// `false` if the parent is of type `owner`, `true` if the parent is on
+    /// the `@derivedFrom` side of the relation
+    pub reverse: bool,
 }
 
 /// Window results of an `EntityQuery` query along the parent's id:
@@ -405,6 +458,10 @@ pub struct EntityQuery {
     /// A range to limit the size of the result.
     pub range: EntityRange,
 
+    /// Return all entity versions in a range of blocks instead of the
+    /// versions at `block`
+    pub history: Option<EntityHistory>,
+
     /// Optional logger for anything related to this query
     pub logger: Option<Logger>,
 
@@ -426,6 +483,7 @@ impl EntityQuery {
             filter: None,
             order: EntityOrder::Default,
             range: EntityRange::first(100),
+            history: None,
             logger: None,
             query_id: None,
             _force_use_of_new: (),
@@ -447,6 +505,11 @@ impl EntityQuery {
         self
     }
 
+    pub fn history(mut self, history: EntityHistory) -> Self {
+        self.history = Some(history);
+        self
+    }
+
     pub fn first(mut self, first: u32) -> Self {
         self.range.first = Some(first);
         self
@@ -782,6 +845,8 @@ pub enum StoreError {
     QueryExecutionError(String),
     #[error("invalid identifier: {0}")]
     InvalidIdentifier(String),
+    #[error("invalid index: {0}")]
+    InvalidIndex(String),
     #[error(
         "subgraph `{0}` has already processed block `{1}`; \
          there are most likely two (or more) nodes indexing this subgraph"
@@ -858,6 +923,47 @@ pub struct StoredDynamicDataSource {
     pub creation_block: Option<BlockNumber>,
 }
 
+/// A block handler that a mapping scheduled with `scheduler.atBlock` to
+/// run once when the subgraph processes block `block`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledBlockHandler {
+    /// The name of the data source that scheduled the handler
+    pub data_source: String,
+    /// The address of the data source that scheduled the handler, empty if
+    /// the data source has no address
+    pub address: Vec<u8>,
+    /// The name of the function in the data source's mapping to call
+    pub handler: String,
+    pub block: BlockNumber,
+}
+
+/// A context that a mapping stored for its data source with
+/// `dataSource.setContext`. It replaces the context that the data source
+/// was created with
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataSourceContextUpdate {
+    /// The name of the data source
+    pub data_source: String,
+    /// The address of the data source, empty if the data source has no
+    /// address
+    pub address: Vec<u8>,
+    pub context: DataSourceContext,
+}
+
+/// Callbacks that report the progress of pruning the history of a
+/// deployment. All methods do nothing by default
+pub trait PruneReporter: Send + 'static {
+    /// Pruning `table` is about to start
+    fn start_table(&mut self, _table: &str) {}
+    /// A batch of `rows` versions was removed from `table`; `done` is the
+    /// fraction of the table that has been looked at so far
+    fn prune_batch(&mut self, _table: &str, _rows: usize, _done: f64) {}
+    /// The indexes of `table` are about to be rebuilt
+    fn start_reindex(&mut self, _table: &str) {}
+    /// Pruning `table` has finished after removing `rows` versions
+    fn finish_table(&mut self, _table: &str, _rows: usize) {}
+}
+
 pub trait SubscriptionManager: Send + Sync + 'static {
     /// Subscribe to changes for specific subgraphs and entities.
     ///
@@ -930,6 +1036,51 @@ pub trait EnsLookup: Send + Sync + 'static {
     fn find_name(&self, hash: &str) -> Result<Option<String>, StoreError>;
 }
 
+/// Look up the timestamps of blocks on any chain the node has locally, so
+/// that mappings on one chain can refer to blocks on another
+pub trait BlockTimestampLookup: Send + Sync + 'static {
+    /// Return the timestamp of the final block `number` on `network`, or
+    /// `None` if the node does not have that block or it is not final yet
+    fn block_timestamp(&self, network: &str, number: BlockNumber) -> Result<Option<u64>, Error>;
+}
+
+/// Fetch the timestamps of final blocks of one chain from that chain's
+/// providers
+pub trait ChainBlockTimestamps: Send + Sync + 'static {
+    /// Return the timestamp of the final block `number`, or `None` if that
+    /// block is not final yet or the providers do not know it
+    fn block_timestamp(&self, number: BlockNumber) -> Result<Option<u64>, Error>;
+}
+
+/// Look up block timestamps in the local chain stores, and ask the
+/// providers of a chain for final blocks that are not in its store, for
+/// example, because they were evicted from the block cache
+pub struct FallbackBlockTimestamps {
+    store: Arc<dyn BlockTimestampLookup>,
+    providers: HashMap<String, Arc<dyn ChainBlockTimestamps>>,
+}
+
+impl FallbackBlockTimestamps {
+    pub fn new(
+        store: Arc<dyn BlockTimestampLookup>,
+        providers: HashMap<String, Arc<dyn ChainBlockTimestamps>>,
+    ) -> Self {
+        FallbackBlockTimestamps { store, providers }
+    }
+}
+
+impl BlockTimestampLookup for FallbackBlockTimestamps {
+    fn block_timestamp(&self, network: &str, number: BlockNumber) -> Result<Option<u64>, Error> {
+        if let Some(timestamp) = self.store.block_timestamp(network, number)? {
+            return Ok(Some(timestamp));
+        }
+        match self.providers.get(network) {
+            Some(providers) => providers.block_timestamp(number),
+            None => Ok(None),
+        }
+    }
+}
+
 /// Common trait for store implementations.
 #[async_trait]
 pub trait SubgraphStore: Send + Sync + 'static {
@@ -997,6 +1148,12 @@ pub trait SubgraphStore: Send + Sync + 'static {
         deployment: DeploymentId,
     ) -> Result<Arc<dyn WritableStore>, StoreError>;
 
+    /// Write any changes that are still pending for `deployment` and forget
+    /// its `WritableStore`, so that the next call to `writable` starts from
+    /// what is in the database. This must be called when the subgraph is
+    /// stopped, for example, because it was unassigned from this node
+    async fn stop_subgraph(&self, deployment: &DeploymentLocator) -> Result<(), StoreError>;
+
     /// The network indexer does not follow the normal flow of how subgraphs
     /// are indexed, and therefore needs a special way to get a
     /// `WritableStore`. This method should not be used outside of that, and
@@ -1013,8 +1170,177 @@ pub trait SubgraphStore: Send + Sync + 'static {
     /// being set up
     fn least_block_ptr(&self, id: &DeploymentHash) -> Result<Option<BlockPtr>, StoreError>;
 
+    /// The first block for which the deployment `id` still has data;
+    /// pruning removes the history before that block
+    fn earliest_block(&self, id: &DeploymentHash) -> Result<BlockNumber, StoreError>;
+
     /// Find the deployment locators for the subgraph with the given hash
     fn locators(&self, hash: &str) -> Result<Vec<DeploymentLocator>, StoreError>;
+
+    /// Return the labels that are attached to the deployment `id`
+    fn deployment_labels(
+        &self,
+        id: &DeploymentHash,
+    ) -> Result<BTreeMap<String, String>, StoreError>;
+
+    /// Return the hash of the genesis block of the chain that `deployment`
+    /// was created against, or `None` if that is not known
+    fn genesis_block_hash(
+        &self,
+        deployment: &DeploymentLocator,
+    ) -> Result<Option<BlockHash>, StoreError>;
+}
+
+/// Keeps track of the changes that have been handed to a `WritableStore`
+/// but that have not been written to the database yet, i.e., that wait in
+/// a write batch. Block streams use this to stop fetching blocks while the
+/// store is falling behind instead of buffering more and more blocks in
+/// memory. Changes that are written as part of the call that hands them to
+/// the store never wait and are not counted
+pub struct WriteBacklog {
+    batches: AtomicUsize,
+    ops: AtomicUsize,
+    bytes: AtomicUsize,
+    max_ops: usize,
+    max_bytes: usize,
+    drained: tokio::sync::Notify,
+    // How long the last batch waited before it was written, and how long
+    // writing it took
+    last_commit: Mutex<Option<(Duration, Duration)>>,
+}
+
+impl WriteBacklog {
+    pub fn new(max_ops: usize, max_bytes: usize) -> Self {
+        WriteBacklog {
+            batches: AtomicUsize::new(0),
+            ops: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+            max_ops,
+            max_bytes,
+            drained: tokio::sync::Notify::new(),
+            last_commit: Mutex::new(None),
+        }
+    }
+
+    /// Record that a batch of `ops` entity modifications of `bytes` total
+    /// size is waiting to be written
+    pub fn add(&self, ops: usize, bytes: usize) {
+        self.batches.fetch_add(1, Ordering::SeqCst);
+        self.ops.fetch_add(ops, Ordering::SeqCst);
+        self.bytes.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    /// Record that a batch previously passed to `add` has been written
+    pub fn remove(&self, ops: usize, bytes: usize) {
+        self.batches.fetch_sub(1, Ordering::SeqCst);
+        self.ops.fetch_sub(ops, Ordering::SeqCst);
+        self.bytes.fetch_sub(bytes, Ordering::SeqCst);
+        self.drained.notify_waiters();
+    }
+
+    /// The number of entity modifications and their size in bytes that
+    /// are waiting to be written
+    pub fn pending(&self) -> (usize, usize) {
+        (
+            self.ops.load(Ordering::SeqCst),
+            self.bytes.load(Ordering::SeqCst),
+        )
+    }
+
+    /// Record that a batch waited `wait` before it was written and that
+    /// writing it took `duration`
+    pub fn record_commit(&self, wait: Duration, duration: Duration) {
+        *self.last_commit.lock().unwrap() = Some((wait, duration));
+    }
+
+    /// The state of the backlog for the indexing status API
+    pub fn info(&self) -> status::WriteInfo {
+        let (ops, bytes) = self.pending();
+        let last_commit = *self.last_commit.lock().unwrap();
+        status::WriteInfo {
+            pending_batches: self.batches.load(Ordering::SeqCst),
+            pending_ops: ops,
+            pending_bytes: bytes,
+            last_commit_wait: last_commit.map(|(wait, _)| wait),
+            last_commit_duration: last_commit.map(|(_, duration)| duration),
+        }
+    }
+
+    /// Whether so many changes are waiting to be written that no more
+    /// blocks should be fetched
+    pub fn is_full(&self) -> bool {
+        let (ops, bytes) = self.pending();
+        ops > self.max_ops || bytes > self.max_bytes
+    }
+
+    /// Wait until the backlog is no longer full
+    pub async fn wait_for_capacity(&self) {
+        loop {
+            // Create the future before checking so that we can not miss a
+            // notification that happens in between
+            let drained = self.drained.notified();
+            if !self.is_full() {
+                return;
+            }
+            drained.await;
+        }
+    }
+}
+
+/// Approximations of the memory that indexing a deployment takes up, so
+/// that operators can tell which deployments are responsible for the
+/// memory usage of a node. They only cover the largest consumers and leave
+/// out everything else a deployment allocates
+#[derive(Default)]
+pub struct MemoryUsage {
+    entity_cache_bytes: AtomicUsize,
+    wasm_memory_bytes: AtomicUsize,
+    buffered_blocks: AtomicUsize,
+    buffered_triggers: AtomicUsize,
+}
+
+impl MemoryUsage {
+    /// Record the weight of the entity cache that is kept between blocks
+    pub fn set_entity_cache_bytes(&self, bytes: usize) {
+        self.entity_cache_bytes.store(bytes, Ordering::SeqCst);
+    }
+
+    /// Record the size of the linear memory of the Wasm instance that
+    /// handled the most recent trigger
+    pub fn set_wasm_memory_bytes(&self, bytes: usize) {
+        self.wasm_memory_bytes.store(bytes, Ordering::SeqCst);
+    }
+
+    /// Record that a block with `triggers` triggers is waiting in the
+    /// buffer of the block stream
+    pub fn add_buffered_block(&self, triggers: usize) {
+        self.buffered_blocks.fetch_add(1, Ordering::SeqCst);
+        self.buffered_triggers.fetch_add(triggers, Ordering::SeqCst);
+    }
+
+    /// Record that a block previously passed to `add_buffered_block` was
+    /// taken out of the buffer
+    pub fn remove_buffered_block(&self, triggers: usize) {
+        self.buffered_blocks.fetch_sub(1, Ordering::SeqCst);
+        self.buffered_triggers.fetch_sub(triggers, Ordering::SeqCst);
+    }
+
+    /// Forget about all buffered blocks, e.g., because the block stream
+    /// that buffered them was dropped
+    pub fn clear_buffered_blocks(&self) {
+        self.buffered_blocks.store(0, Ordering::SeqCst);
+        self.buffered_triggers.store(0, Ordering::SeqCst);
+    }
+
+    /// The memory usage for metrics and the indexing status API
+    pub fn info(&self) -> status::MemoryInfo {
+        status::MemoryInfo {
+            entity_cache_bytes: self.entity_cache_bytes.load(Ordering::SeqCst),
+            wasm_memory_bytes: self.wasm_memory_bytes.load(Ordering::SeqCst),
+            buffered_blocks: self.buffered_blocks.load(Ordering::SeqCst),
+            buffered_triggers: self.buffered_triggers.load(Ordering::SeqCst),
+        }
+    }
 }
 
 /// A view of the store for indexing. All indexing-related operations need
@@ -1026,6 +1352,19 @@ pub trait WritableStore: Send + Sync + 'static {
     /// Get a pointer to the most recently processed block in the subgraph.
     fn block_ptr(&self) -> Option<BlockPtr>;
 
+    /// The changes that are waiting to be written for this deployment, if
+    /// the store keeps track of them
+    fn write_backlog(&self) -> Option<Arc<WriteBacklog>> {
+        None
+    }
+
+    /// The memory that indexing this deployment takes up, if the store
+    /// keeps track of it. The store only holds on to it so that the
+    /// indexing status API can find it
+    fn memory_usage(&self) -> Option<Arc<MemoryUsage>> {
+        None
+    }
+
     /// Returns the Firehose `cursor` this deployment is currently at in the block stream of events. This
     /// is used when re-connecting a Firehose stream to start back exactly where we left off.
     fn block_cursor(&self) -> Option<String>;
@@ -1058,6 +1397,10 @@ pub trait WritableStore: Send + Sync + 'static {
     /// Set subgraph status to failed with the given error as the cause.
     async fn fail_subgraph(&self, error: SubgraphError) -> Result<(), StoreError>;
 
+    /// Record warnings that mappings emitted. Only the most recent warnings
+    /// for each deployment are kept.
+    fn add_warnings(&self, warnings: Vec<SubgraphWarning>) -> Result<(), StoreError>;
+
     async fn supports_proof_of_indexing(&self) -> Result<bool, StoreError>;
 
     /// Looks up an entity using the given store key at the latest block.
@@ -1074,6 +1417,8 @@ pub trait WritableStore: Send + Sync + 'static {
         mods: Vec<EntityModification>,
         stopwatch: StopwatchMetrics,
         data_sources: Vec<StoredDynamicDataSource>,
+        scheduled_handlers: Vec<ScheduledBlockHandler>,
+        data_source_contexts: Vec<DataSourceContextUpdate>,
         deterministic_errors: Vec<SubgraphError>,
     ) -> Result<(), StoreError>;
 
@@ -1098,6 +1443,16 @@ pub trait WritableStore: Send + Sync + 'static {
     /// Load the dynamic data sources for the given deployment
     async fn load_dynamic_data_sources(&self) -> Result<Vec<StoredDynamicDataSource>, StoreError>;
 
+    /// Load the scheduled block handlers that are due after the current
+    /// block pointer of the deployment, ordered by the block at which they
+    /// are due and the order in which they were scheduled
+    async fn load_scheduled_block_handlers(&self)
+        -> Result<Vec<ScheduledBlockHandler>, StoreError>;
+
+    /// Load the latest context that mappings stored with
+    /// `dataSource.setContext` for each data source
+    async fn load_data_source_contexts(&self) -> Result<Vec<DataSourceContextUpdate>, StoreError>;
+
     /// Report the name of the shard in which the subgraph is stored. This
     /// should only be used for reporting and monitoring
     fn shard(&self) -> &str;
@@ -1123,6 +1478,26 @@ pub trait QueryStoreManager: Send + Sync + 'static {
         target: QueryTarget,
         for_subscription: bool,
     ) -> Result<Arc<dyn QueryStore + Send + Sync>, QueryExecutionError>;
+
+    /// Get a new `QueryStore` for a query whose estimated cost is `cost`.
+    /// Stores can use the cost to send expensive queries to read replicas
+    /// and keep cheap ones on the main database; by default, this is the
+    /// same as `query_store(target, false)`
+    async fn query_store_for_cost(
+        &self,
+        target: QueryTarget,
+        _cost: u64,
+    ) -> Result<Arc<dyn QueryStore + Send + Sync>, QueryExecutionError> {
+        self.query_store(target, false).await
+    }
+
+    /// If `deployment` was a version of a subgraph whose current version
+    /// is a newer deployment, return that deployment and when it became
+    /// the current version. The `sunset_at` of the result is never set.
+    async fn deprecation(
+        &self,
+        deployment: &DeploymentHash,
+    ) -> Result<Option<Deprecation>, QueryExecutionError>;
 }
 
 // The type that the connection pool uses to track wait times for
@@ -1135,6 +1510,24 @@ pub trait BlockStore: Send + Sync + 'static {
     fn chain_store(&self, network: &str) -> Option<Arc<Self::ChainStore>>;
 }
 
+/// A Firehose cursor for backfilling blocks, together with the provider
+/// that issued it. The cursor can only be used with that provider.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackfillCursor {
+    pub provider: String,
+    pub cursor: String,
+}
+
+/// Writes blocks into a chain store in bulk, for example, during backfill.
+/// A copier uses one database connection for all the blocks it copies and
+/// releases it when it is dropped.
+#[async_trait]
+pub trait BlockCopier: Send {
+    /// Insert or update all `blocks` using `COPY`, which is much faster
+    /// than `upsert_blocks` for large batches of blocks.
+    async fn copy_blocks(&mut self, blocks: Vec<Arc<dyn Block>>) -> Result<(), Error>;
+}
+
 /// Common trait for blockchain store implementations.
 #[async_trait]
 pub trait ChainStore: Send + Sync + 'static {
@@ -1146,6 +1539,14 @@ pub trait ChainStore: Send + Sync + 'static {
 
     fn upsert_light_blocks(&self, blocks: &[&dyn Block]) -> Result<(), Error>;
 
+    /// Insert or update all `blocks` with a single statement.
+    async fn upsert_blocks(&self, blocks: Vec<Arc<dyn Block>>) -> Result<(), Error>;
+
+    /// Create a `BlockCopier` for bulk inserts of blocks. Callers should
+    /// use one copier for a whole backfill run so that its batches share
+    /// one connection.
+    fn block_copier(&self) -> Box<dyn BlockCopier>;
+
     /// Try to update the head block pointer to the block with the highest block number.
     ///
     /// Only updates pointer if there is a block with a higher block number than the current head
@@ -1182,19 +1583,87 @@ pub trait ChainStore: Send + Sync + 'static {
     /// The head block cursor will be None on initial set up.
     fn chain_head_cursor(&self) -> Result<Option<String>, Error>;
 
-    /// This method does actually three operations:
+    /// Get the latest head block cursor that the Firehose `provider` gave
+    /// us, together with the block it was given for. Cursors can only be
+    /// used with the provider that issued them.
+    fn provider_head_cursor(&self, provider: &str) -> Result<Option<(String, BlockPtr)>, Error>;
+
+    /// Remove the head block cursor for this chain, and the cursors of all
+    /// providers, so that the next time the chain head is ingested, the
+    /// stream starts without a cursor. The head block pointer itself is left
+    /// unchanged.
+    fn clear_chain_head_cursor(&self) -> Result<(), Error>;
+
+    /// Get the latest block of this chain that the Firehose reported as
+    /// irreversible, if any.
+    fn chain_final_block_ptr(&self) -> Result<Option<BlockPtr>, Error>;
+
+    /// Record `ptr` as the latest irreversible block of this chain.
+    fn set_chain_final_block(&self, ptr: &BlockPtr) -> Result<(), Error>;
+
+    /// Get the block number up to which historical blocks should be
+    /// backfilled for this chain, if one was set.
+    fn chain_backfill_target_block_num(&self) -> Result<Option<BlockNumber>, Error>;
+
+    /// Set the block number up to which historical blocks should be
+    /// backfilled for this chain. Passing `None` clears the target.
+    fn set_chain_backfill_target_block_num(&self, target: Option<BlockNumber>)
+        -> Result<(), Error>;
+
+    /// Get the backfill progress for this chain, i.e., the highest block
+    /// number such that all blocks up to it have been backfilled, and the
+    /// cursor of the stream that is backfilling the blocks after it.
+    ///
+    /// Both will be None if no backfill has been done yet.
+    fn chain_backfill_progress(
+        &self,
+    ) -> Result<(Option<BlockNumber>, Option<BackfillCursor>), Error>;
+
+    /// Record the backfill progress for this chain. Passing `None` for both
+    /// resets the backfill so that it starts again from the genesis block.
+    fn set_chain_backfill_progress(
+        &self,
+        number: Option<BlockNumber>,
+        cursor: Option<BackfillCursor>,
+    ) -> Result<(), Error>;
+
+    /// Get the ABI that was cached for the contract at `address` whose
+    /// runtime code has the keccak256 hash `code_hash`, if any.
+    fn cached_abi(&self, address: &[u8], code_hash: &[u8]) -> Result<Option<String>, Error>;
+
+    /// Cache the JSON `abi` for the contract at `address` whose runtime
+    /// code has the keccak256 hash `code_hash`.
+    fn set_cached_abi(&self, address: &[u8], code_hash: &[u8], abi: &str) -> Result<(), Error>;
+
+    /// Like `set_chain_head`, but upserts all of `blocks` and makes the
+    /// last of them the chain head, all in one transaction. Does nothing if
+    /// `blocks` is empty.
+    async fn set_chain_head_batch(
+        self: Arc<Self>,
+        blocks: Vec<Arc<dyn Block>>,
+        cursor: String,
+        provider: &str,
+    ) -> Result<(), Error>;
+
+    /// This method does actually four operations:
     /// - Upserts received block into blocks table
     /// - Update chain head block into networks table
     /// - Update chain head cursor into networks table
+    /// - Remember the cursor as the latest one from `provider`
     async fn set_chain_head(
         self: Arc<Self>,
         block: Arc<dyn Block>,
         cursor: String,
+        provider: &str,
     ) -> Result<(), Error>;
 
     /// Returns the blocks present in the store.
     fn blocks(&self, hashes: &[H256]) -> Result<Vec<serde_json::Value>, Error>;
 
+    /// Returns the data of the blocks present in the store, in binary form
+    /// if the block was stored that way, and as JSON otherwise
+    fn block_data(&self, hashes: &[H256]) -> Result<Vec<BlockData>, Error>;
+
     /// Get the `offset`th ancestor of `block_hash`, where offset=0 means the block matching
     /// `block_hash` and offset=1 means its parent. Returns None if unable to complete due to
     /// missing blocks in the chain store.
@@ -1219,6 +1688,20 @@ pub trait ChainStore: Send + Sync + 'static {
     /// Return the hashes of all blocks with the given number
     fn block_hashes_by_block_number(&self, number: BlockNumber) -> Result<Vec<H256>, Error>;
 
+    /// Return the timestamp (in seconds) of the block with the given
+    /// `number` if that block is final and in the store. The result for a
+    /// final block never changes, which makes it safe to expose to mappings.
+    fn final_block_timestamp(&self, number: BlockNumber) -> Result<Option<u64>, Error>;
+
+    /// Return the number of the latest final block. That is the final
+    /// block the chain recorded, or the block `REORG_THRESHOLD` blocks
+    /// behind the chain head for chains that do not record one
+    fn final_block_number(&self) -> Result<Option<BlockNumber>, Error>;
+
+    /// Return the block with the highest number that is in the store,
+    /// regardless of what the chain head pointer says
+    fn latest_stored_block_ptr(&self) -> Result<Option<BlockPtr>, Error>;
+
     /// Confirm that block number `number` has hash `hash` and that the store
     /// may purge any other blocks with that number
     fn confirm_block_hash(&self, number: BlockNumber, hash: &H256) -> Result<usize, Error>;
@@ -1233,6 +1716,16 @@ pub trait ChainStore: Send + Sync + 'static {
     ) -> Result<Vec<transaction_receipt::LightTransactionReceipt>, StoreError>;
 }
 
+/// The data that the `ChainStore` holds for a block
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlockData {
+    /// The protobuf encoding of a block that came from Firehose
+    Bytes(Vec<u8>),
+    /// Blocks of chains that do not provide a binary encoding, and blocks
+    /// that were stored before the chain store could hold binary data
+    Json(serde_json::Value),
+}
+
 pub trait EthereumCallCache: Send + Sync + 'static {
     /// Cached return value.
     fn get_call(
@@ -1260,6 +1753,16 @@ pub trait QueryStore: Send + Sync {
         query: EntityQuery,
     ) -> Result<Vec<BTreeMap<String, r::Value>>, QueryExecutionError>;
 
+    /// Search all fulltext fields of the deployment for `text` and return
+    /// the matching entities of all types, ordered by decreasing rank
+    fn fulltext_search(
+        &self,
+        text: &str,
+        range: EntityRange,
+        block: BlockNumber,
+        query_id: Option<String>,
+    ) -> Result<Vec<BTreeMap<String, r::Value>>, QueryExecutionError>;
+
     async fn is_deployment_synced(&self) -> Result<bool, Error>;
 
     fn block_ptr(&self) -> Result<Option<BlockPtr>, StoreError>;
@@ -1311,6 +1814,16 @@ pub trait StatusStore: Send + Sync + 'static {
         deployment_hash: &str,
     ) -> Result<Vec<(String, String)>, StoreError>;
 
+    /// Support for the block debug API. Return summaries of all blocks
+    /// matching `lookup` that are stored for `network`. There can be more
+    /// than one block for a number if the chain store holds blocks from
+    /// different forks
+    fn stored_blocks(
+        &self,
+        network: &str,
+        lookup: BlockLookup,
+    ) -> Result<Vec<StoredBlock>, StoreError>;
+
     /// A value of None indicates that the table is not available. Re-deploying
     /// the subgraph fixes this. It is undesirable to force everything to
     /// re-sync from scratch, so existing deployments will continue without a
@@ -1322,6 +1835,15 @@ pub trait StatusStore: Send + Sync + 'static {
         indexer: &Option<Address>,
         block: BlockPtr,
     ) -> Result<Option<[u8; 32]>, StoreError>;
+
+    /// Support for the transaction receipt debug API. Return the receipts
+    /// that the chain store for `network` has cached for the block with
+    /// `block_hash`
+    async fn transaction_receipts(
+        &self,
+        network: &str,
+        block_hash: &H256,
+    ) -> Result<Vec<transaction_receipt::LightTransactionReceipt>, StoreError>;
 }
 
 /// An entity operation that can be transacted into the store; as opposed to
@@ -1415,6 +1937,9 @@ pub struct EntityCache {
     // Marks whether updates should go in `handler_updates`.
     in_handler: bool,
 
+    /// Keys that `get_in_block` looked up without finding a change
+    misses_in_block: HashSet<EntityKey>,
+
     data_sources: Vec<StoredDynamicDataSource>,
 
     /// The store is only used to read entities.
@@ -1443,6 +1968,7 @@ impl EntityCache {
             updates: HashMap::new(),
             handler_updates: HashMap::new(),
             in_handler: false,
+            misses_in_block: HashSet::new(),
             data_sources: vec![],
             store,
         }
@@ -1457,6 +1983,7 @@ impl EntityCache {
             updates: HashMap::new(),
             handler_updates: HashMap::new(),
             in_handler: false,
+            misses_in_block: HashSet::new(),
             data_sources: vec![],
             store,
         }
@@ -1496,10 +2023,65 @@ impl EntityCache {
         Ok(entity)
     }
 
+    /// Get the entity with the given `key` if it was changed earlier in
+    /// the current block. Unlike `get`, this never queries the store, and
+    /// returns `None` for entities that the block has not touched or that
+    /// it removed.
+    pub fn get_in_block(&mut self, key: &EntityKey) -> Option<Entity> {
+        if !self.updates.contains_key(key) && !self.handler_updates.contains_key(key) {
+            self.misses_in_block.insert(key.clone());
+            return None;
+        }
+
+        let mut entity = self.current.get(key).cloned().flatten();
+        if let Some(op) = self.updates.get(key).cloned() {
+            entity = op.apply_to(entity)
+        }
+        if let Some(op) = self.handler_updates.get(key).cloned() {
+            entity = op.apply_to(entity)
+        }
+        entity
+    }
+
     pub fn remove(&mut self, key: EntityKey) {
         self.entity_op(key, EntityOp::Remove);
     }
 
+    /// The keys of the entities that were changed through this cache
+    pub fn updated_keys(&self) -> impl Iterator<Item = &EntityKey> {
+        assert!(!self.in_handler);
+        self.updates.keys()
+    }
+
+    /// The keys of the entities that were read or changed through this
+    /// cache. The outcome of changes made through two caches that started
+    /// out empty does not depend on their order as long as neither cache
+    /// changed an entity that the other one touched
+    pub fn touched_keys(&self) -> impl Iterator<Item = &EntityKey> {
+        assert!(!self.in_handler);
+        self.current
+            .keys()
+            .chain(self.updates.keys())
+            .chain(self.misses_in_block.iter())
+    }
+
+    /// A key that one of `caches` changed and another one touched, if
+    /// there is any. Changes made through caches without such a key can be
+    /// combined with `extend` in any order
+    pub fn conflicting_key<'a>(caches: &[&'a EntityCache]) -> Option<&'a EntityKey> {
+        let mut writers: HashMap<&EntityKey, usize> = HashMap::new();
+        for (idx, cache) in caches.iter().enumerate() {
+            for key in cache.updated_keys() {
+                writers.insert(key, idx);
+            }
+        }
+        caches.iter().enumerate().find_map(|(idx, cache)| {
+            cache
+                .touched_keys()
+                .find(|key| writers.get(key).map_or(false, |writer| *writer != idx))
+        })
+    }
+
     /// Store the `entity` under the given `key`. The `entity` may be only a
     /// partial entity; the cache will ensure partial updates get merged
     /// with existing data. The entity will be validated against the
@@ -1565,10 +2147,11 @@ impl EntityCache {
         }
     }
 
-    pub(crate) fn extend(&mut self, other: EntityCache) {
+    pub fn extend(&mut self, other: EntityCache) {
         assert!(!other.in_handler);
 
         self.current.extend(other.current);
+        self.misses_in_block.extend(other.misses_in_block);
         for (key, op) in other.updates {
             self.entity_op(key, op);
         }
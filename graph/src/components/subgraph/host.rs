@@ -7,6 +7,7 @@ use async_trait::async_trait;
 use futures::sync::mpsc;
 
 use crate::blockchain::TriggerWithHandler;
+use crate::components::store::{DataSourceContextUpdate, MemoryUsage, ScheduledBlockHandler};
 use crate::prelude::*;
 use crate::{blockchain::Blockchain, components::subgraph::SharedProofOfIndexing};
 use crate::{components::metrics::HistogramVec, runtime::DeterministicHostError};
@@ -50,6 +51,14 @@ pub trait RuntimeHost<C: Blockchain>: Send + Sync + 'static {
         logger: &Logger,
     ) -> Result<Option<TriggerWithHandler<C>>, Error>;
 
+    /// If `handler` was scheduled by this host's data source, return the
+    /// trigger for running it on `block`
+    fn match_scheduled_handler(
+        &self,
+        handler: &ScheduledBlockHandler,
+        block: Arc<C::Block>,
+    ) -> Result<Option<TriggerWithHandler<C>>, Error>;
+
     async fn process_mapping_trigger(
         &self,
         logger: &Logger,
@@ -62,12 +71,21 @@ pub trait RuntimeHost<C: Blockchain>: Send + Sync + 'static {
     /// Block number in which this host was created.
     /// Returns `None` for static data sources.
     fn creation_block_number(&self) -> Option<BlockNumber>;
+
+    /// If `update` is for this host's data source, use its context for
+    /// later triggers instead of the context the data source was created
+    /// with
+    fn update_context(&self, update: &DataSourceContextUpdate);
+
+    /// Go back to the context the data source was created with
+    fn reset_context(&self);
 }
 
 pub struct HostMetrics {
     handler_execution_time: Box<HistogramVec>,
     host_fn_execution_time: Box<HistogramVec>,
     pub stopwatch: StopwatchMetrics,
+    pub memory_usage: Arc<MemoryUsage>,
 }
 
 impl HostMetrics {
@@ -75,6 +93,7 @@ impl HostMetrics {
         registry: Arc<impl MetricsRegistry>,
         subgraph: &str,
         stopwatch: StopwatchMetrics,
+        memory_usage: Arc<MemoryUsage>,
     ) -> Self {
         let handler_execution_time = registry
             .new_deployment_histogram_vec(
@@ -98,6 +117,7 @@ impl HostMetrics {
             handler_execution_time,
             host_fn_execution_time,
             stopwatch,
+            memory_usage,
         }
     }
 
@@ -156,9 +176,11 @@ pub trait RuntimeHostBuilder<C: Blockchain>: Clone + Send + Sync + 'static {
     ) -> Result<Self::Host, Error>;
 
     /// Spawn a mapping and return a channel for mapping requests. The sender should be able to be
-    /// cached and shared among mappings that use the same wasm file.
+    /// cached and shared among mappings that use the same wasm file. The `api_version` of the
+    /// mapping determines which optional Wasm features the module may use.
     fn spawn_mapping(
         raw_module: Vec<u8>,
+        api_version: semver::Version,
         logger: Logger,
         subgraph_id: DeploymentHash,
         metrics: Arc<HostMetrics>,
@@ -1,7 +1,12 @@
+use std::collections::BTreeMap;
+
 use crate::blockchain::Blockchain;
 use crate::prelude::*;
 use crate::util::lfu_cache::LfuCache;
-use crate::{components::store::WritableStore, data::subgraph::schema::SubgraphError};
+use crate::{
+    components::store::{DataSourceContextUpdate, ScheduledBlockHandler, WritableStore},
+    data::subgraph::schema::{SubgraphError, SubgraphWarning},
+};
 
 #[derive(Clone, Debug)]
 pub struct DataSourceTemplateInfo<C: Blockchain> {
@@ -15,11 +20,32 @@ pub struct DataSourceTemplateInfo<C: Blockchain> {
 pub struct BlockState<C: Blockchain> {
     pub entity_cache: EntityCache,
     pub deterministic_errors: Vec<SubgraphError>,
+    pub warnings: Vec<SubgraphWarning>,
     created_data_sources: Vec<DataSourceTemplateInfo<C>>,
 
     // Data sources created in the current handler.
     handler_created_data_sources: Vec<DataSourceTemplateInfo<C>>,
 
+    // Block handlers scheduled with `scheduler.atBlock` in this block.
+    pub scheduled_handlers: Vec<ScheduledBlockHandler>,
+
+    // Block handlers scheduled in the current handler.
+    handler_scheduled_handlers: Vec<ScheduledBlockHandler>,
+
+    // Data source contexts stored with `dataSource.setContext` in this block.
+    pub data_source_contexts: Vec<DataSourceContextUpdate>,
+
+    // Data source contexts stored in the current handler.
+    handler_data_source_contexts: Vec<DataSourceContextUpdate>,
+
+    // Values that handlers of the current block stored with
+    // `block.context.set`. They are only visible to later handlers of the
+    // same block.
+    block_context: BTreeMap<String, Value>,
+
+    // Values stored with `block.context.set` in the current handler.
+    handler_block_context: BTreeMap<String, Value>,
+
     // Marks whether a handler is currently executing.
     in_handler: bool,
 }
@@ -32,8 +58,15 @@ impl<C: Blockchain> BlockState<C> {
         BlockState {
             entity_cache: EntityCache::with_current(store, lfu_cache),
             deterministic_errors: Vec::new(),
+            warnings: Vec::new(),
             created_data_sources: Vec::new(),
             handler_created_data_sources: Vec::new(),
+            scheduled_handlers: Vec::new(),
+            handler_scheduled_handlers: Vec::new(),
+            data_source_contexts: Vec::new(),
+            handler_data_source_contexts: Vec::new(),
+            block_context: BTreeMap::new(),
+            handler_block_context: BTreeMap::new(),
             in_handler: false,
         }
     }
@@ -44,19 +77,53 @@ impl<C: Blockchain> BlockState<C> {
         let BlockState {
             entity_cache,
             deterministic_errors,
+            warnings,
             created_data_sources,
             handler_created_data_sources,
+            scheduled_handlers,
+            handler_scheduled_handlers,
+            data_source_contexts,
+            handler_data_source_contexts,
+            block_context,
+            handler_block_context,
             in_handler,
         } = self;
 
         match in_handler {
-            true => handler_created_data_sources.extend(other.created_data_sources),
-            false => created_data_sources.extend(other.created_data_sources),
+            true => {
+                handler_created_data_sources.extend(other.created_data_sources);
+                handler_scheduled_handlers.extend(other.scheduled_handlers);
+                handler_data_source_contexts.extend(other.data_source_contexts);
+                handler_block_context.extend(other.block_context);
+            }
+            false => {
+                created_data_sources.extend(other.created_data_sources);
+                scheduled_handlers.extend(other.scheduled_handlers);
+                data_source_contexts.extend(other.data_source_contexts);
+                block_context.extend(other.block_context);
+            }
         }
         deterministic_errors.extend(other.deterministic_errors);
+        warnings.extend(other.warnings);
         entity_cache.extend(other.entity_cache);
     }
 
+    /// Whether the only effects of the handlers that ran with this state
+    /// are entity changes. Only such states can be merged with states of
+    /// other triggers of the same block regardless of the order of the
+    /// triggers, since errors, warnings, created data sources, scheduled
+    /// handlers, data source contexts and the block context all depend on
+    /// that order
+    pub fn has_only_entity_changes(&self) -> bool {
+        assert!(!self.in_handler);
+        self.deterministic_errors.is_empty()
+            && self.warnings.is_empty()
+            && self.created_data_sources.is_empty()
+            && self.scheduled_handlers.is_empty()
+            && self.data_source_contexts.is_empty()
+            && self.block_context.is_empty()
+    }
+
     pub fn has_errors(&self) -> bool {
         !self.deterministic_errors.is_empty()
     }
@@ -82,6 +149,12 @@ impl<C: Blockchain> BlockState<C> {
         self.in_handler = false;
         self.created_data_sources
             .extend(self.handler_created_data_sources.drain(..));
+        self.scheduled_handlers
+            .extend(self.handler_scheduled_handlers.drain(..));
+        self.data_source_contexts
+            .extend(self.handler_data_source_contexts.drain(..));
+        self.block_context
+            .extend(std::mem::take(&mut self.handler_block_context));
         self.entity_cache.exit_handler()
     }
 
@@ -89,6 +162,9 @@ impl<C: Blockchain> BlockState<C> {
         assert!(self.in_handler);
         self.in_handler = false;
         self.handler_created_data_sources.clear();
+        self.handler_scheduled_handlers.clear();
+        self.handler_data_source_contexts.clear();
+        self.handler_block_context.clear();
         self.entity_cache.exit_handler_and_discard_changes();
         self.deterministic_errors.push(e);
     }
@@ -97,4 +173,46 @@ impl<C: Blockchain> BlockState<C> {
         assert!(self.in_handler);
         self.handler_created_data_sources.push(ds);
     }
+
+    /// Remember that `handler` should run once the subgraph processes the
+    /// block it was scheduled for. The handler is discarded if the current
+    /// handler fails.
+    pub fn push_scheduled_handler(&mut self, handler: ScheduledBlockHandler) {
+        assert!(self.in_handler);
+        self.handler_scheduled_handlers.push(handler);
+    }
+
+    /// Remember that a mapping stored a new context for its data source.
+    /// The context is discarded if the current handler fails.
+    pub fn push_data_source_context(&mut self, update: DataSourceContextUpdate) {
+        assert!(self.in_handler);
+        self.handler_data_source_contexts.push(update);
+    }
+
+    /// Return the latest context that the current or an earlier handler of
+    /// this block stored for the data source with `name` and `address`
+    pub fn data_source_context(&self, name: &str, address: &[u8]) -> Option<&DataSourceContext> {
+        self.handler_data_source_contexts
+            .iter()
+            .rev()
+            .chain(self.data_source_contexts.iter().rev())
+            .find(|update| update.data_source == name && update.address == address)
+            .map(|update| &update.context)
+    }
+
+    /// Store `value` under `key` so that later handlers of the same block
+    /// can read it with `block_context`. The value is discarded if the
+    /// current handler fails.
+    pub fn set_block_context(&mut self, key: String, value: Value) {
+        assert!(self.in_handler);
+        self.handler_block_context.insert(key, value);
+    }
+
+    /// Return the value stored under `key` by the current or an earlier
+    /// handler of this block
+    pub fn block_context(&self, key: &str) -> Option<&Value> {
+        self.handler_block_context
+            .get(key)
+            .or_else(|| self.block_context.get(key))
+    }
 }
@@ -12,7 +12,7 @@ pub use self::instance::{BlockState, DataSourceTemplateInfo};
 pub use self::instance_manager::SubgraphInstanceManager;
 pub use self::proof_of_indexing::{
     BlockEventStream, CausalityRegion, ProofOfIndexing, ProofOfIndexingEvent,
-    ProofOfIndexingFinisher, SharedProofOfIndexing,
+    ProofOfIndexingFinisher, RecordedHandler, SharedProofOfIndexing,
 };
 pub use self::provider::SubgraphAssignmentProvider;
 pub use self::registrar::{SubgraphRegistrar, SubgraphVersionSwitchingMode};
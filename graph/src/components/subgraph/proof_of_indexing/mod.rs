@@ -3,7 +3,7 @@ mod online;
 mod reference;
 
 pub use event::ProofOfIndexingEvent;
-pub use online::{BlockEventStream, ProofOfIndexing, ProofOfIndexingFinisher};
+pub use online::{BlockEventStream, ProofOfIndexing, ProofOfIndexingFinisher, RecordedHandler};
 pub use reference::CausalityRegion;
 
 use atomic_refcell::AtomicRefCell;
@@ -262,4 +262,118 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn recording_replays_events() {
+        let logger = Logger::root(Discard, o!());
+        let data = hashmap! {
+            "val".to_owned() => Value::Int(1)
+        };
+        let set = ProofOfIndexingEvent::SetEntity {
+            entity_type: "t",
+            id: "id",
+            data: &data,
+        };
+        let remove = ProofOfIndexingEvent::RemoveEntity {
+            entity_type: "t",
+            id: "id",
+        };
+
+        let write = |poi: &mut ProofOfIndexing| {
+            poi.start_handler("eth");
+            poi.write(&logger, "eth", &set);
+            poi.start_handler("eth");
+            poi.write(&logger, "eth", &remove);
+            poi.write_deterministic_error(&logger, "eth");
+        };
+
+        let mut direct = ProofOfIndexing::new(1);
+        write(&mut direct);
+
+        let mut recording = ProofOfIndexing::recording(1);
+        write(&mut recording);
+        let mut replayed = ProofOfIndexing::new(1);
+        for handler in recording.take_recorded_handlers() {
+            replayed.replay(&logger, handler);
+        }
+
+        let digest = |poi: ProofOfIndexing| {
+            let mut regions = poi.take();
+            regions.remove("eth").unwrap().pause(None)
+        };
+        assert_eq!(digest(direct), digest(replayed));
+    }
+
+    #[test]
+    fn replay_in_trigger_order_matches_sequential_processing() {
+        let logger = Logger::root(Discard, o!());
+        let data = hashmap! {
+            "val".to_owned() => Value::Int(1)
+        };
+        let events = vec![
+            ProofOfIndexingEvent::SetEntity {
+                entity_type: "t",
+                id: "a",
+                data: &data,
+            },
+            ProofOfIndexingEvent::SetEntity {
+                entity_type: "u",
+                id: "b",
+                data: &data,
+            },
+            ProofOfIndexingEvent::RemoveEntity {
+                entity_type: "t",
+                id: "c",
+            },
+            ProofOfIndexingEvent::RemoveEntity {
+                entity_type: "u",
+                id: "d",
+            },
+        ];
+        // The group of data sources that handles each trigger; the third
+        // trigger is not handled by any data source
+        let group_of_trigger = vec![Some(0), Some(1), None, Some(0), Some(1)];
+
+        let sequential = || {
+            let mut poi = ProofOfIndexing::new(1);
+            let mut events = events.iter();
+            for group in &group_of_trigger {
+                poi.start_handler("eth");
+                if group.is_some() {
+                    poi.write(&logger, "eth", events.next().unwrap());
+                }
+            }
+            poi
+        };
+        let recorded = || {
+            let mut groups = vec![ProofOfIndexing::recording(1), ProofOfIndexing::recording(1)];
+            let mut events = events.iter();
+            for group in group_of_trigger.iter().flatten() {
+                groups[*group].start_handler("eth");
+                groups[*group].write(&logger, "eth", events.next().unwrap());
+            }
+            groups
+                .into_iter()
+                .map(|poi| poi.take_recorded_handlers())
+                .collect::<Vec<_>>()
+        };
+        let digest = |poi: ProofOfIndexing| {
+            let mut regions = poi.take();
+            regions.remove("eth").unwrap().pause(None)
+        };
+
+        let mut replayed = ProofOfIndexing::new(1);
+        replayed.replay_in_trigger_order(&logger, "eth", &group_of_trigger, recorded());
+        assert_eq!(digest(sequential()), digest(replayed));
+
+        // Replaying one group after the other changes the digest
+        let mut by_group = ProofOfIndexing::new(1);
+        by_group.replay_in_trigger_order(
+            &logger,
+            "eth",
+            &[Some(0), Some(0), Some(1), Some(1), None],
+            recorded(),
+        );
+        assert_ne!(digest(sequential()), digest(by_group));
+    }
 }
@@ -5,7 +5,7 @@
 use super::ProofOfIndexingEvent;
 use crate::{
     blockchain::BlockPtr,
-    prelude::{debug, BlockNumber, DeploymentHash, Logger},
+    prelude::{debug, BlockNumber, DeploymentHash, Logger, Value},
 };
 use lazy_static::lazy_static;
 use stable_hash::crypto::{Blake3SeqNo, SetHasher};
@@ -108,6 +108,30 @@ impl BlockEventStream {
     }
 }
 
+/// An event that a recording `ProofOfIndexing` keeps instead of hashing it
+enum RecordedEvent {
+    RemoveEntity {
+        causality_region: String,
+        entity_type: String,
+        id: String,
+    },
+    SetEntity {
+        causality_region: String,
+        entity_type: String,
+        id: String,
+        data: HashMap<String, Value>,
+    },
+    DeterministicError {
+        causality_region: String,
+    },
+}
+
+/// The events that one handler wrote to a recording `ProofOfIndexing`
+pub struct RecordedHandler {
+    causality_region: String,
+    events: Vec<RecordedEvent>,
+}
+
 #[derive(Default)]
 pub struct ProofOfIndexing {
     block_number: BlockNumber,
@@ -116,6 +140,8 @@ pub struct ProofOfIndexing {
     /// state with other data sources. This may also give us some freedom to change
     /// the order of triggers in the future.
     per_causality_region: HashMap<String, BlockEventStream>,
+    /// Set for a recording proof of indexing, see `ProofOfIndexing::recording`
+    recorded: Option<Vec<RecordedHandler>>,
 }
 
 impl fmt::Debug for ProofOfIndexing {
@@ -129,10 +155,116 @@ impl ProofOfIndexing {
         Self {
             block_number,
             per_causality_region: HashMap::new(),
+            recorded: None,
+        }
+    }
+
+    /// A proof of indexing that does not update any digest, but records the
+    /// events of each handler so that they can be replayed into another
+    /// proof of indexing with `replay`. This makes it possible to process
+    /// the triggers of a block out of order and still hash their events in
+    /// the order of the triggers
+    pub fn recording(block_number: BlockNumber) -> Self {
+        Self {
+            block_number,
+            per_causality_region: HashMap::new(),
+            recorded: Some(Vec::new()),
+        }
+    }
+
+    /// The handlers recorded by a recording proof of indexing, in the order
+    /// in which they were started
+    pub fn take_recorded_handlers(self) -> Vec<RecordedHandler> {
+        self.recorded
+            .expect("only a recording proof of indexing has recorded handlers")
+    }
+
+    /// Write the events of a recorded handler as if the handler had been
+    /// processed with this proof of indexing
+    pub fn replay(&mut self, logger: &Logger, handler: RecordedHandler) {
+        self.start_handler(&handler.causality_region);
+        for event in handler.events {
+            match event {
+                RecordedEvent::RemoveEntity {
+                    causality_region,
+                    entity_type,
+                    id,
+                } => self.write(
+                    logger,
+                    &causality_region,
+                    &ProofOfIndexingEvent::RemoveEntity {
+                        entity_type: &entity_type,
+                        id: &id,
+                    },
+                ),
+                RecordedEvent::SetEntity {
+                    causality_region,
+                    entity_type,
+                    id,
+                    data,
+                } => self.write(
+                    logger,
+                    &causality_region,
+                    &ProofOfIndexingEvent::SetEntity {
+                        entity_type: &entity_type,
+                        id: &id,
+                        data: &data,
+                    },
+                ),
+                RecordedEvent::DeterministicError { causality_region } => {
+                    self.write_deterministic_error(logger, &causality_region)
+                }
+            }
         }
     }
 
+    /// Replay handlers that were recorded by several recording proofs of
+    /// indexing in the order of the triggers that started them.
+    /// `group_of_trigger` names, for each trigger of the block, the index
+    /// into `recorded` of the proof of indexing that processed it; triggers
+    /// without a group only start a handler
+    pub fn replay_in_trigger_order(
+        &mut self,
+        logger: &Logger,
+        causality_region: &str,
+        group_of_trigger: &[Option<usize>],
+        recorded: Vec<Vec<RecordedHandler>>,
+    ) {
+        let mut recorded: Vec<_> = recorded
+            .into_iter()
+            .map(|handlers| handlers.into_iter())
+            .collect();
+        for group in group_of_trigger {
+            match group {
+                Some(group) => {
+                    let handler = recorded[*group]
+                        .next()
+                        .expect("every processed trigger starts a handler");
+                    self.replay(logger, handler);
+                }
+                None => self.start_handler(causality_region),
+            }
+        }
+    }
+
+    fn record(&mut self, event: RecordedEvent) {
+        // Unwrap: Only called for a recording proof of indexing, and events
+        // are only written in handlers.
+        let handlers = self.recorded.as_mut().unwrap();
+        handlers
+            .last_mut()
+            .expect("events are written after a handler starts")
+            .events
+            .push(event);
+    }
+
     pub fn write_deterministic_error(&mut self, logger: &Logger, causality_region: &str) {
+        if self.recorded.is_some() {
+            return self.record(RecordedEvent::DeterministicError {
+                causality_region: causality_region.to_owned(),
+            });
+        }
+
         let redacted_events = self.with_causality_region(causality_region, |entry| {
             entry.vec_length - entry.handler_start
         });
@@ -159,10 +291,43 @@ impl ProofOfIndexing {
             );
         }
 
+        if self.recorded.is_some() {
+            let causality_region = causality_region.to_owned();
+            return self.record(match event {
+                ProofOfIndexingEvent::RemoveEntity { entity_type, id } => {
+                    RecordedEvent::RemoveEntity {
+                        causality_region,
+                        entity_type: entity_type.to_string(),
+                        id: id.to_string(),
+                    }
+                }
+                ProofOfIndexingEvent::SetEntity {
+                    entity_type,
+                    id,
+                    data,
+                } => RecordedEvent::SetEntity {
+                    causality_region,
+                    entity_type: entity_type.to_string(),
+                    id: id.to_string(),
+                    data: (*data).clone(),
+                },
+                ProofOfIndexingEvent::DeterministicError { .. } => {
+                    RecordedEvent::DeterministicError { causality_region }
+                }
+            });
+        }
+
         self.with_causality_region(causality_region, |entry| entry.write(event))
     }
 
     pub fn start_handler(&mut self, causality_region: &str) {
+        if let Some(handlers) = &mut self.recorded {
+            return handlers.push(RecordedHandler {
+                causality_region: causality_region.to_owned(),
+                events: Vec::new(),
+            });
+        }
+
         self.with_causality_region(causality_region, |entry| entry.start_handler())
     }
 
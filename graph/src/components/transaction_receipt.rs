@@ -3,7 +3,7 @@
 //! This module exposes the [`LightTransactionReceipt`] type, which holds basic information about
 //! the retrieved transaction receipts.
 
-use web3::types::{TransactionReceipt, H256, U256, U64};
+use web3::types::{Log, TransactionReceipt, H256, U256, U64};
 
 /// Like web3::types::Receipt, but with fewer fields.
 #[derive(Debug, PartialEq)]
@@ -12,8 +12,10 @@ pub struct LightTransactionReceipt {
     pub transaction_index: U64,
     pub block_hash: Option<H256>,
     pub block_number: Option<U64>,
+    pub cumulative_gas_used: Option<U256>,
     pub gas_used: Option<U256>,
     pub status: Option<U64>,
+    pub logs: Vec<Log>,
 }
 
 impl From<TransactionReceipt> for LightTransactionReceipt {
@@ -23,8 +25,10 @@ impl From<TransactionReceipt> for LightTransactionReceipt {
             transaction_index,
             block_hash,
             block_number,
+            cumulative_gas_used,
             gas_used,
             status,
+            logs,
             ..
         } = receipt;
         LightTransactionReceipt {
@@ -32,8 +36,10 @@ impl From<TransactionReceipt> for LightTransactionReceipt {
             transaction_index,
             block_hash,
             block_number,
+            cumulative_gas_used: Some(cumulative_gas_used),
             gas_used,
             status,
+            logs,
         }
     }
 }
@@ -326,6 +326,38 @@ impl ValueExt for Value {
 pub trait DirectiveFinder {
     fn find_directive(&self, name: &str) -> Option<&Directive>;
     fn is_derived(&self) -> bool;
+
+    /// The name of the join table if this has a `@relation(through: "..")`
+    /// directive
+    fn relation_through(&self) -> Option<&str> {
+        self.find_directive("relation")
+            .and_then(|directive| directive.argument("through"))
+            .and_then(|value| match value {
+                Value::String(through) => Some(through.as_str()),
+                _ => None,
+            })
+    }
+
+    /// The number of leading bytes that are indexed if this has a
+    /// `@storage(prefix: N)` directive
+    fn storage_prefix(&self) -> Option<usize> {
+        self.find_directive("storage")
+            .and_then(|directive| directive.argument("prefix"))
+            .and_then(|value| match value {
+                Value::Int(prefix) => prefix.as_i64(),
+                _ => None,
+            })
+            .filter(|prefix| *prefix > 0)
+            .map(|prefix| prefix as usize)
+    }
+
+    /// Whether large values are stored out of line and uncompressed, i.e.,
+    /// whether this has a `@storage(external: true)` directive
+    fn storage_external(&self) -> bool {
+        self.find_directive("storage")
+            .and_then(|directive| directive.argument("external"))
+            .map_or(false, |value| value == &Value::Boolean(true))
+    }
 }
 
 impl DirectiveFinder for ObjectType {
@@ -5,21 +5,28 @@ use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 use std::mem;
 
-use super::ObjectTypeExt;
+use super::{DocumentExt, ObjectTypeExt};
 
 #[derive(Copy, Clone, Debug)]
 pub enum ObjectOrInterface<'a> {
     Object(&'a s::ObjectType),
     Interface(&'a s::InterfaceType),
+    /// Unions are only used in the API schema; they have no fields of their
+    /// own
+    Union(&'a s::UnionType),
 }
 
+/// The fields of a union
+static NO_FIELDS: Vec<s::Field> = Vec::new();
+
 impl<'a> PartialEq for ObjectOrInterface<'a> {
     fn eq(&self, other: &Self) -> bool {
         use ObjectOrInterface::*;
         match (self, other) {
             (Object(a), Object(b)) => a.name == b.name,
             (Interface(a), Interface(b)) => a.name == b.name,
-            (Interface(_), Object(_)) | (Object(_), Interface(_)) => false,
+            (Union(a), Union(b)) => a.name == b.name,
+            _ => false,
         }
     }
 }
@@ -45,8 +52,11 @@ impl<'a> Ord for ObjectOrInterface<'a> {
         match (self, other) {
             (Object(a), Object(b)) => a.name.cmp(&b.name),
             (Interface(a), Interface(b)) => a.name.cmp(&b.name),
+            (Union(a), Union(b)) => a.name.cmp(&b.name),
             (Interface(_), Object(_)) => Ordering::Less,
             (Object(_), Interface(_)) => Ordering::Greater,
+            (Union(_), _) => Ordering::Less,
+            (_, Union(_)) => Ordering::Greater,
         }
     }
 }
@@ -63,11 +73,18 @@ impl<'a> From<&'a s::InterfaceType> for ObjectOrInterface<'a> {
     }
 }
 
+impl<'a> From<&'a s::UnionType> for ObjectOrInterface<'a> {
+    fn from(union: &'a s::UnionType) -> Self {
+        ObjectOrInterface::Union(union)
+    }
+}
+
 impl<'a> From<ObjectOrInterface<'a>> for EntityType {
     fn from(ooi: ObjectOrInterface) -> Self {
         match ooi {
             ObjectOrInterface::Object(ty) => EntityType::from(ty),
             ObjectOrInterface::Interface(ty) => EntityType::from(ty),
+            ObjectOrInterface::Union(ty) => EntityType::new(ty.name.clone()),
         }
     }
 }
@@ -76,13 +93,13 @@ impl<'a> ObjectOrInterface<'a> {
     pub fn is_object(self) -> bool {
         match self {
             ObjectOrInterface::Object(_) => true,
-            ObjectOrInterface::Interface(_) => false,
+            ObjectOrInterface::Interface(_) | ObjectOrInterface::Union(_) => false,
         }
     }
 
     pub fn is_interface(self) -> bool {
         match self {
-            ObjectOrInterface::Object(_) => false,
+            ObjectOrInterface::Object(_) | ObjectOrInterface::Union(_) => false,
             ObjectOrInterface::Interface(_) => true,
         }
     }
@@ -91,6 +108,7 @@ impl<'a> ObjectOrInterface<'a> {
         match self {
             ObjectOrInterface::Object(object) => &object.name,
             ObjectOrInterface::Interface(interface) => &interface.name,
+            ObjectOrInterface::Union(union) => &union.name,
         }
     }
 
@@ -98,6 +116,7 @@ impl<'a> ObjectOrInterface<'a> {
         match self {
             ObjectOrInterface::Object(object) => &object.directives,
             ObjectOrInterface::Interface(interface) => &interface.directives,
+            ObjectOrInterface::Union(union) => &union.directives,
         }
     }
 
@@ -105,6 +124,7 @@ impl<'a> ObjectOrInterface<'a> {
         match self {
             ObjectOrInterface::Object(object) => &object.fields,
             ObjectOrInterface::Interface(interface) => &interface.fields,
+            ObjectOrInterface::Union(_) => &NO_FIELDS,
         }
     }
 
@@ -119,6 +139,14 @@ impl<'a> ObjectOrInterface<'a> {
                 .types_for_interface()
                 .get(&interface.into())
                 .map(|object_types| object_types.iter().collect()),
+            ObjectOrInterface::Union(union) => Some(
+                schema
+                    .document
+                    .get_object_type_definitions()
+                    .into_iter()
+                    .filter(|object_type| union.types.contains(&object_type.name))
+                    .collect(),
+            ),
         }
     }
 
@@ -134,6 +162,7 @@ impl<'a> ObjectOrInterface<'a> {
             ObjectOrInterface::Interface(i) => types_for_interface[&i.into()]
                 .iter()
                 .any(|o| o.name == typename),
+            ObjectOrInterface::Union(u) => u.types.iter().any(|name| name == typename),
         }
     }
 
@@ -141,6 +170,7 @@ impl<'a> ObjectOrInterface<'a> {
         match self {
             ObjectOrInterface::Object(o) => o.is_meta(),
             ObjectOrInterface::Interface(i) => i.is_meta(),
+            ObjectOrInterface::Union(_) => false,
         }
     }
 }
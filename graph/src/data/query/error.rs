@@ -11,7 +11,10 @@ use std::sync::Arc;
 use crate::data::graphql::SerializableValue;
 use crate::data::subgraph::*;
 use crate::prelude::q;
-use crate::{components::store::StoreError, prelude::CacheWeight};
+use crate::{
+    components::store::{BlockNumber, StoreError},
+    prelude::CacheWeight,
+};
 
 #[derive(Debug)]
 pub struct CloneableAnyhowError(Arc<anyhow::Error>);
@@ -47,6 +50,7 @@ pub enum QueryExecutionError {
     ResolveEntitiesError(String),
     OrderByNotSupportedError(String, String),
     OrderByNotSupportedForType(String),
+    OrderDirectionMismatch(usize, usize),
     FilterNotSupportedError(String, String),
     UnknownField(Pos, String, String),
     EmptyQuery,
@@ -74,7 +78,10 @@ pub enum QueryExecutionError {
     Throttled,
     UndefinedFragment(String),
     // Using slow and prefetch query resolution yield different results
-    IncorrectPrefetchResult { slow: q::Value, prefetch: q::Value },
+    IncorrectPrefetchResult {
+        slow: q::Value,
+        prefetch: q::Value,
+    },
     Panic(String),
     EventStreamError,
     FulltextQueryRequiresFilter,
@@ -83,6 +90,9 @@ pub enum QueryExecutionError {
     SubgraphManifestResolveError(Arc<SubgraphManifestResolveError>),
     InvalidSubgraphManifest,
     ResultTooBig(usize, usize),
+    /// The query asked for a block before the earliest block for which
+    /// the deployment still has data (deployment, earliest, requested)
+    PrunedBlock(DeploymentHash, BlockNumber, BlockNumber),
 }
 
 impl QueryExecutionError {
@@ -101,6 +111,7 @@ impl QueryExecutionError {
             | MissingVariableError(_, _)
             | OrderByNotSupportedError(_, _)
             | OrderByNotSupportedForType(_)
+            | OrderDirectionMismatch(_, _)
             | FilterNotSupportedError(_, _)
             | UnknownField(_, _, _)
             | EmptyQuery
@@ -139,7 +150,8 @@ impl QueryExecutionError {
             | SubgraphManifestResolveError(_)
             | InvalidSubgraphManifest
             | ValidationError(_, _)
-            | ResultTooBig(_, _) => false,
+            | ResultTooBig(_, _)
+            | PrunedBlock(_, _, _) => false,
         }
     }
 }
@@ -203,6 +215,9 @@ impl fmt::Display for QueryExecutionError {
             OrderByNotSupportedForType(field_type) => {
                 write!(f, "Ordering by `{}` fields is not supported", field_type)
             }
+            OrderDirectionMismatch(fields, directions) => {
+                write!(f, "`orderDirection` must have one direction or one for each of the {} `orderBy` fields, but has {}", fields, directions)
+            }
             FilterNotSupportedError(value, filter) => {
                 write!(f, "Filter not supported by value `{}`: `{}`", value, filter)
             }
@@ -287,6 +302,8 @@ impl fmt::Display for QueryExecutionError {
             SubgraphManifestResolveError(e) => write!(f, "failed to resolve subgraph manifest: {}", e),
             InvalidSubgraphManifest => write!(f, "invalid subgraph manifest file"),
             ResultTooBig(actual, limit) => write!(f, "the result size of {} is larger than the allowed limit of {}", actual, limit),
+            PrunedBlock(deployment, earliest, block) => write!(f, "subgraph {} only has data starting at block number {} \
+                           and data for block number {} is therefore not available", deployment, earliest, block),
         }
     }
 }
@@ -136,6 +136,9 @@ pub struct Query {
     pub shape_hash: u64,
     pub query_text: Arc<String>,
     pub variables_text: Arc<String>,
+    /// Return `BigInt` and `BigDecimal` values as JSON numbers when that
+    /// does not lose precision instead of always returning strings
+    pub big_numbers_as_json: bool,
     _force_use_of_new: (),
 }
 
@@ -160,6 +163,7 @@ impl Query {
             shape_hash,
             query_text: Arc::new(query_text),
             variables_text: Arc::new(variables_text),
+            big_numbers_as_json: false,
             _force_use_of_new: (),
         }
     }
@@ -1,9 +1,10 @@
 use super::error::{QueryError, QueryExecutionError};
 use crate::data::value::Object;
 use crate::prelude::{r, CacheWeight, DeploymentHash};
+use chrono::{DateTime, Utc};
 use http::header::{
     ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
-    CONTENT_TYPE,
+    CONTENT_TYPE, LINK,
 };
 use serde::ser::*;
 use serde::Serialize;
@@ -41,16 +42,48 @@ where
 
 pub type Data = Object;
 
+/// Format `time` as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+fn http_date(time: &DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Marks the results of a query against a deployment that has been
+/// replaced by a newer current version of its subgraph
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deprecation {
+    /// The deployment that is now the current version of the subgraph
+    pub successor: DeploymentHash,
+    /// When the successor became the current version
+    pub deprecated_at: DateTime<Utc>,
+    /// When the queried deployment will stop being available, if known
+    pub sunset_at: Option<DateTime<Utc>>,
+}
+
+impl Serialize for Deprecation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Deprecation", 3)?;
+        state.serialize_field("successor", self.successor.as_str())?;
+        state.serialize_field("deprecatedAt", &self.deprecated_at.to_rfc3339())?;
+        state.serialize_field(
+            "sunsetAt",
+            &self.sunset_at.as_ref().map(|sunset| sunset.to_rfc3339()),
+        )?;
+        state.end()
+    }
+}
+
 #[derive(Debug)]
 /// A collection of query results that is serialized as a single result.
 pub struct QueryResults {
     results: Vec<Arc<QueryResult>>,
+    deprecation: Option<Deprecation>,
 }
 
 impl QueryResults {
     pub fn empty() -> Self {
         QueryResults {
             results: Vec::new(),
+            deprecation: None,
         }
     }
 
@@ -70,6 +103,9 @@ impl Serialize for QueryResults {
         if has_errors {
             len += 1;
         }
+        if self.deprecation.is_some() {
+            len += 1;
+        }
 
         let mut state = serializer.serialize_struct("QueryResults", len)?;
 
@@ -106,6 +142,21 @@ impl Serialize for QueryResults {
             state.serialize_field("errors", &SerError(self))?;
         }
 
+        // Serialize extensions.
+        if let Some(deprecation) = &self.deprecation {
+            struct SerExtensions<'a>(&'a Deprecation);
+
+            impl Serialize for SerExtensions<'_> {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    let mut ser = serializer.serialize_map(Some(1))?;
+                    ser.serialize_entry("deprecation", self.0)?;
+                    ser.end()
+                }
+            }
+
+            state.serialize_field("extensions", &SerExtensions(deprecation))?;
+        }
+
         state.end()
     }
 }
@@ -114,6 +165,7 @@ impl From<Data> for QueryResults {
     fn from(x: Data) -> Self {
         QueryResults {
             results: vec![Arc::new(x.into())],
+            deprecation: None,
         }
     }
 }
@@ -122,13 +174,17 @@ impl From<QueryResult> for QueryResults {
     fn from(x: QueryResult) -> Self {
         QueryResults {
             results: vec![Arc::new(x)],
+            deprecation: None,
         }
     }
 }
 
 impl From<Arc<QueryResult>> for QueryResults {
     fn from(x: Arc<QueryResult>) -> Self {
-        QueryResults { results: vec![x] }
+        QueryResults {
+            results: vec![x],
+            deprecation: None,
+        }
     }
 }
 
@@ -136,6 +192,7 @@ impl From<QueryExecutionError> for QueryResults {
     fn from(x: QueryExecutionError) -> Self {
         QueryResults {
             results: vec![Arc::new(x.into())],
+            deprecation: None,
         }
     }
 }
@@ -144,6 +201,7 @@ impl From<Vec<QueryExecutionError>> for QueryResults {
     fn from(x: Vec<QueryExecutionError>) -> Self {
         QueryResults {
             results: vec![Arc::new(x.into())],
+            deprecation: None,
         }
     }
 }
@@ -153,11 +211,36 @@ impl QueryResults {
         self.results.push(other);
     }
 
+    pub fn set_deprecation(&mut self, deprecation: Option<Deprecation>) {
+        self.deprecation = deprecation;
+    }
+
+    pub fn deprecation(&self) -> Option<&Deprecation> {
+        self.deprecation.as_ref()
+    }
+
     pub fn as_http_response<T: From<String>>(&self) -> http::Response<T> {
         let status_code = http::StatusCode::OK;
         let json =
             serde_json::to_string(self).expect("Failed to serialize GraphQL response to JSON");
-        http::Response::builder()
+        let mut builder = http::Response::builder();
+        if let Some(deprecation) = &self.deprecation {
+            // See RFC 8594 for the `Sunset` header and the IETF draft
+            // 'The Deprecation HTTP Header Field' for the `Deprecation` header
+            builder = builder
+                .header("Deprecation", http_date(&deprecation.deprecated_at))
+                .header(
+                    LINK,
+                    format!(
+                        "</subgraphs/id/{}>; rel=\"successor-version\"",
+                        deprecation.successor
+                    ),
+                );
+            if let Some(sunset) = &deprecation.sunset_at {
+                builder = builder.header("Sunset", http_date(sunset));
+            }
+        }
+        builder
             .status(status_code)
             .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
             .header(ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type, User-Agent")
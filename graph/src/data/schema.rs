@@ -6,12 +6,14 @@ use crate::data::subgraph::{DeploymentHash, SubgraphName};
 use crate::prelude::{
     lazy_static,
     q::Value,
+    r,
     s::{self, Definition, InterfaceType, ObjectType, TypeDefinition, *},
 };
 
 use anyhow::{Context, Error};
 use graphql_parser::{self, Pos};
 use inflector::Inflector;
+use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -32,6 +34,14 @@ pub const META_FIELD_NAME: &str = "_meta";
 
 pub const BLOCK_FIELD_TYPE: &str = "_Block_";
 
+pub const SEARCH_FIELD_TYPE: &str = "_SearchResult_";
+pub const SEARCH_FIELD_NAME: &str = "search";
+
+/// The largest number of leading bytes of an attribute that can be indexed
+/// with `@storage(prefix: N)`. Postgres limits BTree entries to about 2700
+/// bytes
+pub const MAX_STORAGE_PREFIX: usize = 2048;
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Strings(Vec<String>);
 
@@ -57,6 +67,10 @@ pub enum SchemaValidationError {
     InterfaceFieldsMissing(String, String, Strings), // (type, interface, missing_fields)
     #[error("Field `{1}` in type `{0}` has invalid @derivedFrom: {2}")]
     InvalidDerivedFrom(String, String, String), // (type, field, reason)
+    #[error("Field `{1}` in type `{0}` has invalid @relation: {2}")]
+    InvalidRelation(String, String, String), // (type, field, reason)
+    #[error("Field `{1}` in type `{0}` has invalid @storage: {2}")]
+    InvalidStorage(String, String, String), // (type, field, reason)
     #[error("The following type names are reserved: `{0}`")]
     UsageOfReservedTypes(Strings),
     #[error("_Schema_ type is only for @imports and must not have any fields")]
@@ -349,6 +363,17 @@ impl SchemaReference {
     }
 }
 
+/// The values that introspection queries against an API schema resolve
+/// to. They only depend on the schema, and are therefore built once and
+/// then cached with the schema
+#[derive(Debug)]
+pub struct IntrospectionObjects {
+    /// The `__Type` objects for all types in the schema, keyed by type name
+    pub types: BTreeMap<String, r::Value>,
+    /// The list of `__Directive` objects for the schema
+    pub directives: r::Value,
+}
+
 #[derive(Debug)]
 pub struct ApiSchema {
     schema: Schema,
@@ -357,6 +382,8 @@ pub struct ApiSchema {
     pub query_type: Arc<ObjectType>,
     pub subscription_type: Option<Arc<ObjectType>>,
     object_types: HashMap<String, Arc<ObjectType>>,
+
+    introspection: OnceCell<Arc<IntrospectionObjects>>,
 }
 
 impl ApiSchema {
@@ -393,9 +420,27 @@ impl ApiSchema {
             query_type: Arc::new(query_type),
             subscription_type,
             object_types,
+            introspection: OnceCell::new(),
         })
     }
 
+    /// Return the introspection objects for this schema, using `build` to
+    /// construct them the first time they are needed
+    pub fn introspection_objects(
+        &self,
+        build: impl FnOnce(&Schema) -> IntrospectionObjects,
+    ) -> Arc<IntrospectionObjects> {
+        self.introspection
+            .get_or_init(|| Arc::new(build(&self.schema)))
+            .clone()
+    }
+
+    /// Return `true` if the introspection objects for this schema have
+    /// already been built
+    pub fn has_introspection_objects(&self) -> bool {
+        self.introspection.get().is_some()
+    }
+
     pub fn document(&self) -> &s::Document {
         &self.schema.document
     }
@@ -814,6 +859,8 @@ impl Schema {
         let mut errors: Vec<SchemaValidationError> = std::array::IntoIter::new([
             self.validate_schema_types(),
             self.validate_derived_from(),
+            self.validate_relations(),
+            self.validate_storage(),
             self.validate_schema_type_has_no_fields(),
             self.validate_directives_on_schema_type(),
             self.validate_reserved_types_usage(),
@@ -1452,6 +1499,190 @@ impl Schema {
         Ok(())
     }
 
+    /// Validate `@relation(through: "..")` directives. The field must be a
+    /// list of an entity type, and the name of the join table must be unique
+    /// and must not clash with the name of a type in the schema. Names are
+    /// compared the way the store names tables, i.e., snakecased. The other
+    /// side of the relation can be declared with a `@derivedFrom` that
+    /// points at this field
+    fn validate_relations(&self) -> Result<(), SchemaValidationError> {
+        fn invalid(
+            object_type: &ObjectType,
+            field_name: &str,
+            reason: &str,
+        ) -> SchemaValidationError {
+            SchemaValidationError::InvalidRelation(
+                object_type.name.to_owned(),
+                field_name.to_owned(),
+                reason.to_owned(),
+            )
+        }
+
+        let type_definitions = self.document.get_object_type_definitions();
+        let mut join_tables = HashSet::new();
+        let type_tables: HashSet<_> = type_definitions
+            .iter()
+            .map(|object_type| object_type.name.to_snake_case())
+            .collect();
+
+        for (object_type, field, directive) in type_definitions.iter().flat_map(|object_type| {
+            object_type.fields.iter().filter_map(move |field| {
+                field
+                    .find_directive("relation")
+                    .map(|directive| (*object_type, field, directive))
+            })
+        }) {
+            let through = match directive.argument("through") {
+                Some(Value::String(through)) => through,
+                Some(_) => {
+                    return Err(invalid(
+                        object_type,
+                        &field.name,
+                        "the @relation `through` argument must be a string",
+                    ))
+                }
+                None => {
+                    return Err(invalid(
+                        object_type,
+                        &field.name,
+                        "the @relation directive must have a `through` argument",
+                    ))
+                }
+            };
+
+            if field.is_derived() {
+                return Err(invalid(
+                    object_type,
+                    &field.name,
+                    "a field can not have both @relation and @derivedFrom",
+                ));
+            }
+
+            if !field.field_type.is_list() {
+                return Err(invalid(
+                    object_type,
+                    &field.name,
+                    "the field must be a list of entities",
+                ));
+            }
+
+            let target_type_name = field.field_type.get_base_type();
+            if !type_definitions
+                .iter()
+                .any(|target| target.name.eq(target_type_name))
+            {
+                return Err(invalid(
+                    object_type,
+                    &field.name,
+                    "type must be an existing entity",
+                ));
+            }
+
+            let table = through.to_snake_case();
+            if self.document.get_named_type(through).is_some() || type_tables.contains(&table) {
+                let msg = format!("the join table `{}` has the name of a type", through);
+                return Err(invalid(object_type, &field.name, &msg));
+            }
+
+            if !join_tables.insert(table) {
+                let msg = format!("the join table `{}` is used more than once", through);
+                return Err(invalid(object_type, &field.name, &msg));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate `@storage(prefix: N, external: true)` directives. Only
+    /// `Bytes` attributes other than the `id` can be indexed on a prefix,
+    /// and only `String` and `Bytes` attributes can be stored externally
+    fn validate_storage(&self) -> Result<(), SchemaValidationError> {
+        fn invalid(
+            object_type: &ObjectType,
+            field_name: &str,
+            reason: &str,
+        ) -> SchemaValidationError {
+            SchemaValidationError::InvalidStorage(
+                object_type.name.to_owned(),
+                field_name.to_owned(),
+                reason.to_owned(),
+            )
+        }
+
+        let type_definitions = self.document.get_object_type_definitions();
+        for (object_type, field, directive) in type_definitions.iter().flat_map(|object_type| {
+            object_type.fields.iter().filter_map(move |field| {
+                field
+                    .find_directive("storage")
+                    .map(|directive| (*object_type, field, directive))
+            })
+        }) {
+            if field.is_derived() {
+                return Err(invalid(
+                    object_type,
+                    &field.name,
+                    "a field can not have both @storage and @derivedFrom",
+                ));
+            }
+
+            let base_type = field.field_type.get_base_type();
+            for (name, value) in &directive.arguments {
+                match name.as_str() {
+                    "prefix" => {
+                        let valid = match value {
+                            Value::Int(prefix) => prefix.as_i64().map_or(false, |prefix| {
+                                prefix > 0 && prefix <= MAX_STORAGE_PREFIX as i64
+                            }),
+                            _ => false,
+                        };
+                        if !valid {
+                            let msg = format!(
+                                "the @storage `prefix` argument must be an integer \
+                                 between 1 and {}",
+                                MAX_STORAGE_PREFIX
+                            );
+                            return Err(invalid(object_type, &field.name, &msg));
+                        }
+                        if field.name == "id" {
+                            return Err(invalid(
+                                object_type,
+                                &field.name,
+                                "the `id` can not be indexed on a prefix",
+                            ));
+                        }
+                        if base_type != "Bytes" || field.field_type.is_list() {
+                            return Err(invalid(
+                                object_type,
+                                &field.name,
+                                "only `Bytes` attributes can be indexed on a prefix",
+                            ));
+                        }
+                    }
+                    "external" => {
+                        if !matches!(value, Value::Boolean(_)) {
+                            return Err(invalid(
+                                object_type,
+                                &field.name,
+                                "the @storage `external` argument must be a boolean",
+                            ));
+                        }
+                        if base_type != "String" && base_type != "Bytes" {
+                            return Err(invalid(
+                                object_type,
+                                &field.name,
+                                "only `String` and `Bytes` attributes can be stored externally",
+                            ));
+                        }
+                    }
+                    _ => {
+                        let msg = format!("unknown argument `{}`", name);
+                        return Err(invalid(object_type, &field.name, &msg));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Validate that `object` implements `interface`.
     fn validate_interface_implementation(
         object: &ObjectType,
@@ -1616,6 +1847,139 @@ type Account implements Address @entity { id: ID!, txn: Transaction! @derivedFro
     validate("j: B @derivedFrom(field: \"id\")", "ok");
 }
 
+#[test]
+fn test_relation_validation() {
+    const OTHER_TYPES: &str = "
+type B @entity { id: ID!, as: [A!]! @derivedFrom(field: \"bs\") }
+type C @entity { id: ID! }
+interface I { id: ID! }";
+
+    fn validate(field: &str, errmsg: &str) {
+        let raw = format!("type A @entity {{ id: ID!\n {} }}\n{}", field, OTHER_TYPES);
+
+        let document = graphql_parser::parse_schema(&raw)
+            .expect("Failed to parse raw schema")
+            .into_static();
+        let schema = Schema::new(DeploymentHash::new("id").unwrap(), document);
+        match schema.validate_relations() {
+            Err(ref e) => match e {
+                SchemaValidationError::InvalidRelation(_, _, msg) => assert_eq!(errmsg, msg),
+                _ => panic!("expected variant SchemaValidationError::InvalidRelation"),
+            },
+            Ok(_) => {
+                if errmsg != "ok" {
+                    panic!("expected validation for `{}` to fail", field)
+                }
+            }
+        }
+    }
+
+    validate("bs: [B!]! @relation(through: \"AtoB\")", "ok");
+    validate(
+        "bs: [B!]! @relation",
+        "the @relation directive must have a `through` argument",
+    );
+    validate(
+        "bs: [B!]! @relation(through: 1)",
+        "the @relation `through` argument must be a string",
+    );
+    validate(
+        "bs: B @relation(through: \"AtoB\")",
+        "the field must be a list of entities",
+    );
+    validate(
+        "bs: [I!]! @relation(through: \"AtoI\")",
+        "type must be an existing entity",
+    );
+    validate(
+        "bs: [B!]! @relation(through: \"C\")",
+        "the join table `C` has the name of a type",
+    );
+    validate(
+        "bs: [B!]! @relation(through: \"AtoB\")\n cs: [C!]! @relation(through: \"AtoB\")",
+        "the join table `AtoB` is used more than once",
+    );
+    validate(
+        "bs: [B!]! @relation(through: \"AtoB\")\n cs: [C!]! @relation(through: \"ato_b\")",
+        "the join table `ato_b` is used more than once",
+    );
+    validate(
+        "bs: [B!]! @relation(through: \"c\")",
+        "the join table `c` has the name of a type",
+    );
+    validate(
+        "bs: [B!]! @relation(through: \"AtoB\") @derivedFrom(field: \"id\")",
+        "a field can not have both @relation and @derivedFrom",
+    );
+}
+
+#[test]
+fn test_storage_validation() {
+    fn validate(field: &str, errmsg: &str) {
+        let raw = format!(
+            "type A @entity {{ id: ID!\n {} }}\ntype B @entity {{ id: ID!, a: A! }}",
+            field
+        );
+
+        let document = graphql_parser::parse_schema(&raw)
+            .expect("Failed to parse raw schema")
+            .into_static();
+        let schema = Schema::new(DeploymentHash::new("id").unwrap(), document);
+        match schema.validate_storage() {
+            Err(ref e) => match e {
+                SchemaValidationError::InvalidStorage(_, _, msg) => assert_eq!(errmsg, msg),
+                _ => panic!("expected variant SchemaValidationError::InvalidStorage"),
+            },
+            Ok(_) => {
+                if errmsg != "ok" {
+                    panic!("expected validation for `{}` to fail", field)
+                }
+            }
+        }
+    }
+
+    validate("data: Bytes! @storage(prefix: 64)", "ok");
+    validate("data: Bytes @storage(prefix: 64, external: true)", "ok");
+    validate("name: String! @storage(external: true)", "ok");
+    validate("names: [String!]! @storage(external: false)", "ok");
+    validate(
+        "data: Bytes! @storage(prefix: 0)",
+        "the @storage `prefix` argument must be an integer between 1 and 2048",
+    );
+    validate(
+        "data: Bytes! @storage(prefix: 4096)",
+        "the @storage `prefix` argument must be an integer between 1 and 2048",
+    );
+    validate(
+        "data: Bytes! @storage(prefix: \"64\")",
+        "the @storage `prefix` argument must be an integer between 1 and 2048",
+    );
+    validate(
+        "name: String! @storage(prefix: 64)",
+        "only `Bytes` attributes can be indexed on a prefix",
+    );
+    validate(
+        "data: [Bytes!]! @storage(prefix: 64)",
+        "only `Bytes` attributes can be indexed on a prefix",
+    );
+    validate(
+        "count: Int! @storage(external: true)",
+        "only `String` and `Bytes` attributes can be stored externally",
+    );
+    validate(
+        "data: Bytes! @storage(external: 1)",
+        "the @storage `external` argument must be a boolean",
+    );
+    validate(
+        "data: Bytes! @storage(compress: false)",
+        "unknown argument `compress`",
+    );
+    validate(
+        "bs: [B!]! @derivedFrom(field: \"a\") @storage(external: true)",
+        "a field can not have both @storage and @derivedFrom",
+    );
+}
+
 #[test]
 fn test_reserved_type_with_fields() {
     const ROOT_SCHEMA: &str = "
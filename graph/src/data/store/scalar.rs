@@ -38,16 +38,138 @@ impl From<bigdecimal::BigDecimal> for BigDecimal {
     }
 }
 
+/// How `BigDecimal` values with more significant digits than their
+/// precision allows are rounded
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BigDecimalRounding {
+    /// Round towards zero
+    Down,
+    /// Round to the nearest value, and away from zero for ties
+    HalfUp,
+    /// Round to the nearest value, and to an even last digit for ties
+    HalfEven,
+}
+
+/// The number of significant digits that `BigDecimal` operations keep and
+/// how they round. Subgraphs set it with the `bigDecimal` section of their
+/// manifest; without a `rounding`, results are rounded the way they always
+/// have been
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BigDecimalPrecision {
+    #[serde(rename = "precision", default = "BigDecimalPrecision::default_digits")]
+    pub digits: u32,
+    #[serde(default)]
+    pub rounding: Option<BigDecimalRounding>,
+}
+
+impl BigDecimalPrecision {
+    fn default_digits() -> u32 {
+        BigDecimal::MAX_SIGNFICANT_DIGITS as u32
+    }
+
+    /// The precision with which values are read back from the store.
+    /// Values are normalized before they are stored, and this precision
+    /// preserves them regardless of the precision they were normalized to
+    pub fn stored() -> Self {
+        BigDecimalPrecision {
+            digits: BigDecimal::MAX_CONFIGURABLE_DIGITS,
+            rounding: Some(BigDecimalRounding::Down),
+        }
+    }
+
+    fn round(&self, value: &bigdecimal::BigDecimal) -> bigdecimal::BigDecimal {
+        use num_traits::pow::Pow;
+
+        let rounding = match self.rounding {
+            Some(rounding) => rounding,
+            None => return value.with_prec(self.digits as u64),
+        };
+
+        let digits = value.digits();
+        if digits <= self.digits as u64 {
+            return value.clone();
+        }
+        let excess = (digits - self.digits as u64) as u32;
+        let (int_val, scale) = value.as_bigint_and_exponent();
+        let divisor = num_bigint::BigInt::from(10).pow(excess);
+        // Integer division truncates, i.e., rounds towards zero
+        let mut quotient = &int_val / &divisor;
+        let twice_remainder = (&int_val % &divisor).magnitude() * num_bigint::BigUint::from(2u32);
+        let is_odd = quotient.to_bytes_le().1[0] & 1 == 1;
+        let away_from_zero = match rounding {
+            BigDecimalRounding::Down => false,
+            BigDecimalRounding::HalfUp => &twice_remainder >= divisor.magnitude(),
+            BigDecimalRounding::HalfEven => {
+                &twice_remainder > divisor.magnitude()
+                    || (&twice_remainder == divisor.magnitude() && is_odd)
+            }
+        };
+        if away_from_zero {
+            match int_val.sign() {
+                BigIntSign::Minus => quotient -= num_bigint::BigInt::from(1),
+                _ => quotient += num_bigint::BigInt::from(1),
+            }
+        }
+        bigdecimal::BigDecimal::new(quotient, scale - excess as i64)
+    }
+}
+
+impl Default for BigDecimalPrecision {
+    fn default() -> Self {
+        BigDecimalPrecision {
+            digits: Self::default_digits(),
+            rounding: None,
+        }
+    }
+}
+
 impl BigDecimal {
     /// These are the limits of IEEE-754 decimal128, a format we may want to switch to. See
     /// https://en.wikipedia.org/wiki/Decimal128_floating-point_format.
     pub const MIN_EXP: i32 = -6143;
     pub const MAX_EXP: i32 = 6144;
     pub const MAX_SIGNFICANT_DIGITS: i32 = 34;
+    /// The most significant digits a `BigDecimalPrecision` can ask for.
+    /// Division computes 100 digits, so more would not make a difference
+    pub const MAX_CONFIGURABLE_DIGITS: u32 = 100;
 
     pub fn new(digits: BigInt, exp: i64) -> Self {
+        Self::new_with_precision(digits, exp, &BigDecimalPrecision::default())
+    }
+
+    pub fn new_with_precision(digits: BigInt, exp: i64, precision: &BigDecimalPrecision) -> Self {
         // bigdecimal uses `scale` as the opposite of the power of ten, so negate `exp`.
-        Self::from(bigdecimal::BigDecimal::new(digits.0, -exp))
+        BigDecimal(bigdecimal::BigDecimal::new(digits.0, -exp)).normalized_with(precision)
+    }
+
+    pub fn from_str_with_precision(
+        s: &str,
+        precision: &BigDecimalPrecision,
+    ) -> Result<Self, <bigdecimal::BigDecimal as FromStr>::Err> {
+        Ok(BigDecimal(bigdecimal::BigDecimal::from_str(s)?).normalized_with(precision))
+    }
+
+    pub fn add_with_precision(self, other: Self, precision: &BigDecimalPrecision) -> Self {
+        BigDecimal(self.0.add(other.0)).normalized_with(precision)
+    }
+
+    pub fn sub_with_precision(self, other: Self, precision: &BigDecimalPrecision) -> Self {
+        BigDecimal(self.0.sub(other.0)).normalized_with(precision)
+    }
+
+    pub fn mul_with_precision(self, other: Self, precision: &BigDecimalPrecision) -> Self {
+        BigDecimal(self.0.mul(other.0)).normalized_with(precision)
+    }
+
+    /// Panics if `other` is zero, just like `Div`
+    pub fn div_with_precision(self, other: Self, precision: &BigDecimalPrecision) -> Self {
+        if other == BigDecimal::from(0) {
+            panic!("Cannot divide by zero-valued `BigDecimal`!")
+        }
+
+        BigDecimal(self.0.div(other.0)).normalized_with(precision)
     }
 
     pub fn zero() -> BigDecimal {
@@ -60,6 +182,14 @@ impl BigDecimal {
         self.0.as_bigint_and_exponent()
     }
 
+    /// The value of `self` as a fixed-point number with `decimals` decimal
+    /// places, i.e., `self * 10^decimals`. Digits beyond that are
+    /// truncated, rounding towards zero
+    pub fn to_fixed(&self, decimals: u8) -> BigInt {
+        let (digits, _) = self.0.with_scale(decimals as i64).as_bigint_and_exponent();
+        BigInt(digits)
+    }
+
     pub fn digits(&self) -> u64 {
         self.0.digits()
     }
@@ -68,12 +198,19 @@ impl BigDecimal {
     // is included in a released version supported by Diesel.
     #[must_use]
     pub fn normalized(&self) -> BigDecimal {
+        self.normalized_with(&BigDecimalPrecision::default())
+    }
+
+    /// Like `normalized`, but rounds to the significant digits and with
+    /// the rounding mode of `precision`
+    #[must_use]
+    pub fn normalized_with(&self, precision: &BigDecimalPrecision) -> BigDecimal {
         if self == &BigDecimal::zero() {
             return BigDecimal::zero();
         }
 
         // Round to the maximum significant digits.
-        let big_decimal = self.0.with_prec(Self::MAX_SIGNFICANT_DIGITS as u64);
+        let big_decimal = precision.round(&self.0);
 
         let (bigint, exp) = big_decimal.as_bigint_and_exponent();
         let (sign, mut digits) = bigint.to_radix_be(10);
@@ -322,6 +459,15 @@ impl BigInt {
         BigInt(self.0.pow(&exponent))
     }
 
+    /// The integer square root of `self`, rounded down, or `None` if `self`
+    /// is negative
+    pub fn sqrt(&self) -> Option<Self> {
+        match self.0.sign() {
+            BigIntSign::Minus => None,
+            _ => Some(BigInt(self.0.sqrt())),
+        }
+    }
+
     pub fn bits(&self) -> usize {
         self.0.bits()
     }
@@ -658,4 +804,73 @@ mod test {
             assert_eq!(normalized.to_string(), string);
         }
     }
+
+    #[test]
+    fn big_decimal_precision() {
+        fn round(s: &str, digits: u32, rounding: BigDecimalRounding) -> String {
+            let precision = BigDecimalPrecision {
+                digits,
+                rounding: Some(rounding),
+            };
+            BigDecimal::from_str_with_precision(s, &precision)
+                .unwrap()
+                .to_string()
+        }
+
+        use BigDecimalRounding::*;
+        assert_eq!("1.23", round("1.235", 3, Down));
+        assert_eq!("1.24", round("1.235", 3, HalfUp));
+        assert_eq!("1.24", round("1.235", 3, HalfEven));
+        assert_eq!("1.22", round("1.225", 3, HalfEven));
+        assert_eq!("1.23", round("1.2251", 3, HalfEven));
+        assert_eq!("-1.24", round("-1.235", 3, HalfUp));
+        assert_eq!("-1.22", round("-1.225", 3, HalfEven));
+        assert_eq!("-1.23", round("-1.2399", 3, Down));
+        assert_eq!("10", round("9.995", 3, HalfUp));
+        assert_eq!("120", round("123", 2, Down));
+
+        // More digits than the default precision survive
+        let s = "1.".to_owned() + &"1".repeat(59);
+        assert_eq!(s, round(&s, 60, Down));
+        assert_eq!(34, BigDecimal::from_str(&s).unwrap().digits());
+
+        // Results of operations are rounded, too
+        let precision = BigDecimalPrecision {
+            digits: 50,
+            rounding: Some(HalfEven),
+        };
+        let third = BigDecimal::from(1).div_with_precision(BigDecimal::from(3), &precision);
+        assert_eq!(50, third.digits());
+        let two_thirds = BigDecimal::from(2).div_with_precision(BigDecimal::from(3), &precision);
+        assert_eq!(
+            "0.".to_owned() + &"6".repeat(49) + "7",
+            two_thirds.to_string()
+        );
+    }
+
+    #[test]
+    fn big_int_sqrt() {
+        assert_eq!(Some(BigInt::from(0)), BigInt::from(0).sqrt());
+        assert_eq!(Some(BigInt::from(3)), BigInt::from(15).sqrt());
+        assert_eq!(Some(BigInt::from(4)), BigInt::from(16).sqrt());
+        let x = BigInt::from_str("79228162514264337593543950336").unwrap();
+        assert_eq!(Some(BigInt::from(1u64 << 48)), x.sqrt());
+        assert_eq!(None, BigInt::from(-1).sqrt());
+    }
+
+    #[test]
+    fn big_decimal_fixed_point() {
+        let x = BigDecimal::from_str("12.3456").unwrap();
+        assert_eq!(BigInt::from(1234), x.to_fixed(2));
+        assert_eq!(BigInt::from(123_456_000), x.to_fixed(7));
+        assert_eq!(BigInt::from(12), x.to_fixed(0));
+        // Truncation rounds towards zero
+        let x = BigDecimal::from_str("-12.3456").unwrap();
+        assert_eq!(BigInt::from(-1234), x.to_fixed(2));
+        // Round trip through the fixed-point representation
+        assert_eq!(
+            BigDecimal::from_str("-12.34").unwrap(),
+            BigDecimal::new(BigInt::from(-1234), -2)
+        );
+    }
 }
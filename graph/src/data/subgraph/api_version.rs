@@ -10,6 +10,36 @@ use super::SubgraphManifestValidationError;
 /// different API versions if at least one of them is equal to or higher than `0.0.5`.
 pub const API_VERSION_0_0_5: Version = Version::new(0, 0, 5);
 
+/// This version allows mappings to use the Wasm SIMD and bulk-memory proposals.
+pub const API_VERSION_0_0_7: Version = Version::new(0, 0, 7);
+
+/// This version rejects mappings whose handlers use floating point numbers.
+pub const API_VERSION_0_0_8: Version = Version::new(0, 0, 8);
+
+/// This version tolerates strings whose byte length is odd, as they appear
+/// in the static data of modules built with newer AssemblyScript compilers.
+pub const API_VERSION_0_0_9: Version = Version::new(0, 0, 9);
+
+/// This version allows the manifest to change the precision and rounding
+/// of `BigDecimal` operations with its `bigDecimal` section.
+pub const API_VERSION_0_0_10: Version = Version::new(0, 0, 10);
+
+/// This version reads typed arrays through a view of the Wasm memory
+/// instead of copying their whole backing buffer out of it first.
+pub const API_VERSION_0_0_11: Version = Version::new(0, 0, 11);
+
+/// This version passes the transaction receipt to Ethereum event handlers
+/// that ask for it with `receipt: true`.
+pub const API_VERSION_0_0_12: Version = Version::new(0, 0, 12);
+
+/// This version passes entities and JSON objects to mappings with the
+/// layout of AssemblyScript's `Map` instead of a flat array of entries.
+pub const API_VERSION_0_0_13: Version = Version::new(0, 0, 13);
+
+/// This version limits the memory of a mapping instance to 512MiB instead
+/// of the 4GiB that 32-bit Wasm can address.
+pub const API_VERSION_0_0_14: Version = Version::new(0, 0, 14);
+
 /// Before this check was introduced, there were already subgraphs in the wild with spec version
 /// 0.0.3, due to confusion with the api version. To avoid breaking those, we accept 0.0.3 though it
 /// doesn't exist.
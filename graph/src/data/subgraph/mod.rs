@@ -30,7 +30,7 @@ use crate::data::{
     schema::{Schema, SchemaImportError, SchemaValidationError},
     subgraph::features::validate_subgraph_features,
 };
-use crate::prelude::{r, CheapClone};
+use crate::prelude::{r, BigDecimal, BigDecimalPrecision, CheapClone};
 use crate::{blockchain::DataSource, data::graphql::TryFromValue};
 use crate::{blockchain::DataSourceTemplate as _, data::query::QueryExecutionError};
 use crate::{
@@ -357,6 +357,8 @@ pub enum SubgraphManifestValidationError {
     FeatureValidationError(#[from] SubgraphFeatureValidationError),
     #[error("data source {0} is invalid: {1}")]
     DataSourceValidation(String, Error),
+    #[error("the bigDecimal section is invalid: {0}")]
+    BigDecimalPrecisionInvalid(String),
 }
 
 #[derive(Error, Debug)]
@@ -468,12 +470,21 @@ impl Graft {
             )),
             Ok(Some(ptr)) => {
                 if ptr.number < self.block {
-                    gbi(format!(
+                    return gbi(format!(
                         "failed to graft onto `{}` at block {} since it has only processed block {}",
                         self.base, self.block, ptr.number
-                    ))
-                } else {
-                    vec![]
+                    ));
+                }
+                // Grafting copies the base's data as of `self.block`,
+                // which is gone if the base was pruned past it
+                match store.earliest_block(&self.base) {
+                    Err(e) => gbi(e.to_string()),
+                    Ok(earliest) if earliest > self.block => gbi(format!(
+                        "failed to graft onto `{}` at block {} since it has been pruned \
+                         and only has data starting at block {}",
+                        self.base, self.block, earliest
+                    )),
+                    Ok(_) => vec![],
                 }
             }
         }
@@ -494,6 +505,8 @@ pub struct BaseSubgraphManifest<C, S, D, T> {
     pub graft: Option<Graft>,
     #[serde(default)]
     pub templates: Vec<T>,
+    #[serde(rename = "bigDecimal", default)]
+    pub big_decimal_precision: BigDecimalPrecision,
     #[serde(skip_serializing, default)]
     pub chain: PhantomData<C>,
 }
@@ -558,8 +571,14 @@ impl<C: Blockchain> UnvalidatedSubgraphManifest<C> {
         }
 
         // For API versions newer than 0.0.5, validate that all mappings uses the same api_version
-        if let Err(different_api_versions) = self.0.unified_mapping_api_version() {
-            errors.push(different_api_versions.into());
+        match self.0.unified_mapping_api_version() {
+            Ok(api_version) => {
+                errors.extend(validate_big_decimal_precision(
+                    &self.0.big_decimal_precision,
+                    &api_version,
+                ));
+            }
+            Err(different_api_versions) => errors.push(different_api_versions.into()),
         };
 
         let mut networks = self
@@ -700,6 +719,7 @@ impl<C: Blockchain> UnresolvedSubgraphManifest<C> {
             data_sources,
             graft,
             templates,
+            big_decimal_precision,
             chain,
         } = self;
 
@@ -749,11 +769,37 @@ impl<C: Blockchain> UnresolvedSubgraphManifest<C> {
             data_sources,
             graft,
             templates,
+            big_decimal_precision,
             chain,
         })
     }
 }
 
+fn validate_big_decimal_precision(
+    precision: &BigDecimalPrecision,
+    api_version: &UnifiedMappingApiVersion,
+) -> Option<SubgraphManifestValidationError> {
+    if *precision == BigDecimalPrecision::default() {
+        return None;
+    }
+    if !api_version.equal_or_greater_than(&API_VERSION_0_0_10) {
+        return Some(SubgraphManifestValidationError::BigDecimalPrecisionInvalid(
+            format!("it requires apiVersion {} or higher", API_VERSION_0_0_10),
+        ));
+    }
+    let min = BigDecimal::MAX_SIGNFICANT_DIGITS as u32;
+    let max = BigDecimal::MAX_CONFIGURABLE_DIGITS;
+    if !(min..=max).contains(&precision.digits) {
+        return Some(SubgraphManifestValidationError::BigDecimalPrecisionInvalid(
+            format!(
+                "the precision must be between {} and {}, but is {}",
+                min, max, precision.digits
+            ),
+        ));
+    }
+    None
+}
+
 /// Important details about the current state of a subgraph deployment
 /// used while executing queries against a deployment
 ///
@@ -772,6 +818,9 @@ pub struct DeploymentState {
     pub max_reorg_depth: u32,
     /// The number of the last block that the subgraph has processed
     pub latest_ethereum_block_number: BlockNumber,
+    /// The first block for which the subgraph can answer queries; the
+    /// history before it has been pruned
+    pub earliest_block_number: BlockNumber,
 }
 
 impl DeploymentState {
@@ -213,6 +213,16 @@ impl StableHash for SubgraphError {
     }
 }
 
+/// A warning that a mapping emitted with `log.subgraphWarning`. Warnings
+/// are informational and do not affect the health of the subgraph
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubgraphWarning {
+    pub message: String,
+    pub block_ptr: BlockPtr,
+    /// The name of the data source whose mapping emitted the warning
+    pub data_source: String,
+}
+
 pub fn generate_entity_id() -> String {
     // Fast crypto RNG from operating system
     let mut rng = OsRng::new().unwrap();
@@ -1,6 +1,9 @@
 //! Support for the indexing status API
 
-use super::schema::{SubgraphError, SubgraphHealth};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::schema::{SubgraphError, SubgraphHealth, SubgraphWarning};
 use crate::components::store::DeploymentId;
 use crate::data::graphql::{object, IntoValue};
 use crate::prelude::{r, web3::types::H256, BlockPtr, Value};
@@ -99,13 +102,41 @@ pub struct Info {
     pub fatal_error: Option<SubgraphError>,
     pub non_fatal_errors: Vec<SubgraphError>,
 
+    /// The most recent warnings that mappings emitted, oldest first
+    pub warnings: Vec<SubgraphWarning>,
+
     /// Indexing status on different chains involved in the subgraph's data sources.
     pub chains: Vec<ChainInfo>,
 
     pub entity_count: u64,
 
+    /// The number of current entities for each entity type. Entity types
+    /// whose count is not known yet are missing
+    pub entity_counts: BTreeMap<String, u64>,
+
     /// ID of the Graph Node that the subgraph is indexed by.
     pub node: Option<String>,
+
+    /// Labels attached to the deployment, keyed by label name
+    pub labels: BTreeMap<String, String>,
+
+    /// Writes that are waiting for the database. Only known on the node
+    /// that indexes the deployment
+    pub writes: Option<WriteInfo>,
+
+    /// Approximate memory usage of indexing the deployment. Only known on
+    /// the node that indexes the deployment
+    pub memory: Option<MemoryInfo>,
+}
+
+impl Info {
+    /// Return `true` if the deployment has all the labels in `labels`
+    /// with the given values
+    pub fn has_labels(&self, labels: &[(String, String)]) -> bool {
+        labels
+            .iter()
+            .all(|(key, value)| self.labels.get(key) == Some(value))
+    }
 }
 
 impl IntoValue for Info {
@@ -115,11 +146,16 @@ impl IntoValue for Info {
             subgraph,
             chains,
             entity_count,
+            entity_counts,
             fatal_error,
             health,
             node,
             non_fatal_errors,
+            warnings,
             synced,
+            labels,
+            writes,
+            memory,
         } = self;
 
         fn subgraph_error_to_value(subgraph_error: SubgraphError) -> r::Value {
@@ -150,6 +186,46 @@ impl IntoValue for Info {
             .map(subgraph_error_to_value)
             .collect();
         let fatal_error_val = fatal_error.map_or(r::Value::Null, subgraph_error_to_value);
+        let warnings: Vec<_> = warnings
+            .into_iter()
+            .map(|warning| {
+                let SubgraphWarning {
+                    message,
+                    block_ptr,
+                    data_source,
+                } = warning;
+                object! {
+                    __typename: "SubgraphWarning",
+                    message: message,
+                    block: object! {
+                        __typename: "Block",
+                        number: block_ptr.number,
+                        hash: r::Value::from(Value::Bytes(block_ptr.hash.into())),
+                    },
+                    dataSource: data_source,
+                }
+            })
+            .collect();
+        let entity_counts: Vec<_> = entity_counts
+            .into_iter()
+            .map(|(entity_type, count)| {
+                object! {
+                    __typename: "EntityCount",
+                    entityType: entity_type,
+                    count: format!("{}", count),
+                }
+            })
+            .collect();
+        let labels: Vec<_> = labels
+            .into_iter()
+            .map(|(key, value)| {
+                object! {
+                    __typename: "Label",
+                    key: key,
+                    value: value,
+                }
+            })
+            .collect();
 
         object! {
             __typename: "SubgraphIndexingStatus",
@@ -158,9 +234,74 @@ impl IntoValue for Info {
             health: r::Value::from(health),
             fatalError: fatal_error_val,
             nonFatalErrors: non_fatal_errors,
+            warnings: warnings,
             chains: chains.into_iter().map(|chain| chain.into_value()).collect::<Vec<_>>(),
             entityCount: format!("{}", entity_count),
+            entityCounts: entity_counts,
             node: node,
+            labels: labels,
+            writes: writes,
+            memory: memory,
+        }
+    }
+}
+
+/// The writes of a deployment that have been handed to the store but are
+/// not in the database yet, and how long writing the last batch took
+#[derive(Debug)]
+pub struct WriteInfo {
+    pub pending_batches: usize,
+    pub pending_ops: usize,
+    pub pending_bytes: usize,
+    pub last_commit_wait: Option<Duration>,
+    pub last_commit_duration: Option<Duration>,
+}
+
+impl IntoValue for WriteInfo {
+    fn into_value(self) -> r::Value {
+        let WriteInfo {
+            pending_batches,
+            pending_ops,
+            pending_bytes,
+            last_commit_wait,
+            last_commit_duration,
+        } = self;
+
+        object! {
+            __typename: "WriteStatus",
+            pendingBatches: pending_batches as i32,
+            pendingEntityOperations: pending_ops as i32,
+            pendingBytes: pending_bytes as u64,
+            lastCommitWaitMs: last_commit_wait.map(|wait| wait.as_millis() as i32),
+            lastCommitDurationMs: last_commit_duration.map(|duration| duration.as_millis() as i32),
+        }
+    }
+}
+
+/// Approximate memory usage of indexing a deployment, see `MemoryUsage`
+#[derive(Debug)]
+pub struct MemoryInfo {
+    pub entity_cache_bytes: usize,
+    pub wasm_memory_bytes: usize,
+    pub buffered_blocks: usize,
+    pub buffered_triggers: usize,
+}
+
+impl IntoValue for MemoryInfo {
+    fn into_value(self) -> r::Value {
+        let MemoryInfo {
+            entity_cache_bytes,
+            wasm_memory_bytes,
+            buffered_blocks,
+            buffered_triggers,
+        } = self;
+
+        object! {
+            __typename: "MemoryStatus",
+            entityCacheBytes: entity_cache_bytes as u64,
+            wasmMemoryBytes: wasm_memory_bytes as u64,
+            bufferedBlocks: buffered_blocks as i32,
+            bufferedTriggers: buffered_triggers as i32,
         }
     }
 }
@@ -6,25 +6,52 @@ use crate::{
     prelude::{debug, info},
 };
 use anyhow::Context;
-use futures03::StreamExt;
+use futures03::{stream::BoxStream, StreamExt};
 use http::uri::{Scheme, Uri};
 use rand::prelude::IteratorRandom;
 use slog::Logger;
-use std::{collections::BTreeMap, fmt::Display, sync::Arc};
+use std::{collections::BTreeMap, fmt::Display, sync::Arc, time::Duration};
 use tonic::{
     metadata::MetadataValue,
-    transport::{Channel, ClientTlsConfig},
-    Request,
+    transport::{Certificate, Channel, ClientTlsConfig, Identity},
+    Request, Status,
 };
 
 use super::codec as firehose;
 
+/// The stream of responses for a `blocks` request to a Firehose endpoint
+pub type FirehoseStream = BoxStream<'static, Result<firehose::Response, Status>>;
+
+/// Settings for the gRPC connection to a Firehose provider. Streams to a
+/// Firehose are long-lived, and load balancers or proxies between
+/// `graph-node` and the Firehose can drop them without either side
+/// noticing; these settings make it possible to detect that
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FirehoseConnectionSettings {
+    /// How often to send HTTP/2 keepalive pings, even while the
+    /// connection is idle
+    pub keepalive_interval: Option<Duration>,
+    /// How long to wait for the response to a keepalive ping before
+    /// closing the connection
+    pub keepalive_timeout: Option<Duration>,
+    /// How long to wait for the next message on a block stream before
+    /// giving up on the stream
+    pub message_timeout: Option<Duration>,
+    /// The PEM encoded certificate of the CA that signed the provider's
+    /// certificate. If it is not set, the system's trusted roots are used
+    pub ca_certificate: Option<Vec<u8>>,
+    /// The PEM encoded certificate and private key with which to
+    /// authenticate to the provider for mutual TLS
+    pub client_identity: Option<(Vec<u8>, Vec<u8>)>,
+}
+
 #[derive(Clone, Debug)]
 pub struct FirehoseEndpoint {
     pub provider: String,
     pub uri: String,
     pub token: Option<String>,
     channel: Channel,
+    message_timeout: Option<Duration>,
     _logger: Logger,
 }
 
@@ -40,19 +67,40 @@ impl FirehoseEndpoint {
         provider: S,
         url: S,
         token: Option<String>,
+        settings: FirehoseConnectionSettings,
     ) -> Result<Self, anyhow::Error> {
         let uri = url
             .as_ref()
             .parse::<Uri>()
             .expect("the url should have been validated by now, so it is a valid Uri");
 
-        let endpoint = match uri.scheme().unwrap_or_else(|| &Scheme::HTTP).as_str() {
+        let mut endpoint = match uri.scheme().unwrap_or_else(|| &Scheme::HTTP).as_str() {
             "http" => Channel::builder(uri),
-            "https" => Channel::builder(uri)
-                .tls_config(ClientTlsConfig::new())
-                .expect("TLS config on this host is invalid"),
+            "https" => {
+                let mut tls = ClientTlsConfig::new();
+                if let Some(ca) = &settings.ca_certificate {
+                    tls = tls.ca_certificate(Certificate::from_pem(ca));
+                }
+                if let Some((cert, key)) = &settings.client_identity {
+                    tls = tls.identity(Identity::from_pem(cert, key));
+                }
+                Channel::builder(uri).tls_config(tls).with_context(|| {
+                    format!(
+                        "invalid TLS configuration for firehose provider {}",
+                        provider.as_ref()
+                    )
+                })?
+            }
             _ => panic!("invalid uri scheme for firehose endpoint"),
         };
+        if let Some(interval) = settings.keepalive_interval {
+            endpoint = endpoint
+                .http2_keep_alive_interval(interval)
+                .keep_alive_while_idle(true);
+        }
+        if let Some(timeout) = settings.keepalive_timeout {
+            endpoint = endpoint.keep_alive_timeout(timeout);
+        }
 
         let uri = endpoint.uri().to_string();
         let channel = endpoint.connect_lazy().with_context(|| {
@@ -68,6 +116,7 @@ impl FirehoseEndpoint {
             uri,
             channel,
             token,
+            message_timeout: settings.message_timeout,
             _logger: logger,
         })
     }
@@ -118,7 +167,7 @@ impl FirehoseEndpoint {
     pub async fn stream_blocks(
         self: Arc<Self>,
         request: firehose::Request,
-    ) -> Result<tonic::Streaming<firehose::Response>, anyhow::Error> {
+    ) -> Result<FirehoseStream, anyhow::Error> {
         let token_metadata = match self.token.clone() {
             Some(token) => Some(MetadataValue::from_str(token.as_str())?),
             None => None,
@@ -138,9 +187,30 @@ impl FirehoseEndpoint {
         let response_stream = client.blocks(request).await?;
         let block_stream = response_stream.into_inner();
 
-        Ok(block_stream)
+        match self.message_timeout {
+            None => Ok(block_stream.boxed()),
+            Some(timeout) => {
+                // Turn a stream that has gone quiet into an error so that
+                // callers reconnect instead of waiting forever
+                let block_stream =
+                    tokio_stream::StreamExt::timeout(block_stream, timeout).map(move |message| {
+                        match message {
+                            Ok(message) => message,
+                            Err(_) => Err(Status::deadline_exceeded(format!(
+                                "no message received from firehose in {}s",
+                                timeout.as_secs()
+                            ))),
+                        }
+                    });
+                Ok(block_stream.boxed())
+            }
+        }
     }
 }
+/// The deployment label that names the Firehose provider a deployment's
+/// block stream should use instead of a random provider for the chain
+pub const FIREHOSE_PROVIDER_LABEL: &str = "firehose_provider";
+
 #[derive(Clone, Debug)]
 pub struct FirehoseEndpoints(Vec<Arc<FirehoseEndpoint>>);
 
@@ -153,6 +223,10 @@ impl FirehoseEndpoints {
         self.0.len()
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<FirehoseEndpoint>> {
+        self.0.iter()
+    }
+
     pub fn random(&self) -> Option<&Arc<FirehoseEndpoint>> {
         if self.0.len() == 0 {
             return None;
@@ -163,6 +237,25 @@ impl FirehoseEndpoints {
         Some(&self.0.iter().choose(&mut rng).unwrap())
     }
 
+    /// Return the endpoint of the provider called `provider`
+    pub fn get(&self, provider: &str) -> Option<&Arc<FirehoseEndpoint>> {
+        self.0.iter().find(|endpoint| endpoint.provider == provider)
+    }
+
+    /// Return the endpoint of `provider` if it is given, and a random
+    /// endpoint otherwise
+    pub fn select(&self, provider: Option<&str>) -> Result<Arc<FirehoseEndpoint>, anyhow::Error> {
+        match provider {
+            Some(provider) => self.get(provider).cloned().ok_or_else(|| {
+                anyhow::anyhow!("no firehose endpoint for provider `{}` available", provider)
+            }),
+            None => self
+                .random()
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("no firehose endpoint available")),
+        }
+    }
+
     pub fn remove(&mut self, provider: &str) {
         self.0
             .retain(|network_endpoint| network_endpoint.provider != provider);
@@ -214,3 +307,53 @@ impl FirehoseNetworks {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use slog::{o, Discard, Logger};
+
+    use super::{FirehoseConnectionSettings, FirehoseEndpoint, FirehoseEndpoints};
+
+    async fn endpoint(provider: &str) -> Arc<FirehoseEndpoint> {
+        let url = format!("http://{}.example.com:9000", provider);
+        let endpoint = FirehoseEndpoint::new(
+            Logger::root(Discard, o!()),
+            provider,
+            url.as_str(),
+            None,
+            FirehoseConnectionSettings::default(),
+        )
+        .await
+        .expect("can create endpoint");
+        Arc::new(endpoint)
+    }
+
+    #[tokio::test]
+    async fn select_provider() {
+        let mut endpoints = FirehoseEndpoints::new();
+        assert!(endpoints.select(None).is_err());
+
+        endpoints.0.push(endpoint("first").await);
+        endpoints.0.push(endpoint("second").await);
+
+        let selected = endpoints.select(Some("second")).unwrap();
+        assert_eq!("second", selected.provider);
+        let selected = endpoints.select(Some("first")).unwrap();
+        assert_eq!("first", selected.provider);
+
+        let selected = endpoints.select(None).unwrap();
+        assert!(selected.provider == "first" || selected.provider == "second");
+
+        let err = endpoints.select(Some("third")).unwrap_err();
+        assert_eq!(
+            "no firehose endpoint for provider `third` available",
+            err.to_string()
+        );
+
+        endpoints.remove("second");
+        assert!(endpoints.select(Some("second")).is_err());
+        assert_eq!("first", endpoints.select(None).unwrap().provider);
+    }
+}
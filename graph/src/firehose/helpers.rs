@@ -17,3 +17,23 @@ where
 
     Ok(Arc::new(M::decode(any_block.value.as_ref())?))
 }
+
+/// Decode only the header of the block in `block_response` into `M`, a
+/// message that declares a subset of the fields of the chain's full block
+/// message with the same tags. Fields that `M` does not declare are
+/// skipped without being decoded, which makes this much cheaper than
+/// `decode_firehose_block` when only the block pointers are needed, as is
+/// the case for reverts.
+pub fn decode_firehose_block_header<M>(
+    block_response: &firehose::Response,
+) -> Result<M, prost::DecodeError>
+where
+    M: prost::Message + BlockchainBlock + Default,
+{
+    let any_block = block_response
+        .block
+        .as_ref()
+        .expect("block payload information should always be present");
+
+    M::decode(any_block.value.as_ref())
+}
@@ -4,4 +4,4 @@ mod helpers;
 
 pub use codec::*;
 pub use endpoints::*;
-pub use helpers::decode_firehose_block;
+pub use helpers::{decode_firehose_block, decode_firehose_block_header};
@@ -78,16 +78,27 @@ impl IpfsClient {
             .await
     }
 
+    /// Download the contents as a stream of chunks. The `timeout` applies
+    /// to the entire download
     pub async fn cat(
         &self,
         cid: String,
+        timeout: Option<Duration>,
     ) -> Result<impl Stream<Item = Result<Bytes, reqwest::Error>>, reqwest::Error> {
         Ok(self
-            .call(self.url("cat", cid), None, None)
+            .call(self.url("cat", cid), None, timeout)
             .await?
             .bytes_stream())
     }
 
+    /// Calls `block get` to download the raw block `cid`
+    pub async fn get_block(&self, cid: String, timeout: Duration) -> Result<Bytes, reqwest::Error> {
+        self.call(self.url("block/get", cid), None, Some(timeout))
+            .await?
+            .bytes()
+            .await
+    }
+
     pub async fn test(&self) -> Result<(), reqwest::Error> {
         self.call(format!("{}api/v0/version", self.base), None, None)
             .await
@@ -114,11 +114,12 @@ pub mod prelude {
     pub use crate::components::server::subscription::SubscriptionServer;
     pub use crate::components::store::{
         AttributeNames, BlockNumber, ChainStore, ChildMultiplicity, EntityCache, EntityChange,
-        EntityChangeOperation, EntityCollection, EntityFilter, EntityKey, EntityLink,
-        EntityModification, EntityOperation, EntityOrder, EntityQuery, EntityRange, EntityWindow,
-        EthereumCallCache, ParentLink, PoolWaitStats, QueryStore, QueryStoreManager, StoreError,
-        StoreEvent, StoreEventStream, StoreEventStreamBox, SubgraphStore, WindowAttribute,
-        BLOCK_NUMBER_MAX, SUBSCRIPTION_THROTTLE_INTERVAL,
+        EntityChangeOperation, EntityCollection, EntityFilter, EntityHistory, EntityKey,
+        EntityLink, EntityModification, EntityOperation, EntityOrder, EntityQuery, EntityRange,
+        EntityWindow, EthereumCallCache, JoinLink, OrderDirection, ParentLink, PoolWaitStats,
+        QueryStore, QueryStoreManager, StoreError, StoreEvent, StoreEventStream,
+        StoreEventStreamBox, SubgraphStore, WindowAttribute, BLOCK_NUMBER_MAX,
+        SUBSCRIPTION_THROTTLE_INTERVAL,
     };
     pub use crate::components::subgraph::{
         BlockState, DataSourceTemplateInfo, HostMetrics, RuntimeHost, RuntimeHostBuilder,
@@ -136,7 +137,9 @@ pub mod prelude {
     };
     pub use crate::data::schema::{ApiSchema, Schema};
     pub use crate::data::store::ethereum::*;
-    pub use crate::data::store::scalar::{BigDecimal, BigInt, BigIntSign};
+    pub use crate::data::store::scalar::{
+        BigDecimal, BigDecimalPrecision, BigDecimalRounding, BigInt, BigIntSign,
+    };
     pub use crate::data::store::{
         AssignmentEvent, Attribute, Entity, NodeId, SubscriptionFilter, ToEntityId, ToEntityKey,
         TryIntoEntity, Value, ValueType,
@@ -1,5 +1,9 @@
+use std::borrow::Cow;
+
 use semver::Version;
 
+use crate::data::store::scalar::BigDecimalPrecision;
+
 use super::{AscIndexId, AscPtr, AscType, DeterministicHostError, IndexForAscTypeId};
 /// A type that can read and write to the Asc heap. Call `asc_new` and `asc_get`
 /// for reading and writing Rust structs from and to Asc.
@@ -9,10 +13,25 @@ pub trait AscHeap {
     /// Allocate new space and write `bytes`, return the allocated address.
     fn raw_new(&mut self, bytes: &[u8]) -> Result<u32, DeterministicHostError>;
 
+    /// Overwrite memory at `offset` with `bytes`. The memory must have been
+    /// allocated with `raw_new` before.
+    fn raw_write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), DeterministicHostError>;
+
     fn get(&self, offset: u32, size: u32) -> Result<Vec<u8>, DeterministicHostError>;
 
+    /// Like `get`, but heaps that can hand out a view of their memory
+    /// return the bytes without copying them
+    fn get_view(&self, offset: u32, size: u32) -> Result<Cow<[u8]>, DeterministicHostError> {
+        self.get(offset, size).map(Cow::Owned)
+    }
+
     fn api_version(&self) -> Version;
 
+    /// The precision with which `BigDecimal` values are read from the heap
+    fn big_decimal_precision(&self) -> BigDecimalPrecision {
+        BigDecimalPrecision::default()
+    }
+
     fn asc_type_id(
         &mut self,
         type_id_index: IndexForAscTypeId,
@@ -84,7 +84,7 @@ impl<C: AscType> AscPtr<C> {
 
                 let header = Self::generate_header(
                     heap,
-                    C::INDEX_ASC_TYPE_ID,
+                    asc_obj.index_asc_type_id(),
                     asc_obj.content_len(&bytes),
                     bytes.len(),
                 )?;
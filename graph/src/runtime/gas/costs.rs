@@ -22,7 +22,7 @@ lazy_static! {
         .ok()
         .map(|s| {
             u64::from_str(&s.replace("_", "")).unwrap_or_else(|_| {
-                panic!("GRAPH_LOAD_WINDOW_SIZE must be a number, but is `{}`", s)
+                panic!("GRAPH_MAX_GAS_PER_HANDLER must be a number, but is `{}`", s)
             })
         })
         .unwrap_or(CONST_MAX_GAS_PER_HANDLER);
@@ -65,9 +65,21 @@ pub const BIG_MATH_GAS_OP: GasOp = GasOp {
     size_mult: BIG_MATH_GAS_PER_BYTE,
 };
 
+// Recovering a public key takes about 100µs; allow up to 10 million recoveries
+pub const ECRECOVER: Gas = Gas(CONST_MAX_GAS_PER_HANDLER / 10_000_000);
+
+// Verifying an ed25519 signature takes about 50µs, plus hashing the message
+pub const ED25519_VERIFY_GAS_OP: GasOp = GasOp {
+    base_cost: CONST_MAX_GAS_PER_HANDLER / 20_000_000,
+    size_mult: DEFAULT_GAS_PER_BYTE,
+};
+
 // Allow up to 100,000 data sources to be created
 pub const CREATE_DATA_SOURCE: Gas = Gas(CONST_MAX_GAS_PER_HANDLER / 100_000);
 
+// Allow up to 100,000 block handlers to be scheduled
+pub const SCHEDULE_BLOCK_HANDLER: Gas = Gas(CONST_MAX_GAS_PER_HANDLER / 100_000);
+
 pub const LOG_OP: GasOp = GasOp {
     // Allow up to 100,000 logs
     base_cost: CONST_MAX_GAS_PER_HANDLER / 100_000,
@@ -27,6 +27,13 @@ pub trait AscIndexId {
     /// Info about identifier (`idof<T>`):
     /// https://www.assemblyscript.org/garbage-collection.html#runtime-interface
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId;
+
+    /// The identifier of this object. Types whose layout depends on the
+    /// api version override this to use a different identifier for each
+    /// layout.
+    fn index_asc_type_id(&self) -> IndexForAscTypeId {
+        Self::INDEX_ASC_TYPE_ID
+    }
 }
 
 /// A type that has a direct correspondence to an Asc type.
@@ -240,6 +247,28 @@ pub enum IndexForAscTypeId {
     NearChunkHeader = 84,
     NearBlock = 85,
     NearReceiptWithOutcome = 86,
+
+    // More Ethereum Type IDs
+    EthereumContractCreation = 87,
+
+    // YAML Type IDs
+    YamlValue = 88,
+    ArrayYamlValue = 89,
+    TypedMapEntryYamlValueYamlValue = 90,
+    ArrayTypedMapEntryYamlValueYamlValue = 91,
+    TypedMapYamlValueYamlValue = 92,
+    WrappedYamlValue = 93,
+    ResultYamlValueBool = 94,
+
+    // More Ethereum Type IDs
+    EthereumTransactionReceipt = 95,
+    EthereumLog = 96,
+    ArrayEthereumLog = 97,
+    EthereumLightTransactionReceipt = 98,
+
+    // AssemblyScript `Map`s with string keys
+    MapStringStoreValue = 99,
+    MapStringJsonValue = 100,
 }
 
 impl ToAscObj<u32> for IndexForAscTypeId {
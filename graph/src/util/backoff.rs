@@ -1,3 +1,4 @@
+use rand::Rng;
 use std::time::Duration;
 
 /// Facilitate sleeping with an exponential backoff. Sleep durations will
@@ -8,6 +9,7 @@ pub struct ExponentialBackoff {
     pub attempt: u64,
     base: Duration,
     ceiling: Duration,
+    jitter: f64,
 }
 
 impl ExponentialBackoff {
@@ -16,9 +18,18 @@ impl ExponentialBackoff {
             attempt: 0,
             base,
             ceiling,
+            jitter: 0.0,
         }
     }
 
+    /// Randomize every sleep by up to `jitter` times its duration in either
+    /// direction so that many clients that back off at the same time do
+    /// not retry in lockstep. `jitter` is clamped to `[0, 1]`
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.max(0.0).min(1.0);
+        self
+    }
+
     /// Record that we made an attempt and sleep for the appropriate amount
     /// of time. Do not use this from async contexts since it uses
     /// `thread::sleep`
@@ -33,7 +44,10 @@ impl ExponentialBackoff {
     }
 
     pub fn delay(&self) -> Duration {
-        let mut delay = self.base.saturating_mul(1 << self.attempt);
+        let factor = 1u32
+            .checked_shl(self.attempt.min(u32::MAX as u64) as u32)
+            .unwrap_or(u32::MAX);
+        let mut delay = self.base.saturating_mul(factor);
         if delay > self.ceiling {
             delay = self.ceiling;
         }
@@ -41,7 +55,11 @@ impl ExponentialBackoff {
     }
 
     fn next_attempt(&mut self) -> Duration {
-        let delay = self.delay();
+        let mut delay = self.delay();
+        if self.jitter > 0.0 {
+            let factor = rand::thread_rng().gen_range(1.0 - self.jitter, 1.0 + self.jitter);
+            delay = delay.mul_f64(factor);
+        }
         self.attempt += 1;
         delay
     }
@@ -50,3 +68,31 @@ impl ExponentialBackoff {
         self.attempt = 0;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let base = Duration::from_millis(100);
+        let ceiling = Duration::from_secs(10);
+        let mut backoff = ExponentialBackoff::new(base, ceiling).with_jitter(0.5);
+
+        for attempt in 0..10 {
+            let delay = backoff.delay();
+            let jittered = backoff.next_attempt();
+            assert!(jittered >= delay.mul_f64(0.5), "attempt {}", attempt);
+            assert!(jittered <= delay.mul_f64(1.5), "attempt {}", attempt);
+        }
+        assert_eq!(ceiling, backoff.delay());
+    }
+
+    #[test]
+    fn delay_does_not_overflow() {
+        let ceiling = Duration::from_secs(30);
+        let mut backoff = ExponentialBackoff::new(Duration::from_millis(250), ceiling);
+        backoff.attempt = 100;
+        assert_eq!(ceiling, backoff.delay());
+    }
+}
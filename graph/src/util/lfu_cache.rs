@@ -155,6 +155,10 @@ impl<K: Clone + Ord + Eq + Hash + Debug + CacheWeight, V: CacheWeight + Default>
             .is_some()
     }
 
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.queue.iter().map(|(entry, _)| &entry.key)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.queue.is_empty()
     }
@@ -163,6 +167,11 @@ impl<K: Clone + Ord + Eq + Hash + Debug + CacheWeight, V: CacheWeight + Default>
         self.queue.len()
     }
 
+    /// The total weight of all entries in the cache
+    pub fn weight(&self) -> usize {
+        self.total_weight
+    }
+
     /// Same as `evict_with_period(max_weight, STALE_PERIOD)`
     pub fn evict(&mut self, max_weight: usize) -> Option<(usize, usize, usize)> {
         self.evict_with_period(max_weight, STALE_PERIOD)
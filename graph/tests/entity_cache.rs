@@ -1,13 +1,16 @@
 use async_trait::async_trait;
 use graph::blockchain::BlockPtr;
-use graph::data::subgraph::schema::{SubgraphError, SubgraphHealth};
+use graph::data::subgraph::schema::{SubgraphError, SubgraphHealth, SubgraphWarning};
 use graph::prelude::{Schema, StopwatchMetrics, StoreError};
 use lazy_static::lazy_static;
 use slog::Logger;
 use std::collections::BTreeMap;
 use std::sync::Arc;
 
-use graph::components::store::{EntityType, StoredDynamicDataSource, WritableStore};
+use graph::components::store::{
+    DataSourceContextUpdate, EntityType, ScheduledBlockHandler, StoredDynamicDataSource,
+    WritableStore,
+};
 use graph::{
     components::store::{DeploymentId, DeploymentLocator},
     prelude::{anyhow, DeploymentHash, Entity, EntityCache, EntityKey, EntityModification, Value},
@@ -74,6 +77,10 @@ impl WritableStore for MockStore {
         unimplemented!()
     }
 
+    fn add_warnings(&self, _: Vec<SubgraphWarning>) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
     async fn supports_proof_of_indexing(&self) -> Result<bool, StoreError> {
         unimplemented!()
     }
@@ -98,6 +105,8 @@ impl WritableStore for MockStore {
         _: Vec<EntityModification>,
         _: StopwatchMetrics,
         _: Vec<StoredDynamicDataSource>,
+        _: Vec<ScheduledBlockHandler>,
+        _: Vec<DataSourceContextUpdate>,
         _: Vec<SubgraphError>,
     ) -> Result<(), StoreError> {
         unimplemented!()
@@ -122,6 +131,16 @@ impl WritableStore for MockStore {
         unimplemented!()
     }
 
+    async fn load_scheduled_block_handlers(
+        &self,
+    ) -> Result<Vec<ScheduledBlockHandler>, StoreError> {
+        unimplemented!()
+    }
+
+    async fn load_data_source_contexts(&self) -> Result<Vec<DataSourceContextUpdate>, StoreError> {
+        unimplemented!()
+    }
+
     fn deployment_synced(&self) -> Result<(), StoreError> {
         unimplemented!()
     }
@@ -324,3 +343,146 @@ fn consecutive_modifications() {
         },])
     );
 }
+
+#[test]
+fn get_in_block_ignores_store() {
+    let store = {
+        let entities = vec![
+            make_band(
+                "mogwai",
+                vec![("id", "mogwai".into()), ("name", "Mogwai".into())],
+            )
+            .1,
+        ];
+        MockStore::new(entity_version_map("Band", entities))
+    };
+    let mut cache = EntityCache::new(Arc::new(store));
+
+    // The entity exists in the store, but has not been touched by the block
+    let (mogwai_key, _) = make_band("mogwai", vec![]);
+    assert_eq!(None, cache.get_in_block(&mogwai_key));
+
+    let (sigurros_key, sigurros_data) = make_band(
+        "sigurros",
+        vec![("id", "sigurros".into()), ("name", "Sigur Ros".into())],
+    );
+    cache
+        .set(sigurros_key.clone(), sigurros_data.clone())
+        .unwrap();
+    assert_eq!(Some(sigurros_data), cache.get_in_block(&sigurros_key));
+
+    cache.remove(sigurros_key.clone());
+    assert_eq!(None, cache.get_in_block(&sigurros_key));
+}
+
+fn band_store() -> Arc<MockStore> {
+    let entities = vec![
+        make_band(
+            "mogwai",
+            vec![("id", "mogwai".into()), ("name", "Mogwai".into())],
+        )
+        .1,
+        make_band(
+            "sigurros",
+            vec![("id", "sigurros".into()), ("name", "Sigur Ros".into())],
+        )
+        .1,
+    ];
+    Arc::new(MockStore::new(entity_version_map("Band", entities)))
+}
+
+#[test]
+fn conflicting_key_detects_reads_and_writes_of_other_caches() {
+    let store = band_store();
+    let (mogwai_key, mogwai_data) = make_band(
+        "mogwai",
+        vec![("id", "mogwai".into()), ("founded", 1995.into())],
+    );
+    let (sigurros_key, sigurros_data) = make_band(
+        "sigurros",
+        vec![("id", "sigurros".into()), ("founded", 1994.into())],
+    );
+
+    // Caches that change different entities and read nothing else do not
+    // conflict
+    let mut first = EntityCache::new(store.clone());
+    first.set(mogwai_key.clone(), mogwai_data.clone()).unwrap();
+    let mut second = EntityCache::new(store.clone());
+    second
+        .set(sigurros_key.clone(), sigurros_data.clone())
+        .unwrap();
+    assert_eq!(None, EntityCache::conflicting_key(&[&first, &second]));
+
+    // Reading an entity that another cache changes is a conflict
+    second.get(&mogwai_key).unwrap();
+    assert_eq!(
+        Some(&mogwai_key),
+        EntityCache::conflicting_key(&[&first, &second])
+    );
+
+    // Checking whether another cache changed an entity earlier in the
+    // block is a conflict, too
+    let mut third = EntityCache::new(store.clone());
+    assert_eq!(None, third.get_in_block(&sigurros_key));
+    assert_eq!(
+        Some(&sigurros_key),
+        EntityCache::conflicting_key(&[&third, &second])
+    );
+
+    // Two caches changing the same entity conflict
+    let mut fourth = EntityCache::new(store.clone());
+    fourth.set(mogwai_key.clone(), mogwai_data).unwrap();
+    assert_eq!(
+        Some(&mogwai_key),
+        EntityCache::conflicting_key(&[&first, &fourth])
+    );
+}
+
+#[test]
+fn extend_matches_sequential_changes() {
+    let store = band_store();
+    let (mogwai_key, mogwai_data) = make_band(
+        "mogwai",
+        vec![("id", "mogwai".into()), ("founded", 1995.into())],
+    );
+    let (sigurros_key, _) = make_band("sigurros", vec![]);
+    let (mono_key, mono_data) =
+        make_band("mono", vec![("id", "mono".into()), ("name", "Mono".into())]);
+    let (mono_update_key, mono_update_data) = make_band(
+        "mono",
+        vec![
+            ("id", "mono".into()),
+            ("label", "Temporary Residence".into()),
+        ],
+    );
+
+    // Process the changes of two data sources interleaved in one cache
+    let mut sequential = EntityCache::new(store.clone());
+    sequential.set(mono_key.clone(), mono_data.clone()).unwrap();
+    sequential
+        .set(mogwai_key.clone(), mogwai_data.clone())
+        .unwrap();
+    sequential.remove(sigurros_key.clone());
+    sequential
+        .set(mono_update_key.clone(), mono_update_data.clone())
+        .unwrap();
+
+    // Process the changes of each data source in its own cache and combine
+    // them afterwards
+    let mut first = EntityCache::new(store.clone());
+    first.set(mono_key, mono_data).unwrap();
+    first.set(mono_update_key, mono_update_data).unwrap();
+    let mut second = EntityCache::new(store.clone());
+    second.set(mogwai_key, mogwai_data).unwrap();
+    second.remove(sigurros_key);
+    assert_eq!(None, EntityCache::conflicting_key(&[&first, &second]));
+
+    let mut parallel = EntityCache::new(store.clone());
+    parallel.extend(first);
+    parallel.extend(second);
+
+    assert_eq!(
+        sort_by_entity_key(sequential.as_modifications().unwrap().modifications),
+        sort_by_entity_key(parallel.as_modifications().unwrap().modifications)
+    );
+}
@@ -22,6 +22,7 @@ use crate::execution::ast as a;
 use crate::introspection::{is_introspection_field, INTROSPECTION_QUERY_TYPE};
 use crate::prelude::*;
 use crate::schema::ast as sast;
+use crate::values::coercion::big_number_as_json_number;
 
 lazy_static! {
     // Comma separated subgraph ids to cache queries for.
@@ -132,6 +133,7 @@ struct HashableQuery<'a> {
     query_schema_id: &'a DeploymentHash,
     selection_set: &'a a::SelectionSet,
     block_ptr: &'a BlockPtr,
+    big_numbers_as_json: bool,
 }
 
 /// Note that the use of StableHash here is a little bit loose. In particular,
@@ -160,6 +162,9 @@ impl StableHash for HashableQuery<'_> {
 
         self.block_ptr
             .stable_hash(sequence_number.next_child(), state);
+
+        self.big_numbers_as_json
+            .stable_hash(sequence_number.next_child(), state);
     }
 }
 
@@ -175,6 +180,7 @@ fn cache_key(
         query_schema_id: ctx.query.schema.id(),
         selection_set,
         block_ptr,
+        big_numbers_as_json: ctx.query.big_numbers_as_json,
     };
     stable_hash::<SetHasher, _>(&query)
 }
@@ -223,8 +229,7 @@ where
     R: Resolver,
 {
     pub fn as_introspection_context(&self) -> ExecutionContext<IntrospectionResolver> {
-        let introspection_resolver =
-            IntrospectionResolver::new(&self.logger, self.query.schema.schema());
+        let introspection_resolver = IntrospectionResolver::new(&self.logger, &self.query.schema);
 
         ExecutionContext {
             logger: self.logger.cheap_clone(),
@@ -618,7 +623,10 @@ fn resolve_field_value_for_named_type(
                 .resolve_object(field_value, field, field_definition, i.into())
         }
 
-        s::TypeDefinition::Union(_) => Err(QueryExecutionError::Unimplemented("unions".to_owned())),
+        s::TypeDefinition::Union(t) => {
+            ctx.resolver
+                .resolve_object(field_value, field, field_definition, t.into())
+        }
 
         s::TypeDefinition::InputObject(_) => unreachable!("input objects are never resolved"),
     }
@@ -676,9 +684,10 @@ fn resolve_field_value_for_list_type(
                     .resolve_objects(field_value, field, field_definition, t.into())
                     .map_err(|e| vec![e]),
 
-                s::TypeDefinition::Union(_) => Err(vec![QueryExecutionError::Unimplemented(
-                    "unions".to_owned(),
-                )]),
+                s::TypeDefinition::Union(t) => ctx
+                    .resolver
+                    .resolve_objects(field_value, field, field_definition, t.into())
+                    .map_err(|e| vec![e]),
 
                 s::TypeDefinition::InputObject(_) => {
                     unreachable!("input objects are never resolved")
@@ -754,14 +763,19 @@ fn complete_value(
             match named_type {
                 // Complete scalar values
                 s::TypeDefinition::Scalar(scalar_type) => {
-                    resolved_value.coerce_scalar(scalar_type).map_err(|value| {
+                    let value = resolved_value.coerce_scalar(scalar_type).map_err(|value| {
                         vec![QueryExecutionError::ScalarCoercionError(
                             field.position,
                             field.name.to_owned(),
                             value.into(),
                             scalar_type.name.to_owned(),
                         )]
-                    })
+                    })?;
+                    if ctx.query.big_numbers_as_json {
+                        Ok(big_number_as_json_number(value, scalar_type))
+                    } else {
+                        Ok(value)
+                    }
                 }
 
                 // Complete enum values
@@ -130,6 +130,15 @@ pub struct Query {
     pub query_text: Arc<String>,
     pub variables_text: Arc<String>,
     pub query_id: String,
+
+    /// Whether `BigInt` and `BigDecimal` results should be returned as
+    /// JSON numbers where that is safe
+    pub big_numbers_as_json: bool,
+
+    /// A rough estimate of how expensive the query is to run, based on
+    /// its complexity and the number of filters it uses. It is used to
+    /// decide whether the query should run against a read replica
+    pub cost: u64,
 }
 
 impl Query {
@@ -220,12 +229,13 @@ impl Query {
         };
 
         // It's important to check complexity first, so `validate_fields`
-        // doesn't risk a stack overflow from invalid queries. We don't
-        // really care about the resulting complexity, only that all the
-        // checks that `check_complexity` performs pass successfully
-        let _ = raw_query.check_complexity(max_complexity, max_depth)?;
+        // doesn't risk a stack overflow from invalid queries. Besides the
+        // checks that `check_complexity` performs, we only use the
+        // complexity to estimate the cost of the query
+        let complexity = raw_query.check_complexity(max_complexity, max_depth)?;
         raw_query.validate_fields()?;
         let selection_set = raw_query.convert()?;
+        let cost = complexity.saturating_mul(1 + filter_count(&selection_set));
 
         let query = Self {
             schema,
@@ -238,6 +248,8 @@ impl Query {
             query_text: query.query_text.cheap_clone(),
             variables_text: query.variables_text.cheap_clone(),
             query_id,
+            big_numbers_as_json: query.big_numbers_as_json,
+            cost,
         };
 
         Ok(Arc::new(query))
@@ -431,6 +443,22 @@ fn coerce_variable(
     })
 }
 
+/// The number of conditions in the `where` filters of the fields in
+/// `selection_set` and all its descendants
+fn filter_count(selection_set: &a::SelectionSet) -> u64 {
+    selection_set
+        .fields()
+        .flat_map(|(_, fields)| fields)
+        .map(|field| {
+            let conditions = match field.argument_value("where") {
+                Some(r::Value::Object(filter)) => filter.iter().count() as u64,
+                _ => 0,
+            };
+            conditions + filter_count(&field.selection_set)
+        })
+        .sum()
+}
+
 struct RawQuery<'s> {
     /// The schema against which to execute the query
     schema: Arc<ApiSchema>,
@@ -493,7 +521,7 @@ impl<'s> RawQuery<'s> {
 
                             // `Scalar` and `Enum` cannot have selection sets.
                             // `InputObject` can't appear in a selection.
-                            // `Union` is not yet supported.
+                            // `Union` has no fields of its own.
                             s::TypeDefinition::Scalar(_)
                             | s::TypeDefinition::Enum(_)
                             | s::TypeDefinition::InputObject(_)
@@ -909,7 +937,10 @@ impl Transform {
             } else {
                 let ty = field_type.field_type.get_base_type();
                 let type_set = a::ObjectTypeSet::from_name(&self.schema, ty)?;
-                let ty = self.schema.object_or_interface(ty).unwrap();
+                let ty = match self.schema.get_named_type(ty) {
+                    Some(s::TypeDefinition::Union(union)) => union.into(),
+                    _ => self.schema.object_or_interface(ty).unwrap(),
+                };
                 self.expand_selection_set(selection_set, &type_set, ty)?
             }
         };
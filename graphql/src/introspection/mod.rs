@@ -1,7 +1,7 @@
 mod resolver;
 mod schema;
 
-pub use self::resolver::IntrospectionResolver;
+pub use self::resolver::{warm_introspection, IntrospectionResolver};
 pub use self::schema::{
     introspection_schema, is_introspection_field, INTROSPECTION_DOCUMENT, INTROSPECTION_QUERY_TYPE,
 };
@@ -3,6 +3,7 @@ use graphql_parser::Pos;
 use std::collections::BTreeMap;
 
 use graph::data::graphql::{object, DocumentExt, ObjectOrInterface};
+use graph::data::schema::IntrospectionObjects;
 use graph::prelude::*;
 
 use crate::execution::ast as a;
@@ -221,12 +222,7 @@ fn union_type_object(schema: &Schema, union_type: &s::UnionType) -> r::Value {
         possibleTypes:
             schema.document.get_object_type_definitions()
                 .iter()
-                .filter(|object_type| {
-                    object_type
-                        .implements_interfaces
-                        .iter()
-                        .any(|implemented_name| implemented_name == &union_type.name)
-                })
+                .filter(|object_type| union_type.types.contains(&object_type.name))
                 .map(|object_type| r::Value::String(object_type.name.to_owned()))
                 .collect::<Vec<_>>(),
     }
@@ -301,43 +297,59 @@ fn input_value(
     }
 }
 
+fn introspection_objects(schema: &Schema) -> IntrospectionObjects {
+    // Generate queryable objects for all types in the schema
+    let mut types = schema_type_objects(schema);
+
+    // Generate queryable objects for all directives in the schema
+    let directives = schema_directive_objects(schema, &mut types);
+
+    IntrospectionObjects { types, directives }
+}
+
+/// Build the introspection objects for `schema` unless they have been
+/// built already, so that the first introspection query against the
+/// schema does not have to pay for building them
+pub fn warm_introspection(schema: &ApiSchema) {
+    schema.introspection_objects(introspection_objects);
+}
+
 #[derive(Clone)]
 pub struct IntrospectionResolver {
     _logger: Logger,
-    type_objects: TypeObjectsMap,
-    directives: r::Value,
+    objects: Arc<IntrospectionObjects>,
 }
 
 impl IntrospectionResolver {
-    pub fn new(logger: &Logger, schema: &Schema) -> Self {
+    /// Create a resolver for introspection queries against `schema`. The
+    /// introspection objects are only built for the first resolver for a
+    /// schema and cached with the schema after that
+    pub fn new(logger: &Logger, schema: &ApiSchema) -> Self {
         let logger = logger.new(o!("component" => "IntrospectionResolver"));
 
-        // Generate queryable objects for all types in the schema
-        let mut type_objects = schema_type_objects(schema);
-
-        // Generate queryable objects for all directives in the schema
-        let directives = schema_directive_objects(schema, &mut type_objects);
-
         IntrospectionResolver {
             _logger: logger,
-            type_objects,
-            directives,
+            objects: schema.introspection_objects(introspection_objects),
         }
     }
 
+    fn type_objects(&self) -> &TypeObjectsMap {
+        &self.objects.types
+    }
+
     fn schema_object(&self) -> r::Value {
         object! {
             queryType:
-                self.type_objects
+                self.type_objects()
                     .get(&String::from("Query"))
                     .cloned(),
             subscriptionType:
-                self.type_objects
+                self.type_objects()
                     .get(&String::from("Subscription"))
                     .cloned(),
             mutationType: r::Value::Null,
-            types: self.type_objects.values().cloned().collect::<Vec<_>>(),
-            directives: self.directives.clone(),
+            types: self.type_objects().values().cloned().collect::<Vec<_>>(),
+            directives: self.objects.directives.clone(),
         }
     }
 
@@ -346,7 +358,7 @@ impl IntrospectionResolver {
             r::Value::String(s) => Some(s),
             _ => None,
         }
-        .and_then(|name| self.type_objects.get(name).cloned())
+        .and_then(|name| self.type_objects().get(name).cloned())
         .unwrap_or(r::Value::Null)
     }
 }
@@ -393,7 +405,7 @@ impl Resolver for IntrospectionResolver {
                                 r::Value::String(ref type_name) => Some(type_name),
                                 _ => None,
                             })
-                            .filter_map(|type_name| self.type_objects.get(type_name).cloned())
+                            .filter_map(|type_name| self.type_objects().get(type_name).cloned())
                             .map(|v| r::Value::try_from(v))
                             .collect::<Result<_, _>>()
                             .map_err(|v| {
@@ -431,7 +443,7 @@ impl Resolver for IntrospectionResolver {
             }
             "type" | "ofType" => match prefetched_object {
                 Some(r::Value::String(type_name)) => self
-                    .type_objects
+                    .type_objects()
                     .get(&type_name)
                     .cloned()
                     .unwrap_or(r::Value::Null),
@@ -27,7 +27,9 @@ mod runner;
 /// Prelude that exports the most important traits and types.
 pub mod prelude {
     pub use super::execution::{ast as a, ExecutionContext, Query, Resolver};
-    pub use super::introspection::{introspection_schema, IntrospectionResolver};
+    pub use super::introspection::{
+        introspection_schema, warm_introspection, IntrospectionResolver,
+    };
     pub use super::query::{execute_query, ext::BlockConstraint, QueryExecutionOptions};
     pub use super::schema::{api_schema, APISchemaError};
     pub use super::store::StoreResolver;
@@ -12,13 +12,14 @@ use graph::prometheus::{Gauge, Histogram};
 use graph::{
     components::store::SubscriptionManager,
     prelude::{
-        async_trait, o, CheapClone, DeploymentState, GraphQlRunner as GraphQlRunnerTrait, Logger,
-        Query, QueryExecutionError, Subscription, SubscriptionError, SubscriptionResult,
+        async_trait, chrono, o, warn, CheapClone, DeploymentState,
+        GraphQlRunner as GraphQlRunnerTrait, Logger, Query, QueryExecutionError, Subscription,
+        SubscriptionError, SubscriptionResult,
     },
 };
 use graph::{data::graphql::effort::LoadManager, prelude::QueryStoreManager};
 use graph::{
-    data::query::{QueryResults, QueryTarget},
+    data::query::{Deprecation, QueryResults, QueryTarget},
     prelude::QueryStore,
 };
 
@@ -105,6 +106,14 @@ lazy_static! {
         .map(|s| u32::from_str(&s)
             .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_GRAPHQL_MAX_SKIP")))
         .unwrap_or(std::u32::MAX);
+    // How many days after a deployment was replaced by a newer version of
+    // its subgraph it will be removed. This is only used to tell clients
+    // that still query the deployment by its hash when it will go away
+    static ref DEPRECATED_VERSION_SUNSET: Option<chrono::Duration> =
+        env::var("GRAPH_DEPRECATED_VERSION_SUNSET_DAYS")
+            .ok()
+            .map(|s| chrono::Duration::days(i64::from_str(&s)
+                .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_DEPRECATED_VERSION_SUNSET_DAYS"))));
     // Allow skipping the check whether a deployment has changed while
     // we were running a query. Once we are sure that the check mechanism
     // is reliable, this variable should be removed
@@ -192,19 +201,15 @@ where
         // mix data from different block heights even if no reverts happen
         // while the query is running. `self.store` can not be used after this
         // point, and everything needs to go through the `store` we are
-        // setting up here
-        let store = self.store.query_store(target, false).await?;
-        let state = store.deployment_state().await?;
-        let network = Some(store.network_name().to_string());
-        let schema = store.api_schema()?;
-
-        // Test only, see c435c25decbc4ad7bbbadf8e0ced0ff2
-        #[cfg(debug_assertions)]
-        let state = INITIAL_DEPLOYMENT_STATE_FOR_TESTS
-            .lock()
-            .unwrap()
-            .clone()
-            .unwrap_or(state);
+        // setting up here.
+        //
+        // Which replica a query runs against can depend on its cost, which
+        // we only know once we have looked at the query. The store that we
+        // use for that always uses the main database and is only used to
+        // get the schema
+        let schema_store = self.store.query_store(target, true).await?;
+        let network = Some(schema_store.network_name().to_string());
+        let schema = schema_store.api_schema()?;
 
         let max_depth = max_depth.unwrap_or(*GRAPHQL_MAX_DEPTH);
         let query = crate::execution::Query::new(
@@ -215,6 +220,21 @@ where
             max_complexity,
             max_depth,
         )?;
+
+        // Use the deployment we got the schema for, even if `target` is a
+        // subgraph name whose current version changed in the meantime
+        let target = QueryTarget::Deployment(query.schema.id().clone());
+        let store = self.store.query_store_for_cost(target, query.cost).await?;
+        let state = store.deployment_state().await?;
+
+        // Test only, see c435c25decbc4ad7bbbadf8e0ced0ff2
+        #[cfg(debug_assertions)]
+        let state = INITIAL_DEPLOYMENT_STATE_FOR_TESTS
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or(state);
+
         self.load_manager
             .decide(
                 &store.wait_stats(),
@@ -231,6 +251,7 @@ where
             let resolver = StoreResolver::at_block(
                 &self.logger,
                 store.cheap_clone(),
+                &state,
                 self.subscription_manager.cheap_clone(),
                 bc,
                 error_policy,
@@ -290,17 +311,45 @@ where
         max_first: Option<u32>,
         max_skip: Option<u32>,
     ) -> QueryResults {
-        self.execute(
-            query,
-            target,
-            max_complexity,
-            max_depth,
-            max_first,
-            max_skip,
-            self.result_size.cheap_clone(),
-        )
-        .await
-        .unwrap_or_else(|e| e)
+        let deployment = match &target {
+            QueryTarget::Deployment(id) => Some(id.clone()),
+            QueryTarget::Name(_) => None,
+        };
+
+        let mut results = self
+            .execute(
+                query,
+                target,
+                max_complexity,
+                max_depth,
+                max_first,
+                max_skip,
+                self.result_size.cheap_clone(),
+            )
+            .await
+            .unwrap_or_else(|e| e);
+
+        // Clients that query a deployment by its hash instead of through the
+        // subgraph name get told when that deployment has been replaced
+        if let Some(deployment) = deployment {
+            match self.store.deprecation(&deployment).await {
+                Ok(deprecation) => results.set_deprecation(deprecation.map(|deprecation| {
+                    Deprecation {
+                        sunset_at: DEPRECATED_VERSION_SUNSET
+                            .map(|sunset| deprecation.deprecated_at + sunset),
+                        ..deprecation
+                    }
+                })),
+                Err(e) => warn!(
+                    self.logger,
+                    "Failed to check whether deployment was replaced";
+                    "deployment" => deployment.as_str(),
+                    "error" => e.to_string()
+                ),
+            }
+        }
+
+        results
     }
 
     async fn run_subscription(
@@ -1,3 +1,4 @@
+use std::collections::BTreeSet;
 use std::str::FromStr;
 
 use graphql_parser::Pos;
@@ -7,8 +8,10 @@ use lazy_static::lazy_static;
 use crate::schema::ast;
 
 use graph::data::{
-    graphql::ext::{DirectiveExt, DocumentExt, ValueExt},
-    schema::{META_FIELD_NAME, META_FIELD_TYPE, SCHEMA_TYPE_NAME},
+    graphql::ext::{DirectiveExt, DirectiveFinder, DocumentExt, ValueExt},
+    schema::{
+        META_FIELD_NAME, META_FIELD_TYPE, SCHEMA_TYPE_NAME, SEARCH_FIELD_NAME, SEARCH_FIELD_TYPE,
+    },
 };
 use graph::prelude::s::{Value, *};
 use graph::prelude::*;
@@ -26,6 +29,14 @@ pub enum APISchemaError {
 
 const BLOCK_HEIGHT: &str = "Block_height";
 
+const BLOCK_RANGE: &str = "Block_range";
+
+/// The fields of entity types that hold the range of blocks in which an
+/// entity version is valid. They are only set for queries with a
+/// `blockRange` argument
+pub(crate) const VALID_FROM_FIELD: &str = "_validFrom";
+pub(crate) const VALID_TO_FIELD: &str = "_validTo";
+
 const ERROR_POLICY_TYPE: &str = "_SubgraphErrorPolicy_";
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -86,8 +97,10 @@ pub fn api_schema(input_schema: &Document) -> Result<Document, APISchemaError> {
     add_builtin_scalar_types(&mut schema)?;
     add_order_direction_enum(&mut schema);
     add_block_height_type(&mut schema);
+    add_block_range_type(&mut schema);
     add_meta_field_type(&mut schema);
     add_types_for_object_types(&mut schema, &object_types)?;
+    add_version_fields(&mut schema, &object_types);
     add_types_for_interface_types(&mut schema, &interface_types)?;
     add_field_arguments(&mut schema, input_schema)?;
     add_query_type(&mut schema, &object_types, &interface_types)?;
@@ -178,8 +191,25 @@ fn add_directives(schema: &mut Document) {
         repeatable: false,
     });
 
+    let relation = Definition::DirectiveDefinition(DirectiveDefinition {
+        position: Pos::default(),
+        description: None,
+        name: "relation".to_owned(),
+        arguments: vec![InputValue {
+            position: Pos::default(),
+            description: None,
+            name: "through".to_owned(),
+            value_type: Type::NamedType("String".to_owned()),
+            default_value: None,
+            directives: vec![],
+        }],
+        locations: vec![DirectiveLocation::FieldDefinition],
+        repeatable: false,
+    });
+
     schema.definitions.push(entity);
     schema.definitions.push(derived_from);
+    schema.definitions.push(relation);
     schema.definitions.push(subgraph_id);
 }
 
@@ -243,6 +273,72 @@ fn add_block_height_type(schema: &mut Document) {
     schema.definitions.push(def);
 }
 
+/// Adds a global `Block_range` type to the schema. The `blockRange`
+/// argument accepts values of this type
+fn add_block_range_type(schema: &mut Document) {
+    let typedef = TypeDefinition::InputObject(InputObjectType {
+        position: Pos::default(),
+        description: None,
+        name: BLOCK_RANGE.to_string(),
+        directives: vec![],
+        fields: vec![
+            InputValue {
+                position: Pos::default(),
+                description: None,
+                name: "from".to_owned(),
+                value_type: Type::NonNullType(Box::new(Type::NamedType("Int".to_owned()))),
+                default_value: None,
+                directives: vec![],
+            },
+            InputValue {
+                position: Pos::default(),
+                description: None,
+                name: "to".to_owned(),
+                value_type: Type::NonNullType(Box::new(Type::NamedType("Int".to_owned()))),
+                default_value: None,
+                directives: vec![],
+            },
+        ],
+    });
+    let def = Definition::TypeDefinition(typedef);
+    schema.definitions.push(def);
+}
+
+/// Adds the `_validFrom` and `_validTo` fields to all entity types
+fn add_version_fields(schema: &mut Document, object_types: &[&ObjectType]) {
+    for object_type in object_types {
+        if object_type.name.eq(SCHEMA_TYPE_NAME) {
+            continue;
+        }
+        let object_type = ast::get_object_type_mut(schema, &object_type.name)
+            .expect("object type from input schema is missing in API schema");
+        object_type.fields.push(Field {
+            position: Pos::default(),
+            description: Some(
+                "The first block in which this version of the entity is valid. \
+                 Only set when querying with `blockRange`"
+                    .to_owned(),
+            ),
+            name: VALID_FROM_FIELD.to_owned(),
+            arguments: vec![],
+            field_type: Type::NamedType("Int".to_owned()),
+            directives: vec![],
+        });
+        object_type.fields.push(Field {
+            position: Pos::default(),
+            description: Some(
+                "The first block in which this version of the entity is no longer valid, \
+                 or null if it is still valid. Only set when querying with `blockRange`"
+                    .to_owned(),
+            ),
+            name: VALID_TO_FIELD.to_owned(),
+            arguments: vec![],
+            field_type: Type::NamedType("Int".to_owned()),
+            directives: vec![],
+        });
+    }
+}
+
 /// Adds a global `_Meta_` type to the schema. The `_meta` field
 /// accepts values of this type
 fn add_meta_field_type(schema: &mut Document) {
@@ -368,8 +464,10 @@ fn field_filter_input_values(
             Ok(match named_type {
                 TypeDefinition::Object(_) | TypeDefinition::Interface(_) => {
                     // Only add `where` filter fields for object and interface fields
-                    // if they are not @derivedFrom
-                    if ast::get_derived_from_directive(field).is_some() {
+                    // if they are not @derivedFrom or stored in a join table
+                    if ast::get_derived_from_directive(field).is_some()
+                        || field.relation_through().is_some()
+                    {
                         vec![]
                     } else {
                         // We allow filtering with `where: { other: "some-id" }` and
@@ -472,7 +570,9 @@ fn field_list_filter_input_values(
         // derived, we allow ID strings to be passed on.
         let input_field_type = match typedef {
             TypeDefinition::Interface(_) | TypeDefinition::Object(_) => {
-                if ast::get_derived_from_directive(field).is_some() {
+                if ast::get_derived_from_directive(field).is_some()
+                    || field.relation_through().is_some()
+                {
                     return None;
                 } else {
                     Type::NamedType("String".into())
@@ -484,18 +584,25 @@ fn field_list_filter_input_values(
         };
 
         Some(
-            vec!["", "not", "contains", "not_contains"]
-                .into_iter()
-                .map(|filter_type| {
-                    input_value(
-                        &field.name,
-                        filter_type,
-                        Type::ListType(Box::new(Type::NonNullType(Box::new(
-                            input_field_type.clone(),
-                        )))),
-                    )
-                })
-                .collect(),
+            vec![
+                "",
+                "not",
+                "contains",
+                "not_contains",
+                "contains_any",
+                "contains_all",
+            ]
+            .into_iter()
+            .map(|filter_type| {
+                input_value(
+                    &field.name,
+                    filter_type,
+                    Type::ListType(Box::new(Type::NonNullType(Box::new(
+                        input_field_type.clone(),
+                    )))),
+                )
+            })
+            .collect(),
         )
     })
 }
@@ -532,16 +639,33 @@ fn add_query_type(
         .iter()
         .map(|t| t.name.as_str())
         .filter(|name| !name.eq(&SCHEMA_TYPE_NAME))
-        .chain(interface_types.iter().map(|t| t.name.as_str()))
-        .flat_map(|name| query_fields_for_type(name))
+        .flat_map(|name| query_fields_for_object_type(name))
+        .chain(
+            interface_types
+                .iter()
+                .flat_map(|t| query_fields_for_type(t.name.as_str())),
+        )
         .collect::<Vec<Field>>();
-    let mut fulltext_fields = schema
+    let fulltexts = schema
         .get_fulltext_directives()
-        .map_err(|_| APISchemaError::FulltextSearchNonDeterministic)?
+        .map_err(|_| APISchemaError::FulltextSearchNonDeterministic)?;
+    let mut fulltext_fields = fulltexts
         .iter()
         .filter_map(|fulltext| query_field_for_fulltext(fulltext))
         .collect();
     fields.append(&mut fulltext_fields);
+    // The `search` field is only added if it does not clash with a field
+    // for an entity type or a fulltext query
+    let search = search_result_type(&fulltexts)
+        .filter(|_| !fields.iter().any(|field| field.name == SEARCH_FIELD_NAME));
+    if let Some(search_result_type) = search {
+        fields.push(query_field_for_search());
+        schema
+            .definitions
+            .push(Definition::TypeDefinition(TypeDefinition::Union(
+                search_result_type,
+            )));
+    }
     fields.push(meta_field());
 
     let typedef = TypeDefinition::Object(ObjectType {
@@ -612,6 +736,81 @@ fn query_field_for_fulltext(fulltext: &Directive) -> Option<Field> {
     })
 }
 
+/// The union of all entity types that are included in a fulltext index,
+/// or `None` if the schema does not have any fulltext indexes
+fn search_result_type(fulltexts: &[&Directive]) -> Option<UnionType> {
+    let types: BTreeSet<String> = fulltexts
+        .iter()
+        .flat_map(|fulltext| {
+            fulltext
+                .argument("include")
+                .and_then(|includes| includes.as_list())
+                .into_iter()
+                .flatten()
+        })
+        .filter_map(|include| include.as_object())
+        .filter_map(|include| include.get("entity").and_then(|entity| entity.as_str()))
+        .map(|entity| entity.to_string())
+        .collect();
+
+    if types.is_empty() {
+        return None;
+    }
+    Some(UnionType {
+        position: Pos::default(),
+        description: None,
+        name: SEARCH_FIELD_TYPE.to_string(),
+        directives: vec![],
+        types: types.into_iter().collect(),
+    })
+}
+
+/// The `search` field searches all fulltext indexes at once and returns
+/// the matching entities of all types ordered by rank
+fn query_field_for_search() -> Field {
+    Field {
+        position: Pos::default(),
+        description: Some("Search all fulltext indexes".to_string()),
+        name: SEARCH_FIELD_NAME.to_string(),
+        arguments: vec![
+            // text: String
+            InputValue {
+                position: Pos::default(),
+                description: None,
+                name: String::from("text"),
+                value_type: Type::NonNullType(Box::new(Type::NamedType(String::from("String")))),
+                default_value: None,
+                directives: vec![],
+            },
+            // first: Int
+            InputValue {
+                position: Pos::default(),
+                description: None,
+                name: String::from("first"),
+                value_type: Type::NamedType(String::from("Int")),
+                default_value: Some(Value::Int(100.into())),
+                directives: vec![],
+            },
+            // skip: Int
+            InputValue {
+                position: Pos::default(),
+                description: None,
+                name: String::from("skip"),
+                value_type: Type::NamedType(String::from("Int")),
+                default_value: Some(Value::Int(0.into())),
+                directives: vec![],
+            },
+            // block: BlockHeight
+            block_argument(),
+            subgraph_error_argument(),
+        ],
+        field_type: Type::NonNullType(Box::new(Type::ListType(Box::new(Type::NonNullType(
+            Box::new(Type::NamedType(SEARCH_FIELD_TYPE.to_string())),
+        ))))),
+        directives: vec![],
+    }
+}
+
 /// Adds a root `Subscription` object type to the schema.
 fn add_subscription_type(
     schema: &mut Document,
@@ -666,6 +865,23 @@ fn block_argument() -> InputValue {
     }
 }
 
+fn block_range_argument() -> InputValue {
+    InputValue {
+        position: Pos::default(),
+        description: Some(
+            "Return all versions of the entities that were valid at some point \
+             between the blocks `from` and `to`, inclusive, instead of the versions \
+             valid at one block. The fields `_validFrom` and `_validTo` of each \
+             version hold the range of blocks in which it is valid."
+                .to_owned(),
+        ),
+        name: "blockRange".to_string(),
+        value_type: Type::NamedType(BLOCK_RANGE.to_owned()),
+        default_value: None,
+        directives: vec![],
+    }
+}
+
 fn subgraph_error_argument() -> InputValue {
     InputValue {
         position: Pos::default(),
@@ -722,6 +938,19 @@ fn query_fields_for_type(type_name: &str) -> Vec<Field> {
     ]
 }
 
+/// Generates `Query` fields for the given object type. Unlike the fields for
+/// interfaces, the collection field can query entity versions in a range of
+/// blocks
+fn query_fields_for_object_type(type_name: &str) -> Vec<Field> {
+    let mut fields = query_fields_for_type(type_name);
+    fields
+        .last_mut()
+        .expect("the last field is the collection field")
+        .arguments
+        .push(block_range_argument());
+    fields
+}
+
 fn meta_field() -> Field {
     lazy_static! {
         static ref META_FIELD: Field = Field {
@@ -760,15 +989,22 @@ fn collection_arguments_for_named_type(type_name: &str) -> Vec<InputValue> {
     let args = vec![
         skip,
         first,
+        // `orderBy` and `orderDirection` are lists so that entities can be
+        // ordered by several fields; input coercion turns a single value
+        // into a list with one element
         input_value(
             &"orderBy".to_string(),
             "",
-            Type::NamedType(format!("{}_orderBy", type_name)),
+            Type::ListType(Box::new(Type::NonNullType(Box::new(Type::NamedType(
+                format!("{}_orderBy", type_name),
+            ))))),
         ),
         input_value(
             &"orderDirection".to_string(),
             "",
-            Type::NamedType("OrderDirection".to_string()),
+            Type::ListType(Box::new(Type::NonNullType(Box::new(Type::NamedType(
+                "OrderDirection".to_string(),
+            ))))),
         ),
         input_value(
             &"where".to_string(),
@@ -855,7 +1091,8 @@ fn add_field_arguments(
 
 #[cfg(test)]
 mod tests {
-    use graph::data::graphql::DocumentExt;
+    use graph::data::graphql::{DocumentExt, TypeExt};
+    use graph::data::schema::SEARCH_FIELD_TYPE;
     use graphql_parser::schema::*;
 
     use super::api_schema;
@@ -1016,10 +1253,14 @@ mod tests {
                 "favoritePetNames_not",
                 "favoritePetNames_contains",
                 "favoritePetNames_not_contains",
+                "favoritePetNames_contains_any",
+                "favoritePetNames_contains_all",
                 "pets",
                 "pets_not",
                 "pets_contains",
                 "pets_not_contains",
+                "pets_contains_any",
+                "pets_contains_all",
                 "favoriteFurType",
                 "favoriteFurType_not",
                 "favoriteFurType_in",
@@ -1108,6 +1349,7 @@ mod tests {
                 "where",
                 "block",
                 "subgraphError",
+                "blockRange",
             ]
             .iter()
             .map(ToString::to_string)
@@ -1249,4 +1491,51 @@ type Gravatar @entity {
         }
         .expect("\"metadata\" field is missing on Query type");
     }
+
+    #[test]
+    fn api_schema_contains_search_field_for_fulltext_entities() {
+        const SCHEMA: &str = r#"
+type _Schema_ @fulltext(
+  name: "gravatarSearch"
+  language: en
+  algorithm: rank
+  include: [{ entity: "Gravatar", fields: [{ name: "displayName" }] }]
+) @fulltext(
+  name: "accountSearch"
+  language: en
+  algorithm: rank
+  include: [{ entity: "Account", fields: [{ name: "name" }] }]
+)
+type Gravatar @entity {
+  id: ID!
+  displayName: String!
+}
+type Account @entity {
+  id: ID!
+  name: String!
+}
+type Transfer @entity {
+  id: ID!
+}
+"#;
+        let input_schema = parse_schema(SCHEMA).expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+
+        let query_type = schema
+            .get_named_type("Query")
+            .expect("Query type is missing in derived API schema");
+        let search_field = match query_type {
+            TypeDefinition::Object(t) => ast::get_field(t, &String::from("search")),
+            _ => None,
+        }
+        .expect("\"search\" field is missing on Query type");
+        assert_eq!(SEARCH_FIELD_TYPE, search_field.field_type.get_base_type());
+
+        match schema.get_named_type(SEARCH_FIELD_TYPE) {
+            Some(TypeDefinition::Union(union)) => {
+                assert_eq!(vec!["Account", "Gravatar"], union.types)
+            }
+            _ => panic!("search result type is missing or not a union"),
+        }
+    }
 }
@@ -23,6 +23,8 @@ pub(crate) enum FilterOp {
     NotIn,
     Contains,
     NotContains,
+    ContainsAny,
+    ContainsAll,
     StartsWith,
     NotStartsWith,
     EndsWith,
@@ -40,6 +42,8 @@ pub(crate) fn parse_field_as_filter(key: &str) -> (String, FilterOp) {
         k if k.ends_with("_lte") => ("_lte", FilterOp::LessOrEqual),
         k if k.ends_with("_not_in") => ("_not_in", FilterOp::NotIn),
         k if k.ends_with("_in") => ("_in", FilterOp::In),
+        k if k.ends_with("_contains_any") => ("_contains_any", FilterOp::ContainsAny),
+        k if k.ends_with("_contains_all") => ("_contains_all", FilterOp::ContainsAll),
         k if k.ends_with("_not_contains") => ("_not_contains", FilterOp::NotContains),
         k if k.ends_with("_contains") => ("_contains", FilterOp::Contains),
         k if k.ends_with("_not_starts_with") => ("_not_starts_with", FilterOp::NotStartsWith),
@@ -12,20 +12,25 @@ use std::collections::BTreeMap;
 use std::rc::Rc;
 use std::time::Instant;
 
-use graph::{components::store::EntityType, data::graphql::*};
+use graph::data::schema::SEARCH_FIELD_TYPE;
+use graph::{
+    components::store::{EntityType, VALID_FROM_ATTRIBUTE, VALID_TO_ATTRIBUTE},
+    data::graphql::*,
+};
 use graph::{
     data::graphql::ext::DirectiveFinder,
     prelude::{
         s, ApiSchema, AttributeNames, BlockNumber, ChildMultiplicity, EntityCollection,
-        EntityFilter, EntityLink, EntityOrder, EntityWindow, Logger, ParentLink,
+        EntityFilter, EntityLink, EntityOrder, EntityWindow, JoinLink, Logger, ParentLink,
         QueryExecutionError, QueryStore, StoreError, Value as StoreValue, WindowAttribute,
     },
 };
 
 use crate::execution::{ast as a, ExecutionContext, Resolver};
 use crate::runner::ResultSizeMetrics;
+use crate::schema::api::{VALID_FROM_FIELD, VALID_TO_FIELD};
 use crate::schema::ast as sast;
-use crate::store::query::build_query;
+use crate::store::query::{build_query, build_range};
 use crate::store::StoreResolver;
 
 lazy_static! {
@@ -252,6 +257,9 @@ enum JoinRelation<'a> {
     Direct(JoinField<'a>),
     // Name of the field in the parent type containing child ids
     Derived(JoinField<'a>),
+    // Parent and child are connected through the join table of a
+    // `@relation(through: ..)` field
+    Through(JoinLink),
 }
 
 #[derive(Debug)]
@@ -274,12 +282,25 @@ impl<'a> JoinCond<'a> {
         let field = parent_type
             .field(field_name)
             .expect("field_name is a valid field of parent_type");
-        let relation =
-            if let Some(derived_from_field) = sast::get_derived_from_field(child_type, field) {
-                JoinRelation::Direct(JoinField::new(derived_from_field))
+        let relation = if field.relation_through().is_some() {
+            JoinRelation::Through(JoinLink {
+                owner: parent_type.into(),
+                field: field.name.clone(),
+                reverse: false,
+            })
+        } else if let Some(derived_from_field) = sast::get_derived_from_field(child_type, field) {
+            if derived_from_field.relation_through().is_some() {
+                JoinRelation::Through(JoinLink {
+                    owner: child_type.into(),
+                    field: derived_from_field.name.clone(),
+                    reverse: true,
+                })
             } else {
-                JoinRelation::Derived(JoinField::new(field))
-            };
+                JoinRelation::Direct(JoinField::new(derived_from_field))
+            }
+        } else {
+            JoinRelation::Derived(JoinField::new(field))
+        };
         JoinCond {
             parent_type: parent_type.into(),
             child_type: child_type.into(),
@@ -349,6 +370,12 @@ impl<'a> JoinCond<'a> {
                 };
                 (ids, EntityLink::Parent(parent_link))
             }
+            JoinRelation::Through(join_link) => {
+                // the join table connects parent ids to child ids, we
+                // only need the parent ids
+                let ids = parents_by_id.into_iter().map(|(id, _)| id).collect();
+                (ids, EntityLink::Through(join_link.clone(), multiplicity))
+            }
         }
     }
 }
@@ -556,37 +583,46 @@ fn execute_selection_set<'a>(
             let field_type = object_type
                 .field(&field.name)
                 .expect("field names are valid");
-            let child_type = schema
-                .object_or_interface(field_type.field_type.get_base_type())
-                .expect("we only collect fields that are objects or interfaces");
-
-            let join = Join::new(
-                ctx.query.schema.as_ref(),
-                object_type,
-                child_type,
-                &field.name,
-            );
 
-            // "Select by Specific Attribute Names" is an experimental feature and can be disabled completely.
-            // If this environment variable is set, the program will use an empty collection that,
-            // effectively, causes the `AttributeNames::All` variant to be used as a fallback value for all
-            // queries.
-            let collected_columns =
-                if *DISABLE_EXPERIMENTAL_FEATURE_SELECT_BY_SPECIFIC_ATTRIBUTE_NAMES {
-                    SelectedAttributes(BTreeMap::new())
-                } else {
-                    SelectedAttributes::for_field(field)?
-                };
+            let children = if field_type.field_type.get_base_type() == SEARCH_FIELD_TYPE {
+                // The search across all fulltext indexes can only appear on
+                // the root query type
+                execute_search(resolver, ctx, field)
+            } else {
+                let child_type = schema
+                    .object_or_interface(field_type.field_type.get_base_type())
+                    .expect("we only collect fields that are objects or interfaces");
+
+                let join = Join::new(
+                    ctx.query.schema.as_ref(),
+                    object_type,
+                    child_type,
+                    &field.name,
+                );
+
+                // "Select by Specific Attribute Names" is an experimental feature and can be disabled completely.
+                // If this environment variable is set, the program will use an empty collection that,
+                // effectively, causes the `AttributeNames::All` variant to be used as a fallback value for all
+                // queries.
+                let collected_columns =
+                    if *DISABLE_EXPERIMENTAL_FEATURE_SELECT_BY_SPECIFIC_ATTRIBUTE_NAMES {
+                        SelectedAttributes(BTreeMap::new())
+                    } else {
+                        SelectedAttributes::for_field(field)?
+                    };
+
+                execute_field(
+                    resolver,
+                    &ctx,
+                    &parents,
+                    &join,
+                    field,
+                    field_type,
+                    collected_columns,
+                )
+            };
 
-            match execute_field(
-                resolver,
-                &ctx,
-                &parents,
-                &join,
-                field,
-                field_type,
-                collected_columns,
-            ) {
+            match children {
                 Ok(children) => {
                     match execute_selection_set(resolver, ctx, children, &field.selection_set) {
                         Ok(children) => {
@@ -645,6 +681,37 @@ fn execute_field(
     .map_err(|e| vec![e])
 }
 
+/// Search all fulltext indexes for the `text` argument of the root `search`
+/// field. The resulting entities can be of any type that is included in a
+/// fulltext index
+fn execute_search(
+    resolver: &StoreResolver,
+    ctx: &ExecutionContext<impl Resolver>,
+    field: &a::Field,
+) -> Result<Vec<Node>, Vec<QueryExecutionError>> {
+    // Argument coercion turns the `text` argument into an object
+    // `{ <field name>: text }`
+    let text = match field.argument_value("text") {
+        Some(r::Value::Object(object)) => match object.iter().next() {
+            Some((_, r::Value::String(text))) => text,
+            _ => return Err(vec![QueryExecutionError::FulltextQueryRequiresFilter]),
+        },
+        _ => return Err(vec![QueryExecutionError::FulltextQueryRequiresFilter]),
+    };
+    let range = build_range(field, ctx.max_first, ctx.max_skip).map_err(|e| vec![e])?;
+
+    resolver
+        .store
+        .fulltext_search(
+            text,
+            range,
+            resolver.block_number(),
+            Some(ctx.query.query_id.clone()),
+        )
+        .map(|entities| entities.into_iter().map(Node::from).collect())
+        .map_err(|e| vec![e])
+}
+
 /// Query child entities for `parents` from the store. The `join` indicates
 /// in which child field to look for the parent's id/join field. When
 /// `is_single` is `true`, there is at most one child per parent.
@@ -696,9 +763,26 @@ fn fetch(
         }
         query.collection = EntityCollection::Window(windows);
     }
-    store
-        .find_query_values(query)
-        .map(|entities| entities.into_iter().map(|entity| entity.into()).collect())
+    let is_history = query.history.is_some();
+    store.find_query_values(query).map(|entities| {
+        entities
+            .into_iter()
+            .map(|mut entity| {
+                if is_history {
+                    // Expose the block range of each version under the
+                    // names of the fields in the API schema
+                    for (attr, field) in &[
+                        (VALID_FROM_ATTRIBUTE, VALID_FROM_FIELD),
+                        (VALID_TO_ATTRIBUTE, VALID_TO_FIELD),
+                    ] {
+                        let value = entity.remove(*attr).unwrap_or(r::Value::Null);
+                        entity.insert(field.to_string(), value);
+                    }
+                }
+                entity.into()
+            })
+            .collect()
+    })
 }
 
 #[derive(Debug, Default, Clone)]
@@ -706,16 +790,21 @@ pub(crate) struct SelectedAttributes(BTreeMap<String, AttributeNames>);
 
 impl SelectedAttributes {
     /// Extract the attributes we should select from `selection_set`. In
-    /// particular, disregard derived fields since they are not stored
+    /// particular, disregard derived fields, fields stored in a join
+    /// table, and the fields for the block range of entity versions since
+    /// they are not stored in the entity's table
     fn for_field(field: &a::Field) -> Result<SelectedAttributes, Vec<QueryExecutionError>> {
         let mut map = BTreeMap::new();
         for (object_type, fields) in field.selection_set.fields() {
             let column_names = fields
+                .filter(|field| field.name != VALID_FROM_FIELD && field.name != VALID_TO_FIELD)
                 .filter(|field| {
-                    // Keep fields that are not derived and for which we
-                    // can find the field type
+                    // Keep fields that are not derived or stored in a
+                    // join table and for which we can find the field type
                     sast::get_field(object_type, &field.name)
-                        .map(|field_type| !field_type.is_derived())
+                        .map(|field_type| {
+                            !field_type.is_derived() && field_type.relation_through().is_none()
+                        })
                         .unwrap_or(false)
                 })
                 .map(|field| field.name.clone())
@@ -725,16 +814,23 @@ impl SelectedAttributes {
                 AttributeNames::Select(column_names),
             );
         }
-        // We need to also select the `orderBy` field if there is one.
+        // We need to also select the `orderBy` fields if there are any.
         // Because of how the API Schema is set up, `orderBy` can only have
-        // an enum value
-        match field.argument_value("orderBy") {
-            None => { /* nothing to do */ }
-            Some(r::Value::Enum(e)) => {
-                for columns in map.values_mut() {
-                    columns.add_str(e);
-                }
-            }
+        // an enum value or a list of them
+        let order_by = match field.argument_value("orderBy") {
+            None => vec![],
+            Some(r::Value::Enum(e)) => vec![e],
+            Some(r::Value::List(values)) => values
+                .iter()
+                .map(|value| match value {
+                    r::Value::Enum(e) => Ok(e),
+                    v => Err(vec![constraint_violation!(
+                        "'orderBy' attribute must be a list of enums but contains {:?}",
+                        v
+                    )
+                    .into()]),
+                })
+                .collect::<Result<_, _>>()?,
             Some(v) => {
                 return Err(vec![constraint_violation!(
                     "'orderBy' attribute must be an enum but is {:?}",
@@ -742,6 +838,11 @@ impl SelectedAttributes {
                 )
                 .into()]);
             }
+        };
+        for e in order_by {
+            for columns in map.values_mut() {
+                columns.add_str(e);
+            }
         }
         Ok(SelectedAttributes(map))
     }
@@ -1,4 +1,5 @@
 use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::convert::TryFrom;
 use std::mem::discriminant;
 
 use graph::data::value::Object;
@@ -10,12 +11,6 @@ use crate::schema::ast as sast;
 
 use super::prefetch::SelectedAttributes;
 
-#[derive(Debug)]
-enum OrderDirection {
-    Ascending,
-    Descending,
-}
-
 /// Builds a EntityQuery from GraphQL arguments.
 ///
 /// Panics if `entity` is not present in `schema`.
@@ -42,30 +37,67 @@ pub(crate) fn build_query<'a>(
                 (o.into(), selected_columns)
             })
             .collect(),
+        ObjectOrInterface::Union(_) => {
+            // Unions are only used for the results of a fulltext search
+            // across entity types, which does not go through here
+            return Err(QueryExecutionError::Unimplemented("unions".to_owned()));
+        }
     });
     let mut query = EntityQuery::new(parse_subgraph_id(entity)?, block, entity_types)
         .range(build_range(field, max_first, max_skip)?);
     if let Some(filter) = build_filter(entity, field)? {
         query = query.filter(filter);
     }
-    let order = match (
+    if let Some(history) = build_history(field, block)? {
+        query = query.history(history);
+    }
+    let order = build_order(
         build_order_by(entity, field)?,
         build_order_direction(field)?,
-    ) {
-        (Some((attr, value_type)), OrderDirection::Ascending) => {
-            EntityOrder::Ascending(attr, value_type)
-        }
-        (Some((attr, value_type)), OrderDirection::Descending) => {
-            EntityOrder::Descending(attr, value_type)
-        }
-        (None, _) => EntityOrder::Default,
-    };
+    )?;
     query = query.order(order);
     Ok(query)
 }
 
+/// Combines the fields from `orderBy` with the directions from
+/// `orderDirection`. A single direction applies to all fields, otherwise
+/// there must be one direction for each field
+fn build_order(
+    order_by: Vec<(String, ValueType)>,
+    directions: Vec<OrderDirection>,
+) -> Result<EntityOrder, QueryExecutionError> {
+    let directions = match directions.len() {
+        0 => vec![OrderDirection::Ascending; order_by.len()],
+        1 => vec![directions[0]; order_by.len()],
+        n if n == order_by.len() => directions,
+        n => {
+            return Err(QueryExecutionError::OrderDirectionMismatch(
+                order_by.len(),
+                n,
+            ))
+        }
+    };
+    let mut order_by: Vec<_> = order_by
+        .into_iter()
+        .zip(directions)
+        .map(|((attr, value_type), direction)| (attr, value_type, direction))
+        .collect();
+    Ok(match order_by.len() {
+        0 => EntityOrder::Default,
+        1 => match order_by.pop().unwrap() {
+            (attr, value_type, OrderDirection::Ascending) => {
+                EntityOrder::Ascending(attr, value_type)
+            }
+            (attr, value_type, OrderDirection::Descending) => {
+                EntityOrder::Descending(attr, value_type)
+            }
+        },
+        _ => EntityOrder::Multiple(order_by),
+    })
+}
+
 /// Parses GraphQL arguments into a EntityRange, if present.
-fn build_range(
+pub(crate) fn build_range(
     field: &a::Field,
     max_first: u32,
     max_skip: u32,
@@ -106,6 +138,41 @@ fn build_range(
     })
 }
 
+/// Parses the `blockRange` argument into an `EntityHistory`, if present.
+/// Since the query runs at `block`, the history can not extend past it
+fn build_history(
+    field: &a::Field,
+    block: BlockNumber,
+) -> Result<Option<EntityHistory>, QueryExecutionError> {
+    let range = match field.argument_value("blockRange") {
+        Some(r::Value::Object(range)) => range,
+        Some(r::Value::Null) | None => return Ok(None),
+        _ => unreachable!("blockRange is a Block_range input object"),
+    };
+    let bound = |name: &str| match range.get(name) {
+        Some(r::Value::Int(n)) => BlockNumber::try_from(*n).map_err(|_| {
+            QueryExecutionError::ValidationError(
+                Some(field.position),
+                format!("`blockRange.{}` must be a block number, but is {}", name, n),
+            )
+        }),
+        _ => unreachable!("the bounds of a Block_range are non-null Ints"),
+    };
+    let from = bound("from")?;
+    let to = bound("to")?.min(block);
+    if from < 0 || from > to {
+        return Err(QueryExecutionError::ValidationError(
+            Some(field.position),
+            format!(
+                "`blockRange` must contain at least one block that the subgraph has indexed, \
+                 but covers blocks {} to {}",
+                from, to
+            ),
+        ));
+    }
+    Ok(Some(EntityHistory { from, to }))
+}
+
 /// Parses GraphQL arguments into an EntityFilter, if present.
 fn build_filter(
     entity: ObjectOrInterface,
@@ -174,6 +241,8 @@ fn build_filter_from_object(
                     NotIn => EntityFilter::NotIn(field_name, list_values(store_value, "_not_in")?),
                     Contains => EntityFilter::Contains(field_name, store_value),
                     NotContains => EntityFilter::NotContains(field_name, store_value),
+                    ContainsAny => EntityFilter::ContainsAny(field_name, store_value),
+                    ContainsAll => EntityFilter::ContainsAll(field_name, store_value),
                     StartsWith => EntityFilter::StartsWith(field_name, store_value),
                     NotStartsWith => EntityFilter::NotStartsWith(field_name, store_value),
                     EndsWith => EntityFilter::EndsWith(field_name, store_value),
@@ -213,28 +282,42 @@ fn list_values(value: Value, filter_type: &str) -> Result<Vec<Value>, QueryExecu
     }
 }
 
-/// Parses GraphQL arguments into an field name to order by, if present.
+/// Parses GraphQL arguments into the field names to order by, if present.
 fn build_order_by(
     entity: ObjectOrInterface,
     field: &a::Field,
-) -> Result<Option<(String, ValueType)>, QueryExecutionError> {
+) -> Result<Vec<(String, ValueType)>, QueryExecutionError> {
+    let order_by_field = |name: &String| {
+        let field = sast::get_field(entity, name).ok_or_else(|| {
+            QueryExecutionError::EntityFieldError(entity.name().to_owned(), name.clone())
+        })?;
+        sast::get_field_value_type(&field.field_type)
+            .map(|value_type| (name.to_owned(), value_type))
+            .map_err(|_| {
+                QueryExecutionError::OrderByNotSupportedError(
+                    entity.name().to_owned(),
+                    name.clone(),
+                )
+            })
+    };
+
     match field.argument_value("orderBy") {
-        Some(r::Value::Enum(name)) => {
-            let field = sast::get_field(entity, name).ok_or_else(|| {
-                QueryExecutionError::EntityFieldError(entity.name().to_owned(), name.clone())
-            })?;
-            sast::get_field_value_type(&field.field_type)
-                .map(|value_type| Some((name.to_owned(), value_type)))
-                .map_err(|_| {
-                    QueryExecutionError::OrderByNotSupportedError(
-                        entity.name().to_owned(),
-                        name.clone(),
-                    )
-                })
-        }
+        Some(r::Value::Enum(name)) => Ok(vec![order_by_field(name)?]),
+        Some(r::Value::List(names)) if !names.is_empty() => names
+            .iter()
+            .map(|name| match name {
+                r::Value::Enum(name) => order_by_field(name),
+                _ => Err(QueryExecutionError::OrderByNotSupportedError(
+                    entity.name().to_owned(),
+                    name.to_string(),
+                )),
+            })
+            .collect(),
         _ => match field.argument_value("text") {
-            Some(r::Value::Object(filter)) => build_fulltext_order_by_from_object(filter),
-            None => Ok(None),
+            Some(r::Value::Object(filter)) => Ok(build_fulltext_order_by_from_object(filter)?
+                .into_iter()
+                .collect()),
+            None => Ok(vec![]),
             _ => Err(QueryExecutionError::InvalidFilterError),
         },
     }
@@ -255,16 +338,20 @@ fn build_fulltext_order_by_from_object(
     )
 }
 
-/// Parses GraphQL arguments into a EntityOrder, if present.
-fn build_order_direction(field: &a::Field) -> Result<OrderDirection, QueryExecutionError> {
-    Ok(field
-        .argument_value("orderDirection")
-        .map(|value| match value {
-            r::Value::Enum(name) if name == "asc" => OrderDirection::Ascending,
+/// Parses GraphQL arguments into the directions to order by, if present.
+fn build_order_direction(field: &a::Field) -> Result<Vec<OrderDirection>, QueryExecutionError> {
+    fn direction(value: &r::Value) -> OrderDirection {
+        match value {
             r::Value::Enum(name) if name == "desc" => OrderDirection::Descending,
             _ => OrderDirection::Ascending,
-        })
-        .unwrap_or(OrderDirection::Ascending))
+        }
+    }
+
+    Ok(match field.argument_value("orderDirection") {
+        Some(r::Value::List(values)) => values.iter().map(direction).collect(),
+        Some(r::Value::Null) | None => vec![],
+        Some(value) => vec![direction(value)],
+    })
 }
 
 /// Parses the subgraph ID from the ObjectType directives.
@@ -347,7 +434,7 @@ mod tests {
         },
         prelude::{
             s::{self, Directive, Field, InputValue, ObjectType, Type, Value as SchemaValue},
-            EntityOrder,
+            EntityOrder, OrderDirection,
         },
     };
     use graphql_parser::Pos;
@@ -651,6 +738,87 @@ mod tests {
         );
     }
 
+    #[test]
+    fn build_query_parses_multiple_order_by_fields() {
+        let order = |args: Vec<(&str, r::Value)>| {
+            build_query(
+                &default_object(),
+                BLOCK_NUMBER_MAX,
+                &default_field_with_vec(args),
+                &BTreeMap::new(),
+                std::u32::MAX,
+                std::u32::MAX,
+                Default::default(),
+            )
+            .map(|query| query.order)
+        };
+        let enums = |names: &[&str]| {
+            r::Value::List(
+                names
+                    .iter()
+                    .map(|name| r::Value::Enum(name.to_string()))
+                    .collect(),
+            )
+        };
+
+        assert_eq!(
+            EntityOrder::Multiple(vec![
+                (
+                    "name".to_string(),
+                    ValueType::String,
+                    OrderDirection::Descending
+                ),
+                (
+                    "email".to_string(),
+                    ValueType::String,
+                    OrderDirection::Ascending
+                )
+            ]),
+            order(vec![
+                ("orderBy", enums(&["name", "email"])),
+                ("orderDirection", enums(&["desc", "asc"])),
+            ])
+            .unwrap()
+        );
+
+        // A single direction applies to all fields
+        assert_eq!(
+            EntityOrder::Multiple(vec![
+                (
+                    "name".to_string(),
+                    ValueType::String,
+                    OrderDirection::Descending
+                ),
+                (
+                    "email".to_string(),
+                    ValueType::String,
+                    OrderDirection::Descending
+                )
+            ]),
+            order(vec![
+                ("orderBy", enums(&["name", "email"])),
+                ("orderDirection", enums(&["desc"])),
+            ])
+            .unwrap()
+        );
+
+        // A list with one field is the same as just that field
+        assert_eq!(
+            EntityOrder::Descending("email".to_string(), ValueType::String),
+            order(vec![
+                ("orderBy", enums(&["email"])),
+                ("orderDirection", r::Value::Enum("desc".to_string())),
+            ])
+            .unwrap()
+        );
+
+        assert!(order(vec![
+            ("orderBy", enums(&["name", "email"])),
+            ("orderDirection", enums(&["desc", "asc", "asc"])),
+        ])
+        .is_err());
+    }
+
     #[test]
     fn build_query_yields_default_range_if_none_is_present() {
         assert_eq!(
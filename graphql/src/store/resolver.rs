@@ -28,6 +28,8 @@ pub struct StoreResolver {
     pub(crate) block_ptr: Option<BlockPtr>,
     deployment: DeploymentHash,
     has_non_fatal_errors: bool,
+    /// The first block for which the deployment has data
+    earliest_block: BlockNumber,
     error_policy: ErrorPolicy,
     result_size: Arc<ResultSizeMetrics>,
 }
@@ -55,6 +57,7 @@ impl StoreResolver {
 
             // Checking for non-fatal errors does not work with subscriptions.
             has_non_fatal_errors: false,
+            earliest_block: 0,
             error_policy: ErrorPolicy::Deny,
             result_size,
         }
@@ -64,10 +67,13 @@ impl StoreResolver {
     /// by `bc`. Any calls to find objects will always return entities as
     /// of that block. Note that if `bc` is `BlockConstraint::Latest` we use
     /// whatever the latest block for the subgraph was when the resolver was
-    /// created
+    /// created. The earliest block that can be queried is taken from
+    /// `state` so that callers that already loaded it do not need to look
+    /// it up again
     pub async fn at_block(
         logger: &Logger,
         store: Arc<dyn QueryStore>,
+        state: &DeploymentState,
         subscription_manager: Arc<dyn SubscriptionManager>,
         bc: BlockConstraint,
         error_policy: ErrorPolicy,
@@ -83,6 +89,17 @@ impl StoreResolver {
         .map_err(|e| QueryExecutionError::Panic(e.to_string()))
         .and_then(|x| x)?; // Propagate panics.
 
+        // Queries can not go back farther than the history that pruning
+        // left behind
+        let earliest_block = state.earliest_block_number;
+        if (block_ptr.number as BlockNumber) < earliest_block {
+            return Err(QueryExecutionError::PrunedBlock(
+                deployment,
+                earliest_block,
+                block_ptr.number as BlockNumber,
+            ));
+        }
+
         let has_non_fatal_errors = store
             .has_non_fatal_errors(Some(block_ptr.block_number()))
             .await?;
@@ -94,6 +111,7 @@ impl StoreResolver {
             block_ptr: Some(block_ptr),
             deployment,
             has_non_fatal_errors,
+            earliest_block,
             error_policy,
             result_size,
         };
@@ -218,6 +236,10 @@ impl StoreResolver {
                 "hasIndexingErrors".to_string(),
                 r::Value::Boolean(self.has_non_fatal_errors),
             );
+            map.insert(
+                "earliestBlock".to_string(),
+                r::Value::Int(self.earliest_block.into()),
+            );
             map.insert(
                 "__typename".to_string(),
                 r::Value::String(META_FIELD_TYPE.to_string()),
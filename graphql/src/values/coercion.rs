@@ -1,8 +1,13 @@
 use crate::schema;
 use graph::prelude::s::{EnumType, InputValue, ScalarType, Type, TypeDefinition};
-use graph::prelude::{q, r, QueryExecutionError};
+use graph::prelude::{q, r, BigDecimal, BigInt, QueryExecutionError};
 use std::collections::BTreeMap;
 use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// The largest integer that can be represented exactly as a JSON number
+/// by clients that parse numbers as IEEE-754 doubles, `2^53 - 1`
+const MAX_SAFE_INTEGER: i64 = 9_007_199_254_740_991;
 
 /// A GraphQL value that can be coerced according to a type.
 pub trait MaybeCoercible<T> {
@@ -57,6 +62,112 @@ impl MaybeCoercible<ScalarType> for q::Value {
     }
 }
 
+/// Turn `s` in scientific notation like `1.5e18` into the integer it
+/// denotes. Returns `None` if `s` is malformed or does not denote an
+/// integer. This does not go through `BigDecimal` since that would round
+/// to `BigDecimal::MAX_SIGNFICANT_DIGITS`
+fn expand_scientific_int(s: &str) -> Option<BigInt> {
+    let (mantissa, exp) = s.split_once(|c| c == 'e' || c == 'E')?;
+    let exp = i64::from_str(exp).ok()?;
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa.strip_prefix('+').unwrap_or(mantissa)),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if (int.is_empty() && frac.is_empty())
+        || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    // The value is `digits * 10^shift`
+    let digits = format!("{}{}", int, frac);
+    let shift = exp.checked_sub(frac.len() as i64)?;
+    let digits = if shift >= 0 {
+        if shift > BigDecimal::MAX_EXP as i64 {
+            return None;
+        }
+        format!("{}{}", digits, "0".repeat(shift as usize))
+    } else {
+        let keep = digits.len().saturating_sub(shift.unsigned_abs() as usize);
+        if digits[keep..].bytes().any(|b| b != b'0') {
+            return None;
+        }
+        digits[..keep].to_string()
+    };
+    match digits.as_str() {
+        "" => Some(BigInt::from(0)),
+        digits => BigInt::from_str(&format!("{}{}", sign, digits)).ok(),
+    }
+}
+
+/// Accept numbers and strings in scientific notation like `1.5e18` for
+/// `BigInt` and `BigDecimal` inputs, as long as they can be turned into
+/// the string representation that the store expects without losing
+/// precision. Floats are only accepted for `BigInt` if they are integers
+/// that can not have been rounded when the client's input was parsed.
+///
+/// On error, the `value` is returned as `Err(value)`.
+fn coerce_big_number(value: r::Value, using_type: &ScalarType) -> Result<r::Value, r::Value> {
+    fn is_scientific(s: &str) -> bool {
+        s.contains(|c| c == 'e' || c == 'E')
+    }
+
+    match (using_type.name.as_str(), value) {
+        ("BigInt", r::Value::Float(f)) => {
+            if f.is_finite() && f.fract() == 0.0 && f.abs() <= MAX_SAFE_INTEGER as f64 {
+                Ok(r::Value::String((f as i64).to_string()))
+            } else {
+                Err(r::Value::Float(f))
+            }
+        }
+        ("BigInt", r::Value::String(s)) if is_scientific(&s) => match expand_scientific_int(&s) {
+            Some(int) => Ok(r::Value::String(int.to_string())),
+            None => Err(r::Value::String(s)),
+        },
+        ("BigDecimal", r::Value::Float(f)) if !f.is_finite() => Err(r::Value::Float(f)),
+        ("BigDecimal", r::Value::String(s)) if is_scientific(&s) => {
+            match BigDecimal::from_str(&s) {
+                Ok(d) => {
+                    let (_, scale) = d.as_bigint_and_exponent();
+                    if scale < -(BigDecimal::MAX_EXP as i64)
+                        || scale > -(BigDecimal::MIN_EXP as i64)
+                    {
+                        Err(r::Value::String(s))
+                    } else {
+                        Ok(r::Value::String(d.to_string()))
+                    }
+                }
+                Err(_) => Err(r::Value::String(s)),
+            }
+        }
+        (_, value) => Ok(value),
+    }
+}
+
+/// Turn `BigInt` and `BigDecimal` result values into JSON numbers if
+/// clients that parse JSON numbers as doubles get exactly the same value;
+/// all other values are returned unchanged
+pub(crate) fn big_number_as_json_number(value: r::Value, using_type: &ScalarType) -> r::Value {
+    match (using_type.name.as_str(), value) {
+        ("BigInt", r::Value::String(s)) => match i64::from_str(&s) {
+            Ok(n) if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&n) => r::Value::Int(n),
+            _ => r::Value::String(s),
+        },
+        ("BigDecimal", r::Value::String(s)) => {
+            let exact = f64::from_str(&s).ok().filter(|f| {
+                f.is_finite()
+                    && BigDecimal::from_str(&f.to_string()).ok() == BigDecimal::from_str(&s).ok()
+            });
+            match exact {
+                Some(f) => r::Value::Float(f),
+                None => r::Value::String(s),
+            }
+        }
+        (_, value) => value,
+    }
+}
+
 /// On error, the `value` is returned as `Err(value)`.
 fn coerce_to_definition<'a>(
     value: r::Value,
@@ -68,7 +179,7 @@ fn coerce_to_definition<'a>(
         TypeDefinition::Enum(t) => value.coerce_enum(t),
 
         // Try to coerce Scalar values
-        TypeDefinition::Scalar(t) => value.coerce_scalar(t),
+        TypeDefinition::Scalar(t) => coerce_big_number(value, t)?.coerce_scalar(t),
 
         // Try to coerce InputObject values
         TypeDefinition::InputObject(t) => match value {
@@ -195,18 +306,25 @@ pub(crate) fn coerce_value<'a>(
             Ok(r::Value::List(coerced_values))
         }
 
-        // Otherwise the list type is not coercible.
-        (Type::ListType(_), value) => Err(value),
+        // A value that is not a list is coerced into a list with just that
+        // value if it is coercible into the inner type
+        (Type::ListType(_), value) => {
+            let t = match ty {
+                Type::ListType(ty) => ty,
+                _ => unreachable!(),
+            };
+            Ok(r::Value::List(vec![coerce_value(value, t, resolver)?]))
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use graph::prelude::r::Value;
-    use graphql_parser::schema::{EnumType, EnumValue, ScalarType, TypeDefinition};
+    use graphql_parser::schema::{EnumType, EnumValue, ScalarType, Type, TypeDefinition};
     use graphql_parser::Pos;
 
-    use super::coerce_to_definition;
+    use super::{big_number_as_json_number, coerce_to_definition, coerce_value};
 
     #[test]
     fn coercion_using_enum_type_definitions_is_correct() {
@@ -249,6 +367,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn coercion_of_single_values_into_lists() {
+        let enum_type = TypeDefinition::Enum(EnumType {
+            name: "Enum".to_string(),
+            description: None,
+            directives: vec![],
+            position: Pos::default(),
+            values: vec![EnumValue {
+                name: "ValidVariant".to_string(),
+                position: Pos::default(),
+                description: None,
+                directives: vec![],
+            }],
+        });
+        let resolver = |_: &str| Some(&enum_type);
+        let list_type = Type::ListType(Box::new(Type::NonNullType(Box::new(Type::NamedType(
+            "Enum".to_string(),
+        )))));
+        let valid = || Value::Enum("ValidVariant".to_string());
+
+        assert_eq!(
+            coerce_value(Value::List(vec![valid(), valid()]), &list_type, &resolver),
+            Ok(Value::List(vec![valid(), valid()]))
+        );
+        assert_eq!(
+            coerce_value(valid(), &list_type, &resolver),
+            Ok(Value::List(vec![valid()]))
+        );
+        assert!(coerce_value(
+            Value::Enum("InvalidVariant".to_string()),
+            &list_type,
+            &resolver
+        )
+        .is_err());
+    }
+
     #[test]
     fn coercion_using_boolean_type_definitions_is_correct() {
         let bool_type = TypeDefinition::Scalar(ScalarType {
@@ -313,6 +467,14 @@ mod tests {
             Ok(Value::String("-5".to_string())),
         );
 
+        // We can coerce strings in scientific notation
+        assert_eq!(
+            coerce_to_definition(Value::String("1.5e3".to_string()), "", &resolver),
+            Ok(Value::String("1500".to_string()))
+        );
+        assert!(coerce_to_definition(Value::String("1.5e".to_string()), "", &resolver).is_err());
+        assert!(coerce_to_definition(Value::Float(f64::INFINITY), "", &resolver).is_err());
+
         // We don't support going from Value::Boolean -> TypeDefinition::Scalar(Boolean)
         assert!(coerce_to_definition(Value::Boolean(true), "", &resolver).is_err());
         assert!(coerce_to_definition(Value::Boolean(false), "", &resolver).is_err());
@@ -393,6 +555,75 @@ mod tests {
             coerce_to_definition(Value::Int((-1234 as i32).into()), "", &resolver,),
             Ok(Value::String("-1234".to_string()))
         );
+
+        // And from Value::Float if it is an integer that is known to be exact
+        assert_eq!(
+            coerce_to_definition(Value::Float(1e15), "", &resolver),
+            Ok(Value::String("1000000000000000".to_string()))
+        );
+        assert!(coerce_to_definition(Value::Float(1.5), "", &resolver).is_err());
+        assert!(coerce_to_definition(Value::Float(1e18), "", &resolver).is_err());
+        assert!(coerce_to_definition(Value::Float(f64::NAN), "", &resolver).is_err());
+
+        // And from strings in scientific notation that denote an integer
+        assert_eq!(
+            coerce_to_definition(Value::String("1e18".to_string()), "", &resolver),
+            Ok(Value::String("1000000000000000000".to_string()))
+        );
+        assert_eq!(
+            coerce_to_definition(Value::String("-1.5E3".to_string()), "", &resolver),
+            Ok(Value::String("-1500".to_string()))
+        );
+        assert_eq!(
+            coerce_to_definition(Value::String("1200e-2".to_string()), "", &resolver),
+            Ok(Value::String("12".to_string()))
+        );
+        assert_eq!(
+            coerce_to_definition(
+                Value::String("1234567890123456789012345678901234567890e2".to_string()),
+                "",
+                &resolver
+            ),
+            Ok(Value::String(
+                "123456789012345678901234567890123456789000".to_string()
+            ))
+        );
+        assert!(coerce_to_definition(Value::String("1.5e0".to_string()), "", &resolver).is_err());
+        assert!(coerce_to_definition(Value::String("1e".to_string()), "", &resolver).is_err());
+        assert!(coerce_to_definition(Value::String("e5".to_string()), "", &resolver).is_err());
+    }
+
+    #[test]
+    fn big_numbers_as_json_numbers() {
+        let big_int = ScalarType::new("BigInt".to_string());
+        let big_decimal = ScalarType::new("BigDecimal".to_string());
+
+        assert_eq!(
+            big_number_as_json_number(Value::String("-1234".to_string()), &big_int),
+            Value::Int(-1234)
+        );
+        assert_eq!(
+            big_number_as_json_number(Value::String("9007199254740992".to_string()), &big_int),
+            Value::String("9007199254740992".to_string())
+        );
+        assert_eq!(
+            big_number_as_json_number(Value::String("23.7".to_string()), &big_decimal),
+            Value::Float(23.7)
+        );
+        assert_eq!(
+            big_number_as_json_number(
+                Value::String("0.12345678901234567890".to_string()),
+                &big_decimal
+            ),
+            Value::String("0.12345678901234567890".to_string())
+        );
+        assert_eq!(
+            big_number_as_json_number(
+                Value::String("0x21f".to_string()),
+                &ScalarType::new("Bytes".to_string())
+            ),
+            Value::String("0x21f".to_string())
+        );
     }
 
     #[test]
@@ -9,8 +9,8 @@ use graph::prelude::{
     QueryExecutionError, QueryResult, Schema,
 };
 use graph_graphql::prelude::{
-    a, api_schema, execute_query, ExecutionContext, Query as PreparedQuery, QueryExecutionOptions,
-    Resolver,
+    a, api_schema, execute_query, warm_introspection, ExecutionContext, Query as PreparedQuery,
+    QueryExecutionOptions, Resolver,
 };
 use test_store::LOAD_MANAGER;
 
@@ -554,6 +554,11 @@ fn expected_mock_schema_introspection() -> r::Value {
 
 /// Execute an introspection query.
 async fn introspection_query(schema: Schema, query: &str) -> QueryResult {
+    let schema = Arc::new(ApiSchema::from_api_schema(schema).unwrap());
+    api_introspection_query(schema, query).await
+}
+
+async fn api_introspection_query(schema: Arc<ApiSchema>, query: &str) -> QueryResult {
     // Create the query
     let query = Query::new(
         graphql_parser::parse_query(query).unwrap().into_static(),
@@ -570,7 +575,6 @@ async fn introspection_query(schema: Schema, query: &str) -> QueryResult {
         load_manager: LOAD_MANAGER.clone(),
     };
 
-    let schema = Arc::new(ApiSchema::from_api_schema(schema).unwrap());
     let result = match PreparedQuery::new(&logger, schema, None, query, None, 100) {
         Ok(query) => Ok(Arc::try_unwrap(execute_query(query, None, None, options).await).unwrap()),
         Err(e) => Err(e),
@@ -1279,3 +1283,31 @@ async fn introspection_possible_types() {
         )])
     )
 }
+
+#[tokio::test]
+async fn introspection_objects_are_cached_with_schema() {
+    const QUERY: &str = "query { __type(name: \"User\") { name fields { name } } }";
+
+    let expected = introspection_query(mock_schema(), QUERY)
+        .await
+        .to_result()
+        .unwrap()
+        .unwrap();
+
+    let schema = Arc::new(ApiSchema::from_api_schema(mock_schema()).unwrap());
+    assert!(!schema.has_introspection_objects());
+    warm_introspection(&schema);
+    assert!(schema.has_introspection_objects());
+
+    // Queries against a schema that was warmed up, and repeated queries
+    // that use the cached objects, give the same results as the first
+    // query against a fresh schema
+    for _ in 0..2 {
+        let response = api_introspection_query(schema.clone(), QUERY)
+            .await
+            .to_result()
+            .unwrap()
+            .unwrap();
+        assert_eq!(expected, response);
+    }
+}
@@ -1666,6 +1666,77 @@ fn query_at_block() {
     })
 }
 
+#[test]
+fn query_block_range() {
+    use test_store::block_store::BLOCK_TWO;
+
+    run_test_sequentially(|store| async move {
+        let deployment = setup(store.as_ref());
+
+        // Rename m1 in block 2 so that it has two versions
+        let m1 = Entity::from(vec![
+            ("id", Value::from("m1")),
+            ("name", Value::from("Johnny")),
+            ("mainBand", Value::from("b1")),
+            (
+                "bands",
+                Value::List(vec![Value::from("b1"), Value::from("b2")]),
+            ),
+        ]);
+        transact_entity_operations(
+            &STORE.subgraph_store(),
+            &deployment,
+            BLOCK_TWO.block_ptr(),
+            vec![EntityOperation::Set {
+                key: EntityKey::data(
+                    deployment.hash.clone(),
+                    "Musician".to_owned(),
+                    "m1".to_owned(),
+                ),
+                data: m1,
+            }],
+        )
+        .unwrap();
+
+        let query = "query { musicians(blockRange: { from: 0, to: 2 }, where: { id_in: [\"m1\", \"m3\"] }) { \
+                       id name _validFrom _validTo } }";
+        let query = graphql_parser::parse_query(query)
+            .expect("invalid test query")
+            .into_static();
+        let result = execute_query_document(&deployment.hash, query).await;
+        let exp = object! {
+            musicians: vec![
+                object! { id: "m1", name: "John", _validFrom: 0, _validTo: 2 },
+                object! { id: "m1", name: "Johnny", _validFrom: 2, _validTo: r::Value::Null },
+                object! { id: "m3", name: "Tom", _validFrom: 1, _validTo: r::Value::Null },
+            ]
+        };
+        assert_eq!(extract_data!(result), Some(exp));
+
+        // Only the versions that overlap the range are returned
+        let query = "query { musicians(blockRange: { from: 2, to: 2 }, where: { id: \"m1\" }) { \
+                       id name _validFrom _validTo } }";
+        let query = graphql_parser::parse_query(query)
+            .expect("invalid test query")
+            .into_static();
+        let result = execute_query_document(&deployment.hash, query).await;
+        let exp = object! {
+            musicians: vec![
+                object! { id: "m1", name: "Johnny", _validFrom: 2, _validTo: r::Value::Null },
+            ]
+        };
+        assert_eq!(extract_data!(result), Some(exp));
+
+        // The range must contain blocks the subgraph has indexed
+        let query = "query { musicians(blockRange: { from: 3, to: 5 }) { id } }";
+        let query = graphql_parser::parse_query(query)
+            .expect("invalid test query")
+            .into_static();
+        let result = execute_query_document(&deployment.hash, query).await;
+        assert!(result.has_errors());
+    })
+}
+
 #[test]
 fn query_at_block_with_vars() {
     run_test_sequentially(|store| async move {
@@ -1,4 +1,6 @@
-use std::{collections::HashMap, env, num::ParseIntError, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap, env, num::ParseIntError, path::PathBuf, sync::Arc, time::Duration,
+};
 
 use config::PoolSize;
 use git_testament::{git_testament, render_testament};
@@ -6,20 +8,21 @@ use graph::{data::graphql::effort::LoadManager, prelude::chrono, prometheus::Reg
 use graph_core::MetricsRegistry;
 use graph_graphql::prelude::GraphQlRunner;
 use lazy_static::lazy_static;
-use structopt::StructOpt;
+use structopt::{clap::Shell, StructOpt};
 
 use graph::{
     log::logger,
-    prelude::{info, o, slog, tokio, Logger, NodeId},
+    prelude::{info, o, slog, tokio, BlockNumber, Logger, NodeId},
 };
 use graph_node::{manager::PanicSubscriptionManager, store_builder::StoreBuilder};
 use graph_store_postgres::{
-    connection_pool::ConnectionPool, BlockStore, Shard, Store, SubgraphStore, SubscriptionManager,
-    PRIMARY_SHARD,
+    command_support::catalog::StorageParams, connection_pool::ConnectionPool, BlockStore, Shard,
+    Store, SubgraphStore, SubscriptionManager, PRIMARY_SHARD,
 };
 
 use graph_node::config::{self, Config as Cfg};
 use graph_node::manager::commands;
+use graph_node::manager::display::OutputFormat;
 
 git_testament!(TESTAMENT);
 
@@ -49,7 +52,7 @@ pub struct Opt {
         env = "GRAPH_NODE_CONFIG",
         help = "the name of the configuration file\n"
     )]
-    pub config: String,
+    pub config: Option<String>,
     #[structopt(
         long,
         default_value = "default",
@@ -64,6 +67,14 @@ pub struct Opt {
         help = "the size for connection pools. Set to 0\n to use pool size from configuration file\n corresponding to NODE_ID"
     )]
     pub pool_size: u32,
+    #[structopt(
+        long,
+        global = true,
+        default_value = "text",
+        possible_values = &OutputFormat::VARIANTS,
+        help = "print results as human-readable text or as JSON\n"
+    )]
+    pub output: OutputFormat,
     #[structopt(subcommand)]
     pub cmd: Command,
 }
@@ -98,9 +109,18 @@ pub enum Command {
     /// with `remove`
     Unused(UnusedCommand),
     /// Remove a named subgraph
+    ///
+    /// With `--drop`, also drop the data of the subgraph's deployments that
+    /// are not used by any other subgraph. Deployments are dropped in
+    /// parallel, and dropping is retried if it can not get the locks it
+    /// needs because of long-running queries
     Remove {
         /// The name of the subgraph to remove
         name: String,
+        /// Drop the data of deployments that become unused and report how
+        /// much space was reclaimed
+        #[structopt(long)]
+        drop: bool,
     },
     /// Create a subgraph name
     Create {
@@ -182,6 +202,29 @@ pub enum Command {
 
     /// Manage database indexes
     Index(IndexCommand),
+
+    /// Manage deployment labels
+    ///
+    /// Labels are attached to deployment hashes and can be set before a
+    /// deployment is created so that placement rules can match on them
+    Label(LabelCommand),
+
+    /// Back up and restore individual deployments, and manage read-only
+    /// SQL access to them
+    Deployment(DeploymentCommand),
+
+    /// Estimate the effect of pruning the history of deployments
+    Prune(PruneCommand),
+
+    /// Print a completion script for `shell` to standard output
+    ///
+    /// For example, `graphman completions bash > /etc/bash_completion.d/graphman`.
+    /// This command does not need a configuration file
+    Completions {
+        /// The shell for which to generate completions
+        #[structopt(possible_values = &Shell::variants())]
+        shell: Shell,
+    },
 }
 
 impl Command {
@@ -234,6 +277,9 @@ pub enum ConfigCommand {
         name: String,
         /// The network the subgraph indexes
         network: String,
+        /// Deployment labels in the form `key=value`
+        #[structopt(long = "label", short = "l")]
+        labels: Vec<String>,
     },
     /// Information about the size of database pools
     Pools {
@@ -267,14 +313,20 @@ pub enum CopyCommand {
     /// subgraph will start as a copy of all blocks of `src` that are
     /// `offset` behind the current subgraph head of `src`. The offset
     /// should be chosen such that only final blocks are copied
+    ///
+    /// The source keeps indexing and answering queries while the copy is
+    /// made. Once the copy has caught up with the source, use `graphman
+    /// copy activate` to switch queries over to it, for example, to move a
+    /// deployment to a different shard without downtime
     Create {
         /// How far behind `src` subgraph head to copy
         #[structopt(long, short, default_value = "200")]
         offset: u32,
-        /// The IPFS hash of the source deployment
-        src: String,
         /// The name of the database shard into which to copy
+        #[structopt(long, short)]
         shard: String,
+        /// The IPFS hash of the source deployment
+        src: String,
         /// The name of the node that should index the copy
         node: String,
         /// The shard of the `src` subgraph in case that is ambiguous
@@ -284,8 +336,13 @@ pub enum CopyCommand {
     ///
     /// This will route queries to that specific copy (with some delay); the
     /// previously active copy will become inactive. Only copies that have
-    /// progressed at least as far as the original should be activated.
+    /// progressed at least as far as the original can be activated unless
+    /// `--force` is given. The switch happens in one transaction so that
+    /// queries never see the deployment without an active copy
     Activate {
+        /// Activate the copy even if it is behind the active copy
+        #[structopt(long, short)]
+        force: bool,
         /// The IPFS hash of the deployment to activate
         deployment: String,
         /// The name of the database shard that holds the copy
@@ -323,6 +380,66 @@ pub enum ChainCommand {
     /// There must be no deployments using that chain. If there are, the
     /// subgraphs and/or deployments using the chain must first be removed
     Remove { name: String },
+    /// Manage the backfill of historical blocks for a chain
+    Backfill(BackfillCommand),
+    /// Clear the Firehose cursors that are stored for a chain
+    ///
+    /// Use this to recover from a corrupted cursor. Without a head cursor,
+    /// the chain head stream starts from the current head block; without a
+    /// backfill cursor, the backfill restarts right after the blocks that
+    /// have already been backfilled
+    ResetCursor {
+        /// Clear the cursor of the chain head stream
+        #[structopt(long, required_unless = "backfill")]
+        head: bool,
+        /// Clear the cursor of the backfill stream
+        #[structopt(long)]
+        backfill: bool,
+        /// Do not ask for confirmation
+        #[structopt(long, short)]
+        force: bool,
+        /// The name of the chain
+        name: String,
+    },
+    /// Remove old blocks from the block cache of a chain
+    ///
+    /// Removes all blocks that are more than `keep` blocks behind the chain
+    /// head, except for the genesis block, the blocks that deployments
+    /// have as their head block and the `ETHEREUM_REORG_THRESHOLD` blocks
+    /// before them. Removed blocks are fetched from the chain
+    /// again when they are needed
+    Truncate {
+        /// The number of recent blocks to keep
+        #[structopt(long)]
+        keep: BlockNumber,
+        /// Do not ask for confirmation
+        #[structopt(long, short)]
+        force: bool,
+        /// The name of the chain
+        name: String,
+    },
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum BackfillCommand {
+    /// Set the block number up to which historical blocks are backfilled
+    ///
+    /// The target takes effect the next time the block ingestor for the
+    /// chain is started and overrides GRAPH_FIREHOSE_BACKFILL_TARGET
+    SetTarget {
+        #[structopt(long, help = "clear the target instead of setting it\n")]
+        clear: bool,
+        /// The name of the chain
+        name: String,
+        /// The block number up to which to backfill
+        #[structopt(required_unless = "clear")]
+        block: Option<i32>,
+    },
+    /// Show the backfill target, cursor, and progress of a chain
+    Status { name: String },
+    /// Reset the backfill progress so that the next backfill starts again
+    /// from the genesis block
+    Reset { name: String },
 }
 
 #[derive(Clone, Debug, StructOpt)]
@@ -346,6 +463,9 @@ pub enum StatsCommand {
     /// table name is passed, perform a full count of entities and versions
     /// in that table, which can be very slow, but is needed since the
     /// statistics based data can be off by an order of magnitude.
+    ///
+    /// Tables that have many versions for comparatively few entities are
+    /// marked as candidates for the account-like flag.
     Show {
         /// The namespace of the deployment in the form `sgdNNNN`
         nsp: String,
@@ -359,6 +479,48 @@ pub enum StatsCommand {
         /// The name of the Entity to ANALYZE, in camel case
         entity: String,
     },
+    /// Set the fillfactor and autovacuum parameters of tables
+    ///
+    /// Without any of the parameter options, the parameters for each table
+    /// are chosen based on how the table has been used so far: tables that
+    /// mostly see inserts are treated as append-only, all others as
+    /// mutable. With parameter options, exactly those parameters are set.
+    /// Changing the fillfactor only affects pages written afterwards.
+    SetStorageParams {
+        /// Reset the parameters to the database defaults
+        #[structopt(long, conflicts_with_all = &["fillfactor", "vacuum_scale_factor", "analyze_scale_factor"])]
+        reset: bool,
+        /// The fillfactor in percent
+        #[structopt(long)]
+        fillfactor: Option<i32>,
+        /// The `autovacuum_vacuum_scale_factor`
+        #[structopt(long)]
+        vacuum_scale_factor: Option<f64>,
+        /// The `autovacuum_analyze_scale_factor`
+        #[structopt(long)]
+        analyze_scale_factor: Option<f64>,
+        /// The namespace of the deployment in the form `sgdNNNN`
+        nsp: String,
+        /// The name of the table to change; all tables if omitted
+        table: Option<String>,
+    },
+    /// Set how large values of a column are stored
+    ///
+    /// Attributes declared with `@storage(external: true)` are stored as
+    /// `external`, i.e., out of line and without compression, all others as
+    /// `extended`, which compresses large values. Compression saves space
+    /// but makes computing index prefixes more expensive. Only affects
+    /// values written afterwards.
+    SetColumnStorage {
+        /// The namespace of the deployment in the form `sgdNNNN`
+        nsp: String,
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: String,
+        /// One of `plain`, `main`, `external`, or `extended`
+        storage: String,
+    },
 }
 
 #[derive(Clone, Debug, StructOpt)]
@@ -369,7 +531,9 @@ pub enum IndexCommand {
     /// names must be declared the in camel case, following GraphQL conventions.
     ///
     /// The index will have its validity checked after the operation and will be dropped if it is
-    /// invalid.
+    /// invalid. Combinations of method and fields that can not work are rejected before the index
+    /// is built: `gin` indexes need list or fulltext fields, `gist` indexes must include
+    /// `block_range`, and `hash` and `spgist` indexes can only cover one field.
     ///
     /// This command may be time-consuming.
     Create {
@@ -378,7 +542,8 @@ pub enum IndexCommand {
         /// The Entity name, in camel case.
         #[structopt(empty_values = false)]
         entity: String,
-        /// The Field names, in camel case.
+        /// The Field names, in camel case. Use `block_range` for the block
+        /// range of entity versions
         #[structopt(min_values = 1, required = true)]
         fields: Vec<String>,
         /// The index method. Defaults to `btree`.
@@ -388,12 +553,185 @@ pub enum IndexCommand {
         )]
         method: String,
     },
+    /// List the indexes of a deployment
+    List {
+        /// The namespace of the deployment in the form `sgdNNNN`
+        nsp: String,
+        /// Only list the indexes of this table
+        table: Option<String>,
+    },
+    /// Recommend indexes to drop or to create for a deployment
+    ///
+    /// The advice is based on the statistics that Postgres keeps about how
+    /// often tables and indexes are scanned. Attribute indexes that have
+    /// never been used are candidates for dropping; columns without an index
+    /// in large tables that are mostly scanned sequentially are candidates
+    /// for `index create`. The statistics are per database, and should be
+    /// consulted on the database that serves queries
+    Advise {
+        /// The id of the deployment
+        id: String,
+    },
+    /// Drop an index of a deployment
+    ///
+    /// The index is dropped concurrently, so that the deployment can still
+    /// be queried and indexed while it is being dropped. Use `index list`
+    /// to find the names of the indexes of a deployment
+    Drop {
+        /// The id of the deployment
+        id: String,
+        /// The name of the index
+        #[structopt(empty_values = false)]
+        index_name: String,
+    },
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum PruneCommand {
+    /// Estimate how much pruning a deployment would remove
+    ///
+    /// For each table of the deployment, estimate how many entity versions
+    /// are only needed for queries at blocks that are more than `history`
+    /// blocks behind the deployment's current block, and how much disk
+    /// space removing them would free up. The estimate is based on the
+    /// statistics that Postgres keeps, which are refreshed when a table is
+    /// analyzed, and requires Postgres 14 or later. No data is changed
+    Preview {
+        /// The deployment hash `Qm..`
+        deployment: String,
+        /// The number of blocks of history to keep
+        history: BlockNumber,
+        /// The shard of the deployment if `deployment` itself is ambiguous
+        #[structopt(long)]
+        shard: Option<String>,
+    },
+    /// Remove entity versions that are only needed for old blocks
+    ///
+    /// Remove all entity versions that are only needed for queries at
+    /// blocks that are more than `history` blocks behind the deployment's
+    /// current block, and rebuild the indexes of the deployment's tables.
+    /// Afterwards, queries for blocks before that are rejected. Pruning
+    /// can take a long time; it is safe to interrupt it and to run it
+    /// again. Rebuilding indexes requires Postgres 12 or later
+    Run {
+        /// The deployment hash `Qm..`
+        deployment: String,
+        /// The number of blocks of history to keep; must be at least the
+        /// reorg threshold
+        history: BlockNumber,
+        /// The shard of the deployment if `deployment` itself is ambiguous
+        #[structopt(long)]
+        shard: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum LabelCommand {
+    /// Set a label on a deployment, replacing any previous value
+    Set {
+        /// The deployment hash `Qm..`
+        deployment: String,
+        /// The name of the label
+        key: String,
+        /// The value of the label
+        value: String,
+    },
+    /// Remove a label from a deployment
+    Remove {
+        /// The deployment hash `Qm..`
+        deployment: String,
+        /// The name of the label
+        key: String,
+    },
+    /// List the labels of a deployment
+    List {
+        /// The deployment hash `Qm..`
+        deployment: String,
+    },
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum DeploymentCommand {
+    /// Write a logical backup of a deployment into a directory
+    ///
+    /// The backup contains the deployment's entities, metadata, dynamic
+    /// data sources and Firehose cursor. It is taken from a consistent
+    /// snapshot, and the deployment can keep indexing while it is written
+    Dump {
+        /// The deployment hash `Qm..`
+        deployment: String,
+        /// The directory into which to write the backup
+        directory: PathBuf,
+        /// The shard of the deployment if `deployment` itself is ambiguous
+        #[structopt(long)]
+        shard: Option<String>,
+    },
+    /// Recreate a deployment from a backup written with `dump`
+    ///
+    /// The deployment is created in `shard`, which must not already
+    /// contain it. It only becomes the active copy if there is no other
+    /// active copy of the deployment. With `--node`, the deployment is
+    /// assigned to that node and resumes indexing where the backup left off
+    Restore {
+        /// The directory that contains the backup
+        directory: PathBuf,
+        /// The name of the database shard into which to restore
+        #[structopt(long, default_value = "primary")]
+        shard: String,
+        /// The name of the node that should index the restored deployment
+        #[structopt(long)]
+        node: Option<String>,
+    },
+    /// Give analysts read-only SQL access to a deployment
+    ///
+    /// Creates the role `<namespace>_reader`, which can not log in, and
+    /// lets it read the tables of the deployment and, through row level
+    /// security, only the deployment's own rows in the shared metadata
+    /// tables. Analysts get access by being granted that role
+    GrantReader {
+        /// The deployment hash `Qm..`
+        deployment: String,
+        /// The shard of the deployment if `deployment` itself is ambiguous
+        #[structopt(long)]
+        shard: Option<String>,
+        /// Only print the SQL instead of running it
+        #[structopt(long)]
+        print: bool,
+    },
+    /// Drop the read-only role of a deployment
+    RevokeReader {
+        /// The deployment hash `Qm..`
+        deployment: String,
+        /// The shard of the deployment if `deployment` itself is ambiguous
+        #[structopt(long)]
+        shard: Option<String>,
+        /// Only print the SQL instead of running it
+        #[structopt(long)]
+        print: bool,
+    },
+    /// Bind a deployment to the chain that is now configured for its network
+    ///
+    /// Deployments remember the genesis block of the chain they were
+    /// created against and refuse to start if a different chain has been
+    /// configured under the same name since. This command binds all
+    /// copies of the deployment to the current chain. The deployment has
+    /// to be restarted, e.g., with `unassign` and `reassign`, afterwards
+    Rebind {
+        /// The deployment hash `Qm..`
+        deployment: String,
+        /// The shard of the deployment if `deployment` itself is ambiguous
+        #[structopt(long)]
+        shard: Option<String>,
+        /// Do not ask for confirmation
+        #[structopt(long, short)]
+        force: bool,
+    },
 }
 
 impl From<Opt> for config::Opt {
     fn from(opt: Opt) -> Self {
         let mut config_opt = config::Opt::default();
-        config_opt.config = Some(opt.config);
+        config_opt.config = opt.config;
         config_opt.store_connection_pool_size = 5;
         config_opt
     }
@@ -536,6 +874,15 @@ impl Context {
 async fn main() {
     let opt = Opt::from_args();
 
+    if let Command::Completions { shell } = opt.cmd {
+        Opt::clap().gen_completions_to("graphman", shell, &mut std::io::stdout());
+        return;
+    }
+    if opt.config.is_none() {
+        eprintln!("error: a configuration file must be set with --config or GRAPH_NODE_CONFIG");
+        std::process::exit(1);
+    }
+
     // Set up logger
     let logger = match env::var_os("GRAPH_LOG") {
         Some(_) => logger(false),
@@ -591,14 +938,16 @@ async fn main() {
             } else {
                 (ctx.primary_pool(), None)
             };
-            commands::info::run(primary, store, name, current, pending, used)
+            commands::info::run(primary, store, name, current, pending, used, opt.output)
         }
         Unused(cmd) => {
             let store = ctx.subgraph_store();
             use UnusedCommand::*;
 
             match cmd {
-                List { existing } => commands::unused_deployments::list(store, existing),
+                List { existing } => {
+                    commands::unused_deployments::list(store, existing, opt.output)
+                }
                 Record => commands::unused_deployments::record(store),
                 Remove {
                     count,
@@ -615,14 +964,16 @@ async fn main() {
             use ConfigCommand::*;
 
             match cmd {
-                Place { name, network } => {
-                    commands::config::place(&ctx.config.deployment, &name, &network)
-                }
+                Place {
+                    name,
+                    network,
+                    labels,
+                } => commands::config::place(&ctx.config.deployment, &name, &network, labels),
                 Check { print } => commands::config::check(&ctx.config, print),
                 Pools { nodes, shard } => commands::config::pools(&ctx.config, nodes, shard),
             }
         }
-        Remove { name } => commands::remove::run(ctx.subgraph_store(), name),
+        Remove { name, drop } => commands::remove::run(ctx.subgraph_store(), name, drop),
         Create { name } => commands::create::run(ctx.subgraph_store(), name),
         Unassign { id, shard } => {
             commands::assign::unassign(logger.clone(), ctx.subgraph_store(), id, shard).await
@@ -694,9 +1045,11 @@ async fn main() {
                     offset,
                     src_shard,
                 } => commands::copy::create(ctx.store(), src, src_shard, shard, node, offset).await,
-                Activate { deployment, shard } => {
-                    commands::copy::activate(ctx.subgraph_store(), deployment, shard)
-                }
+                Activate {
+                    deployment,
+                    shard,
+                    force,
+                } => commands::copy::activate(ctx.subgraph_store(), deployment, shard, force),
                 List => commands::copy::list(ctx.pools()),
                 Status { dst } => commands::copy::status(ctx.pools(), dst),
             }
@@ -725,6 +1078,31 @@ async fn main() {
                     let (block_store, primary) = ctx.block_store_and_primary_pool();
                     commands::chain::remove(primary, block_store, name)
                 }
+                Backfill(cmd) => {
+                    use BackfillCommand::*;
+                    let (block_store, _) = ctx.block_store_and_primary_pool();
+                    match cmd {
+                        SetTarget { clear, name, block } => {
+                            let block = if clear { None } else { block };
+                            commands::chain::backfill_set_target(block_store, name, block)
+                        }
+                        Status { name } => commands::chain::backfill_status(block_store, name),
+                        Reset { name } => commands::chain::backfill_reset(block_store, name),
+                    }
+                }
+                ResetCursor {
+                    head,
+                    backfill,
+                    force,
+                    name,
+                } => {
+                    let (block_store, _) = ctx.block_store_and_primary_pool();
+                    commands::chain::reset_cursor(block_store, name, head, backfill, force)
+                }
+                Truncate { keep, force, name } => {
+                    let store = ctx.store();
+                    commands::chain::truncate(store, name, keep, force)
+                }
             }
         }
         Stats(cmd) => {
@@ -733,12 +1111,33 @@ async fn main() {
                 AccountLike { clear, table } => {
                     commands::stats::account_like(ctx.pools(), clear, table)
                 }
-                Show { nsp, table } => commands::stats::show(ctx.pools(), nsp, table),
+                Show { nsp, table } => commands::stats::show(ctx.pools(), nsp, table, opt.output),
                 Analyze { id, entity } => {
                     let store = ctx.store();
                     let subgraph_store = store.subgraph_store();
                     commands::stats::analyze(subgraph_store, id, entity).await
                 }
+                SetStorageParams {
+                    reset,
+                    fillfactor,
+                    vacuum_scale_factor,
+                    analyze_scale_factor,
+                    nsp,
+                    table,
+                } => {
+                    let params = StorageParams {
+                        fillfactor,
+                        autovacuum_vacuum_scale_factor: vacuum_scale_factor,
+                        autovacuum_analyze_scale_factor: analyze_scale_factor,
+                    };
+                    commands::stats::set_storage_params(ctx.pools(), nsp, table, params, reset)
+                }
+                SetColumnStorage {
+                    nsp,
+                    table,
+                    column,
+                    storage,
+                } => commands::stats::set_column_storage(ctx.pools(), nsp, table, column, storage),
             }
         }
         Index(cmd) => {
@@ -754,8 +1153,92 @@ async fn main() {
                     let subgraph_store = store.subgraph_store();
                     commands::index::create(subgraph_store, id, entity, fields, method).await
                 }
+                List { nsp, table } => commands::index::list(ctx.pools(), nsp, table, opt.output),
+                Advise { id } => {
+                    let store = ctx.store();
+                    let subgraph_store = store.subgraph_store();
+                    commands::index::advise(subgraph_store, id, opt.output).await
+                }
+                Drop { id, index_name } => {
+                    let store = ctx.store();
+                    let subgraph_store = store.subgraph_store();
+                    commands::index::drop(subgraph_store, id, index_name).await
+                }
+            }
+        }
+        Label(cmd) => {
+            use LabelCommand::*;
+            let primary = ctx.primary_pool();
+            match cmd {
+                Set {
+                    deployment,
+                    key,
+                    value,
+                } => commands::label::set(primary, deployment, key, value),
+                Remove { deployment, key } => commands::label::remove(primary, deployment, key),
+                List { deployment } => commands::label::list(primary, deployment),
+            }
+        }
+        Deployment(cmd) => {
+            use DeploymentCommand::*;
+            match cmd {
+                Dump {
+                    deployment,
+                    directory,
+                    shard,
+                } => commands::dump::dump(ctx.subgraph_store(), deployment, shard, directory),
+                Restore {
+                    directory,
+                    shard,
+                    node,
+                } => commands::dump::restore(ctx.subgraph_store(), directory, shard, node),
+                GrantReader {
+                    deployment,
+                    shard,
+                    print,
+                } => commands::reader::grant(ctx.subgraph_store(), deployment, shard, print),
+                RevokeReader {
+                    deployment,
+                    shard,
+                    print,
+                } => commands::reader::revoke(ctx.subgraph_store(), deployment, shard, print),
+                Rebind {
+                    deployment,
+                    shard,
+                    force,
+                } => commands::rebind::run(ctx.subgraph_store(), deployment, shard, force),
+            }
+        }
+        Prune(cmd) => {
+            use PruneCommand::*;
+            match cmd {
+                Preview {
+                    deployment,
+                    history,
+                    shard,
+                } => {
+                    let (store, pools) = ctx.store_and_pools();
+                    commands::prune::preview(
+                        store.subgraph_store(),
+                        pools,
+                        deployment,
+                        shard,
+                        history,
+                    )
+                }
+                Run {
+                    deployment,
+                    history,
+                    shard,
+                } => {
+                    let (store, pools) = ctx.store_and_pools();
+                    commands::prune::run(store.subgraph_store(), pools, deployment, shard, history)
+                }
             }
         }
+        Completions { .. } => {
+            unreachable!("completions are generated before connecting to the database")
+        }
     };
     if let Err(e) = result {
         die!("error: {}", e)
@@ -38,14 +38,9 @@ enum ProviderNetworkStatus {
 /// continue regardless.
 const NET_VERSION_WAIT_TIME: Duration = Duration::from_secs(30);
 
-lazy_static! {
-    // Default to an Ethereum reorg threshold to 50 blocks
-    pub static ref REORG_THRESHOLD: BlockNumber = env::var("ETHEREUM_REORG_THRESHOLD")
-        .ok()
-        .map(|s| BlockNumber::from_str(&s)
-            .unwrap_or_else(|_| panic!("failed to parse env var ETHEREUM_REORG_THRESHOLD")))
-        .unwrap_or(50);
+pub use graph::blockchain::REORG_THRESHOLD;
 
+lazy_static! {
     // Default to an ancestor count of 50 blocks
     pub static ref ANCESTOR_COUNT: BlockNumber = env::var("ETHEREUM_ANCESTOR_COUNT")
         .ok()
@@ -207,6 +202,7 @@ pub async fn create_firehose_networks(
                     &provider.label,
                     &firehose.url,
                     firehose.token.clone(),
+                    firehose.connection_settings()?,
                 )
                 .await?;
 
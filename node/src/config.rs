@@ -1,6 +1,7 @@
 use graph::{
     anyhow::Error,
     blockchain::BlockchainKind,
+    firehose::{self, FirehoseConnectionSettings},
     prelude::{
         anyhow::{anyhow, bail, Context, Result},
         info,
@@ -20,6 +21,7 @@ use std::fs::read_to_string;
 use std::{
     collections::{BTreeMap, BTreeSet},
     fmt,
+    time::Duration,
 };
 use url::Url;
 
@@ -385,6 +387,17 @@ impl ChainSection {
         for (_, chain) in self.chains.iter_mut() {
             chain.validate()?
         }
+
+        // Every name, whether the chain's own or an alias, must refer to
+        // exactly one chain
+        let mut names = BTreeSet::new();
+        for (name, chain) in &self.chains {
+            for name in std::iter::once(name).chain(chain.aliases.iter()) {
+                if !names.insert(name) {
+                    bail!("the chain name or alias `{}` is used more than once", name);
+                }
+            }
+        }
         Ok(())
     }
 
@@ -463,6 +476,8 @@ impl ChainSection {
                     shard: PRIMARY_SHARD.to_string(),
                     protocol: BlockchainKind::Ethereum,
                     providers: vec![],
+                    fork_steps: default_fork_steps(),
+                    aliases: vec![],
                 });
                 entry.providers.push(provider);
             }
@@ -478,16 +493,35 @@ pub struct Chain {
     pub protocol: BlockchainKind,
     #[serde(rename = "provider")]
     pub providers: Vec<Provider>,
+    /// The fork steps the Firehose block ingestor requests when following
+    /// the chain head
+    #[serde(default = "default_fork_steps")]
+    pub fork_steps: Vec<ForkStep>,
+    /// Other names under which the chain is known. Manifests can use any
+    /// of them, but deployments always record the chain's own name
+    #[serde(default)]
+    pub aliases: Vec<String>,
 }
 
 fn default_blockchain_kind() -> BlockchainKind {
     BlockchainKind::Ethereum
 }
 
+fn default_fork_steps() -> Vec<ForkStep> {
+    vec![ForkStep::New, ForkStep::Undo]
+}
+
 impl Chain {
     fn validate(&mut self) -> Result<()> {
         // `Config` validates that `self.shard` references a configured shard
 
+        if self.fork_steps.is_empty() {
+            bail!("fork_steps for a chain can not be empty");
+        }
+        if self.fork_steps.contains(&ForkStep::Undo) && !self.fork_steps.contains(&ForkStep::New) {
+            bail!("fork_steps can only contain `undo` if they also contain `new`");
+        }
+
         for provider in self.providers.iter_mut() {
             provider.validate()?
         }
@@ -495,6 +529,24 @@ impl Chain {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForkStep {
+    New,
+    Undo,
+    Irreversible,
+}
+
+impl From<ForkStep> for firehose::ForkStep {
+    fn from(step: ForkStep) -> Self {
+        match step {
+            ForkStep::New => firehose::ForkStep::StepNew,
+            ForkStep::Undo => firehose::ForkStep::StepUndo,
+            ForkStep::Irreversible => firehose::ForkStep::StepIrreversible,
+        }
+    }
+}
+
 fn deserialize_http_headers<'de, D>(deserializer: D) -> Result<HeaderMap, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -533,6 +585,56 @@ pub enum ProviderDetails {
 pub struct FirehoseProvider {
     pub url: String,
     pub token: Option<String>,
+    /// Interval in seconds at which HTTP/2 keepalive pings are sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keepalive_interval: Option<u64>,
+    /// Time in seconds after which the connection is closed if a keepalive
+    /// ping is not answered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keepalive_timeout: Option<u64>,
+    /// Time in seconds after which a block stream is considered dead if
+    /// no message was received on it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_timeout: Option<u64>,
+    /// Path to the PEM encoded certificate of the CA that signed the
+    /// provider's certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+    /// Path to the PEM encoded client certificate for mutual TLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    /// Path to the PEM encoded private key for `client_cert`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<String>,
+}
+
+impl FirehoseProvider {
+    pub fn connection_settings(&self) -> Result<FirehoseConnectionSettings> {
+        fn read_pem(kind: &str, path: &str) -> Result<Vec<u8>> {
+            std::fs::read(path).with_context(|| format!("failed to read {} `{}`", kind, path))
+        }
+
+        let ca_certificate = self
+            .ca_cert
+            .as_ref()
+            .map(|path| read_pem("CA certificate", path))
+            .transpose()?;
+        let client_identity = match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => Some((
+                read_pem("client certificate", cert)?,
+                read_pem("client key", key)?,
+            )),
+            _ => None,
+        };
+
+        Ok(FirehoseConnectionSettings {
+            keepalive_interval: self.keepalive_interval.map(Duration::from_secs),
+            keepalive_timeout: self.keepalive_timeout.map(Duration::from_secs),
+            message_timeout: self.message_timeout.map(Duration::from_secs),
+            ca_certificate,
+            client_identity,
+        })
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -574,7 +676,7 @@ impl Provider {
                 // A Firehose url must be a valid Uri since gRPC library we use (Tonic)
                 // works with Uri.
                 let label = &self.label;
-                firehose.url.parse::<Uri>().map_err(|e| {
+                let uri = firehose.url.parse::<Uri>().map_err(|e| {
                     anyhow!(
                         "the url `{}` for firehose provider {} is not a legal URI: {}",
                         firehose.url,
@@ -586,6 +688,45 @@ impl Provider {
                 if let Some(token) = &firehose.token {
                     firehose.token = Some(shellexpand::env(token)?.into_owned());
                 }
+
+                for path in [
+                    &mut firehose.ca_cert,
+                    &mut firehose.client_cert,
+                    &mut firehose.client_key,
+                ] {
+                    if let Some(p) = path {
+                        *path = Some(shellexpand::env(p)?.into_owned());
+                    }
+                }
+                if firehose.client_cert.is_some() != firehose.client_key.is_some() {
+                    return Err(anyhow!(
+                        "firehose provider {} must set both `client_cert` and `client_key` \
+                         or neither of them",
+                        label
+                    ));
+                }
+                let uses_tls = firehose.ca_cert.is_some() || firehose.client_cert.is_some();
+                if uses_tls && uri.scheme_str() != Some("https") {
+                    return Err(anyhow!(
+                        "firehose provider {} sets TLS certificates, but its url `{}` does not use https",
+                        label,
+                        firehose.url
+                    ));
+                }
+
+                for (name, value) in [
+                    ("keepalive_interval", firehose.keepalive_interval),
+                    ("keepalive_timeout", firehose.keepalive_timeout),
+                    ("message_timeout", firehose.message_timeout),
+                ] {
+                    if value == Some(0) {
+                        return Err(anyhow!(
+                            "`{}` for firehose provider {} must be greater than 0",
+                            name,
+                            label
+                        ));
+                    }
+                }
             }
 
             ProviderDetails::Web3(ref mut web3) => {
@@ -805,12 +946,17 @@ impl DeploymentPlacer for Deployment {
         &self,
         name: &str,
         network: &str,
+        labels: &BTreeMap<String, String>,
     ) -> Result<Option<(Vec<ShardName>, Vec<NodeId>)>, String> {
         // Errors here are really programming errors. We should have validated
         // everything already so that the various conversions can't fail. We
         // still return errors so that they bubble up to the deployment request
         // rather than crashing the node and burying the crash in the logs
-        let placement = match self.rules.iter().find(|rule| rule.matches(name, network)) {
+        let placement = match self
+            .rules
+            .iter()
+            .find(|rule| rule.matches(name, network, labels))
+        {
             Some(rule) => {
                 let shards = rule.shard_names().map_err(|e| e.to_string())?;
                 let indexers: Vec<_> = rule
@@ -848,8 +994,8 @@ impl Rule {
         self.pred.matches_anything()
     }
 
-    fn matches(&self, name: &str, network: &str) -> bool {
-        self.pred.matches(name, network)
+    fn matches(&self, name: &str, network: &str, labels: &BTreeMap<String, String>) -> bool {
+        self.pred.matches(name, network, labels)
     }
 
     fn shard_names(&self) -> Result<Vec<ShardName>, StoreError> {
@@ -877,20 +1023,31 @@ struct Predicate {
     #[serde(with = "serde_regex", default = "any_name")]
     name: Regex,
     network: Option<NetworkPredicate>,
+    /// Deployment labels that must all be present with the given values
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    labels: BTreeMap<String, String>,
 }
 
 impl Predicate {
     fn matches_anything(&self) -> bool {
-        self.name.as_str() == ANY_NAME && self.network.is_none()
+        self.name.as_str() == ANY_NAME && self.network.is_none() && self.labels.is_empty()
     }
 
-    pub fn matches(&self, name: &str, network: &str) -> bool {
+    pub fn matches(&self, name: &str, network: &str, labels: &BTreeMap<String, String>) -> bool {
         if let Some(n) = &self.network {
             if !n.matches(network) {
                 return false;
             }
         }
 
+        if !self
+            .labels
+            .iter()
+            .all(|(key, value)| labels.get(key) == Some(value))
+        {
+            return false;
+        }
+
         match self.name.find(name) {
             None => false,
             Some(m) => m.as_str() == name,
@@ -903,6 +1060,7 @@ impl Default for Predicate {
         Predicate {
             name: any_name(),
             network: None,
+            labels: BTreeMap::new(),
         }
     }
 }
@@ -1002,13 +1160,17 @@ where
 mod tests {
 
     use super::{
-        Chain, Config, FirehoseProvider, Provider, ProviderDetails, Transport, Web3Provider,
+        default_fork_steps, Chain, ChainSection, Config, Deployment, FirehoseProvider, ForkStep,
+        Provider, ProviderDetails, Transport, Web3Provider,
     };
     use graph::blockchain::BlockchainKind;
+    use graph::firehose::FirehoseConnectionSettings;
+    use graph_store_postgres::DeploymentPlacer;
     use http::{HeaderMap, HeaderValue};
-    use std::collections::BTreeSet;
+    use std::collections::{BTreeMap, BTreeSet};
     use std::fs::read_to_string;
     use std::path::{Path, PathBuf};
+    use std::time::Duration;
 
     #[test]
     fn it_works_on_standard_config() {
@@ -1041,6 +1203,8 @@ mod tests {
                 shard: "primary".to_string(),
                 protocol: BlockchainKind::Ethereum,
                 providers: vec![],
+                fork_steps: default_fork_steps(),
+                aliases: vec![],
             },
             actual
         );
@@ -1062,11 +1226,83 @@ mod tests {
                 shard: "primary".to_string(),
                 protocol: BlockchainKind::Near,
                 providers: vec![],
+                fork_steps: default_fork_steps(),
+                aliases: vec![],
+            },
+            actual
+        );
+    }
+
+    #[test]
+    fn it_works_on_chain_with_fork_steps() {
+        let actual = toml::from_str(
+            r#"
+            shard = "primary"
+            protocol = "near"
+            provider = []
+            fork_steps = [ "irreversible" ]
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Chain {
+                shard: "primary".to_string(),
+                protocol: BlockchainKind::Near,
+                providers: vec![],
+                fork_steps: vec![ForkStep::Irreversible],
+                aliases: vec![],
+            },
+            actual
+        );
+    }
+
+    #[test]
+    fn it_works_on_chain_with_aliases() {
+        let actual = toml::from_str(
+            r#"
+            shard = "primary"
+            provider = []
+            aliases = [ "ethereum-mainnet" ]
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Chain {
+                shard: "primary".to_string(),
+                protocol: BlockchainKind::Ethereum,
+                providers: vec![],
+                fork_steps: default_fork_steps(),
+                aliases: vec!["ethereum-mainnet".to_string()],
             },
             actual
         );
     }
 
+    #[test]
+    fn fails_on_duplicate_chain_alias() {
+        let mut actual: ChainSection = toml::from_str(
+            r#"
+            ingestor = "block_ingestor_node"
+            [mainnet]
+            shard = "primary"
+            provider = []
+            aliases = [ "ethereum" ]
+            [ethereum]
+            shard = "primary"
+            provider = []
+        "#,
+        )
+        .unwrap();
+
+        let err = actual.validate().unwrap_err();
+        assert_eq!(
+            "the chain name or alias `ethereum` is used more than once",
+            err.to_string()
+        );
+    }
+
     #[test]
     fn it_works_on_deprecated_provider_from_toml() {
         let actual = toml::from_str(
@@ -1236,12 +1472,115 @@ mod tests {
                 details: ProviderDetails::Firehose(FirehoseProvider {
                     url: "http://localhost:9000".to_owned(),
                     token: None,
+                    keepalive_interval: None,
+                    keepalive_timeout: None,
+                    message_timeout: None,
+                    ca_cert: None,
+                    client_cert: None,
+                    client_key: None,
                 }),
             },
             actual
         );
     }
 
+    #[test]
+    fn it_works_on_firehose_provider_with_connection_settings() {
+        let mut actual: Provider = toml::from_str(
+            r#"
+                label = "firehose"
+                details = { type = "firehose", url = "http://localhost:9000", keepalive_interval = 30, keepalive_timeout = 10, message_timeout = 120 }
+            "#,
+        )
+        .unwrap();
+        actual.validate().unwrap();
+
+        let firehose = match actual.details {
+            ProviderDetails::Firehose(firehose) => firehose,
+            ProviderDetails::Web3(_) => panic!("expected a firehose provider"),
+        };
+        assert_eq!(
+            FirehoseConnectionSettings {
+                keepalive_interval: Some(Duration::from_secs(30)),
+                keepalive_timeout: Some(Duration::from_secs(10)),
+                message_timeout: Some(Duration::from_secs(120)),
+                ca_certificate: None,
+                client_identity: None,
+            },
+            firehose.connection_settings().unwrap()
+        );
+
+        let mut actual: Provider = toml::from_str(
+            r#"
+                label = "firehose"
+                details = { type = "firehose", url = "http://localhost:9000", message_timeout = 0 }
+            "#,
+        )
+        .unwrap();
+        assert!(actual.validate().is_err());
+    }
+
+    #[test]
+    fn it_validates_firehose_tls_settings() {
+        let validate = |details: &str| {
+            let mut provider: Provider =
+                toml::from_str(&format!("label = \"firehose\"\ndetails = {}", details)).unwrap();
+            provider.validate()
+        };
+
+        validate(r#"{ type = "firehose", url = "https://localhost:9000", ca_cert = "/etc/ca.pem", client_cert = "/etc/cert.pem", client_key = "/etc/key.pem" }"#).unwrap();
+        validate(
+            r#"{ type = "firehose", url = "https://localhost:9000", ca_cert = "/etc/ca.pem" }"#,
+        )
+        .unwrap();
+        // Client certificate without a key
+        assert!(validate(r#"{ type = "firehose", url = "https://localhost:9000", client_cert = "/etc/cert.pem" }"#).is_err());
+        // Certificates without https
+        assert!(validate(
+            r#"{ type = "firehose", url = "http://localhost:9000", ca_cert = "/etc/ca.pem" }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn it_places_deployments_by_labels() {
+        let deployment: Deployment = toml::from_str(
+            r#"
+            [[rule]]
+            match = { labels = { tier = "gold", team = "core" } }
+            indexers = [ "index_node_gold" ]
+
+            [[rule]]
+            indexers = [ "index_node_default" ]
+        "#,
+        )
+        .unwrap();
+        deployment.validate().unwrap();
+
+        let indexers = |labels: &[(&str, &str)]| {
+            let labels: BTreeMap<_, _> = labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            let (_, nodes) = deployment
+                .place("subgraph", "mainnet", &labels)
+                .unwrap()
+                .unwrap();
+            nodes.into_iter().map(|n| n.to_string()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            vec!["index_node_gold"],
+            indexers(&[("tier", "gold"), ("team", "core"), ("sla", "24h")])
+        );
+        assert_eq!(vec!["index_node_default"], indexers(&[("tier", "gold")]));
+        assert_eq!(
+            vec!["index_node_default"],
+            indexers(&[("tier", "silver"), ("team", "core")])
+        );
+        assert_eq!(vec!["index_node_default"], indexers(&[]));
+    }
+
     fn read_resource_as_string<P: AsRef<Path>>(path: P) -> String {
         let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         d.push("resources/tests");
@@ -2,7 +2,8 @@ use ethereum::{BlockIngestor as EthereumBlockIngestor, EthereumAdapterTrait, Eth
 use git_testament::{git_testament, render_testament};
 use graph::blockchain::firehose_block_ingestor::FirehoseBlockIngestor;
 use graph::blockchain::{Block as BlockchainBlock, Blockchain, BlockchainKind, BlockchainMap};
-use graph::components::store::BlockStore;
+use graph::components::store::SubscriptionManager as _;
+use graph::components::store::{BlockStore, FallbackBlockTimestamps};
 use graph::data::graphql::effort::LoadManager;
 use graph::firehose::{FirehoseEndpoints, FirehoseNetworks};
 use graph::log::logger;
@@ -19,7 +20,7 @@ use graph_node::chain::{
     connect_ethereum_networks, connect_firehose_networks, create_ethereum_networks,
     create_firehose_networks, create_ipfs_clients, ANCESTOR_COUNT, REORG_THRESHOLD,
 };
-use graph_node::config::Config;
+use graph_node::config::{ChainSection, Config};
 use graph_node::opt;
 use graph_node::store_builder::StoreBuilder;
 use graph_server_http::GraphQLServer as GraphQLQueryServer;
@@ -27,12 +28,15 @@ use graph_server_index_node::IndexNodeServer;
 use graph_server_json_rpc::JsonRpcServer;
 use graph_server_metrics::PrometheusMetricsServer;
 use graph_server_websocket::SubscriptionServer as GraphQLSubscriptionServer;
-use graph_store_postgres::{register_jobs as register_store_jobs, ChainHeadUpdateListener, Store};
+use graph_store_postgres::{
+    register_jobs as register_store_jobs, ChainHeadUpdateListener, Store,
+    SubgraphStore as DieselSubgraphStore, SubscriptionManager,
+};
 use std::collections::BTreeMap;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
 use std::sync::atomic;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{collections::HashMap, env};
 use structopt::StructOpt;
 use tokio::sync::mpsc;
@@ -223,6 +227,14 @@ async fn main() {
             &logger_factory,
         );
 
+        let block_timestamps = Arc::new(FallbackBlockTimestamps::new(
+            network_store.block_store(),
+            ethereum_chains
+                .iter()
+                .map(|(name, chain)| (name.clone(), chain.block_timestamps()))
+                .collect(),
+        ));
+
         let near_chains = near_networks_as_chains(
             &mut blockchain_map,
             &logger,
@@ -231,6 +243,12 @@ async fn main() {
             &logger_factory,
         );
 
+        for (name, chain) in &config.chains.chains {
+            for alias in &chain.aliases {
+                blockchain_map.insert_alias(alias.clone(), name.clone());
+            }
+        }
+
         let blockchain_map = Arc::new(blockchain_map);
 
         let load_manager = Arc::new(LoadManager::new(
@@ -254,6 +272,14 @@ async fn main() {
         let subscription_server =
             GraphQLSubscriptionServer::new(&logger, graphql_runner.clone(), network_store.clone());
 
+        if std::env::var_os("GRAPH_DISABLE_SCHEMA_WARMUP").is_none() {
+            start_schema_warmup(
+                &logger,
+                network_store.subgraph_store(),
+                subscription_manager.clone(),
+            );
+        }
+
         let mut index_node_server = IndexNodeServer::new(
             &logger_factory,
             graphql_runner.clone(),
@@ -276,7 +302,9 @@ async fn main() {
 
             start_firehose_block_ingestor::<_, NearFirehoseHeaderOnlyBlock>(
                 &logger,
+                metrics_registry.clone(),
                 &network_store,
+                &config.chains,
                 near_chains,
             );
 
@@ -294,6 +322,7 @@ async fn main() {
         let subgraph_instance_manager = SubgraphInstanceManager::new(
             &logger_factory,
             network_store.subgraph_store(),
+            block_timestamps,
             blockchain_map.cheap_clone(),
             metrics_registry.clone(),
             link_resolver.cheap_clone(),
@@ -544,6 +573,72 @@ fn near_networks_as_chains(
     HashMap::from_iter(chains)
 }
 
+/// Load the API schemas and build the introspection objects of all active
+/// deployments in the background so that the first queries after a restart
+/// do not have to, and do the same for every deployment that gets assigned
+/// to an index node later on
+fn start_schema_warmup(
+    logger: &Logger,
+    subgraph_store: Arc<DieselSubgraphStore>,
+    subscription_manager: Arc<SubscriptionManager>,
+) {
+    let logger = logger.new(o!("component" => "SchemaWarmup"));
+
+    graph::spawn(async move {
+        let start = Instant::now();
+        let store = subgraph_store.cheap_clone();
+        let warmup_logger = logger.clone();
+        let res = graph::spawn_blocking_allow_panic(move || {
+            store.warm_schema_caches(&warmup_logger, &[])
+        })
+        .await;
+        match res {
+            Ok(Ok(count)) => info!(logger, "Warmed up schema caches";
+                                   "deployments" => count,
+                                   "time_ms" => start.elapsed().as_millis()),
+            Ok(Err(e)) => {
+                error!(logger, "Failed to warm up schema caches"; "error" => e.to_string())
+            }
+            Err(e) => error!(logger, "Failed to warm up schema caches"; "error" => e.to_string()),
+        }
+
+        // New deployments get assigned when they are deployed; warm up their
+        // schemas as soon as we hear about that
+        let mut events = subscription_manager
+            .subscribe(FromIterator::from_iter([SubscriptionFilter::Assignment]))
+            .compat();
+        while let Some(Ok(event)) = events.next().await {
+            let deployments: Vec<_> = event
+                .changes
+                .iter()
+                .filter_map(|change| match change {
+                    EntityChange::Assignment {
+                        deployment,
+                        operation: EntityChangeOperation::Set,
+                    } => Some(deployment.hash.to_string()),
+                    _ => None,
+                })
+                .collect();
+            if deployments.is_empty() {
+                continue;
+            }
+
+            let store = subgraph_store.cheap_clone();
+            let warmup_logger = logger.clone();
+            let _ = graph::spawn_blocking_allow_panic(move || {
+                if let Err(e) = store.warm_schema_caches(&warmup_logger, &deployments) {
+                    error!(warmup_logger, "Failed to warm up schema caches"; "error" => e.to_string());
+                }
+            })
+            .await;
+        }
+        debug!(
+            logger,
+            "Assignment event stream ended, stopping schema warmup"
+        );
+    });
+}
+
 fn start_block_ingestor(
     logger: &Logger,
     logger_factory: &LoggerFactory,
@@ -617,7 +712,9 @@ struct FirehoseChain<C: Blockchain> {
 
 fn start_firehose_block_ingestor<C, M>(
     logger: &Logger,
+    registry: Arc<MetricsRegistry>,
     store: &Store,
+    config: &ChainSection,
     chains: HashMap<String, FirehoseChain<C>>,
 ) where
     C: Blockchain,
@@ -644,18 +741,19 @@ fn start_firehose_block_ingestor<C, M>(
                 "network_name" => &network_name
             );
 
-            let endpoint = chain
-                .firehose_endpoints
-                .random()
-                .expect("One Firehose endpoint should exist at that execution point");
-
             match store.block_store().chain_store(network_name.as_ref()) {
                 Some(s) => {
-                    let block_ingestor = FirehoseBlockIngestor::<M>::new(
+                    let mut block_ingestor = FirehoseBlockIngestor::<M>::new(
                         s,
-                        endpoint.clone(),
-                        logger.new(o!("component" => "FirehoseBlockIngestor", "provider" => endpoint.provider.clone())),
-                    );
+                        chain.firehose_endpoints.clone(),
+                        logger.new(o!("component" => "FirehoseBlockIngestor")),
+                    )
+                    .with_metrics(registry.clone(), network_name);
+                    if let Some(chain) = config.chains.get(network_name) {
+                        block_ingestor = block_ingestor.with_fork_steps(
+                            chain.fork_steps.iter().map(|step| (*step).into()).collect(),
+                        );
+                    }
 
                     // Run the Firehose block ingestor in the background
                     graph::spawn(block_ingestor.run());
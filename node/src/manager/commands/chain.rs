@@ -9,10 +9,12 @@ use graph::prelude::{anyhow, anyhow::bail};
 use graph::{
     components::store::BlockStore as _, prelude::anyhow::Error, prelude::serde_json as json,
 };
-use graph_store_postgres::BlockStore;
 use graph_store_postgres::{
     command_support::catalog::block_store, connection_pool::ConnectionPool,
 };
+use graph_store_postgres::{BlockStore, Store};
+
+use crate::manager::display::prompt_for_confirmation;
 
 pub fn list(primary: ConnectionPool, store: Arc<BlockStore>) -> Result<(), Error> {
     let mut chains = {
@@ -37,7 +39,7 @@ pub fn list(primary: ConnectionPool, store: Arc<BlockStore>) -> Result<(), Error
             Some(chain_store) => chain_store
                 .chain_head_ptr()?
                 .map(|ptr| ptr.number.to_string())
-                .unwrap_or("none".to_string()),
+                .unwrap_or_else(|| "none".to_string()),
         };
         println!(
             "{:<20} | {:<10} | {:<10} | {:>7} | {:>10}",
@@ -55,7 +57,7 @@ pub fn info(
     hashes: bool,
 ) -> Result<(), Error> {
     fn row(label: &str, value: impl std::fmt::Display) {
-        println!("{:<16} | {}", label, value.to_string());
+        println!("{:<16} | {}", label, value);
     }
 
     fn print_ptr(label: &str, ptr: Option<BlockPtr>, hashes: bool) {
@@ -81,6 +83,7 @@ pub fn info(
         .chain_store(&chain.name)
         .ok_or_else(|| anyhow!("unknown chain: {}", name))?;
     let head_block = chain_store.chain_head_ptr()?;
+    let final_block = chain_store.chain_final_block_ptr()?;
     let ancestor = match &head_block {
         None => None,
         Some(head_block) => chain_store
@@ -98,6 +101,7 @@ pub fn info(
         row("genesis", chain.genesis_block);
     }
     print_ptr("head block", head_block, hashes);
+    print_ptr("final block", final_block, hashes);
     row("reorg threshold", offset);
     print_ptr("reorg ancestor", ancestor, hashes);
 
@@ -126,3 +130,146 @@ pub fn remove(primary: ConnectionPool, store: Arc<BlockStore>, name: String) ->
 
     Ok(())
 }
+
+fn chain_store(
+    store: &BlockStore,
+    name: &str,
+) -> Result<Arc<graph_store_postgres::ChainStore>, Error> {
+    store
+        .chain_store(name)
+        .ok_or_else(|| anyhow!("unknown chain: {}", name))
+}
+
+pub fn backfill_set_target(
+    store: Arc<BlockStore>,
+    name: String,
+    block: Option<BlockNumber>,
+) -> Result<(), Error> {
+    let chain_store = chain_store(&store, &name)?;
+    chain_store.set_chain_backfill_target_block_num(block)?;
+    match block {
+        Some(block) => println!("backfill target for {} set to block {}", name, block),
+        None => println!("backfill target for {} cleared", name),
+    }
+    Ok(())
+}
+
+pub fn backfill_status(store: Arc<BlockStore>, name: String) -> Result<(), Error> {
+    fn row(label: &str, value: impl std::fmt::Display) {
+        println!("{:<16} | {}", label, value);
+    }
+
+    let chain_store = chain_store(&store, &name)?;
+    let target = chain_store.chain_backfill_target_block_num()?;
+    let (progress, cursor) = chain_store.chain_backfill_progress()?;
+
+    row("name", &name);
+    row(
+        "target",
+        target
+            .map(|target| target.to_string())
+            .unwrap_or_else(|| "ø".to_string()),
+    );
+    row(
+        "progress",
+        progress
+            .map(|progress| progress.to_string())
+            .unwrap_or_else(|| "ø".to_string()),
+    );
+    match cursor {
+        Some(cursor) => {
+            row("cursor", cursor.cursor);
+            row("provider", cursor.provider);
+        }
+        None => row("cursor", "ø"),
+    }
+    if let Some(target) = target {
+        // Blocks are backfilled starting at the genesis block, block 0
+        let done = progress
+            .map(|progress| progress.min(target) + 1)
+            .unwrap_or(0);
+        let percent = 100.0 * done as f64 / (target as f64 + 1.0);
+        row("complete", format!("{:.2}%", percent));
+    }
+
+    Ok(())
+}
+
+pub fn backfill_reset(store: Arc<BlockStore>, name: String) -> Result<(), Error> {
+    let chain_store = chain_store(&store, &name)?;
+    chain_store.set_chain_backfill_progress(None, None)?;
+    println!("backfill progress for {} reset", name);
+    Ok(())
+}
+
+/// Clear the head and/or backfill cursor of the chain `name`. The head
+/// stream restarts from the current chain head block, and the backfill
+/// restarts right after the blocks it has already backfilled
+pub fn reset_cursor(
+    store: Arc<BlockStore>,
+    name: String,
+    head: bool,
+    backfill: bool,
+    force: bool,
+) -> Result<(), Error> {
+    let chain_store = chain_store(&store, &name)?;
+
+    let cursors = match (head, backfill) {
+        (true, true) => "head and backfill cursors",
+        (true, false) => "head cursor",
+        (false, true) => "backfill cursor",
+        (false, false) => bail!("one of --head or --backfill must be given"),
+    };
+    let prompt = format!(
+        "Clearing the {} for {} makes graph-node request blocks from \
+         the Firehose without a cursor. Any graph-node instance ingesting \
+         blocks for {} should be restarted afterwards. Continue?",
+        cursors, name, name
+    );
+    if !force && !prompt_for_confirmation(&prompt)? {
+        println!("Aborting");
+        return Ok(());
+    }
+
+    if head {
+        chain_store.clear_chain_head_cursor()?;
+    }
+    if backfill {
+        let (progress, _) = chain_store.chain_backfill_progress()?;
+        chain_store.set_chain_backfill_progress(progress, None)?;
+    }
+    println!("{} for {} cleared", cursors, name);
+    Ok(())
+}
+
+/// Remove the blocks of chain `name` that are more than `keep` blocks
+/// behind the chain head from the block cache
+pub fn truncate(
+    store: Arc<Store>,
+    name: String,
+    keep: BlockNumber,
+    force: bool,
+) -> Result<(), Error> {
+    let prompt = format!(
+        "Removing all blocks of {} that are more than {} blocks behind the \
+         chain head from the block cache means that they will have to be \
+         fetched from the chain again when they are needed. Continue?",
+        name, keep
+    );
+    if !force && !prompt_for_confirmation(&prompt)? {
+        println!("Aborting");
+        return Ok(());
+    }
+
+    match store.evict_cached_blocks(&name, keep)? {
+        Some((block, rows)) => println!(
+            "removed {} blocks before block {} from the block cache for {}",
+            rows, block, name
+        ),
+        None => println!(
+            "the block cache for {} does not have blocks that are more than {} blocks behind the chain head",
+            name, keep
+        ),
+    }
+    Ok(())
+}
@@ -8,8 +8,32 @@ use graph_store_postgres::DeploymentPlacer;
 
 use crate::config::Config;
 
-pub fn place(placer: &dyn DeploymentPlacer, name: &str, network: &str) -> Result<(), Error> {
-    match placer.place(name, network).map_err(|s| anyhow!(s))? {
+pub fn place(
+    placer: &dyn DeploymentPlacer,
+    name: &str,
+    network: &str,
+    labels: Vec<String>,
+) -> Result<(), Error> {
+    let labels = labels
+        .into_iter()
+        .map(|label| {
+            let mut pair = label.splitn(2, '=');
+            match (pair.next(), pair.next()) {
+                (Some(key), Some(value)) if !key.is_empty() => {
+                    Ok((key.to_string(), value.to_string()))
+                }
+                _ => Err(anyhow!(
+                    "malformed label `{}`, it must be of the form `key=value`",
+                    label
+                )),
+            }
+        })
+        .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+    match placer
+        .place(name, network, &labels)
+        .map_err(|s| anyhow!(s))?
+    {
         None => {
             println!(
                 "no matching placement rule; default placement from JSON RPC call would be used"
@@ -48,6 +48,32 @@ struct CopyTableState {
     started_at: UtcDateTime,
     finished_at: Option<UtcDateTime>,
     duration_ms: i64,
+    first_vid: i64,
+    relation: String,
+}
+
+impl CopyTableState {
+    /// The entity type, or the entity type and the `@relation` field for
+    /// the join table of that field
+    fn name(&self) -> String {
+        if self.relation.is_empty() {
+            self.entity_type.clone()
+        } else {
+            format!("{}.{}", self.entity_type, self.relation)
+        }
+    }
+
+    /// The number of entity versions that have been copied so far, and
+    /// the number that need to be copied overall
+    fn progress(tables: &[CopyTableState]) -> (i64, i64) {
+        tables.iter().fold((0, 0), |(next, target), table| {
+            let total = (table.target_vid - table.first_vid + 1).max(0);
+            (
+                next + (table.next_vid - table.first_vid).min(total),
+                target + total,
+            )
+        })
+    }
 }
 
 impl CopyState {
@@ -67,7 +93,7 @@ impl CopyState {
 
         let tables = cts::table
             .filter(cts::dst.eq(dst))
-            .order_by(cts::entity_type)
+            .order_by((cts::entity_type, cts::relation, cts::first_vid))
             .load::<CopyTableState>(&dconn)?;
 
         Ok(cs::table
@@ -127,7 +153,12 @@ pub async fn create(
     Ok(())
 }
 
-pub fn activate(store: Arc<SubgraphStore>, deployment: String, shard: String) -> Result<(), Error> {
+pub fn activate(
+    store: Arc<SubgraphStore>,
+    deployment: String,
+    shard: String,
+    force: bool,
+) -> Result<(), Error> {
     let shard = Shard::new(shard)?;
     let deployment = deployment::as_hash(deployment)?;
     let deployment = store
@@ -139,6 +170,21 @@ pub fn activate(store: Arc<SubgraphStore>, deployment: String, shard: String) ->
                 shard
             )
         })?;
+    let lag = store.activation_lag(&deployment)?;
+    if lag > 0 {
+        if !force {
+            bail!(
+                "the copy {} is {} blocks behind the active copy; try again once it has caught up, \
+                 or use `--force` to activate it anyway",
+                deployment,
+                lag
+            );
+        }
+        println!(
+            "activating copy {} even though it is {} blocks behind",
+            deployment, lag
+        );
+    }
     store.activate(&deployment)?;
     println!("activated copy {}", deployment);
     Ok(())
@@ -187,8 +233,7 @@ pub fn list(pools: HashMap<Shard, ConnectionPool>) -> Result<(), Error> {
                     None => match state.finished_at {
                         Some(finished_at) => status("finished", finished_at),
                         None => {
-                            let target: i64 = tables.iter().map(|table| table.target_vid).sum();
-                            let next: i64 = tables.iter().map(|table| table.next_vid).sum();
+                            let (next, target) = CopyTableState::progress(&tables);
                             let done = next as f64 / target as f64 * 100.0;
                             status("started", state.started_at);
                             println!("{:20} | {:.2}% done, {}/{}", "progress", done, next, target)
@@ -263,8 +308,7 @@ pub fn status(pools: HashMap<Shard, ConnectionPool>, dst: i32) -> Result<(), Err
     let progress = match &state.finished_at {
         Some(_) => done(&state.finished_at),
         None => {
-            let target: i64 = tables.iter().map(|table| table.target_vid).sum();
-            let next: i64 = tables.iter().map(|table| table.next_vid).sum();
+            let (next, target) = CopyTableState::progress(&tables);
             let pct = next as f64 / target as f64 * 100.0;
             format!("{:.2}% done, {}/{}", pct, next, target)
         }
@@ -308,7 +352,7 @@ pub fn status(pools: HashMap<Shard, ConnectionPool>, dst: i32) -> Result<(), Err
     );
     println!("{:-<74}", "-");
     for table in tables {
-        let status = if table.next_vid > 0 && table.next_vid < table.target_vid {
+        let status = if table.next_vid > table.first_vid && table.next_vid < table.target_vid {
             ">".to_string()
         } else if table.target_vid < 0 {
             // empty source table
@@ -319,7 +363,7 @@ pub fn status(pools: HashMap<Shard, ConnectionPool>, dst: i32) -> Result<(), Err
         println!(
             "{} {:<28} | {:>8} | {:>8} | {:>8} | {:>8}",
             status,
-            table.entity_type,
+            table.name(),
             table.next_vid,
             table.target_vid,
             table.batch_size,
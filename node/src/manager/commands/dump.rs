@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use graph::prelude::{
+    anyhow::{anyhow, Error},
+    NodeId,
+};
+use graph_store_postgres::{Shard, SubgraphStore};
+
+use crate::manager::deployment::locate;
+
+pub fn dump(
+    store: Arc<SubgraphStore>,
+    hash: String,
+    shard: Option<String>,
+    directory: PathBuf,
+) -> Result<(), Error> {
+    let deployment = locate(store.as_ref(), hash, shard)?;
+
+    println!("dumping {} into {}", deployment, directory.display());
+    store.dump(&deployment, &directory)?;
+    Ok(())
+}
+
+pub fn restore(
+    store: Arc<SubgraphStore>,
+    directory: PathBuf,
+    shard: String,
+    node: Option<String>,
+) -> Result<(), Error> {
+    let shard = Shard::new(shard)?;
+    let node = node
+        .map(|node| NodeId::new(node.clone()).map_err(|()| anyhow!("illegal node id `{}`", node)))
+        .transpose()?;
+
+    println!("restoring {} into shard {}", directory.display(), shard);
+    let deployment = store.restore(&directory, shard, node)?;
+    println!("restored {}", deployment);
+    Ok(())
+}
@@ -1,9 +1,18 @@
+use diesel::{sql_query, sql_types::Text, RunQueryDsl};
 use graph::{
     components::store::EntityType,
     prelude::{anyhow, DeploymentHash, StoreError},
 };
-use graph_store_postgres::SubgraphStore;
-use std::{collections::HashSet, sync::Arc};
+use graph_store_postgres::{
+    connection_pool::ConnectionPool, IndexAdvice, IndexMethod, Shard, SubgraphStore,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::manager::commands::stats::site_and_conn;
+use crate::manager::display::{List, OutputFormat};
 
 fn validate_fields<T: AsRef<str>>(fields: &[T]) -> Result<(), anyhow::Error> {
     // Must be non-empty. Double checking, since [`StructOpt`] already checks this.
@@ -28,9 +37,10 @@ pub async fn create(
     let deployment_hash = DeploymentHash::new(id)
         .map_err(|e| anyhow::anyhow!("Subgraph hash must be a valid IPFS hash: {}", e))?;
     let entity_type = EntityType::new(entity_name);
+    let method: IndexMethod = index_method.parse()?;
     println!("Index creation started. Please wait.");
     match store
-        .create_manual_index(&deployment_hash, entity_type, field_names, index_method)
+        .create_manual_index(&deployment_hash, entity_type, field_names, method)
         .await
     {
         Ok(()) => Ok(()),
@@ -41,3 +51,100 @@ pub async fn create(
         Err(other) => Err(anyhow::anyhow!(other)),
     }
 }
+
+pub async fn advise(
+    store: Arc<SubgraphStore>,
+    id: String,
+    format: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    let deployment_hash = DeploymentHash::new(id)
+        .map_err(|e| anyhow::anyhow!("Subgraph hash must be a valid IPFS hash: {}", e))?;
+    let advice = store.index_advice(&deployment_hash).await?;
+
+    let mut list = List::new(vec!["action", "command", "reason"]);
+    for item in advice {
+        let command = match &item {
+            IndexAdvice::Drop { index, .. } => {
+                format!("graphman index drop {} {}", deployment_hash, index)
+            }
+            IndexAdvice::Create { entity, field, .. } => {
+                format!(
+                    "graphman index create {} {} {}",
+                    deployment_hash, entity, field
+                )
+            }
+        };
+        let action = match &item {
+            IndexAdvice::Drop { .. } => "drop",
+            IndexAdvice::Create { .. } => "create",
+        };
+        list.append(vec![action.to_string(), command, item.to_string()]);
+    }
+
+    if list.is_empty() && format == OutputFormat::Text {
+        println!("No advice");
+    } else {
+        list.render_as(format);
+    }
+    Ok(())
+}
+
+pub async fn drop(
+    store: Arc<SubgraphStore>,
+    id: String,
+    index_name: String,
+) -> Result<(), anyhow::Error> {
+    let deployment_hash = DeploymentHash::new(id)
+        .map_err(|e| anyhow::anyhow!("Subgraph hash must be a valid IPFS hash: {}", e))?;
+    store
+        .drop_index(&deployment_hash, index_name.clone())
+        .await?;
+    println!("Dropped index `{}`", index_name);
+    Ok(())
+}
+
+/// List the indexes of the tables of the deployment with namespace `nsp`,
+/// or only those of `table`
+pub fn list(
+    pools: HashMap<Shard, ConnectionPool>,
+    nsp: String,
+    table: Option<String>,
+    format: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    #[derive(QueryableByName)]
+    struct Index {
+        #[sql_type = "Text"]
+        tablename: String,
+        #[sql_type = "Text"]
+        indexname: String,
+        #[sql_type = "Text"]
+        indexdef: String,
+    }
+
+    let (site, conn) = site_and_conn(pools, &nsp)?;
+
+    let query = "select tablename, indexname, indexdef
+                   from pg_indexes
+                  where schemaname = $1
+                  order by tablename, indexname";
+    let indexes = sql_query(query)
+        .bind::<Text, _>(site.namespace.as_str())
+        .load::<Index>(&conn)?;
+
+    let mut list = List::new(vec!["table", "index", "definition"]);
+    for index in indexes {
+        if table
+            .as_ref()
+            .map_or(true, |table| table == &index.tablename)
+        {
+            list.append(vec![index.tablename, index.indexname, index.indexdef]);
+        }
+    }
+
+    if list.is_empty() && format == OutputFormat::Text {
+        println!("No indexes");
+    } else {
+        list.render_as(format);
+    }
+    Ok(())
+}
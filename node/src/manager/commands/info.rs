@@ -4,6 +4,7 @@ use graph::{components::store::StatusStore, data::subgraph::status, prelude::any
 use graph_store_postgres::{connection_pool::ConnectionPool, Store};
 
 use crate::manager::deployment::Deployment;
+use crate::manager::display::OutputFormat;
 
 fn find(
     pool: ConnectionPool,
@@ -37,6 +38,7 @@ pub fn run(
     current: bool,
     pending: bool,
     used: bool,
+    format: OutputFormat,
 ) -> Result<(), anyhow::Error> {
     let deployments = find(pool, name, current, pending, used)?;
     let ids: Vec<_> = deployments.iter().map(|d| d.locator().id).collect();
@@ -45,10 +47,10 @@ pub fn run(
         None => vec![],
     };
 
-    if deployments.is_empty() {
+    if deployments.is_empty() && format == OutputFormat::Text {
         println!("No matches");
     } else {
-        Deployment::print_table(deployments, statuses);
+        Deployment::print_table(deployments, statuses, format);
     }
     Ok(())
 }
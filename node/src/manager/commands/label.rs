@@ -0,0 +1,49 @@
+use graph::prelude::{
+    anyhow::{anyhow, bail, Error},
+    DeploymentHash,
+};
+use graph_store_postgres::{command_support::catalog::Connection, connection_pool::ConnectionPool};
+
+fn deployment_hash(hash: String) -> Result<DeploymentHash, Error> {
+    DeploymentHash::new(hash).map_err(|s| anyhow!("illegal deployment hash `{}`", s))
+}
+
+pub fn set(
+    primary: ConnectionPool,
+    deployment: String,
+    key: String,
+    value: String,
+) -> Result<(), Error> {
+    let deployment = deployment_hash(deployment)?;
+    if key.is_empty() {
+        bail!("the label key must not be empty");
+    }
+    let conn = Connection::new(primary.get()?);
+    conn.set_deployment_label(&deployment, &key, &value)?;
+    println!("set label {}={} on {}", key, value, deployment);
+    Ok(())
+}
+
+pub fn remove(primary: ConnectionPool, deployment: String, key: String) -> Result<(), Error> {
+    let deployment = deployment_hash(deployment)?;
+    let conn = Connection::new(primary.get()?);
+    if conn.remove_deployment_label(&deployment, &key)? {
+        println!("removed label {} from {}", key, deployment);
+    } else {
+        println!("deployment {} does not have label {}", deployment, key);
+    }
+    Ok(())
+}
+
+pub fn list(primary: ConnectionPool, deployment: String) -> Result<(), Error> {
+    let deployment = deployment_hash(deployment)?;
+    let conn = Connection::new(primary.get()?);
+    let labels = conn.deployment_labels(&deployment)?;
+    if labels.is_empty() {
+        println!("deployment {} has no labels", deployment);
+    }
+    for (key, value) in labels {
+        println!("{:<20} | {}", key, value);
+    }
+    Ok(())
+}
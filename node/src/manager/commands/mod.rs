@@ -3,10 +3,15 @@ pub mod chain;
 pub mod config;
 pub mod copy;
 pub mod create;
+pub mod dump;
 pub mod index;
 pub mod info;
+pub mod label;
 pub mod listen;
+pub mod prune;
 pub mod query;
+pub mod reader;
+pub mod rebind;
 pub mod remove;
 pub mod rewind;
 pub mod run;
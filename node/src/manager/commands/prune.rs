@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Double, Integer, Nullable, Text};
+use diesel::{PgConnection, RunQueryDsl};
+use graph::components::store::{DeploymentLocator, PruneReporter};
+use graph::prelude::anyhow::{anyhow, bail, Error};
+use graph::prelude::BlockNumber;
+use graph_store_postgres::command_support::catalog as store_catalog;
+use graph_store_postgres::command_support::catalog::Site;
+use graph_store_postgres::connection_pool::ConnectionPool;
+use graph_store_postgres::{Shard, SubgraphStore, PRIMARY_SHARD};
+
+use crate::chain::REORG_THRESHOLD;
+use crate::manager::deployment::locate;
+use crate::manager::display::human_bytes;
+
+#[derive(QueryableByName)]
+struct LatestBlock {
+    #[sql_type = "Nullable<Integer>"]
+    number: Option<i32>,
+}
+
+#[derive(QueryableByName)]
+struct ServerVersion {
+    #[sql_type = "Integer"]
+    version: i32,
+}
+
+#[derive(QueryableByName)]
+struct TableEstimate {
+    #[sql_type = "Text"]
+    tablename: String,
+    #[sql_type = "BigInt"]
+    versions: i64,
+    #[sql_type = "BigInt"]
+    bytes: i64,
+    /// The fraction of versions whose block range ends before the cutoff
+    #[sql_type = "Double"]
+    removable: f64,
+}
+
+impl TableEstimate {
+    fn removable_versions(&self) -> i64 {
+        (self.versions as f64 * self.removable) as i64
+    }
+
+    fn reclaimed_bytes(&self) -> u64 {
+        (self.bytes as f64 * self.removable) as u64
+    }
+
+    fn header() {
+        println!(
+            "{:^30} | {:^12} | {:^12} | {:^7} | {:^10}",
+            "table", "versions", "removable", "ratio", "reclaimed"
+        );
+        println!(
+            "{:-^30}-+-{:-^12}-+-{:-^12}-+-{:-^7}-+-{:-^10}",
+            "", "", "", "", ""
+        );
+    }
+
+    fn print(&self) {
+        println!(
+            "{:<30} | {:>12} | {:>12} | {:>6.1}% | {:>10}",
+            self.tablename,
+            self.versions,
+            self.removable_versions(),
+            self.removable * 100.0,
+            human_bytes(self.reclaimed_bytes())
+        );
+    }
+}
+
+fn deployment_and_conn(
+    store: &SubgraphStore,
+    pools: &HashMap<Shard, ConnectionPool>,
+    hash: String,
+    shard: Option<String>,
+) -> Result<
+    (
+        DeploymentLocator,
+        Site,
+        PooledConnection<ConnectionManager<PgConnection>>,
+    ),
+    Error,
+> {
+    let deployment = locate(store, hash, shard)?;
+    let primary = store_catalog::Connection::new(pools.get(&*PRIMARY_SHARD).unwrap().get()?);
+    let site = primary
+        .find_site_by_ref(deployment.id.into())?
+        .ok_or_else(|| anyhow!("deployment {} does not exist", deployment))?;
+    let conn = pools
+        .get(&site.shard)
+        .ok_or_else(|| anyhow!("unknown shard {}", site.shard))?
+        .get()?;
+    Ok((deployment, site, conn))
+}
+
+fn latest_block(conn: &PgConnection, deployment: &DeploymentLocator) -> Result<BlockNumber, Error> {
+    sql_query(
+        "select latest_ethereum_block_number::int4 as number
+           from subgraphs.subgraph_deployment
+          where id = $1",
+    )
+    .bind::<Integer, _>(deployment.id.0)
+    .get_result::<LatestBlock>(conn)?
+    .number
+    .ok_or_else(|| anyhow!("deployment {} has not processed any blocks", deployment))
+}
+
+/// Estimate how many entity versions pruning the deployment to the last
+/// `history` blocks would remove, and how much space that would free up.
+/// The estimate is based on the histograms of `block_range` bounds that
+/// Postgres keeps for each table and is therefore only as good as the
+/// statistics from the last time the tables were analyzed. Nothing is
+/// changed in the database
+pub fn preview(
+    store: Arc<SubgraphStore>,
+    pools: HashMap<Shard, ConnectionPool>,
+    hash: String,
+    shard: Option<String>,
+    history: BlockNumber,
+) -> Result<(), Error> {
+    if history < 0 {
+        bail!("the history must be a positive number of blocks");
+    }
+
+    let (deployment, site, conn) = deployment_and_conn(store.as_ref(), &pools, hash, shard)?;
+
+    // `pg_stats.range_bounds_histogram` was added in Postgres 14
+    let version = sql_query("select current_setting('server_version_num')::int4 as version")
+        .get_result::<ServerVersion>(&conn)?
+        .version;
+    if version < 140000 {
+        bail!(
+            "estimating prune savings requires Postgres 14 or later, but shard {} runs {}",
+            site.shard,
+            version
+        );
+    }
+
+    let latest = latest_block(&conn, &deployment)?;
+    let cutoff = latest - history;
+
+    println!(
+        "Keeping history for blocks [{}, {}] of {} ({})",
+        cutoff.max(0),
+        latest,
+        deployment,
+        site.namespace
+    );
+    if cutoff <= 0 {
+        println!(
+            "The deployment does not have more than {} blocks of history",
+            history
+        );
+        return Ok(());
+    }
+
+    // Versions whose block range ended before the cutoff can be removed.
+    // The upper bounds of the histogram divide those versions into buckets
+    // of equal size; versions that are still current have no upper bound
+    let estimates = sql_query(
+        "select c.relname::text as tablename,
+                c.reltuples::int8 as versions,
+                pg_total_relation_size(c.oid)::int8 as bytes,
+                coalesce((select count(*) filter (where upper(b) <= $2)::float8
+                                 / nullif(count(*), 0)
+                            from unnest(s.range_bounds_histogram::text::int4range[]) b), 0)
+                  as removable
+           from pg_namespace n, pg_class c, pg_stats s
+          where n.nspname = $1
+            and c.relnamespace = n.oid
+            and s.schemaname = n.nspname
+            and s.tablename = c.relname
+            and s.attname = 'block_range'
+          order by c.relname",
+    )
+    .bind::<Text, _>(site.namespace.as_str())
+    .bind::<Integer, _>(cutoff)
+    .load::<TableEstimate>(&conn)?;
+
+    TableEstimate::header();
+    for estimate in &estimates {
+        estimate.print();
+    }
+
+    let versions: i64 = estimates.iter().map(|e| e.removable_versions()).sum();
+    let bytes: u64 = estimates.iter().map(|e| e.reclaimed_bytes()).sum();
+    println!(
+        "\nPruning would remove about {} versions and reclaim about {}",
+        versions,
+        human_bytes(bytes)
+    );
+    println!(
+        "Tables without statistics are not listed; run `graphman stats analyze` \
+         to refresh the statistics of a table"
+    );
+    Ok(())
+}
+
+/// Print the progress of pruning
+struct Progress {
+    start: Instant,
+    table_start: Instant,
+    last_report: Instant,
+}
+
+impl Progress {
+    fn new() -> Self {
+        let now = Instant::now();
+        Progress {
+            start: now,
+            table_start: now,
+            last_report: now,
+        }
+    }
+}
+
+impl PruneReporter for Progress {
+    fn start_table(&mut self, table: &str) {
+        self.table_start = Instant::now();
+        print!("{:<30} | pruning", table);
+        std::io::stdout().flush().ok();
+    }
+
+    fn prune_batch(&mut self, _table: &str, _rows: usize, done: f64) {
+        if self.last_report.elapsed() >= Duration::from_secs(10) {
+            self.last_report = Instant::now();
+            print!(" {:.0}%", done * 100.0);
+            std::io::stdout().flush().ok();
+        }
+    }
+
+    fn start_reindex(&mut self, _table: &str) {
+        print!(" | reindexing");
+        std::io::stdout().flush().ok();
+    }
+
+    fn finish_table(&mut self, _table: &str, rows: usize) {
+        println!(
+            " | removed {} versions in {}s",
+            rows,
+            self.table_start.elapsed().as_secs()
+        );
+    }
+}
+
+/// Remove the entity versions of the deployment that are only needed for
+/// queries at blocks more than `history` blocks behind its current block
+pub fn run(
+    store: Arc<SubgraphStore>,
+    pools: HashMap<Shard, ConnectionPool>,
+    hash: String,
+    shard: Option<String>,
+    history: BlockNumber,
+) -> Result<(), Error> {
+    if history < *REORG_THRESHOLD {
+        bail!(
+            "the history must be at least the reorg threshold of {} blocks",
+            *REORG_THRESHOLD
+        );
+    }
+
+    let (deployment, _, conn) = deployment_and_conn(store.as_ref(), &pools, hash, shard)?;
+    let latest = latest_block(&conn, &deployment)?;
+    drop(conn);
+    let earliest_block = latest - history;
+    if earliest_block <= 0 {
+        println!(
+            "The deployment does not have more than {} blocks of history",
+            history
+        );
+        return Ok(());
+    }
+
+    println!(
+        "Pruning {} to keep history for blocks [{}, {}]",
+        deployment, earliest_block, latest
+    );
+    let mut progress = Progress::new();
+    store.prune(&deployment, earliest_block, &mut progress)?;
+    println!(
+        "Finished pruning in {}s",
+        progress.start.elapsed().as_secs()
+    );
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use graph::prelude::anyhow::Error;
+use graph_store_postgres::SubgraphStore;
+
+use crate::manager::deployment::locate;
+
+pub fn grant(
+    store: Arc<SubgraphStore>,
+    hash: String,
+    shard: Option<String>,
+    print: bool,
+) -> Result<(), Error> {
+    let deployment = locate(store.as_ref(), hash, shard)?;
+    let (role, sql) = store.reader_role(&deployment, true, print)?;
+    if print {
+        print!("{}", sql);
+    } else {
+        println!("role {} can now read the data of {}", role, deployment);
+        println!("give an analyst access with `grant \"{}\" to <role>`", role);
+    }
+    Ok(())
+}
+
+pub fn revoke(
+    store: Arc<SubgraphStore>,
+    hash: String,
+    shard: Option<String>,
+    print: bool,
+) -> Result<(), Error> {
+    let deployment = locate(store.as_ref(), hash, shard)?;
+    let (role, sql) = store.reader_role(&deployment, false, print)?;
+    if print {
+        print!("{}", sql);
+    } else {
+        println!("dropped role {} for {}", role, deployment);
+    }
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use graph::prelude::anyhow::{bail, Error};
+use graph_store_postgres::SubgraphStore;
+
+use crate::manager::deployment::locate;
+use crate::manager::display::prompt_for_confirmation;
+
+pub fn run(
+    store: Arc<SubgraphStore>,
+    hash: String,
+    shard: Option<String>,
+    force: bool,
+) -> Result<(), Error> {
+    let deployment = locate(store.as_ref(), hash, shard)?;
+    let (network, bound, current) = store.chain_binding(&deployment)?;
+
+    let current = match current {
+        Some(current) => current,
+        None => bail!(
+            "there is no chain for network {} of deployment {}",
+            network,
+            deployment
+        ),
+    };
+    let bound = match bound {
+        Some(bound) if bound == current => {
+            println!(
+                "deployment {} is already bound to the chain for {}",
+                deployment, network
+            );
+            return Ok(());
+        }
+        Some(bound) => bound.hash_hex(),
+        None => "no chain".to_string(),
+    };
+
+    let prompt = format!(
+        "Deployment {} is bound to {} but the chain for {} has genesis \
+         block {}. Rebinding it makes the deployment index that chain \
+         on top of the data it already has. Continue?",
+        deployment,
+        bound,
+        network,
+        current.hash_hex()
+    );
+    if !force && !prompt_for_confirmation(&prompt)? {
+        println!("Aborting");
+        return Ok(());
+    }
+
+    let genesis_block_hash = store.rebind(&deployment)?;
+    println!(
+        "deployment {} is now bound to the chain with genesis block {}",
+        deployment,
+        genesis_block_hash.hash_hex()
+    );
+    println!("restart the deployment with `graphman unassign` and `graphman reassign`");
+    Ok(())
+}
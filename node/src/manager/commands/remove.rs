@@ -1,14 +1,96 @@
-use std::sync::Arc;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Instant;
 
 use graph::prelude::{anyhow, Error, SubgraphName, SubgraphStore as _};
-use graph_store_postgres::SubgraphStore;
+use graph_store_postgres::{DeploymentId, SubgraphStore};
 
-pub fn run(store: Arc<SubgraphStore>, name: String) -> Result<(), Error> {
+use crate::manager::display::human_bytes;
+
+pub fn run(store: Arc<SubgraphStore>, name: String, drop: bool) -> Result<(), Error> {
     let name = SubgraphName::new(name.clone())
         .map_err(|()| anyhow!("illegal subgraph name `{}`", name))?;
 
+    // Remember the deployments before the subgraph is gone so that we can
+    // drop the ones that are not used anymore afterwards
+    let deployments = if drop {
+        store.deployments_for_subgraph(&name)?
+    } else {
+        vec![]
+    };
+
     println!("Removing subgraph {}", name);
     store.remove_subgraph(name)?;
 
+    if !deployments.is_empty() {
+        drop_deployments(store, deployments);
+    }
+
     Ok(())
 }
+
+/// Drop the data of `deployments` in the background, one thread per
+/// deployment, so that a deployment whose tables are locked by long-running
+/// queries does not hold up the others. Deployments that are still used by
+/// another subgraph or still assigned are left alone.
+fn drop_deployments(store: Arc<SubgraphStore>, deployments: Vec<DeploymentId>) {
+    let start = Instant::now();
+    let (sender, receiver) = mpsc::channel();
+
+    println!("Dropping data for {} deployment(s)", deployments.len());
+    for id in deployments {
+        let store = store.clone();
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let start = Instant::now();
+            let res = store.remove_deployment(id);
+            // The receiver only goes away when all senders are gone
+            sender.send((id, res, start.elapsed())).ok();
+        });
+    }
+    drop(sender);
+
+    let mut reclaimed = 0;
+    let mut removed = 0;
+    let mut in_use = 0;
+    let mut failed = 0;
+    for (id, res, elapsed) in receiver {
+        match res {
+            Ok(Some(bytes)) => {
+                println!(
+                    "  dropped sgd{} in {:.1}s, reclaimed {}",
+                    id,
+                    elapsed.as_millis() as f64 / 1000.0,
+                    human_bytes(bytes)
+                );
+                reclaimed += bytes;
+                removed += 1;
+            }
+            Ok(None) => {
+                println!("  kept sgd{} since it is still in use", id);
+                in_use += 1;
+            }
+            Err(e) => {
+                println!("  failed to drop sgd{}: {}", id, e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "Dropped {} deployment(s) in {:.1}s and reclaimed {}",
+        removed,
+        start.elapsed().as_millis() as f64 / 1000.0,
+        human_bytes(reclaimed)
+    );
+    if in_use > 0 {
+        println!("{} deployment(s) are still in use and were kept", in_use);
+    }
+    if failed > 0 {
+        println!(
+            "{} deployment(s) could not be dropped; they can be removed later with \
+             `graphman unused record` and `graphman unused remove`",
+            failed
+        );
+    }
+}
@@ -11,9 +11,9 @@ use ethereum::{EthereumNetworks, ProviderEthRpcMetrics};
 use futures::future::join_all;
 use futures::TryFutureExt;
 use graph::anyhow::{format_err, Error};
-use graph::blockchain::{BlockchainKind, BlockchainMap, ChainIdentifier};
+use graph::blockchain::{BlockchainKind, BlockchainMap, ChainIdentifier, REORG_THRESHOLD};
 use graph::cheap_clone::CheapClone;
-use graph::components::store::BlockStore as _;
+use graph::components::store::{BlockStore as _, FallbackBlockTimestamps};
 use graph::firehose::{FirehoseEndpoint, FirehoseEndpoints, FirehoseNetworks};
 use graph::ipfs_client::IpfsClient;
 use graph::prelude::MetricsRegistry as MetricsRegistryTrait;
@@ -29,6 +29,7 @@ use graph_core::{
     SubgraphInstanceManager, SubgraphRegistrar as IpfsSubgraphRegistrar,
 };
 use lazy_static::lazy_static;
+use std::iter::FromIterator;
 use std::str::FromStr;
 
 pub async fn run(
@@ -110,6 +111,11 @@ pub async fn run(
         true,
     );
 
+    let block_timestamps = Arc::new(FallbackBlockTimestamps::new(
+        network_store.block_store(),
+        HashMap::from_iter(vec![(network_name.clone(), chain.block_timestamps())]),
+    ));
+
     let mut blockchain_map = BlockchainMap::new();
     blockchain_map.insert(network_name.clone(), Arc::new(chain));
 
@@ -117,6 +123,7 @@ pub async fn run(
     let subgraph_instance_manager = SubgraphInstanceManager::new(
         &logger_factory,
         subgraph_store.clone(),
+        block_timestamps,
         blockchain_map.clone(),
         metrics_registry.clone(),
         link_resolver.cheap_clone(),
@@ -231,13 +238,6 @@ enum ProviderNetworkStatus {
 const NET_VERSION_WAIT_TIME: Duration = Duration::from_secs(30);
 
 lazy_static! {
-    // Default to an Ethereum reorg threshold to 50 blocks
-    static ref REORG_THRESHOLD: BlockNumber = env::var("ETHEREUM_REORG_THRESHOLD")
-        .ok()
-        .map(|s| BlockNumber::from_str(&s)
-            .unwrap_or_else(|_| panic!("failed to parse env var ETHEREUM_REORG_THRESHOLD")))
-        .unwrap_or(50);
-
     // Default to an ancestor count of 50 blocks
     static ref ANCESTOR_COUNT: BlockNumber = env::var("ETHEREUM_ANCESTOR_COUNT")
         .ok()
@@ -399,6 +399,7 @@ async fn create_firehose_networks(
                     &provider.label,
                     &firehose.url,
                     firehose.token.clone(),
+                    firehose.connection_settings()?,
                 )
                 .await?;
 
@@ -10,14 +10,19 @@ use diesel::RunQueryDsl;
 use graph::components::store::EntityType;
 use graph::prelude::anyhow;
 use graph::prelude::anyhow::bail;
+use graph::prelude::serde_json as json;
 use graph::prelude::DeploymentHash;
+use graph_store_postgres::command_support::catalog::ColumnStorage;
 use graph_store_postgres::command_support::catalog::Site;
+use graph_store_postgres::command_support::catalog::StorageParams;
 use graph_store_postgres::command_support::{catalog as store_catalog, SqlName};
 use graph_store_postgres::connection_pool::ConnectionPool;
 use graph_store_postgres::Shard;
 use graph_store_postgres::SubgraphStore;
 use graph_store_postgres::PRIMARY_SHARD;
 
+use crate::manager::display::OutputFormat;
+
 fn parse_table_name(table: &str) -> Result<(&str, SqlName), anyhow::Error> {
     let mut parts = table.split('.');
     let nsp = parts
@@ -34,7 +39,7 @@ fn parse_table_name(table: &str) -> Result<(&str, SqlName), anyhow::Error> {
     Ok((nsp, table))
 }
 
-fn site_and_conn(
+pub(crate) fn site_and_conn(
     pools: HashMap<Shard, ConnectionPool>,
     nsp: &str,
 ) -> Result<(Site, PooledConnection<ConnectionManager<PgConnection>>), anyhow::Error> {
@@ -65,10 +70,18 @@ pub fn account_like(
     Ok(())
 }
 
+/// Tables need at least this many versions to be suggested as
+/// account-like
+const ACCOUNT_LIKE_MIN_VERSIONS: i32 = 10_000;
+/// Tables where the ratio of entities to versions is below this are
+/// suggested as account-like
+const ACCOUNT_LIKE_MAX_RATIO: f32 = 0.01;
+
 pub fn show(
     pools: HashMap<Shard, ConnectionPool>,
     nsp: String,
     table: Option<String>,
+    format: OutputFormat,
 ) -> Result<(), anyhow::Error> {
     let (site, conn) = site_and_conn(pools, &nsp)?;
 
@@ -91,11 +104,25 @@ pub fn show(
             println!("{:-^30}-+-{:-^10}-+-{:-^10}-+-{:-^7}", "", "", "", "");
         }
 
+        /// Tables with many versions for few entities benefit from the
+        /// account-like optimization
+        fn is_candidate(&self) -> bool {
+            self.versions >= ACCOUNT_LIKE_MIN_VERSIONS
+                && (self.entities as f32) < (self.versions as f32) * ACCOUNT_LIKE_MAX_RATIO
+        }
+
         fn print(&self, account_like: bool) {
+            let flag = if account_like {
+                "(a)"
+            } else if self.is_candidate() {
+                "(c)"
+            } else {
+                "   "
+            };
             println!(
                 "{:<26} {:3} | {:>10} | {:>10} | {:>5.1}%",
                 self.tablename,
-                if account_like { "(a)" } else { "   " },
+                flag,
                 self.entities,
                 self.versions,
                 self.entities as f32 * 100.0 / self.versions as f32
@@ -104,6 +131,19 @@ pub fn show(
 
         fn footer() {
             println!("  (a): account-like flag set");
+            println!(
+                "  (c): candidate for the account-like flag, see `graphman stats account-like`"
+            );
+        }
+
+        fn to_json(&self, account_like: bool) -> json::Value {
+            json::json!({
+                "table": self.tablename,
+                "accountLike": account_like,
+                "accountLikeCandidate": !account_like && self.is_candidate(),
+                "entities": self.entities,
+                "versions": self.versions,
+            })
         }
     }
 
@@ -125,22 +165,17 @@ pub fn show(
 
     let account_like = store_catalog::account_like(&conn, &site)?;
 
-    VersionStats::header();
-    for stat in &stats {
-        stat.print(account_like.contains(&stat.tablename));
-    }
-    VersionStats::footer();
-
-    if let Some(table) = table {
-        if !stats.iter().any(|stat| stat.tablename == table) {
+    if let Some(table) = &table {
+        if !stats.iter().any(|stat| &stat.tablename == table) {
             bail!(
                 "deployment {} does not have a table `{}`",
                 site.namespace,
                 table
             );
         }
+    }
 
-        println!("doing a full count on {}.{} ...", site.namespace, table);
+    let full_count = |table: &str| {
         let query = format!(
             "select count(distinct id)::int4 as entities,
                     count(*)::int4 as versions,
@@ -149,8 +184,33 @@ pub fn show(
             nsp = &site.namespace,
             table = table
         );
-        let stat = sql_query(query).get_result::<VersionStats>(&conn)?;
-        stat.print(account_like.contains(&stat.tablename));
+        sql_query(query).get_result::<VersionStats>(&conn)
+    };
+
+    match format {
+        OutputFormat::Text => {
+            VersionStats::header();
+            for stat in &stats {
+                stat.print(account_like.contains(&stat.tablename));
+            }
+            VersionStats::footer();
+
+            if let Some(table) = table {
+                println!("doing a full count on {}.{} ...", site.namespace, table);
+                let stat = full_count(&table)?;
+                stat.print(account_like.contains(&stat.tablename));
+            }
+        }
+        OutputFormat::Json => {
+            let full_count = table.map(|table| full_count(&table)).transpose()?;
+            let to_json =
+                |stat: &VersionStats| stat.to_json(account_like.contains(&stat.tablename));
+            let output = json::json!({
+                "tables": stats.iter().map(to_json).collect::<Vec<_>>(),
+                "fullCount": full_count.as_ref().map(to_json),
+            });
+            println!("{}", output);
+        }
     }
 
     Ok(())
@@ -174,3 +234,83 @@ pub async fn analyze(
         .await
         .map_err(|e| anyhow!(e))
 }
+
+pub fn set_storage_params(
+    pools: HashMap<Shard, ConnectionPool>,
+    nsp: String,
+    table: Option<String>,
+    params: StorageParams,
+    reset: bool,
+) -> Result<(), anyhow::Error> {
+    let (site, conn) = site_and_conn(pools, &nsp)?;
+
+    let activity = store_catalog::table_activity(&conn, &site.namespace)?;
+    if let Some(table) = &table {
+        if !activity
+            .iter()
+            .any(|activity| &activity.table_name == table)
+        {
+            bail!(
+                "deployment {} does not have a table `{}`",
+                site.namespace,
+                table
+            );
+        }
+    }
+
+    for activity in activity.iter().filter(|activity| {
+        table
+            .as_ref()
+            .map_or(true, |table| &activity.table_name == table)
+    }) {
+        let table_name = SqlName::verbatim(activity.table_name.clone());
+        if reset {
+            store_catalog::reset_storage_params(&conn, &site.namespace, &table_name)?;
+            println!("{}.{}: reset", site.namespace, table_name);
+            continue;
+        }
+        let (params, kind) = if params.is_empty() {
+            let observed = StorageParams::for_activity(activity.inserts, activity.updates);
+            let kind = if observed == StorageParams::append_only() {
+                "append-only"
+            } else {
+                "mutable"
+            };
+            (observed, kind)
+        } else {
+            (params.clone(), "explicit")
+        };
+        store_catalog::set_storage_params(&conn, &site.namespace, &table_name, &params)?;
+        println!(
+            "{}.{}: {} ({}, {} inserts, {} updates)",
+            site.namespace, table_name, params, kind, activity.inserts, activity.updates
+        );
+    }
+    Ok(())
+}
+
+pub fn set_column_storage(
+    pools: HashMap<Shard, ConnectionPool>,
+    nsp: String,
+    table: String,
+    column: String,
+    storage: String,
+) -> Result<(), anyhow::Error> {
+    let storage: ColumnStorage = storage.parse()?;
+    let (site, conn) = site_and_conn(pools, &nsp)?;
+
+    let table_name = SqlName::verbatim(table);
+    let column_name = SqlName::verbatim(column);
+    store_catalog::set_column_storage(
+        &conn,
+        &site.namespace,
+        &table_name,
+        &[&column_name],
+        storage,
+    )?;
+    println!(
+        "{}.{}.{}: {}",
+        site.namespace, table_name, column_name, storage
+    );
+    Ok(())
+}
@@ -3,7 +3,7 @@ use std::{sync::Arc, time::Instant};
 use graph::prelude::{anyhow::Error, chrono};
 use graph_store_postgres::{unused, SubgraphStore, UnusedDeployment};
 
-use crate::manager::display::List;
+use crate::manager::display::{human_bytes, List, OutputFormat};
 
 fn make_list() -> List {
     List::new(vec!["id", "shard", "namespace", "subgraphs", "entities"])
@@ -29,7 +29,7 @@ fn add_row(list: &mut List, deployment: UnusedDeployment) {
     ])
 }
 
-pub fn list(store: Arc<SubgraphStore>, existing: bool) -> Result<(), Error> {
+pub fn list(store: Arc<SubgraphStore>, existing: bool, format: OutputFormat) -> Result<(), Error> {
     let mut list = make_list();
 
     let filter = if existing {
@@ -42,10 +42,10 @@ pub fn list(store: Arc<SubgraphStore>, existing: bool) -> Result<(), Error> {
         add_row(&mut list, deployment);
     }
 
-    if list.is_empty() {
+    if list.is_empty() && format == OutputFormat::Text {
         println!("no unused deployments");
     } else {
-        list.render();
+        list.render_as(format);
     }
 
     Ok(())
@@ -100,6 +100,7 @@ pub fn remove(
         return Ok(());
     }
 
+    let mut reclaimed = 0;
     for (i, deployment) in unused.iter().take(count).enumerate() {
         println!("{:=<36} {:4} {:=<36}", "", i + 1, "");
         println!(
@@ -122,12 +123,20 @@ pub fn remove(
 
         let start = Instant::now();
         match store.remove_deployment(deployment.id) {
-            Ok(()) => {
+            Ok(Some(bytes)) => {
                 println!(
-                    "done removing {} from {} in {:.1}s\n",
+                    "done removing {} from {} in {:.1}s, reclaimed {}\n",
                     deployment.namespace,
                     deployment.shard,
-                    start.elapsed().as_millis() as f64 / 1000.0
+                    start.elapsed().as_millis() as f64 / 1000.0,
+                    human_bytes(bytes)
+                );
+                reclaimed += bytes;
+            }
+            Ok(None) => {
+                println!(
+                    "{} is in use again and was not removed\n",
+                    deployment.namespace
                 );
             }
             Err(e) => {
@@ -135,5 +144,6 @@ pub fn remove(
             }
         }
     }
+    println!("Reclaimed {} in total", human_bytes(reclaimed));
     Ok(())
 }
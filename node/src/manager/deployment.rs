@@ -14,7 +14,7 @@ use graph_store_postgres::connection_pool::ConnectionPool;
 use graph_store_postgres::{command_support::catalog as store_catalog, Shard, SubgraphStore};
 
 use crate::manager::deployment;
-use crate::manager::display::List;
+use crate::manager::display::{List, OutputFormat};
 
 #[derive(Queryable, PartialEq, Eq, Hash, Debug)]
 pub struct Deployment {
@@ -81,7 +81,7 @@ impl Deployment {
         )
     }
 
-    pub fn print_table(deployments: Vec<Self>, statuses: Vec<status::Info>) {
+    pub fn print_table(deployments: Vec<Self>, statuses: Vec<status::Info>, format: OutputFormat) {
         let mut rows = vec![
             "name",
             "status",
@@ -133,7 +133,7 @@ impl Deployment {
             list.append(rows);
         }
 
-        list.render();
+        list.render_as(format);
     }
 }
 
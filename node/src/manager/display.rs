@@ -1,3 +1,31 @@
+use std::str::FromStr;
+
+use graph::prelude::{anyhow::anyhow, serde_json as json, Error};
+
+/// How commands print their results: as tables for humans, or as JSON for
+/// scripts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub const VARIANTS: [&'static str; 2] = ["text", "json"];
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(anyhow!("unknown output format `{}`", s)),
+        }
+    }
+}
+
 pub struct List {
     pub headers: Vec<String>,
     pub rows: Vec<Vec<String>>,
@@ -28,6 +56,32 @@ impl List {
         self.rows.is_empty()
     }
 
+    /// Render the list in `format`. In JSON, the list is an array with one
+    /// object per row that maps each header to the row's value
+    pub fn render_as(&self, format: OutputFormat) {
+        match format {
+            OutputFormat::Text => self.render(),
+            OutputFormat::Json => println!("{}", self.to_json()),
+        }
+    }
+
+    fn to_json(&self) -> json::Value {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                let object = self
+                    .headers
+                    .iter()
+                    .zip(row)
+                    .map(|(header, value)| (header.clone(), json::Value::from(value.as_str())))
+                    .collect::<json::Map<_, _>>();
+                json::Value::Object(object)
+            })
+            .collect();
+        json::Value::Array(rows)
+    }
+
     pub fn render(&self) {
         const LINE_WIDTH: usize = 78;
 
@@ -52,3 +106,34 @@ impl List {
         }
     }
 }
+
+/// Format a number of bytes for humans, e.g., `1.5 GiB`
+pub fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Ask the user to confirm an action by typing `y` or `yes`. Anything else,
+/// including an empty line, counts as a refusal
+pub fn prompt_for_confirmation(prompt: &str) -> Result<bool, std::io::Error> {
+    use std::io::Write;
+
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
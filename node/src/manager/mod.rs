@@ -8,7 +8,7 @@ use graph::{
 pub mod catalog;
 pub mod commands;
 pub mod deployment;
-mod display;
+pub mod display;
 
 /// A dummy subscription manager that always panics
 pub struct PanicSubscriptionManager;
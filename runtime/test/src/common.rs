@@ -42,6 +42,8 @@ fn mock_host_exports(
             event_handlers: vec![],
             call_handlers: vec![],
             block_handlers: vec![],
+            creation_handlers: vec![],
+            handler_timeout: None,
             link: Link {
                 link: "link".to_owned(),
             },
@@ -58,6 +60,8 @@ fn mock_host_exports(
         Arc::new(templates),
         Arc::new(graph_core::LinkResolver::from(IpfsClient::localhost())),
         ens_lookup,
+        test_store::STORE.block_store(),
+        BigDecimalPrecision::default(),
     )
 }
 
@@ -130,6 +134,8 @@ pub fn mock_data_source(path: &str, api_version: Version) -> DataSource {
             event_handlers: vec![],
             call_handlers: vec![],
             block_handlers: vec![],
+            creation_handlers: vec![],
+            handler_timeout: None,
             link: Link {
                 link: "link".to_owned(),
             },
@@ -2,13 +2,17 @@ use graph::data::store::scalar;
 use graph::data::subgraph::*;
 use graph::prelude::web3::types::U256;
 use graph::prelude::*;
-use graph::runtime::AscPtr;
+use graph::runtime::gas::GasCounter;
 use graph::runtime::{asc_get, asc_new, try_asc_get};
+use graph::runtime::{AscHeap, AscPtr, DeterministicHostError};
 use graph::{components::store::*, ipfs_client::IpfsClient};
 use graph_chain_ethereum::{Chain, DataSource};
 use graph_mock::MockMetricsRegistry;
-use graph_runtime_wasm::asc_abi::class::{Array, AscBigInt, AscEntity, AscString, Uint8Array};
-use graph_runtime_wasm::{ExperimentalFeatures, ValidModule, WasmInstance};
+use graph_runtime_wasm::asc_abi::class::{
+    Array, AscBigInt, AscEntity, AscEnum, AscString, Uint8Array, YamlValueKind,
+};
+use graph_runtime_wasm::module::WasmInstanceContext;
+use graph_runtime_wasm::{ExperimentalFeatures, ValidModule, WasmFeatures, WasmInstance};
 use hex;
 use semver::Version;
 use std::collections::{BTreeMap, HashMap};
@@ -87,6 +91,7 @@ fn test_valid_module_and_store_with_timeout(
         metrics_registry,
         deployment_id.as_str(),
         stopwatch_metrics,
+        Arc::new(MemoryUsage::default()),
     ));
 
     let experimental_features = ExperimentalFeatures {
@@ -94,7 +99,13 @@ fn test_valid_module_and_store_with_timeout(
     };
 
     let module = WasmInstance::from_valid_module_with_ctx(
-        Arc::new(ValidModule::new(data_source.mapping.runtime.as_ref()).unwrap()),
+        Arc::new(
+            ValidModule::new(
+                data_source.mapping.runtime.as_ref(),
+                WasmFeatures::for_api_version(&api_version),
+            )
+            .unwrap(),
+        ),
         mock_context(
             deployment.clone(),
             data_source,
@@ -700,11 +711,11 @@ async fn abort_v0_0_5() {
     );
 }
 
-fn test_bytes_to_base58(api_version: Version, gas_used: u64) {
+fn test_bytes_to_base58(wasm_version: Version, api_version: Version, gas_used: u64) {
     let mut module = test_module(
         "bytesToBase58",
         mock_data_source(
-            &wasm_file_path("bytes_to_base58.wasm", api_version.clone()),
+            &wasm_file_path("bytes_to_base58.wasm", wasm_version),
             api_version.clone(),
         ),
         api_version,
@@ -721,12 +732,49 @@ fn test_bytes_to_base58(api_version: Version, gas_used: u64) {
 
 #[tokio::test]
 async fn bytes_to_base58_v0_0_4() {
-    test_bytes_to_base58(API_VERSION_0_0_4, 51577627);
+    test_bytes_to_base58(API_VERSION_0_0_4, API_VERSION_0_0_4, 51577627);
 }
 
 #[tokio::test]
 async fn bytes_to_base58_v0_0_5() {
-    test_bytes_to_base58(API_VERSION_0_0_5, 477157);
+    test_bytes_to_base58(API_VERSION_0_0_5, API_VERSION_0_0_5, 477157);
+}
+
+#[tokio::test]
+async fn bytes_to_base58_v0_0_11() {
+    // Reading the bytes through a view of the Wasm memory must not change
+    // the result or the gas that is charged
+    test_bytes_to_base58(API_VERSION_0_0_5, API_VERSION_0_0_11, 477157);
+}
+
+#[tokio::test]
+async fn memory_limit_v0_0_14() {
+    // Any module built for api version 0.0.5 will do since the limit only
+    // depends on the api version of the mapping. The allocation is larger
+    // than the 512MiB limit, but below the largest block that the
+    // AssemblyScript allocator hands out, so that it fails because the
+    // memory can not grow. Every instance fails the same way
+    for _ in 0..2 {
+        let mut module = test_module(
+            "memoryLimit",
+            mock_data_source(
+                &wasm_file_path("bytes_to_base58.wasm", API_VERSION_0_0_5),
+                API_VERSION_0_0_14,
+            ),
+            API_VERSION_0_0_14,
+        );
+        let bytes = vec![0u8; 768 << 20];
+        let err = module.raw_new(&bytes).unwrap_err();
+        assert!(
+            matches!(err, DeterministicHostError::Other(_)),
+            "unexpected error: {}",
+            err
+        );
+        assert_eq!(
+            "mapping exceeded the memory limit of 512 MiB",
+            err.to_string()
+        );
+    }
 }
 
 fn test_data_source_create(api_version: Version, gas_used: u64) {
@@ -1016,3 +1064,335 @@ fn test_safe_null_ptr_read(api_version: Version) {
 async fn safe_null_ptr_read_0_0_5() {
     test_safe_null_ptr_read(API_VERSION_0_0_5);
 }
+
+#[test]
+fn wasm_features_follow_api_version() {
+    assert_eq!(
+        WasmFeatures::for_api_version(&Version::new(0, 0, 6)),
+        WasmFeatures::default()
+    );
+    assert_eq!(
+        WasmFeatures::for_api_version(&API_VERSION_0_0_7),
+        WasmFeatures {
+            simd: true,
+            bulk_memory: true,
+            strict_floats: false
+        }
+    );
+    assert_eq!(
+        WasmFeatures::for_api_version(&API_VERSION_0_0_8),
+        WasmFeatures {
+            simd: true,
+            bulk_memory: true,
+            strict_floats: true
+        }
+    );
+}
+
+/// A module whose instance context is used to call host functions
+/// directly, without a mapping that calls them
+fn host_fn_module(subgraph_id: &str) -> WasmInstance<Chain> {
+    test_module(
+        subgraph_id,
+        mock_data_source(
+            &wasm_file_path("crypto.wasm", API_VERSION_0_0_5),
+            API_VERSION_0_0_5,
+        ),
+        API_VERSION_0_0_5,
+    )
+}
+
+/// A host function that takes and returns bytes
+type BytesHostFn = fn(
+    &mut WasmInstanceContext<Chain>,
+    &GasCounter,
+    AscPtr<Uint8Array>,
+) -> Result<AscPtr<Uint8Array>, DeterministicHostError>;
+
+/// Call the host function `f` with `input`
+fn call_bytes_fn(
+    module: &WasmInstance<Chain>,
+    f: BytesHostFn,
+    input: &[u8],
+) -> Result<Vec<u8>, DeterministicHostError> {
+    let mut ctx = module.instance_ctx_mut();
+    let input = asc_new(&mut *ctx, input)?;
+    let output = f(&mut *ctx, &GasCounter::new(), input)?;
+    asc_get(&*ctx, output)
+}
+
+#[tokio::test]
+async fn crypto_hashes() {
+    let module = host_fn_module("cryptoHashes");
+    let hash = |f: BytesHostFn, input: &str| {
+        hex::encode(call_bytes_fn(&module, f, input.as_bytes()).unwrap())
+    };
+
+    // Test vectors from FIPS 180-2, the RIPEMD-160 paper and RFC 7693
+    let sha256 = WasmInstanceContext::<Chain>::crypto_sha256;
+    assert_eq!(
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        hash(sha256, "")
+    );
+    assert_eq!(
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        hash(sha256, "abc")
+    );
+
+    let ripemd160 = WasmInstanceContext::<Chain>::crypto_ripemd160;
+    assert_eq!(
+        "9c1185a5c5e9fc54612808977ee8f548b2258d31",
+        hash(ripemd160, "")
+    );
+    assert_eq!(
+        "8eb208f7e05d987a9b044a8e98c6b087f15a0bfc",
+        hash(ripemd160, "abc")
+    );
+
+    let blake2b = WasmInstanceContext::<Chain>::crypto_blake2b;
+    assert_eq!(
+        "786a02f742015903c6c6fd852552d272912f4740e15847618a86e217f71f5419\
+         d25e1031afee585313896444934eb04b903a685b1448b755d56f701afe9be2ce",
+        hash(blake2b, "")
+    );
+    assert_eq!(
+        "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+         7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923",
+        hash(blake2b, "abc")
+    );
+}
+
+#[tokio::test]
+async fn crypto_ecrecover() {
+    let module = host_fn_module("cryptoEcrecover");
+    let ecrecover =
+        |hash: &str, signature: &str| -> Result<Option<String>, DeterministicHostError> {
+            let mut ctx = module.instance_ctx_mut();
+            let hash = asc_new(&mut *ctx, hex::decode(hash).unwrap().as_slice())?;
+            let signature = asc_new(&mut *ctx, hex::decode(signature).unwrap().as_slice())?;
+            let address = ctx.crypto_ecrecover(&GasCounter::new(), hash, signature)?;
+            if address.is_null() {
+                return Ok(None);
+            }
+            let address: Vec<u8> = asc_get(&*ctx, address)?;
+            Ok(Some(hex::encode(address)))
+        };
+
+    // The hash of the message "Some data" as produced by `eth_sign`, signed
+    // with the private key
+    // 4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318
+    const HASH: &str = "1da44b586eb0729ff70a73c326926f6ed5a25f5b056e7f47fbc6e58d86871655";
+    const RS: &str = "57e4bc943401ebb5eff2e0b1c0f7097b6903689a2ec07aa96543323dc9ca54f1\
+                      262d176ed7adbd01801ed3fc5389b8418875bd3d8230fb90042d16257062d751";
+    const SIGNER: &str = "2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+
+    // `v` can be given as 27/28 or as 0/1
+    assert_eq!(
+        Some(SIGNER.to_string()),
+        ecrecover(HASH, &format!("{}1b", RS)).unwrap()
+    );
+    assert_eq!(
+        Some(SIGNER.to_string()),
+        ecrecover(HASH, &format!("{}00", RS)).unwrap()
+    );
+
+    // The wrong recovery id or a different hash do not recover the signer
+    assert_ne!(
+        Some(SIGNER.to_string()),
+        ecrecover(HASH, &format!("{}1c", RS)).unwrap()
+    );
+    let other_hash = format!("00{}", &HASH[2..]);
+    assert_ne!(
+        Some(SIGNER.to_string()),
+        ecrecover(&other_hash, &format!("{}1b", RS)).unwrap()
+    );
+
+    // An invalid recovery id recovers nothing
+    assert_eq!(None, ecrecover(HASH, &format!("{}1d", RS)).unwrap());
+
+    // Hashes and signatures of the wrong length are errors
+    let err = ecrecover(&HASH[2..], &format!("{}1b", RS)).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("the message hash must be 32 bytes long but is 31 bytes long"));
+    let err = ecrecover(HASH, RS).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("the signature must be 65 bytes long but is 64 bytes long"));
+}
+
+#[tokio::test]
+async fn crypto_ed25519_verify() {
+    let module = host_fn_module("cryptoEd25519Verify");
+    let verify =
+        |pubkey: &str, message: &str, signature: &str| -> Result<bool, DeterministicHostError> {
+            let mut ctx = module.instance_ctx_mut();
+            let pubkey = asc_new(&mut *ctx, hex::decode(pubkey).unwrap().as_slice())?;
+            let message = asc_new(&mut *ctx, hex::decode(message).unwrap().as_slice())?;
+            let signature = asc_new(&mut *ctx, hex::decode(signature).unwrap().as_slice())?;
+            ctx.crypto_ed25519_verify(&GasCounter::new(), pubkey, message, signature)
+        };
+
+    // Tests 1 and 2 from RFC 8032, section 7.1
+    const PUBKEY1: &str = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+    const SIG1: &str = "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+                        5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b";
+    const PUBKEY2: &str = "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c";
+    const SIG2: &str = "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+                        085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00";
+
+    assert!(verify(PUBKEY1, "", SIG1).unwrap());
+    assert!(verify(PUBKEY2, "72", SIG2).unwrap());
+
+    // Signatures for a different message or by a different key do not
+    // verify
+    assert!(!verify(PUBKEY2, "73", SIG2).unwrap());
+    assert!(!verify(PUBKEY1, "72", SIG2).unwrap());
+    assert!(!verify(PUBKEY2, "", SIG1).unwrap());
+
+    // A public key that is not a point on the curve does not verify anything
+    let not_a_point = format!("02{}", "00".repeat(31));
+    assert!(!verify(&not_a_point, "", SIG1).unwrap());
+
+    // Public keys and signatures of the wrong length are errors
+    let err = verify(&PUBKEY1[2..], "", SIG1).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("the public key must be 32 bytes long but is 31 bytes long"));
+    let err = verify(PUBKEY1, "", &SIG1[2..]).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("the signature must be 64 bytes long but is 63 bytes long"));
+}
+
+#[tokio::test]
+async fn encoding_hex_and_base64() {
+    let module = host_fn_module("encodingHexAndBase64");
+    type EncodeFn = fn(
+        &mut WasmInstanceContext<Chain>,
+        &GasCounter,
+        AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<AscString>, DeterministicHostError>;
+    type DecodeFn = fn(
+        &mut WasmInstanceContext<Chain>,
+        &GasCounter,
+        AscPtr<AscString>,
+    ) -> Result<AscPtr<Uint8Array>, DeterministicHostError>;
+
+    let encode = |f: EncodeFn, input: &str| -> String {
+        let mut ctx = module.instance_ctx_mut();
+        let input = asc_new(&mut *ctx, input.as_bytes()).unwrap();
+        let output = f(&mut *ctx, &GasCounter::new(), input).unwrap();
+        asc_get(&*ctx, output).unwrap()
+    };
+    let decode = |f: DecodeFn, input: &str| -> Result<String, DeterministicHostError> {
+        let mut ctx = module.instance_ctx_mut();
+        let input = asc_new(&mut *ctx, input)?;
+        let output = f(&mut *ctx, &GasCounter::new(), input)?;
+        let output: Vec<u8> = asc_get(&*ctx, output)?;
+        Ok(String::from_utf8(output).unwrap())
+    };
+
+    let hex_encode = WasmInstanceContext::<Chain>::encoding_hex_encode;
+    let hex_decode = WasmInstanceContext::<Chain>::encoding_hex_decode;
+    let base64_encode = WasmInstanceContext::<Chain>::encoding_base64_encode;
+    let base64_decode = WasmInstanceContext::<Chain>::encoding_base64_decode;
+
+    // Test vectors from RFC 4648, section 10
+    for (plain, hex, base64) in vec![
+        ("", "", ""),
+        ("f", "66", "Zg=="),
+        ("fo", "666f", "Zm8="),
+        ("foo", "666f6f", "Zm9v"),
+        ("foob", "666f6f62", "Zm9vYg=="),
+        ("fooba", "666f6f6261", "Zm9vYmE="),
+        ("foobar", "666f6f626172", "Zm9vYmFy"),
+    ] {
+        assert_eq!(hex, encode(hex_encode, plain));
+        assert_eq!(plain, decode(hex_decode, hex).unwrap());
+        assert_eq!(base64, encode(base64_encode, plain));
+        assert_eq!(plain, decode(base64_decode, base64).unwrap());
+    }
+
+    // Hex strings can have a `0x` prefix and uppercase digits
+    assert_eq!("foo", decode(hex_decode, "0x666F6f").unwrap());
+
+    // Malformed input is an error
+    for malformed in vec!["0x666", "66zz", "0x0x66"] {
+        let err = decode(hex_decode, malformed).unwrap_err();
+        assert!(err.to_string().contains("Failed to decode hex string"));
+    }
+    for malformed in vec!["Zm9v!", "Zm9v\n", "Z"] {
+        let err = decode(base64_decode, malformed).unwrap_err();
+        assert!(err.to_string().contains("Failed to decode base64 string"));
+    }
+}
+
+#[tokio::test]
+async fn yaml_from_bytes() {
+    let module = host_fn_module("yamlFromBytes");
+    let parse = |yaml: &str| -> Result<AscEnum<YamlValueKind>, DeterministicHostError> {
+        let mut ctx = module.instance_ctx_mut();
+        let bytes = asc_new(&mut *ctx, yaml.as_bytes())?;
+        let value = ctx.yaml_from_bytes(&GasCounter::new(), bytes)?;
+        value.read_ptr(&*ctx)
+    };
+    let string = |value: AscEnum<YamlValueKind>| -> String {
+        let ptr: AscPtr<AscString> = value.payload.into();
+        asc_get(&*module.instance_ctx_mut(), ptr).unwrap()
+    };
+
+    assert!(matches!(parse("~").unwrap().kind, YamlValueKind::Null));
+    assert!(matches!(parse("null").unwrap().kind, YamlValueKind::Null));
+
+    let value = parse("true").unwrap();
+    assert!(matches!(value.kind, YamlValueKind::Bool));
+    assert!(bool::from(value.payload));
+
+    // Numbers are passed to mappings as strings
+    let value = parse("-1.5").unwrap();
+    assert!(matches!(value.kind, YamlValueKind::Number));
+    assert_eq!("-1.5", string(value));
+
+    let value = parse("'1.5'").unwrap();
+    assert!(matches!(value.kind, YamlValueKind::String));
+    assert_eq!("1.5", string(value));
+
+    assert!(matches!(
+        parse("[1, two, { three: 3 }]").unwrap().kind,
+        YamlValueKind::Array
+    ));
+    assert!(matches!(
+        parse("a: 1\n2: { d: e }\n").unwrap().kind,
+        YamlValueKind::Object
+    ));
+
+    // Malformed YAML is an error for `yaml.fromBytes`, but is returned as
+    // an error result from `yaml.try_fromBytes`
+    for malformed in vec!["a: [1, 2", "a: b: c", "{ a: 1"] {
+        let err = parse(malformed).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Failed to parse YAML from byte array"));
+
+        let mut ctx = module.instance_ctx_mut();
+        let bytes = asc_new(&mut *ctx, malformed.as_bytes()).unwrap();
+        let result = ctx
+            .yaml_try_from_bytes(&GasCounter::new(), bytes)
+            .unwrap()
+            .read_ptr(&*ctx)
+            .unwrap();
+        assert!(result.value.is_null());
+        assert!(!result.error.is_null());
+    }
+
+    let mut ctx = module.instance_ctx_mut();
+    let bytes = asc_new(&mut *ctx, "a: 1".as_bytes()).unwrap();
+    let result = ctx
+        .yaml_try_from_bytes(&GasCounter::new(), bytes)
+        .unwrap()
+        .read_ptr(&*ctx)
+        .unwrap();
+    assert!(!result.value.is_null());
+    assert!(result.error.is_null());
+}
@@ -1,10 +1,11 @@
 use crate::asc_abi::{v0_0_4, v0_0_5};
 use ethabi;
+use graph::data::subgraph::{API_VERSION_0_0_11, API_VERSION_0_0_13};
 use graph::{
     data::store,
     runtime::{AscHeap, AscIndexId, AscType, AscValue, IndexForAscTypeId},
 };
-use graph::{prelude::serde_json, runtime::DeterministicHostError};
+use graph::{prelude::serde_json, prelude::serde_yaml, runtime::DeterministicHostError};
 use graph::{prelude::slog, runtime::AscPtr};
 use graph_runtime_derive::AscType;
 use semver::Version;
@@ -103,6 +104,9 @@ impl<T: AscValue> TypedArray<T> {
     ) -> Result<Vec<T>, DeterministicHostError> {
         match self {
             Self::ApiVersion0_0_4(t) => t.to_vec(heap),
+            Self::ApiVersion0_0_5(t) if heap.api_version() >= API_VERSION_0_0_11 => {
+                t.to_vec_from_view(heap)
+            }
             Self::ApiVersion0_0_5(t) => t.to_vec(heap),
         }
     }
@@ -304,6 +308,10 @@ impl AscIndexId for Array<Uint8Array> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayUint8Array;
 }
 
+impl AscIndexId for Array<AscPtr<Uint8Array>> {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayUint8Array;
+}
+
 impl AscIndexId for Array<AscPtr<AscEnum<EthereumValueKind>>> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayEthereumValue;
 }
@@ -316,6 +324,10 @@ impl AscIndexId for Array<AscPtr<AscEnum<JsonValueKind>>> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayJsonValue;
 }
 
+impl AscIndexId for Array<AscPtr<AscEnum<YamlValueKind>>> {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayYamlValue;
+}
+
 impl AscIndexId for Array<AscPtr<AscString>> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayString;
 }
@@ -330,6 +342,13 @@ impl AscIndexId for Array<AscPtr<AscTypedMapEntry<AscString, AscEnum<StoreValueK
         IndexForAscTypeId::ArrayTypedMapEntryStringStoreValue;
 }
 
+impl AscIndexId
+    for Array<AscPtr<AscTypedMapEntry<AscEnum<YamlValueKind>, AscEnum<YamlValueKind>>>>
+{
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId =
+        IndexForAscTypeId::ArrayTypedMapEntryYamlValueYamlValue;
+}
+
 impl AscIndexId for Array<u8> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayU8;
 }
@@ -469,6 +488,10 @@ impl AscIndexId for AscEnum<JsonValueKind> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::JsonValue;
 }
 
+impl AscIndexId for AscEnum<YamlValueKind> {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::YamlValue;
+}
+
 pub type AscEnumArray<D> = AscPtr<Array<AscPtr<AscEnum<D>>>>;
 
 #[repr(u32)]
@@ -573,6 +596,10 @@ impl AscIndexId for AscTypedMapEntry<AscString, AscEnum<JsonValueKind>> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::TypedMapEntryStringJsonValue;
 }
 
+impl AscIndexId for AscTypedMapEntry<AscEnum<YamlValueKind>, AscEnum<YamlValueKind>> {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::TypedMapEntryYamlValueYamlValue;
+}
+
 pub(crate) type AscTypedMapEntryArray<K, V> = Array<AscPtr<AscTypedMapEntry<K, V>>>;
 
 #[repr(C)]
@@ -581,12 +608,67 @@ pub struct AscTypedMap<K, V> {
     pub entries: AscPtr<AscTypedMapEntryArray<K, V>>,
 }
 
-impl AscIndexId for AscTypedMap<AscString, AscEnum<StoreValueKind>> {
+/// Wrapper of the maps with string keys that entities and JSON objects are
+/// passed as. Up to api version 0.0.12 they are a `TypedMap`, a flat array
+/// of entries, and starting with 0.0.13 they have the layout of
+/// AssemblyScript's `Map`, in which mappings can look up keys by their hash.
+pub enum AscStringMap<V> {
+    Flat(AscTypedMap<AscString, V>),
+    Hashed(v0_0_5::Map<AscString, V>),
+}
+
+impl<V> AscType for AscStringMap<V> {
+    fn to_asc_bytes(&self) -> Result<Vec<u8>, DeterministicHostError> {
+        match self {
+            Self::Flat(map) => map.to_asc_bytes(),
+            Self::Hashed(map) => map.to_asc_bytes(),
+        }
+    }
+
+    fn from_asc_bytes(
+        asc_obj: &[u8],
+        api_version: &Version,
+    ) -> Result<Self, DeterministicHostError> {
+        match api_version {
+            version if *version >= API_VERSION_0_0_13 => Ok(Self::Hashed(
+                v0_0_5::Map::from_asc_bytes(asc_obj, api_version)?,
+            )),
+            _ => Ok(Self::Flat(AscTypedMap::from_asc_bytes(
+                asc_obj,
+                api_version,
+            )?)),
+        }
+    }
+
+    fn asc_size<H: AscHeap + ?Sized>(
+        _ptr: AscPtr<Self>,
+        _heap: &H,
+    ) -> Result<u32, DeterministicHostError> {
+        // Only used for old api versions, which always use `TypedMap`
+        Ok(std::mem::size_of::<AscTypedMap<AscString, V>>() as u32)
+    }
+}
+
+impl AscIndexId for AscStringMap<AscEnum<StoreValueKind>> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::TypedMapStringStoreValue;
+
+    fn index_asc_type_id(&self) -> IndexForAscTypeId {
+        match self {
+            Self::Flat(_) => Self::INDEX_ASC_TYPE_ID,
+            Self::Hashed(_) => IndexForAscTypeId::MapStringStoreValue,
+        }
+    }
 }
 
-impl AscIndexId for AscTypedMap<AscString, AscEnum<JsonValueKind>> {
+impl AscIndexId for AscStringMap<AscEnum<JsonValueKind>> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::TypedMapStringJsonValue;
+
+    fn index_asc_type_id(&self) -> IndexForAscTypeId {
+        match self {
+            Self::Flat(_) => Self::INDEX_ASC_TYPE_ID,
+            Self::Hashed(_) => IndexForAscTypeId::MapStringJsonValue,
+        }
+    }
 }
 
 impl AscIndexId for AscTypedMap<AscString, AscTypedMap<AscString, AscEnum<JsonValueKind>>> {
@@ -594,8 +676,13 @@ impl AscIndexId for AscTypedMap<AscString, AscTypedMap<AscString, AscEnum<JsonVa
         IndexForAscTypeId::TypedMapStringTypedMapStringJsonValue;
 }
 
-pub type AscEntity = AscTypedMap<AscString, AscEnum<StoreValueKind>>;
-pub(crate) type AscJson = AscTypedMap<AscString, AscEnum<JsonValueKind>>;
+pub type AscEntity = AscStringMap<AscEnum<StoreValueKind>>;
+impl AscIndexId for AscTypedMap<AscEnum<YamlValueKind>, AscEnum<YamlValueKind>> {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::TypedMapYamlValueYamlValue;
+}
+
+pub(crate) type AscJson = AscStringMap<AscEnum<JsonValueKind>>;
+pub(crate) type AscYamlMap = AscTypedMap<AscEnum<YamlValueKind>, AscEnum<YamlValueKind>>;
 
 #[repr(u32)]
 #[derive(AscType, Copy, Clone)]
@@ -631,6 +718,41 @@ impl JsonValueKind {
     }
 }
 
+/// Unlike JSON objects, the keys of YAML mappings can be any YAML value
+#[repr(u32)]
+#[derive(AscType, Copy, Clone)]
+pub enum YamlValueKind {
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl Default for YamlValueKind {
+    fn default() -> Self {
+        YamlValueKind::Null
+    }
+}
+
+impl AscValue for YamlValueKind {}
+
+impl YamlValueKind {
+    pub(crate) fn get_kind(value: &serde_yaml::Value) -> Self {
+        use serde_yaml::Value;
+
+        match value {
+            Value::Null => YamlValueKind::Null,
+            Value::Bool(_) => YamlValueKind::Bool,
+            Value::Number(_) => YamlValueKind::Number,
+            Value::String(_) => YamlValueKind::String,
+            Value::Sequence(_) => YamlValueKind::Array,
+            Value::Mapping(_) => YamlValueKind::Object,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(AscType)]
 pub struct AscBigDecimal {
@@ -681,6 +803,10 @@ impl AscIndexId for AscResult<AscPtr<AscEnum<JsonValueKind>>, bool> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ResultJsonValueBool;
 }
 
+impl AscIndexId for AscResult<AscPtr<AscEnum<YamlValueKind>>, bool> {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ResultYamlValueBool;
+}
+
 #[repr(C)]
 #[derive(AscType)]
 pub struct AscWrapped<V: AscValue> {
@@ -699,6 +825,10 @@ impl AscIndexId for AscWrapped<AscPtr<AscEnum<JsonValueKind>>> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::WrappedJsonValue;
 }
 
+impl AscIndexId for AscWrapped<AscPtr<AscEnum<YamlValueKind>>> {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::WrappedYamlValue;
+}
+
 impl<V: AscValue> Copy for AscWrapped<V> {}
 
 impl<V: AscValue> Clone for AscWrapped<V> {
@@ -1,6 +1,10 @@
 use crate::asc_abi::class;
 use anyhow::anyhow;
-use graph::runtime::{AscHeap, AscPtr, AscType, AscValue, DeterministicHostError, HEADER_SIZE};
+use graph::data::subgraph::API_VERSION_0_0_9;
+use graph::runtime::{
+    AscHeap, AscIndexId, AscPtr, AscType, AscValue, DeterministicHostError, IndexForAscTypeId,
+    HEADER_SIZE,
+};
 use graph_runtime_derive::AscType;
 use semver::Version;
 use std::marker::PhantomData;
@@ -153,6 +157,39 @@ impl<T: AscValue> TypedArray<T> {
             heap.api_version(),
         )
     }
+
+    /// Like `to_vec`, but reads the elements through a view of the heap
+    /// instead of first copying the whole backing `ArrayBuffer` out of it.
+    /// The elements must lie within the backing buffer.
+    pub(crate) fn to_vec_from_view<H: AscHeap + ?Sized>(
+        &self,
+        heap: &H,
+    ) -> Result<Vec<T>, DeterministicHostError> {
+        self.buffer.check_is_not_null()?;
+
+        let out_of_bounds = || {
+            DeterministicHostError::from(anyhow!(
+                "TypedArray with data start {} and length {} is not within its buffer at {}",
+                self.data_start,
+                self.byte_length,
+                self.buffer.wasm_ptr()
+            ))
+        };
+        let end = self
+            .data_start
+            .checked_sub(self.buffer.wasm_ptr())
+            .and_then(|offset| offset.checked_add(self.byte_length))
+            .ok_or_else(out_of_bounds)?;
+        if end > self.buffer.read_len(heap)? {
+            return Err(out_of_bounds());
+        }
+
+        let api_version = heap.api_version();
+        heap.get_view(self.data_start, self.byte_length)?
+            .chunks_exact(size_of::<T>())
+            .map(|asc_obj| T::from_asc_bytes(asc_obj, &api_version))
+            .collect()
+    }
 }
 
 /// Asc std string: "Strings are encoded as UTF-16LE in AssemblyScript"
@@ -205,8 +242,17 @@ impl AscType for AscString {
     /// The Rust representation of an Asc object as layed out in Asc memory.
     fn from_asc_bytes(
         asc_obj: &[u8],
-        _api_version: &Version,
+        api_version: &Version,
     ) -> Result<Self, DeterministicHostError> {
+        // Strings that newer compilers put into static data can have an
+        // `rt_size` that also counts a trailing padding byte. That byte is
+        // not part of any code unit and is dropped
+        let asc_obj = if api_version >= &API_VERSION_0_0_9 {
+            &asc_obj[..asc_obj.len() - asc_obj.len() % 2]
+        } else {
+            asc_obj
+        };
+
         // UTF-16 (used in assemblyscript) always uses one
         // pair of bytes per code unit.
         // https://mathiasbynens.be/notes/javascript-encoding
@@ -303,3 +349,319 @@ impl<T: AscValue> Array<T> {
         )
     }
 }
+
+/// The hash that AssemblyScript's `Map` uses for string keys, FNV-1a over
+/// the bytes of the UTF-16 code units of the string.
+pub(crate) fn string_hash(content: &[u16]) -> u32 {
+    const FNV_OFFSET: u32 = 2166136261;
+    const FNV_PRIME: u32 = 16777619;
+
+    content
+        .iter()
+        .flat_map(|code_unit| code_unit.to_le_bytes())
+        .fold(FNV_OFFSET, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(FNV_PRIME)
+        })
+}
+
+/// The flag in `MapEntry::tagged_next` that marks deleted entries.
+const MAP_ENTRY_EMPTY: u32 = 1;
+
+/// An entry of a `Map`, stored inline in its entries buffer.
+#[repr(C)]
+#[derive(AscType)]
+pub struct MapEntry<K, V> {
+    pub key: AscPtr<K>,
+    pub value: AscPtr<V>,
+    // The next entry in the same bucket, or 0. The lowest bit is set if
+    // the entry has been deleted
+    pub tagged_next: u32,
+}
+
+/// The `ArrayBuffer` that holds the entries of a `Map`, with room for
+/// `Map::entries_capacity` entries.
+pub struct MapEntries<K, V> {
+    buffer: ArrayBuffer,
+    ty: PhantomData<(K, V)>,
+}
+
+impl<K, V> MapEntries<K, V> {
+    fn new(entries: &[MapEntry<K, V>], capacity: usize) -> Result<Self, DeterministicHostError> {
+        let mut content = Vec::with_capacity(capacity * size_of::<MapEntry<K, V>>());
+        for entry in entries {
+            content.extend(entry.to_asc_bytes()?);
+        }
+        content.resize(capacity * size_of::<MapEntry<K, V>>(), 0);
+        Ok(MapEntries {
+            buffer: ArrayBuffer::new(&content)?,
+            ty: PhantomData,
+        })
+    }
+
+    fn get(
+        &self,
+        count: usize,
+        api_version: &Version,
+    ) -> Result<Vec<MapEntry<K, V>>, DeterministicHostError> {
+        self.buffer
+            .content
+            .chunks(size_of::<MapEntry<K, V>>())
+            .take(count)
+            .map(|entry| MapEntry::from_asc_bytes(entry, api_version))
+            .collect()
+    }
+}
+
+impl<K, V> AscType for MapEntries<K, V> {
+    fn to_asc_bytes(&self) -> Result<Vec<u8>, DeterministicHostError> {
+        self.buffer.to_asc_bytes()
+    }
+
+    fn from_asc_bytes(
+        asc_obj: &[u8],
+        api_version: &Version,
+    ) -> Result<Self, DeterministicHostError> {
+        Ok(MapEntries {
+            buffer: ArrayBuffer::from_asc_bytes(asc_obj, api_version)?,
+            ty: PhantomData,
+        })
+    }
+
+    fn content_len(&self, asc_bytes: &[u8]) -> usize {
+        self.buffer.content_len(asc_bytes)
+    }
+}
+
+impl<K, V> AscIndexId for MapEntries<K, V> {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayBuffer;
+}
+
+/// AssemblyScript's `Map`, a hash table that keeps its entries in insertion
+/// order and chains the entries of each bucket.
+/// See https://github.com/AssemblyScript/assemblyscript/blob/v0.19.10/std/assembly/map.ts
+#[repr(C)]
+#[derive(AscType)]
+pub struct Map<K, V> {
+    // The first entry of each bucket, `usize[buckets_mask + 1]`
+    buckets: AscPtr<ArrayBuffer>,
+    buckets_mask: u32,
+    // The entries in insertion order, `MapEntry<K, V>[entries_capacity]`
+    entries: AscPtr<MapEntries<K, V>>,
+    entries_capacity: i32,
+    // The number of entries in use, including deleted ones
+    entries_offset: i32,
+    // The number of entries that have not been deleted
+    entries_count: i32,
+}
+
+impl<K, V> Map<K, V> {
+    /// Allocate the buckets and entries for `entries`, a list of keys,
+    /// values and the hashes of the keys, as if they had been inserted with
+    /// `Map.set` in that order. The keys must be distinct.
+    pub(crate) fn new<H: AscHeap + ?Sized>(
+        entries: &[(AscPtr<K>, AscPtr<V>, u32)],
+        heap: &mut H,
+    ) -> Result<Self, DeterministicHostError> {
+        // Grow like `Map.set` does, which rehashes into twice as many buckets
+        // when the entries don't fit anymore
+        const INITIAL_CAPACITY: usize = 4;
+        const FILL_FACTOR_N: usize = 8;
+        const FILL_FACTOR_D: usize = 3;
+        let mut buckets_len = INITIAL_CAPACITY;
+        while buckets_len * FILL_FACTOR_N / FILL_FACTOR_D < entries.len() {
+            buckets_len *= 2;
+        }
+        let buckets_mask = (buckets_len - 1) as u32;
+        let entries_capacity = buckets_len * FILL_FACTOR_N / FILL_FACTOR_D;
+
+        // The entries of a bucket are linked by their address, which is only
+        // known once the entries buffer is allocated
+        let mut map_entries: Vec<MapEntry<K, V>> = entries
+            .iter()
+            .map(|(key, value, _)| MapEntry {
+                key: AscPtr::new(key.wasm_ptr()),
+                value: AscPtr::new(value.wasm_ptr()),
+                tagged_next: 0,
+            })
+            .collect();
+        let entries_ptr =
+            AscPtr::alloc_obj(MapEntries::new(&map_entries, entries_capacity)?, heap)?;
+
+        let entry_size = size_of::<MapEntry<K, V>>() as u32;
+        let mut buckets = vec![0u32; buckets_len];
+        for (i, (entry, (_, _, hash))) in map_entries.iter_mut().zip(entries).enumerate() {
+            let bucket = &mut buckets[(hash & buckets_mask) as usize];
+            entry.tagged_next = *bucket;
+            *bucket = entries_ptr.wasm_ptr() + i as u32 * entry_size;
+        }
+        let mut content = Vec::with_capacity(map_entries.len() * entry_size as usize);
+        for entry in &map_entries {
+            content.extend(entry.to_asc_bytes()?);
+        }
+        heap.raw_write(entries_ptr.wasm_ptr(), &content)?;
+
+        let buckets = class::ArrayBuffer::new(&buckets, heap.api_version())?;
+        let buckets_ptr = AscPtr::alloc_obj(buckets, heap)?;
+
+        Ok(Map {
+            buckets: AscPtr::new(buckets_ptr.wasm_ptr()),
+            buckets_mask,
+            entries: entries_ptr,
+            entries_capacity: entries_capacity as i32,
+            entries_offset: entries.len() as i32,
+            entries_count: entries.len() as i32,
+        })
+    }
+
+    /// The keys and values of the entries that have not been deleted, in
+    /// insertion order.
+    pub(crate) fn entries<H: AscHeap + ?Sized>(
+        &self,
+        heap: &H,
+    ) -> Result<Vec<(AscPtr<K>, AscPtr<V>)>, DeterministicHostError> {
+        self.entries.check_is_not_null()?;
+
+        let entries = self.entries.read_ptr(heap)?;
+        Ok(entries
+            .get(self.entries_offset.max(0) as usize, &heap.api_version())?
+            .into_iter()
+            .filter(|entry| entry.tagged_next & MAP_ENTRY_EMPTY == 0)
+            .map(|entry| (entry.key, entry.value))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::runtime::{asc_get, asc_new};
+    use std::convert::TryInto;
+
+    #[test]
+    fn string_with_odd_byte_length() {
+        let bytes = [b'h', 0, b'i', 0, 0];
+
+        let err = AscString::from_asc_bytes(&bytes, &Version::new(0, 0, 8));
+        assert!(err.is_err());
+
+        let string = AscString::from_asc_bytes(&bytes, &API_VERSION_0_0_9).unwrap();
+        assert_eq!(&[b'h' as u16, b'i' as u16][..], &*string.content);
+        assert_eq!(4, string.content_len(&bytes));
+    }
+
+    #[test]
+    fn string_hash_is_fnv_1a_over_utf16() {
+        let utf16 = |s: &str| s.encode_utf16().collect::<Vec<_>>();
+
+        assert_eq!(2166136261, string_hash(&utf16("")));
+        assert_eq!(723832900, string_hash(&utf16("a")));
+        assert_eq!(
+            string_hash(&[b'i' as u16, b'd' as u16]),
+            string_hash(&utf16("id"))
+        );
+    }
+
+    /// A heap that is just a vector of bytes
+    struct BytesHeap {
+        memory: Vec<u8>,
+    }
+
+    impl AscHeap for BytesHeap {
+        fn raw_new(&mut self, bytes: &[u8]) -> Result<u32, DeterministicHostError> {
+            self.memory.extend_from_slice(bytes);
+            Ok((self.memory.len() - bytes.len()) as u32)
+        }
+
+        fn raw_write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), DeterministicHostError> {
+            let start = offset as usize;
+            self.memory
+                .get_mut(start..start + bytes.len())
+                .ok_or_else(|| anyhow!("write at offset {} is out of bounds", offset))?
+                .copy_from_slice(bytes);
+            Ok(())
+        }
+
+        fn get(&self, offset: u32, size: u32) -> Result<Vec<u8>, DeterministicHostError> {
+            let start = offset as usize;
+            self.memory
+                .get(start..start + size as usize)
+                .map(Vec::from)
+                .ok_or_else(|| anyhow!("read at offset {} is out of bounds", offset).into())
+        }
+
+        fn api_version(&self) -> Version {
+            Version::new(0, 0, 6)
+        }
+
+        fn asc_type_id(
+            &mut self,
+            type_id_index: IndexForAscTypeId,
+        ) -> Result<u32, DeterministicHostError> {
+            Ok(type_id_index as u32)
+        }
+    }
+
+    #[test]
+    fn map_round_trip() {
+        type StringMap = Map<class::AscString, class::AscString>;
+        type StringMapEntry = MapEntry<class::AscString, class::AscString>;
+
+        let mut heap = BytesHeap { memory: vec![0; 8] };
+        let hash = |key: &str| string_hash(&key.encode_utf16().collect::<Vec<_>>());
+
+        // Enough entries that the map has to grow past its initial capacity
+        let entries: Vec<(String, String)> = (0..20)
+            .map(|i| (format!("key{}", i), format!("value{}", i)))
+            .collect();
+        let mut asc_entries = Vec::new();
+        for (key, value) in &entries {
+            let key_ptr: AscPtr<class::AscString> = asc_new(&mut heap, key.as_str()).unwrap();
+            let value_ptr: AscPtr<class::AscString> = asc_new(&mut heap, value.as_str()).unwrap();
+            asc_entries.push((key_ptr, value_ptr, hash(key)));
+        }
+
+        let map = StringMap::new(&asc_entries, &mut heap).unwrap();
+        assert_eq!(7, map.buckets_mask);
+        assert_eq!(21, map.entries_capacity);
+        assert_eq!(20, map.entries_offset);
+        assert_eq!(20, map.entries_count);
+
+        // The map survives a trip through its memory layout, and its
+        // entries come back in insertion order
+        let map =
+            StringMap::from_asc_bytes(&map.to_asc_bytes().unwrap(), &heap.api_version()).unwrap();
+        let actual: Vec<(String, String)> = map
+            .entries(&heap)
+            .unwrap()
+            .into_iter()
+            .map(|(key, value)| (asc_get(&heap, key).unwrap(), asc_get(&heap, value).unwrap()))
+            .collect();
+        assert_eq!(entries, actual);
+
+        // Every key can be found the way `Map.find` looks for it, by
+        // following the chain of entries in the bucket for its hash
+        let buckets = map.buckets.read_ptr(&heap).unwrap();
+        let find = |key: &str| -> Option<String> {
+            let bucket = (hash(key) & map.buckets_mask) as usize * size_of::<u32>();
+            let mut next = u32::from_le_bytes(
+                buckets.content[bucket..bucket + size_of::<u32>()]
+                    .try_into()
+                    .unwrap(),
+            );
+            while next != 0 {
+                let bytes = heap.get(next, size_of::<StringMapEntry>() as u32).unwrap();
+                let entry = StringMapEntry::from_asc_bytes(&bytes, &heap.api_version()).unwrap();
+                let entry_key: String = asc_get(&heap, entry.key).unwrap();
+                if entry_key == key {
+                    return Some(asc_get(&heap, entry.value).unwrap());
+                }
+                next = entry.tagged_next & !MAP_ENTRY_EMPTY;
+            }
+            None
+        };
+        for (key, value) in &entries {
+            assert_eq!(Some(value), find(key).as_ref());
+        }
+        assert_eq!(None, find("key20"));
+    }
+}
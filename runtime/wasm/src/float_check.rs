@@ -0,0 +1,288 @@
+//! Reject modules whose handler code uses floating point numbers
+//!
+//! Wasm floating point arithmetic is deterministic except for the bit
+//! patterns of NaNs, which we canonicalize, but float results are easily
+//! affected by things like the order of operations that compilers are free
+//! to change between versions, and they are a common source of proof of
+//! indexing divergences. Mappings with an api version that enables strict
+//! floats therefore must not use floats at all; they can use the
+//! fixed-point `BigDecimal` helpers instead.
+//!
+//! Only code that can be reached from an exported function is checked, so
+//! that unused library code that happens to use floats does not cause a
+//! module to be rejected. Any function in a table is considered reachable
+//! from an indirect call. SIMD instructions are rejected in their entirety
+//! since their lanes can hold floats.
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Error};
+use parity_wasm::elements::{
+    External, Func, FunctionType, Instruction, Internal, Module, Type, ValueType,
+};
+
+/// Check that no function reachable from an export of `module` uses
+/// floats, either in its signature, its locals or its instructions
+pub(crate) fn check_no_floats(module: &Module) -> Result<(), Error> {
+    let imported_funcs = module
+        .import_section()
+        .map(|imports| {
+            imports
+                .entries()
+                .iter()
+                .filter(|entry| matches!(entry.external(), External::Function(_)))
+                .count()
+        })
+        .unwrap_or(0) as u32;
+    let funcs: &[Func] = module
+        .function_section()
+        .map(|section| section.entries())
+        .unwrap_or(&[]);
+    let bodies = module
+        .code_section()
+        .map(|section| section.bodies())
+        .unwrap_or(&[]);
+    let types: &[Type] = module
+        .type_section()
+        .map(|section| section.types())
+        .unwrap_or(&[]);
+
+    let table_funcs: Vec<u32> = module
+        .elements_section()
+        .map(|section| {
+            section
+                .entries()
+                .iter()
+                .flat_map(|segment| segment.members().iter().cloned())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut pending: Vec<u32> = module
+        .export_section()
+        .map(|exports| {
+            exports
+                .entries()
+                .iter()
+                .filter_map(|export| match export.internal() {
+                    Internal::Function(idx) => Some(*idx),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    pending.extend(module.start_section());
+
+    let mut seen = HashSet::new();
+    while let Some(idx) = pending.pop() {
+        if !seen.insert(idx) || idx < imported_funcs {
+            // Imports are host functions, which do not use floats
+            continue;
+        }
+        let local_idx = (idx - imported_funcs) as usize;
+        let (func, body) = match (funcs.get(local_idx), bodies.get(local_idx)) {
+            (Some(func), Some(body)) => (func, body),
+            _ => return Err(anyhow!("function {} is not defined in the module", idx)),
+        };
+
+        let Type::Function(signature) = types
+            .get(func.type_ref() as usize)
+            .ok_or_else(|| anyhow!("function {} has an invalid type", idx))?;
+        if has_float_type(signature) {
+            return Err(anyhow!(
+                "function {} has floating point parameters or results, which \
+                 this api version does not allow",
+                idx
+            ));
+        }
+        if body
+            .locals()
+            .iter()
+            .any(|local| is_float(&local.value_type()))
+        {
+            return Err(anyhow!(
+                "function {} has floating point locals, which this api version \
+                 does not allow",
+                idx
+            ));
+        }
+
+        for instruction in body.code().elements() {
+            if is_float_instruction(instruction) {
+                return Err(anyhow!(
+                    "function {} uses the floating point instruction `{}`, which \
+                     this api version does not allow",
+                    idx,
+                    instruction
+                ));
+            }
+            match instruction {
+                Instruction::Call(callee) => pending.push(*callee),
+                Instruction::CallIndirect(_, _) => pending.extend(table_funcs.iter().cloned()),
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_float(value_type: &ValueType) -> bool {
+    matches!(
+        value_type,
+        ValueType::F32 | ValueType::F64 | ValueType::V128
+    )
+}
+
+fn has_float_type(signature: &FunctionType) -> bool {
+    signature.params().iter().any(is_float) || signature.results().iter().any(is_float)
+}
+
+fn is_float_instruction(instruction: &Instruction) -> bool {
+    use Instruction::*;
+
+    matches!(
+        instruction,
+        F32Load(_, _)
+            | F64Load(_, _)
+            | F32Store(_, _)
+            | F64Store(_, _)
+            | F32Const(_)
+            | F64Const(_)
+            | F32Eq
+            | F32Ne
+            | F32Lt
+            | F32Gt
+            | F32Le
+            | F32Ge
+            | F64Eq
+            | F64Ne
+            | F64Lt
+            | F64Gt
+            | F64Le
+            | F64Ge
+            | F32Abs
+            | F32Neg
+            | F32Ceil
+            | F32Floor
+            | F32Trunc
+            | F32Nearest
+            | F32Sqrt
+            | F32Add
+            | F32Sub
+            | F32Mul
+            | F32Div
+            | F32Min
+            | F32Max
+            | F32Copysign
+            | F64Abs
+            | F64Neg
+            | F64Ceil
+            | F64Floor
+            | F64Trunc
+            | F64Nearest
+            | F64Sqrt
+            | F64Add
+            | F64Sub
+            | F64Mul
+            | F64Div
+            | F64Min
+            | F64Max
+            | F64Copysign
+            | I32TruncSF32
+            | I32TruncUF32
+            | I32TruncSF64
+            | I32TruncUF64
+            | I64TruncSF32
+            | I64TruncUF32
+            | I64TruncSF64
+            | I64TruncUF64
+            | F32ConvertSI32
+            | F32ConvertUI32
+            | F32ConvertSI64
+            | F32ConvertUI64
+            | F32DemoteF64
+            | F64ConvertSI32
+            | F64ConvertUI32
+            | F64ConvertSI64
+            | F64ConvertUI64
+            | F64PromoteF32
+            | I32ReinterpretF32
+            | I64ReinterpretF64
+            | F32ReinterpretI32
+            | F64ReinterpretI64
+            | Simd(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parity_wasm::builder;
+    use parity_wasm::elements::Instructions;
+
+    /// A module that exports `handler`, which calls `helper`; `helper` is
+    /// made up of `helper_code`
+    fn module(helper_code: Vec<Instruction>, unused_code: Vec<Instruction>) -> Module {
+        let mut code = helper_code;
+        code.push(Instruction::End);
+        let mut unused = unused_code;
+        unused.push(Instruction::End);
+
+        builder::module()
+            // Function 0: the handler
+            .function()
+            .signature()
+            .build()
+            .body()
+            .with_instructions(Instructions::new(vec![
+                Instruction::Call(1),
+                Instruction::End,
+            ]))
+            .build()
+            .build()
+            // Function 1: the helper
+            .function()
+            .signature()
+            .build()
+            .body()
+            .with_instructions(Instructions::new(code))
+            .build()
+            .build()
+            // Function 2: never called
+            .function()
+            .signature()
+            .build()
+            .body()
+            .with_instructions(Instructions::new(unused))
+            .build()
+            .build()
+            .export()
+            .field("handler")
+            .internal()
+            .func(0)
+            .build()
+            .build()
+    }
+
+    #[test]
+    fn integer_code_is_accepted() {
+        let module = module(
+            vec![Instruction::I32Const(1), Instruction::Drop],
+            vec![Instruction::F64Const(0), Instruction::Drop],
+        );
+        assert!(check_no_floats(&module).is_ok());
+    }
+
+    #[test]
+    fn reachable_float_code_is_rejected() {
+        let module = module(
+            vec![
+                Instruction::I32Const(1),
+                Instruction::F64ConvertSI32,
+                Instruction::Drop,
+            ],
+            vec![],
+        );
+        let err = check_no_floats(&module).unwrap_err();
+        assert!(err.to_string().contains("function 1"));
+    }
+}
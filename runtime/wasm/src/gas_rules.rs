@@ -1,7 +1,7 @@
 use std::{convert::TryInto, num::NonZeroU32};
 
 use graph::runtime::gas::CONST_MAX_GAS_PER_HANDLER;
-use parity_wasm::elements::Instruction;
+use parity_wasm::elements::{BulkInstruction::*, Instruction};
 use pwasm_utils::rules::{MemoryGrowCost, Rules};
 
 pub struct GasRules;
@@ -142,6 +142,15 @@ impl Rules for GasRules {
             Return => 100,
             Drop => 100,
             SignExt(_) => 100,
+
+            // SIMD instructions are charged like the float instructions above
+            Simd(_) => 100,
+
+            // The number of bytes touched by these is only known at runtime, so they are
+            // charged generously
+            Bulk(MemoryCopy) | Bulk(MemoryFill) | Bulk(MemoryInit(_)) | Bulk(TableCopy)
+            | Bulk(TableInit(_)) => 10_000,
+            Bulk(MemoryDrop(_)) | Bulk(TableDrop(_)) => 100,
             Nop => 1,
             Unreachable => 1,
         };
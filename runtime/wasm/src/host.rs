@@ -8,13 +8,15 @@ use futures03::channel::oneshot::channel;
 use graph::blockchain::RuntimeAdapter;
 use graph::blockchain::{Blockchain, DataSource};
 use graph::blockchain::{HostFn, TriggerWithHandler};
-use graph::components::store::EnsLookup;
+use graph::components::store::{
+    BlockTimestampLookup, DataSourceContextUpdate, EnsLookup, ScheduledBlockHandler,
+};
 use graph::components::subgraph::{MappingError, SharedProofOfIndexing};
 use graph::prelude::{
     RuntimeHost as RuntimeHostTrait, RuntimeHostBuilder as RuntimeHostBuilderTrait, *,
 };
 
-use crate::mapping::{MappingContext, MappingRequest};
+use crate::mapping::{MappingContext, MappingRequest, WasmFeatures};
 use crate::{host_exports::HostExports, module::ExperimentalFeatures};
 use graph::runtime::gas::Gas;
 
@@ -31,6 +33,9 @@ pub struct RuntimeHostBuilder<C: Blockchain> {
     runtime_adapter: Arc<C::RuntimeAdapter>,
     link_resolver: Arc<dyn LinkResolver>,
     ens_lookup: Arc<dyn EnsLookup>,
+    block_timestamps: Arc<dyn BlockTimestampLookup>,
+    big_decimal_precision: BigDecimalPrecision,
+    replay: bool,
 }
 
 impl<C: Blockchain> Clone for RuntimeHostBuilder<C> {
@@ -39,6 +44,9 @@ impl<C: Blockchain> Clone for RuntimeHostBuilder<C> {
             runtime_adapter: self.runtime_adapter.cheap_clone(),
             link_resolver: self.link_resolver.cheap_clone(),
             ens_lookup: self.ens_lookup.cheap_clone(),
+            block_timestamps: self.block_timestamps.cheap_clone(),
+            big_decimal_precision: self.big_decimal_precision,
+            replay: self.replay,
         }
     }
 }
@@ -48,11 +56,17 @@ impl<C: Blockchain> RuntimeHostBuilder<C> {
         runtime_adapter: Arc<C::RuntimeAdapter>,
         link_resolver: Arc<dyn LinkResolver>,
         ens_lookup: Arc<dyn EnsLookup>,
+        block_timestamps: Arc<dyn BlockTimestampLookup>,
+        big_decimal_precision: BigDecimalPrecision,
+        replay: bool,
     ) -> Self {
         RuntimeHostBuilder {
             runtime_adapter,
             link_resolver,
             ens_lookup,
+            block_timestamps,
+            big_decimal_precision,
+            replay,
         }
     }
 }
@@ -63,6 +77,7 @@ impl<C: Blockchain> RuntimeHostBuilderTrait<C> for RuntimeHostBuilder<C> {
 
     fn spawn_mapping(
         raw_module: Vec<u8>,
+        api_version: semver::Version,
         logger: Logger,
         subgraph_id: DeploymentHash,
         metrics: Arc<HostMetrics>,
@@ -72,6 +87,7 @@ impl<C: Blockchain> RuntimeHostBuilderTrait<C> for RuntimeHostBuilder<C> {
         };
         crate::mapping::spawn_module(
             raw_module,
+            WasmFeatures::for_api_version(&api_version),
             logger,
             subgraph_id,
             metrics,
@@ -100,6 +116,9 @@ impl<C: Blockchain> RuntimeHostBuilderTrait<C> for RuntimeHostBuilder<C> {
             mapping_request_sender,
             metrics,
             self.ens_lookup.cheap_clone(),
+            self.block_timestamps.cheap_clone(),
+            self.big_decimal_precision,
+            self.replay,
         )
     }
 }
@@ -126,6 +145,9 @@ where
         mapping_request_sender: Sender<MappingRequest<C>>,
         metrics: Arc<HostMetrics>,
         ens_lookup: Arc<dyn EnsLookup>,
+        block_timestamps: Arc<dyn BlockTimestampLookup>,
+        big_decimal_precision: BigDecimalPrecision,
+        replay: bool,
     ) -> Result<Self, Error> {
         // Create new instance of externally hosted functions invoker. The `Arc` is simply to avoid
         // implementing `Clone` for `HostExports`.
@@ -136,9 +158,16 @@ where
             templates,
             link_resolver,
             ens_lookup,
+            block_timestamps,
+            big_decimal_precision,
         ));
 
-        let host_fns = Arc::new(runtime_adapter.host_fns(&data_source)?);
+        let host_fns = if replay {
+            runtime_adapter.replay_host_fns(&data_source)?
+        } else {
+            runtime_adapter.host_fns(&data_source)?
+        };
+        let host_fns = Arc::new(host_fns);
 
         Ok(RuntimeHost {
             host_fns,
@@ -225,6 +254,21 @@ impl<C: Blockchain> RuntimeHostTrait<C> for RuntimeHost<C> {
         self.data_source.match_and_decode(trigger, block, logger)
     }
 
+    fn match_scheduled_handler(
+        &self,
+        handler: &ScheduledBlockHandler,
+        block: Arc<C::Block>,
+    ) -> Result<Option<TriggerWithHandler<C>>, Error> {
+        if handler.data_source != self.data_source.name()
+            || handler.address.as_slice() != self.data_source.address().unwrap_or_default()
+        {
+            return Ok(None);
+        }
+        self.data_source
+            .scheduled_handler_trigger(&handler.handler, block)
+            .map(Some)
+    }
+
     async fn process_mapping_trigger(
         &self,
         logger: &Logger,
@@ -240,6 +284,19 @@ impl<C: Blockchain> RuntimeHostTrait<C> for RuntimeHost<C> {
     fn creation_block_number(&self) -> Option<BlockNumber> {
         self.data_source.creation_block()
     }
+
+    fn update_context(&self, update: &DataSourceContextUpdate) {
+        if update.data_source == self.data_source.name()
+            && update.address.as_slice() == self.data_source.address().unwrap_or_default()
+        {
+            self.host_exports
+                .set_stored_data_source_context(Some(update.context.clone()));
+        }
+    }
+
+    fn reset_context(&self) {
+        self.host_exports.set_stored_data_source_context(None);
+    }
 }
 
 impl<C: Blockchain> PartialEq for RuntimeHost<C> {
@@ -2,12 +2,16 @@ use crate::{error::DeterminismLevel, module::IntoTrap};
 use graph::blockchain::DataSource;
 use graph::blockchain::{Blockchain, DataSourceTemplate as _};
 use graph::components::store::EntityType;
-use graph::components::store::{EnsLookup, EntityKey};
+use graph::components::store::{
+    BlockTimestampLookup, DataSourceContextUpdate, EnsLookup, EntityKey, ScheduledBlockHandler,
+};
 use graph::components::subgraph::{CausalityRegion, ProofOfIndexingEvent, SharedProofOfIndexing};
 use graph::data::store;
+use graph::data::subgraph::schema::SubgraphWarning;
 use graph::prelude::ethabi::param_type::Reader;
 use graph::prelude::ethabi::{decode, encode, Token};
 use graph::prelude::serde_json;
+use graph::prelude::serde_yaml;
 use graph::prelude::{slog::b, slog::record_static, *};
 use graph::runtime::gas::{self, complexity, Gas, GasCounter};
 pub use graph::runtime::{DeterministicHostError, HostExportError};
@@ -16,14 +20,64 @@ use semver::Version;
 use std::collections::HashMap;
 use std::ops::Deref;
 use std::str::FromStr;
+use std::sync::RwLock;
 use std::time::{Duration, Instant};
 use web3::types::H160;
 
 use graph::ensure;
 use wasmtime::Trap;
 
+use crate::mapping::MAX_SUBGRAPH_WARNINGS_PER_BLOCK;
 use crate::module::{WasmInstance, WasmInstanceContext};
 
+lazy_static! {
+    /// The keys of the fields that mappings have logged. `slog` needs keys
+    /// that live forever, so each distinct key is leaked once
+    static ref LOG_FIELD_KEYS: std::sync::Mutex<HashMap<String, &'static str>> =
+        std::sync::Mutex::new(HashMap::new());
+}
+
+/// The most distinct field keys that mappings can log; fields with other
+/// keys are logged under the key `fields`
+const MAX_LOG_FIELD_KEYS: usize = 1_000;
+
+fn log_field_key(key: &str) -> Option<&'static str> {
+    let mut keys = LOG_FIELD_KEYS.lock().unwrap();
+    if let Some(key) = keys.get(key) {
+        return Some(key);
+    }
+    if keys.len() >= MAX_LOG_FIELD_KEYS {
+        return None;
+    }
+    let interned: &'static str = Box::leak(key.to_string().into_boxed_str());
+    keys.insert(key.to_string(), interned);
+    Some(interned)
+}
+
+/// The key/value pairs that a mapping passed to `log.logWithFields`,
+/// sorted by key
+struct MappingLogFields(std::collections::BTreeMap<String, store::Value>);
+
+impl slog::KV for MappingLogFields {
+    fn serialize(
+        &self,
+        _record: &slog::Record,
+        serializer: &mut dyn slog::Serializer,
+    ) -> slog::Result {
+        let mut overflow = Vec::new();
+        for (key, value) in &self.0 {
+            match log_field_key(key) {
+                Some(key) => serializer.emit_arguments(key, &format_args!("{}", value))?,
+                None => overflow.push(format!("{}: {}", key, value)),
+            }
+        }
+        if !overflow.is_empty() {
+            serializer.emit_arguments("fields", &format_args!("{}", overflow.join(", ")))?;
+        }
+        Ok(())
+    }
+}
+
 fn write_poi_event(
     proof_of_indexing: &SharedProofOfIndexing,
     poi_event: &ProofOfIndexingEvent,
@@ -56,10 +110,14 @@ impl IntoTrap for HostExportError {
 pub struct HostExports<C: Blockchain> {
     pub(crate) subgraph_id: DeploymentHash,
     pub api_version: Version,
+    pub(crate) big_decimal_precision: BigDecimalPrecision,
     data_source_name: String,
     data_source_address: Vec<u8>,
     data_source_network: String,
     data_source_context: Arc<Option<DataSourceContext>>,
+    /// The context that a mapping stored with `dataSource.setContext` in
+    /// an earlier block, which replaces `data_source_context`
+    stored_data_source_context: RwLock<Option<Arc<DataSourceContext>>>,
     /// Some data sources have indeterminism or different notions of time. These
     /// need to be each be stored separately to separate causality between them,
     /// and merge the results later. Right now, this is just the ethereum
@@ -68,6 +126,9 @@ pub struct HostExports<C: Blockchain> {
     templates: Arc<Vec<C::DataSourceTemplate>>,
     pub(crate) link_resolver: Arc<dyn LinkResolver>,
     ens_lookup: Arc<dyn EnsLookup>,
+    block_timestamps: Arc<dyn BlockTimestampLookup>,
+    /// Overrides the node's handler timeout for this data source
+    pub(crate) handler_timeout: Option<Duration>,
 }
 
 impl<C: Blockchain> HostExports<C> {
@@ -78,18 +139,24 @@ impl<C: Blockchain> HostExports<C> {
         templates: Arc<Vec<C::DataSourceTemplate>>,
         link_resolver: Arc<dyn LinkResolver>,
         ens_lookup: Arc<dyn EnsLookup>,
+        block_timestamps: Arc<dyn BlockTimestampLookup>,
+        big_decimal_precision: BigDecimalPrecision,
     ) -> Self {
         Self {
             subgraph_id,
             api_version: data_source.api_version(),
+            big_decimal_precision,
             data_source_name: data_source.name().to_owned(),
             data_source_address: data_source.address().unwrap_or_default().to_owned(),
             data_source_context: data_source.context().cheap_clone(),
+            stored_data_source_context: RwLock::new(None),
             causality_region: CausalityRegion::from_network(&data_source_network),
             data_source_network,
             templates,
             link_resolver,
             ens_lookup,
+            block_timestamps,
+            handler_timeout: data_source.handler_timeout(),
         }
     }
 
@@ -231,6 +298,27 @@ impl<C: Blockchain> HostExports<C> {
         Ok(state.entity_cache.get(&store_key)?)
     }
 
+    /// Like `store_get`, but only looks at entities that were changed
+    /// earlier in the current block and never queries the database
+    pub(crate) fn store_get_in_block(
+        &self,
+        state: &mut BlockState<C>,
+        entity_type: String,
+        entity_id: String,
+        gas: &GasCounter,
+    ) -> Result<Option<Entity>, DeterministicHostError> {
+        let store_key = EntityKey {
+            subgraph_id: self.subgraph_id.clone(),
+            entity_type: EntityType::new(entity_type),
+            entity_id,
+        };
+
+        let result = state.entity_cache.get_in_block(&store_key);
+        gas.consume_host_fn(gas::STORE_GET.with_args(complexity::Linear, (&store_key, &result)))?;
+
+        Ok(result)
+    }
+
     /// Prints the module of `n` in hex.
     /// Integers are encoded using the least amount of digits (no leading zero digits).
     /// Their encoding may be of uneven length. The number zero encodes as "0x0".
@@ -261,6 +349,15 @@ impl<C: Blockchain> HostExports<C> {
         block_on03(self.link_resolver.cat(logger, &Link { link }))
     }
 
+    pub(crate) fn ipfs_get_block(
+        &self,
+        logger: &Logger,
+        link: String,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        // Does not consume gas because this is not a part of the deterministic feature set.
+        block_on03(self.link_resolver.get_block(logger, &Link { link }))
+    }
+
     // Read the IPFS file `link`, split it into JSON objects, and invoke the
     // exported function `callback` on each JSON object. The successful return
     // value contains the block state produced by each callback invocation. Each
@@ -393,6 +490,114 @@ impl<C: Blockchain> HostExports<C> {
         Ok(tiny_keccak::keccak256(data))
     }
 
+    pub(crate) fn crypto_sha256(
+        &self,
+        input: Vec<u8>,
+        gas: &GasCounter,
+    ) -> Result<Vec<u8>, DeterministicHostError> {
+        use sha2::{Digest, Sha256};
+
+        let data = &input[..];
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Size, data))?;
+        Ok(Sha256::digest(data).to_vec())
+    }
+
+    pub(crate) fn crypto_ripemd160(
+        &self,
+        input: Vec<u8>,
+        gas: &GasCounter,
+    ) -> Result<Vec<u8>, DeterministicHostError> {
+        use ripemd160::{Digest, Ripemd160};
+
+        let data = &input[..];
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Size, data))?;
+        Ok(Ripemd160::digest(data).to_vec())
+    }
+
+    /// Recover the address that signed the 32 byte `hash` with the 65 byte
+    /// `signature` `r || s || v`, where `v` is either `0`/`1` or `27`/`28`.
+    /// Returns `None` if no address can be recovered
+    pub(crate) fn crypto_ecrecover(
+        &self,
+        hash: Vec<u8>,
+        signature: Vec<u8>,
+        gas: &GasCounter,
+    ) -> Result<Option<H160>, DeterministicHostError> {
+        gas.consume_host_fn(gas::ECRECOVER)?;
+
+        if hash.len() != 32 {
+            return Err(DeterministicHostError::from(anyhow!(
+                "ecrecover: the message hash must be 32 bytes long but is {} bytes long",
+                hash.len()
+            )));
+        }
+        if signature.len() != 65 {
+            return Err(DeterministicHostError::from(anyhow!(
+                "ecrecover: the signature must be 65 bytes long but is {} bytes long",
+                signature.len()
+            )));
+        }
+        let recovery_id = match signature[64] {
+            v @ 0..=1 => v as i32,
+            v @ 27..=28 => v as i32 - 27,
+            _ => return Ok(None),
+        };
+        Ok(web3::signing::recover(&hash, &signature[..64], recovery_id).ok())
+    }
+
+    /// Check that the 64 byte `signature` is a valid ed25519 signature of
+    /// `message` by the 32 byte `pubkey`. Returns `false` if the signature
+    /// does not verify or `pubkey` is not a valid public key
+    pub(crate) fn crypto_ed25519_verify(
+        &self,
+        pubkey: Vec<u8>,
+        message: Vec<u8>,
+        signature: Vec<u8>,
+        gas: &GasCounter,
+    ) -> Result<bool, DeterministicHostError> {
+        use ed25519_dalek::{PublicKey, Signature, Verifier, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+        use std::convert::TryFrom;
+
+        gas.consume_host_fn(gas::ED25519_VERIFY_GAS_OP.with_args(complexity::Size, &message))?;
+
+        if pubkey.len() != PUBLIC_KEY_LENGTH {
+            return Err(DeterministicHostError::from(anyhow!(
+                "ed25519Verify: the public key must be {} bytes long but is {} bytes long",
+                PUBLIC_KEY_LENGTH,
+                pubkey.len()
+            )));
+        }
+        if signature.len() != SIGNATURE_LENGTH {
+            return Err(DeterministicHostError::from(anyhow!(
+                "ed25519Verify: the signature must be {} bytes long but is {} bytes long",
+                SIGNATURE_LENGTH,
+                signature.len()
+            )));
+        }
+        let pubkey = match PublicKey::from_bytes(&pubkey) {
+            Ok(pubkey) => pubkey,
+            Err(_) => return Ok(false),
+        };
+        let signature = match Signature::try_from(signature.as_slice()) {
+            Ok(signature) => signature,
+            Err(_) => return Ok(false),
+        };
+        Ok(pubkey.verify(&message, &signature).is_ok())
+    }
+
+    /// BLAKE2b with a 64 byte digest
+    pub(crate) fn crypto_blake2b(
+        &self,
+        input: Vec<u8>,
+        gas: &GasCounter,
+    ) -> Result<Vec<u8>, DeterministicHostError> {
+        use blake2::{Blake2b, Digest};
+
+        let data = &input[..];
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Size, data))?;
+        Ok(Blake2b::digest(data).to_vec())
+    }
+
     pub(crate) fn big_int_plus(
         &self,
         x: BigInt,
@@ -466,6 +671,21 @@ impl<C: Blockchain> HostExports<C> {
         Ok(x.pow(exp))
     }
 
+    /// The integer square root of `x`, rounded down
+    pub(crate) fn big_int_sqrt(
+        &self,
+        x: BigInt,
+        gas: &GasCounter,
+    ) -> Result<BigInt, DeterministicHostError> {
+        gas.consume_host_fn(gas::BIG_MATH_GAS_OP.with_args(complexity::Mul, (&x, &x)))?;
+        x.sqrt().ok_or_else(|| {
+            DeterministicHostError::from(anyhow!(
+                "can not take the square root of the negative number {}",
+                x
+            ))
+        })
+    }
+
     pub(crate) fn big_int_from_string(
         &self,
         s: String,
@@ -527,6 +747,52 @@ impl<C: Blockchain> HostExports<C> {
         Ok(::bs58::encode(&bytes).into_string())
     }
 
+    /// Lowercase hex encoding of `bytes`, without a `0x` prefix
+    pub(crate) fn encoding_hex_encode(
+        &self,
+        bytes: Vec<u8>,
+        gas: &GasCounter,
+    ) -> Result<String, DeterministicHostError> {
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Size, &bytes))?;
+        Ok(hex::encode(bytes))
+    }
+
+    /// Decode a hex string with an optional `0x` prefix; both upper- and
+    /// lowercase digits are accepted
+    pub(crate) fn encoding_hex_decode(
+        &self,
+        string: String,
+        gas: &GasCounter,
+    ) -> Result<Vec<u8>, DeterministicHostError> {
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Size, &string))?;
+        let s = string.strip_prefix("0x").unwrap_or(&string);
+        hex::decode(s)
+            .with_context(|| format!("Failed to decode hex string: '{}'", truncate(&string)))
+            .map_err(DeterministicHostError::from)
+    }
+
+    /// Base64 encoding of `bytes` with the standard alphabet and padding
+    pub(crate) fn encoding_base64_encode(
+        &self,
+        bytes: Vec<u8>,
+        gas: &GasCounter,
+    ) -> Result<String, DeterministicHostError> {
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Size, &bytes))?;
+        Ok(base64::encode(bytes))
+    }
+
+    /// Decode a base64 string that uses the standard alphabet and padding
+    pub(crate) fn encoding_base64_decode(
+        &self,
+        string: String,
+        gas: &GasCounter,
+    ) -> Result<Vec<u8>, DeterministicHostError> {
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Size, &string))?;
+        base64::decode(&string)
+            .with_context(|| format!("Failed to decode base64 string: '{}'", truncate(&string)))
+            .map_err(DeterministicHostError::from)
+    }
+
     pub(crate) fn big_decimal_plus(
         &self,
         x: BigDecimal,
@@ -534,7 +800,7 @@ impl<C: Blockchain> HostExports<C> {
         gas: &GasCounter,
     ) -> Result<BigDecimal, DeterministicHostError> {
         gas.consume_host_fn(gas::BIG_MATH_GAS_OP.with_args(complexity::Linear, (&x, &y)))?;
-        Ok(x + y)
+        Ok(x.add_with_precision(y, &self.big_decimal_precision))
     }
 
     pub(crate) fn big_decimal_minus(
@@ -544,7 +810,7 @@ impl<C: Blockchain> HostExports<C> {
         gas: &GasCounter,
     ) -> Result<BigDecimal, DeterministicHostError> {
         gas.consume_host_fn(gas::BIG_MATH_GAS_OP.with_args(complexity::Linear, (&x, &y)))?;
-        Ok(x - y)
+        Ok(x.sub_with_precision(y, &self.big_decimal_precision))
     }
 
     pub(crate) fn big_decimal_times(
@@ -554,7 +820,7 @@ impl<C: Blockchain> HostExports<C> {
         gas: &GasCounter,
     ) -> Result<BigDecimal, DeterministicHostError> {
         gas.consume_host_fn(gas::BIG_MATH_GAS_OP.with_args(complexity::Mul, (&x, &y)))?;
-        Ok(x * y)
+        Ok(x.mul_with_precision(y, &self.big_decimal_precision))
     }
 
     /// Maximum precision of 100 decimal digits.
@@ -571,7 +837,7 @@ impl<C: Blockchain> HostExports<C> {
                 x
             )));
         }
-        Ok(x / y)
+        Ok(x.div_with_precision(y, &self.big_decimal_precision))
     }
 
     pub(crate) fn big_decimal_equals(
@@ -584,6 +850,34 @@ impl<C: Blockchain> HostExports<C> {
         Ok(x == y)
     }
 
+    /// The `BigDecimal` for the fixed-point number `x` with `decimals`
+    /// decimal places, i.e., `x * 10^-decimals`
+    pub(crate) fn big_decimal_from_fixed(
+        &self,
+        x: BigInt,
+        decimals: u8,
+        gas: &GasCounter,
+    ) -> Result<BigDecimal, DeterministicHostError> {
+        gas.consume_host_fn(gas::BIG_MATH_GAS_OP.with_args(complexity::Size, &x))?;
+        Ok(BigDecimal::new_with_precision(
+            x,
+            -(decimals as i64),
+            &self.big_decimal_precision,
+        ))
+    }
+
+    /// The fixed-point number with `decimals` decimal places for `x`,
+    /// truncating any digits beyond that
+    pub(crate) fn big_decimal_to_fixed(
+        &self,
+        x: BigDecimal,
+        decimals: u8,
+        gas: &GasCounter,
+    ) -> Result<BigInt, DeterministicHostError> {
+        gas.consume_host_fn(gas::BIG_MATH_GAS_OP.with_args(complexity::Size, &x))?;
+        Ok(x.to_fixed(decimals))
+    }
+
     pub(crate) fn big_decimal_to_string(
         &self,
         x: BigDecimal,
@@ -599,7 +893,7 @@ impl<C: Blockchain> HostExports<C> {
         gas: &GasCounter,
     ) -> Result<BigDecimal, DeterministicHostError> {
         gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Size, &s))?;
-        BigDecimal::from_str(&s)
+        BigDecimal::from_str_with_precision(&s, &self.big_decimal_precision)
             .with_context(|| format!("string  is not a BigDecimal: '{}'", s))
             .map_err(DeterministicHostError::from)
     }
@@ -655,25 +949,89 @@ impl<C: Blockchain> HostExports<C> {
         Ok(())
     }
 
+    /// Schedule `handler` to run once when the subgraph processes `block`.
+    /// The handler will be called with `block` as its argument
+    pub(crate) fn scheduler_at_block(
+        &self,
+        logger: &Logger,
+        state: &mut BlockState<C>,
+        block: BlockNumber,
+        handler: String,
+        current_block: BlockNumber,
+        gas: &GasCounter,
+    ) -> Result<(), DeterministicHostError> {
+        gas.consume_host_fn(gas::SCHEDULE_BLOCK_HANDLER)?;
+
+        if block <= current_block {
+            return Err(DeterministicHostError::from(anyhow!(
+                "Failed to schedule handler `{}` at block {}: \
+                 handlers can only be scheduled for blocks after the current block {}",
+                handler,
+                block,
+                current_block
+            )));
+        }
+
+        info!(
+            logger,
+            "Schedule block handler";
+            "handler" => &handler,
+            "block" => block
+        );
+
+        state.push_scheduled_handler(ScheduledBlockHandler {
+            data_source: self.data_source_name.clone(),
+            address: self.data_source_address.clone(),
+            handler,
+            block,
+        });
+
+        Ok(())
+    }
+
     pub(crate) fn ens_name_by_hash(&self, hash: &str) -> Result<Option<String>, anyhow::Error> {
         Ok(self.ens_lookup.find_name(hash)?)
     }
 
+    /// Look up the timestamp of block `number` on `network`. Since the
+    /// block might simply not have been ingested yet, not finding it is a
+    /// nondeterministic error.
+    pub(crate) fn chain_block_timestamp(
+        &self,
+        network: &str,
+        number: BlockNumber,
+        gas: &GasCounter,
+    ) -> Result<u64, HostExportError> {
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Size, network))?;
+
+        match self.block_timestamps.block_timestamp(network, number)? {
+            Some(timestamp) => Ok(timestamp),
+            None => Err(HostExportError::Unknown(anyhow!(
+                "block {} on network `{}` is not available or not final yet",
+                number,
+                network
+            ))),
+        }
+    }
+
     pub(crate) fn log_log(
         &self,
         logger: &Logger,
         level: slog::Level,
         msg: String,
+        fields: HashMap<String, store::Value>,
         gas: &GasCounter,
     ) -> Result<(), DeterministicHostError> {
         gas.consume_host_fn(gas::LOG_OP.with_args(complexity::Size, &msg))?;
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Size, &fields))?;
 
         let rs = record_static!(level, self.data_source_name.as_str());
+        let fields = MappingLogFields(fields.into_iter().collect());
 
         logger.log(&slog::Record::new(
             &rs,
             &format_args!("{}", msg),
-            b!("data_source" => &self.data_source_name),
+            b!("data_source" => &self.data_source_name, fields),
         ));
 
         if level == slog::Level::Critical {
@@ -684,6 +1042,31 @@ impl<C: Blockchain> HostExports<C> {
         Ok(())
     }
 
+    pub(crate) fn log_subgraph_warning(
+        &self,
+        logger: &Logger,
+        state: &mut BlockState<C>,
+        block_ptr: &BlockPtr,
+        msg: String,
+        gas: &GasCounter,
+    ) -> Result<(), DeterministicHostError> {
+        gas.consume_host_fn(gas::LOG_OP.with_args(complexity::Size, &msg))?;
+
+        warn!(logger, "Subgraph warning: {}", msg;
+              "data_source" => &self.data_source_name);
+
+        // Only the first few warnings in each block are kept so that a
+        // mapping can not flood the store with warnings
+        if state.warnings.len() < *MAX_SUBGRAPH_WARNINGS_PER_BLOCK {
+            state.warnings.push(SubgraphWarning {
+                message: msg,
+                block_ptr: block_ptr.cheap_clone(),
+                data_source: self.data_source_name.clone(),
+            });
+        }
+        Ok(())
+    }
+
     pub(crate) fn data_source_address(
         &self,
         gas: &GasCounter,
@@ -702,14 +1085,75 @@ impl<C: Blockchain> HostExports<C> {
 
     pub(crate) fn data_source_context(
         &self,
+        state: &BlockState<C>,
         gas: &GasCounter,
     ) -> Result<Entity, DeterministicHostError> {
         gas.consume_host_fn(Gas::new(gas::DEFAULT_BASE_COST))?;
-        Ok(self
-            .data_source_context
+        Ok(self.current_data_source_context(state))
+    }
+
+    /// Store `value` under `key` in the context of the data source. Later
+    /// handlers of the data source see the new context, and it is saved
+    /// with the block so that it also survives restarts
+    pub(crate) fn data_source_set_context(
+        &self,
+        state: &mut BlockState<C>,
+        key: String,
+        value: Value,
+        gas: &GasCounter,
+    ) -> Result<(), DeterministicHostError> {
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Linear, (&key, &value)))?;
+
+        let mut context = self.current_data_source_context(state);
+        context.set(key, value);
+        state.push_data_source_context(DataSourceContextUpdate {
+            data_source: self.data_source_name.clone(),
+            address: self.data_source_address.clone(),
+            context,
+        });
+        Ok(())
+    }
+
+    fn current_data_source_context(&self, state: &BlockState<C>) -> Entity {
+        if let Some(context) =
+            state.data_source_context(&self.data_source_name, &self.data_source_address)
+        {
+            return context.clone();
+        }
+        if let Some(context) = self.stored_data_source_context.read().unwrap().as_ref() {
+            return context.as_ref().clone();
+        }
+        self.data_source_context
             .as_ref()
             .clone()
-            .unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn set_stored_data_source_context(&self, context: Option<DataSourceContext>) {
+        *self.stored_data_source_context.write().unwrap() = context.map(Arc::new);
+    }
+
+    pub(crate) fn block_context_set(
+        &self,
+        state: &mut BlockState<C>,
+        key: String,
+        value: Value,
+        gas: &GasCounter,
+    ) -> Result<(), DeterministicHostError> {
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Linear, (&key, &value)))?;
+        state.set_block_context(key, value);
+        Ok(())
+    }
+
+    pub(crate) fn block_context_get(
+        &self,
+        state: &BlockState<C>,
+        key: String,
+        gas: &GasCounter,
+    ) -> Result<Option<Value>, DeterministicHostError> {
+        let value = state.block_context(&key).cloned();
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Linear, (&key, &value)))?;
+        Ok(value)
     }
 
     pub(crate) fn json_from_bytes(
@@ -722,6 +1166,27 @@ impl<C: Blockchain> HostExports<C> {
             .map_err(|e| DeterministicHostError::from(Error::from(e)))
     }
 
+    pub(crate) fn yaml_from_bytes(
+        &self,
+        bytes: &[u8],
+        gas: &GasCounter,
+    ) -> Result<serde_yaml::Value, DeterministicHostError> {
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(gas::complexity::Size, &bytes))?;
+        serde_yaml::from_slice(bytes).map_err(|e| DeterministicHostError::from(Error::from(e)))
+    }
+
+    /// The canonical JSON text for `value`, see `write_canonical_json`
+    pub(crate) fn json_to_canonical(
+        &self,
+        value: &serde_json::Value,
+        gas: &GasCounter,
+    ) -> Result<String, DeterministicHostError> {
+        let mut json = String::new();
+        write_canonical_json(value, &mut json)?;
+        gas.consume_host_fn(gas::DEFAULT_GAS_OP.with_args(complexity::Size, &json))?;
+        Ok(json)
+    }
+
     pub(crate) fn string_to_h160(
         &self,
         string: &str,
@@ -786,6 +1251,85 @@ fn string_to_h160(string: &str) -> Result<H160, DeterministicHostError> {
         .map_err(DeterministicHostError::from)
 }
 
+/// Shorten `s` for use in error messages
+fn truncate(s: &str) -> String {
+    const MAX_LEN: usize = 64;
+
+    match s.char_indices().nth(MAX_LEN) {
+        Some((idx, _)) => format!("{}...", &s[..idx]),
+        None => s.to_string(),
+    }
+}
+
+/// Write `value` to `out` as canonical JSON, i.e., the same value always
+/// results in the same text: there is no whitespace, object keys are sorted
+/// by their UTF-8 bytes, and numbers are written in plain decimal notation
+/// without trailing zeros, so that `1.50e2` becomes `150`
+fn write_canonical_json(
+    value: &serde_json::Value,
+    out: &mut String,
+) -> Result<(), DeterministicHostError> {
+    use serde_json::Value;
+
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(number) => out.push_str(&canonical_json_number(number)?),
+        // Serializing a string can not fail
+        Value::String(s) => out.push_str(&serde_json::to_string(s).unwrap()),
+        Value::Array(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical_json(value, out)?;
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::to_string(key).unwrap());
+                out.push(':');
+                write_canonical_json(value, out)?;
+            }
+            out.push('}');
+        }
+    }
+    Ok(())
+}
+
+fn canonical_json_number(number: &serde_json::Number) -> Result<String, DeterministicHostError> {
+    // Keep all digits; the number only gets normalized
+    let precision = BigDecimalPrecision {
+        digits: u32::MAX,
+        rounding: Some(BigDecimalRounding::Down),
+    };
+    let s = number.to_string();
+    let value = BigDecimal::from_str_with_precision(&s, &precision)
+        .with_context(|| format!("`{}` is not a JSON number", truncate(&s)))
+        .map_err(DeterministicHostError::from)?;
+
+    // Written out in decimal notation, exponents are limited to what
+    // `BigDecimal` allows so the text can not get arbitrarily long
+    let exp = -value.as_bigint_and_exponent().1;
+    if exp < BigDecimal::MIN_EXP as i64 || exp > BigDecimal::MAX_EXP as i64 {
+        return Err(DeterministicHostError::from(anyhow!(
+            "the exponent of JSON number `{}` is outside the `{}` to `{}` range",
+            truncate(&s),
+            BigDecimal::MIN_EXP,
+            BigDecimal::MAX_EXP
+        )));
+    }
+    Ok(value.to_string())
+}
+
 fn bytes_to_string(logger: &Logger, bytes: Vec<u8>) -> String {
     let s = String::from_utf8_lossy(&bytes);
 
@@ -834,3 +1378,26 @@ fn bytes_to_string_is_lossy() {
         )
     )
 }
+
+#[test]
+fn canonical_json() {
+    let json = |s: &str| {
+        let value: serde_json::Value = serde_json::from_str(s).unwrap();
+        let mut out = String::new();
+        write_canonical_json(&value, &mut out).map(|()| out)
+    };
+
+    assert_eq!(
+        r#"{"a":[1,true,null],"b":{"c":"x\"y","d":{}}}"#,
+        json(r#"{ "b": { "d": {}, "c": "x\"y" }, "a": [1, true, null] }"#).unwrap()
+    );
+    assert_eq!(
+        "[150,0.5,-12,0,1.5]",
+        json("[1.50e2, 0.50, -12.0, -0, 15E-1]").unwrap()
+    );
+    assert_eq!(
+        "123456789012345678901234567890123456789012345678901234567890",
+        json("123456789012345678901234567890123456789012345678901234567890").unwrap()
+    );
+    assert!(json("1e100000").is_err());
+}
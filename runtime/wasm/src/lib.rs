@@ -16,11 +16,12 @@ pub mod host_exports;
 
 pub mod error;
 
+mod float_check;
 mod gas_rules;
 
 pub use host::RuntimeHostBuilder;
 pub use host_exports::HostExports;
-pub use mapping::{MappingContext, ValidModule};
+pub use mapping::{MappingContext, ValidModule, WasmFeatures};
 pub use module::{ExperimentalFeatures, WasmInstance};
 
 #[cfg(debug_assertions)]
@@ -1,17 +1,51 @@
+use crate::float_check;
 use crate::gas_rules::GasRules;
 use crate::module::{ExperimentalFeatures, WasmInstance};
 use futures::sync::mpsc;
 use futures03::channel::oneshot::Sender;
 use graph::blockchain::{Blockchain, HostFn, TriggerWithHandler};
 use graph::components::subgraph::{MappingError, SharedProofOfIndexing};
+use graph::data::subgraph::{API_VERSION_0_0_14, API_VERSION_0_0_7, API_VERSION_0_0_8};
 use graph::prelude::*;
 use graph::runtime::gas::Gas;
-use std::collections::BTreeMap;
-use std::sync::Arc;
+use semver::Version;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
 use std::thread;
 
 const ONE_MIB: usize = 1 << 20; // 1_048_576
 
+/// The number of 64KiB pages that 32-bit Wasm can address
+const WASM_MAX_PAGES: u32 = 1 << 16;
+
+/// The size of a Wasm page in bytes
+const WASM_PAGE_SIZE: u64 = 1 << 16;
+
+/// The number of pages that the memory of mapping instances with
+/// `API_VERSION_0_0_14` or later may grow to, 512MiB
+const MAX_MEMORY_PAGES_0_0_14: u32 = 512 * 16;
+
+/// The number of pages that the memory of a mapping instance with
+/// `api_version` may grow to. A handler that needs more fails with a
+/// deterministic error, which makes this limit part of what a subgraph's
+/// results depend on: it must be the same on every node and may only
+/// change with a new api version. Api versions before 0.0.14 allow the
+/// 4GiB that 32-bit Wasm can address.
+pub fn max_memory_pages(api_version: &Version) -> u32 {
+    if api_version >= &API_VERSION_0_0_14 {
+        MAX_MEMORY_PAGES_0_0_14
+    } else {
+        WASM_MAX_PAGES
+    }
+}
+
+/// Whether growing a memory of `current_pages` pages by `size` bytes would
+/// take it past `max_pages`
+pub(crate) fn exceeds_memory_limit(current_pages: u64, size: u64, max_pages: u32) -> bool {
+    let needed_pages = (size + WASM_PAGE_SIZE - 1) / WASM_PAGE_SIZE;
+    current_pages + needed_pages > max_pages as u64
+}
+
 lazy_static! {
     /// Verbose logging of mapping inputs
     pub static ref LOG_TRIGGER_DATA: bool = std::env::var("GRAPH_LOG_TRIGGER_DATA").is_ok();
@@ -22,11 +56,75 @@ lazy_static! {
         .and_then(|max_stack_size| max_stack_size.parse().ok())
         // 512KiB
         .unwrap_or(ONE_MIB / 2);
+
+    /// Disable the Wasm SIMD proposal even for mappings whose api version allows it
+    static ref DISABLE_WASM_SIMD: bool = std::env::var("GRAPH_RUNTIME_DISABLE_WASM_SIMD").is_ok();
+
+    /// Disable the Wasm bulk-memory proposal even for mappings whose api version allows it
+    static ref DISABLE_WASM_BULK_MEMORY: bool =
+        std::env::var("GRAPH_RUNTIME_DISABLE_WASM_BULK_MEMORY").is_ok();
+
+    /// How many warnings the mappings of a subgraph can emit with
+    /// `log.subgraphWarning` for one block. Additional warnings are only
+    /// logged
+    pub static ref MAX_SUBGRAPH_WARNINGS_PER_BLOCK: usize =
+        std::env::var("GRAPH_MAX_SUBGRAPH_WARNINGS_PER_BLOCK")
+            .ok()
+            .map(|s| {
+                s.parse().unwrap_or_else(|_| {
+                    panic!("GRAPH_MAX_SUBGRAPH_WARNINGS_PER_BLOCK must be a number, but is `{}`", s)
+                })
+            })
+            .unwrap_or(10);
+
+    /// How many compiled modules the process-wide module cache holds
+    static ref MODULE_CACHE_SIZE: usize = std::env::var("GRAPH_RUNTIME_MODULE_CACHE_SIZE")
+        .ok()
+        .map(|s| {
+            s.parse().unwrap_or_else(|_| {
+                panic!("GRAPH_RUNTIME_MODULE_CACHE_SIZE must be a number, but is `{}`", s)
+            })
+        })
+        .unwrap_or(100);
+
+    static ref MODULE_CACHE: Mutex<ModuleCache> = Mutex::new(ModuleCache::new(*MODULE_CACHE_SIZE));
+}
+
+/// Optional Wasm proposals that a module may use. Which of them are enabled
+/// is part of the subgraph's semantics, and therefore tied to the api
+/// version of the mapping.
+///
+/// Determinism: SIMD floating point instructions are subject to the same
+/// NaN canonicalization as scalar ones, and the relaxed SIMD proposal,
+/// whose results are allowed to differ between platforms, is not enabled.
+/// Bulk-memory instructions are deterministic, but since the amount of
+/// memory they touch is only known at runtime, they are charged a flat,
+/// conservative amount of gas.
+///
+/// With `strict_floats`, modules whose handlers use floating point numbers
+/// are rejected altogether, see `float_check`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WasmFeatures {
+    pub simd: bool,
+    pub bulk_memory: bool,
+    pub strict_floats: bool,
+}
+
+impl WasmFeatures {
+    pub fn for_api_version(api_version: &Version) -> Self {
+        let supported = api_version >= &API_VERSION_0_0_7;
+        WasmFeatures {
+            simd: supported && !*DISABLE_WASM_SIMD,
+            bulk_memory: supported && !*DISABLE_WASM_BULK_MEMORY,
+            strict_floats: api_version >= &API_VERSION_0_0_8,
+        }
+    }
 }
 
 /// Spawn a wasm module in its own thread.
 pub fn spawn_module<C: Blockchain>(
     raw_module: Vec<u8>,
+    features: WasmFeatures,
     logger: Logger,
     subgraph_id: DeploymentHash,
     host_metrics: Arc<HostMetrics>,
@@ -34,7 +132,7 @@ pub fn spawn_module<C: Blockchain>(
     timeout: Option<Duration>,
     experimental_features: ExperimentalFeatures,
 ) -> Result<mpsc::Sender<MappingRequest<C>>, anyhow::Error> {
-    let valid_module = Arc::new(ValidModule::new(&raw_module)?);
+    let valid_module = ValidModule::cached(&raw_module, features)?;
 
     // Create channel for event handling requests
     let (mapping_request_sender, mapping_request_receiver) = mpsc::channel(100);
@@ -141,12 +239,16 @@ pub struct ValidModule {
 }
 
 impl ValidModule {
-    /// Pre-process and validate the module.
-    pub fn new(raw_module: &[u8]) -> Result<Self, anyhow::Error> {
+    /// Pre-process and validate the module. Modules that use a Wasm feature that is not
+    /// enabled in `features` are rejected.
+    pub fn new(raw_module: &[u8], features: WasmFeatures) -> Result<Self, anyhow::Error> {
         // Add the gas calls here. Module name "gas" must match. See also
         // e3f03e62-40e4-4f8c-b4a1-d0375cca0b76. We do this by round-tripping the module through
         // parity - injecting gas then serializing again.
         let parity_module = parity_wasm::elements::Module::from_bytes(raw_module)?;
+        if features.strict_floats {
+            float_check::check_no_floats(&parity_module)?;
+        }
         let parity_module = pwasm_utils::inject_gas_counter(parity_module, &GasRules, "gas")
             .map_err(|_| anyhow!("Failed to inject gas counter"))?;
         let raw_module = parity_module.to_bytes()?;
@@ -160,6 +262,10 @@ impl ValidModule {
         config.cranelift_nan_canonicalization(true); // For NaN determinism.
         config.cranelift_opt_level(wasmtime::OptLevel::None);
         config.max_wasm_stack(*MAX_STACK_SIZE).unwrap(); // Safe because this only panics if size passed is 0.
+        config.wasm_simd(features.simd);
+        // Reference types depend on bulk memory; we never allow them.
+        config.wasm_reference_types(false);
+        config.wasm_bulk_memory(features.bulk_memory);
 
         let engine = &wasmtime::Engine::new(&config)?;
         let module = wasmtime::Module::from_binary(&engine, &raw_module)?;
@@ -182,4 +288,143 @@ impl ValidModule {
             import_name_to_modules,
         })
     }
+
+    /// Like `new`, but reuses the module from an earlier call with the same
+    /// bytes and features if it is still in the process-wide module cache.
+    /// Data sources created from the same template all share one module
+    pub fn cached(raw_module: &[u8], features: WasmFeatures) -> Result<Arc<Self>, anyhow::Error> {
+        use sha2::{Digest, Sha256};
+
+        let key = (Sha256::digest(raw_module).into(), features);
+        if let Some(module) = MODULE_CACHE.lock().unwrap().get(&key) {
+            return Ok(module);
+        }
+
+        // Compile without holding the lock; if another thread compiles the
+        // same module at the same time, one of the two copies is dropped
+        let module = Arc::new(ValidModule::new(raw_module, features)?);
+        MODULE_CACHE
+            .lock()
+            .unwrap()
+            .insert(key, module.cheap_clone());
+        Ok(module)
+    }
+}
+
+type ModuleKey = ([u8; 32], WasmFeatures);
+
+/// Compiled modules keyed by the hash of their bytes and the features they
+/// were compiled with. Once the cache is full, adding a module evicts the
+/// one that was used least recently
+struct ModuleCache {
+    capacity: usize,
+    clock: u64,
+    modules: HashMap<ModuleKey, (u64, Arc<ValidModule>)>,
+}
+
+impl ModuleCache {
+    fn new(capacity: usize) -> Self {
+        ModuleCache {
+            capacity,
+            clock: 0,
+            modules: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &ModuleKey) -> Option<Arc<ValidModule>> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.modules.get_mut(key).map(|(last_used, module)| {
+            *last_used = clock;
+            module.cheap_clone()
+        })
+    }
+
+    fn insert(&mut self, key: ModuleKey, module: Arc<ValidModule>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.modules.len() >= self.capacity && !self.modules.contains_key(&key) {
+            let lru = self
+                .modules
+                .iter()
+                .min_by_key(|(_, (last_used, _))| *last_used)
+                .map(|(key, _)| *key);
+            if let Some(lru) = lru {
+                self.modules.remove(&lru);
+            }
+        }
+        self.clock += 1;
+        self.modules.insert(key, (self.clock, module));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The smallest valid module: just the magic number and the version
+    const EMPTY_MODULE: &[u8] = b"\0asm\x01\0\0\0";
+
+    #[test]
+    fn module_cache_reuses_modules() {
+        let features = WasmFeatures::default();
+        let first = ValidModule::cached(EMPTY_MODULE, features).unwrap();
+        let second = ValidModule::cached(EMPTY_MODULE, features).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let simd = WasmFeatures {
+            simd: true,
+            ..features
+        };
+        let other = ValidModule::cached(EMPTY_MODULE, simd).unwrap();
+        assert!(!Arc::ptr_eq(&first, &other));
+    }
+
+    #[test]
+    fn module_cache_evicts_least_recently_used() {
+        let module = || Arc::new(ValidModule::new(EMPTY_MODULE, WasmFeatures::default()).unwrap());
+        let key = |n: u8| ([n; 32], WasmFeatures::default());
+
+        let mut cache = ModuleCache::new(2);
+        cache.insert(key(1), module());
+        cache.insert(key(2), module());
+        assert!(cache.get(&key(1)).is_some());
+
+        cache.insert(key(3), module());
+        assert!(cache.get(&key(1)).is_some());
+        assert!(cache.get(&key(2)).is_none());
+        assert!(cache.get(&key(3)).is_some());
+    }
+
+    #[test]
+    fn memory_limit_depends_on_api_version() {
+        for (version, limit) in &[
+            (Version::new(0, 0, 4), 4 << 30),
+            (Version::new(0, 0, 5), 4 << 30),
+            (Version::new(0, 0, 13), 4 << 30),
+            (API_VERSION_0_0_14, 512 << 20),
+            (Version::new(0, 0, 15), 512 << 20),
+        ] {
+            assert_eq!(
+                *limit,
+                max_memory_pages(version) as u64 * WASM_PAGE_SIZE,
+                "api version {}",
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn memory_limit_counts_partial_pages() {
+        let max_pages = 16;
+
+        assert!(!exceeds_memory_limit(15, 0, max_pages));
+        assert!(!exceeds_memory_limit(15, 1, max_pages));
+        assert!(!exceeds_memory_limit(15, WASM_PAGE_SIZE, max_pages));
+        assert!(exceeds_memory_limit(15, WASM_PAGE_SIZE + 1, max_pages));
+        assert!(exceeds_memory_limit(16, 1, max_pages));
+        assert!(!exceeds_memory_limit(0, 16 * WASM_PAGE_SIZE, max_pages));
+        assert!(exceeds_memory_limit(0, 16 * WASM_PAGE_SIZE + 1, max_pages));
+    }
 }
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::cell::{RefCell, RefMut};
 use std::collections::HashMap;
 use std::convert::TryFrom;
@@ -28,6 +29,7 @@ use graph::{
 use crate::asc_abi::class::*;
 use crate::host_exports::HostExports;
 use crate::mapping::ValidModule;
+use crate::mapping::{exceeds_memory_limit, max_memory_pages};
 
 mod into_wasm_ret;
 pub mod stopwatch;
@@ -71,6 +73,10 @@ impl<C: Blockchain> AscHeap for WasmInstance<C> {
         ctx.raw_new(bytes)
     }
 
+    fn raw_write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), DeterministicHostError> {
+        self.instance_ctx_mut().raw_write(offset, bytes)
+    }
+
     fn get(&self, offset: u32, size: u32) -> Result<Vec<u8>, DeterministicHostError> {
         self.instance_ctx().get(offset, size)
     }
@@ -79,6 +85,10 @@ impl<C: Blockchain> AscHeap for WasmInstance<C> {
         self.instance_ctx().api_version()
     }
 
+    fn big_decimal_precision(&self) -> BigDecimalPrecision {
+        self.instance_ctx().big_decimal_precision()
+    }
+
     fn asc_type_id(
         &mut self,
         type_id_index: IndexForAscTypeId,
@@ -218,6 +228,13 @@ impl<C: Blockchain> WasmInstance<C> {
             self.instance_ctx_mut().ctx.state.exit_handler();
         }
 
+        {
+            let ctx = self.instance_ctx();
+            ctx.host_metrics
+                .memory_usage
+                .set_wasm_memory_bytes(ctx.memory.data_size());
+        }
+
         let gas = self.gas.get();
         Ok((self.take_ctx().ctx.state, gas))
     }
@@ -265,7 +282,8 @@ pub struct WasmInstanceContext<C: Blockchain> {
 }
 
 impl<C: Blockchain> WasmInstance<C> {
-    /// Instantiates the module and sets it to be interrupted after `timeout`.
+    /// Instantiates the module and sets it to be interrupted after `timeout`,
+    /// unless the data source of `ctx` sets its own handler timeout.
     pub fn from_valid_module_with_ctx(
         valid_module: Arc<ValidModule>,
         ctx: MappingContext<C>,
@@ -273,7 +291,15 @@ impl<C: Blockchain> WasmInstance<C> {
         timeout: Option<Duration>,
         experimental_features: ExperimentalFeatures,
     ) -> Result<WasmInstance<C>, anyhow::Error> {
-        let mut linker = wasmtime::Linker::new(&wasmtime::Store::new(valid_module.module.engine()));
+        let timeout = ctx.host_exports.handler_timeout.or(timeout);
+
+        // Every instance gets its own store, so the memory limit only affects this instance and
+        // the memory is released when the instance is dropped after handling its trigger.
+        let limits = wasmtime::StoreLimitsBuilder::new()
+            .memory_pages(max_memory_pages(&ctx.host_exports.api_version))
+            .build();
+        let store = wasmtime::Store::new_with_limits(valid_module.module.engine(), limits);
+        let mut linker = wasmtime::Linker::new(&store);
         let host_fns = ctx.host_fns.cheap_clone();
         let api_version = ctx.host_exports.api_version.clone();
 
@@ -449,6 +475,7 @@ impl<C: Blockchain> WasmInstance<C> {
         link!("abort", abort, message_ptr, file_name_ptr, line, column);
 
         link!("store.get", store_get, "host_export_store_get", entity, id);
+        link!("store.getInBlock", store_get_in_block, entity, id);
         link!(
             "store.set",
             store_set,
@@ -464,6 +491,12 @@ impl<C: Blockchain> WasmInstance<C> {
         //
         // For reference, search this codebase for: ff652476-e6ad-40e4-85b8-e815d6c6e5e2
         link!("ipfs.cat", ipfs_cat, "host_export_ipfs_cat", hash_ptr);
+        link!(
+            "ipfs.getBlock",
+            ipfs_get_block,
+            "host_export_ipfs_get_block",
+            hash_ptr
+        );
         link!(
             "ipfs.map",
             ipfs_map,
@@ -483,14 +516,40 @@ impl<C: Blockchain> WasmInstance<C> {
         link!("typeConversion.stringToH160", string_to_h160, ptr);
         link!("typeConversion.bytesToBase58", bytes_to_base58, ptr);
 
+        link!("encoding.hexEncode", encoding_hex_encode, ptr);
+        link!("encoding.hexDecode", encoding_hex_decode, ptr);
+        link!("encoding.base64Encode", encoding_base64_encode, ptr);
+        link!("encoding.base64Decode", encoding_base64_decode, ptr);
+
         link!("json.fromBytes", json_from_bytes, ptr);
         link!("json.try_fromBytes", json_try_from_bytes, ptr);
         link!("json.toI64", json_to_i64, ptr);
         link!("json.toU64", json_to_u64, ptr);
         link!("json.toF64", json_to_f64, ptr);
         link!("json.toBigInt", json_to_big_int, ptr);
+        link!("json.toBytes", json_to_bytes, ptr);
+        link!("json.stringify", json_stringify, ptr);
+
+        link!("yaml.fromBytes", yaml_from_bytes, ptr);
+        link!("yaml.try_fromBytes", yaml_try_from_bytes, ptr);
 
         link!("crypto.keccak256", crypto_keccak_256, ptr);
+        link!("crypto.sha256", crypto_sha256, ptr);
+        link!("crypto.ripemd160", crypto_ripemd160, ptr);
+        link!("crypto.blake2b", crypto_blake2b, ptr);
+        link!(
+            "crypto.ecrecover",
+            crypto_ecrecover,
+            hash_ptr,
+            signature_ptr
+        );
+        link!(
+            "crypto.ed25519Verify",
+            crypto_ed25519_verify,
+            pubkey_ptr,
+            message_ptr,
+            signature_ptr
+        );
 
         link!("bigInt.plus", big_int_plus, x_ptr, y_ptr);
         link!("bigInt.minus", big_int_minus, x_ptr, y_ptr);
@@ -499,6 +558,7 @@ impl<C: Blockchain> WasmInstance<C> {
         link!("bigInt.dividedByDecimal", big_int_divided_by_decimal, x, y);
         link!("bigInt.mod", big_int_mod, x_ptr, y_ptr);
         link!("bigInt.pow", big_int_pow, x_ptr, exp);
+        link!("bigInt.sqrt", big_int_sqrt, x_ptr);
         link!("bigInt.fromString", big_int_from_string, ptr);
         link!("bigInt.bitOr", big_int_bit_or, x_ptr, y_ptr);
         link!("bigInt.bitAnd", big_int_bit_and, x_ptr, y_ptr);
@@ -512,6 +572,13 @@ impl<C: Blockchain> WasmInstance<C> {
         link!("bigDecimal.times", big_decimal_times, x_ptr, y_ptr);
         link!("bigDecimal.dividedBy", big_decimal_divided_by, x, y);
         link!("bigDecimal.equals", big_decimal_equals, x_ptr, y_ptr);
+        link!(
+            "bigDecimal.fromFixed",
+            big_decimal_from_fixed,
+            x_ptr,
+            decimals
+        );
+        link!("bigDecimal.toFixed", big_decimal_to_fixed, x_ptr, decimals);
 
         link!("dataSource.create", data_source_create, name, params);
         link!(
@@ -524,10 +591,30 @@ impl<C: Blockchain> WasmInstance<C> {
         link!("dataSource.address", data_source_address,);
         link!("dataSource.network", data_source_network,);
         link!("dataSource.context", data_source_context,);
+        link!("dataSource.setContext", data_source_set_context, key, value);
+        link!("scheduler.atBlock", scheduler_at_block, block, handler_ptr);
+
+        link!("block.context.set", block_context_set, key, value);
+        link!("block.context.get", block_context_get, key);
 
         link!("ens.nameByHash", ens_name_by_hash, ptr);
 
+        link!(
+            "chain.blockTimestamp",
+            chain_block_timestamp,
+            network_ptr,
+            block_number
+        );
+
         link!("log.log", log_log, level, msg_ptr);
+        link!(
+            "log.logWithFields",
+            log_log_with_fields,
+            level,
+            msg_ptr,
+            fields_ptr
+        );
+        link!("log.subgraphWarning", log_subgraph_warning, msg_ptr);
 
         // `arweave and `box` functionality was removed, but apiVersion <= 0.0.4 must link it.
         if api_version <= Version::new(0, 0, 4) {
@@ -587,6 +674,18 @@ impl<C: Blockchain> WasmInstance<C> {
     }
 }
 
+impl<C: Blockchain> WasmInstanceContext<C> {
+    /// Whether allocating `size` more bytes would grow the memory past the
+    /// limit for the api version of the mapping
+    fn memory_limit_reached(&self, size: i32) -> bool {
+        exceeds_memory_limit(
+            self.memory.size() as u64,
+            size as u64,
+            max_memory_pages(&self.ctx.host_exports.api_version),
+        )
+    }
+}
+
 impl<C: Blockchain> AscHeap for WasmInstanceContext<C> {
     fn raw_new(&mut self, bytes: &[u8]) -> Result<u32, DeterministicHostError> {
         // We request large chunks from the AssemblyScript allocator to use as arenas that we
@@ -600,10 +699,25 @@ impl<C: Blockchain> AscHeap for WasmInstanceContext<C> {
             // causes at most half of memory to be wasted, which is acceptable.
             let arena_size = size.max(MIN_ARENA_SIZE);
 
-            // Unwrap: This may panic if more memory needs to be requested from the OS and that
-            // fails. This error is not deterministic since it depends on the operating conditions
-            // of the node.
-            self.arena_start_ptr = self.memory_allocate.call(arena_size).unwrap();
+            self.arena_start_ptr = match self.memory_allocate.call(arena_size) {
+                Ok(ptr) => ptr,
+
+                // The memory limit is the same on every node for a given api version, so
+                // growing the memory past it is a deterministic error.
+                Err(_) if self.memory_limit_reached(arena_size) => {
+                    return Err(DeterministicHostError::from(anyhow!(
+                        "mapping exceeded the memory limit of {} MiB",
+                        max_memory_pages(&self.ctx.host_exports.api_version) / 16
+                    )));
+                }
+
+                // Below the limit, this may fail if more memory needs to be requested from the
+                // OS and that fails. This error is not deterministic since it depends on the
+                // operating conditions of the node, and must not be reported as a handler error.
+                // Panicking only terminates the thread of this mapping, which makes the subgraph
+                // fail with a non-deterministic error that is retried.
+                Err(e) => panic!("failed to allocate {} bytes: {}", arena_size, e),
+            };
             self.arena_free_size = arena_size;
 
             match &self.ctx.host_exports.api_version {
@@ -631,6 +745,16 @@ impl<C: Blockchain> AscHeap for WasmInstanceContext<C> {
         Ok(ptr as u32)
     }
 
+    fn raw_write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), DeterministicHostError> {
+        self.memory.write(offset as usize, bytes).map_err(|_| {
+            DeterministicHostError::from(anyhow!(
+                "Heap access out of bounds. Offset: {} Size: {}",
+                offset,
+                bytes.len()
+            ))
+        })
+    }
+
     fn get(&self, offset: u32, size: u32) -> Result<Vec<u8>, DeterministicHostError> {
         let offset = offset as usize;
         let size = size as usize;
@@ -648,10 +772,31 @@ impl<C: Blockchain> AscHeap for WasmInstanceContext<C> {
         Ok(data)
     }
 
+    fn get_view(&self, offset: u32, size: u32) -> Result<Cow<[u8]>, DeterministicHostError> {
+        let start = offset as usize;
+        let end = start.checked_add(size as usize);
+
+        // Safety: the slice borrows `self`, and the memory can only grow,
+        // which would invalidate the slice, through `&mut self`
+        let memory = unsafe { self.memory.data_unchecked() };
+        match end {
+            Some(end) if end <= memory.len() => Ok(Cow::Borrowed(&memory[start..end])),
+            _ => Err(DeterministicHostError::from(anyhow!(
+                "Heap access out of bounds. Offset: {} Size: {}",
+                offset,
+                size
+            ))),
+        }
+    }
+
     fn api_version(&self) -> Version {
         self.ctx.host_exports.api_version.clone()
     }
 
+    fn big_decimal_precision(&self) -> BigDecimalPrecision {
+        self.ctx.host_exports.big_decimal_precision
+    }
+
     fn asc_type_id(
         &mut self,
         type_id_index: IndexForAscTypeId,
@@ -891,6 +1036,26 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         Ok(ret)
     }
 
+    /// function store.getInBlock(entity: string, id: string): Entity | null
+    pub fn store_get_in_block(
+        &mut self,
+        gas: &GasCounter,
+        entity_ptr: AscPtr<AscString>,
+        id_ptr: AscPtr<AscString>,
+    ) -> Result<AscPtr<AscEntity>, DeterministicHostError> {
+        let entity_type = asc_get(self, entity_ptr)?;
+        let id = asc_get(self, id_ptr)?;
+        let entity_option =
+            self.ctx
+                .host_exports
+                .store_get_in_block(&mut self.ctx.state, entity_type, id, gas)?;
+
+        match entity_option {
+            Some(entity) => asc_new(self, &entity.sorted()),
+            None => Ok(AscPtr::null()),
+        }
+    }
+
     /// function typeConversion.bytesToString(bytes: Bytes): string
     pub fn bytes_to_string(
         &mut self,
@@ -1035,12 +1200,52 @@ impl<C: Blockchain> WasmInstanceContext<C> {
 
         let link = asc_get(self, link_ptr)?;
         let ipfs_res = self.ctx.host_exports.ipfs_cat(&self.ctx.logger, link);
+        self.ipfs_bytes_or_null("ipfs.cat", link_ptr, ipfs_res)
+    }
+
+    /// function ipfs.getBlock(hash: String): Bytes
+    pub fn ipfs_get_block(
+        &mut self,
+        gas: &GasCounter,
+        link_ptr: AscPtr<AscString>,
+    ) -> Result<AscPtr<Uint8Array>, HostExportError> {
+        // Not enabled on the network, no gas consumed.
+        drop(gas);
+
+        if !self.experimental_features.allow_non_deterministic_ipfs {
+            return Err(HostExportError::Deterministic(anyhow!(
+                "`ipfs.getBlock` is not supported. Improved support for IPFS will be added in the future"
+            )));
+        }
+
+        let link = asc_get(self, link_ptr)?;
+        let ipfs_res = self.ctx.host_exports.ipfs_get_block(&self.ctx.logger, link);
+        self.ipfs_bytes_or_null("ipfs.getBlock", link_ptr, ipfs_res)
+    }
+
+    /// Turn the result of reading from IPFS into what the mapping gets. A
+    /// timeout might not happen on a retry or on another node and fails
+    /// the handler with a non-deterministic error; for any other error,
+    /// like a file that is too large or does not exist, the mapping gets
+    /// `null`
+    fn ipfs_bytes_or_null(
+        &mut self,
+        host_fn: &str,
+        link_ptr: AscPtr<AscString>,
+        ipfs_res: Result<Vec<u8>, anyhow::Error>,
+    ) -> Result<AscPtr<Uint8Array>, HostExportError> {
         match ipfs_res {
             Ok(bytes) => asc_new(self, &*bytes).map_err(Into::into),
 
+            Err(e) if is_timeout(&e) => Err(HostExportError::Unknown(e.context(format!(
+                "{} of `{}` timed out",
+                host_fn,
+                asc_get::<String, _, _>(self, link_ptr)?
+            )))),
+
             // Return null in case of error.
             Err(e) => {
-                info!(&self.ctx.logger, "Failed ipfs.cat, returning `null`";
+                info!(&self.ctx.logger, "Failed {}, returning `null`", host_fn;
                                     "link" => asc_get::<String, _, _>(self, link_ptr)?,
                                     "error" => e.to_string());
                 Ok(AscPtr::null())
@@ -1154,6 +1359,76 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         asc_new(self, &*big_int)
     }
 
+    /// function json.toBytes(value: JSONValue): Bytes
+    pub fn json_to_bytes(
+        &mut self,
+        gas: &GasCounter,
+        value_ptr: AscPtr<AscEnum<JsonValueKind>>,
+    ) -> Result<AscPtr<Uint8Array>, DeterministicHostError> {
+        let value: serde_json::Value = try_asc_get(self, value_ptr)?;
+        let json = self.ctx.host_exports.json_to_canonical(&value, gas)?;
+        asc_new(self, json.as_bytes())
+    }
+
+    /// function json.stringify(value: JSONValue): string
+    pub fn json_stringify(
+        &mut self,
+        gas: &GasCounter,
+        value_ptr: AscPtr<AscEnum<JsonValueKind>>,
+    ) -> Result<AscPtr<AscString>, DeterministicHostError> {
+        let value: serde_json::Value = try_asc_get(self, value_ptr)?;
+        let json = self.ctx.host_exports.json_to_canonical(&value, gas)?;
+        asc_new(self, json.as_str())
+    }
+
+    /// function yaml.fromBytes(bytes: Bytes): YAMLValue
+    pub fn yaml_from_bytes(
+        &mut self,
+        gas: &GasCounter,
+        bytes_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<AscEnum<YamlValueKind>>, DeterministicHostError> {
+        let bytes: Vec<u8> = asc_get(self, bytes_ptr)?;
+        let result = self
+            .ctx
+            .host_exports
+            .yaml_from_bytes(&bytes, gas)
+            .with_context(|| {
+                format!(
+                    "Failed to parse YAML from byte array. Bytes (truncated to 1024 chars): `{:?}`",
+                    &bytes[..bytes.len().min(1024)],
+                )
+            })
+            .map_err(DeterministicHostError::from)?;
+        asc_new(self, &result)
+    }
+
+    /// function yaml.try_fromBytes(bytes: Bytes): Result<YAMLValue, boolean>
+    pub fn yaml_try_from_bytes(
+        &mut self,
+        gas: &GasCounter,
+        bytes_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<AscResult<AscPtr<AscEnum<YamlValueKind>>, bool>>, DeterministicHostError>
+    {
+        let bytes: Vec<u8> = asc_get(self, bytes_ptr)?;
+        let result = self
+            .ctx
+            .host_exports
+            .yaml_from_bytes(&bytes, gas)
+            .map_err(|e| {
+                warn!(
+                    &self.ctx.logger,
+                    "Failed to parse YAML from byte array";
+                    "bytes" => format!("{:?}", bytes),
+                    "error" => format!("{}", e)
+                );
+
+                // Map YAML errors to boolean to match the `Result<YAMLValue, boolean>`
+                // result type expected by mappings
+                true
+            });
+        asc_new(self, &result)
+    }
+
     /// function crypto.keccak256(input: Bytes): Bytes
     pub fn crypto_keccak_256(
         &mut self,
@@ -1167,6 +1442,79 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         asc_new(self, input.as_ref())
     }
 
+    /// function crypto.sha256(input: Bytes): Bytes
+    pub fn crypto_sha256(
+        &mut self,
+        gas: &GasCounter,
+        input_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<Uint8Array>, DeterministicHostError> {
+        let hash = self
+            .ctx
+            .host_exports
+            .crypto_sha256(asc_get(self, input_ptr)?, gas)?;
+        asc_new(self, hash.as_slice())
+    }
+
+    /// function crypto.ripemd160(input: Bytes): Bytes
+    pub fn crypto_ripemd160(
+        &mut self,
+        gas: &GasCounter,
+        input_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<Uint8Array>, DeterministicHostError> {
+        let hash = self
+            .ctx
+            .host_exports
+            .crypto_ripemd160(asc_get(self, input_ptr)?, gas)?;
+        asc_new(self, hash.as_slice())
+    }
+
+    /// function crypto.ecrecover(messageHash: Bytes, signature: Bytes): Address | null
+    pub fn crypto_ecrecover(
+        &mut self,
+        gas: &GasCounter,
+        hash_ptr: AscPtr<Uint8Array>,
+        signature_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<Uint8Array>, DeterministicHostError> {
+        let address = self.ctx.host_exports.crypto_ecrecover(
+            asc_get(self, hash_ptr)?,
+            asc_get(self, signature_ptr)?,
+            gas,
+        )?;
+        match address {
+            Some(address) => asc_new(self, address.as_bytes()),
+            None => Ok(AscPtr::null()),
+        }
+    }
+
+    /// function crypto.ed25519Verify(pubkey: Bytes, message: Bytes, signature: Bytes): bool
+    pub fn crypto_ed25519_verify(
+        &mut self,
+        gas: &GasCounter,
+        pubkey_ptr: AscPtr<Uint8Array>,
+        message_ptr: AscPtr<Uint8Array>,
+        signature_ptr: AscPtr<Uint8Array>,
+    ) -> Result<bool, DeterministicHostError> {
+        self.ctx.host_exports.crypto_ed25519_verify(
+            asc_get(self, pubkey_ptr)?,
+            asc_get(self, message_ptr)?,
+            asc_get(self, signature_ptr)?,
+            gas,
+        )
+    }
+
+    /// function crypto.blake2b(input: Bytes): Bytes
+    pub fn crypto_blake2b(
+        &mut self,
+        gas: &GasCounter,
+        input_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<Uint8Array>, DeterministicHostError> {
+        let hash = self
+            .ctx
+            .host_exports
+            .crypto_blake2b(asc_get(self, input_ptr)?, gas)?;
+        asc_new(self, hash.as_slice())
+    }
+
     /// function bigInt.plus(x: BigInt, y: BigInt): BigInt
     pub fn big_int_plus(
         &mut self,
@@ -1271,6 +1619,19 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         asc_new(self, &result)
     }
 
+    /// function bigInt.sqrt(x: BigInt): BigInt
+    pub fn big_int_sqrt(
+        &mut self,
+        gas: &GasCounter,
+        x_ptr: AscPtr<AscBigInt>,
+    ) -> Result<AscPtr<AscBigInt>, DeterministicHostError> {
+        let result = self
+            .ctx
+            .host_exports
+            .big_int_sqrt(asc_get(self, x_ptr)?, gas)?;
+        asc_new(self, &result)
+    }
+
     /// function bigInt.bitOr(x: BigInt, y: BigInt): BigInt
     pub fn big_int_bit_or(
         &mut self,
@@ -1344,6 +1705,58 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         asc_new(self, &result)
     }
 
+    /// function encoding.hexEncode(bytes: Bytes): string
+    pub fn encoding_hex_encode(
+        &mut self,
+        gas: &GasCounter,
+        bytes_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<AscString>, DeterministicHostError> {
+        let result = self
+            .ctx
+            .host_exports
+            .encoding_hex_encode(asc_get(self, bytes_ptr)?, gas)?;
+        asc_new(self, &result)
+    }
+
+    /// function encoding.hexDecode(s: string): Bytes
+    pub fn encoding_hex_decode(
+        &mut self,
+        gas: &GasCounter,
+        str_ptr: AscPtr<AscString>,
+    ) -> Result<AscPtr<Uint8Array>, DeterministicHostError> {
+        let result = self
+            .ctx
+            .host_exports
+            .encoding_hex_decode(asc_get(self, str_ptr)?, gas)?;
+        asc_new(self, result.as_slice())
+    }
+
+    /// function encoding.base64Encode(bytes: Bytes): string
+    pub fn encoding_base64_encode(
+        &mut self,
+        gas: &GasCounter,
+        bytes_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<AscString>, DeterministicHostError> {
+        let result = self
+            .ctx
+            .host_exports
+            .encoding_base64_encode(asc_get(self, bytes_ptr)?, gas)?;
+        asc_new(self, &result)
+    }
+
+    /// function encoding.base64Decode(s: string): Bytes
+    pub fn encoding_base64_decode(
+        &mut self,
+        gas: &GasCounter,
+        str_ptr: AscPtr<AscString>,
+    ) -> Result<AscPtr<Uint8Array>, DeterministicHostError> {
+        let result = self
+            .ctx
+            .host_exports
+            .encoding_base64_decode(asc_get(self, str_ptr)?, gas)?;
+        asc_new(self, result.as_slice())
+    }
+
     /// function bigDecimal.toString(x: BigDecimal): string
     pub fn big_decimal_to_string(
         &mut self,
@@ -1444,6 +1857,38 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         )
     }
 
+    /// function bigDecimal.fromFixed(x: BigInt, decimals: u8): BigDecimal
+    pub fn big_decimal_from_fixed(
+        &mut self,
+        gas: &GasCounter,
+        x_ptr: AscPtr<AscBigInt>,
+        decimals: u32,
+    ) -> Result<AscPtr<AscBigDecimal>, DeterministicHostError> {
+        let decimals =
+            u8::try_from(decimals).map_err(|e| DeterministicHostError::from(Error::from(e)))?;
+        let result =
+            self.ctx
+                .host_exports
+                .big_decimal_from_fixed(asc_get(self, x_ptr)?, decimals, gas)?;
+        asc_new(self, &result)
+    }
+
+    /// function bigDecimal.toFixed(x: BigDecimal, decimals: u8): BigInt
+    pub fn big_decimal_to_fixed(
+        &mut self,
+        gas: &GasCounter,
+        x_ptr: AscPtr<AscBigDecimal>,
+        decimals: u32,
+    ) -> Result<AscPtr<AscBigInt>, DeterministicHostError> {
+        let decimals =
+            u8::try_from(decimals).map_err(|e| DeterministicHostError::from(Error::from(e)))?;
+        let result =
+            self.ctx
+                .host_exports
+                .big_decimal_to_fixed(try_asc_get(self, x_ptr)?, decimals, gas)?;
+        asc_new(self, &result)
+    }
+
     /// function dataSource.create(name: string, params: Array<string>): void
     pub fn data_source_create(
         &mut self,
@@ -1464,6 +1909,38 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         )
     }
 
+    /// function scheduler.atBlock(block: i32, handler: string): void
+    pub fn scheduler_at_block(
+        &mut self,
+        gas: &GasCounter,
+        block: u32,
+        handler_ptr: AscPtr<AscString>,
+    ) -> Result<(), DeterministicHostError> {
+        let block = block as i32;
+        let handler: String = asc_get(self, handler_ptr)?;
+
+        // Catch typos in the handler name now rather than when the
+        // handler becomes due
+        match self.valid_module.module.get_export(&handler) {
+            Some(wasmtime::ExternType::Func(_)) => {}
+            _ => {
+                return Err(DeterministicHostError::from(anyhow!(
+                    "Failed to schedule handler `{}`: the mapping does not export a function with that name",
+                    handler
+                )))
+            }
+        }
+
+        self.ctx.host_exports.scheduler_at_block(
+            &self.ctx.logger,
+            &mut self.ctx.state,
+            block,
+            handler,
+            self.ctx.block_ptr.number,
+            gas,
+        )
+    }
+
     /// function createWithContext(name: string, params: Array<string>, context: DataSourceContext): void
     pub fn data_source_create_with_context(
         &mut self,
@@ -1512,10 +1989,59 @@ impl<C: Blockchain> WasmInstanceContext<C> {
     ) -> Result<AscPtr<AscEntity>, DeterministicHostError> {
         asc_new(
             self,
-            &self.ctx.host_exports.data_source_context(gas)?.sorted(),
+            &self
+                .ctx
+                .host_exports
+                .data_source_context(&self.ctx.state, gas)?
+                .sorted(),
         )
     }
 
+    /// function dataSource.setContext(key: string, value: Value): void
+    pub fn data_source_set_context(
+        &mut self,
+        gas: &GasCounter,
+        key_ptr: AscPtr<AscString>,
+        value_ptr: AscPtr<AscEnum<StoreValueKind>>,
+    ) -> Result<(), DeterministicHostError> {
+        let key = asc_get(self, key_ptr)?;
+        let value = try_asc_get(self, value_ptr)?;
+        self.ctx
+            .host_exports
+            .data_source_set_context(&mut self.ctx.state, key, value, gas)
+    }
+
+    /// function block.context.set(key: string, value: Value): void
+    pub fn block_context_set(
+        &mut self,
+        gas: &GasCounter,
+        key_ptr: AscPtr<AscString>,
+        value_ptr: AscPtr<AscEnum<StoreValueKind>>,
+    ) -> Result<(), DeterministicHostError> {
+        let key = asc_get(self, key_ptr)?;
+        let value = try_asc_get(self, value_ptr)?;
+        self.ctx
+            .host_exports
+            .block_context_set(&mut self.ctx.state, key, value, gas)
+    }
+
+    /// function block.context.get(key: string): Value | null
+    pub fn block_context_get(
+        &mut self,
+        gas: &GasCounter,
+        key_ptr: AscPtr<AscString>,
+    ) -> Result<AscPtr<AscEnum<StoreValueKind>>, DeterministicHostError> {
+        let key = asc_get(self, key_ptr)?;
+        match self
+            .ctx
+            .host_exports
+            .block_context_get(&self.ctx.state, key, gas)?
+        {
+            Some(value) => asc_new(self, &value),
+            None => Ok(AscPtr::null()),
+        }
+    }
+
     pub fn ens_name_by_hash(
         &mut self,
         gas: &GasCounter,
@@ -1539,6 +2065,21 @@ impl<C: Blockchain> WasmInstanceContext<C> {
             .unwrap_or(Ok(AscPtr::null()))
     }
 
+    /// function chain.blockTimestamp(network: string, blockNumber: i32): BigInt
+    pub fn chain_block_timestamp(
+        &mut self,
+        gas: &GasCounter,
+        network_ptr: AscPtr<AscString>,
+        block_number: i32,
+    ) -> Result<AscPtr<AscBigInt>, HostExportError> {
+        let network: String = asc_get(self, network_ptr)?;
+        let timestamp = self
+            .ctx
+            .host_exports
+            .chain_block_timestamp(&network, block_number, gas)?;
+        asc_new(self, &BigInt::from(timestamp)).map_err(Into::into)
+    }
+
     pub fn log_log(
         &mut self,
         gas: &GasCounter,
@@ -1549,7 +2090,39 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         let msg: String = asc_get(self, msg)?;
         self.ctx
             .host_exports
-            .log_log(&self.ctx.logger, level, msg, gas)
+            .log_log(&self.ctx.logger, level, msg, HashMap::new(), gas)
+    }
+
+    /// function log.logWithFields(level: number, msg: string, fields: Entity): void
+    pub fn log_log_with_fields(
+        &mut self,
+        gas: &GasCounter,
+        level: u32,
+        msg: AscPtr<AscString>,
+        fields_ptr: AscPtr<AscEntity>,
+    ) -> Result<(), DeterministicHostError> {
+        let level = LogLevel::from(level).into();
+        let msg: String = asc_get(self, msg)?;
+        let fields = try_asc_get(self, fields_ptr)?;
+        self.ctx
+            .host_exports
+            .log_log(&self.ctx.logger, level, msg, fields, gas)
+    }
+
+    /// function log.subgraphWarning(msg: string): void
+    pub fn log_subgraph_warning(
+        &mut self,
+        gas: &GasCounter,
+        msg: AscPtr<AscString>,
+    ) -> Result<(), DeterministicHostError> {
+        let msg: String = asc_get(self, msg)?;
+        self.ctx.host_exports.log_subgraph_warning(
+            &self.ctx.logger,
+            &mut self.ctx.state,
+            &self.ctx.block_ptr,
+            msg,
+            gas,
+        )
     }
 
     /// function encode(token: ethereum.Value): Bytes | null
@@ -1609,3 +2182,13 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         )))
     }
 }
+
+/// Whether `e` was caused by a request that timed out
+fn is_timeout(e: &Error) -> bool {
+    e.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .map(|e| e.is_timeout())
+            .unwrap_or(false)
+    })
+}
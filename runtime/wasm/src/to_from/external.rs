@@ -1,10 +1,12 @@
 use ethabi;
+use std::str::FromStr;
 
+use graph::data::subgraph::API_VERSION_0_0_13;
 use graph::runtime::{
     asc_get, asc_new, try_asc_get, AscIndexId, AscPtr, AscType, AscValue, ToAscObj,
 };
 use graph::{data::store, runtime::DeterministicHostError};
-use graph::{prelude::serde_json, runtime::FromAscObj};
+use graph::{prelude::serde_json, prelude::serde_yaml, runtime::FromAscObj};
 use graph::{prelude::web3::types as web3, runtime::AscHeap};
 use graph::{
     prelude::{BigDecimal, BigInt},
@@ -12,6 +14,7 @@ use graph::{
 };
 
 use crate::asc_abi::class::*;
+use crate::to_from::asc_hashed_string_map;
 
 impl ToAscObj<Uint8Array> for web3::H160 {
     fn to_asc_obj<H: AscHeap + ?Sized>(
@@ -108,7 +111,11 @@ impl TryFromAscObj<AscBigDecimal> for BigDecimal {
         let bytes = exp.to_signed_bytes_le();
         let mut byte_array = if exp >= 0.into() { [0; 8] } else { [255; 8] };
         byte_array[..bytes.len()].copy_from_slice(&bytes);
-        let big_decimal = BigDecimal::new(digits, i64::from_le_bytes(byte_array));
+        let big_decimal = BigDecimal::new_with_precision(
+            digits,
+            i64::from_le_bytes(byte_array),
+            &heap.big_decimal_precision(),
+        );
 
         // Validate the exponent.
         let exp = -big_decimal.as_bigint_and_exponent().1;
@@ -300,9 +307,12 @@ impl ToAscObj<AscJson> for serde_json::Map<String, serde_json::Value> {
         &self,
         heap: &mut H,
     ) -> Result<AscJson, DeterministicHostError> {
-        Ok(AscTypedMap {
+        if heap.api_version() >= API_VERSION_0_0_13 {
+            return asc_hashed_string_map(heap, self.iter().map(|(k, v)| (k.as_str(), v)));
+        }
+        Ok(AscStringMap::Flat(AscTypedMap {
             entries: asc_new(heap, &*self.iter().collect::<Vec<_>>())?,
-        })
+        }))
     }
 }
 
@@ -312,8 +322,69 @@ impl ToAscObj<AscEntity> for Vec<(String, store::Value)> {
         &self,
         heap: &mut H,
     ) -> Result<AscEntity, DeterministicHostError> {
-        Ok(AscTypedMap {
+        if heap.api_version() >= API_VERSION_0_0_13 {
+            return asc_hashed_string_map(heap, self.iter().map(|(k, v)| (k.as_str(), v)));
+        }
+        Ok(AscStringMap::Flat(AscTypedMap {
             entries: asc_new(heap, self.as_slice())?,
+        }))
+    }
+}
+
+impl TryFromAscObj<AscJson> for serde_json::Map<String, serde_json::Value> {
+    fn try_from_asc_obj<H: AscHeap + ?Sized>(
+        asc_map: AscJson,
+        heap: &H,
+    ) -> Result<Self, DeterministicHostError> {
+        let entries: Vec<(String, serde_json::Value)> = Vec::try_from_asc_obj(asc_map, heap)?;
+        let mut map = serde_json::Map::new();
+        for (key, value) in entries {
+            if map.contains_key(&key) {
+                return Err(DeterministicHostError::from(anyhow::anyhow!(
+                    "JSON object has more than one entry for key `{}`",
+                    key
+                )));
+            }
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+impl TryFromAscObj<AscEnum<JsonValueKind>> for serde_json::Value {
+    fn try_from_asc_obj<H: AscHeap + ?Sized>(
+        asc_enum: AscEnum<JsonValueKind>,
+        heap: &H,
+    ) -> Result<Self, DeterministicHostError> {
+        use serde_json::Value;
+
+        let payload = asc_enum.payload;
+        Ok(match asc_enum.kind {
+            JsonValueKind::Null => Value::Null,
+            JsonValueKind::Bool => Value::Bool(bool::from(payload)),
+            JsonValueKind::Number => {
+                let ptr: AscPtr<AscString> = AscPtr::from(payload);
+                let number: String = asc_get(heap, ptr)?;
+                let number = serde_json::Number::from_str(&number).map_err(|_| {
+                    DeterministicHostError::from(anyhow::anyhow!(
+                        "`{}` is not a JSON number",
+                        number
+                    ))
+                })?;
+                Value::Number(number)
+            }
+            JsonValueKind::String => {
+                let ptr: AscPtr<AscString> = AscPtr::from(payload);
+                Value::String(asc_get(heap, ptr)?)
+            }
+            JsonValueKind::Array => {
+                let ptr: AscEnumArray<JsonValueKind> = AscPtr::from(payload);
+                Value::Array(try_asc_get(heap, ptr)?)
+            }
+            JsonValueKind::Object => {
+                let ptr: AscPtr<AscJson> = AscPtr::from(payload);
+                Value::Object(try_asc_get(heap, ptr)?)
+            }
         })
     }
 }
@@ -342,6 +413,41 @@ impl ToAscObj<AscEnum<JsonValueKind>> for serde_json::Value {
     }
 }
 
+impl ToAscObj<AscYamlMap> for serde_yaml::Mapping {
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscYamlMap, DeterministicHostError> {
+        Ok(AscTypedMap {
+            entries: asc_new(heap, &*self.iter().collect::<Vec<_>>())?,
+        })
+    }
+}
+
+impl ToAscObj<AscEnum<YamlValueKind>> for serde_yaml::Value {
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscEnum<YamlValueKind>, DeterministicHostError> {
+        use serde_yaml::Value;
+
+        let payload = match self {
+            Value::Null => EnumPayload(0),
+            Value::Bool(b) => EnumPayload::from(*b),
+            Value::Number(number) => asc_new(heap, &*number.to_string())?.into(),
+            Value::String(string) => asc_new(heap, string.as_str())?.into(),
+            Value::Sequence(sequence) => asc_new(heap, sequence.as_slice())?.into(),
+            Value::Mapping(mapping) => asc_new(heap, mapping)?.into(),
+        };
+
+        Ok(AscEnum {
+            kind: YamlValueKind::get_kind(self),
+            _padding: 0,
+            payload,
+        })
+    }
+}
+
 impl From<u32> for LogLevel {
     fn from(i: u32) -> Self {
         match i {
@@ -11,6 +11,7 @@ use graph::runtime::{
 };
 
 use crate::asc_abi::class::*;
+use crate::asc_abi::v0_0_5;
 
 ///! Implementations of `ToAscObj` and `FromAscObj` for Rust types.
 ///! Standard Rust types go in `mod.rs` and external types in `external.rs`.
@@ -212,3 +213,57 @@ where
         Ok(HashMap::from_iter(entries.into_iter()))
     }
 }
+
+impl<V: AscType + AscIndexId, T: TryFromAscObj<AscString>, U: TryFromAscObj<V>>
+    TryFromAscObj<AscStringMap<V>> for Vec<(T, U)>
+where
+    Array<AscPtr<AscTypedMapEntry<AscString, V>>>: AscIndexId,
+    AscTypedMapEntry<AscString, V>: AscIndexId,
+{
+    fn try_from_asc_obj<H: AscHeap + ?Sized>(
+        asc_map: AscStringMap<V>,
+        heap: &H,
+    ) -> Result<Self, DeterministicHostError> {
+        match asc_map {
+            AscStringMap::Flat(map) => try_asc_get(heap, map.entries),
+            AscStringMap::Hashed(map) => map
+                .entries(heap)?
+                .into_iter()
+                .map(|(key, value)| Ok((try_asc_get(heap, key)?, try_asc_get(heap, value)?)))
+                .collect(),
+        }
+    }
+}
+
+impl<V: AscType + AscIndexId, T: TryFromAscObj<AscString> + Hash + Eq, U: TryFromAscObj<V>>
+    TryFromAscObj<AscStringMap<V>> for HashMap<T, U>
+where
+    Array<AscPtr<AscTypedMapEntry<AscString, V>>>: AscIndexId,
+    AscTypedMapEntry<AscString, V>: AscIndexId,
+{
+    fn try_from_asc_obj<H: AscHeap + ?Sized>(
+        asc_map: AscStringMap<V>,
+        heap: &H,
+    ) -> Result<Self, DeterministicHostError> {
+        let entries: Vec<(T, U)> = Vec::try_from_asc_obj(asc_map, heap)?;
+        Ok(HashMap::from_iter(entries.into_iter()))
+    }
+}
+
+/// Allocate `entries` as an AssemblyScript `Map`. The keys must be distinct.
+pub(crate) fn asc_hashed_string_map<'a, V, T, H>(
+    heap: &mut H,
+    entries: impl Iterator<Item = (&'a str, &'a T)>,
+) -> Result<AscStringMap<V>, DeterministicHostError>
+where
+    V: AscType + AscIndexId,
+    T: ToAscObj<V> + 'a,
+    H: AscHeap + ?Sized,
+{
+    let mut asc_entries = Vec::new();
+    for (key, value) in entries {
+        let hash = v0_0_5::string_hash(&key.encode_utf16().collect::<Vec<_>>());
+        asc_entries.push((asc_new(heap, key)?, asc_new(heap, value)?, hash));
+    }
+    Ok(AscStringMap::Hashed(v0_0_5::Map::new(&asc_entries, heap)?))
+}
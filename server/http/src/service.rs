@@ -17,6 +17,11 @@ use hyper::{Body, Method, Request, Response, StatusCode};
 
 use crate::request::GraphQLRequest;
 
+/// Clients that send this header with the value `json` get `BigInt` and
+/// `BigDecimal` values as JSON numbers whenever that does not lose
+/// precision
+const BIG_NUMBERS_HEADER: &str = "Graph-Big-Numbers";
+
 pub struct GraphQLServiceMetrics {
     query_execution_time: Box<HistogramVec>,
     failed_query_execution_time: Box<HistogramVec>,
@@ -175,7 +180,7 @@ where
             GraphQLServerError::ClientError(format!("Invalid subgraph name {:?}", subgraph_name))
         })?;
 
-        self.handle_graphql_query(subgraph_name.into(), request.into_body())
+        self.handle_graphql_query(subgraph_name.into(), request)
             .await
     }
 
@@ -188,28 +193,36 @@ where
             .map_err(|id| GraphQLServerError::ClientError(format!("Invalid subgraph id `{}`", id)));
         match res {
             Err(_) => self.handle_not_found(),
-            Ok(id) => self
-                .handle_graphql_query(id.into(), request.into_body())
-                .boxed(),
+            Ok(id) => self.handle_graphql_query(id.into(), request).boxed(),
         }
     }
 
     async fn handle_graphql_query(
         self,
         target: QueryTarget,
-        request_body: Body,
+        request: Request<Body>,
     ) -> GraphQLServiceResult {
         let service = self.clone();
         let service_metrics = self.metrics.clone();
 
+        let big_numbers_as_json = request
+            .headers()
+            .get(BIG_NUMBERS_HEADER)
+            .map_or(false, |value| {
+                value.as_bytes().eq_ignore_ascii_case(b"json")
+            });
+
         let start = Instant::now();
-        let body = hyper::body::to_bytes(request_body)
+        let body = hyper::body::to_bytes(request.into_body())
             .map_err(|_| GraphQLServerError::InternalError("Failed to read request body".into()))
             .await?;
         let query = GraphQLRequest::new(body).compat().await;
 
         let result = match query {
-            Ok(query) => service.graphql_runner.run_query(query, target).await,
+            Ok(mut query) => {
+                query.big_numbers_as_json = big_numbers_as_json;
+                service.graphql_runner.run_query(query, target).await
+            }
             Err(GraphQLServerError::QueryError(e)) => QueryResult::from(e).into(),
             Err(e) => return Err(e),
         };
@@ -228,7 +241,10 @@ where
             Ok(Response::builder()
                 .status(200)
                 .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
-                .header(ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type, User-Agent")
+                .header(
+                    ACCESS_CONTROL_ALLOW_HEADERS,
+                    format!("Content-Type, User-Agent, {}", BIG_NUMBERS_HEADER),
+                )
                 .header(ACCESS_CONTROL_ALLOW_METHODS, "GET, OPTIONS, POST")
                 .header(CONTENT_TYPE, "text/html")
                 .body(Body::from(""))
@@ -6,8 +6,8 @@ use graph::data::subgraph::features::detect_features;
 use graph::data::subgraph::{status, MAX_SPEC_VERSION};
 use graph::prelude::*;
 use graph::{
-    components::store::StatusStore,
-    data::graphql::{IntoValue, ObjectOrInterface, ValueMap},
+    components::{store::StatusStore, transaction_receipt::LightTransactionReceipt},
+    data::graphql::{object, IntoValue, ObjectOrInterface, ValueMap},
 };
 use graph_graphql::prelude::{a, ExecutionContext, Resolver};
 use std::convert::TryInto;
@@ -55,11 +55,33 @@ where
                     .collect(),
                 _ => unreachable!(),
             })
-            .unwrap_or_else(|| Vec::new());
+            .unwrap_or_default();
 
-        let infos = self
+        let labels: Vec<(String, String)> = field
+            .argument_value("labels")
+            .map(|value| match value {
+                r::Value::List(labels) => labels
+                    .into_iter()
+                    .map(|label| match label {
+                        r::Value::Object(obj) => match (obj.get("key"), obj.get("value")) {
+                            (Some(r::Value::String(key)), Some(r::Value::String(value))) => {
+                                (key.clone(), value.clone())
+                            }
+                            _ => unreachable!(),
+                        },
+                        _ => unreachable!(),
+                    })
+                    .collect(),
+                _ => unreachable!(),
+            })
+            .unwrap_or_default();
+
+        let infos: Vec<_> = self
             .store
-            .status(status::Filter::Deployments(deployments))?;
+            .status(status::Filter::Deployments(deployments))?
+            .into_iter()
+            .filter(|info| info.has_labels(&labels))
+            .collect();
         Ok(infos.into_value())
     }
 
@@ -131,6 +153,25 @@ where
         Ok(poi)
     }
 
+    async fn resolve_transaction_receipts(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        // We can safely unwrap because the arguments are non-nullable and
+        // have been validated.
+        let network = field.get_required::<String>("network").unwrap();
+        let block_hash = field.get_required::<H256>("blockHash").unwrap();
+
+        let receipts = self
+            .store
+            .transaction_receipts(&network, &block_hash)
+            .await?;
+
+        Ok(r::Value::List(
+            receipts.into_iter().map(receipt_to_value).collect(),
+        ))
+    }
+
     fn resolve_indexing_status_for_version(
         &self,
         field: &a::Field,
@@ -247,6 +288,52 @@ where
     }
 }
 
+fn receipt_to_value(receipt: LightTransactionReceipt) -> r::Value {
+    fn bytes(bytes: &[u8]) -> r::Value {
+        r::Value::from(Value::Bytes(bytes.into()))
+    }
+
+    let LightTransactionReceipt {
+        transaction_hash,
+        transaction_index,
+        block_hash,
+        block_number,
+        cumulative_gas_used,
+        gas_used,
+        status,
+        logs,
+    } = receipt;
+
+    let logs: Vec<_> = logs
+        .into_iter()
+        .map(|log| {
+            object! {
+                __typename: "Log",
+                address: bytes(log.address.as_bytes()),
+                topics: log
+                    .topics
+                    .iter()
+                    .map(|topic| bytes(topic.as_bytes()))
+                    .collect::<Vec<_>>(),
+                data: bytes(&log.data.0),
+                logIndex: log.log_index.map(|index| index.to_string()),
+            }
+        })
+        .collect();
+
+    object! {
+        __typename: "TransactionReceipt",
+        transactionHash: bytes(transaction_hash.as_bytes()),
+        transactionIndex: transaction_index.to_string(),
+        blockHash: block_hash.map(|hash| bytes(hash.as_bytes())),
+        blockNumber: block_number.map(|number| number.to_string()),
+        cumulativeGasUsed: cumulative_gas_used.map(|gas| gas.to_string()),
+        gasUsed: gas_used.map(|gas| gas.to_string()),
+        status: status.map(|status| status.to_string()),
+        logs: logs,
+    }
+}
+
 struct ValidationPostProcessResult {
     features: r::Value,
     errors: r::Value,
@@ -416,6 +503,11 @@ where
                 self.resolve_indexing_statuses_for_subgraph_name(field)
             }
 
+            // The top-level `transactionReceipts` field
+            (None, "TransactionReceipt", "transactionReceipts") => {
+                graph::block_on(self.resolve_transaction_receipts(field))
+            }
+
             // Resolve fields of `Object` values (e.g. the `chains` field of `ChainIndexingStatus`)
             (value, _, _) => Ok(value.unwrap_or(r::Value::Null)),
         }
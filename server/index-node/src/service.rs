@@ -7,7 +7,10 @@ use hyper::{Body, Method, Request, Response, StatusCode};
 use std::task::Context;
 use std::task::Poll;
 
-use graph::{components::server::query::GraphQLServerError, data::query::QueryResults};
+use graph::{
+    components::server::{index_node::BlockLookup, query::GraphQLServerError},
+    data::query::QueryResults,
+};
 use graph::{components::store::StatusStore, prelude::*};
 use graph_graphql::prelude::{execute_query, Query as PreparedQuery, QueryExecutionOptions};
 
@@ -186,6 +189,36 @@ where
             .unwrap()
     }
 
+    /// Handles requests for the blocks that the chain store holds for a
+    /// network. The block can be given by number or by hash
+    fn handle_stored_blocks(
+        &self,
+        network: &str,
+        block: &str,
+    ) -> Result<Response<Body>, GraphQLServerError> {
+        let lookup = match block.parse::<BlockNumber>() {
+            Ok(number) => BlockLookup::Number(number),
+            Err(_) => BlockLookup::Hash(block.trim_start_matches("0x").parse().map_err(|_| {
+                GraphQLServerError::ClientError(format!(
+                    "`{}` is neither a block number nor a block hash",
+                    block
+                ))
+            })?),
+        };
+
+        let blocks = self.store.stored_blocks(network, lookup)?;
+        if blocks.is_empty() {
+            return Ok(Self::handle_not_found());
+        }
+        let json = serde_json::to_string(&blocks).expect("Failed to serialize blocks to JSON");
+        Ok(Response::builder()
+            .status(200)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(json))
+            .unwrap())
+    }
+
     async fn handle_call(self, req: Request<Body>) -> Result<Response<Body>, GraphQLServerError> {
         let method = req.method().clone();
 
@@ -221,6 +254,8 @@ where
 
             (Method::GET, ["explorer", rest @ ..]) => self.explorer.handle(&self.logger, rest),
 
+            (Method::GET, ["blocks", network, block]) => self.handle_stored_blocks(network, block),
+
             _ => Ok(Self::handle_not_found()),
         }
     }
@@ -0,0 +1,4 @@
+alter table public.ethereum_networks
+    drop column backfill_target_block_number,
+    drop column backfill_block_number,
+    drop column backfill_cursor;
@@ -0,0 +1,4 @@
+alter table public.ethereum_networks
+    add column backfill_target_block_number int4 default null,
+    add column backfill_block_number int4 default null,
+    add column backfill_cursor text default null;
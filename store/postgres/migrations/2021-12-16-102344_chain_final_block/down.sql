@@ -0,0 +1,3 @@
+alter table public.ethereum_networks
+    drop column final_block_hash,
+    drop column final_block_number;
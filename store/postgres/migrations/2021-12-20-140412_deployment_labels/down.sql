@@ -0,0 +1 @@
+drop table subgraphs.deployment_labels;
@@ -0,0 +1,8 @@
+-- Labels are keyed by deployment hash rather than deployment id so that
+-- they can be set before a deployment is created and be used to place it
+create table subgraphs.deployment_labels(
+    deployment text not null,
+    key        text not null,
+    value      text not null,
+    primary key(deployment, key)
+);
@@ -0,0 +1 @@
+drop table if exists public.eth_abi_cache;
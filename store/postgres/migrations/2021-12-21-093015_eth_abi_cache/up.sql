@@ -0,0 +1,10 @@
+-- ABIs of verified contracts that were fetched from Sourcify for dynamic
+-- data sources whose template does not bundle an ABI
+create table if not exists public.eth_abi_cache(
+  network    text not null,
+  address    bytea not null,
+  code_hash  bytea not null,
+  abi        text not null,
+  fetched_at timestamptz not null default now(),
+  primary key(network, address, code_hash)
+);
@@ -0,0 +1,15 @@
+-- Warnings that mappings emit with `log.subgraphWarning`. They are purely
+-- informational and only the most recent ones are kept for each deployment
+create table subgraphs.subgraph_warning(
+    id           bigserial primary key,
+    deployment   int not null
+                 references subgraphs.subgraph_deployment(id) on delete cascade,
+    message      text not null,
+    block_hash   bytea not null,
+    block_number int not null,
+    data_source  text not null,
+    created_at   timestamptz not null default now()
+);
+
+create index subgraph_warning_deployment_id
+    on subgraphs.subgraph_warning(deployment, id);
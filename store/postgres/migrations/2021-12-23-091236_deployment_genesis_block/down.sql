@@ -0,0 +1 @@
+alter table deployment_schemas drop column genesis_block_hash;
@@ -0,0 +1,9 @@
+-- Record the genesis block of the chain a deployment was created against
+-- so that it is not indexed against a different chain that has been
+-- configured under the same name later
+alter table deployment_schemas add column genesis_block_hash text;
+
+update deployment_schemas ds
+   set genesis_block_hash = c.genesis_block_hash
+  from chains c
+ where c.name = ds.network;
@@ -0,0 +1 @@
+drop table subgraphs.scheduled_block_handler;
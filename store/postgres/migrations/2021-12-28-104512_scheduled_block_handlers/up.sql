@@ -0,0 +1,14 @@
+-- Block handlers that mappings scheduled with `scheduler.atBlock`. They
+-- run once, when the deployment processes `block_number`
+create table subgraphs.scheduled_block_handler(
+    vid            bigserial primary key,
+    deployment     text not null,
+    data_source    text not null,
+    address        bytea not null,
+    handler        text not null,
+    block_number   int not null,
+    creation_block int not null
+);
+
+create index scheduled_block_handler_deployment_block
+    on subgraphs.scheduled_block_handler(deployment, block_number);
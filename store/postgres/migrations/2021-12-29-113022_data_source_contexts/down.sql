@@ -0,0 +1 @@
+drop table subgraphs.data_source_context;
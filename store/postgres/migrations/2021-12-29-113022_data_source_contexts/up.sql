@@ -0,0 +1,14 @@
+-- Contexts that mappings stored for their data sources with
+-- `dataSource.setContext`. The latest row for a data source replaces the
+-- context the data source was created with
+create table subgraphs.data_source_context(
+    vid            bigserial primary key,
+    deployment     text not null,
+    data_source    text not null,
+    address        bytea not null,
+    context        text not null,
+    block_number   int not null
+);
+
+create index data_source_context_deployment_data_source
+    on subgraphs.data_source_context(deployment, data_source, address);
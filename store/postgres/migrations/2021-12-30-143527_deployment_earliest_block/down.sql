@@ -0,0 +1 @@
+alter table subgraphs.subgraph_deployment drop column earliest_block_number;
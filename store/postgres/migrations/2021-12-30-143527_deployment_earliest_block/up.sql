@@ -0,0 +1,5 @@
+-- The first block for which a deployment can answer queries. Pruning the
+-- history of a deployment removes the entity versions that are only needed
+-- for queries at earlier blocks
+alter table subgraphs.subgraph_deployment
+  add column earliest_block_number int4 not null default 0;
@@ -0,0 +1,9 @@
+alter table subgraphs.copy_table_state
+  drop constraint copy_table_state_dst_entity_type_first_vid_key;
+
+alter table subgraphs.copy_table_state
+  drop column first_vid;
+
+alter table subgraphs.copy_table_state
+  add constraint copy_table_state_dst_entity_type_key
+      unique(dst, entity_type);
@@ -0,0 +1,11 @@
+-- Large tables can be copied in several vid ranges in parallel; each range
+-- has its own row, identified by the first vid in the range
+alter table subgraphs.copy_table_state
+  add column first_vid int8 not null default 0;
+
+alter table subgraphs.copy_table_state
+  drop constraint copy_table_state_dst_entity_type_key;
+
+alter table subgraphs.copy_table_state
+  add constraint copy_table_state_dst_entity_type_first_vid_key
+      unique(dst, entity_type, first_vid);
@@ -0,0 +1,3 @@
+alter table subgraphs.table_stats
+  drop column entity_type,
+  drop column entity_count;
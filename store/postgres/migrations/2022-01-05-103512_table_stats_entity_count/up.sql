@@ -0,0 +1,6 @@
+-- The number of current entities in each table, maintained by the write
+-- path. A null count means that the count is not known and needs to be
+-- recomputed
+alter table subgraphs.table_stats
+  add column entity_type  text,
+  add column entity_count int8;
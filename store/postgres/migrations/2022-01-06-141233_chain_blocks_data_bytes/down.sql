@@ -0,0 +1,14 @@
+do $$
+declare
+  nsp text;
+begin
+  for nsp in
+    select table_schema
+      from information_schema.tables
+     where table_name = 'blocks'
+       and table_schema ~ '^chain[0-9]+$'
+  loop
+    execute format('alter table %I.blocks drop column if exists data_bytes', nsp);
+  end loop;
+end;
+$$;
@@ -0,0 +1,16 @@
+-- Chains that have their own schema can store the protobuf encoding of
+-- blocks from Firehose instead of JSON
+do $$
+declare
+  nsp text;
+begin
+  for nsp in
+    select table_schema
+      from information_schema.tables
+     where table_name = 'blocks'
+       and table_schema ~ '^chain[0-9]+$'
+  loop
+    execute format('alter table %I.blocks add column if not exists data_bytes bytea', nsp);
+  end loop;
+end;
+$$;
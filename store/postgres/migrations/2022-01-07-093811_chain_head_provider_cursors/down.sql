@@ -0,0 +1 @@
+drop table if exists public.chain_head_cursors;
@@ -0,0 +1,12 @@
+-- Firehose cursors are only meaningful to the provider that issued them;
+-- remember the latest cursor of each provider together with the chain
+-- head it belongs to
+create table if not exists public.chain_head_cursors (
+  network      varchar not null
+               references public.ethereum_networks(name) on delete cascade,
+  provider     text    not null,
+  cursor       text    not null,
+  block_hash   varchar not null,
+  block_number int8    not null,
+  primary key (network, provider)
+);
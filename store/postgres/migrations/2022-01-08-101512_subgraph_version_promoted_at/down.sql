@@ -0,0 +1 @@
+alter table subgraphs.subgraph_version drop column promoted_at;
@@ -0,0 +1,5 @@
+-- When a version became the current version of its subgraph, in seconds
+-- since the epoch. Versions that were current before this column existed
+-- do not have it set
+alter table subgraphs.subgraph_version
+  add column promoted_at numeric;
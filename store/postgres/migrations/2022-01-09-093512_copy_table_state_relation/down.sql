@@ -0,0 +1,12 @@
+delete from subgraphs.copy_table_state
+ where relation != '';
+
+alter table subgraphs.copy_table_state
+  drop constraint copy_table_state_dst_entity_type_relation_first_vid_key;
+
+alter table subgraphs.copy_table_state
+  drop column relation;
+
+alter table subgraphs.copy_table_state
+  add constraint copy_table_state_dst_entity_type_first_vid_key
+      unique(dst, entity_type, first_vid);
@@ -0,0 +1,12 @@
+-- The join tables of `@relation` fields are copied like entity tables;
+-- their rows are identified by the entity type and the name of the field.
+-- The relation is empty for the rows of entity tables
+alter table subgraphs.copy_table_state
+  add column relation text not null default '';
+
+alter table subgraphs.copy_table_state
+  drop constraint copy_table_state_dst_entity_type_first_vid_key;
+
+alter table subgraphs.copy_table_state
+  add constraint copy_table_state_dst_entity_type_relation_first_vid_key
+      unique(dst, entity_type, relation, first_vid);
@@ -0,0 +1 @@
+alter table public.ethereum_networks drop column backfill_cursor_provider;
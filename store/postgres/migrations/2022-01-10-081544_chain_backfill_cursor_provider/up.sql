@@ -0,0 +1,8 @@
+-- Firehose cursors are only meaningful to the provider that issued them;
+-- remember which provider issued the backfill cursor. Existing cursors
+-- can not be attributed to a provider and are discarded, which makes the
+-- backfill resume by block number
+alter table public.ethereum_networks
+  add column backfill_cursor_provider text;
+update public.ethereum_networks
+   set backfill_cursor = null;
@@ -7,7 +7,7 @@ use diesel::sql_types::{Integer, Range};
 use std::io::Write;
 use std::ops::{Bound, RangeBounds, RangeFrom};
 
-use graph::prelude::{BlockNumber, BlockPtr, BLOCK_NUMBER_MAX};
+use graph::prelude::{BlockNumber, BlockPtr, EntityHistory, BLOCK_NUMBER_MAX};
 
 use crate::relational::Table;
 
@@ -123,6 +123,40 @@ impl<'a> QueryFragment<Pg> for BlockRangeContainsClause<'a> {
     }
 }
 
+/// Generate the clause that checks whether the block range of an entity
+/// overlaps the blocks of `history`
+pub struct BlockRangeOverlapsClause<'a> {
+    table_prefix: &'a str,
+    from: BlockNumber,
+    /// The first block after `history.to`
+    to: BlockNumber,
+}
+
+impl<'a> BlockRangeOverlapsClause<'a> {
+    pub fn new(table_prefix: &'a str, history: &EntityHistory) -> Self {
+        Self {
+            table_prefix,
+            from: history.from,
+            to: history.to.saturating_add(1),
+        }
+    }
+}
+
+impl<'a> QueryFragment<Pg> for BlockRangeOverlapsClause<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        out.push_sql(self.table_prefix);
+        out.push_identifier(BLOCK_RANGE_COLUMN)?;
+        out.push_sql(" && int4range(");
+        out.push_bind_param::<Integer, _>(&self.from)?;
+        out.push_sql(", ");
+        out.push_bind_param::<Integer, _>(&self.to)?;
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
 #[test]
 fn block_number_max_is_i32_max() {
     // The code in this file embeds i32::MAX aka BLOCK_NUMBER_MAX in strings
@@ -7,7 +7,7 @@ use std::{
 
 use graph::{
     blockchain::ChainIdentifier,
-    components::store::BlockStore as BlockStoreTrait,
+    components::store::{BlockStore as BlockStoreTrait, BlockTimestampLookup, ChainStore as _},
     prelude::{error, warn, BlockNumber, BlockPtr, Logger},
 };
 use graph::{
@@ -494,3 +494,16 @@ impl BlockStoreTrait for BlockStore {
         self.store(network)
     }
 }
+
+impl BlockTimestampLookup for BlockStore {
+    fn block_timestamp(
+        &self,
+        network: &str,
+        number: BlockNumber,
+    ) -> Result<Option<u64>, anyhow::Error> {
+        match self.store(network) {
+            Some(store) => store.final_block_timestamp(number),
+            None => Ok(None),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use diesel::sql_types::{Bool, Integer};
+use diesel::sql_types::{BigInt, Bool, Integer};
 use diesel::{connection::SimpleConnection, prelude::RunQueryDsl, select};
 use diesel::{insert_into, OptionalExtension};
 use diesel::{pg::PgConnection, sql_query};
@@ -6,17 +6,20 @@ use diesel::{
     sql_types::{Array, Nullable, Text},
     ExpressionMethods, QueryDsl,
 };
-use std::collections::{HashMap, HashSet};
-use std::fmt::Write;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Write};
 use std::iter::FromIterator;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use graph::prelude::anyhow::anyhow;
 use graph::{data::subgraph::schema::POI_TABLE, prelude::StoreError};
 
+use crate::block_range::BLOCK_RANGE_CURRENT;
 use crate::connection_pool::ForeignServer;
 use crate::{
-    primary::{Namespace, Site},
+    primary::{DeploymentId, Namespace, Site},
     relational::SqlName,
 };
 
@@ -44,6 +47,8 @@ table! {
         deployment -> Integer,
         table_name -> Text,
         is_account_like -> Nullable<Bool>,
+        entity_type -> Nullable<Text>,
+        entity_count -> Nullable<BigInt>,
     }
 }
 
@@ -168,6 +173,26 @@ pub fn server_options(
     Ok(HashMap::from_iter(entries))
 }
 
+/// Return the total size in bytes of all tables, including their indexes
+/// and TOAST data, and all sequences in `namespace`. This is the amount of
+/// disk space that dropping the namespace will give back to the database
+pub fn namespace_size(conn: &PgConnection, namespace: &Namespace) -> Result<u64, StoreError> {
+    #[derive(QueryableByName)]
+    struct Size {
+        #[sql_type = "BigInt"]
+        bytes: i64,
+    }
+    let query = "select coalesce(sum(pg_total_relation_size(c.oid)), 0)::bigint as bytes
+                   from pg_class c, pg_namespace n
+                  where c.relnamespace = n.oid
+                    and n.nspname = $1
+                    and c.relkind in ('r', 'm', 'S')";
+    let size = sql_query(query)
+        .bind::<Text, _>(namespace.as_str())
+        .get_result::<Size>(conn)?;
+    Ok(size.bytes.max(0) as u64)
+}
+
 pub fn has_namespace(conn: &PgConnection, namespace: &Namespace) -> Result<bool, StoreError> {
     use pg_namespace as nsp;
 
@@ -247,6 +272,331 @@ pub fn set_account_like(
     Ok(())
 }
 
+/// Add `delta` to the number of current entities in `table_name`. If we
+/// do not know the count for the table yet, it stays unknown until it is
+/// recounted with `recount_entities`
+pub fn update_entity_count(
+    conn: &PgConnection,
+    site: &Site,
+    table_name: &SqlName,
+    entity_type: &str,
+    delta: i64,
+) -> Result<(), StoreError> {
+    let query = "
+        insert into subgraphs.table_stats(deployment, table_name, entity_type)
+        values ($1, $2, $3)
+            on conflict(deployment, table_name)
+            do update set entity_type = excluded.entity_type,
+                          entity_count = table_stats.entity_count + $4";
+    sql_query(query)
+        .bind::<Integer, _>(site.id)
+        .bind::<Text, _>(table_name.as_str())
+        .bind::<Text, _>(entity_type)
+        .bind::<BigInt, _>(delta)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Count the current entities in the table `qualified_name` and store
+/// that as the count for `table_name`. The row in `table_stats` is locked
+/// before counting so that writes to the table that happen concurrently
+/// are either included in the count or add their changes to it after we
+/// are done. Must be run inside a transaction
+pub fn recount_entities(
+    conn: &PgConnection,
+    site: &Site,
+    table_name: &SqlName,
+    entity_type: &str,
+    qualified_name: &SqlName,
+) -> Result<(), StoreError> {
+    let lock = "
+        insert into subgraphs.table_stats(deployment, table_name, entity_type)
+        values ($1, $2, $3)
+            on conflict(deployment, table_name)
+            do update set entity_type = excluded.entity_type";
+    sql_query(lock)
+        .bind::<Integer, _>(site.id)
+        .bind::<Text, _>(table_name.as_str())
+        .bind::<Text, _>(entity_type)
+        .execute(conn)?;
+
+    let count = format!(
+        "update subgraphs.table_stats
+            set entity_count = (select count(*) from {}
+                                 where {})
+          where deployment = $1 and table_name = $2",
+        qualified_name, BLOCK_RANGE_CURRENT
+    );
+    sql_query(count)
+        .bind::<Integer, _>(site.id)
+        .bind::<Text, _>(table_name.as_str())
+        .execute(conn)?;
+    Ok(())
+}
+
+/// The number of current entities for each entity type of `deployments`.
+/// Entity types whose count is not known are left out
+pub(crate) fn entity_counts(
+    conn: &PgConnection,
+    deployments: &[DeploymentId],
+) -> Result<HashMap<DeploymentId, BTreeMap<String, u64>>, StoreError> {
+    use table_stats as ts;
+    let rows = ts::table
+        .filter(ts::deployment.eq_any(deployments))
+        .filter(ts::entity_type.is_not_null())
+        .filter(ts::entity_count.is_not_null())
+        .select((ts::deployment, ts::entity_type, ts::entity_count))
+        .load::<(DeploymentId, Option<String>, Option<i64>)>(conn)?;
+
+    let mut counts: HashMap<_, BTreeMap<_, _>> = HashMap::new();
+    for (deployment, entity_type, count) in rows {
+        if let (Some(entity_type), Some(count)) = (entity_type, count) {
+            counts
+                .entry(deployment)
+                .or_default()
+                .insert(entity_type, count.max(0) as u64);
+        }
+    }
+    Ok(counts)
+}
+
+#[derive(QueryableByName)]
+pub struct EntityCountCheck {
+    #[sql_type = "Text"]
+    pub table_name: String,
+    #[sql_type = "Nullable<BigInt>"]
+    pub entity_count: Option<i64>,
+    /// The number of rows in the table, including old entity versions, as
+    /// estimated by the last `analyze` of the table; `-1` if the table
+    /// has never been analyzed
+    #[sql_type = "BigInt"]
+    pub estimate: i64,
+}
+
+/// Compare the entity counts of all tables in `site` with the number of
+/// rows that Postgres estimates the tables have
+pub fn entity_count_checks(
+    conn: &PgConnection,
+    site: &Site,
+) -> Result<Vec<EntityCountCheck>, StoreError> {
+    let query = "
+        select c.relname::text as table_name, ts.entity_count,
+               c.reltuples::int8 as estimate
+          from pg_class c
+          join pg_namespace n on n.oid = c.relnamespace
+          left join subgraphs.table_stats ts
+            on ts.deployment = $2 and ts.table_name = c.relname
+         where n.nspname = $1
+           and c.relkind = 'r'
+         order by c.relname";
+    Ok(sql_query(query)
+        .bind::<Text, _>(site.namespace.as_str())
+        .bind::<Integer, _>(site.id)
+        .load(conn)?)
+}
+
+/// Storage parameters for an entity table. Parameters that are `None` are
+/// left alone when the parameters are applied to a table
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StorageParams {
+    pub fillfactor: Option<i32>,
+    pub autovacuum_vacuum_scale_factor: Option<f64>,
+    pub autovacuum_analyze_scale_factor: Option<f64>,
+}
+
+impl StorageParams {
+    /// Parameters for tables whose entities change after they have been
+    /// created. Every change clamps the block range of the previous
+    /// version, which leaves a dead tuple behind that vacuum needs to clean
+    /// up, and it helps to leave some room on each page for these updates
+    pub fn mutable() -> Self {
+        StorageParams {
+            fillfactor: Some(90),
+            autovacuum_vacuum_scale_factor: Some(0.05),
+            autovacuum_analyze_scale_factor: Some(0.02),
+        }
+    }
+
+    /// Parameters for tables whose entities are only ever created and
+    /// never changed, so that their pages can be filled completely
+    pub fn append_only() -> Self {
+        StorageParams {
+            fillfactor: Some(100),
+            autovacuum_vacuum_scale_factor: None,
+            autovacuum_analyze_scale_factor: Some(0.05),
+        }
+    }
+
+    /// Choose parameters for a table based on how many rows have been
+    /// inserted into and updated in it. Tables where fewer than 5% of
+    /// writes are updates are treated as append-only
+    pub fn for_activity(inserts: i64, updates: i64) -> Self {
+        if updates * 20 < inserts {
+            Self::append_only()
+        } else {
+            Self::mutable()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+impl fmt::Display for StorageParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        if let Some(fillfactor) = self.fillfactor {
+            write!(f, "{}fillfactor = {}", sep, fillfactor)?;
+            sep = ", ";
+        }
+        if let Some(factor) = self.autovacuum_vacuum_scale_factor {
+            write!(f, "{}autovacuum_vacuum_scale_factor = {}", sep, factor)?;
+            sep = ", ";
+        }
+        if let Some(factor) = self.autovacuum_analyze_scale_factor {
+            write!(f, "{}autovacuum_analyze_scale_factor = {}", sep, factor)?;
+        }
+        Ok(())
+    }
+}
+
+/// Set the storage parameters `params` on the table `nsp.table_name`.
+/// Changing the fillfactor only affects pages that are written afterwards
+pub fn set_storage_params(
+    conn: &PgConnection,
+    nsp: &Namespace,
+    table_name: &SqlName,
+    params: &StorageParams,
+) -> Result<(), StoreError> {
+    if params.is_empty() {
+        return Ok(());
+    }
+    let query = format!(
+        "alter table {}.{} set ({})",
+        nsp,
+        table_name.quoted(),
+        params
+    );
+    Ok(conn.batch_execute(&query)?)
+}
+
+/// Reset the storage parameters that `set_storage_params` sets on
+/// `nsp.table_name` to the database defaults
+pub fn reset_storage_params(
+    conn: &PgConnection,
+    nsp: &Namespace,
+    table_name: &SqlName,
+) -> Result<(), StoreError> {
+    let query = format!(
+        "alter table {}.{} reset (fillfactor, autovacuum_vacuum_scale_factor, \
+                                  autovacuum_analyze_scale_factor)",
+        nsp,
+        table_name.quoted()
+    );
+    Ok(conn.batch_execute(&query)?)
+}
+
+/// How Postgres stores the values of a column that are too large to fit
+/// into a table row; see the Postgres documentation on TOAST
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColumnStorage {
+    /// Never store values out of line or compress them
+    Plain,
+    /// Compress values, and only store them out of line as a last resort
+    Main,
+    /// Store large values out of line without compressing them; this makes
+    /// reading only the beginning of a value, e.g., for prefix indexes, cheap
+    External,
+    /// Compress large values and store them out of line. This is the
+    /// database default for `text` and `bytea`
+    Extended,
+}
+
+impl ColumnStorage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColumnStorage::Plain => "plain",
+            ColumnStorage::Main => "main",
+            ColumnStorage::External => "external",
+            ColumnStorage::Extended => "extended",
+        }
+    }
+}
+
+impl FromStr for ColumnStorage {
+    type Err = StoreError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(ColumnStorage::Plain),
+            "main" => Ok(ColumnStorage::Main),
+            "external" => Ok(ColumnStorage::External),
+            "extended" => Ok(ColumnStorage::Extended),
+            _ => Err(StoreError::Unknown(anyhow!(
+                "unknown column storage `{}`, must be one of plain, main, external, or extended",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ColumnStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Set the storage for `columns` of the table `nsp.table_name`. This only
+/// affects values that are written afterwards
+pub fn set_column_storage(
+    conn: &PgConnection,
+    nsp: &Namespace,
+    table_name: &SqlName,
+    columns: &[&SqlName],
+    storage: ColumnStorage,
+) -> Result<(), StoreError> {
+    if columns.is_empty() {
+        return Ok(());
+    }
+    let clauses: Vec<_> = columns
+        .iter()
+        .map(|column| format!("alter column {} set storage {}", column.quoted(), storage))
+        .collect();
+    let query = format!(
+        "alter table {}.{} {}",
+        nsp,
+        table_name.quoted(),
+        clauses.join(", ")
+    );
+    Ok(conn.batch_execute(&query)?)
+}
+
+/// How many rows have been inserted into and updated in a table since
+/// Postgres' statistics were last reset
+#[derive(QueryableByName)]
+pub struct TableActivity {
+    #[sql_type = "Text"]
+    pub table_name: String,
+    #[sql_type = "BigInt"]
+    pub inserts: i64,
+    #[sql_type = "BigInt"]
+    pub updates: i64,
+}
+
+pub fn table_activity(
+    conn: &PgConnection,
+    nsp: &Namespace,
+) -> Result<Vec<TableActivity>, StoreError> {
+    let query = "
+        select s.relname::text as table_name, s.n_tup_ins as inserts,
+               s.n_tup_upd as updates
+          from pg_stat_user_tables s
+         where s.schemaname = $1
+         order by s.relname";
+    Ok(sql_query(query).bind::<Text, _>(nsp.as_str()).load(conn)?)
+}
+
 pub fn copy_account_like(conn: &PgConnection, src: &Site, dst: &Site) -> Result<usize, StoreError> {
     let src_nsp = if src.shard == dst.shard {
         "subgraphs".to_string()
@@ -257,7 +607,9 @@ pub fn copy_account_like(conn: &PgConnection, src: &Site, dst: &Site) -> Result<
         "insert into subgraphs.table_stats(deployment, table_name, is_account_like)
          select $2 as deployment, ts.table_name, ts.is_account_like
            from {src_nsp}.table_stats ts
-          where ts.deployment = $1",
+          where ts.deployment = $1
+             on conflict(deployment, table_name)
+             do update set is_account_like = excluded.is_account_like",
         src_nsp = src_nsp
     );
     Ok(sql_query(&query)
@@ -414,3 +766,81 @@ pub(crate) fn check_index_is_valid(
         .map(|check| check.is_valid);
     Ok(matches!(result, Some(true)))
 }
+
+/// Return the `(table, index)` names of the hot block range indexes in
+/// the schema `schema_name`, i.e., the indexes whose name starts with
+/// `hot_`
+pub(crate) fn hot_indexes(
+    conn: &PgConnection,
+    schema_name: &str,
+) -> Result<Vec<(String, String)>, StoreError> {
+    #[derive(QueryableByName)]
+    struct HotIndex {
+        #[sql_type = "Text"]
+        tablename: String,
+        #[sql_type = "Text"]
+        indexname: String,
+    }
+
+    let query = "
+        select tablename, indexname
+          from pg_indexes
+         where schemaname = $1
+           and indexname like 'hot\\_%'
+         order by tablename, indexname";
+    Ok(sql_query(query)
+        .bind::<Text, _>(schema_name)
+        .load::<HotIndex>(conn)?
+        .into_iter()
+        .map(|index| (index.tablename, index.indexname))
+        .collect())
+}
+
+/// Return `true` if the schema `schema_name` has an index called
+/// `index_name`, whether it is valid or not
+pub(crate) fn index_exists(
+    conn: &PgConnection,
+    schema_name: &str,
+    index_name: &str,
+) -> Result<bool, StoreError> {
+    #[derive(Queryable, QueryableByName)]
+    struct IndexCount {
+        #[sql_type = "BigInt"]
+        count: i64,
+    }
+
+    let query = "
+        select
+            count(*) as count
+        from
+            pg_class c
+            join pg_index i on i.indexrelid = c.oid
+            join pg_namespace n on c.relnamespace = n.oid
+        where
+            n.nspname = $1
+            and c.relname = $2";
+    let result = sql_query(query)
+        .bind::<Text, _>(schema_name)
+        .bind::<Text, _>(index_name)
+        .get_result::<IndexCount>(conn)?;
+    Ok(result.count > 0)
+}
+
+/// How far the database behind `conn` lags behind the primary it
+/// replicates from. A replica that has replayed everything it received, and
+/// a database that is not a replica, have no lag
+pub(crate) fn replication_lag(conn: &PgConnection) -> Result<Duration, StoreError> {
+    #[derive(QueryableByName)]
+    struct Lag {
+        #[sql_type = "BigInt"]
+        ms: i64,
+    }
+
+    let query = "
+        select coalesce(
+                 case when pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() then 0
+                      else extract(epoch from now() - pg_last_xact_replay_timestamp()) * 1000
+                  end, 0)::int8 as ms";
+    let lag = sql_query(query).get_result::<Lag>(conn)?;
+    Ok(Duration::from_millis(lag.ms.max(0) as u64))
+}
@@ -2,11 +2,12 @@ use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
 use diesel::sql_types::Text;
-use diesel::{insert_into, update};
-use graph::blockchain::{Block, ChainIdentifier};
+use diesel::{delete, insert_into, update};
+use graph::blockchain::{Block, ChainIdentifier, REORG_THRESHOLD};
 use graph::prelude::web3::types::H256;
 use graph::util::timed_cache::TimedCache;
 use graph::{
+    components::store::{BackfillCursor, BlockCopier, BlockData},
     constraint_violation,
     prelude::{
         async_trait, ethabi, CancelableError, ChainStore as ChainStoreTrait, EthereumCallCache,
@@ -28,10 +29,26 @@ use graph::prelude::{
 };
 
 use crate::{
-    block_store::ChainStatus, chain_head_listener::ChainHeadUpdateSender,
-    connection_pool::ConnectionPool,
+    block_store::ChainStatus,
+    chain_head_listener::ChainHeadUpdateSender,
+    connection_pool::{ConnectionPool, CopyClient},
 };
 
+/// Extract the timestamp in seconds from the JSON `data` of a block. Blocks
+/// might store their timestamp as a number, a decimal string, or a hex
+/// string
+fn block_timestamp(data: &json::Value) -> Option<u64> {
+    let block = data.get("block").unwrap_or(data);
+    match block.get("timestamp")? {
+        json::Value::Number(n) => n.as_u64(),
+        json::Value::String(s) => match s.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => s.parse().ok(),
+        },
+        _ => None,
+    }
+}
+
 /// Tables in the 'public' database schema that store chain-specific data
 mod public {
     table! {
@@ -43,6 +60,33 @@ mod public {
             net_version -> Varchar,
             genesis_block_hash -> Varchar,
             head_block_cursor -> Nullable<Varchar>,
+            backfill_target_block_number -> Nullable<Integer>,
+            backfill_block_number -> Nullable<Integer>,
+            backfill_cursor -> Nullable<Varchar>,
+            backfill_cursor_provider -> Nullable<Text>,
+            final_block_hash -> Nullable<Varchar>,
+            final_block_number -> Nullable<BigInt>,
+        }
+    }
+
+    table! {
+        /// The latest head cursor that each Firehose provider gave us
+        chain_head_cursors (network, provider) {
+            network -> Varchar,
+            provider -> Text,
+            cursor -> Text,
+            block_hash -> Varchar,
+            block_number -> BigInt,
+        }
+    }
+
+    table! {
+        eth_abi_cache (network, address, code_hash) {
+            network -> Text,
+            address -> Binary,
+            code_hash -> Binary,
+            abi -> Text,
+            fetched_at -> Timestamptz,
         }
     }
 }
@@ -63,12 +107,13 @@ mod data {
         types::{FromSql, ToSql},
     };
     use diesel::{
-        sql_types::{BigInt, Bytea, Integer, Jsonb},
+        sql_types::{Array, BigInt, Bytea, Integer, Jsonb, Nullable},
         update,
     };
     use diesel_dynamic_schema as dds;
     use graph::blockchain::{Block, BlockHash};
     use graph::{
+        components::store::BlockData,
         constraint_violation,
         prelude::{transaction_receipt::LightTransactionReceipt, StoreError},
     };
@@ -337,7 +382,8 @@ mod data {
                   hash         bytea  not null primary key,
                   number       int8  not null,
                   parent_hash  bytea  not null,
-                  data         jsonb not null
+                  data         jsonb not null,
+                  data_bytes   bytea
                 );
                 create index blocks_number ON {nsp}.blocks using btree(number);
 
@@ -421,6 +467,7 @@ mod data {
 
             let number = block.number() as i64;
             let data = block.data().expect("Failed to serialize block");
+            let data_bytes = block.data_bytes();
             let hash = block.hash();
             let parent_hash = block.parent_hash().unwrap_or_else(|| {
                 BlockHash::try_from(NO_PARENT).expect("NO_PARENT is a valid hash")
@@ -456,16 +503,17 @@ mod data {
                 Storage::Private(Schema { blocks, .. }) => {
                     let query = if overwrite {
                         format!(
-                            "insert into {}(hash, number, parent_hash, data) \
-                             values ($1, $2, $3, $4) \
+                            "insert into {}(hash, number, parent_hash, data, data_bytes) \
+                             values ($1, $2, $3, $4, $5) \
                                  on conflict(hash) \
-                                 do update set number = $2, parent_hash = $3, data = $4",
+                                 do update set number = $2, parent_hash = $3, data = $4, \
+                                               data_bytes = $5",
                             blocks.qname,
                         )
                     } else {
                         format!(
-                            "insert into {}(hash, number, parent_hash, data) \
-                             values ($1, $2, $3, $4) \
+                            "insert into {}(hash, number, parent_hash, data, data_bytes) \
+                             values ($1, $2, $3, $4, $5) \
                                  on conflict(hash) do nothing",
                             blocks.qname
                         )
@@ -475,12 +523,193 @@ mod data {
                         .bind::<BigInt, _>(number)
                         .bind::<Bytea, _>(parent_hash.as_slice())
                         .bind::<Jsonb, _>(data)
+                        .bind::<Nullable<Bytea>, _>(data_bytes)
                         .execute(conn)?;
                 }
             };
             Ok(())
         }
 
+        /// Insert or update all `blocks` with a single statement. If the
+        /// same block appears several times, the last occurrence wins
+        pub(super) fn upsert_blocks(
+            &self,
+            conn: &PgConnection,
+            chain: &str,
+            blocks: &[&dyn Block],
+        ) -> Result<(), StoreError> {
+            const NO_PARENT: &str =
+                "0000000000000000000000000000000000000000000000000000000000000000";
+
+            // Postgres refuses to update the same row twice in one
+            // `insert .. on conflict do update`
+            let mut seen = std::collections::HashSet::new();
+            let mut hashes = Vec::with_capacity(blocks.len());
+            let mut numbers = Vec::with_capacity(blocks.len());
+            let mut parent_hashes = Vec::with_capacity(blocks.len());
+            let mut data = Vec::with_capacity(blocks.len());
+            let mut data_bytes = Vec::with_capacity(blocks.len());
+            for block in blocks.iter().rev() {
+                let hash = block.hash();
+                if !seen.insert(hash.clone()) {
+                    continue;
+                }
+                hashes.push(hash);
+                numbers.push(block.number() as i64);
+                parent_hashes.push(block.parent_hash().unwrap_or_else(|| {
+                    BlockHash::try_from(NO_PARENT).expect("NO_PARENT is a valid hash")
+                }));
+                data.push(block.data().expect("Failed to serialize block"));
+                data_bytes.push(block.data_bytes());
+            }
+            if hashes.is_empty() {
+                return Ok(());
+            }
+
+            match self {
+                Storage::Shared => {
+                    use diesel::upsert::excluded;
+                    use public::ethereum_blocks as b;
+
+                    let values: Vec<_> = hashes
+                        .iter()
+                        .zip(numbers)
+                        .zip(parent_hashes.iter())
+                        .zip(data)
+                        .map(|(((hash, number), parent_hash), data)| {
+                            (
+                                b::hash.eq(hash.hash_hex()),
+                                b::number.eq(number),
+                                b::parent_hash.eq(parent_hash.hash_hex()),
+                                b::network_name.eq(chain),
+                                b::data.eq(data),
+                            )
+                        })
+                        .collect();
+
+                    insert_into(b::table)
+                        .values(values)
+                        .on_conflict(b::hash)
+                        .do_update()
+                        .set((
+                            b::number.eq(excluded(b::number)),
+                            b::parent_hash.eq(excluded(b::parent_hash)),
+                            b::network_name.eq(excluded(b::network_name)),
+                            b::data.eq(excluded(b::data)),
+                        ))
+                        .execute(conn)?;
+                }
+                Storage::Private(Schema { blocks, .. }) => {
+                    let query = format!(
+                        "insert into {}(hash, number, parent_hash, data, data_bytes) \
+                         select * from unnest($1::bytea[], $2::int8[], $3::bytea[], $4::jsonb[], \
+                                              $5::bytea[]) \
+                             on conflict(hash) \
+                             do update set number = excluded.number, \
+                                           parent_hash = excluded.parent_hash, \
+                                           data = excluded.data, \
+                                           data_bytes = excluded.data_bytes",
+                        blocks.qname,
+                    );
+                    let hashes: Vec<_> = hashes.iter().map(|hash| hash.as_slice()).collect();
+                    let parent_hashes: Vec<_> =
+                        parent_hashes.iter().map(|hash| hash.as_slice()).collect();
+                    sql_query(query)
+                        .bind::<Array<Bytea>, _>(hashes)
+                        .bind::<Array<BigInt>, _>(numbers)
+                        .bind::<Array<Bytea>, _>(parent_hashes)
+                        .bind::<Array<Jsonb>, _>(data)
+                        .bind::<Array<Nullable<Bytea>>, _>(data_bytes)
+                        .execute(conn)?;
+                }
+            };
+            Ok(())
+        }
+
+        /// Insert or update all `blocks` by streaming them into a temporary
+        /// table with `COPY` and moving them into the blocks table with a
+        /// single `insert .. on conflict`. That is much faster than
+        /// `upsert_blocks` for the large batches that backfilling produces.
+        /// Only chains with private storage can use this
+        pub(super) fn copy_blocks(
+            &self,
+            client: &mut postgres::Client,
+            blocks: &[&dyn Block],
+        ) -> Result<(), StoreError> {
+            const NO_PARENT: &str =
+                "0000000000000000000000000000000000000000000000000000000000000000";
+
+            let blocks_table = match self {
+                Storage::Shared => {
+                    return Err(constraint_violation!(
+                        "blocks can only be copied into private block storage"
+                    ))
+                }
+                Storage::Private(Schema { blocks, .. }) => blocks,
+            };
+
+            // Postgres refuses to update the same row twice in one
+            // `insert .. on conflict do update`
+            let mut seen = std::collections::HashSet::new();
+            let mut csv = Vec::new();
+            for block in blocks.iter().rev() {
+                let hash = block.hash();
+                if !seen.insert(hash.clone()) {
+                    continue;
+                }
+                let parent_hash = block.parent_hash().unwrap_or_else(|| {
+                    BlockHash::try_from(NO_PARENT).expect("NO_PARENT is a valid hash")
+                });
+                let data = block.data().expect("Failed to serialize block");
+                let data_bytes = block
+                    .data_bytes()
+                    .map(|bytes| format!("\\x{}", hex::encode(bytes)))
+                    .unwrap_or_default();
+                writeln!(
+                    csv,
+                    "\\x{},{},\\x{},\"{}\",{}",
+                    hex::encode(hash.as_slice()),
+                    block.number(),
+                    hex::encode(parent_hash.as_slice()),
+                    data.to_string().replace('"', "\"\""),
+                    data_bytes
+                )
+                .expect("writing to a Vec<u8> does not fail");
+            }
+            if seen.is_empty() {
+                return Ok(());
+            }
+
+            let mut copy = || -> Result<(), Error> {
+                let mut tx = client.transaction()?;
+                tx.batch_execute(&format!(
+                    "create temporary table blocks_copy \
+                       (like {} including defaults) on commit drop",
+                    blocks_table.qname
+                ))?;
+                let mut writer = tx.copy_in(
+                    "copy blocks_copy(hash, number, parent_hash, data, data_bytes) \
+                       from stdin (format csv)",
+                )?;
+                writer.write_all(&csv)?;
+                writer.finish()?;
+                tx.batch_execute(&format!(
+                    "insert into {}(hash, number, parent_hash, data, data_bytes) \
+                     select hash, number, parent_hash, data, data_bytes \
+                       from blocks_copy \
+                         on conflict(hash) \
+                         do update set number = excluded.number, \
+                                       parent_hash = excluded.parent_hash, \
+                                       data = excluded.data, \
+                                       data_bytes = excluded.data_bytes",
+                    blocks_table.qname
+                ))?;
+                tx.commit()?;
+                Ok(())
+            };
+            copy().map_err(StoreError::Unknown)
+        }
+
         pub(super) fn blocks(
             &self,
             conn: &PgConnection,
@@ -521,6 +750,48 @@ mod data {
             .map_err(Into::into)
         }
 
+        /// Like `blocks`, but return the binary data for blocks that were
+        /// stored with it. Blocks in shared storage are always JSON
+        pub(super) fn block_data(
+            &self,
+            conn: &PgConnection,
+            chain: &str,
+            hashes: &[H256],
+        ) -> Result<Vec<BlockData>, Error> {
+            #[derive(QueryableByName)]
+            struct Row {
+                #[sql_type = "Nullable<Bytea>"]
+                data_bytes: Option<Vec<u8>>,
+                #[sql_type = "Jsonb"]
+                data: json::Value,
+            }
+
+            match self {
+                Storage::Shared => Ok(self
+                    .blocks(conn, chain, hashes)?
+                    .into_iter()
+                    .map(BlockData::Json)
+                    .collect()),
+                Storage::Private(Schema { blocks, .. }) => {
+                    let query = format!(
+                        "select data_bytes, coalesce(data -> 'block', data) as data \
+                           from {} where hash = any($1)",
+                        blocks.qname
+                    );
+                    let hashes: Vec<_> = hashes.iter().map(|h| h.as_bytes()).collect();
+                    Ok(sql_query(query)
+                        .bind::<Array<Bytea>, _>(hashes)
+                        .load::<Row>(conn)?
+                        .into_iter()
+                        .map(|row| match row.data_bytes {
+                            Some(bytes) => BlockData::Bytes(bytes),
+                            None => BlockData::Json(row.data),
+                        })
+                        .collect())
+                }
+            }
+        }
+
         pub(super) fn block_hashes_by_block_number(
             &self,
             conn: &PgConnection,
@@ -553,6 +824,38 @@ mod data {
             }
         }
 
+        /// The block with the highest number in the store. If there are
+        /// several blocks with that number, an arbitrary one of them
+        pub(super) fn latest_block(
+            &self,
+            conn: &PgConnection,
+            chain: &str,
+        ) -> Result<Option<BlockPtr>, Error> {
+            let block = match self {
+                Storage::Shared => {
+                    use public::ethereum_blocks as b;
+
+                    b::table
+                        .select((b::hash, b::number))
+                        .filter(b::network_name.eq(&chain))
+                        .order_by(b::number.desc())
+                        .first::<(String, i64)>(conn)
+                        .optional()?
+                        .map(|(hash, number)| hash.parse().map(|hash: H256| (hash, number)))
+                        .transpose()?
+                }
+                Storage::Private(Schema { blocks, .. }) => blocks
+                    .table()
+                    .select((blocks.hash(), blocks.number()))
+                    .order_by(blocks.number().desc())
+                    .first::<(Vec<u8>, i64)>(conn)
+                    .optional()?
+                    .map(|(hash, number)| h256_from_bytes(&hash).map(|hash| (hash, number)))
+                    .transpose()?,
+            };
+            Ok(block.map(BlockPtr::from))
+        }
+
         pub(super) fn confirm_block_hash(
             &self,
             conn: &PgConnection,
@@ -892,6 +1195,52 @@ mod data {
             }
         }
 
+        /// Like `delete_blocks_before`, but keep the blocks whose number is
+        /// in one of the inclusive ranges in `keep` even if they are older
+        /// than `block`
+        pub(super) fn delete_blocks_before_except(
+            &self,
+            conn: &PgConnection,
+            chain: &str,
+            block: i64,
+            keep: &[(BlockNumber, BlockNumber)],
+        ) -> Result<usize, Error> {
+            const KEEP: &str =
+                "not exists (select 1 from unnest($2::int8[], $3::int8[]) as k(lo, hi) \
+                  where number between k.lo and k.hi)";
+
+            let (lo, hi): (Vec<i64>, Vec<i64>) =
+                keep.iter().map(|(lo, hi)| (*lo as i64, *hi as i64)).unzip();
+            match self {
+                Storage::Shared => {
+                    let query = format!(
+                        "delete from {} \
+                          where number < $1 and number > 0 and {} and network_name = $4",
+                        ETHEREUM_BLOCKS_TABLE_NAME, KEEP
+                    );
+                    sql_query(query)
+                        .bind::<BigInt, _>(block)
+                        .bind::<Array<BigInt>, _>(lo)
+                        .bind::<Array<BigInt>, _>(hi)
+                        .bind::<Text, _>(chain)
+                        .execute(conn)
+                        .map_err(Error::from)
+                }
+                Storage::Private(Schema { blocks, .. }) => {
+                    let query = format!(
+                        "delete from {} where number < $1 and number > 0 and {}",
+                        blocks.qname, KEEP
+                    );
+                    sql_query(query)
+                        .bind::<BigInt, _>(block)
+                        .bind::<Array<BigInt>, _>(lo)
+                        .bind::<Array<BigInt>, _>(hi)
+                        .execute(conn)
+                        .map_err(Error::from)
+                }
+            }
+        }
+
         pub(super) fn get_call_and_access(
             &self,
             conn: &PgConnection,
@@ -1132,8 +1481,10 @@ select
     ethereum_hex_to_bytea(receipt ->> 'transactionIndex') as transaction_index,
     ethereum_hex_to_bytea(receipt ->> 'blockHash') as block_hash,
     ethereum_hex_to_bytea(receipt ->> 'blockNumber') as block_number,
+    ethereum_hex_to_bytea(receipt ->> 'cumulativeGasUsed') as cumulative_gas_used,
     ethereum_hex_to_bytea(receipt ->> 'gasUsed') as gas_used,
-    ethereum_hex_to_bytea(receipt ->> 'status') as status
+    ethereum_hex_to_bytea(receipt ->> 'status') as status,
+    (receipt -> 'logs')::text as logs
 from (
     select
         jsonb_array_elements(data -> 'transaction_receipts') as receipt
@@ -1268,12 +1619,21 @@ impl ChainStore {
     }
 
     pub fn chain_head_block(&self, chain: &str) -> Result<Option<BlockNumber>, StoreError> {
+        Self::chain_head_block_with_conn(&self.get_conn()?, chain)
+    }
+
+    /// The number of the head block of `chain`, read through `conn`,
+    /// which must be a connection to the shard that stores the chain
+    pub(crate) fn chain_head_block_with_conn(
+        conn: &PgConnection,
+        chain: &str,
+    ) -> Result<Option<BlockNumber>, StoreError> {
         use public::ethereum_networks as n;
 
         let number: Option<i64> = n::table
             .filter(n::name.eq(chain))
             .select(n::head_block_number)
-            .first::<Option<i64>>(&self.get_conn()?)
+            .first::<Option<i64>>(conn)
             .optional()?
             .flatten();
 
@@ -1300,6 +1660,35 @@ impl ChainStore {
             .set_chain(&conn, &self.chain, genesis_hash, chain);
     }
 
+    /// Remove all blocks from the cache that are more than `keep` blocks
+    /// behind the chain head, except for the genesis block and the blocks
+    /// whose number is in one of the inclusive ranges in `referenced`.
+    /// Return the number of the oldest block that was kept and how many
+    /// blocks were removed, or `None` if the chain does not have enough
+    /// blocks yet
+    pub fn evict_blocks(
+        &self,
+        keep: BlockNumber,
+        referenced: &[(BlockNumber, BlockNumber)],
+    ) -> Result<Option<(BlockNumber, usize)>, StoreError> {
+        let head = match self.chain_head_ptr()? {
+            Some(head) => head,
+            None => return Ok(None),
+        };
+        let block = head.number - keep;
+        if block <= 0 {
+            return Ok(None);
+        }
+        let conn = self.get_conn()?;
+        let rows = self.storage.delete_blocks_before_except(
+            &conn,
+            &self.chain,
+            block as i64,
+            referenced,
+        )?;
+        Ok(Some((block, rows)))
+    }
+
     pub fn truncate_block_cache(&self) -> Result<(), StoreError> {
         let conn = self.get_conn()?;
         self.storage.truncate_block_cache(&conn)?;
@@ -1307,6 +1696,37 @@ impl ChainStore {
     }
 }
 
+/// Remember `cursor` as the latest cursor that `provider` gave us for the
+/// chain head `hash` of `network`
+fn set_provider_cursor(
+    conn: &PgConnection,
+    network: &str,
+    provider: &str,
+    cursor: &str,
+    hash: &str,
+    number: i64,
+) -> Result<(), StoreError> {
+    use public::chain_head_cursors as c;
+
+    insert_into(c::table)
+        .values((
+            c::network.eq(network),
+            c::provider.eq(provider),
+            c::cursor.eq(cursor),
+            c::block_hash.eq(hash),
+            c::block_number.eq(number),
+        ))
+        .on_conflict((c::network, c::provider))
+        .do_update()
+        .set((
+            c::cursor.eq(cursor),
+            c::block_hash.eq(hash),
+            c::block_number.eq(number),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
 #[async_trait]
 impl ChainStoreTrait for ChainStore {
     fn genesis_block_ptr(&self) -> Result<BlockPtr, Error> {
@@ -1328,6 +1748,19 @@ impl ChainStoreTrait for ChainStore {
         .map_err(Error::from)
     }
 
+    async fn upsert_blocks(&self, blocks: Vec<Arc<dyn Block>>) -> Result<(), Error> {
+        upsert_blocks(&self.pool, &self.chain, &self.storage, blocks).await
+    }
+
+    fn block_copier(&self) -> Box<dyn BlockCopier> {
+        Box::new(PostgresBlockCopier {
+            pool: self.pool.clone(),
+            chain: self.chain.clone(),
+            storage: self.storage.clone(),
+            client: None,
+        })
+    }
+
     fn upsert_light_blocks(&self, blocks: &[&dyn Block]) -> Result<(), Error> {
         let conn = self.pool.get()?;
         for block in blocks {
@@ -1443,10 +1876,218 @@ impl ChainStoreTrait for ChainStore {
             .map_err(Error::from)
     }
 
+    fn provider_head_cursor(&self, provider: &str) -> Result<Option<(String, BlockPtr)>, Error> {
+        use public::chain_head_cursors as c;
+
+        let cursor = c::table
+            .filter(c::network.eq(&self.chain))
+            .filter(c::provider.eq(provider))
+            .select((c::cursor, c::block_hash, c::block_number))
+            .first::<(String, String, i64)>(&*self.get_conn()?)
+            .optional()?;
+
+        match cursor {
+            Some((cursor, hash, number)) => {
+                Ok(Some((cursor, (hash.parse::<H256>()?, number).into())))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn clear_chain_head_cursor(&self) -> Result<(), Error> {
+        use public::chain_head_cursors as c;
+        use public::ethereum_networks as n;
+
+        let conn = self.get_conn()?;
+        conn.transaction(|| -> Result<(), Error> {
+            update(n::table.filter(n::name.eq(&self.chain)))
+                .set(n::head_block_cursor.eq(None::<String>))
+                .execute(&conn)?;
+            delete(c::table.filter(c::network.eq(&self.chain))).execute(&conn)?;
+            Ok(())
+        })
+    }
+
+    fn chain_final_block_ptr(&self) -> Result<Option<BlockPtr>, Error> {
+        use public::ethereum_networks::dsl::*;
+
+        let ptr = ethereum_networks
+            .select((final_block_hash, final_block_number))
+            .filter(name.eq(&self.chain))
+            .first::<(Option<String>, Option<i64>)>(&*self.get_conn()?)
+            .optional()?;
+
+        match ptr {
+            Some((Some(hash), Some(number))) => Ok(Some((hash.parse::<H256>()?, number).into())),
+            Some((None, None)) | None => Ok(None),
+            Some(_) => Err(constraint_violation!(
+                "final block hash and number for chain {} must both be set or both be null",
+                self.chain
+            )
+            .into()),
+        }
+    }
+
+    fn set_chain_final_block(&self, ptr: &BlockPtr) -> Result<(), Error> {
+        use public::ethereum_networks as n;
+
+        update(n::table.filter(n::name.eq(&self.chain)))
+            .set((
+                n::final_block_hash.eq(ptr.hash_hex()),
+                n::final_block_number.eq(ptr.number as i64),
+            ))
+            .execute(&*self.get_conn()?)?;
+        Ok(())
+    }
+
+    fn chain_backfill_target_block_num(&self) -> Result<Option<BlockNumber>, Error> {
+        use public::ethereum_networks::dsl::*;
+
+        ethereum_networks
+            .select(backfill_target_block_number)
+            .filter(name.eq(&self.chain))
+            .first::<Option<BlockNumber>>(&*self.get_conn()?)
+            .optional()
+            .map(|target| target.flatten())
+            .map_err(Error::from)
+    }
+
+    fn set_chain_backfill_target_block_num(
+        &self,
+        target: Option<BlockNumber>,
+    ) -> Result<(), Error> {
+        use public::ethereum_networks as n;
+
+        update(n::table.filter(n::name.eq(&self.chain)))
+            .set(n::backfill_target_block_number.eq(target))
+            .execute(&*self.get_conn()?)?;
+        Ok(())
+    }
+
+    fn chain_backfill_progress(
+        &self,
+    ) -> Result<(Option<BlockNumber>, Option<BackfillCursor>), Error> {
+        use public::ethereum_networks::dsl::*;
+
+        let progress = ethereum_networks
+            .select((
+                backfill_block_number,
+                backfill_cursor,
+                backfill_cursor_provider,
+            ))
+            .filter(name.eq(&self.chain))
+            .first::<(Option<BlockNumber>, Option<String>, Option<String>)>(&*self.get_conn()?)
+            .optional()?;
+
+        match progress {
+            Some((number, Some(cursor), Some(provider))) => {
+                Ok((number, Some(BackfillCursor { provider, cursor })))
+            }
+            Some((number, _, _)) => Ok((number, None)),
+            None => Ok((None, None)),
+        }
+    }
+
+    fn set_chain_backfill_progress(
+        &self,
+        number: Option<BlockNumber>,
+        cursor: Option<BackfillCursor>,
+    ) -> Result<(), Error> {
+        use public::ethereum_networks as n;
+
+        let (provider, cursor) = match cursor {
+            Some(BackfillCursor { provider, cursor }) => (Some(provider), Some(cursor)),
+            None => (None, None),
+        };
+        update(n::table.filter(n::name.eq(&self.chain)))
+            .set((
+                n::backfill_block_number.eq(number),
+                n::backfill_cursor.eq(cursor),
+                n::backfill_cursor_provider.eq(provider),
+            ))
+            .execute(&*self.get_conn()?)?;
+        Ok(())
+    }
+
+    fn cached_abi(&self, address: &[u8], code_hash: &[u8]) -> Result<Option<String>, Error> {
+        use public::eth_abi_cache as c;
+
+        c::table
+            .filter(c::network.eq(&self.chain))
+            .filter(c::address.eq(address))
+            .filter(c::code_hash.eq(code_hash))
+            .select(c::abi)
+            .first::<String>(&*self.get_conn()?)
+            .optional()
+            .map_err(Error::from)
+    }
+
+    fn set_cached_abi(&self, address: &[u8], code_hash: &[u8], abi: &str) -> Result<(), Error> {
+        use public::eth_abi_cache as c;
+
+        insert_into(c::table)
+            .values((
+                c::network.eq(&self.chain),
+                c::address.eq(address),
+                c::code_hash.eq(code_hash),
+                c::abi.eq(abi),
+            ))
+            .on_conflict((c::network, c::address, c::code_hash))
+            .do_update()
+            .set(c::abi.eq(abi))
+            .execute(&*self.get_conn()?)?;
+        Ok(())
+    }
+
+    async fn set_chain_head_batch(
+        self: Arc<Self>,
+        blocks: Vec<Arc<dyn Block>>,
+        cursor: String,
+        provider: &str,
+    ) -> Result<(), Error> {
+        use public::ethereum_networks as n;
+
+        let ptr = match blocks.last() {
+            Some(block) => block.ptr(),
+            None => return Ok(()),
+        };
+
+        let pool = self.pool.clone();
+        let network = self.chain.clone();
+        let storage = self.storage.clone();
+
+        let hash = ptr.hash_hex();
+        let number = ptr.number as i64;
+        let provider = provider.to_string();
+
+        pool.with_conn(move |conn, _| {
+            conn.transaction(|| -> Result<(), StoreError> {
+                let blocks: Vec<_> = blocks.iter().map(|block| block.as_ref()).collect();
+                storage.upsert_blocks(&conn, &network, &blocks)?;
+
+                update(n::table.filter(n::name.eq(&network)))
+                    .set((
+                        n::head_block_hash.eq(&hash),
+                        n::head_block_number.eq(number),
+                        n::head_block_cursor.eq(&cursor),
+                    ))
+                    .execute(conn)?;
+                set_provider_cursor(conn, &network, &provider, &cursor, &hash, number)?;
+
+                Ok(())
+            })
+            .map_err(CancelableError::from)
+        })
+        .await?;
+
+        Ok(())
+    }
+
     async fn set_chain_head(
         self: Arc<Self>,
         block: Arc<dyn Block>,
         cursor: String,
+        provider: &str,
     ) -> Result<(), Error> {
         use public::ethereum_networks as n;
 
@@ -1457,6 +2098,7 @@ impl ChainStoreTrait for ChainStore {
         let ptr = block.ptr();
         let hash = ptr.hash_hex();
         let number = ptr.number as i64;
+        let provider = provider.to_string();
 
         pool.with_conn(move |conn, _| {
             conn.transaction(|| -> Result<(), StoreError> {
@@ -1468,9 +2110,10 @@ impl ChainStoreTrait for ChainStore {
                     .set((
                         n::head_block_hash.eq(&hash),
                         n::head_block_number.eq(number),
-                        n::head_block_cursor.eq(cursor),
+                        n::head_block_cursor.eq(&cursor),
                     ))
                     .execute(conn)?;
+                set_provider_cursor(conn, &network, &provider, &cursor, &hash, number)?;
 
                 Ok(())
             })
@@ -1486,6 +2129,11 @@ impl ChainStoreTrait for ChainStore {
         self.storage.blocks(&conn, &self.chain, hashes)
     }
 
+    fn block_data(&self, hashes: &[H256]) -> Result<Vec<BlockData>, Error> {
+        let conn = self.get_conn()?;
+        self.storage.block_data(&conn, &self.chain, hashes)
+    }
+
     fn ancestor_block(
         &self,
         block_ptr: BlockPtr,
@@ -1576,6 +2224,45 @@ impl ChainStoreTrait for ChainStore {
             .block_hashes_by_block_number(&conn, &self.chain, number)
     }
 
+    fn final_block_timestamp(&self, number: BlockNumber) -> Result<Option<u64>, Error> {
+        let final_ptr = match self.chain_final_block_ptr()? {
+            Some(ptr) => ptr,
+            None => match self.chain_head_ptr()? {
+                Some(head) if head.number - *REORG_THRESHOLD >= number => head,
+                _ => return Ok(None),
+            },
+        };
+        if number < 0 || number > final_ptr.number {
+            return Ok(None);
+        }
+
+        // Usually, there's only one block with that number; if we also
+        // have ommers, follow the final block's ancestors to find the block
+        // on the main chain
+        let hashes = self.block_hashes_by_block_number(number)?;
+        let block = match hashes.len() {
+            0 => None,
+            1 => self.blocks(&hashes)?.into_iter().next(),
+            _ => self.ancestor_block(final_ptr.clone(), final_ptr.number - number)?,
+        };
+
+        Ok(block.as_ref().and_then(block_timestamp))
+    }
+
+    fn final_block_number(&self) -> Result<Option<BlockNumber>, Error> {
+        match self.chain_final_block_ptr()? {
+            Some(ptr) => Ok(Some(ptr.number)),
+            None => Ok(self
+                .chain_head_ptr()?
+                .map(|head| head.number - *REORG_THRESHOLD)),
+        }
+    }
+
+    fn latest_stored_block_ptr(&self) -> Result<Option<BlockPtr>, Error> {
+        let conn = self.get_conn()?;
+        self.storage.latest_block(&conn, &self.chain)
+    }
+
     fn confirm_block_hash(&self, number: BlockNumber, hash: &H256) -> Result<usize, Error> {
         let conn = self.get_conn()?;
         self.storage
@@ -1606,6 +2293,61 @@ impl ChainStoreTrait for ChainStore {
     }
 }
 
+async fn upsert_blocks(
+    pool: &ConnectionPool,
+    network: &str,
+    storage: &data::Storage,
+    blocks: Vec<Arc<dyn Block>>,
+) -> Result<(), Error> {
+    let network = network.to_string();
+    let storage = storage.clone();
+    pool.with_conn(move |conn, _| {
+        let blocks: Vec<_> = blocks.iter().map(|block| block.as_ref()).collect();
+        storage
+            .upsert_blocks(&conn, &network, &blocks)
+            .map_err(CancelableError::from)
+    })
+    .await
+    .map_err(Error::from)
+}
+
+/// Copies blocks through one `CopyClient` that is opened for the first
+/// batch and reused for all later ones. If copying a batch fails, the
+/// client is dropped and the next batch opens a new one. Chains with shared
+/// storage can not use `COPY` and fall back to upserting blocks
+struct PostgresBlockCopier {
+    pool: ConnectionPool,
+    chain: String,
+    storage: data::Storage,
+    client: Option<CopyClient>,
+}
+
+#[async_trait]
+impl BlockCopier for PostgresBlockCopier {
+    async fn copy_blocks(&mut self, blocks: Vec<Arc<dyn Block>>) -> Result<(), Error> {
+        if let Storage::Shared = self.storage {
+            return upsert_blocks(&self.pool, &self.chain, &self.storage, blocks).await;
+        }
+
+        let mut client = match self.client.take() {
+            Some(client) => client,
+            None => self.pool.copy_client().await?,
+        };
+        let storage = self.storage.clone();
+        let (client, result) = graph::spawn_blocking_allow_panic(move || {
+            let blocks: Vec<_> = blocks.iter().map(|block| block.as_ref()).collect();
+            let result = storage.copy_blocks(&mut *client, &blocks);
+            (client, result)
+        })
+        .await
+        .map_err(Error::from)?;
+        if result.is_ok() {
+            self.client = Some(client);
+        }
+        result.map_err(Error::from)
+    }
+}
+
 impl EthereumCallCache for ChainStore {
     fn get_call(
         &self,
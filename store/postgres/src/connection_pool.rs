@@ -23,6 +23,7 @@ use graph::{
 };
 
 use std::fmt::{self, Write};
+use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -32,6 +33,7 @@ use std::{collections::HashMap, sync::RwLock};
 use postgres::config::{Config, Host};
 
 use crate::primary::{self, NAMESPACE_PUBLIC};
+use crate::tls::MakeTlsConnector;
 use crate::{advisory_lock, catalog};
 use crate::{Shard, PRIMARY_SHARD};
 
@@ -237,10 +239,13 @@ impl ForeignServer {
         for table_name in [
             "subgraph_error",
             "dynamic_ethereum_contract_data_source",
+            "scheduled_block_handler",
+            "data_source_context",
             "table_stats",
             "subgraph_deployment_assignment",
             "subgraph",
             "subgraph_version",
+            "deployment_labels",
         ] {
             let create_stmt =
                 catalog::create_foreign_table(conn, "subgraphs", table_name, &nsp, &self.name)?;
@@ -491,6 +496,15 @@ impl ConnectionPool {
         ForeignServer::new(pool.shard.clone(), &pool.postgres_url).map_err(|e| e.into())
     }
 
+    /// Open a connection for `COPY .. FROM STDIN`, which Diesel does not
+    /// support. See `CopyClient` for how it relates to the pool; callers
+    /// should hold on to the client for as long as they have data to copy
+    /// rather than open a new one for each batch
+    pub(crate) async fn copy_client(&self) -> Result<CopyClient, StoreError> {
+        let pool = self.get_ready()?;
+        pool.copy_client().await
+    }
+
     /// Check that we can connect to the database
     pub fn check(&self) -> bool {
         true
@@ -698,6 +712,31 @@ impl HandleEvent for EventHandler {
     }
 }
 
+/// A connection made with the `postgres` crate instead of Diesel, for
+/// operations like `COPY .. FROM STDIN` that Diesel can not do. The
+/// connection uses the pool's connection string, including its `sslmode`,
+/// and the pool's connection timeout. It holds one of the permits that
+/// limit how many connections of the pool are in use until it is dropped,
+/// so that it counts against the pool size
+pub(crate) struct CopyClient {
+    client: postgres::Client,
+    _permit: tokio::sync::OwnedSemaphorePermit,
+}
+
+impl Deref for CopyClient {
+    type Target = postgres::Client;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl DerefMut for CopyClient {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.client
+    }
+}
+
 #[derive(Clone)]
 pub struct PoolInner {
     logger: Logger,
@@ -1004,6 +1043,32 @@ impl PoolInner {
         Ok(())
     }
 
+    async fn copy_client(&self) -> Result<CopyClient, StoreError> {
+        let permit = self.limiter.cheap_clone().acquire_owned().await.unwrap();
+
+        let mut config: Config = self
+            .postgres_url
+            .parse()
+            .map_err(|e: postgres::Error| StoreError::Unknown(e.into()))?;
+        config.connect_timeout(*CONNECTION_TIMEOUT);
+        let tls = MakeTlsConnector::new().map_err(|e| StoreError::Unknown(e.into()))?;
+
+        // `postgres::Client` runs its own runtime and must therefore be
+        // created outside of ours
+        let client = graph::spawn_blocking_allow_panic(move || config.connect(tls))
+            .await
+            .map_err(|e| StoreError::Unknown(anyhow!("connecting for COPY panicked: {}", e)))?
+            .map_err(|e| {
+                error!(self.logger, "Failed to open connection for COPY";
+                       "error" => e.to_string());
+                StoreError::Unknown(e.into())
+            })?;
+        Ok(CopyClient {
+            client,
+            _permit: permit,
+        })
+    }
+
     pub(crate) async fn query_permit(&self) -> tokio::sync::OwnedSemaphorePermit {
         let start = Instant::now();
         let permit = self.query_semaphore.cheap_clone().acquire_owned().await;
@@ -11,9 +11,21 @@
 //! `subgraphs.copy_state` and `subgraphs.copy_table_state` so that a copy
 //! operation can resume after an interruption, for example, because
 //! `graph-node` was restarted while the copy was running.
+//!
+//! With `GRAPH_STORE_COPY_WORKERS` set to more than 1, several tables are
+//! copied at the same time, each on its own connection, and tables with
+//! many entity versions are split into ranges of `vid` that are copied
+//! independently. Only the data is copied in parallel; the copy is marked
+//! as finished once all workers are done, and the metadata of the
+//! destination is switched over in a single transaction afterwards.
 use std::{
+    collections::VecDeque,
     convert::TryFrom,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
     time::{Duration, Instant},
 };
 
@@ -29,7 +41,8 @@ use diesel::{
 use graph::{
     components::store::EntityType,
     constraint_violation,
-    prelude::{info, o, warn, BlockNumber, BlockPtr, Logger, StoreError},
+    env::env_var,
+    prelude::{info, lazy_static, o, warn, BlockNumber, BlockPtr, Logger, StoreError},
 };
 
 use crate::{
@@ -42,6 +55,16 @@ use crate::{relational::Table, relational_queries as rq};
 const INITIAL_BATCH_SIZE: i64 = 10_000;
 const TARGET_DURATION: Duration = Duration::from_secs(5 * 60);
 const LOG_INTERVAL: Duration = Duration::from_secs(3 * 60);
+/// Tables with at least this many entity versions are split into ranges
+/// when copying with more than one worker
+const MIN_VIDS_FOR_SPLIT: i64 = 10_000_000;
+
+lazy_static! {
+    /// The number of connections that are used to copy the data of one
+    /// deployment. Additional connections are taken from the fdw pool, and
+    /// fewer workers are used if the pool does not have enough connections
+    static ref COPY_WORKERS: usize = env_var("GRAPH_STORE_COPY_WORKERS", 1usize).max(1);
+}
 
 table! {
     subgraphs.copy_state(dst) {
@@ -71,6 +94,10 @@ table! {
         // Measures just the time we spent working, not any wait time for
         // connections or the like
         duration_ms -> BigInt,
+        first_vid -> BigInt,
+        // The `@relation` field whose join table is copied, or empty when
+        // the row is for the table of `entity_type` itself
+        relation -> Text,
     }
 }
 
@@ -83,7 +110,7 @@ table! {
     }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Status {
     Finished,
     Cancelled,
@@ -152,7 +179,7 @@ impl CopyState {
         dst: Arc<Layout>,
         target_block: BlockPtr,
     ) -> Result<CopyState, StoreError> {
-        let tables = TableState::load(conn, src.as_ref(), dst.as_ref())?;
+        let tables = TableState::load(conn, src.as_ref(), dst.as_ref(), &target_block)?;
         Ok(CopyState {
             src,
             dst,
@@ -179,31 +206,52 @@ impl CopyState {
             ))
             .execute(conn)?;
 
-        let mut tables: Vec<_> = dst
+        // Copy the tables of all entity types that `src` and `dst` have in
+        // common, and the join tables of the `@relation` fields that they
+        // have in common
+        let mut tables: Vec<TableState> = dst
             .tables
             .values()
             .filter_map(|dst_table| {
                 src.table_for_entity(&dst_table.object)
                     .ok()
-                    .map(|src_table| {
-                        TableState::init(
-                            conn,
-                            dst.site.clone(),
-                            src_table.clone(),
-                            dst_table.clone(),
-                            &target_block,
-                        )
-                    })
+                    .map(|src_table| (src_table, dst_table))
+            })
+            .flat_map(|(src_table, dst_table)| {
+                let relations = dst_table
+                    .relations
+                    .iter()
+                    .filter(move |drel| src_table.relation(&drel.field).is_ok())
+                    .map(|drel| Some(drel.field.clone()));
+                std::iter::once(None)
+                    .chain(relations)
+                    .map(move |relation| (src_table, dst_table, relation))
+            })
+            .map(|(src_table, dst_table, relation)| {
+                TableState::init(
+                    conn,
+                    dst.site.clone(),
+                    src_table.clone(),
+                    dst_table.clone(),
+                    relation,
+                    &target_block,
+                )
             })
             .collect::<Result<_, _>>()?;
-        tables.sort_by_key(|table| table.dst.object.to_string());
+        tables.sort_by_key(|table| table.name());
+        let tables: Vec<_> = tables
+            .into_iter()
+            .flat_map(|table| table.split(*COPY_WORKERS))
+            .collect();
 
         let values = tables
             .iter()
             .map(|table| {
                 (
                     cts::entity_type.eq(table.dst.object.as_str()),
+                    cts::relation.eq(table.relation_key()),
                     cts::dst.eq(dst.site.id),
+                    cts::first_vid.eq(table.first_vid),
                     cts::next_vid.eq(table.next_vid),
                     cts::target_vid.eq(table.target_vid),
                     cts::batch_size.eq(table.batch_size),
@@ -266,6 +314,14 @@ struct TableState {
     dst_site: Arc<Site>,
     src: Arc<Table>,
     dst: Arc<Table>,
+    /// When this is set, this state is for the join table of the
+    /// `@relation` field with this name in `src` and `dst`, rather than
+    /// for the tables themselves
+    relation: Option<String>,
+    target_block: BlockNumber,
+    /// The first `vid` in the range of entity versions that this state
+    /// covers; it is 0 unless the table was split into several ranges
+    first_vid: i64,
     /// The `vid` of the next entity version that we will copy
     next_vid: i64,
     target_vid: i64,
@@ -279,6 +335,7 @@ impl TableState {
         dst_site: Arc<Site>,
         src: Arc<Table>,
         dst: Arc<Table>,
+        relation: Option<String>,
         target_block: &BlockPtr,
     ) -> Result<Self, StoreError> {
         #[derive(QueryableByName)]
@@ -287,9 +344,13 @@ impl TableState {
             max_vid: i64,
         }
 
+        let src_name = match &relation {
+            Some(field) => &src.relation(field)?.qualified_name,
+            None => &src.qualified_name,
+        };
         let target_vid = sql_query(&format!(
             "select coalesce(max(vid), -1) as max_vid from {} where lower(block_range) <= $1",
-            src.qualified_name.as_str()
+            src_name.as_str()
         ))
         .bind::<Integer, _>(&target_block.number)
         .load::<MaxVid>(conn)?
@@ -301,6 +362,9 @@ impl TableState {
             dst_site,
             src,
             dst,
+            relation,
+            target_block: target_block.number,
+            first_vid: 0,
             next_vid: 0,
             target_vid,
             batch_size: INITIAL_BATCH_SIZE,
@@ -308,14 +372,71 @@ impl TableState {
         })
     }
 
+    /// Split this table into `parts` ranges of `vid` that can be copied
+    /// independently of each other if it is big enough to make that
+    /// worthwhile
+    fn split(self, parts: usize) -> Vec<TableState> {
+        let parts = parts as i64;
+        if parts <= 1 || self.target_vid < MIN_VIDS_FOR_SPLIT {
+            return vec![self];
+        }
+        let size = (self.target_vid + parts) / parts;
+        (0..parts)
+            .map(|part| {
+                let first_vid = part * size;
+                TableState {
+                    dst_site: self.dst_site.clone(),
+                    src: self.src.clone(),
+                    dst: self.dst.clone(),
+                    relation: self.relation.clone(),
+                    target_block: self.target_block,
+                    first_vid,
+                    next_vid: first_vid,
+                    target_vid: (first_vid + size - 1).min(self.target_vid),
+                    batch_size: self.batch_size,
+                    duration_ms: 0,
+                }
+            })
+            .collect()
+    }
+
     fn finished(&self) -> bool {
         self.next_vid > self.target_vid
     }
 
+    /// The range of `vid`s that the next batch copies: all versions with
+    /// `next_vid <= vid <= next_vid + batch_size - 1`, but not going over
+    /// `target_vid`
+    fn next_batch(&self) -> (i64, i64) {
+        let last_vid = (self.next_vid + self.batch_size - 1).min(self.target_vid);
+        (self.next_vid, last_vid)
+    }
+
+    /// The value of `copy_table_state.relation` for this state
+    fn relation_key(&self) -> &str {
+        self.relation.as_deref().unwrap_or("")
+    }
+
+    /// A name for the table this state copies, for logging
+    fn name(&self) -> String {
+        match &self.relation {
+            Some(field) => format!("{}.{}", self.dst.object, field),
+            None => self.dst.object.to_string(),
+        }
+    }
+
+    /// The number of entity versions in this table's range that have been
+    /// copied, and the number of versions in the range overall
+    fn counts(&self) -> (i64, i64) {
+        let total = (self.target_vid - self.first_vid + 1).max(0);
+        ((self.next_vid - self.first_vid).min(total), total)
+    }
+
     fn load(
         conn: &PgConnection,
         src_layout: &Layout,
         dst_layout: &Layout,
+        target_block: &BlockPtr,
     ) -> Result<Vec<TableState>, StoreError> {
         use copy_table_state as cts;
 
@@ -341,22 +462,54 @@ impl TableState {
                 .map(|table| table.clone())
         }
 
+        fn resolve_relation(
+            table: &Table,
+            kind: &str,
+            relation: &str,
+            dst: DeploymentId,
+            id: i32,
+        ) -> Result<(), StoreError> {
+            table.relation(relation).map(|_| ()).map_err(|e| {
+                constraint_violation!(
+                    "invalid {} relation {}.{} in CopyState {} (table {}): {}",
+                    kind,
+                    table.object,
+                    relation,
+                    dst,
+                    id,
+                    e
+                )
+            })
+        }
+
         cts::table
             .filter(cts::dst.eq(dst_layout.site.id))
             .select((
                 cts::id,
                 cts::entity_type,
+                cts::relation,
+                cts::first_vid,
                 cts::next_vid,
                 cts::target_vid,
                 cts::batch_size,
                 cts::duration_ms,
             ))
-            .order_by(cts::entity_type)
-            .load::<(i32, String, i64, i64, i64, i64)>(conn)?
+            .order_by((cts::entity_type, cts::relation, cts::first_vid))
+            .load::<(i32, String, String, i64, i64, i64, i64, i64)>(conn)?
             .into_iter()
             .map(
-                |(id, entity_type, current_vid, target_vid, batch_size, duration_ms)| {
+                |(
+                    id,
+                    entity_type,
+                    relation,
+                    first_vid,
+                    current_vid,
+                    target_vid,
+                    batch_size,
+                    duration_ms,
+                )| {
                     let entity_type = EntityType::new(entity_type);
+                    let relation = Some(relation).filter(|relation| !relation.is_empty());
                     let src =
                         resolve_entity(src_layout, "source", &entity_type, dst_layout.site.id, id);
                     let dst = resolve_entity(
@@ -366,19 +519,28 @@ impl TableState {
                         dst_layout.site.id,
                         id,
                     );
-                    match (src, dst) {
-                        (Ok(src), Ok(dst)) => Ok(TableState {
-                            dst_site: dst_layout.site.clone(),
-                            src,
-                            dst,
-                            next_vid: current_vid,
-                            target_vid,
-                            batch_size,
-                            duration_ms,
-                        }),
-                        (Err(e), _) => Err(e),
-                        (_, Err(e)) => Err(e),
+                    let (src, dst) = match (src, dst) {
+                        (Ok(src), Ok(dst)) => (src, dst),
+                        (Err(e), _) => return Err(e),
+                        (_, Err(e)) => return Err(e),
+                    };
+                    if let Some(relation) = &relation {
+                        let site = dst_layout.site.id;
+                        resolve_relation(&src, "source", relation, site, id)?;
+                        resolve_relation(&dst, "destination", relation, site, id)?;
                     }
+                    Ok(TableState {
+                        dst_site: dst_layout.site.clone(),
+                        src,
+                        dst,
+                        relation,
+                        target_block: target_block.number,
+                        first_vid,
+                        next_vid: current_vid,
+                        target_vid,
+                        batch_size,
+                        duration_ms,
+                    })
                 },
             )
             .collect()
@@ -402,7 +564,9 @@ impl TableState {
             update(
                 cts::table
                     .filter(cts::dst.eq(self.dst_site.id))
-                    .filter(cts::entity_type.eq(self.dst.object.as_str())),
+                    .filter(cts::entity_type.eq(self.dst.object.as_str()))
+                    .filter(cts::relation.eq(self.relation_key()))
+                    .filter(cts::first_vid.eq(self.first_vid)),
             )
             .set(cts::started_at.eq(sql("now()")))
             .execute(conn)?;
@@ -415,7 +579,9 @@ impl TableState {
         update(
             cts::table
                 .filter(cts::dst.eq(self.dst_site.id))
-                .filter(cts::entity_type.eq(self.dst.object.as_str())),
+                .filter(cts::entity_type.eq(self.dst.object.as_str()))
+                .filter(cts::relation.eq(self.relation_key()))
+                .filter(cts::first_vid.eq(self.first_vid)),
         )
         .set(values)
         .execute(conn)?;
@@ -428,7 +594,9 @@ impl TableState {
         update(
             cts::table
                 .filter(cts::dst.eq(self.dst_site.id))
-                .filter(cts::entity_type.eq(self.dst.object.as_str())),
+                .filter(cts::entity_type.eq(self.dst.object.as_str()))
+                .filter(cts::relation.eq(self.relation_key()))
+                .filter(cts::first_vid.eq(self.first_vid)),
         )
         .set(cts::finished_at.eq(sql("now()")))
         .execute(conn)?;
@@ -456,12 +624,25 @@ impl TableState {
     fn copy_batch(&mut self, conn: &PgConnection) -> Result<Status, StoreError> {
         let start = Instant::now();
 
-        // Copy all versions with next_vid <= vid <= next_vid + batch_size - 1,
-        // but do not go over target_vid
-        let first_batch = self.next_vid == 0;
-        let last_vid = (self.next_vid + self.batch_size - 1).min(self.target_vid);
-        rq::CopyEntityBatchQuery::new(self.dst.as_ref(), &self.src, self.next_vid, last_vid)?
-            .execute(conn)?;
+        let first_batch = self.next_vid == self.first_vid;
+        let (_, last_vid) = self.next_batch();
+        match &self.relation {
+            Some(field) => rq::CopyRelationBatchQuery::new(
+                self.dst.relation(field)?,
+                self.src.relation(field)?,
+                self.next_vid,
+                last_vid,
+                self.target_block,
+            )?
+            .execute(conn)?,
+            None => rq::CopyEntityBatchQuery::new(
+                self.dst.as_ref(),
+                &self.src,
+                self.next_vid,
+                last_vid,
+            )?
+            .execute(conn)?,
+        };
 
         let duration = start.elapsed();
 
@@ -486,24 +667,32 @@ impl TableState {
 }
 
 // A helper for logging progress while data is being copied
-struct CopyProgress<'a> {
-    logger: &'a Logger,
+struct CopyProgress {
+    logger: Logger,
     last_log: Instant,
     src: Arc<Site>,
     dst: Arc<Site>,
+    /// The number of entity versions in tables that have been copied
+    /// completely
     current_vid: i64,
     target_vid: i64,
 }
 
-impl<'a> CopyProgress<'a> {
-    fn new(logger: &'a Logger, state: &CopyState) -> Self {
-        let target_vid: i64 = state.tables.iter().map(|table| table.target_vid).sum();
+impl CopyProgress {
+    fn new(logger: &Logger, state: &CopyState) -> Self {
+        let target_vid: i64 = state.tables.iter().map(|table| table.counts().1).sum();
+        let current_vid: i64 = state
+            .tables
+            .iter()
+            .filter(|table| table.finished())
+            .map(|table| table.counts().0)
+            .sum();
         Self {
-            logger,
+            logger: logger.clone(),
             last_log: Instant::now(),
             src: state.src.site.clone(),
             dst: state.dst.site.clone(),
-            current_vid: 0,
+            current_vid,
             target_vid,
         }
     }
@@ -531,21 +720,22 @@ impl<'a> CopyProgress<'a> {
 
     fn update(&mut self, table: &TableState) {
         if self.last_log.elapsed() > LOG_INTERVAL {
+            let (copied, total) = table.counts();
             info!(
                 self.logger,
                 "Copied {:.2}% of `{}` entities ({}/{} entity versions), {:.2}% of overall data",
-                Self::progress_pct(table.next_vid, table.target_vid),
-                table.dst.object,
+                Self::progress_pct(copied, total),
+                table.name(),
                 table.next_vid,
                 table.target_vid,
-                Self::progress_pct(self.current_vid + table.next_vid, self.target_vid)
+                Self::progress_pct(self.current_vid + copied, self.target_vid)
             );
             self.last_log = Instant::now();
         }
     }
 
     fn table_finished(&mut self, table: &TableState) {
-        self.current_vid += table.next_vid;
+        self.current_vid += table.counts().0;
     }
 
     fn finished(&self) {
@@ -556,10 +746,84 @@ impl<'a> CopyProgress<'a> {
     }
 }
 
+/// The state that the workers copying data share
+struct Work {
+    /// The tables (or ranges of tables) that still need to be copied
+    tables: Mutex<VecDeque<TableState>>,
+    progress: Mutex<CopyProgress>,
+    /// Set when one of the workers encounters an error or notices that
+    /// the copy was cancelled so that the other workers stop, too
+    stop: AtomicBool,
+}
+
+impl Work {
+    fn next_table(&self) -> Option<TableState> {
+        self.tables.lock().unwrap().pop_front()
+    }
+
+    /// Copy tables until there are none left, using `conn` for all
+    /// database work
+    fn copy_tables(&self, conn: &PgConnection) -> Result<Status, StoreError> {
+        let status = self.copy_tables_internal(conn);
+        if !matches!(status, Ok(Status::Finished)) {
+            self.stop.store(true, Ordering::SeqCst);
+        }
+        status
+    }
+
+    fn copy_tables_internal(&self, conn: &PgConnection) -> Result<Status, StoreError> {
+        while let Some(mut table) = self.next_table() {
+            while !table.finished() {
+                if self.stop.load(Ordering::SeqCst) {
+                    return Ok(Status::Cancelled);
+                }
+                // It is important that this check happens outside the write
+                // transaction so that we do not hold on to locks acquired
+                // by the check
+                if table.is_cancelled(conn)? {
+                    return Ok(Status::Cancelled);
+                }
+                let status = conn.transaction(|| table.copy_batch(conn))?;
+                if status == Status::Cancelled {
+                    return Ok(status);
+                }
+                self.progress.lock().unwrap().update(&table);
+            }
+            self.progress.lock().unwrap().table_finished(&table);
+        }
+        Ok(Status::Finished)
+    }
+}
+
+/// Wait for the workers in `handles` to finish and combine their status
+/// with `status`, the status of the work that this thread did. Errors take
+/// precedence over cancellation since other workers stop, and report that
+/// they were cancelled, when one of them fails. A worker that panics stops
+/// all other workers and counts as an error
+fn join_workers(
+    mut status: Result<Status, StoreError>,
+    handles: Vec<thread::JoinHandle<Result<Status, StoreError>>>,
+    stop: &AtomicBool,
+) -> Result<Status, StoreError> {
+    for handle in handles {
+        let res = handle.join().unwrap_or_else(|_| {
+            stop.store(true, Ordering::SeqCst);
+            Err(constraint_violation!("a copy worker panicked"))
+        });
+        status = match (status, res) {
+            (Err(e), _) | (_, Err(e)) => Err(e),
+            (Ok(Status::Cancelled), _) | (_, Ok(Status::Cancelled)) => Ok(Status::Cancelled),
+            (Ok(Status::Finished), Ok(Status::Finished)) => Ok(Status::Finished),
+        };
+    }
+    status
+}
+
 /// A helper for copying subgraphs
 pub struct Connection {
     /// The connection pool for the shard that will contain the destination
     /// of the copy
+    pool: ConnectionPool,
     logger: Logger,
     conn: PooledConnection<ConnectionManager<PgConnection>>,
     src: Arc<Layout>,
@@ -591,6 +855,7 @@ impl Connection {
             false
         })?;
         Ok(Self {
+            pool,
             logger,
             conn,
             src,
@@ -606,6 +871,23 @@ impl Connection {
         self.conn.transaction(|| f(&self.conn))
     }
 
+    /// Get connections for up to `count` additional workers. We do not
+    /// wait for connections that are busy with other copies; if there
+    /// aren't enough, we simply use fewer workers
+    fn worker_connections(
+        &self,
+        count: usize,
+    ) -> Vec<PooledConnection<ConnectionManager<PgConnection>>> {
+        let mut conns = Vec::new();
+        for _ in 0..count {
+            match self.pool.get_fdw(&self.logger, || true) {
+                Ok(conn) => conns.push(conn),
+                Err(_) => break,
+            }
+        }
+        conns
+    }
+
     pub fn copy_data_internal(&self) -> Result<Status, StoreError> {
         let mut state = self.transaction(|conn| {
             CopyState::new(
@@ -616,28 +898,52 @@ impl Connection {
             )
         })?;
 
-        let mut progress = CopyProgress::new(&self.logger, &state);
+        let progress = CopyProgress::new(&self.logger, &state);
         progress.start();
 
-        for table in state.tables.iter_mut().filter(|table| !table.finished()) {
-            while !table.finished() {
-                // It is important that this check happens outside the write
-                // transaction so that we do not hold on to locks acquired
-                // by the check
-                if table.is_cancelled(&self.conn)? {
-                    return Ok(Status::Cancelled);
-                }
-                let status = self.transaction(|conn| table.copy_batch(conn))?;
-                if status == Status::Cancelled {
-                    return Ok(status);
+        let tables: VecDeque<_> = std::mem::take(&mut state.tables)
+            .into_iter()
+            .filter(|table| !table.finished())
+            .collect();
+        let workers = (*COPY_WORKERS).min(tables.len()).max(1);
+        let work = Arc::new(Work {
+            tables: Mutex::new(tables),
+            progress: Mutex::new(progress),
+            stop: AtomicBool::new(false),
+        });
+
+        // This thread does its share of the work on `self.conn`, the
+        // other workers each get their own connection
+        let conns = self.worker_connections(workers - 1);
+        if !conns.is_empty() {
+            info!(
+                &self.logger,
+                "Copying data with {} workers",
+                conns.len() + 1
+            );
+        }
+        let mut handles = Vec::new();
+        for (i, conn) in conns.into_iter().enumerate() {
+            let work = work.clone();
+            let handle = thread::Builder::new()
+                .name(format!("copy-{}-{}", self.dst.site.namespace, i + 1))
+                .spawn(move || work.copy_tables(&conn));
+            match handle {
+                Ok(handle) => handles.push(handle),
+                Err(e) => {
+                    warn!(&self.logger, "Failed to start copy worker: {}", e);
+                    break;
                 }
-                progress.update(table);
             }
-            progress.table_finished(table);
+        }
+
+        let status = work.copy_tables(&self.conn);
+        if join_workers(status, handles, &work.stop)? == Status::Cancelled {
+            return Ok(Status::Cancelled);
         }
 
         self.transaction(|conn| state.finished(conn))?;
-        progress.finished();
+        work.progress.lock().unwrap().finished();
 
         Ok(Status::Finished)
     }
@@ -678,3 +984,144 @@ impl Connection {
         res
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use graph::data::schema::Schema;
+    use graph::prelude::DeploymentHash;
+
+    use super::*;
+    use crate::layout_for_tests::{make_dummy_site, Catalog, Namespace};
+
+    fn table_state(target_vid: i64) -> TableState {
+        let subgraph = DeploymentHash::new("subgraph").unwrap();
+        let schema = Schema::parse("type Thing @entity { id: ID! }", subgraph.clone()).unwrap();
+        let namespace = Namespace::new("sgd0815".to_owned()).unwrap();
+        let site = Arc::new(make_dummy_site(subgraph, namespace, "anet".to_string()));
+        let catalog = Catalog::make_empty(site.clone()).unwrap();
+        let layout = Layout::new(site.clone(), &schema, catalog, false).unwrap();
+        let table = layout.table_for_entity(&EntityType::from("Thing")).unwrap();
+        TableState {
+            dst_site: site,
+            src: table.clone(),
+            dst: table.clone(),
+            relation: None,
+            target_block: 100,
+            first_vid: 0,
+            next_vid: 0,
+            target_vid,
+            batch_size: INITIAL_BATCH_SIZE,
+            duration_ms: 0,
+        }
+    }
+
+    #[test]
+    fn split_covers_all_vids() {
+        // Small tables are not split
+        assert_eq!(1, table_state(MIN_VIDS_FOR_SPLIT - 1).split(4).len());
+        assert_eq!(1, table_state(MIN_VIDS_FOR_SPLIT * 4).split(1).len());
+
+        for target_vid in &[MIN_VIDS_FOR_SPLIT, MIN_VIDS_FOR_SPLIT * 3 + 7] {
+            let ranges = table_state(*target_vid).split(4);
+            assert_eq!(4, ranges.len());
+
+            // The ranges are adjacent, do not overlap, and cover all vids
+            // from 0 up to and including target_vid
+            let mut next = 0;
+            for range in &ranges {
+                assert_eq!(next, range.first_vid);
+                assert_eq!(range.first_vid, range.next_vid);
+                assert!(range.first_vid <= range.target_vid);
+                assert!(!range.finished());
+                next = range.target_vid + 1;
+            }
+            assert_eq!(*target_vid + 1, next);
+
+            let total: i64 = ranges.iter().map(|range| range.counts().1).sum();
+            assert_eq!(*target_vid + 1, total);
+        }
+    }
+
+    #[test]
+    fn resume_partially_copied_range() {
+        let mut ranges = table_state(MIN_VIDS_FOR_SPLIT * 2 - 1).split(2);
+        let mut range = ranges.pop().unwrap();
+        assert_eq!(MIN_VIDS_FOR_SPLIT, range.first_vid);
+        assert_eq!(MIN_VIDS_FOR_SPLIT * 2 - 1, range.target_vid);
+
+        // Pretend that some batches of the range were copied before the
+        // copy was interrupted; copying continues where it left off and
+        // stops at the end of the range
+        range.next_vid = range.target_vid - 10;
+        assert!(!range.finished());
+        assert_eq!(
+            (range.target_vid - range.first_vid - 10, MIN_VIDS_FOR_SPLIT),
+            range.counts()
+        );
+        assert_eq!(
+            (range.target_vid - 10, range.target_vid),
+            range.next_batch()
+        );
+
+        range.next_vid = range.target_vid + 1;
+        assert!(range.finished());
+        assert_eq!((MIN_VIDS_FOR_SPLIT, MIN_VIDS_FOR_SPLIT), range.counts());
+
+        // An unfinished batch in the middle of the range copies a full batch
+        let mut range = ranges.pop().unwrap();
+        range.next_vid = 5;
+        assert_eq!((5, 4 + INITIAL_BATCH_SIZE), range.next_batch());
+    }
+
+    #[test]
+    fn worker_failure_stops_copy() {
+        fn spawn(
+            f: impl FnOnce() -> Result<Status, StoreError> + Send + 'static,
+        ) -> thread::JoinHandle<Result<Status, StoreError>> {
+            thread::spawn(f)
+        }
+
+        let stop = AtomicBool::new(false);
+        let status = join_workers(
+            Ok(Status::Finished),
+            vec![
+                spawn(|| Ok(Status::Finished)),
+                spawn(|| Ok(Status::Finished)),
+            ],
+            &stop,
+        );
+        assert_eq!(Status::Finished, status.unwrap());
+
+        // A failed worker makes the whole copy fail, even if the other
+        // workers report that they were cancelled because of the failure
+        let status = join_workers(
+            Ok(Status::Cancelled),
+            vec![
+                spawn(|| Ok(Status::Cancelled)),
+                spawn(|| Err(constraint_violation!("worker failed"))),
+            ],
+            &stop,
+        );
+        assert!(status.unwrap_err().to_string().contains("worker failed"));
+
+        let status = join_workers(
+            Ok(Status::Finished),
+            vec![
+                spawn(|| Ok(Status::Cancelled)),
+                spawn(|| Ok(Status::Finished)),
+            ],
+            &stop,
+        );
+        assert_eq!(Status::Cancelled, status.unwrap());
+        assert!(!stop.load(Ordering::SeqCst));
+
+        // A worker that panics stops the other workers
+        let status = join_workers(
+            Ok(Status::Finished),
+            vec![spawn(|| panic!("worker panicked"))],
+            &stop,
+        );
+        assert!(status.is_err());
+        assert!(stop.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,173 @@
+//! SQL queries for the data source contexts that mappings store with
+//! `dataSource.setContext`
+
+use diesel::{
+    delete,
+    dsl::count,
+    prelude::{ExpressionMethods, QueryDsl, RunQueryDsl},
+    sql_query,
+    sql_types::{Binary, Integer, Text},
+};
+use diesel::{insert_into, pg::PgConnection};
+
+use graph::{
+    components::store::DataSourceContextUpdate,
+    constraint_violation,
+    prelude::{serde_json, BlockNumber, BlockPtr, DeploymentHash, StoreError},
+};
+
+use crate::connection_pool::ForeignServer;
+use crate::primary::Site;
+
+table! {
+    subgraphs.data_source_context (vid) {
+        vid -> BigInt,
+        deployment -> Text,
+        data_source -> Text,
+        address -> Binary,
+        context -> Text,
+        block_number -> Integer,
+    }
+}
+
+/// Load the latest context that was stored for each data source of
+/// deployment `id`
+pub fn load(conn: &PgConnection, id: &str) -> Result<Vec<DataSourceContextUpdate>, StoreError> {
+    #[derive(QueryableByName)]
+    struct Row {
+        #[sql_type = "Text"]
+        data_source: String,
+        #[sql_type = "Binary"]
+        address: Vec<u8>,
+        #[sql_type = "Text"]
+        context: String,
+    }
+
+    const QUERY: &str = "
+        select distinct on (data_source, address) data_source, address, context
+          from subgraphs.data_source_context
+         where deployment = $1
+         order by data_source, address, block_number desc, vid desc";
+
+    sql_query(QUERY)
+        .bind::<Text, _>(id)
+        .load::<Row>(conn)?
+        .into_iter()
+        .map(|row| {
+            let context = serde_json::from_str(&row.context).map_err(|e| {
+                constraint_violation!(
+                    "invalid context for data source `{}` in deployment {}: {}",
+                    row.data_source,
+                    id,
+                    e
+                )
+            })?;
+            Ok(DataSourceContextUpdate {
+                data_source: row.data_source,
+                address: row.address,
+                context,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn insert(
+    conn: &PgConnection,
+    deployment: &DeploymentHash,
+    updates: &[DataSourceContextUpdate],
+    block_ptr: &BlockPtr,
+) -> Result<usize, StoreError> {
+    use data_source_context as dsc;
+
+    if updates.is_empty() {
+        // Avoids a roundtrip to the DB.
+        return Ok(0);
+    }
+
+    let rows: Vec<_> = updates
+        .iter()
+        .map(|update| {
+            (
+                dsc::deployment.eq(deployment.as_str()),
+                dsc::data_source.eq(&update.data_source),
+                dsc::address.eq(&update.address),
+                // Unwrap: Entities can always be serialized
+                dsc::context.eq(serde_json::to_string(&update.context).unwrap()),
+                dsc::block_number.eq(block_ptr.number),
+            )
+        })
+        .collect();
+
+    insert_into(dsc::table)
+        .values(rows)
+        .execute(conn)
+        .map_err(|e| e.into())
+}
+
+/// Copy the data source contexts for `src` to `dst`. All contexts that
+/// were stored up to and including `target_block` will be copied.
+pub(crate) fn copy(
+    conn: &PgConnection,
+    src: &Site,
+    dst: &Site,
+    target_block: &BlockPtr,
+) -> Result<usize, StoreError> {
+    use data_source_context as dsc;
+
+    let src_nsp = if src.shard == dst.shard {
+        "subgraphs".to_string()
+    } else {
+        ForeignServer::metadata_schema(&src.shard)
+    };
+
+    // Check whether there are any data source contexts for dst which
+    // indicates we already did copy
+    let count = dsc::table
+        .filter(dsc::deployment.eq(dst.deployment.as_str()))
+        .select(count(dsc::vid))
+        .get_result::<i64>(conn)?;
+    if count > 0 {
+        return Ok(count as usize);
+    }
+
+    let query = format!(
+        "\
+      insert into subgraphs.data_source_context(deployment, data_source,
+             address, context, block_number)
+      select $2 as deployment, c.data_source, c.address, c.context,
+             c.block_number
+        from {src_nsp}.data_source_context c
+       where c.deployment = $1
+         and c.block_number <= $3
+       order by c.vid",
+        src_nsp = src_nsp
+    );
+
+    Ok(sql_query(&query)
+        .bind::<Text, _>(src.deployment.as_str())
+        .bind::<Text, _>(dst.deployment.as_str())
+        .bind::<Integer, _>(target_block.number)
+        .execute(conn)?)
+}
+
+/// Remove the contexts that were stored in `block` or later, so that the
+/// data sources go back to the context they had before `block`
+pub(crate) fn revert(
+    conn: &PgConnection,
+    id: &DeploymentHash,
+    block: BlockNumber,
+) -> Result<(), StoreError> {
+    use data_source_context as dsc;
+
+    let contexts = dsc::table.filter(dsc::deployment.eq(id.as_str()));
+    delete(contexts.filter(dsc::block_number.ge(block))).execute(conn)?;
+    Ok(())
+}
+
+pub(crate) fn drop(conn: &PgConnection, id: &DeploymentHash) -> Result<usize, StoreError> {
+    use data_source_context as dsc;
+
+    delete(dsc::table.filter(dsc::deployment.eq(id.as_str())))
+        .execute(conn)
+        .map_err(|e| e.into())
+}
@@ -18,13 +18,24 @@ use graph::prelude::{
     anyhow, bigdecimal::ToPrimitive, hex, web3::types::H256, BigDecimal, BlockNumber, BlockPtr,
     DeploymentHash, DeploymentState, Schema, StoreError,
 };
-use graph::{data::subgraph::schema::SubgraphError, prelude::SubgraphDeploymentEntity};
+use graph::{
+    data::subgraph::schema::{SubgraphError, SubgraphWarning},
+    prelude::SubgraphDeploymentEntity,
+};
 use stable_hash::crypto::SetHasher;
 use std::str::FromStr;
-use std::{collections::BTreeSet, convert::TryFrom, ops::Bound};
+use std::time::Duration;
+use std::{
+    collections::{BTreeSet, HashMap},
+    convert::TryFrom,
+    ops::Bound,
+};
 
 use crate::connection_pool::ForeignServer;
-use crate::{block_range::BLOCK_RANGE_COLUMN, primary::Site};
+use crate::{
+    block_range::BLOCK_RANGE_COLUMN,
+    primary::{DeploymentId, Site},
+};
 use graph::constraint_violation;
 
 #[derive(DbEnum, Debug, Clone, Copy)]
@@ -78,6 +89,7 @@ table! {
         current_reorg_depth -> Integer,
         max_reorg_depth -> Integer,
         firehose_cursor -> Nullable<Text>,
+        earliest_block_number -> Integer,
     }
 }
 
@@ -94,6 +106,17 @@ table! {
     }
 }
 
+table! {
+    subgraphs.subgraph_warning {
+        id -> BigInt,
+        deployment -> Integer,
+        message -> Text,
+        block_hash -> Binary,
+        block_number -> Integer,
+        data_source -> Text,
+    }
+}
+
 table! {
     subgraphs.subgraph_manifest {
         id -> Integer,
@@ -295,11 +318,19 @@ pub fn update_firehose_cursor(
 ) -> Result<(), StoreError> {
     use subgraph_deployment as d;
 
-    update(d::table.filter(d::deployment.eq(id.as_str())))
-        .set((d::firehose_cursor.eq(cursor),))
-        .execute(conn)
-        .map(|_| ())
-        .map_err(|e| e.into())
+    // Avoid writing the row when the cursor does not change, which is the
+    // case for every block when the cursor is empty
+    update(
+        d::table.filter(d::deployment.eq(id.as_str())).filter(
+            d::firehose_cursor
+                .is_null()
+                .or(d::firehose_cursor.ne(cursor)),
+        ),
+    )
+    .set((d::firehose_cursor.eq(cursor),))
+    .execute(conn)
+    .map(|_| ())
+    .map_err(|e| e.into())
 }
 
 pub fn revert_block_ptr(
@@ -394,15 +425,22 @@ pub fn state(conn: &PgConnection, id: DeploymentHash) -> Result<DeploymentState,
             d::reorg_count,
             d::max_reorg_depth,
             d::latest_ethereum_block_number,
+            d::earliest_block_number,
         ))
-        .first::<(String, i32, i32, Option<BigDecimal>)>(conn)
+        .first::<(String, i32, i32, Option<BigDecimal>, BlockNumber)>(conn)
         .optional()?
     {
         None => Err(StoreError::QueryExecutionError(format!(
             "No data found for subgraph {}",
             id
         ))),
-        Some((_, reorg_count, max_reorg_depth, latest_ethereum_block_number)) => {
+        Some((
+            _,
+            reorg_count,
+            max_reorg_depth,
+            latest_ethereum_block_number,
+            earliest_block_number,
+        )) => {
             let reorg_count = convert_to_u32(Some(reorg_count), "reorg_count", id.as_str())?;
             let max_reorg_depth =
                 convert_to_u32(Some(max_reorg_depth), "max_reorg_depth", id.as_str())?;
@@ -414,11 +452,68 @@ pub fn state(conn: &PgConnection, id: DeploymentHash) -> Result<DeploymentState,
                 reorg_count,
                 max_reorg_depth,
                 latest_ethereum_block_number,
+                earliest_block_number,
             })
         }
     }
 }
 
+/// The first block for which the deployment can answer queries
+pub fn earliest_block(conn: &PgConnection, site: &Site) -> Result<BlockNumber, StoreError> {
+    use subgraph_deployment as d;
+
+    Ok(d::table
+        .filter(d::id.eq(site.id))
+        .select(d::earliest_block_number)
+        .get_result::<BlockNumber>(conn)?)
+}
+
+/// Record that the deployment can only answer queries for blocks from
+/// `earliest_block` on. The earliest block never moves backwards
+pub fn set_earliest_block(
+    conn: &PgConnection,
+    site: &Site,
+    earliest_block: BlockNumber,
+) -> Result<(), StoreError> {
+    use subgraph_deployment as d;
+
+    update(d::table.filter(d::id.eq(site.id)))
+        .filter(d::earliest_block_number.lt(earliest_block))
+        .set(d::earliest_block_number.eq(earliest_block))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Copy the earliest block of `src` to `dst`. A copy of a pruned
+/// deployment lacks the same history as its source
+pub(crate) fn copy_earliest_block(
+    conn: &PgConnection,
+    src: &Site,
+    dst: &Site,
+) -> Result<(), StoreError> {
+    let src_nsp = if src.shard == dst.shard {
+        "subgraphs".to_string()
+    } else {
+        ForeignServer::metadata_schema(&src.shard)
+    };
+
+    let query = format!(
+        "\
+      update subgraphs.subgraph_deployment d
+         set earliest_block_number = s.earliest_block_number
+        from {src_nsp}.subgraph_deployment s
+       where s.id = $1
+         and d.id = $2
+         and d.earliest_block_number < s.earliest_block_number",
+        src_nsp = src_nsp
+    );
+    sql_query(&query)
+        .bind::<Integer, _>(src.id)
+        .bind::<Integer, _>(dst.id)
+        .execute(conn)?;
+    Ok(())
+}
+
 /// Mark the deployment `id` as synced
 pub fn set_synced(conn: &PgConnection, id: &DeploymentHash) -> Result<(), StoreError> {
     use subgraph_deployment as d;
@@ -589,6 +684,82 @@ pub(crate) fn insert_subgraph_errors(
     check_health(conn, id, block)
 }
 
+/// Insert `warnings` for the deployment `site` and delete all but the most
+/// recent `max_warnings` warnings for it
+pub(crate) fn insert_subgraph_warnings(
+    conn: &PgConnection,
+    site: &Site,
+    warnings: &[SubgraphWarning],
+    max_warnings: usize,
+) -> Result<(), StoreError> {
+    use subgraph_warning as w;
+
+    let rows: Vec<_> = warnings
+        .iter()
+        .map(|warning| {
+            (
+                w::deployment.eq(site.id),
+                w::message.eq(&warning.message),
+                w::block_hash.eq(warning.block_ptr.hash_slice()),
+                w::block_number.eq(warning.block_ptr.number),
+                w::data_source.eq(&warning.data_source),
+            )
+        })
+        .collect();
+    insert_into(w::table).values(rows).execute(conn)?;
+
+    // Find the oldest warning we want to keep and delete everything before it
+    let oldest: Option<i64> = w::table
+        .filter(w::deployment.eq(site.id))
+        .select(w::id)
+        .order_by(w::id.desc())
+        .offset(max_warnings as i64 - 1)
+        .first(conn)
+        .optional()?;
+    if let Some(oldest) = oldest {
+        delete(
+            w::table
+                .filter(w::deployment.eq(site.id))
+                .filter(w::id.lt(oldest)),
+        )
+        .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Return the warnings for each of `deployments`, oldest first
+pub(crate) fn subgraph_warnings(
+    conn: &PgConnection,
+    deployments: &[DeploymentId],
+) -> Result<HashMap<DeploymentId, Vec<SubgraphWarning>>, StoreError> {
+    use subgraph_warning as w;
+
+    let rows = w::table
+        .filter(w::deployment.eq_any(deployments))
+        .select((
+            w::deployment,
+            w::message,
+            w::block_hash,
+            w::block_number,
+            w::data_source,
+        ))
+        .order_by(w::id)
+        .load::<(DeploymentId, String, Vec<u8>, i32, String)>(conn)?;
+
+    let mut warnings: HashMap<_, Vec<_>> = HashMap::new();
+    for (deployment, message, hash, number, data_source) in rows {
+        warnings
+            .entry(deployment)
+            .or_default()
+            .push(SubgraphWarning {
+                message,
+                block_ptr: BlockPtr::from((H256::from_slice(&hash), number)),
+                data_source,
+            });
+    }
+    Ok(warnings)
+}
+
 #[cfg(debug_assertions)]
 pub(crate) fn error_count(conn: &PgConnection, id: &DeploymentHash) -> Result<usize, StoreError> {
     use subgraph_error as e;
@@ -729,24 +900,42 @@ pub(crate) fn copy_errors(
 ///
 /// Since long-running operations, like a vacuum on one of the tables in the
 /// schema, could block dropping the schema indefinitely, this operation
-/// will wait at most 2s to aquire all necessary locks, and fail if that is
-/// not possible.
+/// will wait at most `lock_timeout` to aquire all necessary locks, and fail
+/// if that is not possible. Such failures can be recognized with
+/// `is_lock_timeout`
 pub fn drop_schema(
     conn: &diesel::pg::PgConnection,
     namespace: &crate::primary::Namespace,
+    lock_timeout: Duration,
 ) -> Result<(), StoreError> {
     let query = format!(
-        "set local lock_timeout=2000; drop schema if exists {} cascade",
+        "set local lock_timeout={}; drop schema if exists {} cascade",
+        lock_timeout.as_millis(),
         namespace
     );
     Ok(conn.batch_execute(&*query)?)
 }
 
+/// Return `true` if `err` was caused by a statement that was canceled
+/// because it could not acquire a lock within the `lock_timeout` set for it
+pub fn is_lock_timeout(err: &StoreError) -> bool {
+    match err {
+        StoreError::Unknown(e) => match e.downcast_ref::<diesel::result::Error>() {
+            Some(diesel::result::Error::DatabaseError(_, info)) => {
+                info.message().contains("lock timeout")
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
 pub fn drop_metadata(conn: &PgConnection, site: &Site) -> Result<(), StoreError> {
     use subgraph_deployment as d;
 
-    // We don't need to delete from subgraph_manifest or subgraph_error
-    // since that cascades from deleting the subgraph_deployment
+    // We don't need to delete from subgraph_manifest, subgraph_error or
+    // subgraph_warning since that cascades from deleting the
+    // subgraph_deployment
     delete(d::table.filter(d::id.eq(site.id))).execute(conn)?;
     Ok(())
 }
@@ -4,7 +4,10 @@ use diesel::connection::SimpleConnection;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
-use graph::components::store::{EntityType, StoredDynamicDataSource};
+use graph::components::store::{
+    DataSourceContextUpdate, EntityType, PruneReporter, ScheduledBlockHandler,
+    StoredDynamicDataSource,
+};
 use graph::data::subgraph::status;
 use graph::prelude::{
     tokio, CancelHandle, CancelToken, CancelableError, PoolWaitStats, SubgraphDeploymentEntity,
@@ -12,13 +15,15 @@ use graph::prelude::{
 use lru_time_cache::LruCache;
 use rand::{seq::SliceRandom, thread_rng};
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::Into;
 use std::convert::TryInto;
 use std::env;
+use std::io::Write;
 use std::iter::FromIterator;
 use std::ops::Bound;
 use std::ops::Deref;
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::{atomic::AtomicUsize, Arc, Mutex};
 use std::time::Duration;
@@ -27,7 +32,7 @@ use std::time::Instant;
 use graph::components::store::EntityCollection;
 use graph::components::subgraph::ProofOfIndexingFinisher;
 use graph::constraint_violation;
-use graph::data::subgraph::schema::{SubgraphError, POI_OBJECT};
+use graph::data::subgraph::schema::{SubgraphError, SubgraphWarning, POI_OBJECT};
 use graph::prelude::{
     anyhow, debug, info, lazy_static, o, warn, web3, ApiSchema, AttributeNames, BlockNumber,
     BlockPtr, CheapClone, DeploymentHash, DeploymentState, Entity, EntityKey, EntityModification,
@@ -37,13 +42,17 @@ use graph::prelude::{
 use graph_graphql::prelude::api_schema;
 use web3::types::Address;
 
-use crate::block_range::block_number;
+use crate::block_range::{block_number, BLOCK_RANGE_COLUMN};
 use crate::catalog;
+use crate::chain_store::ChainStore;
 use crate::deployment;
-use crate::relational::{Layout, LayoutCache};
+use crate::index;
+use crate::index_advice::{self, IndexAdvice};
+use crate::relational::{Layout, LayoutCache, SqlName, PRIMARY_KEY_COLUMN};
 use crate::relational_queries::FromEntityData;
+use crate::writable::{PendingBlock, COPY_INSERTS};
 use crate::{connection_pool::ConnectionPool, detail};
-use crate::{dynds, primary::Site};
+use crate::{data_source_contexts, dump, dynds, primary::Site, reader, scheduled_handlers};
 
 lazy_static! {
     /// `GRAPH_QUERY_STATS_REFRESH_INTERVAL` is how long statistics that
@@ -59,8 +68,91 @@ lazy_static! {
             Duration::from_secs(secs)
         }).unwrap_or(Duration::from_secs(300))
     };
+
+    /// `GRAPH_SCHEMA_CACHE_CAPACITY` is the number of deployments per
+    /// shard whose schemas are kept in memory. Defaults to 100
+    static ref SCHEMA_CACHE_CAPACITY: usize = {
+        env::var("GRAPH_SCHEMA_CACHE_CAPACITY")
+        .ok()
+        .map(|s| {
+            usize::from_str(&s).unwrap_or_else(|_| {
+                panic!("GRAPH_SCHEMA_CACHE_CAPACITY must be a number, but is `{}`", s)
+            })
+        }).unwrap_or(100)
+    };
+
+    /// `GRAPH_STORE_DROP_LOCK_TIMEOUT` is how long dropping the schema of a
+    /// deployment waits for the locks it needs before giving up (in
+    /// milliseconds). Defaults to 2000
+    static ref DROP_LOCK_TIMEOUT: Duration = {
+        env::var("GRAPH_STORE_DROP_LOCK_TIMEOUT")
+        .ok()
+        .map(|s| {
+            let millis = u64::from_str(&s).unwrap_or_else(|_| {
+                panic!("GRAPH_STORE_DROP_LOCK_TIMEOUT must be a number, but is `{}`", s)
+            });
+            Duration::from_millis(millis)
+        }).unwrap_or(Duration::from_millis(2000))
+    };
+
+    /// `GRAPH_STORE_DROP_RETRIES` is how often dropping the schema of a
+    /// deployment is retried when it can not get the locks it needs.
+    /// Defaults to 5
+    static ref DROP_RETRIES: u32 = {
+        env::var("GRAPH_STORE_DROP_RETRIES")
+        .ok()
+        .map(|s| {
+            u32::from_str(&s).unwrap_or_else(|_| {
+                panic!("GRAPH_STORE_DROP_RETRIES must be a number, but is `{}`", s)
+            })
+        }).unwrap_or(5)
+    };
+
+    /// `GRAPH_MAX_SUBGRAPH_WARNINGS` is how many of the warnings that
+    /// mappings emit with `log.subgraphWarning` are kept for each
+    /// deployment. Older warnings are deleted. Defaults to 100
+    static ref MAX_SUBGRAPH_WARNINGS: usize = {
+        env::var("GRAPH_MAX_SUBGRAPH_WARNINGS")
+        .ok()
+        .map(|s| {
+            usize::from_str(&s).unwrap_or_else(|_| {
+                panic!("GRAPH_MAX_SUBGRAPH_WARNINGS must be a number, but is `{}`", s)
+            })
+        }).unwrap_or(100)
+    };
+
+    /// `GRAPH_QUERY_REPLICA_MIN_COST` turns on routing queries by their
+    /// estimated cost: queries that cost at least this much are sent to
+    /// read replicas, and cheaper queries to the main database. If it is
+    /// not set, queries are spread over all databases by their weight
+    static ref REPLICA_MIN_COST: Option<u64> = {
+        env::var("GRAPH_QUERY_REPLICA_MIN_COST")
+        .ok()
+        .map(|s| {
+            u64::from_str(&s).unwrap_or_else(|_| {
+                panic!("GRAPH_QUERY_REPLICA_MIN_COST must be a number, but is `{}`", s)
+            })
+        })
+    };
+
+    /// `GRAPH_STORE_REPLICA_MAX_LAG` is how far a read replica can fall
+    /// behind the main database (in seconds) before expensive queries stop
+    /// being sent to it. Defaults to 30
+    static ref REPLICA_MAX_LAG: Duration = {
+        env::var("GRAPH_STORE_REPLICA_MAX_LAG")
+        .ok()
+        .map(|s| {
+            let secs = u64::from_str(&s).unwrap_or_else(|_| {
+                panic!("GRAPH_STORE_REPLICA_MAX_LAG must be a number, but is `{}`", s)
+            });
+            Duration::from_secs(secs)
+        }).unwrap_or(Duration::from_secs(30))
+    };
 }
 
+/// How often we check how far read replicas lag behind the main database
+const REPLICA_LAG_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
 /// When connected to read replicas, this allows choosing which DB server to use for an operation.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ReplicaId {
@@ -71,6 +163,13 @@ pub enum ReplicaId {
     ReadOnly(usize),
 }
 
+/// The result of the last check of how far a read replica lags behind
+#[derive(Default)]
+struct ReplicaLag {
+    checked_at: Option<Instant>,
+    lagging: bool,
+}
+
 /// Commonly needed information about a subgraph that we cache in
 /// `Store.subgraph_cache`. Only immutable subgraph data can be cached this
 /// way as the cache lives for the lifetime of the `Store` object
@@ -100,6 +199,8 @@ pub struct StoreInner {
     /// The current position in `replica_order` so we know which one to
     /// pick next
     conn_round_robin_counter: AtomicUsize,
+    /// Whether each read replica was lagging the last time we checked
+    replica_lag: Vec<Mutex<ReplicaLag>>,
 
     /// A cache of commonly needed data about a subgraph.
     subgraph_cache: Mutex<LruCache<DeploymentHash, SubgraphInfo>>,
@@ -155,6 +256,11 @@ impl DeploymentStore {
         replica_order.shuffle(&mut rng);
         debug!(logger, "Using postgres host order {:?}", replica_order);
 
+        let replica_lag = read_only_pools
+            .iter()
+            .map(|_| Mutex::new(ReplicaLag::default()))
+            .collect();
+
         // Create the store
         let store = StoreInner {
             logger: logger.clone(),
@@ -162,7 +268,8 @@ impl DeploymentStore {
             read_only_pools,
             replica_order,
             conn_round_robin_counter: AtomicUsize::new(0),
-            subgraph_cache: Mutex::new(LruCache::with_capacity(100)),
+            replica_lag,
+            subgraph_cache: Mutex::new(LruCache::with_capacity(*SCHEMA_CACHE_CAPACITY)),
             layout_cache: LayoutCache::new(*STATS_REFRESH_INTERVAL),
         };
         let store = DeploymentStore(Arc::new(store));
@@ -200,6 +307,9 @@ impl DeploymentStore {
                 conn.batch_execute(&query)?;
 
                 let layout = Layout::create_relational_schema(&conn, site.clone(), schema)?;
+                // The tables are empty, which makes counting cheap, and
+                // gives the write path counts to add to
+                layout.recount_entities(&conn, &[])?;
                 // See if we are grafting and check that the graft is permissible
                 if let Some(base) = graft_base {
                     let errors = layout.can_copy_from(&base);
@@ -227,14 +337,41 @@ impl DeploymentStore {
     }
 
     // Remove the data and metadata for the deployment `site`. This operation
-    // is not reversible
-    pub(crate) fn drop_deployment(&self, site: &Site) -> Result<(), StoreError> {
-        let conn = self.get_conn()?;
-        conn.transaction(|| {
-            crate::deployment::drop_schema(&conn, &site.namespace)?;
-            crate::dynds::drop(&conn, &site.deployment)?;
-            crate::deployment::drop_metadata(&conn, site)
-        })
+    // is not reversible. Return the number of bytes that the data of the
+    // deployment occupied.
+    //
+    // Queries or maintenance operations that hold locks on the deployment's
+    // tables can make dropping its schema time out; in that case, wait a
+    // little and try again, up to `GRAPH_STORE_DROP_RETRIES` times
+    pub(crate) fn drop_deployment(&self, site: &Site) -> Result<u64, StoreError> {
+        let mut attempt = 0;
+        loop {
+            let conn = self.get_conn()?;
+            let res = conn.transaction(|| {
+                let size = catalog::namespace_size(&conn, &site.namespace)?;
+                deployment::drop_schema(&conn, &site.namespace, *DROP_LOCK_TIMEOUT)?;
+                dynds::drop(&conn, &site.deployment)?;
+                scheduled_handlers::drop(&conn, &site.deployment)?;
+                data_source_contexts::drop(&conn, &site.deployment)?;
+                deployment::drop_metadata(&conn, site)?;
+                Ok(size)
+            });
+            match res {
+                Err(e) if deployment::is_lock_timeout(&e) && attempt < *DROP_RETRIES => {
+                    attempt += 1;
+                    let delay = Duration::from_secs(2u64.pow(attempt.min(5)));
+                    warn!(self.logger, "Dropping deployment timed out waiting for locks, retrying";
+                          "sgd" => site.id.to_string(),
+                          "namespace" => site.namespace.as_str(),
+                          "attempt" => attempt,
+                          "retry_delay_s" => delay.as_secs());
+                    // Release the connection while we wait
+                    drop(conn);
+                    std::thread::sleep(delay);
+                }
+                res => return res,
+            }
+        }
     }
 
     pub(crate) fn execute_query<T: FromEntityData>(
@@ -254,10 +391,24 @@ impl DeploymentStore {
             query.order,
             query.range,
             query.block,
+            query.history,
             query.query_id,
         )
     }
 
+    pub(crate) fn fulltext_search<T: FromEntityData>(
+        &self,
+        conn: &PgConnection,
+        site: Arc<Site>,
+        text: &str,
+        range: EntityRange,
+        block: BlockNumber,
+        query_id: Option<String>,
+    ) -> Result<Vec<T>, QueryExecutionError> {
+        let layout = self.layout(conn, site)?;
+        layout.fulltext_search(&self.logger, conn, text, range, block, query_id)
+    }
+
     fn check_interface_entity_uniqueness(
         &self,
         conn: &PgConnection,
@@ -311,16 +462,24 @@ impl DeploymentStore {
         mods: &[EntityModification],
         ptr: &BlockPtr,
         stopwatch: StopwatchMetrics,
-    ) -> Result<i32, StoreError> {
+        copied: &HashSet<EntityKey>,
+    ) -> Result<HashMap<EntityType, i32>, StoreError> {
         use EntityModification::*;
-        let mut count = 0;
+        let mut counts: HashMap<EntityType, i32> = HashMap::new();
 
         // Group `Insert`s and `Overwrite`s by key, and accumulate `Remove`s.
         let mut inserts = HashMap::new();
+        let mut copied_inserts = HashMap::new();
         let mut overwrites = HashMap::new();
         let mut removals = HashMap::new();
         for modification in mods.into_iter() {
             match modification {
+                Insert { key, .. } if copied.contains(key) => {
+                    copied_inserts
+                        .entry(key.entity_type.clone())
+                        .or_insert_with(Vec::new)
+                        .push(key);
+                }
                 Insert { key, data } => {
                     inserts
                         .entry(key.entity_type.clone())
@@ -345,9 +504,20 @@ impl DeploymentStore {
         // Apply modification groups.
         // Inserts:
         for (entity_type, mut entities) in inserts.into_iter() {
-            count +=
-                self.insert_entities(&entity_type, &mut entities, conn, layout, ptr, &stopwatch)?
-                    as i32
+            let count =
+                self.insert_entities(&entity_type, &mut entities, conn, layout, ptr, &stopwatch)?;
+            *counts.entry(entity_type).or_default() += count as i32;
+        }
+
+        // Inserts that were already written with `COPY` only need the
+        // checks and the counting that inserting them does
+        for (entity_type, keys) in copied_inserts.into_iter() {
+            let section = stopwatch.start_section("check_interface_entity_uniqueness");
+            for key in keys.iter() {
+                self.check_interface_entity_uniqueness(conn, layout, key)?;
+            }
+            section.end();
+            *counts.entry(entity_type).or_default() += keys.len() as i32;
         }
 
         // Overwrites:
@@ -358,16 +528,17 @@ impl DeploymentStore {
 
         // Removals
         for (entity_type, entity_keys) in removals.into_iter() {
-            count -= self.remove_entities(
+            let count = self.remove_entities(
                 &entity_type,
                 entity_keys.as_slice(),
                 conn,
                 layout,
                 ptr,
                 &stopwatch,
-            )? as i32;
+            )?;
+            *counts.entry(entity_type).or_default() -= count as i32;
         }
-        Ok(count)
+        Ok(counts)
     }
 
     fn insert_entities<'a>(
@@ -629,6 +800,16 @@ impl DeploymentStore {
         deployment::exists_and_synced(&conn, id.as_str())
     }
 
+    /// The number of the head block of `network`; the blocks of `network`
+    /// must be stored in this store's shard
+    pub(crate) fn chain_head_block(
+        &self,
+        network: &str,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        let conn = self.get_conn()?;
+        ChainStore::chain_head_block_with_conn(&conn, network)
+    }
+
     pub(crate) fn deployment_synced(&self, id: &DeploymentHash) -> Result<(), StoreError> {
         let conn = self.get_conn()?;
         conn.transaction(|| deployment::set_synced(&conn, id))
@@ -641,7 +822,7 @@ impl DeploymentStore {
         namespace: &crate::primary::Namespace,
     ) -> Result<(), StoreError> {
         let conn = self.get_conn()?;
-        deployment::drop_schema(&conn, namespace)
+        deployment::drop_schema(&conn, namespace, *DROP_LOCK_TIMEOUT)
     }
 
     // Only used for tests
@@ -652,6 +833,8 @@ impl DeploymentStore {
         // This needs to touch all the tables in the subgraphs schema
         const QUERY: &str = "
         delete from subgraphs.dynamic_ethereum_contract_data_source;
+        delete from subgraphs.scheduled_block_handler;
+        delete from subgraphs.data_source_context;
         delete from subgraphs.subgraph;
         delete from subgraphs.subgraph_deployment;
         delete from subgraphs.subgraph_deployment_assignment;
@@ -695,6 +878,8 @@ impl DeploymentStore {
     }
 
     /// Creates a new index in the specified Entity table if it doesn't already exist.
+    /// The index is rejected with `StoreError::InvalidIndex` if `method`
+    /// can not be used for the fields, see `index::validate`
     ///
     /// This is a potentially time-consuming operation.
     pub(crate) async fn create_manual_index(
@@ -702,7 +887,7 @@ impl DeploymentStore {
         site: Arc<Site>,
         entity_type: EntityType,
         field_names: Vec<String>,
-        index_method: String,
+        method: index::Method,
     ) -> Result<(), StoreError> {
         let store = self.clone();
 
@@ -713,18 +898,19 @@ impl DeploymentStore {
             let table_name = &table.name;
 
             // resolve column names
-            let column_names = field_names
+            let exprs = field_names
                 .iter()
-                .map(|f| table.column_for_field(f).map(|column| column.name.as_str()))
+                .map(|field| index::Expr::parse(table, field))
                 .collect::<Result<Vec<_>, _>>()?;
+            index::validate(method, &exprs)?;
 
-            let column_names_sep_by_underscores = column_names.join("_");
+            let column_names: Vec<_> = exprs.iter().map(|expr| expr.column()).collect();
             let column_names_sep_by_commas = column_names.join(", ");
-            let index_name = format!("manual_{table_name}_{column_names_sep_by_underscores}");
+            let index_name = index::index_name(table_name.as_str(), &exprs);
 
             let sql = format!(
                 "create index concurrently if not exists {index_name} \
-                 on {schema_name}.{table_name} using {index_method} \
+                 on {schema_name}.{table_name} using {method} \
                  ({column_names_sep_by_commas})"
             );
             // This might take a long time.
@@ -746,6 +932,77 @@ impl DeploymentStore {
         })
         .await
     }
+
+    /// Recommend indexes of the deployment that should be dropped or
+    /// created based on how queries have used its tables so far
+    pub(crate) async fn index_advice(
+        &self,
+        site: Arc<Site>,
+    ) -> Result<Vec<IndexAdvice>, StoreError> {
+        let store = self.clone();
+        self.with_conn(move |conn, _| {
+            let layout = store.layout(conn, site)?;
+            index_advice::advise(conn, &layout).map_err(Into::into)
+        })
+        .await
+    }
+
+    /// Prune the history of the deployment so that it can only answer
+    /// queries for blocks from `earliest_block` on. The earliest block is
+    /// recorded before any data is removed so that queries never see
+    /// partially pruned data.
+    ///
+    /// This can take a very long time and is not run in a transaction;
+    /// pruning again with the same `earliest_block` finishes an interrupted
+    /// run
+    pub(crate) fn prune(
+        &self,
+        site: Arc<Site>,
+        earliest_block: BlockNumber,
+        reporter: &mut dyn PruneReporter,
+    ) -> Result<(), StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site.cheap_clone())?;
+        let latest = deployment::block_ptr(&conn, &site.deployment)?
+            .map(|ptr| ptr.number)
+            .unwrap_or(0);
+        if earliest_block > latest {
+            return Err(constraint_violation!(
+                "can not prune deployment {} to block {} since it has only processed block {}",
+                site.deployment,
+                earliest_block,
+                latest
+            ));
+        }
+        deployment::set_earliest_block(&conn, &site, earliest_block)?;
+        layout.prune(&conn, earliest_block, reporter)
+    }
+
+    /// Drops the index `index_name` of the deployment.
+    ///
+    /// The index is dropped concurrently so that queries and indexing of
+    /// the deployment are not blocked while it is being dropped.
+    pub(crate) async fn drop_index(
+        &self,
+        site: Arc<Site>,
+        index_name: String,
+    ) -> Result<(), StoreError> {
+        self.with_conn(move |conn, _| {
+            let schema_name = site.namespace.clone();
+            if !catalog::index_exists(conn, schema_name.as_str(), &index_name)? {
+                return Err(StoreError::Unknown(anyhow!(
+                    "deployment {} has no index named `{}`",
+                    site.deployment,
+                    index_name
+                ))
+                .into());
+            }
+            let sql = format!("drop index concurrently if exists {schema_name}.\"{index_name}\"");
+            conn.execute(&sql)?;
+            Ok(())
+        })
+        .await
+    }
 }
 
 /// Methods that back the trait `graph::components::Store`, but have small
@@ -756,6 +1013,11 @@ impl DeploymentStore {
         Self::block_ptr_with_conn(&site.deployment, &conn)
     }
 
+    pub(crate) fn earliest_block(&self, site: &Site) -> Result<BlockNumber, StoreError> {
+        let conn = self.get_conn()?;
+        deployment::earliest_block(&conn, site)
+    }
+
     pub(crate) fn block_cursor(&self, site: &Site) -> Result<Option<String>, StoreError> {
         let conn = self.get_conn()?;
 
@@ -918,6 +1180,8 @@ impl DeploymentStore {
         mods: &[EntityModification],
         stopwatch: StopwatchMetrics,
         data_sources: &[StoredDynamicDataSource],
+        scheduled: &[ScheduledBlockHandler],
+        contexts: &[DataSourceContextUpdate],
         deterministic_errors: &[SubgraphError],
     ) -> Result<StoreEvent, StoreError> {
         // All operations should apply only to data or metadata for this subgraph
@@ -938,51 +1202,248 @@ impl DeploymentStore {
         };
 
         let event = conn.transaction(|| -> Result<_, StoreError> {
-            // Emit a store event for the changes we are about to make. We
-            // wait with sending it until we have done all our other work
-            // so that we do not hold a lock on the notification queue
-            // for longer than we have to
-            let event: StoreEvent = mods.iter().collect();
-
-            // Make the changes
             let layout = self.layout(&conn, site.clone())?;
-            let section = stopwatch.start_section("apply_entity_modifications");
-            let count = self.apply_entity_modifications(
+            self.transact_block_with_conn(
                 &conn,
+                &site,
                 layout.as_ref(),
+                block_ptr_to,
+                firehose_cursor,
                 mods,
-                &block_ptr_to,
+                &HashSet::new(),
                 stopwatch,
-            )?;
-            deployment::update_entity_count(
-                &conn,
-                site.as_ref(),
-                layout.count_query.as_str(),
-                count,
-            )?;
-            section.end();
+                data_sources,
+                scheduled,
+                contexts,
+                deterministic_errors,
+            )
+        })?;
+
+        Ok(event)
+    }
+
+    /// Write the changes for several consecutive `blocks` in one
+    /// transaction. Each block is written exactly as
+    /// `transact_block_operations` would write it, but the deployment only
+    /// needs one connection and one commit for all of them. With
+    /// `copy_inserts`, new entities are written with `COPY` ahead of
+    /// that transaction where possible; see `copy_inserts`
+    pub(crate) fn transact_block_batch(
+        &self,
+        site: Arc<Site>,
+        blocks: &[PendingBlock],
+        stopwatch: StopwatchMetrics,
+        copy_inserts: bool,
+    ) -> Result<StoreEvent, StoreError> {
+        if blocks
+            .iter()
+            .flat_map(|block| block.mods.iter())
+            .any(|modification| modification.entity_key().subgraph_id != site.deployment)
+        {
+            panic!(
+                "transact_block_batch must affect only entities \
+                 in the subgraph or in the subgraph of subgraphs"
+            );
+        }
 
-            dynds::insert(&conn, &site.deployment, data_sources, &block_ptr_to)?;
+        let copied = if copy_inserts {
+            self.copy_inserts(&site, blocks, &stopwatch)?
+        } else {
+            vec![HashSet::new(); blocks.len()]
+        };
+
+        let conn = {
+            let _section = stopwatch.start_section("transact_blocks_get_conn");
+            self.get_conn()?
+        };
 
-            if !deterministic_errors.is_empty() {
-                deployment::insert_subgraph_errors(
+        let res = conn.transaction(|| -> Result<_, StoreError> {
+            let layout = self.layout(&conn, site.clone())?;
+            let mut event = StoreEvent::new(vec![]);
+            for (block, copied) in blocks.iter().zip(copied.iter()) {
+                event = event.extend(self.transact_block_with_conn(
                     &conn,
-                    &site.deployment,
-                    deterministic_errors,
-                    block_ptr_to.block_number(),
-                )?;
+                    &site,
+                    layout.as_ref(),
+                    &block.block_ptr,
+                    block.firehose_cursor.as_deref(),
+                    &block.mods,
+                    copied,
+                    stopwatch.cheap_clone(),
+                    &block.data_sources,
+                    &block.scheduled_handlers,
+                    &block.data_source_contexts,
+                    &block.deterministic_errors,
+                )?);
             }
+            Ok(event)
+        });
 
-            deployment::forward_block_ptr(&conn, &site.deployment, block_ptr_to)?;
+        if res.is_err() && copied.iter().any(|keys| !keys.is_empty()) {
+            // The blocks will be processed again, and the entities we
+            // copied for them would get in the way of inserting them
+            // again. If this fails, starting the deployment cleans up
+            if let Err(e) = self.remove_versions_after_block_ptr(&conn, site) {
+                warn!(self.logger, "Failed to remove entities copied for blocks that were not written";
+                      "error" => e.to_string());
+            }
+        }
+        res
+    }
 
-            if let Some(cursor) = firehose_cursor {
-                if cursor != "" {
-                    deployment::update_firehose_cursor(&conn, &site.deployment, cursor)?;
+    /// Write the entities that `blocks` insert with `COPY` over a separate
+    /// connection, and return the keys of the copied entities for each
+    /// block. Only inserts of entities that no earlier block in `blocks`
+    /// touches are copied so that the copied versions never overlap with
+    /// versions that writing the blocks changes.
+    ///
+    /// The copied versions are committed right away, but lie beyond the
+    /// block pointer of the deployment until the transaction that writes
+    /// the blocks moves it. If that transaction never commits, they are
+    /// removed by `remove_versions_after_block_ptr`
+    fn copy_inserts(
+        &self,
+        site: &Arc<Site>,
+        blocks: &[PendingBlock],
+        stopwatch: &StopwatchMetrics,
+    ) -> Result<Vec<HashSet<EntityKey>>, StoreError> {
+        use EntityModification::Insert;
+
+        let layout = self.layout(&self.get_conn()?, site.clone())?;
+
+        let mut seen: HashSet<&EntityKey> = HashSet::new();
+        let mut copied = Vec::with_capacity(blocks.len());
+        let mut by_type: HashMap<&EntityType, Vec<_>> = HashMap::new();
+        for block in blocks {
+            let number = block_number(&block.block_ptr);
+            let mut keys = HashSet::new();
+            for modification in &block.mods {
+                if let Insert { key, data } = modification {
+                    if !seen.contains(key) && layout.can_copy_insert(&key.entity_type) {
+                        by_type
+                            .entry(&key.entity_type)
+                            .or_default()
+                            .push((key, data, number));
+                        keys.insert(key.clone());
+                    }
                 }
             }
+            seen.extend(
+                block
+                    .mods
+                    .iter()
+                    .map(|modification| modification.entity_key()),
+            );
+            copied.push(keys);
+        }
+        if by_type.is_empty() {
+            return Ok(copied);
+        }
 
-            Ok(event)
-        })?;
+        let _section = stopwatch.start_section("copy_entity_inserts");
+        let copies = by_type
+            .into_iter()
+            .map(|(entity_type, entities)| layout.copy_insert(entity_type, &entities))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // `postgres::Client` runs its own runtime and can therefore not be
+        // used on a thread that is already running ours
+        let pool = self.pool.clone();
+        let runtime = tokio::runtime::Handle::current();
+        let copy = move || -> Result<(), Error> {
+            let mut client = runtime.block_on(pool.copy_client())?;
+            let mut tx = client.transaction()?;
+            for (statement, data) in copies {
+                let mut writer = tx.copy_in(statement.as_str())?;
+                writer.write_all(&data)?;
+                writer.finish()?;
+            }
+            tx.commit()?;
+            Ok(())
+        };
+        std::thread::Builder::new()
+            .name(format!("copy-{}", site.deployment))
+            .spawn(copy)
+            .map_err(|e| StoreError::Unknown(e.into()))?
+            .join()
+            .map_err(|_| StoreError::Unknown(anyhow!("copying entities panicked")))?
+            .map_err(StoreError::Unknown)?;
+        Ok(copied)
+    }
+
+    /// Remove all entity versions that start after the block pointer of
+    /// the deployment. Only entities that `copy_inserts` wrote for blocks
+    /// that were then not written can be in that state
+    fn remove_versions_after_block_ptr(
+        &self,
+        conn: &PgConnection,
+        site: Arc<Site>,
+    ) -> Result<(), StoreError> {
+        conn.transaction(|| -> Result<(), StoreError> {
+            let block = match Self::block_ptr_with_conn(&site.deployment, conn)? {
+                Some(ptr) => block_number(&ptr) + 1,
+                None => 0,
+            };
+            let layout = self.layout(conn, site.clone())?;
+            layout.revert_block(conn, &site.deployment, block)?;
+            Ok(())
+        })
+    }
+
+    fn transact_block_with_conn(
+        &self,
+        conn: &PgConnection,
+        site: &Arc<Site>,
+        layout: &Layout,
+        block_ptr_to: &BlockPtr,
+        firehose_cursor: Option<&str>,
+        mods: &[EntityModification],
+        copied: &HashSet<EntityKey>,
+        stopwatch: StopwatchMetrics,
+        data_sources: &[StoredDynamicDataSource],
+        scheduled: &[ScheduledBlockHandler],
+        contexts: &[DataSourceContextUpdate],
+        deterministic_errors: &[SubgraphError],
+    ) -> Result<StoreEvent, StoreError> {
+        // Emit a store event for the changes we are about to make. We
+        // wait with sending it until we have done all our other work
+        // so that we do not hold a lock on the notification queue
+        // for longer than we have to
+        let event: StoreEvent = mods.iter().collect();
+
+        // Make the changes
+        let section = stopwatch.start_section("apply_entity_modifications");
+        let counts =
+            self.apply_entity_modifications(conn, layout, mods, &block_ptr_to, stopwatch, copied)?;
+        deployment::update_entity_count(
+            conn,
+            site.as_ref(),
+            layout.count_query.as_str(),
+            counts.values().sum(),
+        )?;
+        layout.update_entity_counts(conn, &counts)?;
+        section.end();
+
+        dynds::insert(conn, &site.deployment, data_sources, &block_ptr_to)?;
+        scheduled_handlers::insert(conn, &site.deployment, scheduled, &block_ptr_to)?;
+        data_source_contexts::insert(conn, &site.deployment, contexts, &block_ptr_to)?;
+
+        if !deterministic_errors.is_empty() {
+            deployment::insert_subgraph_errors(
+                conn,
+                &site.deployment,
+                deterministic_errors,
+                block_ptr_to.block_number(),
+            )?;
+        }
+
+        deployment::forward_block_ptr(conn, &site.deployment, block_ptr_to)?;
+
+        // Blocks that do not come from Firehose have an empty cursor,
+        // which replaces a cursor that is now stale
+        if let Some(cursor) = firehose_cursor {
+            deployment::update_firehose_cursor(conn, &site.deployment, cursor)?;
+        }
 
         Ok(event)
     }
@@ -1030,7 +1491,7 @@ impl DeploymentStore {
             // The revert functions want the number of the first block that we need to get rid of
             let block = block + 1;
 
-            let (event, count) = layout.revert_block(&conn, &site.deployment, block)?;
+            let (event, counts) = layout.revert_block(&conn, &site.deployment, block)?;
 
             // Revert the meta data changes that correspond to this subgraph.
             // Only certain meta data changes need to be reverted, most
@@ -1043,8 +1504,9 @@ impl DeploymentStore {
                 &conn,
                 site.as_ref(),
                 layout.count_query.as_str(),
-                count,
+                counts.values().sum(),
             )?;
+            layout.update_entity_counts(&conn, &counts)?;
             Ok(event)
         })?;
 
@@ -1115,6 +1577,67 @@ impl DeploymentStore {
         Ok(())
     }
 
+    pub(crate) fn add_warnings(
+        &self,
+        site: &Site,
+        warnings: &[SubgraphWarning],
+    ) -> Result<(), StoreError> {
+        if warnings.is_empty() || *MAX_SUBGRAPH_WARNINGS == 0 {
+            return Ok(());
+        }
+        let conn = self.get_conn()?;
+        conn.transaction(|| {
+            deployment::insert_subgraph_warnings(&conn, site, warnings, *MAX_SUBGRAPH_WARNINGS)
+        })
+    }
+
+    /// Write a dump of the deployment `site` into `dir`. The dump is taken
+    /// from a snapshot of the database so that indexing can continue while
+    /// it is written
+    pub(crate) fn dump(&self, site: Arc<Site>, dir: &Path) -> Result<dump::Metadata, StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site)?;
+        conn.build_transaction()
+            .read_only()
+            .repeatable_read()
+            .run(|| dump::dump(&conn, &layout, dir))
+    }
+
+    /// Create the deployment `site` and load the dump in `dir` into it.
+    /// Both happen in one transaction so that a failed restore does not
+    /// leave a partially restored deployment behind
+    pub(crate) fn restore(
+        &self,
+        schema: &Schema,
+        deployment: SubgraphDeploymentEntity,
+        site: Arc<Site>,
+        dir: &Path,
+        metadata: &dump::Metadata,
+    ) -> Result<(), StoreError> {
+        let conn = self.get_conn()?;
+        conn.transaction(|| -> Result<_, StoreError> {
+            if deployment::exists(&conn, &site)? {
+                return Err(constraint_violation!(
+                    "can not restore into deployment {} since it already exists",
+                    site.deployment
+                ));
+            }
+            deployment::create_deployment(&conn, &site, deployment, false, false)?;
+
+            let query = format!("create schema {}", &site.namespace);
+            conn.batch_execute(&query)?;
+            let layout = Layout::create_relational_schema(&conn, site.clone(), schema)?;
+
+            dump::restore(&conn, &layout, dir, metadata)
+        })
+    }
+
+    /// Run SQL that manages the reader role of a deployment in this shard
+    pub(crate) fn execute_reader_sql(&self, sql: &str) -> Result<(), StoreError> {
+        let conn = self.get_conn()?;
+        reader::execute(&conn, sql)
+    }
+
     pub(crate) fn replica_for_query(
         &self,
         for_subscription: bool,
@@ -1137,6 +1660,60 @@ impl DeploymentStore {
         Ok(replica_id)
     }
 
+    /// Pick the replica for a query with estimated cost `cost`. If routing
+    /// by cost is turned on, cheap queries go to the main database and
+    /// expensive ones to a read replica that is not lagging too far behind,
+    /// picked according to the replica weights. If all replicas are
+    /// lagging, the query goes to the main database
+    pub(crate) fn replica_for_cost(&self, cost: u64) -> Result<ReplicaId, StoreError> {
+        use std::sync::atomic::Ordering;
+
+        let min_cost = match *REPLICA_MIN_COST {
+            Some(min_cost) => min_cost,
+            None => return self.replica_for_query(false),
+        };
+        let start = self.conn_round_robin_counter.fetch_add(1, Ordering::SeqCst);
+        Ok(route_by_cost(
+            cost,
+            min_cost,
+            &self.replica_order,
+            start,
+            |idx| self.replica_is_lagging(idx),
+        ))
+    }
+
+    /// Check whether the read replica `idx` lags more than
+    /// `GRAPH_STORE_REPLICA_MAX_LAG` behind the main database. The result
+    /// is cached for `REPLICA_LAG_CHECK_INTERVAL`. Replicas that we can not
+    /// check are considered lagging
+    fn replica_is_lagging(&self, idx: usize) -> bool {
+        let mut lag = self.replica_lag[idx].lock().unwrap();
+        if let Some(checked_at) = lag.checked_at {
+            if checked_at.elapsed() < REPLICA_LAG_CHECK_INTERVAL {
+                return lag.lagging;
+            }
+        }
+
+        let lagging = match self
+            .read_only_conn(idx)
+            .and_then(|conn| catalog::replication_lag(&conn).map_err(Error::from))
+        {
+            Ok(duration) => duration > *REPLICA_MAX_LAG,
+            Err(e) => {
+                warn!(self.logger, "Failed to check replication lag";
+                      "replica" => idx, "error" => e.to_string());
+                true
+            }
+        };
+        if lagging != lag.lagging {
+            info!(self.logger, "Read replica lag changed";
+                  "replica" => idx, "lagging" => lagging);
+        }
+        lag.checked_at = Some(Instant::now());
+        lag.lagging = lagging;
+        lagging
+    }
+
     pub(crate) async fn load_dynamic_data_sources(
         &self,
         id: DeploymentHash,
@@ -1148,6 +1725,30 @@ impl DeploymentStore {
         .await
     }
 
+    pub(crate) async fn load_scheduled_block_handlers(
+        &self,
+        site: Arc<Site>,
+    ) -> Result<Vec<ScheduledBlockHandler>, StoreError> {
+        self.with_conn(move |conn, _| {
+            conn.transaction(|| {
+                let after = deployment::block_ptr(&conn, &site.deployment)?.map(|ptr| ptr.number);
+                scheduled_handlers::load(&conn, site.deployment.as_str(), after)
+            })
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    pub(crate) async fn load_data_source_contexts(
+        &self,
+        site: Arc<Site>,
+    ) -> Result<Vec<DataSourceContextUpdate>, StoreError> {
+        self.with_conn(move |conn, _| {
+            data_source_contexts::load(&conn, site.deployment.as_str()).map_err(Into::into)
+        })
+        .await
+    }
+
     pub(crate) async fn exists_and_synced(&self, id: DeploymentHash) -> Result<bool, StoreError> {
         self.with_conn(move |conn, _| {
             conn.transaction(|| deployment::exists_and_synced(&conn, &id))
@@ -1216,6 +1817,16 @@ impl DeploymentStore {
                 info!(logger, "Copied {} dynamic data sources", count;
                       "time_ms" => start.elapsed().as_millis());
 
+                let start = Instant::now();
+                let count = scheduled_handlers::copy(&conn, &src.site, &dst.site, &block)?;
+                info!(logger, "Copied {} scheduled block handlers", count;
+                      "time_ms" => start.elapsed().as_millis());
+
+                let start = Instant::now();
+                let count = data_source_contexts::copy(&conn, &src.site, &dst.site, &block)?;
+                info!(logger, "Copied {} data source contexts", count;
+                      "time_ms" => start.elapsed().as_millis());
+
                 // Copy errors across
                 let start = Instant::now();
                 let count = deployment::copy_errors(&conn, &src.site, &dst.site, &block)?;
@@ -1223,6 +1834,7 @@ impl DeploymentStore {
                       "time_ms" => start.elapsed().as_millis());
 
                 catalog::copy_account_like(&conn, &src.site, &dst.site)?;
+                deployment::copy_earliest_block(&conn, &src.site, &dst.site)?;
 
                 // Rewind the subgraph so that entity versions that are
                 // clamped in the future (beyond `block`) become valid for
@@ -1239,6 +1851,7 @@ impl DeploymentStore {
 
                 let start = Instant::now();
                 deployment::set_entity_count(&conn, &dst.site, &dst.count_query)?;
+                dst.recount_entities(&conn, &[])?;
                 info!(logger, "Counted the entities";
                       "time_ms" => start.elapsed().as_millis());
 
@@ -1249,6 +1862,12 @@ impl DeploymentStore {
                     "time_ms" => start.elapsed().as_millis());
                 Ok(())
             })?;
+        } else if *COPY_INSERTS {
+            // Entities that were copied for blocks that were never written
+            // because the node stopped in between lie beyond the block
+            // pointer and have to go before we process those blocks again
+            let conn = self.get_conn()?;
+            self.remove_versions_after_block_ptr(&conn, dst.site.clone())?;
         }
         Ok(())
     }
@@ -1427,6 +2046,127 @@ impl DeploymentStore {
         deployment::error_count(&conn, id)
     }
 
+    /// Make sure that each account-like table of the deployment has a
+    /// partial index that only covers entity versions that were still
+    /// current at a recent block, and drop such indexes that have become
+    /// stale.
+    ///
+    /// The index starts at a block that is between `window` and
+    /// `2 * window` blocks behind the deployment head and only moves every
+    /// `window` blocks so that indexes are not recreated constantly. The
+    /// condition of the index is the one that `BlockRangeContainsClause`
+    /// adds for account-like tables, which lets Postgres use the much
+    /// smaller partial index for queries at recent blocks
+    pub(crate) async fn refresh_hot_indexes(
+        &self,
+        logger: &Logger,
+        site: Arc<Site>,
+        window: BlockNumber,
+    ) -> Result<(), StoreError> {
+        let logger = logger.cheap_clone();
+        self.with_conn(move |conn, _| {
+            let head = match deployment::block_ptr(conn, &site.deployment)? {
+                Some(ptr) => ptr.number,
+                None => return Ok(()),
+            };
+            let start = (head - window).max(0) / window * window;
+            let nsp = site.namespace.as_str();
+            let account_like = catalog::account_like(conn, &site)?;
+            let existing = catalog::hot_indexes(conn, nsp)?;
+
+            let drop_index = |index: &str| -> Result<(), StoreError> {
+                conn.execute(&format!(
+                    "drop index concurrently if exists {nsp}.\"{index}\""
+                ))?;
+                Ok(())
+            };
+
+            for table in &account_like {
+                let name = hot_index_name(table, start);
+                if !catalog::check_index_is_valid(conn, nsp, &name)? {
+                    // Remove what is left over from a failed attempt
+                    drop_index(&name)?;
+                    let sql = format!(
+                        "create index concurrently {name} \
+                           on {nsp}.\"{table}\"({PRIMARY_KEY_COLUMN}) \
+                         where coalesce(upper({BLOCK_RANGE_COLUMN}), {BLOCK_NUMBER_MAX}) > {start}"
+                    );
+                    // This might take a long time.
+                    conn.execute(&sql)?;
+                    if !catalog::check_index_is_valid(conn, nsp, &name)? {
+                        drop_index(&name)?;
+                        warn!(logger, "Creating hot block range index failed";
+                              "deployment" => site.deployment.as_str(),
+                              "index" => &name);
+                        continue;
+                    }
+                    info!(logger, "Created hot block range index";
+                          "deployment" => site.deployment.as_str(),
+                          "index" => &name);
+                }
+                for (_, index) in existing
+                    .iter()
+                    .filter(|(other, index)| other == table && index != &name)
+                {
+                    drop_index(index)?;
+                }
+            }
+            // Tables that are no longer account-like do not need their
+            // hot indexes anymore
+            for (_, index) in existing
+                .iter()
+                .filter(|(table, _)| !account_like.contains(table))
+            {
+                drop_index(index)?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Check the entity counts that the write path maintains for each
+    /// table of `site` against the number of rows Postgres estimates the
+    /// table has, and count the entities in tables whose count is unknown
+    /// or can not be right from scratch. A table can never have more
+    /// current entities than rows, but since the estimate is only updated
+    /// when the table is analyzed, we allow for some slack
+    pub(crate) async fn reconcile_entity_counts(
+        &self,
+        logger: &Logger,
+        site: Arc<Site>,
+    ) -> Result<(), StoreError> {
+        fn has_drifted(check: &catalog::EntityCountCheck) -> bool {
+            match check.entity_count {
+                None => true,
+                Some(count) if count < 0 => true,
+                Some(count) => {
+                    check.estimate >= 0 && count > check.estimate + (check.estimate / 10).max(1000)
+                }
+            }
+        }
+
+        let store = self.clone();
+        let logger = logger.cheap_clone();
+        self.with_conn(move |conn, _| {
+            let layout = store.layout(conn, site.cheap_clone())?;
+            let checks = catalog::entity_count_checks(conn, &site)?;
+            for check in checks.iter().filter(|check| has_drifted(check)) {
+                let name = SqlName::verbatim(check.table_name.clone());
+                if layout.table(&name).is_none() {
+                    continue;
+                }
+                conn.transaction(|| layout.recount_entities(conn, &[&name]))?;
+                info!(logger, "Recounted entities";
+                      "deployment" => site.deployment.as_str(),
+                      "table" => &check.table_name,
+                      "previous_count" => check.entity_count,
+                      "estimate" => check.estimate);
+            }
+            Ok(())
+        })
+        .await
+    }
+
     pub(crate) async fn mirror_primary_tables(&self, logger: &Logger) {
         self.pool.mirror_primary_tables().await.unwrap_or_else(|e| {
             warn!(logger, "Mirroring primary tables failed. We will try again in a few minutes";
@@ -1444,3 +2184,98 @@ impl DeploymentStore {
             .await
     }
 }
+
+/// The name of the hot block range index for `table` that covers the
+/// entity versions that were current at `start` or later. Postgres
+/// truncates identifiers to 63 bytes, and we need to know the name it
+/// actually uses
+fn hot_index_name(table: &str, start: BlockNumber) -> String {
+    let mut name = format!("hot_{}_{}", start, table);
+    name.truncate(63);
+    name
+}
+
+/// The replica for a query with estimated cost `cost` when queries that
+/// cost at least `min_cost` go to read replicas. Replicas are tried in
+/// `replica_order`, beginning at `start`, and the first one for which
+/// `is_lagging` is false is picked. If there is none, the query goes to the
+/// main database
+fn route_by_cost(
+    cost: u64,
+    min_cost: u64,
+    replica_order: &[ReplicaId],
+    start: usize,
+    mut is_lagging: impl FnMut(usize) -> bool,
+) -> ReplicaId {
+    if cost < min_cost {
+        return ReplicaId::Main;
+    }
+
+    let weights_count = replica_order.len();
+    for i in 0..weights_count {
+        if let ReplicaId::ReadOnly(idx) = replica_order[(start + i) % weights_count] {
+            if !is_lagging(idx) {
+                return ReplicaId::ReadOnly(idx);
+            }
+        }
+    }
+    ReplicaId::Main
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDER: [ReplicaId; 3] = [
+        ReplicaId::Main,
+        ReplicaId::ReadOnly(0),
+        ReplicaId::ReadOnly(1),
+    ];
+
+    #[test]
+    fn cheap_queries_go_to_main() {
+        let replica = route_by_cost(99, 100, &ORDER, 1, |_| {
+            panic!("cheap queries do not check the replica lag")
+        });
+        assert_eq!(ReplicaId::Main, replica);
+    }
+
+    #[test]
+    fn expensive_queries_go_to_replicas() {
+        assert_eq!(
+            ReplicaId::ReadOnly(0),
+            route_by_cost(100, 100, &ORDER, 1, |_| false)
+        );
+        assert_eq!(
+            ReplicaId::ReadOnly(1),
+            route_by_cost(100, 100, &ORDER, 2, |_| false)
+        );
+        // The main database is skipped even when it is next in line
+        assert_eq!(
+            ReplicaId::ReadOnly(0),
+            route_by_cost(1000, 100, &ORDER, 3, |_| false)
+        );
+    }
+
+    #[test]
+    fn lagging_replicas_are_skipped() {
+        assert_eq!(
+            ReplicaId::ReadOnly(1),
+            route_by_cost(100, 100, &ORDER, 1, |idx| idx == 0)
+        );
+
+        let mut checked = Vec::new();
+        let replica = route_by_cost(100, 100, &ORDER, 1, |idx| {
+            checked.push(idx);
+            true
+        });
+        assert_eq!(ReplicaId::Main, replica);
+        assert_eq!(vec![0, 1], checked);
+
+        // Without read replicas, everything goes to the main database
+        assert_eq!(
+            ReplicaId::Main,
+            route_by_cost(100, 100, &[ReplicaId::Main], 0, |_| false)
+        );
+    }
+}
@@ -2,11 +2,12 @@
 #![allow(unused_macros)]
 
 //! Queries to support the index node API
+use crate::catalog;
 use crate::primary::Site;
 use crate::{
     deployment::{
         get_fatal_error_id, graph_node_versions, subgraph_deployment, subgraph_error,
-        subgraph_manifest, SubgraphHealth as HealthType,
+        subgraph_manifest, subgraph_warnings, SubgraphHealth as HealthType,
     },
     primary::DeploymentId,
 };
@@ -26,7 +27,7 @@ use graph::{
     },
 };
 use graph::{data::subgraph::status, prelude::web3::types::H256};
-use std::convert::TryFrom;
+use std::{collections::BTreeMap, convert::TryFrom};
 use std::{ops::Bound, sync::Arc};
 
 git_testament_macros!(version);
@@ -236,7 +237,9 @@ impl<'a> TryFrom<DetailAndError<'a>> for status::Info {
             )
         })?;
         let fatal_error = error.map(|e| SubgraphError::try_from(e)).transpose()?;
-        // 'node' needs to be filled in later from a different shard
+        // 'node' and 'labels' need to be filled in later from a different
+        // shard, 'warnings' and 'entity_counts' from different tables, and
+        // 'writes' and 'memory' from the deployment's writable store
         Ok(status::Info {
             id: id.into(),
             subgraph: deployment,
@@ -244,9 +247,14 @@ impl<'a> TryFrom<DetailAndError<'a>> for status::Info {
             health,
             fatal_error,
             non_fatal_errors: vec![],
+            warnings: vec![],
             chains: vec![chain],
             entity_count,
+            entity_counts: BTreeMap::new(),
             node: None,
+            labels: BTreeMap::new(),
+            writes: None,
+            memory: None,
         })
     }
 }
@@ -277,13 +285,13 @@ pub(crate) fn deployment_statuses(
     use subgraph_error as e;
 
     // Empty deployments means 'all of them'
-    if sites.is_empty() {
+    let mut infos = if sites.is_empty() {
         d::table
             .left_outer_join(e::table.on(d::fatal_error.eq(e::id.nullable())))
             .load::<(DeploymentDetail, Option<ErrorDetail>)>(conn)?
             .into_iter()
             .map(|(detail, error)| status::Info::try_from(DetailAndError(detail, error, sites)))
-            .collect()
+            .collect::<Result<Vec<_>, _>>()?
     } else {
         let ids: Vec<_> = sites.into_iter().map(|site| site.id).collect();
 
@@ -293,8 +301,21 @@ pub(crate) fn deployment_statuses(
             .load::<(DeploymentDetail, Option<ErrorDetail>)>(conn)?
             .into_iter()
             .map(|(detail, error)| status::Info::try_from(DetailAndError(detail, error, sites)))
-            .collect()
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let ids: Vec<DeploymentId> = infos.iter().map(|info| info.id.into()).collect();
+    let mut warnings = subgraph_warnings(conn, &ids)?;
+    let mut entity_counts = catalog::entity_counts(conn, &ids)?;
+    for info in &mut infos {
+        info.warnings = warnings
+            .remove(&DeploymentId::from(info.id))
+            .unwrap_or_default();
+        info.entity_counts = entity_counts
+            .remove(&DeploymentId::from(info.id))
+            .unwrap_or_default();
     }
+    Ok(infos)
 }
 
 #[derive(Queryable, QueryableByName, Identifiable, Associations)]
@@ -0,0 +1,380 @@
+//! Logical backups of individual deployments
+//!
+//! A dump of a deployment is a directory that contains a `deployment.json`
+//! file with the deployment's metadata, including its block pointer and
+//! Firehose cursor, one file with the rows of each of the deployment's
+//! tables, and one file with its dynamic data sources. Rows are stored as
+//! JSON, one row per line. All of this is read in one transaction so that
+//! the dump is a consistent snapshot of the deployment.
+//!
+//! A dump can be restored into any shard, which recreates the deployment
+//! exactly as it was when the dump was taken.
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use diesel::{
+    connection::SimpleConnection,
+    pg::PgConnection,
+    sql_query,
+    sql_types::{BigInt, Text},
+    RunQueryDsl,
+};
+use graph::{
+    data::subgraph::schema::{SubgraphHealth, SubgraphManifestEntity},
+    prelude::{
+        anyhow, serde_json, web3::types::H256, BlockNumber, BlockPtr, DeploymentHash, Deserialize,
+        Schema, Serialize, StoreError, SubgraphDeploymentEntity,
+    },
+};
+
+use crate::{deployment, detail, relational::Layout};
+
+/// The version of the format of dumps. Restoring refuses dumps with a
+/// different version
+const FORMAT_VERSION: u32 = 1;
+
+const METADATA_FILE: &str = "deployment.json";
+const DATA_SOURCES_FILE: &str = "data_sources.jsonl";
+
+/// How many rows to read or write at once
+const BATCH_SIZE: i64 = 10_000;
+
+#[derive(Serialize, Deserialize)]
+pub struct DumpBlock {
+    pub hash: String,
+    pub number: BlockNumber,
+}
+
+impl From<&BlockPtr> for DumpBlock {
+    fn from(ptr: &BlockPtr) -> Self {
+        DumpBlock {
+            hash: ptr.hash_hex(),
+            number: ptr.number,
+        }
+    }
+}
+
+impl DumpBlock {
+    fn to_ptr(&self) -> Result<BlockPtr, StoreError> {
+        let hash = self.hash.trim_start_matches("0x");
+        let hash = hex::decode(hash)
+            .map_err(|e| anyhow!("invalid block hash `{}` in dump: {}", self.hash, e))?;
+        Ok(BlockPtr::from((H256::from_slice(&hash), self.number)))
+    }
+}
+
+/// The contents of `deployment.json`
+#[derive(Serialize, Deserialize)]
+pub struct Metadata {
+    pub version: u32,
+    pub deployment: String,
+    pub network: String,
+    pub spec_version: String,
+    pub description: Option<String>,
+    pub repository: Option<String>,
+    pub features: Vec<String>,
+    /// The GraphQL schema of the deployment
+    pub schema: String,
+    pub earliest_block: Option<DumpBlock>,
+    pub latest_block: Option<DumpBlock>,
+    pub firehose_cursor: Option<String>,
+    /// The names of the tables that were dumped
+    pub tables: Vec<String>,
+}
+
+impl Metadata {
+    pub fn read(dir: &Path) -> Result<Self, StoreError> {
+        let path = dir.join(METADATA_FILE);
+        let file = File::open(&path).map_err(|e| io_error(&path, e))?;
+        let metadata: Metadata = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| anyhow!("invalid metadata in {}: {}", path.display(), e))?;
+        if metadata.version != FORMAT_VERSION {
+            return Err(StoreError::Unknown(anyhow!(
+                "the dump in {} has version {} but only version {} is supported",
+                dir.display(),
+                metadata.version,
+                FORMAT_VERSION
+            )));
+        }
+        Ok(metadata)
+    }
+
+    pub fn deployment_hash(&self) -> Result<DeploymentHash, StoreError> {
+        DeploymentHash::new(self.deployment.clone())
+            .map_err(|id| anyhow!("invalid deployment id `{}` in dump", id).into())
+    }
+
+    pub fn schema(&self) -> Result<Schema, StoreError> {
+        Schema::parse(&self.schema, self.deployment_hash()?).map_err(StoreError::from)
+    }
+
+    /// The metadata for a deployment that is restored from this dump. The
+    /// block pointer is only set once all data has been restored
+    pub fn deployment_entity(&self) -> Result<SubgraphDeploymentEntity, StoreError> {
+        Ok(SubgraphDeploymentEntity {
+            manifest: SubgraphManifestEntity {
+                spec_version: self.spec_version.clone(),
+                description: self.description.clone(),
+                repository: self.repository.clone(),
+                features: self.features.clone(),
+                schema: self.schema.clone(),
+            },
+            failed: false,
+            health: SubgraphHealth::Healthy,
+            synced: false,
+            fatal_error: None,
+            non_fatal_errors: vec![],
+            earliest_block: self
+                .earliest_block
+                .as_ref()
+                .map(DumpBlock::to_ptr)
+                .transpose()?,
+            latest_block: None,
+            graft_base: None,
+            graft_block: None,
+            reorg_count: 0,
+            current_reorg_depth: 0,
+            max_reorg_depth: 0,
+        })
+    }
+}
+
+fn io_error(path: &Path, e: io::Error) -> StoreError {
+    StoreError::Unknown(anyhow!("{}: {}", path.display(), e))
+}
+
+fn table_file(dir: &Path, table: &str) -> PathBuf {
+    dir.join(format!("{}.jsonl", table))
+}
+
+/// The names of all tables of `layout`, including join tables
+fn table_names(layout: &Layout) -> Vec<(String, String)> {
+    let nsp = &layout.catalog.site.namespace;
+    let mut names: Vec<_> = layout
+        .tables
+        .values()
+        .flat_map(|table| {
+            std::iter::once(&table.name).chain(table.relations.iter().map(|rel| &rel.name))
+        })
+        .map(|name| (name.to_string(), format!("{}.{}", nsp, name.quoted())))
+        .collect();
+    names.sort();
+    names
+}
+
+#[derive(QueryableByName)]
+struct JsonRow {
+    #[sql_type = "BigInt"]
+    vid: i64,
+    #[sql_type = "Text"]
+    data: String,
+}
+
+/// Write the rows that `query` returns to `path`. The query must select
+/// `vid` and `data` and take the last `vid` that was read and the batch
+/// size as parameters
+fn dump_rows(
+    conn: &PgConnection,
+    query: &str,
+    deployment: Option<&str>,
+    path: &Path,
+) -> Result<usize, StoreError> {
+    let file = File::create(path).map_err(|e| io_error(path, e))?;
+    let mut out = BufWriter::new(file);
+    let mut last_vid = -1;
+    let mut count = 0;
+    loop {
+        let rows: Vec<JsonRow> = match deployment {
+            Some(deployment) => sql_query(query)
+                .bind::<BigInt, _>(last_vid)
+                .bind::<BigInt, _>(BATCH_SIZE)
+                .bind::<Text, _>(deployment)
+                .load(conn)?,
+            None => sql_query(query)
+                .bind::<BigInt, _>(last_vid)
+                .bind::<BigInt, _>(BATCH_SIZE)
+                .load(conn)?,
+        };
+        for row in &rows {
+            writeln!(out, "{}", row.data).map_err(|e| io_error(path, e))?;
+        }
+        count += rows.len();
+        match rows.last() {
+            Some(row) => last_vid = row.vid,
+            None => break,
+        }
+    }
+    out.flush().map_err(|e| io_error(path, e))?;
+    Ok(count)
+}
+
+/// Read `path` and call `f` with batches of the rows in it, formatted as
+/// a JSON array
+fn restore_rows(
+    path: &Path,
+    mut f: impl FnMut(&str) -> Result<(), StoreError>,
+) -> Result<usize, StoreError> {
+    let file = File::open(path).map_err(|e| io_error(path, e))?;
+    let mut batch = String::new();
+    let mut batch_len = 0;
+    let mut count = 0;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| io_error(path, e))?;
+        batch.push(if batch_len == 0 { '[' } else { ',' });
+        batch.push_str(&line);
+        batch_len += 1;
+        count += 1;
+        if batch_len == BATCH_SIZE {
+            batch.push(']');
+            f(&batch)?;
+            batch.clear();
+            batch_len = 0;
+        }
+    }
+    if batch_len > 0 {
+        batch.push(']');
+        f(&batch)?;
+    }
+    Ok(count)
+}
+
+/// Write a dump of the deployment with `layout` into `dir`. This must be
+/// called inside a transaction that is at least `repeatable read` so that
+/// all the data comes from the same snapshot
+pub(crate) fn dump(
+    conn: &PgConnection,
+    layout: &Layout,
+    dir: &Path,
+) -> Result<Metadata, StoreError> {
+    let site = layout.site.as_ref();
+    let entity = detail::deployment_entity(conn, site)?;
+    if entity.failed {
+        return Err(StoreError::Unknown(anyhow!(
+            "can not dump deployment {} because it has failed",
+            site.deployment
+        )));
+    }
+    let firehose_cursor = deployment::get_subgraph_firehose_cursor(conn, &site.deployment)?;
+
+    fs::create_dir_all(dir).map_err(|e| io_error(dir, e))?;
+
+    let mut tables = Vec::new();
+    for (name, qualified_name) in table_names(layout) {
+        let query = format!(
+            "select vid, row_to_json(t)::text as data \
+               from {} t where vid > $1 order by vid limit $2",
+            qualified_name
+        );
+        dump_rows(conn, &query, None, &table_file(dir, &name))?;
+        tables.push(name);
+    }
+
+    let query = "select vid, row_to_json(e)::text as data \
+                   from subgraphs.dynamic_ethereum_contract_data_source e \
+                  where vid > $1 and deployment = $3 order by vid limit $2";
+    dump_rows(
+        conn,
+        query,
+        Some(site.deployment.as_str()),
+        &dir.join(DATA_SOURCES_FILE),
+    )?;
+
+    let SubgraphManifestEntity {
+        spec_version,
+        description,
+        repository,
+        features,
+        schema,
+    } = entity.manifest;
+    let metadata = Metadata {
+        version: FORMAT_VERSION,
+        deployment: site.deployment.to_string(),
+        network: site.network.clone(),
+        spec_version,
+        description,
+        repository,
+        features,
+        schema,
+        earliest_block: entity.earliest_block.as_ref().map(DumpBlock::from),
+        latest_block: entity.latest_block.as_ref().map(DumpBlock::from),
+        firehose_cursor,
+        tables,
+    };
+
+    // Write the metadata last so that an incomplete dump can not be
+    // restored
+    let path = dir.join(METADATA_FILE);
+    let file = File::create(&path).map_err(|e| io_error(&path, e))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &metadata)
+        .map_err(|e| anyhow!("failed to write {}: {}", path.display(), e))?;
+    Ok(metadata)
+}
+
+/// Load the data from the dump in `dir` into the freshly created
+/// deployment with `layout` and set its block pointer and cursor to what
+/// they were when the dump was taken
+pub(crate) fn restore(
+    conn: &PgConnection,
+    layout: &Layout,
+    dir: &Path,
+    metadata: &Metadata,
+) -> Result<(), StoreError> {
+    let site = layout.site.as_ref();
+    let names = table_names(layout);
+
+    for table in &metadata.tables {
+        let qualified_name = names
+            .iter()
+            .find(|(name, _)| name == table)
+            .map(|(_, qualified_name)| qualified_name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "the dump contains table `{}` which the schema does not have",
+                    table
+                )
+            })?;
+        let query = format!(
+            "insert into {table} select * from json_populate_recordset(null::{table}, $1::json)",
+            table = qualified_name
+        );
+        restore_rows(&table_file(dir, table), |rows| {
+            sql_query(&query).bind::<Text, _>(rows).execute(conn)?;
+            Ok(())
+        })?;
+
+        // We inserted explicit vids; make sure that new rows do not
+        // collide with them
+        let query = format!(
+            "select setval(pg_get_serial_sequence('{table}', 'vid'), \
+                           coalesce(max(vid), 0) + 1, false) from {table}",
+            table = qualified_name
+        );
+        conn.batch_execute(&query)?;
+    }
+
+    let query = "insert into subgraphs.dynamic_ethereum_contract_data_source(name, \
+                        address, abi, start_block, ethereum_block_hash, \
+                        ethereum_block_number, deployment, context) \
+                 select e.name, e.address, e.abi, e.start_block, \
+                        e.ethereum_block_hash, e.ethereum_block_number, $2, e.context \
+                   from json_populate_recordset(\
+                          null::subgraphs.dynamic_ethereum_contract_data_source, $1::json) e";
+    restore_rows(&dir.join(DATA_SOURCES_FILE), |rows| {
+        sql_query(query)
+            .bind::<Text, _>(rows)
+            .bind::<Text, _>(site.deployment.as_str())
+            .execute(conn)?;
+        Ok(())
+    })?;
+
+    deployment::set_entity_count(conn, site, &layout.count_query)?;
+    layout.recount_entities(conn, &[])?;
+    if let Some(block) = &metadata.latest_block {
+        deployment::forward_block_ptr(conn, &site.deployment, &block.to_ptr()?)?;
+    }
+    if let Some(cursor) = &metadata.firehose_cursor {
+        deployment::update_firehose_cursor(conn, &site.deployment, cursor)?;
+    }
+    Ok(())
+}
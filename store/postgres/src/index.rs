@@ -0,0 +1,216 @@
+//! Indexes that operators create manually, for example with `graphman
+//! index create`, in addition to the ones that `graph-node` creates for a
+//! deployment. The names of such indexes start with `manual_` so that they
+//! can be told apart from the indexes `graph-node` manages.
+//!
+//! Not every index method makes sense for every kind of column; the
+//! combinations that Postgres would reject or that can not help queries
+//! are rejected before we ask the database to build the index
+use std::fmt;
+use std::str::FromStr;
+
+use graph::prelude::StoreError;
+
+use crate::block_range::BLOCK_RANGE_COLUMN;
+use crate::relational::Table;
+
+/// The prefix of the names of all indexes that were created manually
+pub(crate) const MANUAL_INDEX_PREFIX: &str = "manual_";
+
+/// The index methods that Postgres supports
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    BTree,
+    Hash,
+    Gist,
+    SpGist,
+    Gin,
+    Brin,
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::BTree => "btree",
+            Method::Hash => "hash",
+            Method::Gist => "gist",
+            Method::SpGist => "spgist",
+            Method::Gin => "gin",
+            Method::Brin => "brin",
+        }
+    }
+}
+
+impl FromStr for Method {
+    type Err = StoreError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "btree" => Ok(Method::BTree),
+            "hash" => Ok(Method::Hash),
+            "gist" => Ok(Method::Gist),
+            "spgist" => Ok(Method::SpGist),
+            "gin" => Ok(Method::Gin),
+            "brin" => Ok(Method::Brin),
+            _ => Err(StoreError::InvalidIndex(format!(
+                "unknown index method `{}`",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// One of the expressions an index is built on
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    /// The column with the given name, which holds scalar values
+    Column(String),
+    /// The column with the given name, which holds lists of values
+    List(String),
+    /// The column with the given name, which holds the `tsvector` for a
+    /// fulltext search
+    Fulltext(String),
+    /// The block range of entity versions
+    BlockRange,
+}
+
+impl Expr {
+    /// Resolve `field` of the entity stored in `table`. The name
+    /// `block_range` refers to the block range of entity versions
+    pub(crate) fn parse(table: &Table, field: &str) -> Result<Expr, StoreError> {
+        if field == BLOCK_RANGE_COLUMN {
+            return Ok(Expr::BlockRange);
+        }
+        let column = table.column_for_field(field)?;
+        let name = column.name.as_str().to_string();
+        if column.is_fulltext() {
+            Ok(Expr::Fulltext(name))
+        } else if column.is_list() {
+            Ok(Expr::List(name))
+        } else {
+            Ok(Expr::Column(name))
+        }
+    }
+
+    pub fn column(&self) -> &str {
+        match self {
+            Expr::Column(name) | Expr::List(name) | Expr::Fulltext(name) => name.as_str(),
+            Expr::BlockRange => BLOCK_RANGE_COLUMN,
+        }
+    }
+}
+
+/// Check that an index using `method` can be built on `exprs`
+pub(crate) fn validate(method: Method, exprs: &[Expr]) -> Result<(), StoreError> {
+    let invalid = |msg: String| Err(StoreError::InvalidIndex(msg));
+
+    if exprs.is_empty() {
+        return invalid("an index needs at least one field".to_string());
+    }
+
+    match method {
+        Method::Gin => {
+            if let Some(expr) = exprs
+                .iter()
+                .find(|expr| !matches!(expr, Expr::List(_) | Expr::Fulltext(_)))
+            {
+                return invalid(format!(
+                    "gin indexes can only be built on list or fulltext fields, but `{}` is neither",
+                    expr.column()
+                ));
+            }
+        }
+        Method::Gist => {
+            if !exprs.contains(&Expr::BlockRange) {
+                return invalid(format!(
+                    "gist indexes must include `{}`",
+                    BLOCK_RANGE_COLUMN
+                ));
+            }
+            if let Some(expr) = exprs.iter().find(|expr| matches!(expr, Expr::List(_))) {
+                return invalid(format!(
+                    "gist indexes can not be built on the list field `{}`",
+                    expr.column()
+                ));
+            }
+        }
+        Method::SpGist | Method::Hash => {
+            if exprs.len() != 1 {
+                return invalid(format!(
+                    "{} indexes can only be built on a single field",
+                    method
+                ));
+            }
+        }
+        Method::BTree | Method::Brin => {
+            if let Some(expr) = exprs
+                .iter()
+                .find(|expr| matches!(expr, Expr::List(_) | Expr::Fulltext(_)))
+            {
+                return invalid(format!(
+                    "{} indexes can not be built on the list or fulltext field `{}`, use gin instead",
+                    method,
+                    expr.column()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The name of the manual index on `exprs` of the table `table_name`
+pub(crate) fn index_name(table_name: &str, exprs: &[Expr]) -> String {
+    let columns: Vec<_> = exprs.iter().map(|expr| expr.column()).collect();
+    format!(
+        "{}{}_{}",
+        MANUAL_INDEX_PREFIX,
+        table_name,
+        columns.join("_")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate, Expr, Method};
+
+    fn col(name: &str) -> Expr {
+        Expr::Column(name.to_string())
+    }
+
+    fn list(name: &str) -> Expr {
+        Expr::List(name.to_string())
+    }
+
+    #[test]
+    fn validate_index_methods() {
+        assert!(validate(Method::BTree, &[col("owner"), Expr::BlockRange]).is_ok());
+        assert!(validate(Method::BTree, &[list("tags")]).is_err());
+        assert!(validate(Method::BTree, &[]).is_err());
+
+        assert!(validate(Method::Gin, &[list("tags")]).is_ok());
+        assert!(validate(Method::Gin, &[Expr::Fulltext("search".to_string())]).is_ok());
+        assert!(validate(Method::Gin, &[col("owner")]).is_err());
+
+        assert!(validate(Method::Gist, &[col("owner"), Expr::BlockRange]).is_ok());
+        assert!(validate(Method::Gist, &[col("owner")]).is_err());
+        assert!(validate(Method::Gist, &[list("tags"), Expr::BlockRange]).is_err());
+
+        assert!(validate(Method::Hash, &[col("owner")]).is_ok());
+        assert!(validate(Method::Hash, &[col("owner"), col("name")]).is_err());
+
+        assert!(validate(Method::Brin, &[Expr::BlockRange]).is_ok());
+        assert!(validate(Method::Brin, &[list("tags")]).is_err());
+    }
+
+    #[test]
+    fn parse_index_methods() {
+        assert_eq!(Ok(Method::SpGist), "spgist".parse().map_err(|_| ()));
+        assert!("bitmap".parse::<Method>().is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Recommendations for the indexes of a deployment, based on the usage
+//! statistics that Postgres keeps in `pg_stat_user_indexes` and
+//! `pg_stat_user_tables`
+//!
+//! The statistics are cumulative since they were last reset, and are not
+//! replicated, so the advice for a deployment only reflects the queries
+//! that ran against the database that was asked for it.
+use diesel::{
+    pg::PgConnection,
+    sql_query,
+    sql_types::{BigInt, Text},
+    RunQueryDsl,
+};
+use std::collections::HashMap;
+use std::fmt;
+
+use graph::prelude::StoreError;
+
+use crate::relational::Layout;
+
+/// Tables with fewer rows than this are cheap to scan sequentially and
+/// never get advice for new indexes
+const MIN_ROWS_FOR_NEW_INDEX: i64 = 10_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IndexAdvice {
+    /// The attribute index `index` on `table` has never been used to
+    /// answer a query; dropping it saves `size` bytes and speeds up writes
+    Drop {
+        table: String,
+        index: String,
+        size: i64,
+    },
+    /// The table for `entity` is mostly read with sequential scans, and
+    /// there is no index on `field`
+    Create {
+        entity: String,
+        field: String,
+        seq_scans: i64,
+    },
+}
+
+impl fmt::Display for IndexAdvice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexAdvice::Drop { table, index, size } => write!(
+                f,
+                "index {} on {} has never been used and takes {} bytes",
+                index, table, size
+            ),
+            IndexAdvice::Create {
+                entity,
+                field,
+                seq_scans,
+            } => write!(
+                f,
+                "{} was scanned sequentially {} times and {} is not indexed",
+                entity, seq_scans, field
+            ),
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct IndexUsage {
+    #[sql_type = "Text"]
+    table_name: String,
+    #[sql_type = "Text"]
+    index_name: String,
+    #[sql_type = "Text"]
+    index_def: String,
+    #[sql_type = "BigInt"]
+    scans: i64,
+    #[sql_type = "BigInt"]
+    size: i64,
+}
+
+#[derive(QueryableByName)]
+struct TableUsage {
+    #[sql_type = "Text"]
+    table_name: String,
+    #[sql_type = "BigInt"]
+    seq_scans: i64,
+    #[sql_type = "BigInt"]
+    idx_scans: i64,
+    #[sql_type = "BigInt"]
+    rows: i64,
+}
+
+fn index_usage(conn: &PgConnection, nsp: &str) -> Result<Vec<IndexUsage>, StoreError> {
+    const QUERY: &str = "
+        select s.relname::text as table_name, s.indexrelname::text as index_name,
+               pg_get_indexdef(s.indexrelid) as index_def,
+               s.idx_scan as scans, pg_relation_size(s.indexrelid) as size
+          from pg_stat_user_indexes s
+         where s.schemaname = $1
+         order by s.relname, s.indexrelname";
+    Ok(sql_query(QUERY).bind::<Text, _>(nsp).load(conn)?)
+}
+
+fn table_usage(conn: &PgConnection, nsp: &str) -> Result<Vec<TableUsage>, StoreError> {
+    const QUERY: &str = "
+        select s.relname::text as table_name, s.seq_scan as seq_scans,
+               coalesce(s.idx_scan, 0) as idx_scans, s.n_live_tup as rows
+          from pg_stat_user_tables s
+         where s.schemaname = $1
+         order by s.relname";
+    Ok(sql_query(QUERY).bind::<Text, _>(nsp).load(conn)?)
+}
+
+/// The identifiers that appear in the column list of the index
+/// definition `def`, e.g., `["owner", "block_range"]` for
+/// `create index .. using btree (owner, block_range)`
+fn indexed_identifiers(def: &str) -> Vec<&str> {
+    let columns = match def.find(" USING ") {
+        Some(pos) => &def[pos..],
+        None => def,
+    };
+    columns
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|ident| !ident.is_empty())
+        .collect()
+}
+
+/// Compute advice for the indexes of the deployment with `layout`. Only
+/// the attribute indexes that `graph-node` creates for every column are
+/// considered for dropping, never the indexes that queries rely on for
+/// the primary key or block ranges
+pub fn advise(conn: &PgConnection, layout: &Layout) -> Result<Vec<IndexAdvice>, StoreError> {
+    let nsp = layout.site.namespace.as_str();
+    let indexes = index_usage(conn, nsp)?;
+    let tables = table_usage(conn, nsp)?;
+
+    let mut advice: Vec<_> = indexes
+        .iter()
+        .filter(|index| index.index_name.starts_with("attr_") && index.scans == 0)
+        .map(|index| IndexAdvice::Drop {
+            table: index.table_name.clone(),
+            index: index.index_name.clone(),
+            size: index.size,
+        })
+        .collect();
+
+    let mut indexed: HashMap<&str, Vec<&str>> = HashMap::new();
+    for index in &indexes {
+        indexed
+            .entry(index.table_name.as_str())
+            .or_default()
+            .extend(indexed_identifiers(&index.index_def));
+    }
+
+    for usage in tables
+        .iter()
+        .filter(|usage| usage.rows >= MIN_ROWS_FOR_NEW_INDEX && usage.seq_scans > usage.idx_scans)
+    {
+        let table = match layout
+            .tables
+            .values()
+            .find(|table| table.name.as_str() == usage.table_name)
+        {
+            Some(table) => table,
+            None => continue,
+        };
+        let idents = indexed
+            .get(usage.table_name.as_str())
+            .map(|idents| idents.as_slice())
+            .unwrap_or(&[]);
+        for column in table
+            .columns
+            .iter()
+            .filter(|column| !column.is_list() && !column.is_fulltext())
+            .filter(|column| !idents.contains(&column.name.as_str()))
+        {
+            advice.push(IndexAdvice::Create {
+                entity: table.object.to_string(),
+                field: column.field.clone(),
+                seq_scans: usage.seq_scans,
+            });
+        }
+    }
+    Ok(advice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::indexed_identifiers;
+
+    #[test]
+    fn identifiers_in_index_definitions() {
+        assert_eq!(
+            vec!["USING", "btree", "owner", "block_range"],
+            indexed_identifiers(
+                "CREATE INDEX attr_1_0_token_owner ON sgd1.token USING btree (owner, block_range)"
+            )
+        );
+        assert_eq!(
+            vec!["USING", "btree", "left", "name", "256"],
+            indexed_identifiers(
+                "CREATE INDEX attr_1_1_token_name ON sgd1.token USING btree (\"left\"(name, 256))"
+            )
+        );
+    }
+}
@@ -7,7 +7,9 @@ use async_trait::async_trait;
 use diesel::{prelude::RunQueryDsl, sql_query, sql_types::Double};
 
 use graph::env::env_var;
-use graph::prelude::{chrono, error, lazy_static, Logger, MetricsRegistry, StoreError};
+use graph::prelude::{
+    chrono, error, info, lazy_static, BlockNumber, Logger, MetricsRegistry, StoreError,
+};
 use graph::prometheus::Gauge;
 use graph::util::jobs::{Job, Runner};
 
@@ -19,6 +21,12 @@ lazy_static! {
         let interval: u32 = env_var("GRAPH_REMOVE_UNUSED_INTERVAL", 360);
         chrono::Duration::minutes(interval as i64)
     };
+    /// The number of recent blocks that hot block range indexes cover at
+    /// least. Hot indexes are only maintained when this is set
+    static ref HOT_INDEX_BLOCKS: BlockNumber = env_var("GRAPH_STORE_HOT_INDEX_BLOCKS", 0);
+    /// The number of recent blocks to keep in the block cache of each
+    /// chain. Older blocks are only evicted when this is set
+    static ref BLOCK_CACHE_KEEP: BlockNumber = env_var("GRAPH_STORE_BLOCK_CACHE_KEEP", 0);
 }
 
 pub fn register(
@@ -46,7 +54,29 @@ pub fn register(
     runner.register(
         Arc::new(UnusedJob::new(store.subgraph_store())),
         Duration::from_secs(2 * 60 * 60),
-    )
+    );
+
+    if *BLOCK_CACHE_KEEP > 0 {
+        runner.register(
+            Arc::new(BlockCacheEvictionJob::new(store.clone(), *BLOCK_CACHE_KEEP)),
+            Duration::from_secs(15 * 60),
+        );
+    }
+
+    runner.register(
+        Arc::new(EntityCountsJob::new(store.subgraph_store())),
+        Duration::from_secs(60 * 60),
+    );
+
+    if *HOT_INDEX_BLOCKS > 0 {
+        runner.register(
+            Arc::new(HotIndexesJob::new(
+                store.subgraph_store(),
+                *HOT_INDEX_BLOCKS,
+            )),
+            Duration::from_secs(60 * 60),
+        );
+    }
 }
 
 /// A job that vacuums `subgraphs.subgraph_deployment`. With a large number
@@ -81,6 +111,104 @@ impl Job for VacuumDeploymentsJob {
     }
 }
 
+/// A job that maintains partial indexes on the account-like tables of
+/// deployments that only cover the entity versions that were current
+/// during the last `window` blocks or so
+struct HotIndexesJob {
+    store: Arc<SubgraphStore>,
+    window: BlockNumber,
+}
+
+impl HotIndexesJob {
+    fn new(store: Arc<SubgraphStore>, window: BlockNumber) -> HotIndexesJob {
+        HotIndexesJob { store, window }
+    }
+}
+
+#[async_trait]
+impl Job for HotIndexesJob {
+    fn name(&self) -> &str {
+        "Refresh hot block range indexes"
+    }
+
+    async fn run(&self, logger: &Logger) {
+        self.store.refresh_hot_indexes(logger, self.window).await
+    }
+}
+
+/// A job that removes blocks that are more than `keep` blocks behind the
+/// chain head from the block cache of every chain, except for the blocks
+/// that deployments have as their head block and the blocks they might
+/// need to revert
+struct BlockCacheEvictionJob {
+    store: Arc<Store>,
+    keep: BlockNumber,
+}
+
+impl BlockCacheEvictionJob {
+    fn new(store: Arc<Store>, keep: BlockNumber) -> BlockCacheEvictionJob {
+        BlockCacheEvictionJob { store, keep }
+    }
+}
+
+#[async_trait]
+impl Job for BlockCacheEvictionJob {
+    fn name(&self) -> &str {
+        "Evict old blocks from the block cache"
+    }
+
+    async fn run(&self, logger: &Logger) {
+        let chains = match self.store.block_store().chain_head_pointers() {
+            Ok(ptrs) => ptrs.into_iter().map(|(chain, _)| chain).collect::<Vec<_>>(),
+            Err(e) => {
+                error!(logger, "Failed to list chains for block cache eviction";
+                       "error" => e.to_string());
+                return;
+            }
+        };
+        for chain in chains {
+            match self.store.evict_cached_blocks(&chain, self.keep) {
+                Ok(Some((block, rows))) => {
+                    info!(logger, "Evicted blocks from the block cache";
+                          "chain" => &chain,
+                          "before_block" => block,
+                          "blocks" => rows);
+                }
+                Ok(None) => { /* not enough blocks yet */ }
+                Err(e) => {
+                    error!(logger, "Failed to evict blocks from the block cache";
+                           "chain" => &chain,
+                           "error" => e.to_string());
+                }
+            }
+        }
+    }
+}
+
+/// A job that checks the entity counts that the write path maintains for
+/// each table against the row estimates in `pg_class` and recounts the
+/// tables whose counts have drifted
+struct EntityCountsJob {
+    store: Arc<SubgraphStore>,
+}
+
+impl EntityCountsJob {
+    fn new(store: Arc<SubgraphStore>) -> EntityCountsJob {
+        EntityCountsJob { store }
+    }
+}
+
+#[async_trait]
+impl Job for EntityCountsJob {
+    fn name(&self) -> &str {
+        "Reconcile entity counts"
+    }
+
+    async fn run(&self, logger: &Logger) {
+        self.store.reconcile_entity_counts(logger).await
+    }
+}
+
 struct NotificationQueueUsage {
     primary: ConnectionPool,
     usage_gauge: Box<Gauge>,
@@ -198,7 +326,13 @@ impl Job for UnusedJob {
 
         for deployment in remove {
             match self.store.remove_deployment(deployment.id) {
-                Ok(()) => { /* ignore */ }
+                Ok(Some(reclaimed)) => {
+                    info!(logger, "removed unused deployment";
+                                  "sgd" => deployment.id.to_string(),
+                                  "deployment" => deployment.deployment.as_str(),
+                                  "reclaimed_bytes" => reclaimed);
+                }
+                Ok(None) => { /* still in use */ }
                 Err(e) => {
                     error!(logger, "failed to remove unused deployment";
                                    "sgd" => deployment.id.to_string(),
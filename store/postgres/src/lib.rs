@@ -31,22 +31,29 @@ mod chain_head_listener;
 mod chain_store;
 pub mod connection_pool;
 mod copy;
+mod data_source_contexts;
 mod deployment;
 mod deployment_store;
 mod detail;
+mod dump;
 mod dynds;
 mod functions;
+mod index;
+mod index_advice;
 mod jobs;
 mod jsonb;
 mod notification_listener;
 mod primary;
 pub mod query_store;
+mod reader;
 mod relational;
 mod relational_queries;
+mod scheduled_handlers;
 mod sql_value;
 mod store;
 mod store_events;
 mod subgraph_store;
+mod tls;
 pub mod transaction_receipt;
 mod writable;
 
@@ -65,9 +72,11 @@ pub use self::block_store::BlockStore;
 pub use self::chain_head_listener::ChainHeadUpdateListener;
 pub use self::chain_store::ChainStore;
 pub use self::detail::DeploymentDetail;
+pub use self::index::Method as IndexMethod;
+pub use self::index_advice::IndexAdvice;
 pub use self::jobs::register as register_jobs;
 pub use self::notification_listener::NotificationSender;
-pub use self::primary::{db_version, UnusedDeployment};
+pub use self::primary::{db_version, DeploymentId, UnusedDeployment};
 pub use self::store::Store;
 pub use self::store_events::SubscriptionManager;
 pub use self::subgraph_store::{unused, DeploymentPlacer, Shard, SubgraphStore, PRIMARY_SHARD};
@@ -77,7 +86,10 @@ pub use self::subgraph_store::{unused, DeploymentPlacer, Shard, SubgraphStore, P
 pub mod command_support {
     pub mod catalog {
         pub use crate::block_store::primary as block_store;
-        pub use crate::catalog::{account_like, set_account_like};
+        pub use crate::catalog::{
+            account_like, reset_storage_params, set_account_like, set_column_storage,
+            set_storage_params, table_activity, ColumnStorage, StorageParams, TableActivity,
+        };
         pub use crate::copy::{copy_state, copy_table_state};
         pub use crate::primary::Connection;
         pub use crate::primary::{
@@ -7,7 +7,7 @@ use diesel::{
     dsl::{any, exists, not, select},
     pg::Pg,
     serialize::Output,
-    sql_types::{Array, Integer, Text},
+    sql_types::{Array, BigInt, Integer, Text},
     types::{FromSql, ToSql},
 };
 use diesel::{
@@ -23,10 +23,7 @@ use diesel::{
     Connection as _,
 };
 use graph::{
-    components::store::DeploymentId as GraphDeploymentId,
-    prelude::{chrono, CancelHandle, CancelToken},
-};
-use graph::{
+    blockchain::BlockHash,
     components::store::DeploymentLocator,
     constraint_violation,
     data::subgraph::status,
@@ -35,11 +32,15 @@ use graph::{
         EntityChangeOperation, NodeId, StoreError, SubgraphName, SubgraphVersionSwitchingMode,
     },
 };
+use graph::{
+    components::store::DeploymentId as GraphDeploymentId,
+    prelude::{chrono, CancelHandle, CancelToken},
+};
 use graph::{data::subgraph::schema::generate_entity_id, prelude::StoreEvent};
 use itertools::Itertools;
 use maybe_owned::MaybeOwned;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     convert::TryFrom,
     convert::TryInto,
     fmt,
@@ -49,6 +50,7 @@ use std::{
 
 use crate::{
     block_range::UNVERSIONED_RANGE,
+    block_store::primary as block_store,
     connection_pool::{ConnectionPool, ForeignServer},
     detail::DeploymentDetail,
     subgraph_store::{unused, Shard, PRIMARY_SHARD},
@@ -85,6 +87,7 @@ table! {
         deployment -> Text,
         created_at -> Numeric,
         block_range -> Range<Integer>,
+        promoted_at -> Nullable<Numeric>,
     }
 }
 
@@ -107,6 +110,14 @@ table! {
     }
 }
 
+table! {
+    subgraphs.deployment_labels(deployment, key) {
+        deployment -> Text,
+        key -> Text,
+        value -> Text,
+    }
+}
+
 table! {
     public.ens_names(hash) {
         hash -> Varchar,
@@ -138,6 +149,9 @@ table! {
         /// only one of them will be active. That's the one we use for
         /// querying
         active -> Bool,
+        /// The genesis block of the chain the deployment was created
+        /// against, in the format of `chains.genesis_block_hash`
+        genesis_block_hash -> Nullable<Text>,
     }
 }
 
@@ -203,6 +217,7 @@ struct Schema {
     version: DeploymentSchemaVersion,
     pub network: String,
     pub(crate) active: bool,
+    pub genesis_block_hash: Option<String>,
 }
 
 #[derive(Clone, Queryable, QueryableByName, Debug)]
@@ -331,6 +346,10 @@ pub struct Site {
     /// exactly one for each `deployment`, i.e., other entries for that
     /// deployment have `active = false`
     pub(crate) active: bool,
+    /// The genesis block of the chain the deployment was created against.
+    /// Only `None` if the chain was not known when the deployment was
+    /// created
+    pub genesis_block_hash: Option<BlockHash>,
     /// Only the store and tests can create Sites
     _creation_disallowed: (),
 }
@@ -355,6 +374,18 @@ impl TryFrom<Schema> for Site {
             )
         })?;
         let shard = Shard::new(schema.shard)?;
+        let genesis_block_hash = schema
+            .genesis_block_hash
+            .as_deref()
+            .map(BlockHash::try_from)
+            .transpose()
+            .map_err(|e| {
+                constraint_violation!(
+                    "invalid genesis block hash for deployment {}: {}",
+                    &schema.subgraph,
+                    e
+                )
+            })?;
         Ok(Self {
             id: schema.id,
             deployment,
@@ -362,6 +393,7 @@ impl TryFrom<Schema> for Site {
             shard,
             network: schema.network,
             active: schema.active,
+            genesis_block_hash,
             _creation_disallowed: (),
         })
     }
@@ -384,6 +416,7 @@ pub fn make_dummy_site(deployment: DeploymentHash, namespace: Namespace, network
         namespace,
         network,
         active: true,
+        genesis_block_hash: None,
         _creation_disallowed: (),
     }
 }
@@ -406,7 +439,11 @@ mod queries {
         data::subgraph::status,
         prelude::{DeploymentHash, StoreError, SubgraphName},
     };
-    use std::{collections::HashMap, convert::TryFrom, convert::TryInto};
+    use std::{
+        collections::{BTreeMap, HashMap},
+        convert::TryFrom,
+        convert::TryInto,
+    };
 
     use crate::Shard;
 
@@ -415,6 +452,7 @@ mod queries {
     // These are the only tables that functions in this module may use. If
     // additional tables are needed, they need to be set up for mirroring
     // first
+    use super::deployment_labels as l;
     use super::deployment_schemas as ds;
     use super::subgraph as s;
     use super::subgraph_deployment_assignment as a;
@@ -601,6 +639,37 @@ mod queries {
         Ok(())
     }
 
+    pub(super) fn fill_labels(
+        conn: &PgConnection,
+        infos: &mut [status::Info],
+    ) -> Result<(), StoreError> {
+        let ids: Vec<_> = infos.iter().map(|info| &info.subgraph).collect();
+        let mut labels: HashMap<String, BTreeMap<String, String>> = HashMap::new();
+        for (deployment, key, value) in l::table
+            .filter(l::deployment.eq(any(ids)))
+            .select((l::deployment, l::key, l::value))
+            .load::<(String, String, String)>(conn)?
+        {
+            labels.entry(deployment).or_default().insert(key, value);
+        }
+        for info in infos {
+            info.labels = labels.remove(&info.subgraph).unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    pub(super) fn deployment_labels(
+        conn: &PgConnection,
+        deployment: &str,
+    ) -> Result<BTreeMap<String, String>, StoreError> {
+        Ok(l::table
+            .filter(l::deployment.eq(deployment))
+            .select((l::key, l::value))
+            .load::<(String, String)>(conn)?
+            .into_iter()
+            .collect())
+    }
+
     pub(super) fn assigned_node(
         conn: &PgConnection,
         site: &Site,
@@ -645,6 +714,54 @@ mod queries {
             .unwrap_or((None, None)))
     }
 
+    /// Find the current versions of all subgraphs that `deployment` is a
+    /// version of, and return the newest of them that is a different
+    /// deployment and was created after `deployment` was deployed to that
+    /// subgraph, together with the time it became the current version.
+    /// Versions that were promoted before we recorded that use their
+    /// creation time instead
+    pub(super) fn newer_version(
+        conn: &PgConnection,
+        deployment: &str,
+    ) -> Result<Option<(String, i64)>, StoreError> {
+        let versions = v::table
+            .filter(v::deployment.eq(deployment))
+            .select((v::subgraph, sql::<BigInt>("created_at::int8")))
+            .load::<(String, i64)>(conn)?;
+        if versions.is_empty() {
+            return Ok(None);
+        }
+
+        let subgraphs: Vec<_> = versions
+            .iter()
+            .map(|(subgraph, _)| subgraph.as_str())
+            .collect();
+        let current = v::table
+            .inner_join(s::table.on(s::current_version.eq(v::id.nullable())))
+            .filter(s::id.eq(any(subgraphs)))
+            .select((
+                s::id,
+                v::deployment,
+                sql::<BigInt>("subgraphs.subgraph_version.created_at::int8"),
+                sql::<BigInt>(
+                    "coalesce(subgraphs.subgraph_version.promoted_at, \
+                              subgraphs.subgraph_version.created_at)::int8",
+                ),
+            ))
+            .load::<(String, String, i64, i64)>(conn)?;
+
+        Ok(current
+            .into_iter()
+            .filter(|(subgraph, current, created_at, _)| {
+                current != deployment
+                    && versions
+                        .iter()
+                        .any(|(other, deployed_at)| other == subgraph && created_at > deployed_at)
+            })
+            .map(|(_, current, _, promoted_at)| (current, promoted_at))
+            .max_by_key(|(_, promoted_at)| *promoted_at))
+    }
+
     /// Returns all (subgraph_name, version) pairs for a given deployment hash.
     pub fn subgraphs_by_deployment_hash(
         conn: &PgConnection,
@@ -786,6 +903,9 @@ impl<'a> Connection<'a> {
                     s::pending_version.eq::<Option<&str>>(None),
                 ))
                 .execute(conn)?;
+            update(v::table.filter(v::id.eq(version)))
+                .set(v::promoted_at.eq(sql("extract(epoch from now())")))
+                .execute(conn)?;
         }
 
         // Clean up assignments if we could possibly have changed any
@@ -935,6 +1055,9 @@ impl<'a> Connection<'a> {
                         s::pending_version.eq::<Option<&str>>(None),
                     ))
                     .execute(conn)?;
+                update(v::table.filter(v::id.eq(&version_id)))
+                    .set(v::promoted_at.eq(sql(&format!("{}", created_at))))
+                    .execute(conn)?;
             }
             (Synced, true) => {
                 subgraph_row
@@ -1048,6 +1171,7 @@ impl<'a> Connection<'a> {
         deployment: DeploymentHash,
         network: String,
         active: bool,
+        genesis_block_hash: Option<BlockHash>,
     ) -> Result<Site, StoreError> {
         use deployment_schemas as ds;
         use DeploymentSchemaVersion as v;
@@ -1061,6 +1185,7 @@ impl<'a> Connection<'a> {
                 ds::version.eq(v::Relational),
                 ds::network.eq(network.as_str()),
                 ds::active.eq(active),
+                ds::genesis_block_hash.eq(genesis_block_hash.as_ref().map(|hash| hash.hash_hex())),
             ))
             .returning((ds::id, ds::name))
             .get_results(conn)?;
@@ -1079,10 +1204,41 @@ impl<'a> Connection<'a> {
             namespace,
             network,
             active: true,
+            genesis_block_hash,
             _creation_disallowed: (),
         })
     }
 
+    /// The genesis block of the chain that is currently stored under the
+    /// name `network`, or `None` if there is no such chain
+    pub fn chain_genesis_block_hash(&self, network: &str) -> Result<Option<BlockHash>, StoreError> {
+        block_store::find_chain(self.conn.as_ref(), network)?
+            .map(|chain| chain.network_identifier())
+            .transpose()
+            .map(|ident| ident.map(|ident| ident.genesis_block_hash))
+    }
+
+    /// The shard that stores the blocks of `network`, or `None` if there is
+    /// no such chain
+    pub fn chain_shard(&self, network: &str) -> Result<Option<Shard>, StoreError> {
+        Ok(block_store::find_chain(self.conn.as_ref(), network)?.map(|chain| chain.shard))
+    }
+
+    /// Bind all copies of `deployment` to the chain with the genesis block
+    /// `genesis_block_hash`
+    pub fn set_genesis_block_hash(
+        &self,
+        deployment: &DeploymentHash,
+        genesis_block_hash: &BlockHash,
+    ) -> Result<(), StoreError> {
+        use deployment_schemas as ds;
+
+        update(ds::table.filter(ds::subgraph.eq(deployment.as_str())))
+            .set(ds::genesis_block_hash.eq(genesis_block_hash.hash_hex()))
+            .execute(self.conn.as_ref())?;
+        Ok(())
+    }
+
     pub fn allocate_site(
         &self,
         shard: Shard,
@@ -1093,7 +1249,8 @@ impl<'a> Connection<'a> {
             return Ok(site);
         }
 
-        self.create_site(shard, subgraph.clone(), network, true)
+        let genesis_block_hash = self.chain_genesis_block_hash(&network)?;
+        self.create_site(shard, subgraph.clone(), network, true, genesis_block_hash)
     }
 
     pub fn assigned_node(&self, site: &Site) -> Result<Option<NodeId>, StoreError> {
@@ -1110,23 +1267,60 @@ impl<'a> Connection<'a> {
             return Ok(site);
         }
 
-        self.create_site(shard, src.deployment.clone(), src.network.clone(), false)
+        self.create_site(
+            shard,
+            src.deployment.clone(),
+            src.network.clone(),
+            false,
+            src.genesis_block_hash.clone(),
+        )
+    }
+
+    /// Create a site for restoring `deployment` from a dump into `shard`.
+    /// The site is only active if there is no other active site for the
+    /// deployment. It is an error if there already is a site in `shard`
+    pub fn restore_site(
+        &self,
+        shard: Shard,
+        deployment: &DeploymentHash,
+        network: String,
+    ) -> Result<Site, StoreError> {
+        let conn = self.conn.as_ref();
+        if queries::find_site_in_shard(conn, deployment, &shard)?.is_some() {
+            return Err(StoreError::Unknown(anyhow!(
+                "deployment {} already exists in shard {}",
+                deployment,
+                shard
+            )));
+        }
+        let active = queries::find_active_site(conn, deployment)?.is_none();
+        let genesis_block_hash = self.chain_genesis_block_hash(&network)?;
+        self.create_site(
+            shard,
+            deployment.clone(),
+            network,
+            active,
+            genesis_block_hash,
+        )
     }
 
     pub(crate) fn activate(&self, deployment: &DeploymentLocator) -> Result<(), StoreError> {
         use deployment_schemas as ds;
 
         // We need to tread lightly so we do not violate the unique constraint on
-        // `subgraph where active`
-        update(ds::table.filter(ds::subgraph.eq(deployment.hash.as_str())))
-            .set(ds::active.eq(false))
-            .execute(self.conn.as_ref())?;
+        // `subgraph where active`. Both changes happen in one transaction so
+        // that queries never see a deployment without an active copy
+        self.transaction(|| {
+            update(ds::table.filter(ds::subgraph.eq(deployment.hash.as_str())))
+                .set(ds::active.eq(false))
+                .execute(self.conn.as_ref())?;
 
-        update(ds::table.filter(ds::id.eq(DeploymentId::from(deployment.id))))
-            .set(ds::active.eq(true))
-            .execute(self.conn.as_ref())
-            .map_err(|e| e.into())
-            .map(|_| ())
+            update(ds::table.filter(ds::id.eq(DeploymentId::from(deployment.id))))
+                .set(ds::active.eq(true))
+                .execute(self.conn.as_ref())
+                .map_err(|e| e.into())
+                .map(|_| ())
+        })
     }
 
     /// Remove all subgraph versions and the entry in `deployment_schemas` for
@@ -1485,6 +1679,54 @@ impl<'a> Connection<'a> {
             .map_err(|e| anyhow!("error looking up ens_name for hash {}: {}", hash, e).into())
     }
 
+    /// Set the label `key` of `deployment` to `value`, replacing any
+    /// previous value
+    pub fn set_deployment_label(
+        &self,
+        deployment: &DeploymentHash,
+        key: &str,
+        value: &str,
+    ) -> Result<(), StoreError> {
+        use deployment_labels as l;
+
+        insert_into(l::table)
+            .values((
+                l::deployment.eq(deployment.as_str()),
+                l::key.eq(key),
+                l::value.eq(value),
+            ))
+            .on_conflict((l::deployment, l::key))
+            .do_update()
+            .set(l::value.eq(value))
+            .execute(self.conn.as_ref())?;
+        Ok(())
+    }
+
+    /// Remove the label `key` from `deployment`. Return `true` if the
+    /// deployment had that label
+    pub fn remove_deployment_label(
+        &self,
+        deployment: &DeploymentHash,
+        key: &str,
+    ) -> Result<bool, StoreError> {
+        use deployment_labels as l;
+
+        let count = delete(
+            l::table
+                .filter(l::deployment.eq(deployment.as_str()))
+                .filter(l::key.eq(key)),
+        )
+        .execute(self.conn.as_ref())?;
+        Ok(count > 0)
+    }
+
+    pub fn deployment_labels(
+        &self,
+        deployment: &DeploymentHash,
+    ) -> Result<BTreeMap<String, String>, StoreError> {
+        queries::deployment_labels(self.conn.as_ref(), deployment.as_str())
+    }
+
     pub fn record_active_copy(&self, src: &Site, dst: &Site) -> Result<(), StoreError> {
         use active_copies as cp;
 
@@ -1571,10 +1813,11 @@ impl Mirror {
         // constraint on `deployment_schemas` and is tiny, therefore it's
         // easiest to just mirror it
         const PUBLIC_TABLES: [&str; 3] = ["chains", "deployment_schemas", "active_copies"];
-        const SUBGRAPHS_TABLES: [&str; 3] = [
+        const SUBGRAPHS_TABLES: [&str; 4] = [
             "subgraph_deployment_assignment",
             "subgraph",
             "subgraph_version",
+            "deployment_labels",
         ];
 
         fn copy_table(
@@ -1700,6 +1943,17 @@ impl Mirror {
         self.read(|conn| queries::fill_assignments(conn, infos))
     }
 
+    pub fn fill_labels(&self, infos: &mut [status::Info]) -> Result<(), StoreError> {
+        self.read(|conn| queries::fill_labels(conn, infos))
+    }
+
+    pub fn deployment_labels(
+        &self,
+        deployment: &DeploymentHash,
+    ) -> Result<BTreeMap<String, String>, StoreError> {
+        self.read(|conn| queries::deployment_labels(conn, deployment.as_str()))
+    }
+
     pub fn version_info(&self, version: &str) -> Result<Option<(String, String)>, StoreError> {
         self.read(|conn| queries::version_info(conn, version))
     }
@@ -1726,4 +1980,10 @@ impl Mirror {
     ) -> Result<Option<Site>, StoreError> {
         self.read(|conn| queries::find_site_in_shard(conn, subgraph, shard))
     }
+
+    /// Return the newest deployment that replaced `deployment` as the
+    /// current version of a subgraph, and when it became the current version
+    pub fn newer_version(&self, deployment: &str) -> Result<Option<(String, i64)>, StoreError> {
+        self.read(|conn| queries::newer_version(conn, deployment))
+    }
 }
@@ -45,6 +45,21 @@ impl QueryStoreTrait for QueryStore {
         self.store.execute_query(&conn, self.site.clone(), query)
     }
 
+    fn fulltext_search(
+        &self,
+        text: &str,
+        range: EntityRange,
+        block: BlockNumber,
+        query_id: Option<String>,
+    ) -> Result<Vec<BTreeMap<String, r::Value>>, QueryExecutionError> {
+        let conn = self
+            .store
+            .get_replica_conn(self.replica_id)
+            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        self.store
+            .fulltext_search(&conn, self.site.clone(), text, range, block, query_id)
+    }
+
     /// Return true if the deployment with the given id is fully synced,
     /// and return false otherwise. Errors from the store are passed back up
     async fn is_deployment_synced(&self) -> Result<bool, Error> {
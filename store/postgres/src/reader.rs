@@ -0,0 +1,106 @@
+//! Read-only roles for direct SQL access to a deployment
+//!
+//! Each deployment can get a role `<namespace>_reader`, e.g., `sgd42_reader`,
+//! that can read the tables in the deployment's schema and, through row
+//! level security policies, only the rows for that deployment in the
+//! metadata tables that are shared by all deployments in a shard. The role
+//! can not log in; access is given to analysts by granting the role to
+//! their own login role with `grant sgd42_reader to <analyst>`.
+//!
+//! Row level security does not apply to the owner of a table, and
+//! graph-node is therefore not affected by these policies. Other roles that
+//! can read the metadata tables directly will only see rows for which they
+//! have a policy once row level security is enabled on them.
+use diesel::{connection::SimpleConnection, Connection, PgConnection};
+use graph::prelude::StoreError;
+
+use crate::primary::Site;
+
+/// The metadata tables that contain rows for every deployment in the
+/// shard, together with the condition that selects the rows for a
+/// deployment
+fn metadata_tables(site: &Site) -> Vec<(&'static str, String)> {
+    vec![
+        ("subgraphs.subgraph_deployment", format!("id = {}", site.id)),
+        ("subgraphs.subgraph_manifest", format!("id = {}", site.id)),
+        (
+            "subgraphs.subgraph_error",
+            format!("subgraph_id = '{}'", site.deployment),
+        ),
+    ]
+}
+
+/// The name of the reader role for `site`
+pub fn role_name(site: &Site) -> String {
+    format!("{}_reader", site.namespace)
+}
+
+/// The SQL that creates the reader role for `site` and gives it access to
+/// the deployment's data. Running it again is harmless
+pub fn grant_sql(site: &Site) -> String {
+    let role = role_name(site);
+    let nsp = &site.namespace;
+
+    let mut sql = format!(
+        "do $$ begin\n  \
+           if not exists (select 1 from pg_roles where rolname = '{role}') then\n    \
+             create role \"{role}\" nologin;\n  \
+           end if;\n\
+         end $$;\n\
+         grant usage on schema \"{nsp}\" to \"{role}\";\n\
+         grant select on all tables in schema \"{nsp}\" to \"{role}\";\n\
+         alter default privileges in schema \"{nsp}\" grant select on tables to \"{role}\";\n\
+         grant usage on schema subgraphs to \"{role}\";\n",
+        role = role,
+        nsp = nsp
+    );
+    for (table, cond) in metadata_tables(site) {
+        sql.push_str(&format!(
+            "alter table {table} enable row level security;\n\
+             grant select on {table} to \"{role}\";\n\
+             drop policy if exists \"{role}\" on {table};\n\
+             create policy \"{role}\" on {table} for select to \"{role}\" using ({cond});\n",
+            table = table,
+            role = role,
+            cond = cond
+        ));
+    }
+    sql
+}
+
+/// The SQL that removes all access of the reader role for `site` and drops
+/// it. Row level security stays enabled on the metadata tables
+pub fn revoke_sql(site: &Site) -> String {
+    let role = role_name(site);
+    let nsp = &site.namespace;
+
+    let mut sql = String::new();
+    for (table, _) in metadata_tables(site) {
+        sql.push_str(&format!(
+            "drop policy if exists \"{role}\" on {table};\n",
+            table = table,
+            role = role
+        ));
+    }
+    sql.push_str(&format!(
+        "do $$ begin\n  \
+           if exists (select 1 from pg_roles where rolname = '{role}') then\n    \
+             alter default privileges in schema \"{nsp}\" revoke select on tables from \"{role}\";\n    \
+             revoke all on all tables in schema \"{nsp}\" from \"{role}\";\n    \
+             revoke all on schema \"{nsp}\" from \"{role}\";\n    \
+             revoke all on all tables in schema subgraphs from \"{role}\";\n    \
+             revoke all on schema subgraphs from \"{role}\";\n    \
+             drop role \"{role}\";\n  \
+           end if;\n\
+         end $$;\n",
+        role = role,
+        nsp = nsp
+    ));
+    sql
+}
+
+/// Run the SQL produced by `grant_sql` or `revoke_sql` in one transaction
+pub fn execute(conn: &PgConnection, sql: &str) -> Result<(), StoreError> {
+    conn.transaction(|| conn.batch_execute(sql))
+        .map_err(StoreError::from)
+}
@@ -26,19 +26,21 @@ use std::time::{Duration, Instant};
 use crate::{
     primary::{Namespace, Site},
     relational_queries::{
-        ClampRangeQuery, ConflictingEntityQuery, EntityData, FilterCollection, FilterQuery,
-        FindManyQuery, FindQuery, InsertQuery, RevertClampQuery, RevertRemoveQuery,
+        ClampRangeQuery, ClampRelationQuery, ConflictingEntityQuery, CopyInsert, EntityData,
+        FilterCollection, FilterQuery, FindManyQuery, FindQuery, FulltextSearchQuery, InsertQuery,
+        InsertRelationQuery, RevertClampQuery, RevertRelationClampQuery, RevertRelationRemoveQuery,
+        RevertRemoveQuery,
     },
 };
-use graph::components::store::EntityType;
+use graph::components::store::{EntityType, PruneReporter};
 use graph::data::graphql::ext::{DirectiveFinder, DocumentExt, ObjectTypeExt};
 use graph::data::schema::{FulltextConfig, FulltextDefinition, Schema, SCHEMA_TYPE_NAME};
 use graph::data::store::BYTES_SCALAR;
 use graph::data::subgraph::schema::{POI_OBJECT, POI_TABLE};
 use graph::prelude::{
     anyhow, info, BlockNumber, DeploymentHash, Entity, EntityChange, EntityCollection,
-    EntityFilter, EntityKey, EntityOrder, EntityRange, Logger, QueryExecutionError, StoreError,
-    StoreEvent, ValueType, BLOCK_NUMBER_MAX,
+    EntityFilter, EntityHistory, EntityKey, EntityOrder, EntityRange, Logger, QueryExecutionError,
+    StoreError, StoreEvent, ValueType, BLOCK_NUMBER_MAX,
 };
 
 use crate::block_range::BLOCK_RANGE_COLUMN;
@@ -48,6 +50,8 @@ use crate::{catalog, deployment};
 
 const POSTGRES_MAX_PARAMETERS: usize = u16::MAX as usize; // 65535
 const DELETE_OPERATION_CHUNK_SIZE: usize = 1_000;
+/// The number of `vid`s that one batch of pruning looks at
+const PRUNE_BATCH_SIZE: i64 = 50_000;
 
 /// The size of string prefixes that we index. This is chosen so that we
 /// will index strings that people will do string comparisons like
@@ -90,6 +94,21 @@ lazy_static! {
             })
         }).map(|timeout| format!("set local statement_timeout={}", timeout * 1000))
     };
+
+    /// `GRAPH_SQL_ORDER_BY_HINT_THRESHOLD` is the number of milliseconds
+    /// after which a query that orders by several attributes logs a
+    /// suggestion to create a composite index on these attributes
+    static ref ORDER_BY_HINT_THRESHOLD: Duration = {
+        env::var("GRAPH_SQL_ORDER_BY_HINT_THRESHOLD")
+        .ok()
+        .map(|s| {
+            u64::from_str(&s).unwrap_or_else(|_| {
+                panic!("GRAPH_SQL_ORDER_BY_HINT_THRESHOLD must be a number, but is `{}`", s)
+            })
+        })
+        .map(Duration::from_millis)
+        .unwrap_or(Duration::from_secs(1))
+    };
 }
 
 /// A string we use as a SQL name for a table or column. The important thing
@@ -370,6 +389,8 @@ impl Layout {
                     column_type: ColumnType::Bytes,
                     fulltext_fields: None,
                     is_reference: false,
+                    bytes_prefix: None,
+                    is_external: false,
                 },
                 Column {
                     name: SqlName::from(PRIMARY_KEY_COLUMN),
@@ -380,6 +401,8 @@ impl Layout {
                     column_type: ColumnType::String,
                     fulltext_fields: None,
                     is_reference: false,
+                    bytes_prefix: None,
+                    is_external: false,
                 },
             ],
             /// The position of this table in all the tables for this layout; this
@@ -387,6 +410,7 @@ impl Layout {
             /// predictable
             position: position as u32,
             is_account_like: false,
+            relations: vec![],
         }
     }
 
@@ -405,6 +429,19 @@ impl Layout {
             .as_ddl()
             .map_err(|_| StoreError::Unknown(anyhow!("failed to generate DDL for layout")))?;
         conn.batch_execute(&sql)?;
+
+        // Every entity type can change, so we start all tables out with
+        // the storage parameters for mutable tables; they can be adjusted
+        // later based on how each table is actually used with `graphman
+        // stats set-storage-params`
+        for table in layout.tables.values() {
+            catalog::set_storage_params(
+                conn,
+                &layout.site.namespace,
+                &table.name,
+                &catalog::StorageParams::mutable(),
+            )?;
+        }
         Ok(layout)
     }
 
@@ -578,6 +615,12 @@ impl Layout {
         // number of attributes each entity type has.
         // We add 1 to account for the `block_range` bind parameter
         let chunk_size = POSTGRES_MAX_PARAMETERS / (table.columns.len() + 1);
+        for relation in &table.relations {
+            let query = InsertRelationQuery::new(relation, entities, block)?;
+            if !query.is_empty() {
+                query.execute(conn)?;
+            }
+        }
         for chunk in entities.chunks_mut(chunk_size) {
             count += InsertQuery::new(table, chunk, block)?
                 .get_results(conn)
@@ -586,6 +629,27 @@ impl Layout {
         Ok(count)
     }
 
+    /// Whether new entities of type `entity_type` can be written with
+    /// `copy_insert`
+    pub fn can_copy_insert(&self, entity_type: &EntityType) -> bool {
+        self.tables
+            .get(entity_type)
+            .map_or(false, |table| CopyInsert::is_possible(table))
+    }
+
+    /// The `COPY` statement and its data that insert `entities`, each
+    /// valid from its own block on. Diesel can not run `COPY`, and the
+    /// caller has to run it over a separate connection
+    pub fn copy_insert(
+        &self,
+        entity_type: &EntityType,
+        entities: &[(&EntityKey, &Entity, BlockNumber)],
+    ) -> Result<(String, Vec<u8>), StoreError> {
+        let table = self.table_for_entity(entity_type)?;
+        let copy = CopyInsert::new(table, entities)?;
+        Ok((copy.statement(), copy.data()?))
+    }
+
     pub fn conflicting_entity(
         &self,
         conn: &PgConnection,
@@ -608,6 +672,7 @@ impl Layout {
         order: EntityOrder,
         range: EntityRange,
         block: BlockNumber,
+        history: Option<EntityHistory>,
         query_id: Option<String>,
     ) -> Result<Vec<T>, QueryExecutionError> {
         fn log_query_timing(
@@ -640,6 +705,18 @@ impl Layout {
             );
         }
 
+        // Ordering by several attributes is only fast if there is an index
+        // on all of them
+        let order_by_hint = match &order {
+            EntityOrder::Multiple(keys) => Some(
+                keys.iter()
+                    .map(|(attr, _, _)| attr.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            _ => None,
+        };
+
         let filter_collection = FilterCollection::new(&self, collection, filter.as_ref())?;
         let query = FilterQuery::new(
             &filter_collection,
@@ -647,6 +724,7 @@ impl Layout {
             order,
             range,
             block,
+            history,
             query_id,
         )?;
         let query_clone = query.clone();
@@ -673,6 +751,84 @@ impl Layout {
                 )),
             })?;
         log_query_timing(logger, &query_clone, start.elapsed(), values.len());
+        if let (Some(attrs), Some(table)) = (order_by_hint, filter_collection.first_table()) {
+            if start.elapsed() >= *ORDER_BY_HINT_THRESHOLD {
+                info!(
+                    logger,
+                    "Slow query that orders by several attributes; an index on them might help";
+                    "time_ms" => start.elapsed().as_millis(),
+                    "hint" => format!(
+                        "graphman index create {} {} {}",
+                        self.site.deployment,
+                        table.object.as_str(),
+                        attrs
+                    )
+                );
+            }
+        }
+        values
+            .into_iter()
+            .map(|entity_data| {
+                entity_data
+                    .deserialize_with_layout(self)
+                    .map_err(|e| e.into())
+            })
+            .collect()
+    }
+
+    /// Search all fulltext fields of all entity types for `text` and
+    /// return the matching entities, ordered by decreasing rank
+    pub fn fulltext_search<T: crate::relational_queries::FromEntityData>(
+        &self,
+        logger: &Logger,
+        conn: &PgConnection,
+        text: &str,
+        range: EntityRange,
+        block: BlockNumber,
+        query_id: Option<String>,
+    ) -> Result<Vec<T>, QueryExecutionError> {
+        let query = FulltextSearchQuery::new(
+            self.tables.values().map(|table| table.as_ref()),
+            text,
+            range,
+            block,
+            query_id,
+        );
+        if query.is_empty() {
+            return Ok(vec![]);
+        }
+        let query_clone = query.clone();
+
+        let start = Instant::now();
+        let values = conn
+            .transaction(|| {
+                if let Some(ref timeout_sql) = *STATEMENT_TIMEOUT {
+                    conn.batch_execute(timeout_sql)?;
+                }
+                query.load::<EntityData>(conn)
+            })
+            .map_err(|e| match e {
+                diesel::result::Error::DatabaseError(
+                    diesel::result::DatabaseErrorKind::__Unknown,
+                    ref info,
+                ) if info.message().starts_with("syntax error in tsquery") => {
+                    QueryExecutionError::FulltextQueryInvalidSyntax(info.message().to_string())
+                }
+                _ => QueryExecutionError::ResolveEntitiesError(format!(
+                    "{}, query = {:?}",
+                    e,
+                    debug_query(&query_clone).to_string()
+                )),
+            })?;
+        if *graph::log::LOG_SQL_TIMING {
+            info!(
+                logger,
+                "Query timing (SQL)";
+                "query" => debug_query(&query_clone).to_string().replace("\n", "\t"),
+                "time_ms" => start.elapsed().as_millis(),
+                "entity_count" => values.len()
+            );
+        }
         values
             .into_iter()
             .map(|entity_data| {
@@ -699,6 +855,9 @@ impl Layout {
 
         let section = stopwatch.start_section("update_modification_clamp_range_query");
         ClampRangeQuery::new(table, &entity_type, &entity_keys, block).execute(conn)?;
+        for relation in &table.relations {
+            ClampRelationQuery::new(relation, &entity_keys, block).execute(conn)?;
+        }
         section.end();
 
         let _section = stopwatch.start_section("update_modification_insert_query");
@@ -709,6 +868,12 @@ impl Layout {
         // number of attributes each entity type has.
         // We add 1 to account for the `block_range` bind parameter
         let chunk_size = POSTGRES_MAX_PARAMETERS / (table.columns.len() + 1);
+        for relation in &table.relations {
+            let query = InsertRelationQuery::new(relation, entities, block)?;
+            if !query.is_empty() {
+                query.execute(conn)?;
+            }
+        }
         for chunk in entities.chunks_mut(chunk_size) {
             count += InsertQuery::new(table, chunk, block)?.execute(conn)?;
         }
@@ -727,7 +892,10 @@ impl Layout {
         let _section = stopwatch.start_section("delete_modification_clamp_range_query");
         let mut count = 0;
         for chunk in entity_ids.chunks(DELETE_OPERATION_CHUNK_SIZE) {
-            count += ClampRangeQuery::new(table, &entity_type, chunk, block).execute(conn)?
+            count += ClampRangeQuery::new(table, &entity_type, chunk, block).execute(conn)?;
+            for relation in &table.relations {
+                ClampRelationQuery::new(relation, chunk, block).execute(conn)?;
+            }
         }
         Ok(count)
     }
@@ -737,9 +905,9 @@ impl Layout {
         conn: &PgConnection,
         subgraph_id: &DeploymentHash,
         block: BlockNumber,
-    ) -> Result<(StoreEvent, i32), StoreError> {
+    ) -> Result<(StoreEvent, HashMap<EntityType, i32>), StoreError> {
         let mut changes: Vec<EntityChange> = Vec::new();
-        let mut count: i32 = 0;
+        let mut counts: HashMap<EntityType, i32> = HashMap::new();
 
         for table in self.tables.values() {
             // Remove all versions whose entire block range lies beyond
@@ -764,7 +932,7 @@ impl Layout {
             //   id in (unclamped - unset)  => delete (we now inserted)
             let deleted = removed.difference(&unclamped).count() as i32;
             let inserted = unclamped.difference(&removed).count() as i32;
-            count += inserted - deleted;
+            counts.insert(table.object.clone(), inserted - deleted);
             // EntityChange for versions we just deleted
             let deleted = removed
                 .into_iter()
@@ -780,8 +948,96 @@ impl Layout {
                 entity_type: table.object.clone(),
             });
             changes.extend(set);
+
+            // Join table rows are versioned together with the entity that
+            // declares the relation, and are reverted the same way
+            for relation in &table.relations {
+                RevertRelationRemoveQuery::new(relation, block).execute(conn)?;
+                RevertRelationClampQuery::new(relation, block - 1).execute(conn)?;
+            }
+        }
+        Ok((StoreEvent::new(changes), counts))
+    }
+
+    /// Add the changes in `counts` to the number of current entities that
+    /// we keep for each table in `subgraphs.table_stats`
+    pub fn update_entity_counts(
+        &self,
+        conn: &PgConnection,
+        counts: &HashMap<EntityType, i32>,
+    ) -> Result<(), StoreError> {
+        for (entity_type, count) in counts.iter().filter(|(_, count)| **count != 0) {
+            let table = self.table_for_entity(entity_type)?;
+            catalog::update_entity_count(
+                conn,
+                &self.site,
+                &table.name,
+                entity_type.as_str(),
+                *count as i64,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Count the current entities in `tables` from scratch, or in all
+    /// tables if `tables` is empty. Must be run inside a transaction
+    pub fn recount_entities(
+        &self,
+        conn: &PgConnection,
+        tables: &[&SqlName],
+    ) -> Result<(), StoreError> {
+        for table in self
+            .tables
+            .values()
+            .filter(|table| tables.is_empty() || tables.contains(&&table.name))
+        {
+            catalog::recount_entities(
+                conn,
+                &self.site,
+                &table.name,
+                table.object.as_str(),
+                &table.qualified_name,
+            )?;
         }
-        Ok((StoreEvent::new(changes), count))
+        Ok(())
+    }
+
+    /// Remove all entity versions that are only visible at blocks before
+    /// `earliest_block`, i.e., whose block range ended at or before it, and
+    /// rebuild the indexes of each table afterwards. The proof of indexing
+    /// is kept in full
+    pub fn prune(
+        &self,
+        conn: &PgConnection,
+        earliest_block: BlockNumber,
+        reporter: &mut dyn PruneReporter,
+    ) -> Result<(), StoreError> {
+        let mut tables: Vec<_> = self
+            .tables
+            .values()
+            .filter(|table| table.object.as_str() != POI_OBJECT)
+            .collect();
+        tables.sort_by(|a, b| a.name.as_str().cmp(b.name.as_str()));
+
+        for table in tables {
+            prune_table(
+                conn,
+                table.name.as_str(),
+                &table.qualified_name,
+                earliest_block,
+                reporter,
+            )?;
+            for relation in &table.relations {
+                prune_table(
+                    conn,
+                    relation.name.as_str(),
+                    &relation.qualified_name,
+                    earliest_block,
+                    reporter,
+                )?;
+            }
+        }
+        Ok(())
     }
 
     /// Revert the metadata (dynamic data sources and related entities) for
@@ -795,6 +1051,8 @@ impl Layout {
         block: BlockNumber,
     ) -> Result<(), StoreError> {
         crate::dynds::revert(conn, &subgraph, block)?;
+        crate::scheduled_handlers::revert(conn, &subgraph, block)?;
+        crate::data_source_contexts::revert(conn, &subgraph, block)?;
         crate::deployment::revert_subgraph_errors(conn, &subgraph, block)?;
 
         Ok(())
@@ -974,6 +1232,11 @@ pub struct Column {
     pub column_type: ColumnType,
     pub fulltext_fields: Option<HashSet<String>>,
     is_reference: bool,
+    /// The number of leading bytes that are indexed for `Bytes` attributes
+    /// declared with `@storage(prefix: N)`
+    bytes_prefix: Option<usize>,
+    /// Whether the attribute was declared with `@storage(external: true)`
+    is_external: bool,
 }
 
 impl Column {
@@ -1009,6 +1272,8 @@ impl Column {
             field_type: field.field_type.clone(),
             fulltext_fields: None,
             is_reference,
+            bytes_prefix: field.storage_prefix(),
+            is_external: field.storage_external(),
         })
     }
 
@@ -1023,6 +1288,8 @@ impl Column {
             column_type: ColumnType::TSVector(def.config.clone()),
             fulltext_fields: Some(def.included_fields.clone()),
             is_reference: false,
+            bytes_prefix: None,
+            is_external: false,
         })
     }
 
@@ -1067,6 +1334,26 @@ impl Column {
         named_type(&self.field_type) == "String" && !self.is_list()
     }
 
+    /// The number of leading bytes of this column that are indexed if
+    /// this is a `Bytes` attribute that was declared with
+    /// `@storage(prefix: N)`. All other `Bytes` attributes are indexed in
+    /// their entirety
+    pub fn bytes_prefix(&self) -> Option<usize> {
+        if named_type(&self.field_type) == BYTES_SCALAR && !self.is_list() && !self.is_primary_key()
+        {
+            self.bytes_prefix
+        } else {
+            None
+        }
+    }
+
+    /// Return `true` if large values of this column are stored out of line
+    /// without compression. That makes computing prefixes of them cheap at
+    /// the cost of using more disk space
+    pub fn is_external(&self) -> bool {
+        self.is_external
+    }
+
     pub fn is_assignable_from(&self, source: &Self, object: &EntityType) -> Option<String> {
         if !self.is_nullable() && source.is_nullable() {
             Some(format!(
@@ -1134,6 +1421,10 @@ pub struct Table {
 
     pub columns: Vec<Column>,
 
+    /// The join tables for the `@relation(through: ..)` fields of this
+    /// type. Those fields do not have a column in this table
+    pub relations: Vec<JoinTable>,
+
     /// This kind of entity behaves like an account in that it has a low
     /// ratio of distinct entities to overall number of rows because
     /// entities are updated frequently on average
@@ -1160,10 +1451,19 @@ impl Table {
         let columns = defn
             .fields
             .iter()
-            .filter(|field| !field.is_derived())
+            .filter(|field| !field.is_derived() && field.relation_through().is_none())
             .map(|field| Column::new(&table_name, field, catalog, enums, id_types))
             .chain(fulltexts.iter().map(|def| Column::new_fulltext(def)))
             .collect::<Result<Vec<Column>, StoreError>>()?;
+        let relations = defn
+            .fields
+            .iter()
+            .filter_map(|field| {
+                field
+                    .relation_through()
+                    .map(|through| JoinTable::new(defn, field, through, catalog, id_types))
+            })
+            .collect::<Result<Vec<JoinTable>, StoreError>>()?;
         let qualified_name = SqlName::qualified_name(&catalog.site.namespace, &table_name);
         let is_account_like = ACCOUNT_TABLES.contains(qualified_name.as_str());
         let table = Table {
@@ -1172,6 +1472,7 @@ impl Table {
             qualified_name,
             is_account_like,
             columns,
+            relations,
             position,
         };
         Ok(table)
@@ -1199,6 +1500,13 @@ impl Table {
     }
 
     fn can_copy_from(&self, source: &Self) -> Vec<String> {
+        let relations = self.relations.iter().filter_map(|drel| {
+            source
+                .relations
+                .iter()
+                .find(|srel| srel.field == drel.field)
+                .and_then(|srel| drel.is_assignable_from(srel))
+        });
         self.columns
             .iter()
             .filter_map(|dcol| match source.column(&dcol.name) {
@@ -1215,6 +1523,7 @@ impl Table {
                     }
                 }
             })
+            .chain(relations)
             .collect()
     }
 
@@ -1225,6 +1534,15 @@ impl Table {
             .expect("every table has a primary key")
     }
 
+    /// Find the join table for the `@relation` field `field`. The name
+    /// must be the GraphQL name of the field
+    pub fn relation(&self, field: &str) -> Result<&JoinTable, StoreError> {
+        self.relations
+            .iter()
+            .find(|relation| &relation.field == field)
+            .ok_or_else(|| StoreError::UnknownField(field.to_string()))
+    }
+
     /// Generate the DDL for one table, i.e. one `create table` statement
     /// and all `create index` statements for the table's columns
     ///
@@ -1252,6 +1570,18 @@ impl Table {
             block_range = BLOCK_RANGE_COLUMN
         )?;
 
+        // Store large values of attributes declared with
+        // `@storage(external: true)` out of line and uncompressed
+        for column in self.columns.iter().filter(|column| column.is_external()) {
+            writeln!(
+                out,
+                "alter table {}.{} alter column {} set storage external;",
+                layout.catalog.site.namespace,
+                self.name.quoted(),
+                column.name.quoted()
+            )?;
+        }
+
         // Add a BRIN index on the block_range bounds to exploit the fact
         // that block ranges closely correlate with where in a table an
         // entity appears physically. This index is incredibly efficient for
@@ -1312,9 +1642,12 @@ impl Table {
                 // Attributes that are plain strings are indexed with a BTree; but
                 // they can be too large for Postgres' limit on values that can go
                 // into a BTree. For those attributes, only index the first
-                // STRING_PREFIX_SIZE characters
+                // STRING_PREFIX_SIZE characters. Bytes attributes only have
+                // their prefix indexed if the schema asks for that
                 let index_expr = if column.is_text() {
                     format!("left({}, {})", column.name.quoted(), STRING_PREFIX_SIZE)
+                } else if let Some(prefix) = column.bytes_prefix() {
+                    format!("substring({}, 1, {})", column.name.quoted(), prefix)
                 } else {
                     column.name.quoted()
                 };
@@ -1339,10 +1672,227 @@ impl Table {
                 index_expr = index_expr,
             )?;
         }
+        writeln!(out)?;
+
+        for relation in &self.relations {
+            relation.as_ddl(out, layout)?;
+        }
+        Ok(())
+    }
+}
+
+/// The name of the column in a join table that holds the id of the entity
+/// that declares the `@relation` field
+pub(crate) const JOIN_PARENT_COLUMN: &str = "parent";
+
+/// The name of the column in a join table that holds the id of the entity
+/// the `@relation` field points to
+pub(crate) const JOIN_CHILD_COLUMN: &str = "child";
+
+/// The table that stores a many-to-many relation declared with
+/// `@relation(through: "..")`. Each row connects the entity that declares
+/// the field with one of the entities in the field's value. Rows are
+/// versioned with a block range in the same way as entity versions, so that
+/// the value of the field can be reconstructed for any block
+#[derive(Debug, Clone)]
+pub struct JoinTable {
+    /// The GraphQL name of the `@relation` field
+    pub field: String,
+    /// The name of the `@relation` field in SQL conventions; the value of
+    /// the field is returned under this name when an entity is loaded
+    pub column: SqlName,
+    /// The name of the database table, the snakecased version of the
+    /// `through` argument
+    pub name: SqlName,
+    /// The table name qualified with the schema in which the table lives
+    pub qualified_name: SqlName,
+    /// The type of the ids of the entities declaring the field
+    pub parent_type: ColumnType,
+    /// The type of the entities the field points to
+    pub child: EntityType,
+    /// The type of the ids of the entities the field points to
+    pub child_type: ColumnType,
+}
+
+impl JoinTable {
+    fn new(
+        defn: &s::ObjectType,
+        field: &s::Field,
+        through: &str,
+        catalog: &Catalog,
+        id_types: &IdTypeMap,
+    ) -> Result<JoinTable, StoreError> {
+        SqlName::check_valid_identifier(through, "relation")?;
+
+        let id_type = |entity_type: &EntityType| {
+            id_types
+                .get(entity_type)
+                .map(|id_type| ColumnType::from(*id_type))
+                .ok_or_else(|| StoreError::UnknownTable(entity_type.to_string()))
+        };
+
+        let name = SqlName::from(through);
+        let child = EntityType::new(named_type(&field.field_type).to_owned());
+        Ok(JoinTable {
+            field: field.name.clone(),
+            column: SqlName::from(&*field.name),
+            qualified_name: SqlName::qualified_name(&catalog.site.namespace, &name),
+            name,
+            parent_type: id_type(&EntityType::from(defn))?,
+            child_type: id_type(&child)?,
+            child,
+        })
+    }
+
+    /// Check whether the rows of the join table `source` can be copied
+    /// into this join table, and return an explanation if they can not
+    pub fn is_assignable_from(&self, source: &Self) -> Option<String> {
+        if self.child != source.child {
+            Some(format!(
+                "The relation {} points to {}, but in the source it points to {}",
+                self.field, self.child, source.child
+            ))
+        } else if self.parent_type != source.parent_type || self.child_type != source.child_type {
+            Some(format!(
+                "The ids in relation {} have different types than in the source",
+                self.field
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Return the name and type of the join table columns that hold the
+    /// ids of the parents and the children of a query; for a `reverse`
+    /// query, the parents are the entities the `@relation` field points to
+    pub(crate) fn link_columns(&self, reverse: bool) -> ((&str, &ColumnType), (&str, &ColumnType)) {
+        let parent = (JOIN_PARENT_COLUMN, &self.parent_type);
+        let child = (JOIN_CHILD_COLUMN, &self.child_type);
+        if reverse {
+            (child, parent)
+        } else {
+            (parent, child)
+        }
+    }
+
+    /// Generate the DDL for the join table. Both id columns are indexed
+    /// together with the other one so that the relation can be traversed
+    /// in either direction with an index lookup
+    fn as_ddl(&self, out: &mut String, layout: &Layout) -> fmt::Result {
+        write!(
+            out,
+            "create table {schema_name}.{table_name} (\
+             \n        {vid}                  bigserial primary key,\
+             \n        {parent:20} {parent_type} not null,\
+             \n        {child:20} {child_type} not null,\
+             \n        {block_range}          int4range not null\n);\n",
+            schema_name = layout.catalog.site.namespace,
+            table_name = self.name.quoted(),
+            vid = VID_COLUMN,
+            parent = JOIN_PARENT_COLUMN,
+            parent_type = self.parent_type.sql_type(),
+            child = JOIN_CHILD_COLUMN,
+            child_type = self.child_type.sql_type(),
+            block_range = BLOCK_RANGE_COLUMN
+        )?;
+
+        write!(out,"create index brin_{table_name}\n    \
+                    on {schema_name}.{table_name}\n \
+                       using brin(lower(block_range), coalesce(upper(block_range), {block_max}), vid);\n",
+            table_name = self.name,
+            schema_name = layout.catalog.site.namespace,
+            block_max = BLOCK_NUMBER_MAX)?;
+
+        write!(
+            out,
+            "create index {table_name}_block_range_closed\n    \
+                     on {schema_name}.{table_name}(coalesce(upper(block_range), {block_max}))\n \
+                     where coalesce(upper(block_range), {block_max}) < {block_max};\n",
+            table_name = self.name,
+            schema_name = layout.catalog.site.namespace,
+            block_max = BLOCK_NUMBER_MAX
+        )?;
+
+        for (first, second) in &[
+            (JOIN_PARENT_COLUMN, JOIN_CHILD_COLUMN),
+            (JOIN_CHILD_COLUMN, JOIN_PARENT_COLUMN),
+        ] {
+            write!(
+                out,
+                "create index {table_name}_{first}_{second}\n    \
+                 on {schema_name}.\"{table_name}\" using btree({first}, {second});\n",
+                table_name = self.name,
+                schema_name = layout.catalog.site.namespace,
+                first = first,
+                second = second,
+            )?;
+        }
         writeln!(out)
     }
 }
 
+/// Delete the versions in `qualified_name` whose block range ended at or
+/// before `earliest_block` in batches of `vid`s so that no single statement
+/// runs for very long, then rebuild the table's indexes since they still
+/// take up the space of the deleted versions
+fn prune_table(
+    conn: &PgConnection,
+    name: &str,
+    qualified_name: &SqlName,
+    earliest_block: BlockNumber,
+    reporter: &mut dyn PruneReporter,
+) -> Result<(), StoreError> {
+    #[derive(QueryableByName)]
+    struct VidRange {
+        #[sql_type = "diesel::sql_types::Nullable<diesel::sql_types::BigInt>"]
+        min_vid: Option<i64>,
+        #[sql_type = "diesel::sql_types::Nullable<diesel::sql_types::BigInt>"]
+        max_vid: Option<i64>,
+    }
+
+    reporter.start_table(name);
+    let range = diesel::sql_query(format!(
+        "select min({vid}) as min_vid, max({vid}) as max_vid from {qualified_name}",
+        vid = VID_COLUMN,
+        qualified_name = qualified_name
+    ))
+    .get_result::<VidRange>(conn)?;
+
+    let mut total = 0;
+    if let (Some(min_vid), Some(max_vid)) = (range.min_vid, range.max_vid) {
+        let query = format!(
+            "delete from {qualified_name} \
+              where {vid} >= $1 and {vid} < $2 \
+                and coalesce(upper({block_range}), {block_max}) <= $3",
+            qualified_name = qualified_name,
+            vid = VID_COLUMN,
+            block_range = BLOCK_RANGE_COLUMN,
+            block_max = BLOCK_NUMBER_MAX
+        );
+        let mut start = min_vid;
+        while start <= max_vid {
+            let end = start + PRUNE_BATCH_SIZE;
+            let rows = diesel::sql_query(&query)
+                .bind::<diesel::sql_types::BigInt, _>(start)
+                .bind::<diesel::sql_types::BigInt, _>(end)
+                .bind::<diesel::sql_types::Integer, _>(earliest_block)
+                .execute(conn)?;
+            total += rows;
+            let done = ((end - min_vid) as f64 / (max_vid - min_vid + 1) as f64).min(1.0);
+            reporter.prune_batch(name, rows, done);
+            start = end;
+        }
+    }
+
+    // `reindex concurrently` can not run in a transaction block, and
+    // therefore needs to be a statement of its own
+    reporter.start_reindex(name);
+    conn.batch_execute(&format!("reindex table concurrently {}", qualified_name))?;
+    conn.batch_execute(&format!("analyze {}", qualified_name))?;
+    reporter.finish_table(name, total);
+    Ok(())
+}
+
 /// Return the enclosed named type for a field type, i.e., the type after
 /// stripping List and NonNull.
 fn named_type(field_type: &q::Type) -> &str {
@@ -1547,6 +2097,10 @@ mod tests {
         let layout = test_layout(FORWARD_ENUM_GQL);
         let sql = layout.as_ddl().expect("Failed to generate DDL");
         assert_eq!(FORWARD_ENUM_SQL, sql);
+
+        let layout = test_layout(STORAGE_GQL);
+        let sql = layout.as_ddl().expect("Failed to generate DDL");
+        assert_eq!(STORAGE_DDL, sql);
     }
 
     #[test]
@@ -1561,6 +2115,26 @@ mod tests {
         assert!(column.is_enum());
     }
 
+    #[test]
+    fn relation_uses_join_table() {
+        let layout = test_layout(RELATION_GQL);
+        let table = layout
+            .table(&SqlName::from("author"))
+            .expect("author table exists");
+        assert!(table.column(&SqlName::from("books")).is_none());
+
+        let relation = table.relation("books").expect("books relation exists");
+        assert_eq!(SqlName::from("authorship"), relation.name);
+        assert_eq!(EntityType::new("Book".to_string()), relation.child);
+        assert_eq!(ColumnType::String, relation.parent_type);
+        assert_eq!(ColumnType::Bytes, relation.child_type);
+
+        let sql = layout.as_ddl().expect("Failed to generate DDL");
+        assert!(sql.contains("create table sgd0815.\"authorship\" ("));
+        assert!(sql.contains("create index authorship_parent_child"));
+        assert!(sql.contains("create index authorship_child_parent"));
+    }
+
     #[test]
     fn can_copy_from() {
         let source = test_layout(THING_GQL);
@@ -1622,6 +2196,20 @@ mod tests {
             ],
             dest.can_copy_from(&source)
         );
+        // We can not point a relation at a different type
+        let source = test_layout(
+            "type A { id: ID!, bs: [B!]! @relation(through: \"AtoB\") } \
+             type B { id: ID! } type C { id: ID! }",
+        );
+        let dest = test_layout(
+            "type A { id: ID!, bs: [C!]! @relation(through: \"AtoB\") } \
+             type B { id: ID! } type C { id: ID! }",
+        );
+        assert_eq!(
+            vec!["The relation bs points to C, but in the source it points to B"],
+            dest.can_copy_from(&source)
+        );
+        assert!(source.can_copy_from(&source).is_empty());
     }
 
     const THING_GQL: &str = "
@@ -2000,6 +2588,55 @@ create index attr_2_1_habitat_most_common
 create index attr_2_2_habitat_dwellers
     on sgd0815.\"habitat\" using gin(\"dwellers\");
 
+";
+
+    const RELATION_GQL: &str = "
+        type Author @entity {
+            id: ID!
+            books: [Book!]! @relation(through: \"Authorship\")
+        }
+
+        type Book @entity {
+            id: Bytes!
+            authors: [Author!]! @derivedFrom(field: \"books\")
+        }";
+
+    const STORAGE_GQL: &str = "
+type Call @entity {
+    id: ID!,
+    data: Bytes!,
+    input: Bytes! @storage(prefix: 64, external: true),
+    memo: String @storage(external: true)
+}
+";
+
+    const STORAGE_DDL: &str = "create table sgd0815.\"call\" (
+        \"id\"                 text not null,
+        \"data\"               bytea not null,
+        \"input\"              bytea not null,
+        \"memo\"               text,
+
+        vid                  bigserial primary key,
+        block_range          int4range not null,
+        exclude using gist   (id with =, block_range with &&)
+);
+alter table sgd0815.\"call\" alter column \"input\" set storage external;
+alter table sgd0815.\"call\" alter column \"memo\" set storage external;
+create index brin_call
+    on sgd0815.call
+ using brin(lower(block_range), coalesce(upper(block_range), 2147483647), vid);
+create index call_block_range_closed
+    on sgd0815.call(coalesce(upper(block_range), 2147483647))
+ where coalesce(upper(block_range), 2147483647) < 2147483647;
+create index attr_0_0_call_id
+    on sgd0815.\"call\" using btree(\"id\");
+create index attr_0_1_call_data
+    on sgd0815.\"call\" using btree(\"data\");
+create index attr_0_2_call_input
+    on sgd0815.\"call\" using btree(substring(\"input\", 1, 64));
+create index attr_0_3_call_memo
+    on sgd0815.\"call\" using btree(left(\"memo\", 256));
+
 ";
 
     const FORWARD_ENUM_GQL: &str = "
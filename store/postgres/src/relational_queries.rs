@@ -15,11 +15,11 @@ use lazy_static::lazy_static;
 
 use graph::prelude::{
     anyhow, r, serde_json, Attribute, BlockNumber, ChildMultiplicity, Entity, EntityCollection,
-    EntityFilter, EntityKey, EntityLink, EntityOrder, EntityRange, EntityWindow, ParentLink,
-    QueryExecutionError, StoreError, Value,
+    EntityFilter, EntityHistory, EntityKey, EntityLink, EntityOrder, EntityRange, EntityWindow,
+    JoinLink, OrderDirection, ParentLink, QueryExecutionError, StoreError, Value, ValueType,
 };
 use graph::{
-    components::store::{AttributeNames, EntityType},
+    components::store::{AttributeNames, EntityType, VALID_FROM_ATTRIBUTE, VALID_TO_ATTRIBUTE},
     data::{schema::FulltextAlgorithm, store::scalar},
 };
 use itertools::Itertools;
@@ -32,11 +32,15 @@ use std::iter::FromIterator;
 use std::str::FromStr;
 
 use crate::relational::{
-    Column, ColumnType, IdType, Layout, SqlName, Table, PRIMARY_KEY_COLUMN, STRING_PREFIX_SIZE,
+    Column, ColumnType, IdType, JoinTable, Layout, SqlName, Table, JOIN_CHILD_COLUMN,
+    JOIN_PARENT_COLUMN, PRIMARY_KEY_COLUMN, STRING_PREFIX_SIZE, VID_COLUMN,
 };
 use crate::sql_value::SqlValue;
 use crate::{
-    block_range::{BlockRange, BlockRangeContainsClause, BLOCK_RANGE_COLUMN, BLOCK_RANGE_CURRENT},
+    block_range::{
+        BlockRange, BlockRangeContainsClause, BlockRangeOverlapsClause, BLOCK_RANGE_COLUMN,
+        BLOCK_RANGE_CURRENT,
+    },
     primary::Namespace,
 };
 
@@ -269,6 +273,69 @@ impl ForeignKeyClauses for Column {
     }
 }
 
+/// One of the id columns of a `JoinTable`
+struct JoinColumn<'a> {
+    name: &'a str,
+    column_type: &'a ColumnType,
+}
+
+impl<'a> JoinColumn<'a> {
+    fn new((name, column_type): (&'a str, &'a ColumnType)) -> Self {
+        JoinColumn { name, column_type }
+    }
+}
+
+impl<'a> ForeignKeyClauses for JoinColumn<'a> {
+    fn column_type(&self) -> &ColumnType {
+        self.column_type
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+}
+
+/// Generate the value of each `@relation` field of `table` as an additional
+/// JSONB object that can be appended to the entity data, i.e.
+///
+///   || jsonb_build_object('{field}', array(select j.child
+///                                            from {join_table} j
+///                                           where j.parent = {alias}.id
+///                                             and j.block_range @> $block
+///                                           order by j.vid))
+///
+/// Ordering by `vid` returns the ids in the order in which they were
+/// stored in the list
+fn relations_as_jsonb(
+    table: &Table,
+    alias: &str,
+    block: &BlockNumber,
+    out: &mut AstPass<Pg>,
+) -> QueryResult<()> {
+    for relation in &table.relations {
+        out.push_sql(" || jsonb_build_object('");
+        out.push_sql(relation.column.as_str());
+        out.push_sql("', array(select j.");
+        out.push_sql(JOIN_CHILD_COLUMN);
+        out.push_sql(" from ");
+        out.push_sql(relation.qualified_name.as_str());
+        out.push_sql(" j where j.");
+        out.push_sql(JOIN_PARENT_COLUMN);
+        out.push_sql(" = ");
+        out.push_sql(alias);
+        out.push_sql(".");
+        out.push_sql(PRIMARY_KEY_COLUMN);
+        out.push_sql(" and j.");
+        out.push_sql(BLOCK_RANGE_COLUMN);
+        out.push_sql(" @> ");
+        out.push_bind_param::<Integer, _>(block)?;
+        out.push_sql(" order by j.");
+        out.push_sql(VID_COLUMN);
+        out.push_sql("))");
+    }
+    Ok(())
+}
+
 pub trait FromEntityData {
     type Value: FromColumnValue;
 
@@ -347,15 +414,18 @@ pub trait FromColumnValue: Sized {
             },
             (j::Number(number), ColumnType::BigDecimal) => {
                 let s = number.to_string();
-                scalar::BigDecimal::from_str(s.as_str())
-                    .map(Self::from_big_decimal)
-                    .map_err(|e| {
-                        StoreError::Unknown(anyhow!(
-                            "failed to convert {} to BigDecimal: {}",
-                            number,
-                            e
-                        ))
-                    })
+                scalar::BigDecimal::from_str_with_precision(
+                    s.as_str(),
+                    &scalar::BigDecimalPrecision::stored(),
+                )
+                .map(Self::from_big_decimal)
+                .map_err(|e| {
+                    StoreError::Unknown(anyhow!(
+                        "failed to convert {} to BigDecimal: {}",
+                        number,
+                        e
+                    ))
+                })
             }
             (j::Number(number), ColumnType::BigInt) => Self::from_big_int(number),
             (j::Number(number), column_type) => Err(StoreError::Unknown(anyhow!(
@@ -503,6 +573,16 @@ impl EntityData {
                     if key == "g$parent_id" {
                         let value = T::Value::from_column_value(&ColumnType::String, json)?;
                         out.insert_entity_data("g$parent_id".to_owned(), value);
+                    } else if key == VALID_FROM_ATTRIBUTE || key == VALID_TO_ATTRIBUTE {
+                        let value = T::Value::from_column_value(&ColumnType::Int, json)?;
+                        out.insert_entity_data(key, value);
+                    } else if let Some(relation) = table
+                        .relations
+                        .iter()
+                        .find(|relation| relation.column.as_str() == key)
+                    {
+                        let value = T::Value::from_column_value(&relation.child_type, json)?;
+                        out.insert_entity_data(relation.field.clone(), value);
                     } else if let Some(column) = table.column(&SqlName::verbatim(key)) {
                         let value = T::Value::from_column_value(&column.column_type, json)?;
                         if !value.is_null() {
@@ -779,6 +859,61 @@ impl<'a> QueryFragment<Pg> for PrefixComparison<'a> {
     }
 }
 
+/// Check that the bytea column `column` is one of `values`. Bytea
+/// attributes declared with `@storage(prefix: N)` are only indexed on their
+/// first `N` bytes, and we write `column in (values)` as
+/// `substring(column, 1, N) in (substring(values, 1, N)) and column in (values)`
+/// so that Postgres can use that index to find candidate rows
+#[derive(Constructor)]
+struct BytesPrefixComparison<'a> {
+    column: &'a Column,
+    prefix: usize,
+    values: Vec<&'a Value>,
+}
+
+impl<'a> BytesPrefixComparison<'a> {
+    fn push_prefix(&self, value: Option<&Value>, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.push_sql("substring(");
+        match value {
+            Some(value) => QueryValue(value, &self.column.column_type).walk_ast(out.reborrow())?,
+            None => out.push_identifier(self.column.name.as_str())?,
+        }
+        out.push_sql(", 1, ");
+        out.push_sql(&self.prefix.to_string());
+        out.push_sql(")");
+        Ok(())
+    }
+
+    fn push_values(&self, prefix: bool, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.push_sql(" in (");
+        for (i, value) in self.values.iter().enumerate() {
+            if i > 0 {
+                out.push_sql(", ");
+            }
+            if prefix {
+                self.push_prefix(Some(value), out.reborrow())?;
+            } else {
+                QueryValue(value, &self.column.column_type).walk_ast(out.reborrow())?;
+            }
+        }
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
+impl<'a> QueryFragment<Pg> for BytesPrefixComparison<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.push_sql("(");
+        self.push_prefix(None, out.reborrow())?;
+        self.push_values(true, out.reborrow())?;
+        out.push_sql(" and ");
+        out.push_identifier(self.column.name.as_str())?;
+        self.push_values(false, out.reborrow())?;
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
 /// A `QueryFilter` adds the conditions represented by the `filter` to
 /// the `where` clause of a SQL query. The attributes mentioned in
 /// the `filter` must all come from the given `table`, which is used to
@@ -807,6 +942,8 @@ impl<'a> QueryFilter<'a> {
 
             Contains(attr, _)
             | NotContains(attr, _)
+            | ContainsAny(attr, _)
+            | ContainsAll(attr, _)
             | Equal(attr, _)
             | Not(attr, _)
             | GreaterThan(attr, _)
@@ -925,6 +1062,43 @@ impl<'a> QueryFilter<'a> {
         Ok(())
     }
 
+    /// Compare a list attribute with a list of values using the array
+    /// operators `&&` (`any`) and `@>` (all) so that the comparison can use
+    /// the GIN index on the attribute
+    fn array_overlap(
+        &self,
+        attribute: &Attribute,
+        value: &Value,
+        any: bool,
+        mut out: AstPass<Pg>,
+    ) -> QueryResult<()> {
+        let column = self.column(attribute);
+
+        match value {
+            Value::List(_) if column.is_list() => {
+                out.push_identifier(column.name.as_str())?;
+                if any {
+                    out.push_sql(" && ");
+                } else {
+                    out.push_sql(" @> ");
+                }
+                QueryValue(value, &column.column_type).walk_ast(out)?;
+            }
+            _ => {
+                let filter = match any {
+                    true => "contains_any",
+                    false => "contains_all",
+                };
+                return Err(UnsupportedFilter {
+                    filter: filter.to_owned(),
+                    value: value.clone(),
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+
     fn equals(
         &self,
         attribute: &Attribute,
@@ -933,9 +1107,14 @@ impl<'a> QueryFilter<'a> {
         mut out: AstPass<Pg>,
     ) -> QueryResult<()> {
         let column = self.column(attribute);
+        let bytes_prefix = column
+            .bytes_prefix()
+            .filter(|_| op == Comparison::Equal && matches!(value, Value::Bytes(_)));
 
         if column.is_text() && value.is_string() {
             PrefixComparison::new(op, column, value).walk_ast(out.reborrow())?;
+        } else if let Some(prefix) = bytes_prefix {
+            BytesPrefixComparison::new(column, prefix, vec![value]).walk_ast(out.reborrow())?;
         } else if column.is_fulltext() {
             out.push_identifier(column.name.as_str())?;
             out.push_sql(Comparison::Match.as_str());
@@ -1044,7 +1223,14 @@ impl<'a> QueryFilter<'a> {
             out.push_sql(" or ");
         }
 
-        if have_non_nulls {
+        let bytes_prefix = column.bytes_prefix().filter(|_| have_non_nulls && !negated);
+        if let Some(prefix) = bytes_prefix {
+            let values = values
+                .iter()
+                .filter(|value| value != &&Value::Null)
+                .collect();
+            BytesPrefixComparison::new(column, prefix, values).walk_ast(out.reborrow())?;
+        } else if have_non_nulls {
             if column.is_text()
                 && values.iter().all(|v| match v {
                     Value::String(s) => s.len() <= STRING_PREFIX_SIZE - 1,
@@ -1136,6 +1322,8 @@ impl<'a> QueryFragment<Pg> for QueryFilter<'a> {
 
             Contains(attr, value) => self.contains(attr, value, false, out)?,
             NotContains(attr, value) => self.contains(attr, value, true, out)?,
+            ContainsAny(attr, value) => self.array_overlap(attr, value, true, out)?,
+            ContainsAll(attr, value) => self.array_overlap(attr, value, false, out)?,
 
             Equal(attr, value) => self.equals(attr, value, c::Equal, out)?,
             Not(attr, value) => self.equals(attr, value, c::NotEqual, out)?,
@@ -1175,11 +1363,13 @@ impl<'a> QueryFragment<Pg> for FindQuery<'a> {
         out.unsafe_to_cache_prepared();
 
         // Generate
-        //    select '..' as entity, to_jsonb(e.*) as data
+        //    select '..' as entity, to_jsonb(e.*) {relations} as data
         //      from schema.table e where id = $1
         out.push_sql("select ");
         out.push_bind_param::<Text, _>(&self.table.object.as_str())?;
-        out.push_sql(" as entity, to_jsonb(e.*) as data\n");
+        out.push_sql(" as entity, to_jsonb(e.*)");
+        relations_as_jsonb(self.table, "e", &self.block, &mut out)?;
+        out.push_sql(" as data\n");
         out.push_sql("  from ");
         out.push_sql(self.table.qualified_name.as_str());
         out.push_sql(" e\n where ");
@@ -1231,7 +1421,9 @@ impl<'a> QueryFragment<Pg> for FindManyQuery<'a> {
             }
             out.push_sql("select ");
             out.push_bind_param::<Text, _>(&table.object.as_str())?;
-            out.push_sql(" as entity, to_jsonb(e.*) as data\n");
+            out.push_sql(" as entity, to_jsonb(e.*)");
+            relations_as_jsonb(table, "e", &self.block, &mut out)?;
+            out.push_sql(" as data\n");
             out.push_sql("  from ");
             out.push_sql(table.qualified_name.as_str());
             out.push_sql(" e\n where ");
@@ -1292,12 +1484,7 @@ impl<'a> InsertQuery<'a> {
                     None => (),
                 }
                 if !column.is_nullable() && !entity.contains_key(&column.field) {
-                    return Err(StoreError::QueryExecutionError(format!(
-                    "can not insert entity {}[{}] since value for non-nullable attribute {} is missing. \
-                     To fix this, mark the attribute as nullable in the GraphQL schema or change the \
-                     mapping code to always set this attribute.",
-                    entity_key.entity_type, entity_key.entity_id, column.field
-                )));
+                    return Err(missing_value_error(entity_key, column));
                 }
             }
         }
@@ -1328,6 +1515,15 @@ impl<'a> InsertQuery<'a> {
     }
 }
 
+fn missing_value_error(entity_key: &EntityKey, column: &Column) -> StoreError {
+    StoreError::QueryExecutionError(format!(
+        "can not insert entity {}[{}] since value for non-nullable attribute {} is missing. \
+         To fix this, mark the attribute as nullable in the GraphQL schema or change the \
+         mapping code to always set this attribute.",
+        entity_key.entity_type, entity_key.entity_id, column.field
+    ))
+}
+
 impl<'a> QueryFragment<Pg> for InsertQuery<'a> {
     fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
         out.unsafe_to_cache_prepared();
@@ -1400,6 +1596,136 @@ impl<'a> LoadQuery<PgConnection, ReturnedEntityData> for InsertQuery<'a> {
 
 impl<'a, Conn> RunQueryDsl<Conn> for InsertQuery<'a> {}
 
+/// The `COPY` statement and its CSV data that insert new versions of
+/// entities into `table`, each valid from its own block on. This writes
+/// the same rows as `InsertQuery`, but `COPY` can not compute values, and
+/// it is therefore only possible for tables without fulltext columns and
+/// without join tables; see `CopyInsert::is_possible`
+pub struct CopyInsert<'a> {
+    table: &'a Table,
+    entities: &'a [(&'a EntityKey, &'a Entity, BlockNumber)],
+}
+
+impl<'a> CopyInsert<'a> {
+    pub fn new(
+        table: &'a Table,
+        entities: &'a [(&'a EntityKey, &'a Entity, BlockNumber)],
+    ) -> Result<Self, StoreError> {
+        if !Self::is_possible(table) {
+            return Err(StoreError::ConstraintViolation(format!(
+                "entities of type {} can not be inserted with COPY",
+                table.object
+            )));
+        }
+        for (entity_key, entity, _) in entities {
+            for column in &table.columns {
+                if !column.is_nullable() && !entity.contains_key(&column.field) {
+                    return Err(missing_value_error(entity_key, column));
+                }
+            }
+        }
+        Ok(CopyInsert { table, entities })
+    }
+
+    pub fn is_possible(table: &Table) -> bool {
+        table.relations.is_empty()
+            && !table
+                .columns
+                .iter()
+                .any(|column| matches!(column.column_type, ColumnType::TSVector(_)))
+    }
+
+    /// The `copy .. from stdin` statement for the data
+    pub fn statement(&self) -> String {
+        let columns = self
+            .table
+            .columns
+            .iter()
+            .map(|column| column.name.quoted())
+            .chain(std::iter::once(format!("\"{}\"", BLOCK_RANGE_COLUMN)))
+            .join(", ");
+        format!(
+            "copy {}({}) from stdin (format csv)",
+            self.table.qualified_name.as_str(),
+            columns
+        )
+    }
+
+    /// The rows to insert in CSV format, with one line per entity
+    pub fn data(&self) -> Result<Vec<u8>, StoreError> {
+        let mut data = String::new();
+        for (_, entity, block) in self.entities {
+            for column in &self.table.columns {
+                if let Some(value) = entity.get(&column.field) {
+                    data.push_str(&copy_value(value, &column.column_type)?);
+                }
+                data.push(',');
+            }
+            data.push_str(&format!("\"[{},)\"\n", block));
+        }
+        Ok(data.into_bytes())
+    }
+}
+
+/// Format `value` for a CSV `COPY`. An empty field is `null`; everything
+/// that could contain a quote or a separator is quoted
+fn copy_value(value: &Value, column_type: &ColumnType) -> Result<String, StoreError> {
+    fn quote(s: &str) -> String {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    }
+
+    fn bytes(s: &str) -> Result<String, StoreError> {
+        let bytes = scalar::Bytes::from_str(s)
+            .map_err(|e| StoreError::Unknown(anyhow!("invalid bytes value `{}`: {}", s, e)))?;
+        Ok(format!("\\x{}", hex::encode(bytes.as_slice())))
+    }
+
+    /// The text of a scalar in the form the array and the scalar input
+    /// functions of Postgres take
+    fn scalar(value: &Value, column_type: &ColumnType) -> Result<Option<String>, StoreError> {
+        let text = match value {
+            Value::String(s) => match column_type {
+                ColumnType::Bytes | ColumnType::BytesId => bytes(s)?,
+                _ => s.clone(),
+            },
+            Value::Int(i) => i.to_string(),
+            Value::BigDecimal(d) => d.to_string(),
+            Value::BigInt(i) => i.to_string(),
+            Value::Bool(true) => "t".to_string(),
+            Value::Bool(false) => "f".to_string(),
+            Value::Bytes(b) => format!("\\x{}", hex::encode(b.as_slice())),
+            Value::Null => return Ok(None),
+            Value::List(_) => {
+                return Err(StoreError::Unknown(anyhow!(
+                    "nested lists can not be stored: {}",
+                    value
+                )))
+            }
+        };
+        Ok(Some(text))
+    }
+
+    match value {
+        Value::List(values) => {
+            let elements = values
+                .iter()
+                .map(|value| {
+                    scalar(value, column_type).map(|text| match text {
+                        Some(text) => {
+                            format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+                        }
+                        None => "NULL".to_string(),
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(quote(&format!("{{{}}}", elements.join(","))))
+        }
+        _ => Ok(scalar(value, column_type)?
+            .map(|text| quote(&text))
+            .unwrap_or_default()),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ConflictingEntityQuery<'a> {
     _layout: &'a Layout,
@@ -1527,16 +1853,34 @@ impl ParentIds {
 enum TableLink<'a> {
     Direct(&'a Column, ChildMultiplicity),
     Parent(ParentIds),
+    /// The join table, and whether the parents are the entities the
+    /// `@relation` field points to
+    Through(&'a JoinTable, bool, ChildMultiplicity),
 }
 
 impl<'a> TableLink<'a> {
-    fn new(child_table: &'a Table, link: EntityLink) -> Result<Self, QueryExecutionError> {
+    fn new(
+        layout: &'a Layout,
+        child_table: &'a Table,
+        link: EntityLink,
+    ) -> Result<Self, QueryExecutionError> {
         match link {
             EntityLink::Direct(attribute, multiplicity) => {
                 let column = child_table.column_for_field(attribute.name())?;
                 Ok(TableLink::Direct(column, multiplicity))
             }
             EntityLink::Parent(parent_link) => Ok(TableLink::Parent(ParentIds::new(parent_link))),
+            EntityLink::Through(
+                JoinLink {
+                    owner,
+                    field,
+                    reverse,
+                },
+                multiplicity,
+            ) => {
+                let relation = layout.table_for_entity(&owner)?.relation(&field)?;
+                Ok(TableLink::Through(relation, reverse, multiplicity))
+            }
         }
     }
 }
@@ -1630,7 +1974,7 @@ impl<'a> FilterWindow<'a> {
         let query_filter = query_filter
             .map(|filter| QueryFilter::new(filter, table))
             .transpose()?;
-        let link = TableLink::new(table, link)?;
+        let link = TableLink::new(layout, table, link)?;
         Ok(FilterWindow {
             table,
             query_filter,
@@ -1872,6 +2216,97 @@ impl<'a> FilterWindow<'a> {
         Ok(())
     }
 
+    /// Generate the condition that the child `c` is connected to the parent
+    /// `p` through the join table `relation`
+    ///
+    ///   exists (select 1 from {join_table} j
+    ///            where j.{parent_column} = p.id and j.{child_column} = c.id
+    ///              and j.block_range @> $block)
+    fn join_table_exists(
+        relation: &JoinTable,
+        reverse: bool,
+        block: &BlockNumber,
+        out: &mut AstPass<Pg>,
+    ) -> QueryResult<()> {
+        let ((parent_column, _), (child_column, _)) = relation.link_columns(reverse);
+        out.push_sql(" and exists (select 1 from ");
+        out.push_sql(relation.qualified_name.as_str());
+        out.push_sql(" j where j.");
+        out.push_sql(parent_column);
+        out.push_sql(" = p.id and j.");
+        out.push_sql(child_column);
+        out.push_sql(" = c.id and j.");
+        out.push_sql(BLOCK_RANGE_COLUMN);
+        out.push_sql(" @> ");
+        out.push_bind_param::<Integer, _>(block)?;
+        out.push_sql(")");
+        Ok(())
+    }
+
+    fn children_type_j(
+        &self,
+        relation: &JoinTable,
+        reverse: bool,
+        limit: ParentLimit<'_>,
+        block: BlockNumber,
+        out: &mut AstPass<Pg>,
+    ) -> QueryResult<()> {
+        // Generate
+        //      from unnest({parent_ids}) as p(id)
+        //           cross join lateral
+        //           (select {column names}
+        //              from children c
+        //             where exists (.. c linked to p in the join table ..)
+        //               and .. other conditions on c ..
+        //             order by c.{sort_key}
+        //             limit {first} offset {skip}) c
+        //     order by c.{sort_key}
+
+        let (parent_column, _) = relation.link_columns(reverse);
+        out.push_sql("\n/* children_type_j */  from unnest(");
+        JoinColumn::new(parent_column).bind_ids(&self.ids, out)?;
+        out.push_sql(") as p(id) cross join lateral (select ");
+        write_column_names(&self.column_names, &self.table, out)?;
+        out.push_sql(" from ");
+        out.push_sql(self.table.qualified_name.as_str());
+        out.push_sql(" c where ");
+        BlockRangeContainsClause::new(&self.table, "c.", block).walk_ast(out.reborrow())?;
+        limit.filter(out);
+        Self::join_table_exists(relation, reverse, &block, out)?;
+        self.and_filter(out.reborrow())?;
+        limit.restrict(out)?;
+        out.push_sql(") c");
+        Ok(())
+    }
+
+    fn child_type_j(
+        &self,
+        relation: &JoinTable,
+        reverse: bool,
+        limit: ParentLimit<'_>,
+        block: BlockNumber,
+        out: &mut AstPass<Pg>,
+    ) -> QueryResult<()> {
+        // Generate
+        //      from unnest({parent_ids}) as p(id), children c
+        //     where exists (.. c linked to p in the join table ..)
+        //       and .. other conditions on c ..
+        //     limit {parent_ids.len} + 1
+
+        let (parent_column, _) = relation.link_columns(reverse);
+        out.push_sql("\n/* child_type_j */  from unnest(");
+        JoinColumn::new(parent_column).bind_ids(&self.ids, out)?;
+        out.push_sql(") as p(id), ");
+        out.push_sql(self.table.qualified_name.as_str());
+        out.push_sql(" c where ");
+        BlockRangeContainsClause::new(&self.table, "c.", block).walk_ast(out.reborrow())?;
+        limit.filter(out);
+        Self::join_table_exists(relation, reverse, &block, out)?;
+        self.and_filter(out.reborrow())?;
+        limit.single_limit(self.ids.len(), out);
+        Ok(())
+    }
+
     fn children(
         &self,
         limit: ParentLimit<'_>,
@@ -1899,6 +2334,14 @@ impl<'a> FilterWindow<'a> {
             TableLink::Parent(ParentIds::Scalar(child_ids)) => {
                 self.child_type_d(child_ids, limit, block, &mut out)
             }
+            TableLink::Through(relation, reverse, multiplicity) => match multiplicity {
+                ChildMultiplicity::Many => {
+                    self.children_type_j(relation, *reverse, limit, block, &mut out)
+                }
+                ChildMultiplicity::Single => {
+                    self.child_type_j(relation, *reverse, limit, block, &mut out)
+                }
+            },
         }
     }
 
@@ -1989,7 +2432,7 @@ impl<'a> FilterCollection<'a> {
         }
     }
 
-    fn first_table(&self) -> Option<&Table> {
+    pub(crate) fn first_table(&self) -> Option<&Table> {
         match self {
             FilterCollection::All(entities) => entities.first().map(|pair| pair.0),
             FilterCollection::SingleWindow(window) => Some(window.table),
@@ -2008,7 +2451,7 @@ impl<'a> FilterCollection<'a> {
 
 /// Convenience to pass the name of the column to order by around. If `name`
 /// is `None`, the sort key should be ignored
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum SortKey<'a> {
     None,
     /// Order by `id asc`
@@ -2021,6 +2464,12 @@ pub enum SortKey<'a> {
         value: Option<&'a str>,
         direction: &'static str,
     },
+    /// Order by several other columns, each with its own direction, and
+    /// then by `id` in `id_direction`; `columns` will never contain `id`
+    Keys {
+        columns: Vec<(&'a Column, &'static str)>,
+        id_direction: &'static str,
+    },
 }
 
 impl<'a> SortKey<'a> {
@@ -2070,9 +2519,51 @@ impl<'a> SortKey<'a> {
             }
         }
 
+        fn with_keys<'a>(
+            table: &'a Table,
+            keys: Vec<(String, ValueType, OrderDirection)>,
+        ) -> Result<SortKey<'a>, QueryExecutionError> {
+            let mut columns = Vec::new();
+            for (attribute, _, direction) in keys {
+                let direction = match direction {
+                    OrderDirection::Ascending => ASC,
+                    OrderDirection::Descending => DESC,
+                };
+                let column = table.column_for_field(&attribute)?;
+                if column.is_fulltext() {
+                    return Err(QueryExecutionError::OrderByNotSupportedError(
+                        table.object.as_str().to_owned(),
+                        attribute,
+                    ));
+                }
+                if column.is_primary_key() {
+                    // Since `id` is unique, any keys after it can not
+                    // change the order
+                    return Ok(match (columns.is_empty(), direction) {
+                        (true, ASC) => SortKey::IdAsc,
+                        (true, _) => SortKey::IdDesc,
+                        (false, _) => SortKey::Keys {
+                            columns,
+                            id_direction: direction,
+                        },
+                    });
+                }
+                columns.push((column, direction));
+            }
+            let id_direction = columns
+                .last()
+                .map(|(_, direction)| *direction)
+                .unwrap_or(ASC);
+            Ok(SortKey::Keys {
+                columns,
+                id_direction,
+            })
+        }
+
         match order {
             EntityOrder::Ascending(attr, _) => with_key(table, attr, filter, ASC),
             EntityOrder::Descending(attr, _) => with_key(table, attr, filter, DESC),
+            EntityOrder::Multiple(keys) => with_keys(table, keys),
             EntityOrder::Default => Ok(SortKey::IdAsc),
             EntityOrder::Unordered => Ok(SortKey::None),
         }
@@ -2101,6 +2592,19 @@ impl<'a> SortKey<'a> {
                 out.push_identifier(column.name.as_str())?;
                 Ok(())
             }
+            SortKey::Keys {
+                columns,
+                id_direction: _,
+            } => {
+                for (column, _) in columns {
+                    if column.is_primary_key() {
+                        return Err(constraint_violation!("SortKey::Keys never uses 'id'"));
+                    }
+                    out.push_sql(", c.");
+                    out.push_identifier(column.name.as_str())?;
+                }
+                Ok(())
+            }
         }
     }
 
@@ -2137,6 +2641,13 @@ impl<'a> SortKey<'a> {
                 out.push_sql("order by ");
                 SortKey::sort_expr(column, value, direction, out)
             }
+            SortKey::Keys {
+                columns,
+                id_direction,
+            } => {
+                out.push_sql("order by ");
+                SortKey::multi_sort_expr(columns, id_direction, out)
+            }
         }
     }
 
@@ -2163,6 +2674,13 @@ impl<'a> SortKey<'a> {
                 out.push_sql("order by g$parent_id, ");
                 SortKey::sort_expr(column, value, direction, out)
             }
+            SortKey::Keys {
+                columns,
+                id_direction,
+            } => {
+                out.push_sql("order by g$parent_id, ");
+                SortKey::multi_sort_expr(columns, id_direction, out)
+            }
         }
     }
 
@@ -2217,6 +2735,35 @@ impl<'a> SortKey<'a> {
         }
         Ok(())
     }
+
+    /// Generate
+    ///   name1 direction1, name2 direction2, ..., id
+    fn multi_sort_expr(
+        columns: &[(&Column, &str)],
+        id_direction: &str,
+        out: &mut AstPass<Pg>,
+    ) -> QueryResult<()> {
+        for (column, direction) in columns {
+            if column.is_primary_key() {
+                return Err(constraint_violation!(
+                    "multi_sort_expr called with primary key column"
+                ));
+            }
+            out.push_identifier(column.name.as_str())?;
+            out.push_sql(" ");
+            out.push_sql(direction);
+            if *REVERSIBLE_ORDER_BY_OFF {
+                out.push_sql(" nulls last");
+            }
+            out.push_sql(", ");
+        }
+        out.push_identifier(PRIMARY_KEY_COLUMN)?;
+        if !*REVERSIBLE_ORDER_BY_OFF {
+            out.push_sql(" ");
+            out.push_sql(id_direction);
+        }
+        Ok(())
+    }
 }
 
 /// Generate `[limit {first}] [offset {skip}]
@@ -2248,6 +2795,7 @@ pub struct FilterQuery<'a> {
     sort_key: SortKey<'a>,
     range: FilterRange,
     block: BlockNumber,
+    history: Option<EntityHistory>,
     query_id: Option<String>,
 }
 
@@ -2258,8 +2806,24 @@ impl<'a> FilterQuery<'a> {
         order: EntityOrder,
         range: EntityRange,
         block: BlockNumber,
+        history: Option<EntityHistory>,
         query_id: Option<String>,
     ) -> Result<Self, QueryExecutionError> {
+        // History queries return several versions of the same entity;
+        // we only know how to do that for the simplest form of query
+        if history.is_some() {
+            match collection {
+                FilterCollection::All(entities) if entities.len() == 1 => {}
+                _ => {
+                    return Err(QueryExecutionError::NotSupported(
+                        "querying entity versions in a block range is only supported for \
+                         top-level queries of a single entity type"
+                            .to_string(),
+                    ))
+                }
+            }
+        }
+
         // Get the name of the column we order by; if there is more than one
         // table, we are querying an interface, and the order is on an attribute
         // in that interface so that all tables have a column for that. It is
@@ -2274,6 +2838,7 @@ impl<'a> FilterQuery<'a> {
             sort_key,
             range: FilterRange(range),
             block,
+            history,
             query_id,
         })
     }
@@ -2282,6 +2847,8 @@ impl<'a> FilterQuery<'a> {
     ///     from schema.table c
     ///    where block_range @> $block
     ///      and query_filter
+    /// For history queries, the block range must overlap the blocks of
+    /// the history instead of containing `$block`
     /// Only used when the query is against a `FilterCollection::All`, i.e.
     /// when we do not need to window
     fn filtered_rows(
@@ -2294,7 +2861,14 @@ impl<'a> FilterQuery<'a> {
         out.push_sql(table.qualified_name.as_str());
         out.push_sql(" c");
         out.push_sql("\n where ");
-        BlockRangeContainsClause::new(&table, "c.", self.block).walk_ast(out.reborrow())?;
+        match &self.history {
+            Some(history) => {
+                BlockRangeOverlapsClause::new("c.", history).walk_ast(out.reborrow())?
+            }
+            None => {
+                BlockRangeContainsClause::new(&table, "c.", self.block).walk_ast(out.reborrow())?
+            }
+        }
         if let Some(filter) = table_filter {
             out.push_sql(" and ");
             filter.walk_ast(out.reborrow())?;
@@ -2321,6 +2895,9 @@ impl<'a> FilterQuery<'a> {
     ///         where block_range @> $block
     ///           and filter
     ///         order by .. limit .. skip ..) c
+    ///
+    /// History queries also select the bounds of the block range, and
+    /// order the versions of each entity by their block range
     fn query_no_window_one_entity(
         &self,
         table: &Table,
@@ -2331,9 +2908,31 @@ impl<'a> FilterQuery<'a> {
         Self::select_entity_and_data(table, &mut out);
         out.push_sql(" from (select ");
         write_column_names(&column_names, &table, &mut out)?;
+        if self.history.is_some() {
+            out.push_sql(", lower(");
+            out.push_identifier(BLOCK_RANGE_COLUMN)?;
+            out.push_sql(") as ");
+            out.push_identifier(VALID_FROM_ATTRIBUTE)?;
+            out.push_sql(", upper(");
+            out.push_identifier(BLOCK_RANGE_COLUMN)?;
+            out.push_sql(") as ");
+            out.push_identifier(VALID_TO_ATTRIBUTE)?;
+        }
         self.filtered_rows(table, filter, out.reborrow())?;
         out.push_sql("\n ");
         self.sort_key.order_by(&mut out)?;
+        if self.history.is_some() {
+            // Ordering by id might already have added the block range
+            let ordered_by_range = match self.sort_key {
+                SortKey::None => true,
+                SortKey::IdAsc | SortKey::IdDesc => *ORDER_BY_BLOCK_RANGE,
+                SortKey::Key { .. } | SortKey::Keys { .. } => false,
+            };
+            if !ordered_by_range {
+                out.push_sql(", ");
+                out.push_identifier(BLOCK_RANGE_COLUMN)?;
+            }
+        }
         self.range.walk_ast(out.reborrow())?;
         out.push_sql(") c");
         Ok(())
@@ -2579,7 +3178,185 @@ impl<'a> LoadQuery<PgConnection, EntityData> for FilterQuery<'a> {
 
 impl<'a, Conn> RunQueryDsl<Conn> for FilterQuery<'a> {}
 
-/// Reduce the upper bound of the current entry's block range to `block` as
+/// Search the fulltext columns of several tables at once. An entity
+/// matches if any of the fulltext columns of its table match `text`, and
+/// its rank is the highest rank across those columns. Entities from all
+/// tables are ordered by decreasing rank
+///
+/// We generate a query
+///
+///   with matches as (
+///     select '..' as entity, c.id, c.vid,
+///            greatest(ts_rank(c.{col}, to_tsquery($text)), ..) as rank
+///       from {table} c
+///      where block_range @> $block
+///        and (c.{col} @@ to_tsquery($text) or ..)
+///      union all
+///      ...
+///      order by rank desc, entity, id
+///      limit n offset m)
+///   select m.entity, to_jsonb(c.*) as data, m.id, m.rank
+///     from {table} c, matches m
+///    where c.vid = m.vid and m.entity = '..'
+///    union all
+///    ...
+///    order by rank desc, entity, id
+#[derive(Debug, Clone)]
+pub struct FulltextSearchQuery<'a> {
+    tables: Vec<&'a Table>,
+    text: &'a str,
+    range: FilterRange,
+    block: BlockNumber,
+    query_id: Option<String>,
+}
+
+impl<'a> FulltextSearchQuery<'a> {
+    /// Search `tables`; tables without any fulltext columns are ignored
+    pub fn new(
+        tables: impl Iterator<Item = &'a Table>,
+        text: &'a str,
+        range: EntityRange,
+        block: BlockNumber,
+        query_id: Option<String>,
+    ) -> Self {
+        let tables = tables
+            .filter(|table| table.columns.iter().any(|column| column.is_fulltext()))
+            .collect();
+        FulltextSearchQuery {
+            tables,
+            text,
+            range: FilterRange(range),
+            block,
+            query_id,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    fn fulltext_columns(table: &Table) -> impl Iterator<Item = &Column> {
+        table.columns.iter().filter(|column| column.is_fulltext())
+    }
+
+    /// Generate `to_tsquery($text)`
+    fn tsquery(&self, out: &mut AstPass<Pg>) -> QueryResult<()> {
+        out.push_sql("to_tsquery(");
+        out.push_bind_param::<Text, _>(&self.text)?;
+        out.push_sql(")");
+        Ok(())
+    }
+
+    /// Generate `greatest(ts_rank(c.{col}, to_tsquery($text)), ..)`
+    fn rank(&self, table: &Table, out: &mut AstPass<Pg>) -> QueryResult<()> {
+        out.push_sql("greatest(");
+        for (i, column) in Self::fulltext_columns(table).enumerate() {
+            if i > 0 {
+                out.push_sql(", ");
+            }
+            let algorithm = match &column.column_type {
+                ColumnType::TSVector(config) => match config.algorithm {
+                    FulltextAlgorithm::Rank => "ts_rank(",
+                    FulltextAlgorithm::ProximityRank => "ts_rank_cd(",
+                },
+                _ => unreachable!("fulltext columns are tsvectors"),
+            };
+            out.push_sql(algorithm);
+            out.push_sql("c.");
+            out.push_identifier(column.name.as_str())?;
+            out.push_sql(", ");
+            self.tsquery(out)?;
+            out.push_sql(")");
+        }
+        out.push_sql(")");
+        Ok(())
+    }
+
+    /// Generate `(c.{col} @@ to_tsquery($text) or ..)`
+    fn matches(&self, table: &Table, out: &mut AstPass<Pg>) -> QueryResult<()> {
+        out.push_sql("(");
+        for (i, column) in Self::fulltext_columns(table).enumerate() {
+            if i > 0 {
+                out.push_sql(" or ");
+            }
+            out.push_sql("c.");
+            out.push_identifier(column.name.as_str())?;
+            out.push_sql(Comparison::Match.as_str());
+            self.tsquery(out)?;
+        }
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
+impl<'a> QueryFragment<Pg> for FulltextSearchQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+        if self.tables.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(qid) = &self.query_id {
+            out.push_sql("/* qid: ");
+            out.push_sql(qid);
+            out.push_sql(" */\n");
+        }
+
+        // Step 1: find the matching entities and their rank
+        out.push_sql("with matches as (");
+        for (i, table) in self.tables.iter().enumerate() {
+            if i > 0 {
+                out.push_sql("\nunion all\n");
+            }
+            out.push_sql("select '");
+            out.push_sql(table.object.as_str());
+            out.push_sql("' as entity, c.id, c.vid, ");
+            self.rank(table, &mut out)?;
+            out.push_sql(" as rank");
+            out.push_sql("\n  from ");
+            out.push_sql(table.qualified_name.as_str());
+            out.push_sql(" c");
+            out.push_sql("\n where ");
+            BlockRangeContainsClause::new(table, "c.", self.block).walk_ast(out.reborrow())?;
+            out.push_sql(" and ");
+            self.matches(table, &mut out)?;
+        }
+        out.push_sql("\n order by rank desc, entity, id");
+        self.range.walk_ast(out.reborrow())?;
+        out.push_sql(")\n");
+
+        // Step 2: convert to JSONB
+        for (i, table) in self.tables.iter().enumerate() {
+            if i > 0 {
+                out.push_sql("\nunion all\n");
+            }
+            out.push_sql("select m.entity, to_jsonb(c.*) as data, m.id, m.rank");
+            out.push_sql("\n  from ");
+            out.push_sql(table.qualified_name.as_str());
+            out.push_sql(" c, matches m");
+            out.push_sql("\n where c.vid = m.vid and m.entity = ");
+            out.push_bind_param::<Text, _>(&table.object.as_str())?;
+        }
+        out.push_sql("\n order by rank desc, entity, id");
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for FulltextSearchQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a> LoadQuery<PgConnection, EntityData> for FulltextSearchQuery<'a> {
+    fn internal_load(self, conn: &PgConnection) -> QueryResult<Vec<EntityData>> {
+        conn.query_by_name(&self)
+    }
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for FulltextSearchQuery<'a> {}
+
+/// Reduce the upper bound of the current entry's block range to `block` as
 /// long as that does not result in an empty block range
 #[derive(Debug, Clone, Constructor)]
 pub struct ClampRangeQuery<'a, S> {
@@ -2630,6 +3407,157 @@ where
 
 impl<'a, S, Conn> RunQueryDsl<Conn> for ClampRangeQuery<'a, S> {}
 
+/// Insert the rows for the `@relation` field of `relation` for all
+/// `entities` into its join table, keeping the order of the ids in the
+/// field's value
+#[derive(Debug)]
+pub struct InsertRelationQuery<'a> {
+    relation: &'a JoinTable,
+    parents: Vec<String>,
+    children: Vec<String>,
+    block: BlockNumber,
+}
+
+impl<'a> InsertRelationQuery<'a> {
+    pub fn new(
+        relation: &'a JoinTable,
+        entities: &[(&EntityKey, Cow<Entity>)],
+        block: BlockNumber,
+    ) -> Result<InsertRelationQuery<'a>, StoreError> {
+        let mut parents = Vec::new();
+        let mut children = Vec::new();
+        for (key, entity) in entities {
+            let values = match entity.get(&relation.field) {
+                None | Some(Value::Null) => continue,
+                Some(Value::List(values)) => values,
+                Some(value) => {
+                    return Err(StoreError::QueryExecutionError(format!(
+                        "can not insert entity {}[{}] since the value `{}` of \
+                         attribute {} is not a list of ids",
+                        key.entity_type, key.entity_id, value, relation.field
+                    )))
+                }
+            };
+            for value in values {
+                let child = match value {
+                    Value::String(id) => id.clone(),
+                    Value::Bytes(id) => id.to_string(),
+                    _ => {
+                        return Err(StoreError::QueryExecutionError(format!(
+                            "can not insert entity {}[{}] since `{}` in \
+                             attribute {} is not an id",
+                            key.entity_type, key.entity_id, value, relation.field
+                        )))
+                    }
+                };
+                parents.push(key.entity_id.clone());
+                children.push(child);
+            }
+        }
+        Ok(InsertRelationQuery {
+            relation,
+            parents,
+            children,
+            block,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+}
+
+impl<'a> QueryFragment<Pg> for InsertRelationQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Construct a query
+        //   insert into schema.join_table(parent, child, block_range)
+        //   select p.parent, p.child, int4range($block, null)
+        //     from rows from (unnest($parents), unnest($children))
+        //          with ordinality as p(parent, child, ord)
+        //    order by p.ord
+        //
+        // Inserting in the order of the ids assigns `vid`s in that
+        // order, which we use to restore the order when loading entities
+        out.push_sql("insert into ");
+        out.push_sql(self.relation.qualified_name.as_str());
+        out.push_sql("(");
+        out.push_sql(JOIN_PARENT_COLUMN);
+        out.push_sql(", ");
+        out.push_sql(JOIN_CHILD_COLUMN);
+        out.push_sql(", ");
+        out.push_sql(BLOCK_RANGE_COLUMN);
+        out.push_sql(")\nselect p.parent, p.child, ");
+        let block_range: BlockRange = (self.block..).into();
+        out.push_bind_param::<Range<Integer>, _>(&block_range)?;
+        out.push_sql("\n  from rows from (unnest(");
+        JoinColumn::new(self.relation.link_columns(false).0).bind_ids(&self.parents, &mut out)?;
+        out.push_sql("), unnest(");
+        JoinColumn::new(self.relation.link_columns(false).1).bind_ids(&self.children, &mut out)?;
+        out.push_sql("))\n       with ordinality as p(parent, child, ord)\n order by p.ord");
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for InsertRelationQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for InsertRelationQuery<'a> {}
+
+/// Reduce the upper bound of the block range of the current rows in the
+/// join table `relation` for the given parents to `block`
+#[derive(Debug, Clone, Constructor)]
+pub struct ClampRelationQuery<'a, S> {
+    relation: &'a JoinTable,
+    parent_ids: &'a [S],
+    block: BlockNumber,
+}
+
+impl<'a, S> QueryFragment<Pg> for ClampRelationQuery<'a, S>
+where
+    S: AsRef<str> + diesel::serialize::ToSql<Text, Pg>,
+{
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        // update join_table
+        //    set block_range = int4range(lower(block_range), $block)
+        //  where parent = any($parent_ids)
+        //    and block_range @> INTMAX
+        out.unsafe_to_cache_prepared();
+        out.push_sql("update ");
+        out.push_sql(self.relation.qualified_name.as_str());
+        out.push_sql("\n   set ");
+        out.push_identifier(BLOCK_RANGE_COLUMN)?;
+        out.push_sql(" = int4range(lower(");
+        out.push_identifier(BLOCK_RANGE_COLUMN)?;
+        out.push_sql("), ");
+        out.push_bind_param::<Integer, _>(&self.block)?;
+        out.push_sql(")\n where ");
+        out.push_sql(JOIN_PARENT_COLUMN);
+        out.push_sql(" = any(");
+        JoinColumn::new(self.relation.link_columns(false).0).bind_ids(self.parent_ids, &mut out)?;
+        out.push_sql(") and (");
+        out.push_sql(BLOCK_RANGE_CURRENT);
+        out.push_sql(")");
+
+        Ok(())
+    }
+}
+
+impl<'a, S> QueryId for ClampRelationQuery<'a, S>
+where
+    S: AsRef<str> + diesel::serialize::ToSql<Text, Pg>,
+{
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a, S, Conn> RunQueryDsl<Conn> for ClampRelationQuery<'a, S> {}
+
 /// Helper struct for returning the id's touched by the RevertRemove and
 /// RevertExtend queries
 #[derive(QueryableByName, PartialEq, Eq, Hash)]
@@ -2769,6 +3697,82 @@ impl<'a> LoadQuery<PgConnection, ReturnedEntityData> for RevertClampQuery<'a> {
 
 impl<'a, Conn> RunQueryDsl<Conn> for RevertClampQuery<'a> {}
 
+/// The equivalent of `RevertRemoveQuery` for a join table
+#[derive(Debug, Clone, Constructor)]
+pub struct RevertRelationRemoveQuery<'a> {
+    relation: &'a JoinTable,
+    block: BlockNumber,
+}
+
+impl<'a> QueryFragment<Pg> for RevertRelationRemoveQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Construct a query
+        //   delete from join_table
+        //    where lower(block_range) >= $block
+        out.push_sql("delete from ");
+        out.push_sql(self.relation.qualified_name.as_str());
+        out.push_sql("\n where lower(");
+        out.push_identifier(BLOCK_RANGE_COLUMN)?;
+        out.push_sql(") >= ");
+        out.push_bind_param::<Integer, _>(&self.block)?;
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for RevertRelationRemoveQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for RevertRelationRemoveQuery<'a> {}
+
+/// The equivalent of `RevertClampQuery` for a join table
+#[derive(Debug, Clone, Constructor)]
+pub struct RevertRelationClampQuery<'a> {
+    relation: &'a JoinTable,
+    block: BlockNumber,
+}
+
+impl<'a> QueryFragment<Pg> for RevertRelationClampQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Construct a query
+        //   update join_table
+        //     set block_range = int4range(lower(block_range), null)
+        //   where block_range @> $block
+        //     and not block_range @> INTMAX
+        //     and coalesce(upper(block_range), INTMAX) < INTMAX
+        out.push_sql("update ");
+        out.push_sql(self.relation.qualified_name.as_str());
+        out.push_sql("\n   set ");
+        out.push_identifier(BLOCK_RANGE_COLUMN)?;
+        out.push_sql(" = int4range(lower(");
+        out.push_identifier(BLOCK_RANGE_COLUMN)?;
+        out.push_sql("), null)\n where ");
+        out.push_identifier(BLOCK_RANGE_COLUMN)?;
+        out.push_sql(" @> ");
+        out.push_bind_param::<Integer, _>(&self.block)?;
+        out.push_sql(" and not ");
+        out.push_sql(BLOCK_RANGE_CURRENT);
+        out.push_sql(" and coalesce(upper(");
+        out.push_sql(BLOCK_RANGE_COLUMN);
+        out.push_sql("), 2147483647) < 2147483647");
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for RevertRelationClampQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for RevertRelationClampQuery<'a> {}
+
 #[test]
 fn block_number_max_is_i32_max() {
     // The code in RevertClampQuery::walk_ast embeds i32::MAX
@@ -2875,6 +3879,105 @@ impl<'a> QueryId for CopyEntityBatchQuery<'a> {
 
 impl<'a, Conn> RunQueryDsl<Conn> for CopyEntityBatchQuery<'a> {}
 
+/// Copy the rows of the join table `src` whose `vid` is in the range
+/// `[first_vid, last_vid]` to the join table `dst`. Only rows that were
+/// created at or before `target_block` are copied, and rows that were
+/// still current at `target_block` are made current again
+#[derive(Debug, Clone)]
+pub struct CopyRelationBatchQuery<'a> {
+    src: &'a JoinTable,
+    dst: &'a JoinTable,
+    first_vid: i64,
+    last_vid: i64,
+    target_block: BlockNumber,
+}
+
+impl<'a> CopyRelationBatchQuery<'a> {
+    pub fn new(
+        dst: &'a JoinTable,
+        src: &'a JoinTable,
+        first_vid: i64,
+        last_vid: i64,
+        target_block: BlockNumber,
+    ) -> Result<Self, StoreError> {
+        if let Some(msg) = dst.is_assignable_from(src) {
+            return Err(anyhow!("{}", msg).into());
+        }
+        Ok(Self {
+            src,
+            dst,
+            first_vid,
+            last_vid,
+            target_block,
+        })
+    }
+}
+
+impl<'a> QueryFragment<Pg> for CopyRelationBatchQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Construct a query
+        //   insert into {dst}(parent, child, block_range)
+        //   select parent, child,
+        //          case when coalesce(upper(block_range), INTMAX) > $target
+        //               then int4range(lower(block_range), null)
+        //               else block_range end
+        //     from {src}
+        //    where vid >= $first_vid and vid <= $last_vid
+        //      and lower(block_range) <= $target
+        //    order by vid
+        //
+        // Inserting in the order of the `vid`s in `src` keeps the order
+        // of the ids in the values of the relation field
+        out.push_sql("insert into ");
+        out.push_sql(self.dst.qualified_name.as_str());
+        out.push_sql("(");
+        out.push_sql(JOIN_PARENT_COLUMN);
+        out.push_sql(", ");
+        out.push_sql(JOIN_CHILD_COLUMN);
+        out.push_sql(", ");
+        out.push_sql(BLOCK_RANGE_COLUMN);
+        out.push_sql(")\nselect ");
+        out.push_sql(JOIN_PARENT_COLUMN);
+        out.push_sql(", ");
+        out.push_sql(JOIN_CHILD_COLUMN);
+        out.push_sql(",\n       case when coalesce(upper(");
+        out.push_sql(BLOCK_RANGE_COLUMN);
+        out.push_sql("), 2147483647) > ");
+        out.push_bind_param::<Integer, _>(&self.target_block)?;
+        out.push_sql(" then int4range(lower(");
+        out.push_sql(BLOCK_RANGE_COLUMN);
+        out.push_sql("), null) else ");
+        out.push_sql(BLOCK_RANGE_COLUMN);
+        out.push_sql(" end\n  from ");
+        out.push_sql(self.src.qualified_name.as_str());
+        out.push_sql("\n where ");
+        out.push_sql(VID_COLUMN);
+        out.push_sql(" >= ");
+        out.push_bind_param::<BigInt, _>(&self.first_vid)?;
+        out.push_sql(" and ");
+        out.push_sql(VID_COLUMN);
+        out.push_sql(" <= ");
+        out.push_bind_param::<BigInt, _>(&self.last_vid)?;
+        out.push_sql("\n   and lower(");
+        out.push_sql(BLOCK_RANGE_COLUMN);
+        out.push_sql(") <= ");
+        out.push_bind_param::<Integer, _>(&self.target_block)?;
+        out.push_sql("\n order by ");
+        out.push_sql(VID_COLUMN);
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for CopyRelationBatchQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for CopyRelationBatchQuery<'a> {}
+
 /// Helper struct for returning the id's touched by the RevertRemove and
 /// RevertExtend queries
 #[derive(QueryableByName, PartialEq, Eq, Hash)]
@@ -2954,3 +4057,179 @@ fn iter_column_names<'a, 'b>(
         .sorted()
         .dedup()
 }
+
+#[cfg(test)]
+mod tests {
+    use diesel::debug_query;
+    use graph::data::schema::Schema;
+    use graph::prelude::DeploymentHash;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::layout_for_tests::{make_dummy_site, Catalog, Namespace};
+
+    const CALL_GQL: &str = "
+type Call @entity {
+    id: ID!,
+    data: Bytes!,
+    input: Bytes! @storage(prefix: 64)
+}";
+
+    fn test_layout(gql: &str) -> Layout {
+        let subgraph = DeploymentHash::new("subgraph").unwrap();
+        let schema = Schema::parse(gql, subgraph.clone()).expect("Test schema invalid");
+        let namespace = Namespace::new("sgd0815".to_owned()).unwrap();
+        let site = Arc::new(make_dummy_site(subgraph, namespace, "anet".to_string()));
+        let catalog = Catalog::make_empty(site.clone()).expect("Can not create catalog");
+        Layout::new(site, &schema, catalog, false).expect("Failed to construct Layout")
+    }
+
+    /// The SQL for `filter` on the `Call` table without the list of binds
+    fn filter_sql(filter: EntityFilter) -> String {
+        let layout = test_layout(CALL_GQL);
+        let table = layout.table(&SqlName::from("call")).unwrap();
+        let filter = QueryFilter::new(&filter, table).unwrap();
+        let sql = debug_query::<Pg, _>(&filter).to_string();
+        sql.split(" -- binds").next().unwrap().to_owned()
+    }
+
+    fn bytes(b: &[u8]) -> Value {
+        Value::Bytes(scalar::Bytes::from(b))
+    }
+
+    #[test]
+    fn bytes_prefix_equal() {
+        assert_eq!(
+            "(substring(\"input\", 1, 64) in (substring($1, 1, 64)) and \"input\" in ($2))",
+            filter_sql(EntityFilter::Equal("input".to_owned(), bytes(&[1, 2])))
+        );
+        // Only attributes declared with `@storage(prefix: N)` are compared
+        // on their prefix
+        assert_eq!(
+            "\"data\" = $1",
+            filter_sql(EntityFilter::Equal("data".to_owned(), bytes(&[1, 2])))
+        );
+        assert_eq!(
+            "\"input\" != $1",
+            filter_sql(EntityFilter::Not("input".to_owned(), bytes(&[1, 2])))
+        );
+    }
+
+    #[test]
+    fn bytes_prefix_in() {
+        assert_eq!(
+            "(substring(\"input\", 1, 64) in (substring($1, 1, 64), substring($2, 1, 64)) \
+             and \"input\" in ($3, $4))",
+            filter_sql(EntityFilter::In(
+                "input".to_owned(),
+                vec![bytes(&[1, 2]), bytes(&[3, 4])]
+            ))
+        );
+        assert_eq!(
+            "(\"input\" is null or (substring(\"input\", 1, 64) in (substring($1, 1, 64)) \
+             and \"input\" in ($2)))",
+            filter_sql(EntityFilter::In(
+                "input".to_owned(),
+                vec![Value::Null, bytes(&[1, 2])]
+            ))
+        );
+        assert_eq!(
+            "\"input\" not in ($1, $2)",
+            filter_sql(EntityFilter::NotIn(
+                "input".to_owned(),
+                vec![bytes(&[1, 2]), bytes(&[3, 4])]
+            ))
+        );
+        assert_eq!(
+            "\"data\" in ($1, $2)",
+            filter_sql(EntityFilter::In(
+                "data".to_owned(),
+                vec![bytes(&[1, 2]), bytes(&[3, 4])]
+            ))
+        );
+    }
+
+    #[test]
+    fn copy_relation_batch() {
+        const GQL: &str = "
+type Band @entity { id: ID!, members: [Musician!]! @relation(through: \"BandMember\") }
+type Musician @entity { id: ID! }";
+        let src = test_layout(GQL);
+        let dst = test_layout(GQL);
+        let src = src
+            .table(&SqlName::from("band"))
+            .unwrap()
+            .relation("members")
+            .unwrap();
+        let dst = dst
+            .table(&SqlName::from("band"))
+            .unwrap()
+            .relation("members")
+            .unwrap();
+
+        let query = CopyRelationBatchQuery::new(dst, src, 0, 99, 17).unwrap();
+        let sql = debug_query::<Pg, _>(&query).to_string();
+        assert_eq!(
+            "insert into \"sgd0815\".\"band_member\"(parent, child, block_range)\n\
+             select parent, child,\n       \
+             case when coalesce(upper(block_range), 2147483647) > $1 \
+             then int4range(lower(block_range), null) else block_range end\n  \
+             from \"sgd0815\".\"band_member\"\n \
+             where vid >= $2 and vid <= $3\n   \
+             and lower(block_range) <= $4\n \
+             order by vid",
+            sql.split(" -- binds").next().unwrap()
+        );
+    }
+
+    #[test]
+    fn copy_insert() {
+        const GQL: &str = "
+type Thing @entity {
+    id: ID!,
+    name: String!,
+    count: Int!,
+    big: BigInt,
+    data: Bytes,
+    tags: [String!],
+    flag: Boolean
+}
+type Band @entity { id: ID!, members: [Thing!]! @relation(through: \"BandMember\") }";
+        let layout = test_layout(GQL);
+        let table = layout.table(&SqlName::from("thing")).unwrap();
+        assert!(CopyInsert::is_possible(table));
+        assert!(!CopyInsert::is_possible(
+            layout.table(&SqlName::from("band")).unwrap()
+        ));
+
+        let subgraph = DeploymentHash::new("subgraph").unwrap();
+        let key = EntityKey::data(subgraph, "Thing".to_owned(), "one".to_owned());
+        let mut entity = Entity::new();
+        entity.set("id", "one");
+        entity.set("name", "say \"hi\", ok");
+        entity.set("count", 7);
+        entity.set("data", bytes(&[1, 2]));
+        entity.set(
+            "tags",
+            Value::List(vec![Value::from("a"), Value::from("b\"c")]),
+        );
+        entity.set("flag", true);
+        let entities = [(&key, &entity, 5)];
+
+        let copy = CopyInsert::new(table, &entities).unwrap();
+        assert_eq!(
+            "copy \"sgd0815\".\"thing\"(\"id\", \"name\", \"count\", \"big\", \"data\", \
+             \"tags\", \"flag\", \"block_range\") from stdin (format csv)",
+            copy.statement()
+        );
+        assert_eq!(
+            "\"one\",\"say \"\"hi\"\", ok\",\"7\",,\"\\x0102\",\
+             \"{\"\"a\"\",\"\"b\\\"\"c\"\"}\",\"t\",\"[5,)\"\n",
+            String::from_utf8(copy.data().unwrap()).unwrap()
+        );
+
+        entity.remove("name");
+        let entities = [(&key, &entity, 5)];
+        assert!(CopyInsert::new(table, &entities).is_err());
+    }
+}
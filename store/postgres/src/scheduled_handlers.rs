@@ -0,0 +1,169 @@
+//! SQL queries for the block handlers that mappings schedule with
+//! `scheduler.atBlock`
+
+use diesel::{
+    delete,
+    dsl::count,
+    prelude::{ExpressionMethods, QueryDsl, RunQueryDsl},
+    sql_query,
+    sql_types::{Integer, Text},
+};
+use diesel::{insert_into, pg::PgConnection};
+
+use graph::{
+    components::store::ScheduledBlockHandler,
+    prelude::{BlockNumber, BlockPtr, DeploymentHash, StoreError},
+};
+
+use crate::connection_pool::ForeignServer;
+use crate::primary::Site;
+
+table! {
+    subgraphs.scheduled_block_handler (vid) {
+        vid -> BigInt,
+        deployment -> Text,
+        data_source -> Text,
+        address -> Binary,
+        handler -> Text,
+        block_number -> Integer,
+        creation_block -> Integer,
+    }
+}
+
+/// Load the handlers for deployment `id` that are due after block `after`,
+/// or all handlers if `after` is `None`. The handlers are ordered by the
+/// block at which they are due, and handlers that are due at the same
+/// block in the order in which they were scheduled
+pub fn load(
+    conn: &PgConnection,
+    id: &str,
+    after: Option<BlockNumber>,
+) -> Result<Vec<ScheduledBlockHandler>, StoreError> {
+    use scheduled_block_handler as sbh;
+
+    let after = after.unwrap_or(-1);
+    let handlers = sbh::table
+        .filter(sbh::deployment.eq(id))
+        .filter(sbh::block_number.gt(after))
+        .select((
+            sbh::data_source,
+            sbh::address,
+            sbh::handler,
+            sbh::block_number,
+        ))
+        .order_by((sbh::block_number, sbh::vid))
+        .load::<(String, Vec<u8>, String, BlockNumber)>(conn)?
+        .into_iter()
+        .map(
+            |(data_source, address, handler, block)| ScheduledBlockHandler {
+                data_source,
+                address,
+                handler,
+                block,
+            },
+        )
+        .collect();
+    Ok(handlers)
+}
+
+pub(crate) fn insert(
+    conn: &PgConnection,
+    deployment: &DeploymentHash,
+    handlers: &[ScheduledBlockHandler],
+    block_ptr: &BlockPtr,
+) -> Result<usize, StoreError> {
+    use scheduled_block_handler as sbh;
+
+    if handlers.is_empty() {
+        // Avoids a roundtrip to the DB.
+        return Ok(0);
+    }
+
+    let rows: Vec<_> = handlers
+        .iter()
+        .map(|handler| {
+            (
+                sbh::deployment.eq(deployment.as_str()),
+                sbh::data_source.eq(&handler.data_source),
+                sbh::address.eq(&handler.address),
+                sbh::handler.eq(&handler.handler),
+                sbh::block_number.eq(handler.block),
+                sbh::creation_block.eq(block_ptr.number),
+            )
+        })
+        .collect();
+
+    insert_into(sbh::table)
+        .values(rows)
+        .execute(conn)
+        .map_err(|e| e.into())
+}
+
+/// Copy the scheduled handlers for `src` to `dst`. All handlers that were
+/// scheduled up to and including `target_block` will be copied.
+pub(crate) fn copy(
+    conn: &PgConnection,
+    src: &Site,
+    dst: &Site,
+    target_block: &BlockPtr,
+) -> Result<usize, StoreError> {
+    use scheduled_block_handler as sbh;
+
+    let src_nsp = if src.shard == dst.shard {
+        "subgraphs".to_string()
+    } else {
+        ForeignServer::metadata_schema(&src.shard)
+    };
+
+    // Check whether there are any scheduled handlers for dst which
+    // indicates we already did copy
+    let count = sbh::table
+        .filter(sbh::deployment.eq(dst.deployment.as_str()))
+        .select(count(sbh::vid))
+        .get_result::<i64>(conn)?;
+    if count > 0 {
+        return Ok(count as usize);
+    }
+
+    let query = format!(
+        "\
+      insert into subgraphs.scheduled_block_handler(deployment, data_source,
+             address, handler, block_number, creation_block)
+      select $2 as deployment, h.data_source, h.address, h.handler,
+             h.block_number, h.creation_block
+        from {src_nsp}.scheduled_block_handler h
+       where h.deployment = $1
+         and h.creation_block <= $3
+       order by h.vid",
+        src_nsp = src_nsp
+    );
+
+    Ok(sql_query(&query)
+        .bind::<Text, _>(src.deployment.as_str())
+        .bind::<Text, _>(dst.deployment.as_str())
+        .bind::<Integer, _>(target_block.number)
+        .execute(conn)?)
+}
+
+/// Remove the handlers that were scheduled in `block` or later. Handlers
+/// that already ran in those blocks do not need to be touched since they
+/// become due again once the deployment is reverted past their block
+pub(crate) fn revert(
+    conn: &PgConnection,
+    id: &DeploymentHash,
+    block: BlockNumber,
+) -> Result<(), StoreError> {
+    use scheduled_block_handler as sbh;
+
+    let handlers = sbh::table.filter(sbh::deployment.eq(id.as_str()));
+    delete(handlers.filter(sbh::creation_block.ge(block))).execute(conn)?;
+    Ok(())
+}
+
+pub(crate) fn drop(conn: &PgConnection, id: &DeploymentHash) -> Result<usize, StoreError> {
+    use scheduled_block_handler as sbh;
+
+    delete(sbh::table.filter(sbh::deployment.eq(id.as_str())))
+        .execute(conn)
+        .map_err(|e| e.into())
+}
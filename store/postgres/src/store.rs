@@ -2,14 +2,19 @@ use async_trait::async_trait;
 use std::sync::Arc;
 
 use graph::{
+    blockchain::REORG_THRESHOLD,
     components::{
-        server::index_node::VersionInfo,
-        store::{BlockStore as BlockStoreTrait, QueryStoreManager, StatusStore},
+        server::index_node::{BlockLookup, StoredBlock, VersionInfo},
+        store::{BlockStore as BlockStoreTrait, ChainStore as _, QueryStoreManager, StatusStore},
     },
     constraint_violation,
-    data::subgraph::status,
+    data::{
+        query::{Deprecation, QueryTarget},
+        subgraph::status,
+    },
     prelude::{
-        tokio, web3::types::Address, BlockPtr, CheapClone, DeploymentHash, QueryExecutionError,
+        anyhow, tokio, transaction_receipt::LightTransactionReceipt, web3::types::Address,
+        web3::types::H256, BlockNumber, BlockPtr, CheapClone, DeploymentHash, QueryExecutionError,
         StoreError,
     },
 };
@@ -45,22 +50,40 @@ impl Store {
     pub fn block_store(&self) -> Arc<BlockStore> {
         self.block_store.cheap_clone()
     }
-}
 
-#[async_trait]
-impl QueryStoreManager for Store {
-    async fn query_store(
+    /// Remove the blocks of `chain` that are more than `keep` blocks
+    /// behind the chain head from the block cache, but keep the blocks
+    /// that deployments have as their head block and the `REORG_THRESHOLD`
+    /// blocks before that, since deployments might need them to revert.
+    /// See `ChainStore::evict_blocks`
+    pub fn evict_cached_blocks(
         &self,
-        target: graph::data::query::QueryTarget,
+        chain: &str,
+        keep: BlockNumber,
+    ) -> Result<Option<(BlockNumber, usize)>, StoreError> {
+        let chain_store = self
+            .block_store
+            .chain_store(chain)
+            .ok_or_else(|| anyhow!("unknown network `{}`", chain))?;
+        let referenced: Vec<_> = self
+            .subgraph_store
+            .head_block_numbers(chain)?
+            .into_iter()
+            .map(|head| (head - *REORG_THRESHOLD, head))
+            .collect();
+        chain_store.evict_blocks(keep, &referenced)
+    }
+
+    async fn query_store_internal(
+        &self,
+        target: QueryTarget,
         for_subscription: bool,
-    ) -> Result<
-        Arc<dyn graph::prelude::QueryStore + Send + Sync>,
-        graph::prelude::QueryExecutionError,
-    > {
+        cost: Option<u64>,
+    ) -> Result<Arc<dyn graph::prelude::QueryStore + Send + Sync>, QueryExecutionError> {
         let store = self.subgraph_store.cheap_clone();
         let (store, site, replica) = graph::spawn_blocking_allow_panic(move || {
             store
-                .replica_for_query(target, for_subscription)
+                .replica_for_query(target, for_subscription, cost)
                 .map_err(|e| e.into())
         })
         .await
@@ -79,6 +102,40 @@ impl QueryStoreManager for Store {
     }
 }
 
+#[async_trait]
+impl QueryStoreManager for Store {
+    async fn query_store(
+        &self,
+        target: QueryTarget,
+        for_subscription: bool,
+    ) -> Result<Arc<dyn graph::prelude::QueryStore + Send + Sync>, QueryExecutionError> {
+        self.query_store_internal(target, for_subscription, None)
+            .await
+    }
+
+    async fn query_store_for_cost(
+        &self,
+        target: QueryTarget,
+        cost: u64,
+    ) -> Result<Arc<dyn graph::prelude::QueryStore + Send + Sync>, QueryExecutionError> {
+        self.query_store_internal(target, false, Some(cost)).await
+    }
+
+    async fn deprecation(
+        &self,
+        deployment: &DeploymentHash,
+    ) -> Result<Option<Deprecation>, QueryExecutionError> {
+        let store = self.subgraph_store.cheap_clone();
+        let deployment = deployment.clone();
+        graph::spawn_blocking_allow_panic(move || {
+            store.deprecation(&deployment).map_err(|e| e.into())
+        })
+        .await
+        .map_err(|e| QueryExecutionError::Panic(e.to_string()))
+        .and_then(|x| x)
+    }
+}
+
 #[async_trait]
 impl StatusStore for Store {
     fn status(&self, filter: status::Filter) -> Result<Vec<status::Info>, StoreError> {
@@ -116,6 +173,40 @@ impl StatusStore for Store {
             .subgraphs_for_deployment_hash(deployment_hash)
     }
 
+    fn stored_blocks(
+        &self,
+        network: &str,
+        lookup: BlockLookup,
+    ) -> Result<Vec<StoredBlock>, StoreError> {
+        let chain_store = self
+            .block_store
+            .chain_store(network)
+            .ok_or_else(|| anyhow!("unknown network `{}`", network))?;
+
+        let blocks = match lookup {
+            BlockLookup::Number(number) => chain_store
+                .block_hashes_by_block_number(number)?
+                .into_iter()
+                .map(|hash| (hash, number))
+                .collect(),
+            BlockLookup::Hash(hash) => match chain_store.block_number(hash)? {
+                Some((_, number)) => vec![(hash, number)],
+                None => vec![],
+            },
+        };
+
+        let mut summaries = Vec::with_capacity(blocks.len());
+        for (hash, number) in blocks {
+            let data = chain_store
+                .blocks(&[hash])?
+                .into_iter()
+                .next()
+                .unwrap_or_default();
+            summaries.push(StoredBlock::new(hash, number, &data));
+        }
+        Ok(summaries)
+    }
+
     async fn get_proof_of_indexing(
         &self,
         subgraph_id: &DeploymentHash,
@@ -127,6 +218,18 @@ impl StatusStore for Store {
             .await
     }
 
+    async fn transaction_receipts(
+        &self,
+        network: &str,
+        block_hash: &H256,
+    ) -> Result<Vec<LightTransactionReceipt>, StoreError> {
+        let chain_store = self
+            .block_store
+            .chain_store(network)
+            .ok_or_else(|| anyhow!("unknown network `{}`", network))?;
+        chain_store.transaction_receipts_in_block(block_hash).await
+    }
+
     async fn query_permit(&self) -> tokio::sync::OwnedSemaphorePermit {
         // Status queries go to the primary shard.
         self.block_store.query_permit_primary().await
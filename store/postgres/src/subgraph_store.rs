@@ -5,33 +5,41 @@ use diesel::{
     types::{FromSql, ToSql},
 };
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
+    path::Path,
     sync::{Arc, Mutex},
 };
 use std::{fmt, io::Write};
 use std::{iter::FromIterator, time::Duration};
 
 use graph::{
+    blockchain::BlockHash,
     cheap_clone::CheapClone,
     components::{
         server::index_node::VersionInfo,
         store::{
-            self, DeploymentLocator, EnsLookup as EnsLookupTrait, EntityType,
+            self, DeploymentLocator, EnsLookup as EnsLookupTrait, EntityType, PruneReporter,
             WritableStore as WritableStoreTrait,
         },
     },
     constraint_violation,
-    data::query::QueryTarget,
+    data::query::{Deprecation, QueryTarget},
     data::subgraph::status,
     prelude::StoreEvent,
     prelude::SubgraphDeploymentEntity,
     prelude::{
-        anyhow, futures03::future::join_all, lazy_static, o, web3::types::Address, ApiSchema,
-        BlockPtr, DeploymentHash, Logger, NodeId, Schema, StoreError, SubgraphName,
-        SubgraphStore as SubgraphStoreTrait, SubgraphVersionSwitchingMode,
+        anyhow,
+        bigdecimal::ToPrimitive,
+        chrono::{DateTime, NaiveDateTime, Utc},
+        futures03::future::join_all,
+        lazy_static, o, warn,
+        web3::types::Address,
+        ApiSchema, BlockNumber, BlockPtr, DeploymentHash, Logger, NodeId, Schema, StoreError,
+        SubgraphName, SubgraphStore as SubgraphStoreTrait, SubgraphVersionSwitchingMode,
     },
     util::timed_cache::TimedCache,
 };
+use graph_graphql::prelude::warm_introspection;
 
 use crate::{
     connection_pool::ConnectionPool,
@@ -44,7 +52,10 @@ use crate::{
 use crate::{
     deployment_store::{DeploymentStore, ReplicaId},
     detail::DeploymentDetail,
+    dump, index,
+    index_advice::IndexAdvice,
     primary::UnusedDeployment,
+    reader,
 };
 
 /// The name of a database shard; valid names must match `[a-z0-9_]+`
@@ -115,14 +126,19 @@ impl ToSql<Text, Pg> for Shard {
 }
 
 /// Decide where a new deployment should be placed based on the subgraph
-/// name and the network it is indexing. If the deployment can be placed,
+/// name, the network it is indexing, and the labels that have been attached
+/// to the deployment. If the deployment can be placed,
 /// returns a list of eligible database shards for the deployment and the
 /// names of the indexers that should index it. The deployment should then
 /// be assigned to one of the returned indexers and placed into one of the
 /// shards.
 pub trait DeploymentPlacer {
-    fn place(&self, name: &str, network: &str)
-        -> Result<Option<(Vec<Shard>, Vec<NodeId>)>, String>;
+    fn place(
+        &self,
+        name: &str,
+        network: &str,
+        labels: &BTreeMap<String, String>,
+    ) -> Result<Option<(Vec<Shard>, Vec<NodeId>)>, String>;
 }
 
 /// Tools for managing unused deployments
@@ -160,6 +176,7 @@ pub mod unused {
 ///   about named subgraphs and how they map to deployments
 /// - `subgraphs.subgraph_deployment_assignment`: which index node is
 ///   indexing what deployment
+/// - `subgraphs.deployment_labels`: user-defined labels for deployments
 ///
 /// The primary is also the database that is used to send and receive
 /// notifications through Postgres' `LISTEN`/`NOTIFY` mechanism. That is
@@ -184,6 +201,10 @@ pub mod unused {
 ///   YAML manifest for the deployment
 /// - `subgraphs.dynamic_ethereum_contract_data_source`: the data sources
 ///   that the subgraph has created from templates in the manifest.
+/// - `subgraphs.scheduled_block_handler`: the block handlers that the
+///   subgraph's mappings have scheduled with `scheduler.atBlock`
+/// - `subgraphs.data_source_context`: the contexts that the subgraph's
+///   mappings have stored with `dataSource.setContext`
 /// - `subgraphs.subgraph_error`: details about errors that the deployment
 ///   has encountered
 ///
@@ -254,6 +275,10 @@ pub struct SubgraphStoreInner {
     /// different deployment for the same hash propagate across different
     /// graph-node processes over time.
     sites: TimedCache<DeploymentHash, Site>,
+    /// Cache for whether a deployment has been replaced by a newer current
+    /// version of its subgraph, so that queries against a deployment do not
+    /// need to consult the primary every time
+    deprecations: TimedCache<DeploymentHash, Option<Deprecation>>,
     placer: Arc<dyn DeploymentPlacer + Send + Sync + 'static>,
     sender: Arc<NotificationSender>,
     writables: Mutex<HashMap<DeploymentId, Arc<WritableAgent>>>,
@@ -304,10 +329,12 @@ impl SubgraphStoreInner {
             },
         ));
         let sites = TimedCache::new(SITES_CACHE_TTL);
+        let deprecations = TimedCache::new(SITES_CACHE_TTL);
         SubgraphStoreInner {
             mirror,
             stores,
             sites,
+            deprecations,
             placer,
             sender,
             writables: Mutex::new(HashMap::new()),
@@ -384,6 +411,37 @@ impl SubgraphStoreInner {
             .ok_or(StoreError::UnknownShard(site.shard.as_str().to_string()))
     }
 
+    /// Load the API schemas of `deployments` into the schema cache and
+    /// build their introspection objects so that the first query against
+    /// them does not have to do that. If `deployments` is empty, do that
+    /// for all active deployments. Return the number of deployments whose
+    /// schema caches were warmed up
+    pub fn warm_schema_caches(
+        &self,
+        logger: &Logger,
+        deployments: &[String],
+    ) -> Result<usize, StoreError> {
+        let sites = self.mirror.find_sites(deployments, true)?;
+        let mut count = 0;
+        for site in sites {
+            let info = self
+                .for_site(&site)
+                .and_then(|store| store.subgraph_info(&site));
+            match info {
+                Ok(info) => {
+                    warm_introspection(&info.api);
+                    count += 1;
+                }
+                Err(e) => {
+                    warn!(logger, "Failed to warm up schema cache";
+                        "deployment" => site.deployment.as_str(),
+                        "error" => e.to_string());
+                }
+            }
+        }
+        Ok(count)
+    }
+
     pub(crate) fn layout(&self, id: &DeploymentHash) -> Result<Arc<Layout>, StoreError> {
         let (store, site) = self.store(id)?;
         store.find_layout(site)
@@ -427,9 +485,12 @@ impl SubgraphStoreInner {
     fn place(
         &self,
         name: &SubgraphName,
+        deployment: &DeploymentHash,
         network_name: &str,
         default_node: NodeId,
     ) -> Result<(Shard, NodeId), StoreError> {
+        let labels = self.primary_conn()?.deployment_labels(deployment)?;
+
         // We try to place the deployment according to the configured rules.
         // If they don't yield a match, place into the primary and have
         // `default_node` index the deployment. The latter can only happen
@@ -437,7 +498,7 @@ impl SubgraphStoreInner {
         // uses the legacy command-line options as configuration
         let placement = self
             .placer
-            .place(name.as_str(), network_name)
+            .place(name.as_str(), network_name, &labels)
             .map_err(|msg| {
                 constraint_violation!("illegal indexer name in deployment rule: {}", msg)
             })?;
@@ -489,7 +550,7 @@ impl SubgraphStoreInner {
             //       In that case, we need to use the shard and node
             //       assignment that we used last time to avoid creating
             //       the same deployment in another shard
-            let (shard, node_id) = self.place(&name, &network_name, node_id)?;
+            let (shard, node_id) = self.place(&name, &schema.id, &network_name, node_id)?;
             let conn = self.primary_conn()?;
             let site = conn.allocate_site(shard.clone(), &schema.id, network_name)?;
             let node_id = conn.assigned_node(&site)?.unwrap_or(node_id);
@@ -628,6 +689,141 @@ impl SubgraphStoreInner {
         Ok(dst.as_ref().into())
     }
 
+    /// Write a logical backup of `deployment` into the directory `dir`.
+    /// The backup contains the deployment's entities, its metadata and its
+    /// Firehose cursor and can be loaded into any shard with `restore`
+    pub fn dump(&self, deployment: &DeploymentLocator, dir: &Path) -> Result<(), StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let store = self.for_site(site.as_ref())?;
+        store.dump(site, dir)?;
+        Ok(())
+    }
+
+    /// Give the read-only role for `deployment` access to the deployment's
+    /// data, creating the role if needed, or, if `grant` is `false`, take
+    /// that access away and drop the role. With `dry_run`, nothing is
+    /// changed in the database. Returns the name of the role and the SQL
+    /// that does this
+    pub fn reader_role(
+        &self,
+        deployment: &DeploymentLocator,
+        grant: bool,
+        dry_run: bool,
+    ) -> Result<(String, String), StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let sql = if grant {
+            reader::grant_sql(&site)
+        } else {
+            reader::revoke_sql(&site)
+        };
+        if !dry_run {
+            self.for_site(site.as_ref())?.execute_reader_sql(&sql)?;
+        }
+        Ok((reader::role_name(&site), sql))
+    }
+
+    /// Return the network of `deployment`, the genesis block of the chain
+    /// the deployment is bound to, and the genesis block of the chain that
+    /// is currently stored for that network, if there is one
+    pub fn chain_binding(
+        &self,
+        deployment: &DeploymentLocator,
+    ) -> Result<(String, Option<BlockHash>, Option<BlockHash>), StoreError> {
+        let site = self
+            .mirror
+            .find_site_by_ref(deployment.id.into())?
+            .ok_or_else(|| StoreError::DeploymentNotFound(deployment.to_string()))?;
+        let current = self
+            .primary_conn()?
+            .chain_genesis_block_hash(&site.network)?;
+        Ok((site.network, site.genesis_block_hash, current))
+    }
+
+    /// Bind all copies of `deployment` to the chain that is currently
+    /// stored for its network and return that chain's genesis block
+    pub fn rebind(&self, deployment: &DeploymentLocator) -> Result<BlockHash, StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let conn = self.primary_conn()?;
+        let genesis_block_hash =
+            conn.chain_genesis_block_hash(&site.network)?
+                .ok_or_else(|| {
+                    StoreError::Unknown(anyhow!(
+                        "there is no chain for network {} of deployment {}",
+                        site.network,
+                        deployment
+                    ))
+                })?;
+        conn.set_genesis_block_hash(&site.deployment, &genesis_block_hash)?;
+        Ok(genesis_block_hash)
+    }
+
+    /// Recreate the deployment from the backup in `dir` in `shard`. If
+    /// `node` is given, the deployment is assigned to it and starts
+    /// indexing from where it was when the backup was taken
+    pub fn restore(
+        &self,
+        dir: &Path,
+        shard: Shard,
+        node: Option<NodeId>,
+    ) -> Result<DeploymentLocator, StoreError> {
+        let metadata = dump::Metadata::read(dir)?;
+        let schema = metadata.schema()?;
+        let deployment = metadata.deployment_entity()?;
+
+        let deployment_store = self
+            .stores
+            .get(&shard)
+            .ok_or_else(|| StoreError::UnknownShard(shard.to_string()))?;
+
+        let site = Arc::new(self.primary_conn()?.restore_site(
+            shard,
+            &metadata.deployment_hash()?,
+            metadata.network.clone(),
+        )?);
+        let loc = DeploymentLocator::from(site.as_ref());
+
+        // The shard either has the complete deployment or nothing at all;
+        // if restoring failed, remove the site again so that the restore
+        // can simply be retried
+        if let Err(e) = deployment_store.restore(&schema, deployment, site.clone(), dir, &metadata)
+        {
+            self.primary_conn()?.drop_site(site.as_ref())?;
+            return Err(e);
+        }
+
+        if let Some(node) = node {
+            let pconn = self.primary_conn()?;
+            pconn.transaction(|| -> Result<_, StoreError> {
+                let changes = pconn.assign_subgraph(site.as_ref(), &node)?;
+                let event = StoreEvent::new(changes);
+                pconn.send_store_event(&self.sender, &event)?;
+                Ok(())
+            })?;
+        }
+        Ok(loc)
+    }
+
+    /// The number of blocks by which the copy `deployment` trails the
+    /// currently active copy of the same deployment, or 0 if it has caught
+    /// up with it
+    pub fn activation_lag(
+        &self,
+        deployment: &DeploymentLocator,
+    ) -> Result<BlockNumber, StoreError> {
+        fn head(store: &DeploymentStore, site: &Site) -> Result<BlockNumber, StoreError> {
+            Ok(store.block_ptr(site)?.map(|ptr| ptr.number).unwrap_or(-1))
+        }
+
+        let copy = self.find_site(deployment.id.into())?;
+        let (active_store, active) = self.store(&deployment.hash)?;
+        if copy.id == active.id {
+            return Ok(0);
+        }
+        let copy_head = head(self.for_site(copy.as_ref())?, copy.as_ref())?;
+        let active_head = head(active_store, active.as_ref())?;
+        Ok((active_head - copy_head).max(0))
+    }
+
     /// Mark `deployment` as the only active deployment amongst all sites
     /// with the same deployment hash. Activating this specific deployment
     /// will make queries use that instead of whatever was active before
@@ -670,10 +866,14 @@ impl SubgraphStoreInner {
         Ok(primary::Connection::new(conn))
     }
 
+    /// Pick the replica that should run a query against `target`. If the
+    /// `cost` of the query is known, it is used to decide between the main
+    /// database and read replicas
     pub(crate) fn replica_for_query(
         &self,
         target: QueryTarget,
         for_subscription: bool,
+        cost: Option<u64>,
     ) -> Result<(Arc<DeploymentStore>, Arc<Site>, ReplicaId), StoreError> {
         let id = match target {
             QueryTarget::Name(name) => self.mirror.current_deployment_for_subgraph(&name)?,
@@ -681,7 +881,10 @@ impl SubgraphStoreInner {
         };
 
         let (store, site) = self.store(&id)?;
-        let replica = store.replica_for_query(for_subscription)?;
+        let replica = match cost {
+            Some(cost) if !for_subscription => store.replica_for_cost(cost)?,
+            _ => store.replica_for_query(for_subscription)?,
+        };
 
         Ok((store.clone(), site.clone(), replica))
     }
@@ -757,6 +960,57 @@ impl SubgraphStoreInner {
         Ok(details)
     }
 
+    /// The number of the chain head block of `network`, or `None` if the
+    /// chain is not known or has no chain head yet
+    pub(crate) fn chain_head_block(
+        &self,
+        network: &str,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        let shard = match self.primary_conn()?.chain_shard(network)? {
+            Some(shard) => shard,
+            None => return Ok(None),
+        };
+        let store = self
+            .stores
+            .get(&shard)
+            .ok_or(StoreError::UnknownShard(shard.to_string()))?;
+        store.chain_head_block(network)
+    }
+
+    /// The numbers of the head blocks of all deployments that index
+    /// `network`
+    pub fn head_block_numbers(&self, network: &str) -> Result<Vec<BlockNumber>, StoreError> {
+        let sites = self.primary_conn()?.find_sites_for_network(network)?;
+        let mut by_shard: HashMap<Shard, Vec<String>> = HashMap::new();
+        for site in sites {
+            by_shard
+                .entry(site.shard.clone())
+                .or_default()
+                .push(site.deployment.to_string());
+        }
+
+        let mut numbers = Vec::new();
+        for (shard, ids) in by_shard.into_iter() {
+            let store = self
+                .stores
+                .get(&shard)
+                .ok_or(StoreError::UnknownShard(shard.to_string()))?;
+            for detail in store.deployment_details(ids)? {
+                if let Some(number) = detail.latest_ethereum_block_number {
+                    let number = number.to_i32().ok_or_else(|| {
+                        constraint_violation!(
+                            "the head block number {} of {} is not a valid block number",
+                            number,
+                            detail.deployment
+                        )
+                    })?;
+                    numbers.push(number);
+                }
+            }
+        }
+        Ok(numbers)
+    }
+
     pub fn list_unused_deployments(
         &self,
         filter: unused::Filter,
@@ -764,10 +1018,28 @@ impl SubgraphStoreInner {
         self.primary_conn()?.list_unused_deployments(filter)
     }
 
+    /// Return the ids of the deployments that are versions of the subgraph
+    /// `name`
+    pub fn deployments_for_subgraph(
+        &self,
+        name: &SubgraphName,
+    ) -> Result<Vec<DeploymentId>, StoreError> {
+        Ok(self
+            .mirror
+            .deployments_for_subgraph(name.as_str())?
+            .into_iter()
+            .map(|site| site.id)
+            .collect())
+    }
+
     /// Remove a deployment, i.e., all its data and metadata. This is only permissible
     /// if the deployment is unused in the sense that it is neither the current nor
-    /// pending version of any subgraph, and is not currently assigned to any node
-    pub fn remove_deployment(&self, id: DeploymentId) -> Result<(), StoreError> {
+    /// pending version of any subgraph, and is not currently assigned to any node.
+    ///
+    /// Return the number of bytes of disk space that removing the
+    /// deployment freed up, or `None` if the deployment is still in use and
+    /// was therefore not removed
+    pub fn remove_deployment(&self, id: DeploymentId) -> Result<Option<u64>, StoreError> {
         let site = self.find_site(id)?;
         let store = self.for_site(site.as_ref())?;
 
@@ -787,15 +1059,15 @@ impl SubgraphStoreInner {
         }
 
         if removable {
-            store.drop_deployment(&site)?;
+            let reclaimed = store.drop_deployment(&site)?;
 
             self.primary_conn()?.drop_site(site.as_ref())?;
+            Ok(Some(reclaimed))
         } else {
             self.primary_conn()?
                 .unused_deployment_is_used(site.as_ref())?;
+            Ok(None)
         }
-
-        Ok(())
     }
 
     pub(crate) fn status(&self, filter: status::Filter) -> Result<Vec<status::Info>, StoreError> {
@@ -837,6 +1109,19 @@ impl SubgraphStoreInner {
             infos.extend(store.deployment_statuses(&sites)?);
         }
         self.mirror.fill_assignments(&mut infos)?;
+        self.mirror.fill_labels(&mut infos)?;
+        {
+            let writables = self.writables.lock().unwrap();
+            for info in &mut infos {
+                let writable = writables.get(&DeploymentId::from(info.id));
+                info.writes = writable
+                    .and_then(|writable| writable.write_backlog())
+                    .map(|backlog| backlog.info());
+                info.memory = writable
+                    .and_then(|writable| writable.memory_usage())
+                    .map(|memory| memory.info());
+            }
+        }
         Ok(infos)
     }
 
@@ -890,6 +1175,34 @@ impl SubgraphStoreInner {
         self.mirror.subgraphs_by_deployment_hash(deployment_hash)
     }
 
+    pub(crate) fn deprecation(
+        &self,
+        deployment: &DeploymentHash,
+    ) -> Result<Option<Deprecation>, StoreError> {
+        if let Some(deprecation) = self.deprecations.get(deployment) {
+            return Ok(deprecation.as_ref().clone());
+        }
+
+        let deprecation = self
+            .mirror
+            .newer_version(deployment.as_str())?
+            .map(|(successor, promoted_at)| {
+                let successor = DeploymentHash::new(successor)
+                    .map_err(|id| constraint_violation!("illegal deployment id: {}", id))?;
+                let deprecated_at =
+                    DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(promoted_at, 0), Utc);
+                Ok(Deprecation {
+                    successor,
+                    deprecated_at,
+                    sunset_at: None,
+                })
+            })
+            .transpose()?;
+        self.deprecations
+            .set(deployment.clone(), Arc::new(deprecation.clone()));
+        Ok(deprecation)
+    }
+
     #[cfg(debug_assertions)]
     pub fn error_count(&self, id: &DeploymentHash) -> Result<usize, StoreError> {
         let (store, _) = self.store(id)?;
@@ -948,6 +1261,80 @@ impl SubgraphStoreInner {
         .await;
     }
 
+    /// Refresh the hot block range indexes of all deployments that are
+    /// assigned to a node. See `DeploymentStore::refresh_hot_indexes`
+    pub async fn refresh_hot_indexes(&self, logger: &Logger, window: BlockNumber) {
+        let sites = self.primary_conn().and_then(|conn| {
+            let mut sites = Vec::new();
+            for site in conn.sites()? {
+                if conn.assigned_node(&site)?.is_some() {
+                    sites.push(Arc::new(site));
+                }
+            }
+            Ok(sites)
+        });
+        let sites = match sites {
+            Ok(sites) => sites,
+            Err(e) => {
+                warn!(logger, "Failed to list deployments for hot block range indexes";
+                      "error" => e.to_string());
+                return;
+            }
+        };
+        for site in sites {
+            let res = match self.for_site(&site) {
+                Ok(store) => {
+                    store
+                        .refresh_hot_indexes(logger, site.cheap_clone(), window)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                warn!(logger, "Failed to refresh hot block range indexes";
+                      "deployment" => site.deployment.as_str(),
+                      "error" => e.to_string());
+            }
+        }
+    }
+
+    /// Reconcile the per-table entity counts of all deployments that are
+    /// assigned to a node. See `DeploymentStore::reconcile_entity_counts`
+    pub async fn reconcile_entity_counts(&self, logger: &Logger) {
+        let sites = self.primary_conn().and_then(|conn| {
+            let mut sites = Vec::new();
+            for site in conn.sites()? {
+                if conn.assigned_node(&site)?.is_some() {
+                    sites.push(Arc::new(site));
+                }
+            }
+            Ok(sites)
+        });
+        let sites = match sites {
+            Ok(sites) => sites,
+            Err(e) => {
+                warn!(logger, "Failed to list deployments for reconciling entity counts";
+                      "error" => e.to_string());
+                return;
+            }
+        };
+        for site in sites {
+            let res = match self.for_site(&site) {
+                Ok(store) => {
+                    store
+                        .reconcile_entity_counts(logger, site.cheap_clone())
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                warn!(logger, "Failed to reconcile entity counts";
+                      "deployment" => site.deployment.as_str(),
+                      "error" => e.to_string());
+            }
+        }
+    }
+
     pub async fn analyze(
         &self,
         id: &DeploymentHash,
@@ -962,13 +1349,40 @@ impl SubgraphStoreInner {
         id: &DeploymentHash,
         entity_type: EntityType,
         field_names: Vec<String>,
-        index_method: String,
+        method: index::Method,
     ) -> Result<(), StoreError> {
         let (store, site) = self.store(&id)?;
         store
-            .create_manual_index(site, entity_type, field_names, index_method)
+            .create_manual_index(site, entity_type, field_names, method)
             .await
     }
+
+    pub async fn index_advice(&self, id: &DeploymentHash) -> Result<Vec<IndexAdvice>, StoreError> {
+        let (store, site) = self.store(&id)?;
+        store.index_advice(site).await
+    }
+
+    /// Prune the history of `deployment` so that it only answers queries
+    /// for blocks from `earliest_block` on. See `DeploymentStore::prune`
+    pub fn prune(
+        &self,
+        deployment: &DeploymentLocator,
+        earliest_block: BlockNumber,
+        reporter: &mut dyn PruneReporter,
+    ) -> Result<(), StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let store = self.for_site(&site)?;
+        store.prune(site, earliest_block, reporter)
+    }
+
+    pub async fn drop_index(
+        &self,
+        id: &DeploymentHash,
+        index_name: String,
+    ) -> Result<(), StoreError> {
+        let (store, site) = self.store(&id)?;
+        store.drop_index(site, index_name).await
+    }
 }
 
 struct EnsLookup {
@@ -1085,7 +1499,7 @@ impl SubgraphStoreTrait for SubgraphStore {
         .await
         .unwrap()?; // Propagate panics, there shouldn't be any.
 
-        let writable = Arc::new(WritableAgent::new(self.as_ref().clone(), logger, site)?);
+        let writable = WritableAgent::new(self.as_ref().clone(), logger, site)?;
         self.writables
             .lock()
             .unwrap()
@@ -1093,13 +1507,22 @@ impl SubgraphStoreTrait for SubgraphStore {
         Ok(writable)
     }
 
+    async fn stop_subgraph(&self, deployment: &DeploymentLocator) -> Result<(), StoreError> {
+        let id = DeploymentId::from(deployment.id);
+        let writable = self.writables.lock().unwrap().remove(&id);
+        match writable {
+            Some(writable) => writable.stop().await,
+            None => Ok(()),
+        }
+    }
+
     fn writable_for_network_indexer(
         &self,
         logger: Logger,
         id: &DeploymentHash,
     ) -> Result<Arc<dyn WritableStoreTrait>, StoreError> {
         let site = self.site(id)?;
-        Ok(Arc::new(WritableAgent::new(self.clone(), logger, site)?))
+        Ok(WritableAgent::new(self.clone(), logger, site)?)
     }
 
     fn is_deployed(&self, id: &DeploymentHash) -> Result<bool, StoreError> {
@@ -1115,6 +1538,11 @@ impl SubgraphStoreTrait for SubgraphStore {
         store.block_ptr(site.as_ref())
     }
 
+    fn earliest_block(&self, id: &DeploymentHash) -> Result<BlockNumber, StoreError> {
+        let (store, site) = self.store(id)?;
+        store.earliest_block(site.as_ref())
+    }
+
     /// Find the deployment locators for the subgraph with the given hash
     fn locators(&self, hash: &str) -> Result<Vec<DeploymentLocator>, StoreError> {
         Ok(self
@@ -1124,4 +1552,24 @@ impl SubgraphStoreTrait for SubgraphStore {
             .map(|site| site.into())
             .collect())
     }
+
+    fn deployment_labels(
+        &self,
+        id: &DeploymentHash,
+    ) -> Result<BTreeMap<String, String>, StoreError> {
+        self.mirror.deployment_labels(id)
+    }
+
+    fn genesis_block_hash(
+        &self,
+        deployment: &DeploymentLocator,
+    ) -> Result<Option<BlockHash>, StoreError> {
+        // Bypass the cache of sites so that we see the effect of
+        // `graphman deployment rebind` without a restart
+        let site = self
+            .mirror
+            .find_site_by_ref(deployment.id.into())?
+            .ok_or_else(|| StoreError::DeploymentNotFound(deployment.to_string()))?;
+        Ok(site.genesis_block_hash)
+    }
 }
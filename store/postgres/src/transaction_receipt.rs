@@ -1,6 +1,6 @@
-use diesel::sql_types::{Binary, Nullable};
+use diesel::sql_types::{Binary, Nullable, Text};
 use diesel_derives::QueryableByName;
-use graph::prelude::transaction_receipt::LightTransactionReceipt;
+use graph::prelude::{serde_json, transaction_receipt::LightTransactionReceipt, web3::types::Log};
 use itertools::Itertools;
 use std::convert::TryFrom;
 
@@ -16,9 +16,14 @@ pub(crate) struct RawTransactionReceipt {
     #[sql_type = "Nullable<Binary>"]
     block_number: Option<Vec<u8>>,
     #[sql_type = "Nullable<Binary>"]
+    cumulative_gas_used: Option<Vec<u8>>,
+    #[sql_type = "Nullable<Binary>"]
     gas_used: Option<Vec<u8>>,
     #[sql_type = "Nullable<Binary>"]
     status: Option<Vec<u8>>,
+    /// The JSON array of the receipt's logs
+    #[sql_type = "Nullable<Text>"]
+    logs: Option<String>,
 }
 
 impl TryFrom<RawTransactionReceipt> for LightTransactionReceipt {
@@ -30,24 +35,35 @@ impl TryFrom<RawTransactionReceipt> for LightTransactionReceipt {
             transaction_index,
             block_hash,
             block_number,
+            cumulative_gas_used,
             gas_used,
             status,
+            logs,
         } = value;
 
         let transaction_hash = drain_vector(transaction_hash)?;
         let transaction_index = drain_vector(transaction_index)?;
         let block_hash = block_hash.map(drain_vector).transpose()?;
         let block_number = block_number.map(drain_vector).transpose()?;
+        let cumulative_gas_used = cumulative_gas_used.map(drain_vector).transpose()?;
         let gas_used = gas_used.map(drain_vector).transpose()?;
         let status = status.map(drain_vector).transpose()?;
+        // Receipts without logs have `null` or no `logs` at all
+        let logs = logs
+            .map(|logs| serde_json::from_str::<Option<Vec<Log>>>(&logs))
+            .transpose()?
+            .flatten()
+            .unwrap_or_default();
 
         Ok(LightTransactionReceipt {
             transaction_hash: transaction_hash.into(),
             transaction_index: transaction_index.into(),
             block_hash: block_hash.map(Into::into),
             block_number: block_number.map(Into::into),
+            cumulative_gas_used: cumulative_gas_used.map(Into::into),
             gas_used: gas_used.map(Into::into),
             status: status.map(Into::into),
+            logs,
         })
     }
 }
@@ -69,3 +85,33 @@ fn test_drain_vector() {
     let result = drain_vector(input).expect("failed to drain vector into array");
     assert_eq!(result, expected_output);
 }
+
+#[test]
+fn test_receipt_logs() {
+    let raw = |logs: Option<&str>| RawTransactionReceipt {
+        transaction_hash: vec![1],
+        transaction_index: vec![2],
+        block_hash: None,
+        block_number: None,
+        cumulative_gas_used: Some(vec![1, 0]),
+        gas_used: Some(vec![17]),
+        status: Some(vec![1]),
+        logs: logs.map(str::to_string),
+    };
+
+    let receipt = LightTransactionReceipt::try_from(raw(None)).unwrap();
+    assert_eq!(Some(256.into()), receipt.cumulative_gas_used);
+    assert!(receipt.logs.is_empty());
+
+    let receipt = LightTransactionReceipt::try_from(raw(Some("null"))).unwrap();
+    assert!(receipt.logs.is_empty());
+
+    let logs = r#"[{
+        "address": "0x0000000000000000000000000000000000000001",
+        "topics": ["0x0000000000000000000000000000000000000000000000000000000000000002"],
+        "data": "0x03"
+    }]"#;
+    let receipt = LightTransactionReceipt::try_from(raw(Some(logs))).unwrap();
+    assert_eq!(1, receipt.logs.len());
+    assert_eq!(vec![3u8], receipt.logs[0].data.0);
+}
@@ -1,22 +1,27 @@
-use std::sync::Mutex;
-use std::time::Duration;
-use std::{collections::BTreeMap, sync::Arc};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 
 use graph::data::subgraph::schema;
 use graph::prelude::{Entity, Schema, SubgraphStore as _};
 use graph::{
+    blockchain::REORG_THRESHOLD,
     cheap_clone::CheapClone,
-    components::store::{self, EntityType, WritableStore as WritableStoreTrait},
-    data::subgraph::schema::SubgraphError,
+    components::store::{
+        self, EntityType, MemoryUsage, WritableStore as WritableStoreTrait, WriteBacklog,
+    },
+    data::subgraph::schema::{SubgraphError, SubgraphWarning},
+    env::env_var,
     prelude::StoreEvent,
     prelude::{
-        lazy_static, BlockPtr, DeploymentHash, EntityKey, EntityModification, Error, Logger,
-        StopwatchMetrics, StoreError,
+        anyhow::anyhow, lazy_static, tokio, BlockNumber, BlockPtr, DeploymentHash, EntityKey,
+        EntityModification, Error, Logger, StopwatchMetrics, StoreError,
     },
     slog::{error, warn},
-    util::backoff::ExponentialBackoff,
+    util::{backoff::ExponentialBackoff, cache_weight::CacheWeight},
 };
-use store::StoredDynamicDataSource;
+use store::{DataSourceContextUpdate, ScheduledBlockHandler, StoredDynamicDataSource};
 
 use crate::deployment_store::DeploymentStore;
 use crate::{primary, primary::Site, relational::Layout, SubgraphStore};
@@ -28,6 +33,43 @@ lazy_static! {
     pub static ref SEND_SUBSCRIPTION_NOTIFICATIONS: bool = {
       std::env::var("GRAPH_DISABLE_SUBSCRIPTION_NOTIFICATIONS").ok().is_none()
     };
+
+    /// The number of entity modifications waiting to be written above
+    /// which the block stream of a deployment stops fetching blocks
+    static ref WRITE_BACKLOG_MAX_OPS: usize = env_var("GRAPH_STORE_WRITE_BACKLOG_MAX_OPS", 100_000);
+
+    /// The size in bytes of the entity modifications waiting to be written
+    /// above which the block stream of a deployment stops fetching blocks
+    static ref WRITE_BACKLOG_MAX_BYTES: usize =
+        env_var("GRAPH_STORE_WRITE_BACKLOG_MAX_BYTES", 256 * 1024 * 1024);
+
+    /// The number of entity modifications from consecutive blocks that are
+    /// collected before they are written in one transaction while a
+    /// deployment is syncing. With `0`, the default, every block is
+    /// written right away. Batches count against the write backlog, and
+    /// a batch that can not fit into it would stop the block stream before
+    /// the batch is ever full
+    static ref WRITE_BATCH_SIZE: usize = {
+        let size = env_var("GRAPH_STORE_WRITE_BATCH_SIZE", 0);
+        if size > 0 && size >= *WRITE_BACKLOG_MAX_OPS {
+            panic!(
+                "GRAPH_STORE_WRITE_BATCH_SIZE must be less than \
+                 GRAPH_STORE_WRITE_BACKLOG_MAX_OPS ({}), but is {}",
+                *WRITE_BACKLOG_MAX_OPS, size
+            );
+        }
+        size
+    };
+
+    /// The time in milliseconds after which the blocks collected for a
+    /// batch are written even if the batch has not reached its size yet
+    static ref WRITE_BATCH_INTERVAL: Duration =
+        Duration::from_millis(env_var("GRAPH_STORE_WRITE_BATCH_INTERVAL", 1000));
+
+    /// Whether batches of blocks that are more than `REORG_THRESHOLD`
+    /// blocks behind the chain head write new entities with `COPY`
+    /// instead of `insert` statements
+    pub(crate) static ref COPY_INSERTS: bool = env_var("GRAPH_STORE_COPY_INSERTS", false);
 }
 
 /// A wrapper around `SubgraphStore` that only exposes functions that are
@@ -46,6 +88,10 @@ impl WritableSubgraphStore {
         self.0.send_store_event(event)
     }
 
+    fn chain_head_block(&self, network: &str) -> Result<Option<BlockNumber>, StoreError> {
+        self.0.chain_head_block(network)
+    }
+
     fn layout(&self, id: &DeploymentHash) -> Result<Arc<Layout>, StoreError> {
         self.0.layout(id)
     }
@@ -209,6 +255,12 @@ impl WritableStore {
         .await
     }
 
+    fn add_warnings(&self, warnings: &[SubgraphWarning]) -> Result<(), StoreError> {
+        self.retry("add_warnings", || {
+            self.writable.add_warnings(self.site.as_ref(), warnings)
+        })
+    }
+
     async fn supports_proof_of_indexing(&self) -> Result<bool, StoreError> {
         self.retry_async("supports_proof_of_indexing", || async {
             self.writable
@@ -229,6 +281,8 @@ impl WritableStore {
         mods: &[EntityModification],
         stopwatch: StopwatchMetrics,
         data_sources: &[StoredDynamicDataSource],
+        scheduled_handlers: &[ScheduledBlockHandler],
+        data_source_contexts: &[DataSourceContextUpdate],
         deterministic_errors: &[SubgraphError],
     ) -> Result<(), StoreError> {
         assert!(
@@ -243,6 +297,8 @@ impl WritableStore {
                 mods,
                 stopwatch.cheap_clone(),
                 data_sources,
+                scheduled_handlers,
+                data_source_contexts,
                 deterministic_errors,
             )?;
 
@@ -252,6 +308,26 @@ impl WritableStore {
         })
     }
 
+    fn transact_block_batch(
+        &self,
+        blocks: &[PendingBlock],
+        stopwatch: StopwatchMetrics,
+        copy_inserts: bool,
+    ) -> Result<(), StoreError> {
+        self.retry("transact_block_batch", move || {
+            let event = self.writable.transact_block_batch(
+                self.site.clone(),
+                blocks,
+                stopwatch.cheap_clone(),
+                copy_inserts,
+            )?;
+
+            let _section = stopwatch.start_section("send_store_event");
+            self.try_send_store_event(event)?;
+            Ok(())
+        })
+    }
+
     fn get_many(
         &self,
         ids_for_type: BTreeMap<&EntityType, Vec<&str>>,
@@ -262,6 +338,13 @@ impl WritableStore {
         })
     }
 
+    fn deployment_exists_and_synced(&self) -> Result<bool, StoreError> {
+        self.retry("deployment_exists_and_synced", || {
+            self.writable
+                .deployment_exists_and_synced(&self.site.deployment)
+        })
+    }
+
     async fn is_deployment_synced(&self) -> Result<bool, StoreError> {
         self.retry_async("is_deployment_synced", || async {
             self.writable
@@ -290,6 +373,26 @@ impl WritableStore {
         .await
     }
 
+    async fn load_scheduled_block_handlers(
+        &self,
+    ) -> Result<Vec<ScheduledBlockHandler>, StoreError> {
+        self.retry_async("load_scheduled_block_handlers", || async {
+            self.writable
+                .load_scheduled_block_handlers(self.site.clone())
+                .await
+        })
+        .await
+    }
+
+    async fn load_data_source_contexts(&self) -> Result<Vec<DataSourceContextUpdate>, StoreError> {
+        self.retry_async("load_data_source_contexts", || async {
+            self.writable
+                .load_data_source_contexts(self.site.clone())
+                .await
+        })
+        .await
+    }
+
     fn deployment_synced(&self) -> Result<(), StoreError> {
         self.retry("deployment_synced", || {
             let event = {
@@ -329,11 +432,114 @@ fn same_subgraph(mods: &[EntityModification], id: &DeploymentHash) -> bool {
     mods.iter().all(|md| &md.entity_key().subgraph_id == id)
 }
 
-#[allow(dead_code)]
+/// An estimate of the memory that `md` takes up
+fn modification_weight(md: &EntityModification) -> usize {
+    use EntityModification::*;
+    match md {
+        Insert { key, data } | Overwrite { key, data } => key.weight() + data.weight(),
+        Remove { key } => key.weight(),
+    }
+}
+
+/// The changes of one block that wait in a `WriteBatch`
+pub(crate) struct PendingBlock {
+    pub(crate) block_ptr: BlockPtr,
+    pub(crate) firehose_cursor: Option<String>,
+    pub(crate) mods: Vec<EntityModification>,
+    pub(crate) data_sources: Vec<StoredDynamicDataSource>,
+    pub(crate) scheduled_handlers: Vec<ScheduledBlockHandler>,
+    pub(crate) data_source_contexts: Vec<DataSourceContextUpdate>,
+    pub(crate) deterministic_errors: Vec<SubgraphError>,
+}
+
+/// The blocks of a deployment whose changes have not been written to the
+/// database yet, oldest first
+#[derive(Default)]
+struct WriteBatch {
+    blocks: Vec<PendingBlock>,
+    /// Where the latest change to each entity in `blocks` is, as the index
+    /// of the block and the index of the change in that block's `mods`
+    latest: HashMap<EntityKey, (usize, usize)>,
+    ops: usize,
+    bytes: usize,
+    /// When the oldest block in the batch was added
+    started: Option<Instant>,
+    /// The stopwatch of the newest block in the batch
+    stopwatch: Option<StopwatchMetrics>,
+}
+
+impl WriteBatch {
+    fn push(&mut self, block: PendingBlock, ops: usize, bytes: usize, stopwatch: StopwatchMetrics) {
+        let index = self.blocks.len();
+        for (pos, md) in block.mods.iter().enumerate() {
+            self.latest.insert(md.entity_key().clone(), (index, pos));
+        }
+        self.blocks.push(block);
+        self.ops += ops;
+        self.bytes += bytes;
+        self.started.get_or_insert_with(Instant::now);
+        self.stopwatch = Some(stopwatch);
+    }
+
+    fn is_full(&self) -> bool {
+        self.ops >= *WRITE_BATCH_SIZE || self.is_due()
+    }
+
+    /// Whether the oldest block in the batch has waited long enough that
+    /// the batch should be written regardless of its size
+    fn is_due(&self) -> bool {
+        self.started
+            .map_or(false, |started| started.elapsed() >= *WRITE_BATCH_INTERVAL)
+    }
+
+    /// The latest change to the entity with `key` in this batch. Returns
+    /// `None` if the batch does not change the entity, and `Some(None)` if
+    /// the entity was removed
+    fn entity(&self, key: &EntityKey) -> Option<Option<Entity>> {
+        use EntityModification::*;
+
+        let (block, pos) = self.latest.get(key)?;
+        match &self.blocks[*block].mods[*pos] {
+            Insert { data, .. } | Overwrite { data, .. } => Some(Some(data.clone())),
+            Remove { .. } => Some(None),
+        }
+    }
+}
+
+/// The `WritableStore` that subgraphs use. While a deployment is syncing,
+/// the changes of consecutive blocks are collected in a `WriteBatch` and
+/// written together once the batch is full, so that database round trips
+/// and commits do not dominate the time it takes to sync. Reads see the
+/// changes in the batch, and operations that depend on the state in the
+/// database write the batch first. A batch is also written when it has
+/// waited for `WRITE_BATCH_INTERVAL`, when the write backlog is full, and
+/// when the subgraph is stopped.
+///
+/// Locks are taken in the order `flushing`, `batch`, `writing`, and no
+/// database work happens while `batch` or `writing` are held
 pub struct WritableAgent {
     store: Arc<WritableStore>,
     block_ptr: Mutex<Option<BlockPtr>>,
     block_cursor: Mutex<Option<String>>,
+    backlog: Arc<WriteBacklog>,
+    memory_usage: Arc<MemoryUsage>,
+    batch: Mutex<WriteBatch>,
+    /// The batch that is being written. Reads look at it after `batch`
+    /// until it has been committed
+    writing: Mutex<Option<Arc<WriteBatch>>>,
+    /// Held while a batch is written so that batches are written one at
+    /// a time and in the order in which they were collected
+    flushing: Mutex<()>,
+    /// Ourselves, to hand to blocking tasks that write the batch
+    this: Weak<Self>,
+    synced: AtomicBool,
+    /// Set once the subgraph was stopped; the agent does not accept any
+    /// more blocks after that
+    stopped: AtomicBool,
+    /// Set when writing a batch in the background failed. The block
+    /// pointer has gone back to the one in the database, and the next
+    /// block that is handed to us fails so that processing resumes there
+    flush_failed: AtomicBool,
 }
 
 impl WritableAgent {
@@ -341,16 +547,181 @@ impl WritableAgent {
         subgraph_store: SubgraphStore,
         logger: Logger,
         site: Arc<Site>,
-    ) -> Result<Self, StoreError> {
+    ) -> Result<Arc<Self>, StoreError> {
         let store = Arc::new(WritableStore::new(subgraph_store, logger, site)?);
         let block_ptr = Mutex::new(store.block_ptr()?);
         let block_cursor = Mutex::new(store.block_cursor()?);
-        Ok(Self {
+        let backlog = Arc::new(WriteBacklog::new(
+            *WRITE_BACKLOG_MAX_OPS,
+            *WRITE_BACKLOG_MAX_BYTES,
+        ));
+        let synced = AtomicBool::new(store.deployment_exists_and_synced()?);
+        let agent = Arc::new_cyclic(|this| Self {
             store,
             block_ptr,
             block_cursor,
+            backlog,
+            memory_usage: Arc::new(MemoryUsage::default()),
+            batch: Mutex::new(WriteBatch::default()),
+            writing: Mutex::new(None),
+            flushing: Mutex::new(()),
+            this: this.clone(),
+            synced,
+            stopped: AtomicBool::new(false),
+            flush_failed: AtomicBool::new(false),
+        });
+        if *WRITE_BATCH_SIZE > 0 {
+            Self::spawn_flusher(Arc::downgrade(&agent));
+        }
+        Ok(agent)
+    }
+
+    /// Write batches that have waited for `WRITE_BATCH_INTERVAL` even when
+    /// no new blocks arrive, for example, because the subgraph is waiting
+    /// for the chain head. The task ends once the agent has been dropped
+    fn spawn_flusher(agent: Weak<Self>) {
+        graph::spawn(async move {
+            let mut interval = tokio::time::interval(*WRITE_BATCH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let agent = match agent.upgrade() {
+                    Some(agent) => agent,
+                    None => return,
+                };
+                if !agent.batch.lock().unwrap().is_due() {
+                    continue;
+                }
+                let logger = agent.store.logger.clone();
+                let res = graph::spawn_blocking_allow_panic(move || {
+                    let res = agent.flush();
+                    if res.is_err() {
+                        agent.flush_failed.store(true, Ordering::SeqCst);
+                    }
+                    res
+                })
+                .await;
+                match res {
+                    Ok(Ok(())) => { /* all good */ }
+                    Ok(Err(e)) => {
+                        error!(logger, "Writing a batch of blocks failed"; "error" => e.to_string())
+                    }
+                    Err(e) => {
+                        error!(logger, "Writing a batch of blocks panicked"; "error" => e.to_string())
+                    }
+                }
+            }
+        });
+    }
+
+    /// Write all pending changes and stop accepting new blocks. Called when
+    /// the subgraph is stopped, after which the agent is dropped and the
+    /// next `WritableStore` for the deployment starts from the database
+    pub(crate) async fn stop(self: Arc<Self>) -> Result<(), StoreError> {
+        {
+            // Blocks are only added to the batch while holding its lock;
+            // no block can be added after this
+            let _batch = self.batch.lock().unwrap();
+            self.stopped.store(true, Ordering::SeqCst);
+        }
+        self.flush_async().await
+    }
+
+    /// Write all blocks in the batch. If that fails, their changes are
+    /// lost, and the block pointer goes back to the one in the database so
+    /// that the deployment processes those blocks again. Blocks can be
+    /// added to the next batch while this one is written
+    fn flush(&self) -> Result<(), StoreError> {
+        let _flushing = self.flushing.lock().unwrap();
+        let batch = {
+            let mut batch = self.batch.lock().unwrap();
+            if batch.blocks.is_empty() {
+                return Ok(());
+            }
+            let batch = Arc::new(std::mem::take(&mut *batch));
+            *self.writing.lock().unwrap() = Some(batch.cheap_clone());
+            batch
+        };
+
+        let queued = batch.started.expect("a batch with blocks has a start time");
+        let stopwatch = batch
+            .stopwatch
+            .clone()
+            .expect("a batch with blocks has a stopwatch");
+        let copy_inserts = *COPY_INSERTS && self.are_final(&batch.blocks);
+        let writing = Instant::now();
+        let res = self
+            .store
+            .transact_block_batch(&batch.blocks, stopwatch, copy_inserts);
+        self.backlog
+            .record_commit(writing - queued, writing.elapsed());
+        self.backlog.remove(batch.ops, batch.bytes);
+
+        match &res {
+            Ok(()) => *self.writing.lock().unwrap() = None,
+            Err(_) => self.discard_pending(),
+        }
+        res
+    }
+
+    /// Like `flush`, but write the batch on a blocking thread so that
+    /// async callers do not block the executor
+    async fn flush_async(&self) -> Result<(), StoreError> {
+        let this = self
+            .this
+            .upgrade()
+            .expect("the agent is alive while it is used");
+        graph::spawn_blocking_allow_panic(move || this.flush())
+            .await
+            .map_err(|e| StoreError::Unknown(anyhow!("writing pending blocks panicked: {}", e)))?
+    }
+
+    /// After writing a batch failed, drop the blocks that were collected
+    /// since then, as they build on the lost ones, and go back to the
+    /// block pointer in the database
+    fn discard_pending(&self) {
+        let block_ptr = self.store.block_ptr();
+        let block_cursor = self.store.block_cursor();
+
+        let mut batch = self.batch.lock().unwrap();
+        let lost = std::mem::take(&mut *batch);
+        self.backlog.remove(lost.ops, lost.bytes);
+        if let Ok(ptr) = block_ptr {
+            *self.block_ptr.lock().unwrap() = ptr;
+        }
+        if let Ok(cursor) = block_cursor {
+            *self.block_cursor.lock().unwrap() = cursor;
+        }
+        *self.writing.lock().unwrap() = None;
+    }
+
+    /// The latest change to the entity with `key` that is not in the
+    /// database yet, either in the batch that is being collected or in the
+    /// one that is being written
+    fn pending_entity(&self, key: &EntityKey) -> Option<Option<Entity>> {
+        let batch = self.batch.lock().unwrap();
+        batch.entity(key).or_else(|| {
+            let writing = self.writing.lock().unwrap();
+            writing.as_ref().and_then(|writing| writing.entity(key))
         })
     }
+
+    /// Whether all `blocks` are far enough behind the chain head that they
+    /// will not be reverted. When we can not tell, they might be
+    fn are_final(&self, blocks: &[PendingBlock]) -> bool {
+        let last = match blocks.last() {
+            Some(block) => block.block_ptr.number,
+            None => return false,
+        };
+        match self.store.store.chain_head_block(&self.store.site.network) {
+            Ok(Some(head)) => last < head - *REORG_THRESHOLD,
+            Ok(None) => false,
+            Err(e) => {
+                warn!(self.store.logger, "Failed to get the chain head block";
+                      "error" => e.to_string());
+                false
+            }
+        }
+    }
 }
 
 #[allow(unused_variables)]
@@ -364,6 +735,14 @@ impl WritableStoreTrait for WritableAgent {
         self.block_cursor.lock().unwrap().clone()
     }
 
+    fn write_backlog(&self) -> Option<Arc<WriteBacklog>> {
+        Some(self.backlog.clone())
+    }
+
+    fn memory_usage(&self) -> Option<Arc<MemoryUsage>> {
+        Some(self.memory_usage.clone())
+    }
+
     fn start_subgraph_deployment(&self, logger: &Logger) -> Result<(), StoreError> {
         // TODO: Spin up a background writer thread and establish a channel
         self.store.start_subgraph_deployment(logger)
@@ -374,9 +753,13 @@ impl WritableStoreTrait for WritableAgent {
         block_ptr_to: BlockPtr,
         firehose_cursor: Option<&str>,
     ) -> Result<(), StoreError> {
+        // Reverts are rare enough that it is simpler to write pending
+        // blocks and revert them in the database than to revert in memory
+        self.flush()?;
         *self.block_ptr.lock().unwrap() = Some(block_ptr_to.clone());
-        // TODO: If we haven't written the block yet, revert in memory. If
-        // we have, revert in the database
+        if let Some(cursor) = firehose_cursor {
+            *self.block_cursor.lock().unwrap() = Some(cursor.to_owned());
+        }
         self.store
             .revert_block_operations(block_ptr_to, firehose_cursor)
     }
@@ -386,24 +769,34 @@ impl WritableStoreTrait for WritableAgent {
         current_ptr: &BlockPtr,
         parent_ptr: &BlockPtr,
     ) -> Result<(), StoreError> {
+        self.flush()?;
         self.store
             .unfail_deterministic_error(current_ptr, parent_ptr)
     }
 
     fn unfail_non_deterministic_error(&self, current_ptr: &BlockPtr) -> Result<(), StoreError> {
+        self.flush()?;
         self.store.unfail_non_deterministic_error(current_ptr)
     }
 
     async fn fail_subgraph(&self, error: SubgraphError) -> Result<(), StoreError> {
+        self.flush_async().await?;
         self.store.fail_subgraph(error).await
     }
 
+    fn add_warnings(&self, warnings: Vec<SubgraphWarning>) -> Result<(), StoreError> {
+        self.store.add_warnings(&warnings)
+    }
+
     async fn supports_proof_of_indexing(&self) -> Result<bool, StoreError> {
         self.store.supports_proof_of_indexing().await
     }
 
     fn get(&self, key: &EntityKey) -> Result<Option<Entity>, StoreError> {
-        self.store.get(key)
+        match self.pending_entity(key) {
+            Some(entity) => Ok(entity),
+            None => self.store.get(key),
+        }
     }
 
     fn transact_block_operations(
@@ -413,20 +806,75 @@ impl WritableStoreTrait for WritableAgent {
         mods: Vec<EntityModification>,
         stopwatch: StopwatchMetrics,
         data_sources: Vec<StoredDynamicDataSource>,
+        scheduled_handlers: Vec<ScheduledBlockHandler>,
+        data_source_contexts: Vec<DataSourceContextUpdate>,
         deterministic_errors: Vec<SubgraphError>,
     ) -> Result<(), StoreError> {
-        self.store.transact_block_operations(
-            &block_ptr_to,
-            firehose_cursor.as_deref(),
-            &mods,
-            stopwatch,
-            &data_sources,
-            &deterministic_errors,
-        )?;
-
-        *self.block_ptr.lock().unwrap() = Some(block_ptr_to);
-        *self.block_cursor.lock().unwrap() = firehose_cursor;
+        if self.stopped.load(Ordering::SeqCst) {
+            return Err(StoreError::Canceled);
+        }
+        if self.flush_failed.swap(false, Ordering::SeqCst) {
+            return Err(StoreError::Unknown(anyhow!(
+                "writing a batch of blocks failed, processing resumes at block {:?}",
+                self.block_ptr()
+            )));
+        }
+
+        // Once the deployment is synced, every block is written right away.
+        // Nothing waits to be written then, and the backlog stays empty
+        if self.synced.load(Ordering::SeqCst) || *WRITE_BATCH_SIZE == 0 {
+            let started = Instant::now();
+            let res = self.store.transact_block_operations(
+                &block_ptr_to,
+                firehose_cursor.as_deref(),
+                &mods,
+                stopwatch,
+                &data_sources,
+                &scheduled_handlers,
+                &data_source_contexts,
+                &deterministic_errors,
+            );
+            self.backlog
+                .record_commit(Duration::from_secs(0), started.elapsed());
+            res?;
+
+            *self.block_ptr.lock().unwrap() = Some(block_ptr_to);
+            *self.block_cursor.lock().unwrap() = firehose_cursor;
+            return Ok(());
+        }
 
+        let ops = mods.len();
+        let bytes = mods.iter().map(modification_weight).sum();
+
+        let block = PendingBlock {
+            block_ptr: block_ptr_to.clone(),
+            firehose_cursor: firehose_cursor.clone(),
+            mods,
+            data_sources,
+            scheduled_handlers,
+            data_source_contexts,
+            deterministic_errors,
+        };
+        let full = {
+            // Update the block pointer while holding the lock so that a
+            // failed flush in the background can not be overwritten with
+            // the pointer of a block that was lost with that batch
+            let mut batch = self.batch.lock().unwrap();
+            if self.stopped.load(Ordering::SeqCst) {
+                return Err(StoreError::Canceled);
+            }
+            batch.push(block, ops, bytes, stopwatch);
+            self.backlog.add(ops, bytes);
+            *self.block_ptr.lock().unwrap() = Some(block_ptr_to);
+            *self.block_cursor.lock().unwrap() = firehose_cursor;
+            batch.is_full()
+        };
+
+        // When the backlog is full, the block stream waits for it to
+        // drain, and that only happens when we write the batch
+        if full || self.backlog.is_full() {
+            self.flush()?;
+        }
         Ok(())
     }
 
@@ -434,11 +882,49 @@ impl WritableStoreTrait for WritableAgent {
         &self,
         ids_for_type: BTreeMap<&EntityType, Vec<&str>>,
     ) -> Result<BTreeMap<EntityType, Vec<Entity>>, StoreError> {
-        self.store.get_many(ids_for_type)
+        let mut entities: BTreeMap<EntityType, Vec<Entity>> = BTreeMap::new();
+        let mut missing: BTreeMap<&EntityType, Vec<&str>> = BTreeMap::new();
+        {
+            let batch = self.batch.lock().unwrap();
+            let writing = self.writing.lock().unwrap();
+            if batch.blocks.is_empty() && writing.is_none() {
+                missing = ids_for_type;
+            } else {
+                for (entity_type, ids) in ids_for_type {
+                    for id in ids {
+                        let key = EntityKey {
+                            subgraph_id: self.store.site.deployment.clone(),
+                            entity_type: entity_type.clone(),
+                            entity_id: id.to_string(),
+                        };
+                        let pending = batch
+                            .entity(&key)
+                            .or_else(|| writing.as_ref().and_then(|writing| writing.entity(&key)));
+                        match pending {
+                            Some(Some(entity)) => entities
+                                .entry(entity_type.clone())
+                                .or_default()
+                                .push(entity),
+                            Some(None) => { /* removed in the batch */ }
+                            None => missing.entry(entity_type).or_default().push(id),
+                        }
+                    }
+                }
+            }
+        }
+        if !missing.is_empty() {
+            for (entity_type, stored) in self.store.get_many(missing)? {
+                entities.entry(entity_type).or_default().extend(stored);
+            }
+        }
+        Ok(entities)
     }
 
     fn deployment_synced(&self) -> Result<(), StoreError> {
-        self.store.deployment_synced()
+        self.flush()?;
+        self.store.deployment_synced()?;
+        self.synced.store(true, Ordering::SeqCst);
+        Ok(())
     }
 
     async fn is_deployment_synced(&self) -> Result<bool, StoreError> {
@@ -446,14 +932,27 @@ impl WritableStoreTrait for WritableAgent {
     }
 
     fn unassign_subgraph(&self) -> Result<(), StoreError> {
+        self.flush()?;
         self.store.unassign_subgraph()
     }
 
     async fn load_dynamic_data_sources(&self) -> Result<Vec<StoredDynamicDataSource>, StoreError> {
-        // TODO: Combine in-memory and stored data sources
+        self.flush_async().await?;
         self.store.load_dynamic_data_sources().await
     }
 
+    async fn load_scheduled_block_handlers(
+        &self,
+    ) -> Result<Vec<ScheduledBlockHandler>, StoreError> {
+        self.flush_async().await?;
+        self.store.load_scheduled_block_handlers().await
+    }
+
+    async fn load_data_source_contexts(&self) -> Result<Vec<DataSourceContextUpdate>, StoreError> {
+        self.flush_async().await?;
+        self.store.load_data_source_contexts().await
+    }
+
     fn shard(&self) -> &str {
         self.store.shard()
     }
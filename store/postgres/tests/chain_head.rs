@@ -4,6 +4,7 @@
 use std::future::Future;
 use std::sync::Arc;
 
+use graph::blockchain::Block;
 use graph::prelude::web3::types::H256;
 use graph::prelude::{anyhow::anyhow, anyhow::Error};
 use graph::prelude::{serde_json as json, EthereumBlock};
@@ -242,6 +243,73 @@ fn block_hashes_by_number() {
     })
 }
 
+#[test]
+fn copy_blocks() {
+    let chain = vec![&*GENESIS_BLOCK, &*BLOCK_ONE];
+
+    run_test_async(chain, move |store, _| async move {
+        let present = |block: &FakeBlock| -> bool {
+            !store
+                .blocks(&[block.block_hash()])
+                .expect("can look up blocks")
+                .is_empty()
+        };
+        let blocks = |blocks: Vec<&FakeBlock>| -> Vec<Arc<dyn Block>> {
+            blocks
+                .into_iter()
+                .map(|block| Arc::new(block.clone()) as Arc<dyn Block>)
+                .collect()
+        };
+
+        // All batches go through the same copier
+        let mut copier = store.block_copier();
+        copier
+            .copy_blocks(blocks(vec![&*BLOCK_TWO, &*BLOCK_THREE]))
+            .await
+            .expect("copying blocks 2 and 3 works");
+        assert!(present(&*BLOCK_TWO));
+        assert!(present(&*BLOCK_THREE));
+        assert!(!present(&*BLOCK_FOUR));
+
+        // Blocks that are already there and duplicates within a batch
+        // are updated
+        copier
+            .copy_blocks(blocks(vec![&*BLOCK_THREE, &*BLOCK_FOUR, &*BLOCK_FOUR]))
+            .await
+            .expect("copying blocks 3 and 4 works");
+        assert!(present(&*BLOCK_FOUR));
+        assert_eq!(
+            vec![BLOCK_THREE.block_hash()],
+            store.block_hashes_by_block_number(3).unwrap()
+        );
+        assert_eq!(
+            vec![BLOCK_FOUR.block_hash()],
+            store.block_hashes_by_block_number(4).unwrap()
+        );
+
+        // An empty batch does nothing
+        copier
+            .copy_blocks(Vec::new())
+            .await
+            .expect("copying no blocks works");
+        drop(copier);
+
+        // The copied blocks can be used like any others
+        store
+            .cheap_clone()
+            .attempt_chain_head_update(ANCESTOR_COUNT)
+            .await
+            .expect("attempt_chain_head_update failed");
+        assert_eq!(
+            Some(BLOCK_FOUR.block_hash()),
+            store
+                .chain_head_ptr()
+                .unwrap()
+                .map(|ptr| ptr.hash_as_h256())
+        );
+    })
+}
+
 #[track_caller]
 fn check_ancestor(
     store: &Arc<DieselChainStore>,
@@ -317,6 +385,90 @@ fn ancestor_block_ommers() {
     });
 }
 
+#[test]
+fn final_block_timestamp() {
+    let chain = vec![
+        &*GENESIS_BLOCK,
+        &*BLOCK_ONE,
+        &*BLOCK_ONE_SIBLING,
+        &*BLOCK_TWO,
+        &*BLOCK_THREE,
+        &*BLOCK_FOUR,
+    ];
+
+    run_test(chain, move |store, _| -> Result<(), Error> {
+        store.set_chain_final_block(&BLOCK_THREE.block_ptr())?;
+
+        // `FakeBlock` always has a timestamp of 0
+        assert_eq!(Some(0), store.final_block_timestamp(0)?);
+        assert_eq!(Some(0), store.final_block_timestamp(1)?);
+        assert_eq!(Some(0), store.final_block_timestamp(3)?);
+
+        // Blocks that are not final yet or that do not exist
+        assert_eq!(None, store.final_block_timestamp(4)?);
+        assert_eq!(None, store.final_block_timestamp(17)?);
+        assert_eq!(None, store.final_block_timestamp(-1)?);
+        Ok(())
+    });
+}
+
+#[test]
+fn evict_blocks() {
+    let chain = vec![
+        &*GENESIS_BLOCK,
+        &*BLOCK_ONE,
+        &*BLOCK_ONE_SIBLING,
+        &*BLOCK_TWO,
+        &*BLOCK_THREE,
+        &*BLOCK_FOUR,
+        &*BLOCK_FIVE,
+    ];
+
+    run_test_async(chain, move |store, _| async move {
+        let present = |block: &FakeBlock| -> bool {
+            !store
+                .blocks(&[block.block_hash()])
+                .expect("can look up blocks")
+                .is_empty()
+        };
+
+        // Without a chain head, nothing gets evicted
+        assert_eq!(None, store.evict_blocks(1, &[]).unwrap());
+
+        store
+            .cheap_clone()
+            .attempt_chain_head_update(ANCESTOR_COUNT)
+            .await
+            .expect("attempt_chain_head_update failed");
+
+        // Everything is more recent than that
+        assert_eq!(None, store.evict_blocks(5, &[]).unwrap());
+
+        // Keep the last block and blocks 1 and 2, including the sibling
+        // of block 1
+        assert_eq!(Some((4, 1)), store.evict_blocks(1, &[(1, 2)]).unwrap());
+        for block in vec![
+            &*GENESIS_BLOCK,
+            &*BLOCK_ONE,
+            &*BLOCK_ONE_SIBLING,
+            &*BLOCK_TWO,
+            &*BLOCK_FOUR,
+            &*BLOCK_FIVE,
+        ] {
+            assert!(present(block), "block {} is kept", block.number);
+        }
+        assert!(!present(&*BLOCK_THREE));
+
+        // Ranges that are entirely before the genesis block do not keep
+        // anything, and the genesis block is never evicted
+        assert_eq!(Some((5, 4)), store.evict_blocks(0, &[(-50, 0)]).unwrap());
+        assert!(present(&*GENESIS_BLOCK));
+        assert!(present(&*BLOCK_FIVE));
+        assert!(!present(&*BLOCK_ONE));
+        assert!(!present(&*BLOCK_FOUR));
+    })
+}
+
 #[test]
 fn eth_call_cache() {
     let chain = vec![&*GENESIS_BLOCK, &*BLOCK_ONE, &*BLOCK_TWO];
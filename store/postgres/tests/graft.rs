@@ -367,3 +367,60 @@ fn copy() {
         check_graft(store, deployment).await
     })
 }
+
+fn user_entities(
+    store: &DieselSubgraphStore,
+    deployment: &DeploymentLocator,
+    block: BlockNumber,
+) -> Vec<Entity> {
+    let query = EntityQuery::new(
+        deployment.hash.clone(),
+        block,
+        EntityCollection::All(vec![(EntityType::from(USER), AttributeNames::All)]),
+    )
+    .order(EntityOrder::Ascending("id".to_string(), ValueType::String));
+    store
+        .find(query)
+        .expect("store.find failed to execute query")
+}
+
+#[test]
+fn dump_and_restore() {
+    run_test(|store, src| async move {
+        let shard = store.shard(&src)?;
+        let dir = std::env::temp_dir().join(format!("graft-dump-{}", std::process::id()));
+        let table_file = dir.join("user.jsonl");
+
+        let at_block_1 = user_entities(&store, &src, 1);
+        let latest = user_entities(&store, &src, BLOCK_NUMBER_MAX);
+        store.dump(&src, &dir)?;
+
+        // Get rid of the deployment so that it can be restored into the
+        // same shard
+        store.remove_subgraph(SubgraphName::new("test/graft").unwrap())?;
+        for detail in store.record_unused_deployments()? {
+            store.remove_deployment(detail.id)?;
+        }
+
+        // A restore that fails partway through leaves nothing behind
+        let rows = std::fs::read_to_string(&table_file).unwrap();
+        std::fs::write(&table_file, format!("{}not json\n", rows)).unwrap();
+        store
+            .restore(&dir, shard.clone(), Some(NODE_ID.clone()))
+            .expect_err("restoring a corrupt dump fails");
+        std::fs::write(&table_file, rows).unwrap();
+
+        let dst = store.restore(&dir, shard, Some(NODE_ID.clone()))?;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(at_block_1, user_entities(&store, &dst, 1));
+        assert_eq!(latest, user_entities(&store, &dst, BLOCK_NUMBER_MAX));
+        let block_ptr = store
+            .cheap_clone()
+            .writable(LOGGER.clone(), dst.id)
+            .await?
+            .block_ptr();
+        assert_eq!(Some(BLOCKS[2].clone()), block_ptr);
+        Ok(())
+    })
+}
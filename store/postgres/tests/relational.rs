@@ -3,8 +3,8 @@ use diesel::connection::SimpleConnection as _;
 use diesel::pg::PgConnection;
 use graph::prelude::{
     o, slog, tokio, web3::types::H256, DeploymentHash, Entity, EntityCollection, EntityFilter,
-    EntityKey, EntityOrder, EntityQuery, EntityRange, Logger, Schema, StopwatchMetrics, Value,
-    ValueType, BLOCK_NUMBER_MAX,
+    EntityKey, EntityOrder, EntityQuery, EntityRange, Logger, OrderDirection, Schema,
+    StopwatchMetrics, Value, ValueType, BLOCK_NUMBER_MAX,
 };
 use graph_mock::MockMetricsRegistry;
 use graph_store_postgres::layout_for_tests::set_account_like;
@@ -20,7 +20,7 @@ use std::thread::sleep;
 use std::time::Duration;
 
 use graph::{
-    components::store::{AttributeNames, EntityType},
+    components::store::{AttributeNames, EntityType, PruneReporter},
     data::store::scalar::{BigDecimal, BigInt, Bytes},
 };
 use graph_store_postgres::{
@@ -560,6 +560,44 @@ fn update() {
     });
 }
 
+#[test]
+fn prune() {
+    struct NoReporter;
+    impl PruneReporter for NoReporter {}
+
+    run_test(|conn, layout| {
+        insert_entity(&conn, &layout, "Scalar", vec![SCALAR_ENTITY.clone()]);
+
+        // Update the entity at block 5 so that the original version is only
+        // visible for blocks [0, 5)
+        let mut entity = SCALAR_ENTITY.clone();
+        entity.set("string", "updated");
+        let key = EntityKey::data(
+            THINGS_SUBGRAPH_ID.clone(),
+            "Scalar".to_owned(),
+            entity.id().unwrap().clone(),
+        );
+        let entity_type = EntityType::from("Scalar");
+        let mut entities = vec![(&key, Cow::from(&entity))];
+        layout
+            .update(&conn, &entity_type, &mut entities, 5, &MOCK_STOPWATCH)
+            .expect("Failed to update");
+        assert!(layout.find(conn, &*SCALAR, "one", 3).unwrap().is_some());
+
+        layout
+            .prune(conn, 5, &mut NoReporter)
+            .expect("Failed to prune");
+
+        // The version that ended at block 5 is gone, the current one remains
+        assert!(layout.find(conn, &*SCALAR, "one", 3).unwrap().is_none());
+        let actual = layout
+            .find(conn, &*SCALAR, "one", BLOCK_NUMBER_MAX)
+            .expect("Failed to read Scalar[one]")
+            .unwrap();
+        assert_entity_eq!(scrub(&entity), actual);
+    });
+}
+
 #[test]
 fn update_many() {
     run_test(|conn, layout| {
@@ -714,6 +752,7 @@ fn count_scalar_entities(conn: &PgConnection, layout: &Layout) -> usize {
             },
             BLOCK_NUMBER_MAX,
             None,
+            None,
         )
         .expect("Count query failed")
         .len()
@@ -903,6 +942,7 @@ impl<'a> QueryChecker<'a> {
                 query.range,
                 BLOCK_NUMBER_MAX,
                 None,
+                None,
             )
             .expect("layout.query failed to execute query");
 
@@ -948,6 +988,7 @@ fn user_query() -> EntityQuery {
 trait EasyOrder {
     fn asc(self, attr: &str) -> Self;
     fn desc(self, attr: &str) -> Self;
+    fn order_by(self, keys: &[(&str, OrderDirection)]) -> Self;
     fn unordered(self) -> Self;
 }
 
@@ -962,6 +1003,15 @@ impl EasyOrder for EntityQuery {
         self.order(EntityOrder::Descending(attr.to_owned(), ValueType::String))
     }
 
+    fn order_by(self, keys: &[(&str, OrderDirection)]) -> Self {
+        // The ValueType doesn't matter since relational layouts ignore it
+        let keys = keys
+            .iter()
+            .map(|(attr, direction)| (attr.to_string(), ValueType::String, *direction))
+            .collect();
+        self.order(EntityOrder::Multiple(keys))
+    }
+
     fn unordered(self) -> Self {
         self.order(EntityOrder::Unordered)
     }
@@ -1056,6 +1106,47 @@ fn check_find() {
                 )),
             );
 
+        // list contains any
+        let checker = checker
+            .check(
+                vec!["2", "3"],
+                user_query().filter(EntityFilter::ContainsAny(
+                    "drinks".into(),
+                    vec!["beer", "tea"].into(),
+                )),
+            )
+            .check(
+                vec!["2"],
+                user_query().filter(EntityFilter::ContainsAny(
+                    "drinks".into(),
+                    vec!["wine", "water"].into(),
+                )),
+            )
+            .check(
+                vec![],
+                user_query().filter(EntityFilter::ContainsAny(
+                    "drinks".into(),
+                    vec!["water"].into(),
+                )),
+            );
+
+        // list contains all
+        let checker = checker
+            .check(
+                vec!["3"],
+                user_query().filter(EntityFilter::ContainsAll(
+                    "drinks".into(),
+                    vec!["tea", "coffee"].into(),
+                )),
+            )
+            .check(
+                vec![],
+                user_query().filter(EntityFilter::ContainsAll(
+                    "drinks".into(),
+                    vec!["beer", "tea"].into(),
+                )),
+            );
+
         // string attributes
         let checker = checker
             .check(
@@ -1404,6 +1495,27 @@ fn check_find() {
             .check(vec!["1", "2", "3"], user_query().desc("age"))
             .check(vec!["2", "1", "3"], user_query().asc("name"))
             .check(vec!["3", "1", "2"], user_query().desc("name"))
+            .check(
+                vec!["1", "3", "2"],
+                user_query().order_by(&[
+                    ("coffee", OrderDirection::Ascending),
+                    ("age", OrderDirection::Descending),
+                ]),
+            )
+            .check(
+                vec!["2", "3", "1"],
+                user_query().order_by(&[
+                    ("coffee", OrderDirection::Descending),
+                    ("age", OrderDirection::Ascending),
+                ]),
+            )
+            .check(
+                vec!["2", "3", "1"],
+                user_query().order_by(&[
+                    ("coffee", OrderDirection::Descending),
+                    ("id", OrderDirection::Descending),
+                ]),
+            )
             .check(
                 vec!["1", "2"],
                 user_query()
@@ -1511,6 +1623,7 @@ fn text_find(expected_entity_ids: Vec<&str>, filter: EntityFilter) {
                 query.range,
                 BLOCK_NUMBER_MAX,
                 None,
+                None,
             )
             .expect("layout.query failed to execute query");
 
@@ -1152,6 +1152,8 @@ fn mock_data_source() -> graph_chain_ethereum::DataSource {
             event_handlers: vec![],
             call_handlers: vec![],
             block_handlers: vec![],
+            creation_handlers: vec![],
+            handler_timeout: None,
             link: Link {
                 link: "link".to_owned(),
             },
@@ -1551,6 +1553,8 @@ fn handle_large_string_with_index() {
                 stopwatch_metrics,
                 Vec::new(),
                 Vec::new(),
+                Vec::new(),
+                Vec::new(),
             )
             .expect("Failed to insert large text");
 
@@ -1,10 +1,11 @@
 use graph::{
     components::{
         server::index_node::VersionInfo,
-        store::{DeploymentLocator, StatusStore},
+        store::{DataSourceContextUpdate, DeploymentLocator, ScheduledBlockHandler, StatusStore},
     },
     data::subgraph::schema::SubgraphError,
     data::subgraph::schema::SubgraphHealth,
+    data::subgraph::schema::SubgraphWarning,
     prelude::EntityChange,
     prelude::EntityChangeOperation,
     prelude::QueryStoreManager,
@@ -14,7 +15,7 @@ use graph::{
     prelude::SubgraphName,
     prelude::SubgraphVersionSwitchingMode,
     prelude::{futures03, StoreEvent},
-    prelude::{CheapClone, DeploymentHash, NodeId, SubgraphStore as _},
+    prelude::{BlockNumber, CheapClone, DeploymentHash, Entity, NodeId, SubgraphStore as _},
     semver::Version,
 };
 use graph_store_postgres::layout_for_tests::Connection as Primary;
@@ -415,6 +416,53 @@ fn status() {
     })
 }
 
+#[test]
+fn labels() {
+    const NAME: &str = "labelsSubgraph";
+    const OTHER: &str = "otherLabelsSubgraph";
+
+    run_test_sequentially(|store| async move {
+        use graph::data::subgraph::status;
+
+        let id = DeploymentHash::new(NAME).unwrap();
+        let other = DeploymentHash::new(OTHER).unwrap();
+        remove_subgraphs();
+        create_test_subgraph(&id, SUBGRAPH_GQL);
+        create_test_subgraph(&other, SUBGRAPH_GQL);
+
+        let primary = primary_connection();
+        primary.set_deployment_label(&id, "team", "core").unwrap();
+        primary.set_deployment_label(&id, "tier", "silver").unwrap();
+        // Setting a label again replaces its value
+        primary.set_deployment_label(&id, "tier", "gold").unwrap();
+
+        let labels = primary.deployment_labels(&id).unwrap();
+        assert_eq!(2, labels.len());
+        assert_eq!(Some("core"), labels.get("team").map(String::as_str));
+        assert_eq!(Some("gold"), labels.get("tier").map(String::as_str));
+
+        let infos = store
+            .status(status::Filter::Deployments(vec![
+                id.to_string(),
+                other.to_string(),
+            ]))
+            .unwrap();
+        assert_eq!(2, infos.len());
+        let info = infos.iter().find(|info| info.subgraph == NAME).unwrap();
+        assert_eq!(labels, info.labels);
+        let tier = ("tier".to_string(), "gold".to_string());
+        assert!(info.has_labels(&[tier.clone()]));
+        let info = infos.iter().find(|info| info.subgraph == OTHER).unwrap();
+        assert!(info.labels.is_empty());
+        assert!(!info.has_labels(&[tier]));
+
+        assert!(primary.remove_deployment_label(&id, "team").unwrap());
+        assert!(!primary.remove_deployment_label(&id, "team").unwrap());
+        assert!(primary.remove_deployment_label(&id, "tier").unwrap());
+        assert!(primary.deployment_labels(&id).unwrap().is_empty());
+    })
+}
+
 #[test]
 fn version_info() {
     const NAME: &str = "versionInfoSubgraph";
@@ -513,6 +561,140 @@ fn subgraph_error() {
     })
 }
 
+#[test]
+fn subgraph_warnings() {
+    const NAME: &str = "warningsSubgraph";
+
+    test_store::run_test_sequentially(|store| async move {
+        use graph::data::subgraph::status;
+
+        let id = DeploymentHash::new(NAME).unwrap();
+        remove_subgraphs();
+        let deployment = create_test_subgraph(&id, SUBGRAPH_GQL);
+
+        let writable = store
+            .subgraph_store()
+            .writable(LOGGER.clone(), deployment.id)
+            .await
+            .unwrap();
+        let warning = |message: &str| SubgraphWarning {
+            message: message.to_string(),
+            block_ptr: BLOCKS[1].clone(),
+            data_source: "Contract".to_string(),
+        };
+        writable
+            .add_warnings(vec![warning("first"), warning("second")])
+            .unwrap();
+        writable.add_warnings(vec![]).unwrap();
+
+        let infos = store
+            .status(status::Filter::Deployments(vec![id.to_string()]))
+            .unwrap();
+        assert_eq!(1, infos.len());
+        assert_eq!(vec![warning("first"), warning("second")], infos[0].warnings);
+    })
+}
+
+#[test]
+fn scheduled_block_handlers() {
+    const NAME: &str = "scheduledHandlersSubgraph";
+
+    fn handler(block: BlockNumber) -> ScheduledBlockHandler {
+        ScheduledBlockHandler {
+            data_source: "Contract".to_string(),
+            address: vec![1, 2, 3],
+            handler: format!("handleBlock{}", block),
+            block,
+        }
+    }
+
+    test_store::run_test_sequentially(|store| async move {
+        let id = DeploymentHash::new(NAME).unwrap();
+        remove_subgraphs();
+        let deployment = create_test_subgraph(&id, SUBGRAPH_GQL);
+
+        let writable = store
+            .subgraph_store()
+            .writable(LOGGER.clone(), deployment.id)
+            .await
+            .unwrap();
+        let load = || async { writable.load_scheduled_block_handlers().await.unwrap() };
+
+        transact_scheduled_handlers(&store, &deployment, BLOCKS[0].clone(), vec![handler(3)])
+            .await
+            .unwrap();
+        transact_scheduled_handlers(&store, &deployment, BLOCKS[1].clone(), vec![handler(2)])
+            .await
+            .unwrap();
+        assert_eq!(vec![handler(2), handler(3)], load().await);
+
+        // Handlers are no longer loaded once their block has been processed
+        transact_scheduled_handlers(&store, &deployment, BLOCKS[2].clone(), vec![])
+            .await
+            .unwrap();
+        assert_eq!(vec![handler(3)], load().await);
+
+        // Reverting makes handlers due again, and removes the handlers
+        // that were scheduled in reverted blocks
+        revert_block(&store, &deployment, &BLOCKS[0]).await;
+        assert_eq!(vec![handler(3)], load().await);
+        transact_scheduled_handlers(&store, &deployment, BLOCKS[1].clone(), vec![])
+            .await
+            .unwrap();
+        assert_eq!(vec![handler(3)], load().await);
+    })
+}
+
+#[test]
+fn data_source_contexts() {
+    const NAME: &str = "dataSourceContextsSubgraph";
+
+    fn update(address: u8, n: i32) -> DataSourceContextUpdate {
+        let mut context = Entity::new();
+        context.set("n", n);
+        DataSourceContextUpdate {
+            data_source: "Contract".to_string(),
+            address: vec![address],
+            context,
+        }
+    }
+
+    test_store::run_test_sequentially(|store| async move {
+        let id = DeploymentHash::new(NAME).unwrap();
+        remove_subgraphs();
+        let deployment = create_test_subgraph(&id, SUBGRAPH_GQL);
+
+        let writable = store
+            .subgraph_store()
+            .writable(LOGGER.clone(), deployment.id)
+            .await
+            .unwrap();
+        let load = || async {
+            let mut contexts = writable.load_data_source_contexts().await.unwrap();
+            contexts.sort_by(|a, b| a.address.cmp(&b.address));
+            contexts
+        };
+
+        transact_data_source_contexts(&store, &deployment, BLOCKS[0].clone(), vec![update(1, 0)])
+            .await
+            .unwrap();
+        transact_data_source_contexts(
+            &store,
+            &deployment,
+            BLOCKS[1].clone(),
+            vec![update(1, 1), update(2, 1)],
+        )
+        .await
+        .unwrap();
+        // Only the latest context for each data source is loaded
+        assert_eq!(vec![update(1, 1), update(2, 1)], load().await);
+
+        // Reverting restores the contexts from before the reverted block
+        revert_block(&store, &deployment, &BLOCKS[0]).await;
+        assert_eq!(vec![update(1, 0)], load().await);
+    })
+}
+
 #[test]
 fn fatal_vs_non_fatal() {
     fn setup() -> DeploymentLocator {
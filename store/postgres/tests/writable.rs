@@ -0,0 +1,367 @@
+//! Test that the `WritableStore` collects the changes of consecutive blocks
+//! in a batch while a deployment is syncing, and that the changes in the
+//! batch are visible before and written correctly after the batch is
+//! written to the database
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use test_store::block_store::{self, FakeBlock};
+use test_store::*;
+
+use graph::blockchain::REORG_THRESHOLD;
+use graph::components::store::{
+    DeploymentLocator, EntityCollection, EntityKey, EntityOrder, EntityQuery, EntityType,
+    WritableStore,
+};
+use graph::prelude::*;
+use graph_store_postgres::SubgraphStore as DieselSubgraphStore;
+
+const SCHEMA_GQL: &str = "
+    interface Thing {
+        id: ID!,
+    }
+
+    type Counter implements Thing @entity {
+        id: ID!,
+        count: Int!,
+    }
+
+    type Gadget implements Thing @entity {
+        id: ID!,
+        count: Int!,
+    }
+";
+
+const COUNTER: &str = "Counter";
+const GADGET: &str = "Gadget";
+
+lazy_static! {
+    static ref TEST_SUBGRAPH_ID: DeploymentHash =
+        DeploymentHash::new("writableBatchSubgraph").unwrap();
+}
+
+/// Test harness for running database integration tests. Batching is
+/// configured through environment variables that are read once; every
+/// test in this file sets them the same way before touching the store.
+/// The interval is long enough that the timer never writes a batch while
+/// a test runs. Batches only write new entities with `COPY` in tests that
+/// move the chain head far enough ahead
+fn run_test<R, F>(test: F)
+where
+    F: FnOnce(Arc<DieselSubgraphStore>, Arc<dyn WritableStore>, DeploymentLocator) -> R
+        + Send
+        + 'static,
+    R: std::future::Future<Output = ()> + Send + 'static,
+{
+    std::env::set_var("GRAPH_STORE_WRITE_BATCH_SIZE", "1000");
+    std::env::set_var("GRAPH_STORE_WRITE_BATCH_INTERVAL", "600000");
+    std::env::set_var("GRAPH_STORE_COPY_INSERTS", "true");
+
+    run_test_sequentially(|store| async move {
+        let subgraph_store = store.subgraph_store();
+        remove_subgraph(&TEST_SUBGRAPH_ID);
+
+        let deployment = create_test_subgraph(&TEST_SUBGRAPH_ID, SCHEMA_GQL);
+        let writable = subgraph_store
+            .cheap_clone()
+            .writable(LOGGER.clone(), deployment.id)
+            .await
+            .expect("we can get a writable store");
+
+        test(subgraph_store, writable, deployment).await
+    })
+}
+
+fn key(id: &str) -> EntityKey {
+    EntityKey::data(TEST_SUBGRAPH_ID.clone(), COUNTER.to_owned(), id.to_owned())
+}
+
+fn set(id: &str, count: i32) -> EntityOperation {
+    EntityOperation::Set {
+        key: key(id),
+        data: entity! { id: id, count: count },
+    }
+}
+
+fn set_gadget(id: &str, count: i32) -> EntityOperation {
+    EntityOperation::Set {
+        key: EntityKey::data(TEST_SUBGRAPH_ID.clone(), GADGET.to_owned(), id.to_owned()),
+        data: entity! { id: id, count: count },
+    }
+}
+
+fn remove(id: &str) -> EntityOperation {
+    EntityOperation::Remove { key: key(id) }
+}
+
+fn count(entity: Option<Entity>) -> Option<i32> {
+    entity.map(|entity| match entity.get("count") {
+        Some(Value::Int(count)) => *count,
+        other => panic!("unexpected count {:?}", other),
+    })
+}
+
+/// The counts of all counters and gadgets as they are in the database
+fn stored_counts(
+    store: &DieselSubgraphStore,
+    deployment: &DeploymentLocator,
+) -> Vec<(String, i32)> {
+    stored_counts_at(store, deployment, BLOCK_NUMBER_MAX)
+}
+
+/// The counts of all counters and gadgets as they were in the database at
+/// `block`
+fn stored_counts_at(
+    store: &DieselSubgraphStore,
+    deployment: &DeploymentLocator,
+    block: BlockNumber,
+) -> Vec<(String, i32)> {
+    let query = EntityQuery::new(
+        deployment.hash.clone(),
+        block,
+        EntityCollection::All(vec![
+            (EntityType::from(COUNTER), AttributeNames::All),
+            (EntityType::from(GADGET), AttributeNames::All),
+        ]),
+    )
+    .order(EntityOrder::Ascending("id".to_string(), ValueType::String));
+    store
+        .find(query)
+        .expect("store.find failed to execute query")
+        .into_iter()
+        .map(|entity| {
+            let id = entity.id().unwrap();
+            (id, count(Some(entity)).unwrap())
+        })
+        .collect()
+}
+
+fn stored_block_ptr(store: &DieselSubgraphStore) -> Option<BlockPtr> {
+    store.least_block_ptr(&TEST_SUBGRAPH_ID).unwrap()
+}
+
+/// Move the head of the test chain so far ahead that all of `BLOCKS` are
+/// final and batches write new entities with `COPY`
+async fn advance_chain_head() {
+    let head = FakeBlock::make_no_parent(
+        *REORG_THRESHOLD + 100,
+        "7347afe69254df06729e123610b00b8b11f15cfae3241f9366fb113aec07489c",
+    );
+    STORE
+        .block_store()
+        .chain_store(NETWORK_NAME)
+        .expect("fake chain store")
+        .set_chain_head(Arc::new(head), "cursor".to_string(), "test")
+        .await
+        .unwrap();
+}
+
+/// Undo `advance_chain_head`
+fn reset_chain_head() {
+    block_store::set_chain(vec![], NETWORK_NAME);
+}
+
+#[test]
+fn read_your_writes_through_batch() {
+    run_test(|store, writable, deployment| async move {
+        transact_entity_operations(&store, &deployment, BLOCKS[1].clone(), vec![set("a", 1)])
+            .unwrap();
+        transact_entity_operations(
+            &store,
+            &deployment,
+            BLOCKS[2].clone(),
+            vec![set("a", 2), set("b", 1)],
+        )
+        .unwrap();
+        transact_entity_operations(&store, &deployment, BLOCKS[3].clone(), vec![remove("b")])
+            .unwrap();
+
+        // Nothing has been written yet
+        assert_eq!(
+            Vec::<(String, i32)>::new(),
+            stored_counts(&store, &deployment)
+        );
+        assert_eq!(Some(BLOCKS[0].clone()), stored_block_ptr(&store));
+
+        // Reads see the latest change of each entity in the batch
+        assert_eq!(Some(BLOCKS[3].clone()), writable.block_ptr());
+        assert_eq!(Some(2), count(writable.get(&key("a")).unwrap()));
+        assert_eq!(None, count(writable.get(&key("b")).unwrap()));
+        assert_eq!(None, count(writable.get(&key("c")).unwrap()));
+
+        let counter = EntityType::from(COUNTER);
+        let mut ids_for_type = BTreeMap::new();
+        ids_for_type.insert(&counter, vec!["a", "b", "c"]);
+        let entities = writable.get_many(ids_for_type).unwrap();
+        let counts: Vec<_> = entities
+            .get(&counter)
+            .expect("there are counters")
+            .iter()
+            .map(|entity| count(Some(entity.clone())).unwrap())
+            .collect();
+        assert_eq!(vec![2], counts);
+    })
+}
+
+#[test]
+fn flush_on_stop() {
+    run_test(|store, writable, deployment| async move {
+        transact_entity_operations(&store, &deployment, BLOCKS[1].clone(), vec![set("a", 1)])
+            .unwrap();
+        transact_entity_operations(
+            &store,
+            &deployment,
+            BLOCKS[2].clone(),
+            vec![set("a", 2), set("b", 1)],
+        )
+        .unwrap();
+        assert_eq!(
+            Vec::<(String, i32)>::new(),
+            stored_counts(&store, &deployment)
+        );
+
+        store.stop_subgraph(&deployment).await.unwrap();
+
+        assert_eq!(
+            vec![("a".to_string(), 2), ("b".to_string(), 1)],
+            stored_counts(&store, &deployment)
+        );
+        assert_eq!(Some(BLOCKS[2].clone()), stored_block_ptr(&store));
+
+        // Starting the subgraph again gives us a new store that starts
+        // from what is in the database
+        let restarted = store
+            .cheap_clone()
+            .writable(LOGGER.clone(), deployment.id)
+            .await
+            .unwrap();
+        assert!(!Arc::ptr_eq(&writable, &restarted));
+        assert_eq!(Some(BLOCKS[2].clone()), restarted.block_ptr());
+        assert_eq!(Some(2), count(restarted.get(&key("a")).unwrap()));
+        assert_eq!(Some(1), count(restarted.get(&key("b")).unwrap()));
+    })
+}
+
+#[test]
+fn revert_while_batched() {
+    run_test(|store, writable, deployment| async move {
+        transact_entity_operations(&store, &deployment, BLOCKS[1].clone(), vec![set("a", 1)])
+            .unwrap();
+        transact_entity_operations(
+            &store,
+            &deployment,
+            BLOCKS[2].clone(),
+            vec![set("a", 2), set("b", 1)],
+        )
+        .unwrap();
+
+        writable
+            .revert_block_operations(BLOCKS[1].clone(), None)
+            .unwrap();
+
+        assert_eq!(Some(BLOCKS[1].clone()), writable.block_ptr());
+        assert_eq!(Some(BLOCKS[1].clone()), stored_block_ptr(&store));
+        assert_eq!(Some(1), count(writable.get(&key("a")).unwrap()));
+        assert_eq!(None, count(writable.get(&key("b")).unwrap()));
+        assert_eq!(
+            vec![("a".to_string(), 1)],
+            stored_counts(&store, &deployment)
+        );
+
+        // Processing continues after the block we reverted to
+        transact_entity_operations(&store, &deployment, BLOCKS[2].clone(), vec![set("b", 2)])
+            .unwrap();
+        assert_eq!(Some(2), count(writable.get(&key("b")).unwrap()));
+        store.stop_subgraph(&deployment).await.unwrap();
+        assert_eq!(
+            vec![("a".to_string(), 1), ("b".to_string(), 2)],
+            stored_counts(&store, &deployment)
+        );
+    })
+}
+
+#[test]
+fn copy_inserts() {
+    run_test(|store, _, deployment| async move {
+        advance_chain_head().await;
+
+        transact_entity_operations(
+            &store,
+            &deployment,
+            BLOCKS[1].clone(),
+            vec![set("a", 1), set("b", 1)],
+        )
+        .unwrap();
+        transact_entity_operations(
+            &store,
+            &deployment,
+            BLOCKS[2].clone(),
+            vec![set("a", 2), set("c", 1)],
+        )
+        .unwrap();
+        transact_entity_operations(&store, &deployment, BLOCKS[3].clone(), vec![remove("b")])
+            .unwrap();
+
+        store.stop_subgraph(&deployment).await.unwrap();
+        reset_chain_head();
+
+        // Entities that were copied have the right block range, and later
+        // changes in the batch apply to them
+        assert_eq!(Some(BLOCKS[3].clone()), stored_block_ptr(&store));
+        assert_eq!(
+            vec![("a".to_string(), 1), ("b".to_string(), 1)],
+            stored_counts_at(&store, &deployment, 1)
+        );
+        assert_eq!(
+            vec![
+                ("a".to_string(), 2),
+                ("b".to_string(), 1),
+                ("c".to_string(), 1)
+            ],
+            stored_counts_at(&store, &deployment, 2)
+        );
+        assert_eq!(
+            vec![("a".to_string(), 2), ("c".to_string(), 1)],
+            stored_counts(&store, &deployment)
+        );
+    })
+}
+
+#[test]
+fn copy_inserts_removed_when_batch_fails() {
+    run_test(|store, _, deployment| async move {
+        advance_chain_head().await;
+
+        // Both entities are copied, but writing the batch fails since a
+        // counter and a gadget can not have the same id
+        transact_entity_operations(&store, &deployment, BLOCKS[1].clone(), vec![set("a", 1)])
+            .unwrap();
+        transact_entity_operations(
+            &store,
+            &deployment,
+            BLOCKS[2].clone(),
+            vec![set_gadget("a", 1)],
+        )
+        .unwrap();
+        assert!(store.stop_subgraph(&deployment).await.is_err());
+
+        // The copied entities are gone, and the blocks can be processed
+        // again
+        assert_eq!(Some(BLOCKS[0].clone()), stored_block_ptr(&store));
+        assert_eq!(
+            Vec::<(String, i32)>::new(),
+            stored_counts(&store, &deployment)
+        );
+
+        transact_entity_operations(&store, &deployment, BLOCKS[1].clone(), vec![set("a", 2)])
+            .unwrap();
+        store.stop_subgraph(&deployment).await.unwrap();
+        reset_chain_head();
+
+        assert_eq!(Some(BLOCKS[1].clone()), stored_block_ptr(&store));
+        assert_eq!(
+            vec![("a".to_string(), 2)],
+            stored_counts(&store, &deployment)
+        );
+    })
+}
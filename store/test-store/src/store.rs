@@ -7,8 +7,9 @@ use graph::log;
 use graph::prelude::{QueryStoreManager as _, SubgraphStore as _, *};
 use graph::semver::Version;
 use graph::{
-    blockchain::ChainIdentifier, components::store::DeploymentLocator,
-    components::store::EntityType, components::store::StatusStore,
+    blockchain::ChainIdentifier, components::store::DataSourceContextUpdate,
+    components::store::DeploymentLocator, components::store::EntityType,
+    components::store::ScheduledBlockHandler, components::store::StatusStore,
     components::store::StoredDynamicDataSource, data::subgraph::status, prelude::NodeId,
 };
 use graph_graphql::prelude::{
@@ -28,7 +29,10 @@ use hex_literal::hex;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 use std::time::Instant;
-use std::{collections::BTreeSet, env};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+};
 use std::{marker::PhantomData, sync::Mutex};
 use tokio::runtime::{Builder, Runtime};
 use web3::types::H256;
@@ -143,7 +147,9 @@ pub fn remove_subgraphs() {
 }
 
 pub fn place(name: &str) -> Result<Option<(Vec<Shard>, Vec<NodeId>)>, String> {
-    CONFIG.deployment.place(name, NETWORK_NAME)
+    CONFIG
+        .deployment
+        .place(name, NETWORK_NAME, &BTreeMap::new())
 }
 
 pub fn create_subgraph(
@@ -227,10 +233,70 @@ pub async fn transact_errors(
             Vec::new(),
             stopwatch_metrics,
             Vec::new(),
+            Vec::new(),
+            Vec::new(),
             errs,
         )
 }
 
+/// Transact a block that only schedules `handlers`
+pub async fn transact_scheduled_handlers(
+    store: &Arc<Store>,
+    deployment: &DeploymentLocator,
+    block_ptr_to: BlockPtr,
+    handlers: Vec<ScheduledBlockHandler>,
+) -> Result<(), StoreError> {
+    let metrics_registry = Arc::new(MockMetricsRegistry::new());
+    let stopwatch_metrics = StopwatchMetrics::new(
+        Logger::root(slog::Discard, o!()),
+        deployment.hash.clone(),
+        metrics_registry.clone(),
+    );
+    store
+        .subgraph_store()
+        .writable(LOGGER.clone(), deployment.id.clone())
+        .await?
+        .transact_block_operations(
+            block_ptr_to,
+            None,
+            Vec::new(),
+            stopwatch_metrics,
+            Vec::new(),
+            handlers,
+            Vec::new(),
+            Vec::new(),
+        )
+}
+
+/// Transact a block that only stores data source `contexts`
+pub async fn transact_data_source_contexts(
+    store: &Arc<Store>,
+    deployment: &DeploymentLocator,
+    block_ptr_to: BlockPtr,
+    contexts: Vec<DataSourceContextUpdate>,
+) -> Result<(), StoreError> {
+    let metrics_registry = Arc::new(MockMetricsRegistry::new());
+    let stopwatch_metrics = StopwatchMetrics::new(
+        Logger::root(slog::Discard, o!()),
+        deployment.hash.clone(),
+        metrics_registry.clone(),
+    );
+    store
+        .subgraph_store()
+        .writable(LOGGER.clone(), deployment.id.clone())
+        .await?
+        .transact_block_operations(
+            block_ptr_to,
+            None,
+            Vec::new(),
+            stopwatch_metrics,
+            Vec::new(),
+            Vec::new(),
+            contexts,
+            Vec::new(),
+        )
+}
+
 /// Convenience to transact EntityOperation instead of EntityModification
 pub fn transact_entity_operations(
     store: &Arc<DieselSubgraphStore>,
@@ -269,6 +335,8 @@ pub fn transact_entities_and_dynamic_data_sources(
         stopwatch_metrics,
         data_sources,
         Vec::new(),
+        Vec::new(),
+        Vec::new(),
     )
 }
 
@@ -409,12 +477,14 @@ async fn execute_subgraph_query_internal(
         .query_store(deployment.into(), false)
         .await
         .unwrap();
+    let state = return_err!(store.deployment_state().await);
     for (bc, (selection_set, error_policy)) in return_err!(query.block_constraint()) {
         let logger = logger.clone();
         let resolver = return_err!(
             StoreResolver::at_block(
                 &logger,
                 store.clone(),
+                &state,
                 SUBSCRIPTION_MANAGER.clone(),
                 bc,
                 error_policy,